@@ -0,0 +1,137 @@
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+
+use cool_rs::generator::{generate_program, GenConfig};
+use cool_rs::parsing::scanner::Scanner;
+use cool_rs::semantic::collector::ErrorCollector;
+use cool_rs::semantic::context::AnalysisContext;
+use cool_rs::{check_program, parse_program, prelude};
+
+/// System allocator that counts every allocation, so the suite can report
+/// how many heap allocations each phase performs.
+struct CountingAlloc;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+/// Number of allocations made while running `f`.
+fn count_allocations<T>(f: impl FnOnce() -> T) -> usize {
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    let result = f();
+    let after = ALLOCATIONS.load(Ordering::Relaxed);
+    drop(result);
+    after - before
+}
+
+/// Program shapes: a few flat sizes, one deep hierarchy, one comment-heavy
+/// program and one with deeply nested random expressions.
+fn shapes() -> Vec<(&'static str, GenConfig)> {
+    let flat = |classes| GenConfig { classes, ..GenConfig::default() };
+    vec![
+        ("flat-10", flat(10)),
+        ("flat-100", flat(100)),
+        ("flat-1000", flat(1000)),
+        ("methods-2000", GenConfig { methods: 20, ..flat(2000) }),
+        ("deep-200", GenConfig { classes: 200, depth: 200, ..GenConfig::default() }),
+        ("commented-100", GenConfig { comments: 8, ..flat(100) }),
+        ("nested-100", GenConfig { expr_depth: 6, seed: 1, ..flat(100) }),
+    ]
+}
+
+fn bench_lexing(c: &mut Criterion) {
+    let mut group = c.benchmark_group("lex");
+    for (name, config) in shapes() {
+        let source = generate_program(&config);
+        group.throughput(Throughput::Bytes(source.len() as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(name), &source, |b, source| {
+            b.iter(|| Scanner::new(source).scan_tokens().unwrap())
+        });
+    }
+    group.finish();
+}
+
+fn bench_parsing(c: &mut Criterion) {
+    let mut group = c.benchmark_group("parse");
+    for (name, config) in shapes() {
+        let source = generate_program(&config);
+        group.throughput(Throughput::Bytes(source.len() as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(name), &source, |b, source| {
+            b.iter(|| parse_program(source).unwrap())
+        });
+    }
+    group.finish();
+}
+
+fn bench_tables(c: &mut Criterion) {
+    let mut group = c.benchmark_group("tables");
+    for (name, config) in shapes() {
+        let source = generate_program(&config);
+        let ast = parse_program(&source).unwrap();
+        group.bench_with_input(BenchmarkId::from_parameter(name), &ast, |b, ast| {
+            b.iter(|| AnalysisContext::new(prelude(), ast))
+        });
+    }
+    group.finish();
+}
+
+/// The checks alone, on a class table built once.
+fn bench_semantic(c: &mut Criterion) {
+    let mut group = c.benchmark_group("semantic");
+    for (name, config) in shapes() {
+        let source = generate_program(&config);
+        let ast = parse_program(&source).unwrap();
+        let ctx = AnalysisContext::new(prelude(), &ast);
+        group.bench_with_input(BenchmarkId::from_parameter(name), &ctx, |b, ctx| {
+            b.iter(|| {
+                let mut ec = ErrorCollector::default();
+                check_program(ctx, &mut ec);
+                assert!(!ec.has_errors());
+            })
+        });
+    }
+    group.finish();
+}
+
+/// Print heap allocations per phase for every shape. Criterion measures time
+/// only, so these are reported alongside rather than as a benchmark group.
+fn report_allocations(_c: &mut Criterion) {
+    println!("{:<14} {:>12} {:>12} {:>12} {:>12}", "alloc", "lex", "parse", "tables", "check");
+    for (name, config) in shapes() {
+        let source = generate_program(&config);
+        let lex = count_allocations(|| Scanner::new(&source).scan_tokens().unwrap());
+        let parse = count_allocations(|| parse_program(&source).unwrap());
+
+        let ast = parse_program(&source).unwrap();
+        let tables = count_allocations(|| AnalysisContext::new(prelude(), &ast));
+        let ctx = AnalysisContext::new(prelude(), &ast);
+        let check = count_allocations(|| {
+            let mut ec = ErrorCollector::default();
+            check_program(&ctx, &mut ec);
+            assert!(!ec.has_errors());
+        });
+        println!("{:<14} {:>12} {:>12} {:>12} {:>12}", name, lex, parse, tables, check);
+    }
+}
+
+criterion_group!(benches, report_allocations, bench_lexing, bench_parsing, bench_tables, bench_semantic);
+criterion_main!(benches);
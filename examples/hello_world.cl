@@ -0,0 +1,6 @@
+-- The classic first program.
+class Main inherits IO {
+    main() : Object {
+        out_string("Hello, World.\n")
+    };
+};
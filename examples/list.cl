@@ -0,0 +1,57 @@
+-- Singly linked lists of integers, built with cons and walked with
+-- head/tail. Nil is represented by a plain List; Cons overrides the
+-- accessors.
+
+class List {
+    isNil() : Bool { true };
+
+    head() : Int { { abort(); 0; } };
+
+    tail() : List { { abort(); self; } };
+
+    cons(i : Int) : List {
+        (new Cons).init(i, self)
+    };
+};
+
+class Cons inherits List {
+    car : Int;
+    cdr : List;
+
+    isNil() : Bool { false };
+
+    head() : Int { car };
+
+    tail() : List { cdr };
+
+    init(i : Int, rest : List) : List {
+        {
+            car <- i;
+            cdr <- rest;
+            self;
+        }
+    };
+};
+
+class Main inherits IO {
+    mylist : List;
+
+    print_list(l : List) : Object {
+        if l.isNil() then out_string("\n")
+        else {
+            out_int(l.head()).out_string(" ");
+            print_list(l.tail());
+        }
+        fi
+    };
+
+    main() : Object {
+        {
+            mylist <- new List.cons(1).cons(2).cons(3).cons(4).cons(5);
+            while (not mylist.isNil()) loop {
+                print_list(mylist);
+                mylist <- mylist.tail();
+            } pool;
+        }
+    };
+};
@@ -0,0 +1,92 @@
+-- A persistent stack and a small calculator that evaluates programs in
+-- reverse Polish notation, one character per token: digits push
+-- themselves, + - * pop two operands and push the result.
+
+class Stack {
+    isEmpty() : Bool { true };
+
+    top() : Int { { abort(); 0; } };
+
+    pop() : Stack { { abort(); self; } };
+
+    push(i : Int) : Stack {
+        (new StackNode).init(i, self)
+    };
+};
+
+class StackNode inherits Stack {
+    value : Int;
+    rest : Stack;
+
+    init(i : Int, r : Stack) : Stack {
+        {
+            value <- i;
+            rest <- r;
+            self;
+        }
+    };
+
+    isEmpty() : Bool { false };
+
+    top() : Int { value };
+
+    pop() : Stack { rest };
+};
+
+class Calculator inherits IO {
+    stack : Stack <- new Stack;
+
+    digit(c : String) : Int {
+        if c = "0" then 0 else
+        if c = "1" then 1 else
+        if c = "2" then 2 else
+        if c = "3" then 3 else
+        if c = "4" then 4 else
+        if c = "5" then 5 else
+        if c = "6" then 6 else
+        if c = "7" then 7 else
+        if c = "8" then 8 else
+        if c = "9" then 9 else
+        { out_string("bad token ").out_string(c).out_string("\n"); abort(); 0; }
+        fi fi fi fi fi fi fi fi fi fi
+    };
+
+    apply(op : String) : Object {
+        let b : Int <- stack.top(),
+            a : Int <- stack.pop().top()
+        in {
+            stack <- stack.pop().pop();
+            if op = "+" then stack <- stack.push(a + b) else
+            if op = "-" then stack <- stack.push(a - b) else
+                stack <- stack.push(a * b)
+            fi fi;
+        }
+    };
+
+    eval(program : String) : Int {
+        {
+            (let i : Int <- 0 in
+                while i < program.length() loop {
+                    (let c : String <- program.substr(i, 1) in
+                        if c = "+" then apply(c) else
+                        if c = "-" then apply(c) else
+                        if c = "*" then apply(c) else
+                            stack <- stack.push(digit(c))
+                        fi fi fi
+                    );
+                    i <- i + 1;
+                } pool
+            );
+            stack.top();
+        }
+    };
+};
+
+class Main inherits IO {
+    main() : Object {
+        {
+            out_string("34+2* = ").out_int((new Calculator).eval("34+2*")).out_string("\n");
+            out_string("95-3- = ").out_int((new Calculator).eval("95-3-")).out_string("\n");
+        }
+    };
+};
@@ -0,0 +1,82 @@
+//! Arbitrary token sequences straight into the parser, as `--tokens-in`
+//! feeds it, each input byte choosing one token.
+#![no_main]
+
+use cool_rs::ext::{Extension, Extensions};
+use cool_rs::parsing::token::{Position, Token};
+use cool_rs::symbol::Symbol;
+use libfuzzer_sys::fuzz_target;
+
+fn token(byte: u8) -> Token {
+    const FIXED: [Token; 42] = [
+        Token::Class_,
+        Token::Else,
+        Token::Fi,
+        Token::If,
+        Token::In,
+        Token::Inherits,
+        Token::Let,
+        Token::Loop,
+        Token::Pool,
+        Token::Then,
+        Token::While,
+        Token::Case,
+        Token::Esac,
+        Token::Of,
+        Token::New,
+        Token::Isvoid,
+        Token::Not,
+        Token::Try,
+        Token::Catch,
+        Token::Throw,
+        Token::End,
+        Token::Import,
+        Token::Native,
+        Token::BoolConst(true),
+        Token::Darrow,
+        Token::Assign,
+        Token::Le,
+        Token::Lbrace,
+        Token::Rbrace,
+        Token::Lparen,
+        Token::Rparen,
+        Token::Colon,
+        Token::Semicolon,
+        Token::At,
+        Token::Plus,
+        Token::Minus,
+        Token::Divide,
+        Token::Mul,
+        Token::Neg,
+        Token::Equal,
+        Token::Lt,
+        Token::Period,
+    ];
+    match byte as usize {
+        i if i < FIXED.len() => FIXED[i].clone(),
+        42 => Token::Comma,
+        43 => Token::StrConst("s".to_string()),
+        44 => Token::IntConst("1".to_string()),
+        45 => Token::Error("e".to_string()),
+        46..=49 => Token::Typeid(Symbol::intern(["Main", "Int", "SELF_TYPE", "A"][byte as usize - 46])),
+        _ => Token::Objectid(Symbol::intern(["self", "x", "main", "f"][byte as usize % 4])),
+    }
+}
+
+fuzz_target!(|data: &[u8]| {
+    let tokens = data
+        .iter()
+        .enumerate()
+        .map(|(i, &byte)| {
+            let at = |offset| Position { line: 1 + i / 8, offset };
+            (at(i), token(byte), at(i + 1))
+        })
+        .collect();
+    let all: Extensions =
+        [Extension::Strings, Extension::Arrays, Extension::Exceptions, Extension::Imports, Extension::Natives]
+            .into_iter()
+            .collect();
+    // The tokens' offsets are into text of one byte per token
+    let text = " ".repeat(data.len());
+    let _ = cool_rs::parse_tokens(&text, tokens, &all);
+});
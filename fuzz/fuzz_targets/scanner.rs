@@ -0,0 +1,42 @@
+//! Arbitrary bytes through the scanner, the `--lex` dump and the front end,
+//! with every diagnostic rendered against the input.
+#![no_main]
+
+use cool_rs::compiler::Compiler;
+use cool_rs::diagnostic::render::SourceFile;
+use cool_rs::diagnostic::{Diagnostic, Reporter};
+use cool_rs::ext::{Extension, Extensions};
+use cool_rs::parsing::dump::coolc_tokens;
+use cool_rs::parsing::recovery::SyntaxErrors;
+use cool_rs::parsing::scanner::Scanner;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    // Input files are read as UTF-8, and anything else is rejected first
+    let source = String::from_utf8_lossy(data);
+    let all: Extensions =
+        [Extension::Strings, Extension::Arrays, Extension::Exceptions, Extension::Imports, Extension::Natives]
+            .into_iter()
+            .collect();
+    for extensions in [Extensions::default(), all] {
+        let _ = Scanner::with_extensions(&source, extensions.clone()).scan_tokens();
+        let _ = coolc_tokens(&source, &extensions);
+        let compiler = Compiler::new(extensions);
+        let diagnostics: Vec<Diagnostic> = match compiler.parse(&source) {
+            Ok(classes) => {
+                let mut ec = Default::default();
+                compiler.analyze(&classes, &mut ec);
+                ec.diagnostics()
+            }
+            Err(err) => match err.downcast::<SyntaxErrors>() {
+                Ok(errors) => errors.0.iter().map(|err| Diagnostic::syntax(err, 0, &source)).collect(),
+                Err(_) => Vec::new(),
+            },
+        };
+        let files = [SourceFile { name: "fuzz.cl", text: &source }];
+        let mut reporter = Reporter::new(&files);
+        for diagnostic in &diagnostics {
+            reporter.report(diagnostic);
+        }
+    }
+});
@@ -0,0 +1,67 @@
+//! `Int` arithmetic and what happens when it overflows (`--int-overflow`).
+//!
+//! COOL's `Int` is a 32-bit signed integer. By default a sum, difference,
+//! product, quotient or negation that does not fit wraps around, as two's
+//! complement hardware does; with `trap` it stops the program with a
+//! runtime error instead, as a division by zero does. The constant folder,
+//! the interpreter, the VM and every backend follow the mode they are
+//! given, whatever Rust's own overflow checks in the build of the compiler.
+
+use clap::ValueEnum;
+
+use crate::ast::MathOperator;
+
+/// What an `Int` operation whose result does not fit in 32 bits gives.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, ValueEnum)]
+pub enum Overflow {
+    /// The result modulo 2^32, e.g. `2147483647 + 1 = -2147483648`
+    #[default]
+    Wrap,
+    /// The runtime error `Integer overflow`
+    Trap,
+}
+
+/// The message of the runtime error of an overflow that traps.
+pub const MESSAGE: &str = "Integer overflow";
+
+impl Overflow {
+    /// `a op b`, or `None` if it overflows and overflow traps. The divisor
+    /// of a division must not be `0`.
+    pub fn math(self, op: &MathOperator, a: i32, b: i32) -> Option<i32> {
+        let (value, overflowed) = match op {
+            MathOperator::Add => a.overflowing_add(b),
+            MathOperator::Subtract => a.overflowing_sub(b),
+            MathOperator::Mul => a.overflowing_mul(b),
+            MathOperator::Div => a.overflowing_div(b),
+        };
+        self.result(value, overflowed)
+    }
+
+    /// `~a`, or `None` if it overflows and overflow traps.
+    pub fn negate(self, a: i32) -> Option<i32> {
+        let (value, overflowed) = a.overflowing_neg();
+        self.result(value, overflowed)
+    }
+
+    fn result(self, value: i32, overflowed: bool) -> Option<i32> {
+        (!overflowed || self == Overflow::Wrap).then_some(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_modes() {
+        assert_eq!(Overflow::Wrap.math(&MathOperator::Add, i32::MAX, 1), Some(i32::MIN));
+        assert_eq!(Overflow::Trap.math(&MathOperator::Add, i32::MAX, 1), None);
+        assert_eq!(Overflow::Wrap.math(&MathOperator::Div, i32::MIN, -1), Some(i32::MIN));
+        assert_eq!(Overflow::Trap.math(&MathOperator::Div, i32::MIN, -1), None);
+        assert_eq!(Overflow::Trap.math(&MathOperator::Mul, 46340, 46340), Some(2147395600));
+        assert_eq!(Overflow::Trap.math(&MathOperator::Mul, 46341, 46341), None);
+        assert_eq!(Overflow::Trap.math(&MathOperator::Subtract, -1, i32::MAX), Some(i32::MIN));
+        assert_eq!(Overflow::Wrap.negate(i32::MIN), Some(i32::MIN));
+        assert_eq!(Overflow::Trap.negate(i32::MIN), None);
+    }
+}
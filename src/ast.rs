@@ -1,54 +1,95 @@
 use std::boxed::Box;
 
-#[derive(Debug, Clone, PartialEq)]
+use serde::{Deserialize, Serialize};
+
+use crate::parsing::token::{Position, Span};
+use crate::symbol::Symbol;
+
+pub mod printer;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Program {
+    /// Paths named by `import` directives (`--ext imports`), in source order
+    pub imports: Vec<String>,
     pub classes: Vec<Class>,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Class {
-    pub name: String,
-    pub inherits: Option<String>,
+    pub name: Symbol,
+    pub inherits: Option<Symbol>,
     pub feature_list: Vec<Feature>,
+    /// Index of the input file the class was parsed from; 0 for a
+    /// single-file program and for the built-in classes
+    #[serde(default)]
+    pub file: usize,
+    /// `class Name [inherits Parent]`
+    #[serde(default)]
+    pub location: Location,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum Feature {
     Attribute(VarDecl),
-    Method(String, Vec<ArgDecl>, String, TypedExpr),
+    /// Name, formals, return type, body, and where `name(...) : Type` is
+    Method(Symbol, Vec<ArgDecl>, Symbol, TypedExpr, Location),
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct VarDecl {
-    pub oid: String,
-    pub tid: String,
+    pub oid: Symbol,
+    pub tid: Symbol,
     pub expr: Option<TypedExpr>,
+    /// Where `oid : tid` is
+    #[serde(default)]
+    pub location: Location,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct ArgDecl {
-    pub id: String,
-    pub tid: String,
+    pub id: Symbol,
+    pub tid: Symbol,
+    #[serde(default)]
+    pub location: Location,
+}
+
+/// Where a declaration is in the source, as for expressions: the line for
+/// messages and the span for diagnostics. Declarations built by the
+/// compiler have line 0 and an empty span.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct Location {
+    pub line: usize,
+    pub span: Span,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+impl Location {
+    /// The source between `start` and `end`.
+    pub fn at(start: Position, end: Position) -> Self {
+        Location { line: start.line, span: Span { file: 0, start: start.offset, end: end.offset } }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct CaseBranch {
-    pub id: String,
-    pub tid: String,
+    pub id: Symbol,
+    pub tid: Symbol,
     pub expr: TypedExpr,
+    /// Where `id : tid` is
+    #[serde(default)]
+    pub location: Location,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum Expr {
-    Identifier(String),
+    Identifier(Symbol),
     Bool(bool),
     Int(i32),
     Str(String),
-    New(String),
+    New(Symbol),
     Block(Vec<TypedExpr>),
     Case(Box<TypedExpr>, Vec<CaseBranch>),
     Paren(Box<TypedExpr>),
-    Let(Vec<(String, String, Option<TypedExpr>)>, Box<TypedExpr>),
+    Let(Vec<VarDecl>, Box<TypedExpr>),
     Comparison {
         lhs: Box<TypedExpr>,
         op: ComparisonOperator,
@@ -63,7 +104,7 @@ pub enum Expr {
         op: UnaryOperator,
         s: Box<TypedExpr>,
     },
-    Assignment(String, Box<TypedExpr>),
+    Assignment(Symbol, Box<TypedExpr>),
     Conditional {
         test: Box<TypedExpr>,
         then: Box<TypedExpr>,
@@ -76,20 +117,35 @@ pub enum Expr {
     Isvoid(Box<TypedExpr>),
     Dispatch {
         target: Option<Box<TypedExpr>>,
-        targettype: Option<String>,
-        id: String,
+        targettype: Option<Symbol>,
+        id: Symbol,
         exprs: Vec<TypedExpr>,
     },
+    /// `try body catch id : tid => handler end` (`--ext exceptions`)
+    Try {
+        body: Box<TypedExpr>,
+        id: Symbol,
+        tid: Symbol,
+        handler: Box<TypedExpr>,
+    },
+    /// `throw e` (`--ext exceptions`)
+    Throw(Box<TypedExpr>),
+    /// Body of a method declared `native` (`--ext natives`), supplied by a
+    /// host function
+    Native,
+    /// Body of a method of a built-in class (`Object`, `IO`, `String`, and
+    /// `Array` with `--ext arrays`), which the runtime implements
+    Builtin,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum ComparisonOperator {
     Lt,
     Le,
     Equal,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum MathOperator {
     Add,
     Subtract,
@@ -97,17 +153,20 @@ pub enum MathOperator {
     Div,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum UnaryOperator {
     Neg,
     Not,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct TypedExpr {
     pub expr: Expr,
-    pub static_type: Option<String>,
+    pub static_type: Option<Symbol>,
     pub line: usize,
+    /// Where the expression is in the source, for diagnostics
+    #[serde(default)]
+    pub span: Span,
 }
 
 impl TypedExpr {
@@ -116,55 +175,222 @@ impl TypedExpr {
             expr,
             static_type: None,
             line,
+            span: Span::default(),
+        }
+    }
+
+    /// An expression parsed from the source between `start` and `end`.
+    pub fn at(expr: Expr, start: Position, end: Position) -> Self {
+        TypedExpr {
+            span: Span { file: 0, start: start.offset, end: end.offset },
+            ..TypedExpr::new(expr, start.line)
+        }
+    }
+}
+
+impl Expr {
+    /// Direct subexpressions, in source order.
+    pub fn children(&self) -> Vec<&TypedExpr> {
+        match self {
+            Expr::Identifier(_)
+            | Expr::Bool(_)
+            | Expr::Int(_)
+            | Expr::Str(_)
+            | Expr::New(_)
+            | Expr::Native
+            | Expr::Builtin => Vec::new(),
+            Expr::Block(exprs) => exprs.iter().collect(),
+            Expr::Case(scrutinee, branches) => std::iter::once(&**scrutinee)
+                .chain(branches.iter().map(|b| &b.expr))
+                .collect(),
+            Expr::Paren(inner) | Expr::Isvoid(inner) | Expr::Throw(inner) => vec![inner],
+            Expr::Let(bindings, body) => bindings
+                .iter()
+                .filter_map(|binding| binding.expr.as_ref())
+                .chain(std::iter::once(&**body))
+                .collect(),
+            Expr::Comparison { lhs, rhs, .. } | Expr::Math { lhs, rhs, .. } => vec![lhs, rhs],
+            Expr::UnaryOperation { s, .. } => vec![s],
+            Expr::Assignment(_, rhs) => vec![rhs],
+            Expr::Conditional { test, then, orelse } => vec![test, then, orelse],
+            Expr::While { test, exec } => vec![test, exec],
+            Expr::Try { body, handler, .. } => vec![body, handler],
+            Expr::Dispatch { target, exprs, .. } => {
+                target.as_deref().into_iter().chain(exprs.iter()).collect()
+            }
+        }
+    }
+
+    /// Mutable counterpart of `children`, in the same order.
+    pub fn children_mut(&mut self) -> Vec<&mut TypedExpr> {
+        match self {
+            Expr::Identifier(_)
+            | Expr::Bool(_)
+            | Expr::Int(_)
+            | Expr::Str(_)
+            | Expr::New(_)
+            | Expr::Native
+            | Expr::Builtin => Vec::new(),
+            Expr::Block(exprs) => exprs.iter_mut().collect(),
+            Expr::Case(scrutinee, branches) => std::iter::once(&mut **scrutinee)
+                .chain(branches.iter_mut().map(|b| &mut b.expr))
+                .collect(),
+            Expr::Paren(inner) | Expr::Isvoid(inner) | Expr::Throw(inner) => vec![inner],
+            Expr::Let(bindings, body) => bindings
+                .iter_mut()
+                .filter_map(|binding| binding.expr.as_mut())
+                .chain(std::iter::once(&mut **body))
+                .collect(),
+            Expr::Comparison { lhs, rhs, .. } | Expr::Math { lhs, rhs, .. } => vec![lhs, rhs],
+            Expr::UnaryOperation { s, .. } => vec![s],
+            Expr::Assignment(_, rhs) => vec![rhs],
+            Expr::Conditional { test, then, orelse } => vec![test, then, orelse],
+            Expr::While { test, exec } => vec![test, exec],
+            Expr::Try { body, handler, .. } => vec![body, handler],
+            Expr::Dispatch { target, exprs, .. } => {
+                target.as_deref_mut().into_iter().chain(exprs.iter_mut()).collect()
+            }
         }
     }
 }
 
 impl Program {
     pub fn new(classes: Vec<Class>) -> Self {
-        Program { classes }
+        Program { imports: Vec::new(), classes }
     }
 }
 
 impl Class {
-    pub fn new(name: String, inherits: Option<String>, feature_list: Vec<Feature>) -> Self {
+    pub fn new(name: Symbol, inherits: Option<Symbol>, feature_list: Vec<Feature>) -> Self {
         Class {
             name,
             inherits,
             feature_list,
+            file: 0,
+            location: Location::default(),
+        }
+    }
+
+    pub fn at(mut self, start: Position, end: Position) -> Self {
+        self.location = Location::at(start, end);
+        self
+    }
+
+    /// Mark the class and every expression in it as parsed from input file
+    /// `file`.
+    pub fn set_file(&mut self, file: usize) {
+        self.file = file;
+        self.for_each_location(&mut |_, span| span.file = file);
+    }
+
+    /// Move the class from `from` to `to` in its file, as when the text
+    /// before it grew or shrank: every parsed line and offset moves by the
+    /// same amount. `from` must be at or before the class.
+    pub fn relocate(&mut self, from: Position, to: Position) {
+        self.for_each_location(&mut |line, span| relocate(line, span, from, to));
+    }
+
+    /// Call `f` with the line and span of the class header, of every
+    /// feature and formal, and of every expression. Runs before the nesting
+    /// of the expressions is limited, so it keeps a stack of its own.
+    fn for_each_location(&mut self, f: &mut impl FnMut(&mut usize, &mut Span)) {
+        fn walk(expr: &mut TypedExpr, f: &mut impl FnMut(&mut usize, &mut Span)) {
+            let mut stack = vec![expr];
+            while let Some(expr) = stack.pop() {
+                f(&mut expr.line, &mut expr.span);
+                stack.extend(expr.expr.children_mut());
+            }
+        }
+        f(&mut self.location.line, &mut self.location.span);
+        for feature in &mut self.feature_list {
+            match feature {
+                Feature::Attribute(VarDecl { expr, location, .. }) => {
+                    f(&mut location.line, &mut location.span);
+                    if let Some(init) = expr {
+                        walk(init, f);
+                    }
+                }
+                Feature::Method(_, args, _, body, location) => {
+                    f(&mut location.line, &mut location.span);
+                    for arg in args {
+                        f(&mut arg.location.line, &mut arg.location.span);
+                    }
+                    walk(body, f);
+                }
+            }
         }
     }
 }
 
+/// Move one parsed location from `from` to `to`, as `Class::relocate`.
+/// Locations the compiler made (line 0) stay where they are.
+pub fn relocate(line: &mut usize, span: &mut Span, from: Position, to: Position) {
+    if *line == 0 {
+        return;
+    }
+    *line = *line - from.line + to.line;
+    if !span.is_empty() {
+        span.start = span.start - from.offset + to.offset;
+        span.end = span.end - from.offset + to.offset;
+    }
+}
+
 impl Feature {
-    pub fn new_attribute(oid: String, tid: String, init: Option<TypedExpr>) -> Self {
-        Feature::Attribute(VarDecl { oid, tid, expr: init })
+    pub fn new_attribute(oid: Symbol, tid: Symbol, init: Option<TypedExpr>) -> Self {
+        Feature::Attribute(VarDecl::new(oid, tid, init))
     }
 
     pub fn new_method(
-        name: String,
+        name: Symbol,
         args: Vec<ArgDecl>,
-        return_type: String,
+        return_type: Symbol,
         body: TypedExpr,
     ) -> Self {
-        Feature::Method(name, args, return_type, body)
+        Feature::Method(name, args, return_type, body, Location::default())
+    }
+
+    /// The same feature, declared at `location`.
+    pub fn at(mut self, start: Position, end: Position) -> Self {
+        match &mut self {
+            Feature::Attribute(VarDecl { location, .. }) | Feature::Method(.., location) => {
+                *location = Location::at(start, end)
+            }
+        }
+        self
     }
 }
 
 impl VarDecl {
-    pub fn new(oid: String, tid: String, expr: Option<TypedExpr>) -> Self {
-        VarDecl { oid, tid, expr }
+    pub fn new(oid: Symbol, tid: Symbol, expr: Option<TypedExpr>) -> Self {
+        VarDecl { oid, tid, expr, location: Location::default() }
+    }
+
+    /// A `let` binding parsed from the source, with `oid : tid` between
+    /// `start` and `end`.
+    pub fn at(oid: Symbol, tid: Symbol, expr: Option<TypedExpr>, start: Position, end: Position) -> Self {
+        VarDecl { oid, tid, expr, location: Location::at(start, end) }
     }
 }
 
 impl ArgDecl {
-    pub fn new(id: String, tid: String) -> Self {
-        ArgDecl { id, tid }
+    pub fn new(id: Symbol, tid: Symbol) -> Self {
+        ArgDecl { id, tid, location: Location::default() }
+    }
+
+    /// A formal parsed from the source between `start` and `end`.
+    pub fn at(id: Symbol, tid: Symbol, start: Position, end: Position) -> Self {
+        ArgDecl { id, tid, location: Location::at(start, end) }
     }
 }
 
 impl CaseBranch {
-    pub fn new(id: String, tid: String, expr: TypedExpr) -> Self {
-        CaseBranch { id, tid, expr }
+    pub fn new(id: Symbol, tid: Symbol, expr: TypedExpr) -> Self {
+        CaseBranch { id, tid, expr, location: Location::default() }
+    }
+
+    /// A branch parsed from the source, with `id : tid` between `start`
+    /// and `end`.
+    pub fn at(id: Symbol, tid: Symbol, expr: TypedExpr, start: Position, end: Position) -> Self {
+        CaseBranch { id, tid, expr, location: Location::at(start, end) }
     }
 }
@@ -0,0 +1,405 @@
+//! The AST in the indented format of the reference compiler's `parse` and
+//! `semant` stages, so output can be diffed against `coolc`'s.
+
+use std::fmt::Write;
+
+use crate::ast::{
+    ArgDecl, CaseBranch, Class, ComparisonOperator, Expr, Feature, MathOperator, TypedExpr,
+    UnaryOperator, VarDecl,
+};
+use crate::parsing::dump::escape_str;
+use crate::symbol::sym;
+
+/// AST of `classes` in the reference `parse` format, where every expression
+/// is followed by a `: _no_type` line.
+///
+/// Classes, features and formals take the line they are declared on. Each
+/// class is listed under its input file, `filenames[class.file]`.
+pub fn coolc_parse(classes: &[Class], filenames: &[&str]) -> String {
+    dump(classes, filenames, false)
+}
+
+/// Annotated AST of `classes` in the reference `semant` format, where every
+/// expression is followed by its `: Type` line; otherwise as `coolc_parse`.
+///
+/// `classes` should come from the type checker (`check_program` or
+/// `type_checker::annotate`); expressions without a type print as
+/// `_no_type`.
+pub fn coolc_types(classes: &[Class], filenames: &[&str]) -> String {
+    dump(classes, filenames, true)
+}
+
+fn dump(classes: &[Class], filenames: &[&str], types: bool) -> String {
+    let mut dumper = Dumper { out: String::new(), line: 1, types };
+    let first = classes.first().map_or(1, |class| class.location.line);
+    dumper.node(0, first, "_program");
+    for class in classes {
+        dumper.class(class, filenames[class.file]);
+    }
+    dumper.out
+}
+
+struct Dumper {
+    out: String,
+    /// Line of the most recent node, for the `_no_expr` of an attribute or
+    /// binding without an initializer
+    line: usize,
+    /// Print static types rather than `_no_type`
+    types: bool,
+}
+
+impl Dumper {
+    fn node(&mut self, n: usize, line: usize, name: &str) {
+        self.line = line;
+        writeln!(self.out, "{:n$}#{}", "", line).unwrap();
+        writeln!(self.out, "{:n$}{}", "", name).unwrap();
+    }
+
+    fn text(&mut self, n: usize, text: impl std::fmt::Display) {
+        writeln!(self.out, "{:n$}{}", "", text).unwrap();
+    }
+
+    fn class(&mut self, class: &Class, filename: &str) {
+        self.node(2, class.location.line, "_class");
+        self.text(4, class.name);
+        self.text(4, class.inherits.unwrap_or(sym::OBJECT));
+        self.text(4, escape_str(filename));
+        self.text(4, "(");
+        for feature in &class.feature_list {
+            self.feature(feature);
+        }
+        self.text(4, ")");
+    }
+
+    fn feature(&mut self, feature: &Feature) {
+        match feature {
+            Feature::Attribute(VarDecl { oid, tid, expr, location }) => {
+                self.node(4, location.line, "_attr");
+                self.text(6, oid);
+                self.text(6, tid);
+                match expr {
+                    Some(init) => self.expr(6, init),
+                    None => self.no_expr(6),
+                }
+            }
+            Feature::Method(name, args, ret_type, body, location) => {
+                self.node(4, location.line, "_method");
+                self.text(6, name);
+                for ArgDecl { id, tid, location } in args {
+                    self.node(6, location.line, "_formal");
+                    self.text(8, id);
+                    self.text(8, tid);
+                }
+                self.text(6, ret_type);
+                self.expr(6, body);
+            }
+        }
+    }
+
+    fn no_expr(&mut self, n: usize) {
+        self.node(n, self.line, "_no_expr");
+        self.text(n, ": _no_type");
+    }
+
+    fn expr(&mut self, n: usize, e: &TypedExpr) {
+        let m = n + 2;
+        match &e.expr {
+            Expr::Paren(inner) => return self.expr(n, inner),
+            Expr::Identifier(name) => {
+                self.node(n, e.line, "_object");
+                self.text(m, name);
+            }
+            Expr::Bool(b) => {
+                self.node(n, e.line, "_bool");
+                self.text(m, u8::from(*b));
+            }
+            Expr::Int(i) => {
+                self.node(n, e.line, "_int");
+                self.text(m, i);
+            }
+            Expr::Str(s) => {
+                self.node(n, e.line, "_string");
+                self.text(m, escape_str(s));
+            }
+            Expr::New(type_name) => {
+                self.node(n, e.line, "_new");
+                self.text(m, type_name);
+            }
+            Expr::Block(exprs) => {
+                self.node(n, e.line, "_block");
+                for e in exprs {
+                    self.expr(m, e);
+                }
+            }
+            Expr::Case(scrutinee, branches) => {
+                self.node(n, e.line, "_typcase");
+                self.expr(m, scrutinee);
+                for CaseBranch { id, tid, expr, .. } in branches {
+                    self.node(m, expr.line, "_branch");
+                    self.text(m + 2, id);
+                    self.text(m + 2, tid);
+                    self.expr(m + 2, expr);
+                }
+            }
+            Expr::Let(bindings, body) => return self.let_(n, e, bindings, body),
+            Expr::Comparison { lhs, op, rhs } => {
+                let name = match op {
+                    ComparisonOperator::Lt => "_lt",
+                    ComparisonOperator::Le => "_leq",
+                    ComparisonOperator::Equal => "_eq",
+                };
+                self.node(n, e.line, name);
+                self.expr(m, lhs);
+                self.expr(m, rhs);
+            }
+            Expr::Math { lhs, op, rhs } => {
+                let name = match op {
+                    MathOperator::Add => "_plus",
+                    MathOperator::Subtract => "_sub",
+                    MathOperator::Mul => "_mul",
+                    MathOperator::Div => "_divide",
+                };
+                self.node(n, e.line, name);
+                self.expr(m, lhs);
+                self.expr(m, rhs);
+            }
+            Expr::UnaryOperation { op, s } => {
+                let name = match op {
+                    UnaryOperator::Neg => "_neg",
+                    UnaryOperator::Not => "_comp",
+                };
+                self.node(n, e.line, name);
+                self.expr(m, s);
+            }
+            Expr::Assignment(name, rhs) => {
+                self.node(n, e.line, "_assign");
+                self.text(m, name);
+                self.expr(m, rhs);
+            }
+            Expr::Conditional { test, then, orelse } => {
+                self.node(n, e.line, "_cond");
+                self.expr(m, test);
+                self.expr(m, then);
+                self.expr(m, orelse);
+            }
+            Expr::While { test, exec } => {
+                self.node(n, e.line, "_loop");
+                self.expr(m, test);
+                self.expr(m, exec);
+            }
+            Expr::Isvoid(inner) => {
+                self.node(n, e.line, "_isvoid");
+                self.expr(m, inner);
+            }
+            Expr::Dispatch { target, targettype, id, exprs } => {
+                self.node(n, e.line, if targettype.is_some() { "_static_dispatch" } else { "_dispatch" });
+                match target {
+                    Some(target) => self.expr(m, target),
+                    // A call without a receiver is a dispatch on self
+                    None => {
+                        self.node(m, e.line, "_object");
+                        self.text(m + 2, sym::SELF);
+                        let ty = if self.types { sym::SELF_TYPE.as_str() } else { "_no_type" };
+                        self.text(m, format_args!(": {}", ty));
+                    }
+                }
+                if let Some(tt) = targettype {
+                    self.text(m, tt);
+                }
+                self.text(m, id);
+                self.text(m, "(");
+                for arg in exprs {
+                    self.expr(m, arg);
+                }
+                self.text(m, ")");
+            }
+            // Extension nodes have no reference form; they follow the same style
+            Expr::Try { body, id, tid, handler } => {
+                self.node(n, e.line, "_try");
+                self.expr(m, body);
+                self.text(m, id);
+                self.text(m, tid);
+                self.expr(m, handler);
+            }
+            Expr::Throw(inner) => {
+                self.node(n, e.line, "_throw");
+                self.expr(m, inner);
+            }
+            Expr::Native => self.node(n, e.line, "_native"),
+            Expr::Builtin => self.node(n, e.line, "_builtin"),
+        }
+        self.static_type(n, e);
+    }
+
+    /// The reference AST has one binding per `let`, so several bindings
+    /// print as nested `let`s that all carry the outer expression's type.
+    fn let_(
+        &mut self,
+        n: usize,
+        e: &TypedExpr,
+        bindings: &[VarDecl],
+        body: &TypedExpr,
+    ) {
+        let Some((VarDecl { oid: id, tid, expr: init, .. }, rest)) = bindings.split_first() else {
+            return self.expr(n, body);
+        };
+        let m = n + 2;
+        self.node(n, e.line, "_let");
+        self.text(m, id);
+        self.text(m, tid);
+        match init {
+            Some(init) => self.expr(m, init),
+            None => self.no_expr(m),
+        }
+        self.let_(m, e, rest, body);
+        self.static_type(n, e);
+    }
+
+    fn static_type(&mut self, n: usize, e: &TypedExpr) {
+        match e.static_type.filter(|_| self.types) {
+            Some(ty) => self.text(n, format_args!(": {}", ty)),
+            None => self.text(n, ": _no_type"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::semantic::context::AnalysisContext;
+    use crate::semantic::type_checker::annotate;
+
+    #[test]
+    fn test_coolc_types() {
+        let source = "class Main inherits IO {\n  x : Int;\n  main() : Object {\n    let y : Bool <- not true in out_string(\"a\")\n  };\n};";
+        let classes = crate::parse_program(source).unwrap();
+        let ctx = AnalysisContext::new(crate::prelude(), &classes);
+        let dump = coolc_types(&annotate(&ctx), &["t.cl"]);
+        let expected = "\
+#1
+_program
+  #1
+  _class
+    Main
+    IO
+    \"t.cl\"
+    (
+    #2
+    _attr
+      x
+      Int
+      #2
+      _no_expr
+      : _no_type
+    #3
+    _method
+      main
+      Object
+      #4
+      _let
+        y
+        Bool
+        #4
+        _comp
+          #4
+          _bool
+            1
+          : Bool
+        : Bool
+        #4
+        _dispatch
+          #4
+          _object
+            self
+          : SELF_TYPE
+          out_string
+          (
+          #4
+          _string
+            \"a\"
+          : String
+          )
+        : SELF_TYPE
+      : SELF_TYPE
+    )
+";
+        assert_eq!(dump, expected);
+    }
+
+    #[test]
+    fn test_coolc_parse() {
+        let classes = crate::parse_program("class Main {\n  main() : Object { f(1) };\n};").unwrap();
+        let expected = "\
+#1
+_program
+  #1
+  _class
+    Main
+    Object
+    \"t.cl\"
+    (
+    #2
+    _method
+      main
+      Object
+      #2
+      _dispatch
+        #2
+        _object
+          self
+        : _no_type
+        f
+        (
+        #2
+        _int
+          1
+        : _no_type
+        )
+      : _no_type
+    )
+";
+        assert_eq!(coolc_parse(&classes, &["t.cl"]), expected);
+    }
+
+    #[test]
+    fn test_declaration_lines() {
+        // Formals and attributes without an initializer have lines of their own
+        let source = "class Main {\n  x : Int;\n  f(\n    a : Int,\n    b : Int\n  ) : Int {\n    a\n  };\n};";
+        let classes = crate::parse_program(source).unwrap();
+        let expected = "\
+#1
+_program
+  #1
+  _class
+    Main
+    Object
+    \"t.cl\"
+    (
+    #2
+    _attr
+      x
+      Int
+      #2
+      _no_expr
+      : _no_type
+    #3
+    _method
+      f
+      #4
+      _formal
+        a
+        Int
+      #5
+      _formal
+        b
+        Int
+      Int
+      #7
+      _object
+        a
+      : _no_type
+    )
+";
+        assert_eq!(coolc_parse(&classes, &["t.cl"]), expected);
+    }
+}
@@ -0,0 +1,73 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use clap::Parser;
+use cool_rs::coverage::Coverage;
+use cool_rs::parse_program;
+use eyre::{Context, Result};
+
+/// Command-line options
+#[derive(Parser)]
+#[command(
+    name = "cool-coverage",
+    version,
+    about = "Report which grammar constructs a corpus of COOL programs exercises"
+)]
+struct Cli {
+    /// Files or directories of `.cl` programs
+    #[arg(default_values = ["tests/valid", "tests/invalid", "tests/ui", "examples"])]
+    paths: Vec<PathBuf>,
+
+    /// Exit with a failure status if any construct is never exercised
+    #[arg(long)]
+    deny_gaps: bool,
+}
+
+fn collect_sources(path: &Path, out: &mut Vec<PathBuf>) -> Result<()> {
+    if path.is_dir() {
+        let entries = fs::read_dir(path).wrap_err_with(|| format!("Failed to read {:?}", path))?;
+        let mut entries = entries.collect::<Result<Vec<_>, _>>()?;
+        entries.sort_by_key(|e| e.path());
+        for entry in entries {
+            collect_sources(&entry.path(), out)?;
+        }
+    } else if path.extension().is_some_and(|ext| ext == "cl") {
+        out.push(path.to_path_buf());
+    }
+    Ok(())
+}
+
+fn main() -> Result<ExitCode> {
+    let cli = Cli::parse();
+    let mut files = Vec::new();
+    for path in &cli.paths {
+        collect_sources(path, &mut files)?;
+    }
+
+    let mut coverage = Coverage::default();
+    let mut parsed = 0;
+    for file in &files {
+        let source = fs::read_to_string(file).wrap_err_with(|| format!("Failed to read {:?}", file))?;
+        // Programs with syntax errors contribute nothing, but are not fatal
+        match parse_program(&source) {
+            Ok(classes) => {
+                coverage.record_program(&classes);
+                parsed += 1;
+            }
+            Err(_) => eprintln!("skipping {} (does not parse)", file.display()),
+        }
+    }
+
+    println!("{} of {} files parsed\n", parsed, files.len());
+    print!("{}", coverage.report());
+
+    let gaps = coverage.unexercised();
+    if gaps.is_empty() {
+        println!("\nEvery construct is exercised.");
+        Ok(ExitCode::SUCCESS)
+    } else {
+        println!("\n{} construct(s) never exercised: {}", gaps.len(), gaps.join(", "));
+        Ok(if cli.deny_gaps { ExitCode::FAILURE } else { ExitCode::SUCCESS })
+    }
+}
@@ -0,0 +1,56 @@
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use clap::Parser;
+use cool_rs::differential::{divergence, input_for, programs, run};
+use eyre::{Context, Result};
+
+/// Command-line options
+#[derive(Parser)]
+#[command(
+    name = "cool-difftest",
+    version,
+    about = "Run a corpus of COOL programs with `cool-rs --run` and with a reference implementation and report where they differ"
+)]
+struct Cli {
+    /// Files or directories of `.cl` programs
+    #[arg(default_values = ["tests/conformance", "tests/valid", "examples"])]
+    paths: Vec<PathBuf>,
+
+    /// The reference implementation: a command, split at whitespace, run
+    /// with the program's path added as its last argument
+    #[arg(long, value_name = "COMMAND")]
+    reference: String,
+
+    /// The command to test instead of `cool-rs --run --file` (the `cool-rs`
+    /// next to this binary), given the program's path in the same way
+    #[arg(long, value_name = "COMMAND")]
+    ours: Option<String>,
+}
+
+fn main() -> Result<ExitCode> {
+    let cli = Cli::parse();
+    let reference: Vec<String> = cli.reference.split_whitespace().map(String::from).collect();
+    let ours: Vec<String> = match &cli.ours {
+        Some(ours) => ours.split_whitespace().map(String::from).collect(),
+        None => {
+            let exe = std::env::current_exe()?.with_file_name(format!("cool-rs{}", std::env::consts::EXE_SUFFIX));
+            vec![exe.display().to_string(), "--run".to_string(), "--file".to_string()]
+        }
+    };
+    let files = programs(&cli.paths).wrap_err("Failed to read the corpus")?;
+
+    let mut diverged = 0;
+    for file in &files {
+        let input = input_for(file);
+        let mine = run(&ours, file, &input).wrap_err_with(|| format!("Failed to run {:?}", ours.join(" ")))?;
+        let theirs = run(&reference, file, &input).wrap_err_with(|| format!("Failed to run {:?}", cli.reference))?;
+        if let Some(divergence) = divergence(&mine, &theirs) {
+            println!("DIFF {}: {}", file.display(), divergence);
+            diverged += 1;
+        }
+    }
+
+    println!("{} programs, {} diverged", files.len(), diverged);
+    Ok(if diverged == 0 { ExitCode::SUCCESS } else { ExitCode::FAILURE })
+}
@@ -0,0 +1,69 @@
+use std::fs;
+use std::path::PathBuf;
+
+use clap::Parser;
+use cool_rs::generator::{generate_program, GenConfig};
+use eyre::{Context, Result};
+
+/// Command-line options
+#[derive(Parser)]
+#[command(
+    name = "cool-gen",
+    version,
+    about = "Generate a synthetic, type-correct COOL program for benchmarks and stress tests"
+)]
+struct Cli {
+    /// Number of classes (not counting Main)
+    #[arg(long, default_value_t = 100)]
+    classes: usize,
+
+    /// Methods per class
+    #[arg(long, default_value_t = 10)]
+    methods: usize,
+
+    /// Attributes per class
+    #[arg(long, default_value_t = 5)]
+    attributes: usize,
+
+    /// Length of each inheritance chain (1 = every class inherits Object)
+    #[arg(long, default_value_t = 1)]
+    depth: usize,
+
+    /// `--` comment lines before each class and method
+    #[arg(long, default_value_t = 0)]
+    comments: usize,
+
+    /// Nesting depth of a random Int expression added to each method (0 = none)
+    #[arg(long, default_value_t = 0)]
+    expr_depth: usize,
+
+    /// Seed for the random expressions
+    #[arg(long, default_value_t = 0)]
+    seed: u64,
+
+    /// Write the program here instead of stdout
+    #[arg(short, long, value_name = "FILE")]
+    output: Option<PathBuf>,
+}
+
+fn main() -> Result<()> {
+    let cli = Cli::parse();
+    let program = generate_program(&GenConfig {
+        classes: cli.classes,
+        methods: cli.methods,
+        attributes: cli.attributes,
+        depth: cli.depth,
+        comments: cli.comments,
+        expr_depth: cli.expr_depth,
+        seed: cli.seed,
+    });
+
+    match cli.output {
+        Some(path) => fs::write(&path, program)
+            .wrap_err_with(|| format!("Failed to write {:?}", path)),
+        None => {
+            print!("{}", program);
+            Ok(())
+        }
+    }
+}
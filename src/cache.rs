@@ -0,0 +1,77 @@
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::ext::Extensions;
+
+/// Default location of the incremental cache, relative to the working directory.
+pub const CACHE_DIR: &str = ".cool-cache";
+
+/// On-disk cache with one JSON file per entry, named after the entry's key:
+/// the hash of what it was worked out from. `incremental::Session` keeps
+/// one entry per input file in it.
+pub struct Cache {
+    dir: PathBuf,
+}
+
+impl Cache {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Cache { dir: dir.into() }
+    }
+
+    fn entry_path(&self, key: u64) -> PathBuf {
+        self.dir.join(format!("{:016x}.json", key))
+    }
+
+    /// Missing, unreadable or stale-format entries are treated as a cache miss.
+    pub fn load<T: DeserializeOwned>(&self, key: u64) -> Option<T> {
+        let data = fs::read(self.entry_path(key)).ok()?;
+        serde_json::from_slice(&data).ok()
+    }
+
+    pub fn store(&self, key: u64, entry: &impl Serialize) -> io::Result<()> {
+        fs::create_dir_all(&self.dir)?;
+        let data = serde_json::to_vec(entry)?;
+        // Write to a temporary file first so a crash never leaves a torn entry
+        let tmp = self.dir.join(format!("{:016x}.tmp", key));
+        fs::write(&tmp, data)?;
+        fs::rename(tmp, self.entry_path(key))
+    }
+}
+
+/// FNV-1a over the compiler version and the source text.
+///
+/// The standard library's hasher is not guaranteed to be stable across Rust
+/// releases, so it cannot key data that outlives the process.
+pub fn content_hash(source: &str) -> u64 {
+    fnv1a(&[env!("CARGO_PKG_VERSION").as_bytes(), source.as_bytes()])
+}
+
+/// `content_hash` over each of `texts` in turn, also keyed on the language
+/// extensions they are compiled with, since they change how the same text parses.
+pub fn cache_key(texts: &[&str], extensions: &Extensions) -> u64 {
+    let names: Vec<&str> = extensions.names().collect();
+    let names = names.join(",");
+    let mut parts = vec![env!("CARGO_PKG_VERSION").as_bytes(), names.as_bytes()];
+    parts.extend(texts.iter().map(|text| text.as_bytes()));
+    fnv1a(&parts)
+}
+
+/// FNV-1a over `parts`, with a zero byte between consecutive parts.
+fn fnv1a(parts: &[&[u8]]) -> u64 {
+    const OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0000_0100_0000_01b3;
+
+    let mut hash = OFFSET;
+    for (i, part) in parts.iter().enumerate() {
+        let separator: &[u8] = if i == 0 { &[] } else { &[0] };
+        for &byte in separator.iter().chain(part.iter()) {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(PRIME);
+        }
+    }
+    hash
+}
@@ -0,0 +1,1055 @@
+//! C code generation (`--emit c`).
+//!
+//! Translates the IR of a program that passed semantic analysis (see
+//! `crate::ir`) to one self-contained C99 file that needs nothing but the C
+//! standard library, for platforms with a C compiler and no native backend:
+//! `cc -std=c99 foo.c -o foo`. The runtime, with the built-in methods, comes
+//! first (`RUNTIME`), and `main` evaluates `(new Main).main()`. Objects
+//! follow the LLVM backend:
+//!
+//! * Every object starts with an `Object` header: its class's dispatch
+//!   table, its class tag and its size. A class's object is a
+//!   `struct C_Name` holding the header and then every attribute, inherited
+//!   ones first, so a pointer to it can be read as a pointer to any
+//!   ancestor's struct. Dispatch tables are arrays of function pointers in
+//!   the method order of `Layout`.
+//! * `Int`, `Bool` and `String` objects are immutable; string literals are
+//!   static objects. The IR's unboxed words are `int32_t` variables.
+//! * Every method takes `self` and its arguments as `Object *` and returns
+//!   an `Object *`; void is `NULL`. `M<n><Class>_<method>` is the method of
+//!   the class whose name has `n` characters, so no two names collide.
+//!   `init_Name` runs the attribute initializers of a new object.
+//!
+//! Each IR variable is a C local and each block a label, jumped to with
+//! `goto`. Arithmetic overflows as `--int-overflow` says, as in the
+//! interpreter. A dispatch or `case` on void, a `case` with no branch for
+//! its value, a division by zero and an overflow that traps are reported
+//! with their line, as by `--run`. Memory is never freed.
+//!
+//! The `Array` class of `--ext arrays` is supported: an `Array` object, or
+//! one of a subclass, holds its length and a pointer to its elements right
+//! after the header, and `copy` copies the elements too.
+//!
+//! So is `--ext exceptions`: a `try` pushes a handler on a stack, whose
+//! `setjmp` a `throw` returns to with `longjmp`, and lands in the IR's
+//! handler block; the variables of a function with a `try` are `volatile`,
+//! so that they keep the values they had at the throw. As in the other
+//! backends, the `natives` extension has no code generation.
+
+use std::collections::HashSet;
+use std::fmt::Write;
+
+use eyre::Result;
+
+use super::check_supported;
+use crate::arith::Overflow;
+use super::layout::Layout;
+use crate::ast::{Class, Feature};
+use crate::ext::{Extension, Extensions};
+use crate::ir::{self, BinaryOp, Callee, Function, Inst, Terminator, Ty, UnaryOp, Var};
+use crate::semantic::context::AnalysisContext;
+use crate::symbol::{sym, Symbol};
+
+/// C source for the program of `ctx`, which must have checked cleanly, to
+/// run with `overflow`. `classes` are its classes as annotated by the type
+/// checker.
+pub fn generate(ctx: &AnalysisContext<'_>, classes: &[Class], overflow: Overflow) -> Result<String> {
+    let extensions: Extensions = [Extension::Arrays, Extension::Exceptions].into_iter().collect();
+    check_supported(ctx, classes, &extensions)?;
+    let all: Vec<&Class> = ctx.builtins.iter().chain(classes).collect();
+    let layout = Layout::new(&all);
+    let program = ir::lower_layout(&layout, overflow);
+    let mut generator = Generator { layout: &layout, strings: program.strings.clone(), text: String::new(), body: String::new() };
+    // The class name table needs these whatever the program uses
+    for class in &layout.classes {
+        generator.string_const(class.class.name.as_str());
+    }
+    generator.string_const("");
+    generator.text(&program.functions);
+    let declarations = generator.declarations(&program.functions);
+    let data = generator.data();
+    let mut runtime = RUNTIME.to_string();
+    if generator.array_tags().is_some() {
+        runtime = [&runtime, ARRAY_RUNTIME].join("\n");
+    }
+    if program.functions.iter().any(|function| !handlers(function).is_empty() || throws(function)) {
+        runtime = [&runtime, EXCEPTION_RUNTIME].join("\n");
+    }
+    Ok(format!("{}\n{}\n{}\n{}\n{}", HEADER, declarations, data, runtime, generator.text))
+}
+
+/// Name of the C function for `method` of `class`.
+fn method_name(class: Symbol, method: Symbol) -> String {
+    format!("M{}{}_{}", class.as_str().len(), class, method)
+}
+
+/// Name of the C function for `function`.
+fn function_name(function: &Function) -> String {
+    callee_name(function.class, function.name)
+}
+
+fn callee_name(class: Symbol, method: Symbol) -> String {
+    match method.as_str() {
+        "_init" => format!("init_{}", class),
+        _ => method_name(class, method),
+    }
+}
+
+/// Parameters of the C function for `function`, `volatile` if `longjmp`
+/// may return to it.
+fn parameters(function: &Function, volatile: bool) -> String {
+    let qualifier = if volatile { "volatile " } else { "" };
+    (0..function.params).map(|i| format!("Object *{}v{}", qualifier, i)).collect::<Vec<_>>().join(", ")
+}
+
+/// Whether `function` has a `throw`.
+fn throws(function: &Function) -> bool {
+    function.blocks.iter().any(|block| matches!(block.terminator, Terminator::Throw { .. }))
+}
+
+/// The blocks of `function` that end in a `Try`, each with a handler of its own.
+fn handlers(function: &Function) -> Vec<usize> {
+    (0..function.blocks.len()).filter(|&i| matches!(function.blocks[i].terminator, Terminator::Try { .. })).collect()
+}
+
+struct Generator<'l, 'a> {
+    layout: &'l Layout<'a>,
+    /// Constants, numbered by position; the IR's come first, with its numbers
+    strings: Vec<String>,
+    /// Finished function definitions
+    text: String,
+    /// Statements of the function being emitted
+    body: String,
+}
+
+/// One statement of the function being emitted.
+macro_rules! emit {
+    ($g:expr, $($arg:tt)*) => {
+        writeln!($g.body, "    {}", format_args!($($arg)*)).unwrap()
+    };
+}
+
+impl Generator<'_, '_> {
+    fn string_const(&mut self, s: &str) -> String {
+        let index = match self.strings.iter().position(|t| t == s) {
+            Some(index) => index,
+            None => {
+                self.strings.push(s.to_string());
+                self.strings.len() - 1
+            }
+        };
+        format!("&str_{}.header", index)
+    }
+
+    fn tag(&self, class: Symbol) -> usize {
+        self.layout.class(class).tag
+    }
+
+    /// The tags of `Array` and its descendants, if the program has arrays.
+    fn array_tags(&self) -> Option<(usize, usize)> {
+        let array = self.layout.classes.iter().find(|class| class.class.name.as_str() == "Array")?;
+        Some((array.tag, array.last_descendant))
+    }
+
+    // ---- Declarations and data ----------------------------------------------
+
+    /// The struct of every class and the prototype of every function, so
+    /// the data and the code can refer to them in any order.
+    fn declarations(&self, functions: &[Function]) -> String {
+        let mut out = String::new();
+        for class in &self.layout.classes {
+            let name = class.class.name;
+            if matches!(name, sym::INT | sym::BOOL | sym::STRING) {
+                continue;
+            }
+            writeln!(out, "struct C_{} {{\n    Object header;", name).unwrap();
+            // The fields of `cool_Array` come before the attributes
+            if self.array_tags().is_some_and(|(first, last)| (first..=last).contains(&class.tag)) {
+                writeln!(out, "    int64_t length;\n    Object **items;").unwrap();
+            }
+            for (attr, _) in &class.attributes {
+                writeln!(out, "    Object *f_{};", attr).unwrap();
+            }
+            writeln!(out, "}};").unwrap();
+        }
+        writeln!(out).unwrap();
+        for class in &self.layout.classes {
+            writeln!(out, "static Object *new_{}(void);", class.class.name).unwrap();
+        }
+        // The runtime declares the basic classes' methods, and `ARRAY_RUNTIME`
+        // defines the `Array` ones
+        if let Some((tag, _)) = self.array_tags() {
+            let array = self.layout.classes[tag].class;
+            for feature in &array.feature_list {
+                if let Feature::Method(method, args, ..) = feature {
+                    let params: Vec<String> = (0..=args.len()).map(|i| format!("Object *v{}", i)).collect();
+                    writeln!(out, "static Object *{}({});", method_name(array.name, *method), params.join(", ")).unwrap();
+                }
+            }
+        }
+        for function in functions {
+            writeln!(out, "static Object *{}({});", function_name(function), parameters(function, false)).unwrap();
+        }
+        out
+    }
+
+    fn data(&self) -> String {
+        let mut out = String::new();
+        for (name, class) in [("cool_int_tag", sym::INT), ("cool_bool_tag", sym::BOOL), ("cool_string_tag", sym::STRING)] {
+            writeln!(out, "static const int64_t {} = {};", name, self.tag(class)).unwrap();
+        }
+        // An empty range when there are no arrays
+        let (first, last) = self.array_tags().unwrap_or((1, 0));
+        writeln!(out, "static const int64_t cool_array_first = {}, cool_array_last = {};", first, last).unwrap();
+        for class in &self.layout.classes {
+            let methods: Vec<String> = class
+                .methods
+                .iter()
+                .map(|(method, owner)| format!("(cool_fn){}", method_name(*owner, *method)))
+                .collect();
+            writeln!(out, "static const cool_fn vt_{}[] = {{{}}};", class.class.name, methods.join(", ")).unwrap();
+        }
+
+        let (int_tag, bool_tag, string_tag) = (self.tag(sym::INT), self.tag(sym::BOOL), self.tag(sym::STRING));
+        for (i, s) in self.strings.iter().enumerate() {
+            writeln!(
+                out,
+                "static cool_String str_{} = {{{{vt_String, {}, sizeof(cool_String)}}, {}, \"{}\"}};",
+                i,
+                string_tag,
+                s.len(),
+                escape(s)
+            )
+            .unwrap();
+        }
+        // The default value of Int attributes
+        writeln!(out, "static cool_Int int_0 = {{{{vt_Int, {}, sizeof(cool_Int)}}, 0}};", int_tag).unwrap();
+        for value in 0..2 {
+            writeln!(out, "static cool_Int bool_{} = {{{{vt_Bool, {}, sizeof(cool_Int)}}, {}}};", value, bool_tag, value)
+                .unwrap();
+        }
+
+        // Indexed by tag, for `type_name` and `new SELF_TYPE`
+        let names: Vec<String> = self
+            .layout
+            .classes
+            .iter()
+            .map(|c| format!("&str_{}.header", self.strings.iter().position(|s| s == c.class.name.as_str()).unwrap()))
+            .collect();
+        writeln!(out, "static Object *const cool_class_names[] = {{{}}};", names.join(", ")).unwrap();
+        let news: Vec<String> = self.layout.classes.iter().map(|c| format!("new_{}", c.class.name)).collect();
+        writeln!(out, "static Object *(*const cool_class_new[])(void) = {{{}}};", news.join(", ")).unwrap();
+        out
+    }
+
+    // ---- Code -----------------------------------------------------------------
+
+    fn text(&mut self, functions: &[Function]) {
+        let classes: Vec<Symbol> = self.layout.classes.iter().map(|c| c.class.name).collect();
+        for class in classes {
+            self.new_object(class);
+        }
+        for function in functions {
+            self.function(function);
+        }
+
+        let (_, owner) = *self.layout.class(sym::MAIN_CLASS).methods.iter().find(|(m, _)| *m == sym::MAIN_METHOD).unwrap();
+        writeln!(
+            self.text,
+            "int main(void) {{\n    Object *main = new_Main();\n    {}(main);\n    fflush(stdout);\n    return 0;\n}}",
+            method_name(owner, sym::MAIN_METHOD)
+        )
+        .unwrap();
+    }
+
+    /// `new_C`: a new object of class `C` with default attribute values,
+    /// initialized by `init_C`.
+    fn new_object(&mut self, name: Symbol) {
+        self.body.clear();
+        match self.default_value(name) {
+            // Values are immutable, so every new one can be the same constant
+            Some(value) => emit!(self, "return {};", value),
+            None => {
+                emit!(
+                    self,
+                    "struct C_{0} *self = (struct C_{0} *)cool_alloc(vt_{0}, {1}, sizeof(struct C_{0}));",
+                    name,
+                    self.tag(name)
+                );
+                for (attr, ty) in self.layout.class(name).attributes.clone() {
+                    // Everything else defaults to void, which calloc left in place
+                    if let Some(value) = self.default_value(ty) {
+                        emit!(self, "self->f_{} = {};", attr, value);
+                    }
+                }
+                emit!(self, "return init_{}(&self->header);", name);
+            }
+        }
+        writeln!(self.text, "static Object *new_{}(void) {{\n{}}}\n", name, self.body).unwrap();
+    }
+
+    /// The shared object that is the default value of `Int`, `Bool` and
+    /// `String`; the others default to void.
+    fn default_value(&mut self, ty: Symbol) -> Option<String> {
+        match ty {
+            sym::INT => Some("&int_0.header".to_string()),
+            sym::STRING => Some(self.string_const("")),
+            sym::BOOL => Some("&bool_0.header".to_string()),
+            _ => None,
+        }
+    }
+
+    fn function(&mut self, function: &Function) {
+        self.body.clear();
+        // Variables written after a `setjmp` keep their values through a
+        // `longjmp` back to it only if they are volatile
+        let handlers = handlers(function);
+        let volatile = !handlers.is_empty();
+        let qualifier = if volatile { "volatile " } else { "" };
+        let words: Vec<String> = (function.params..function.vars.len())
+            .filter(|&i| !matches!(function.vars[i], Ty::Object(_)))
+            .map(|i| format!("v{}", i))
+            .collect();
+        let objects: Vec<String> = (function.params..function.vars.len())
+            .filter(|&i| matches!(function.vars[i], Ty::Object(_)))
+            .map(|i| format!("*{}v{}", qualifier, i))
+            .collect();
+        if !words.is_empty() {
+            emit!(self, "{}int32_t {};", qualifier, words.join(", "));
+        }
+        if !objects.is_empty() {
+            emit!(self, "Object {};", objects.join(", "));
+        }
+        for i in &handlers {
+            emit!(self, "cool_handler h{};", i);
+        }
+
+        // Only jumped-to blocks need a label; the others are fallen into
+        let mut targets = HashSet::new();
+        for block in &function.blocks {
+            match block.terminator {
+                Terminator::Jump(target) => {
+                    targets.insert(target);
+                }
+                Terminator::Branch { then, orelse, .. } => {
+                    targets.extend([then, orelse]);
+                }
+                Terminator::Try { body, handler } => {
+                    targets.extend([body, handler]);
+                }
+                Terminator::Return(_)
+                | Terminator::Throw { .. }
+                | Terminator::Rethrow
+                | Terminator::NoMatch { .. }
+                | Terminator::DispatchOnVoid { .. }
+                | Terminator::CaseOnVoid { .. }
+                | Terminator::DivisionByZero { .. }
+                | Terminator::IntegerOverflow { .. } => {}
+            }
+        }
+        for (i, block) in function.blocks.iter().enumerate() {
+            if targets.contains(&ir::BlockId(i as u32)) {
+                writeln!(self.body, "b{}:", i).unwrap();
+            }
+            for inst in &block.insts {
+                self.inst(function, inst);
+            }
+            let next = ir::BlockId(i as u32 + 1);
+            match block.terminator {
+                Terminator::Jump(target) if target == next => {}
+                Terminator::Jump(target) => emit!(self, "goto {};", target),
+                Terminator::Branch { cond, then, orelse } if orelse == next => emit!(self, "if ({}) goto {};", cond, then),
+                Terminator::Branch { cond, then, orelse } if then == next => emit!(self, "if (!{}) goto {};", cond, orelse),
+                Terminator::Branch { cond, then, orelse } => {
+                    emit!(self, "if ({}) goto {};", cond, then);
+                    emit!(self, "goto {};", orelse);
+                }
+                Terminator::Return(value) => emit!(self, "return {};", value),
+                // The runtime reports the class and exits
+                Terminator::NoMatch { object, line } => emit!(self, "return cool_case_abort({}, {});", line, object),
+                Terminator::DispatchOnVoid { method, line } => {
+                    emit!(self, "return cool_dispatch_abort({}, \"{}\");", line, method)
+                }
+                Terminator::CaseOnVoid { line } => emit!(self, "return cool_void_case_abort({});", line),
+                Terminator::DivisionByZero { line } => emit!(self, "return cool_division_abort({});", line),
+                Terminator::IntegerOverflow { line } => emit!(self, "return cool_overflow_abort({});", line),
+                // A throw comes back from `setjmp` with 1
+                Terminator::Try { body, handler } => {
+                    emit!(self, "h{}.prev = cool_handlers;", i);
+                    emit!(self, "cool_handlers = &h{};", i);
+                    emit!(self, "if (setjmp(h{}.env)) goto {};", i, handler);
+                    if body != next {
+                        emit!(self, "goto {};", body);
+                    }
+                }
+                Terminator::Throw { value, line } => emit!(self, "return cool_throw({}, {});", value, line),
+                Terminator::Rethrow => emit!(self, "return cool_rethrow();"),
+            }
+        }
+        writeln!(
+            self.text,
+            "static Object *{}({}) {{\n{}}}\n",
+            function_name(function),
+            parameters(function, volatile),
+            self.body
+        )
+        .unwrap();
+    }
+
+    /// Attribute `index` of an object of `class`, as an lvalue.
+    fn field(&self, class: Symbol, object: Var, index: u32) -> String {
+        let (attr, _) = self.layout.class(class).attributes[index as usize];
+        format!("((struct C_{} *){})->f_{}", class, object, attr)
+    }
+
+    fn inst(&mut self, function: &Function, inst: &Inst) {
+        match inst {
+            // The literal -2147483648 is a negated long in C
+            Inst::Int { dst, value: i32::MIN } => emit!(self, "{} = INT32_MIN;", dst),
+            Inst::Int { dst, value } => emit!(self, "{} = {};", dst, value),
+            Inst::Bool { dst, value } => emit!(self, "{} = {};", dst, u8::from(*value)),
+            Inst::Str { dst, index } => emit!(self, "{} = &str_{}.header;", dst, index),
+            Inst::Void { dst } => emit!(self, "{} = NULL;", dst),
+            Inst::Copy { dst, src } => emit!(self, "{} = {};", dst, src),
+            Inst::Box { dst, src } => {
+                let constructor = if function.vars[src.0 as usize] == Ty::Int { "cool_int" } else { "cool_bool" };
+                emit!(self, "{} = {}({});", dst, constructor, src)
+            }
+            Inst::Unbox { dst, src } => emit!(self, "{} = cool_value({});", dst, src),
+            Inst::Unary { dst, op: UnaryOp::Neg, src } => emit!(self, "{} = cool_wrap(-(int64_t){});", dst, src),
+            Inst::Unary { dst, op: UnaryOp::Not, src } => emit!(self, "{} = !{};", dst, src),
+            Inst::Binary { dst, op, lhs, rhs } => {
+                let (op, arithmetic) = match op {
+                    BinaryOp::Add => ("+", true),
+                    BinaryOp::Sub => ("-", true),
+                    BinaryOp::Mul => ("*", true),
+                    BinaryOp::Div => ("/", true),
+                    BinaryOp::Lt => ("<", false),
+                    BinaryOp::Le => ("<=", false),
+                    BinaryOp::Eq => ("==", false),
+                };
+                if arithmetic {
+                    emit!(self, "{} = cool_wrap((int64_t){} {} {});", dst, lhs, op, rhs)
+                } else {
+                    emit!(self, "{} = {} {} {};", dst, lhs, op, rhs)
+                }
+            }
+            // Exact in 64 bits, where no operation on two words overflows
+            Inst::Overflows { dst, op, lhs, rhs } => {
+                let op = match op {
+                    BinaryOp::Add => "+",
+                    BinaryOp::Sub => "-",
+                    BinaryOp::Mul => "*",
+                    _ => "/",
+                };
+                let exact = format!("(int64_t){} {} {}", lhs, op, rhs);
+                emit!(self, "{} = cool_wrap({}) != {};", dst, exact, exact)
+            }
+            // Identical objects are equal; otherwise the runtime compares
+            // Int, String and Bool values
+            Inst::Equal { dst, lhs, rhs } => emit!(self, "{} = cool_equals({}, {});", dst, lhs, rhs),
+            Inst::IsVoid { dst, src } => emit!(self, "{} = {} == NULL;", dst, src),
+            Inst::New { dst, class } => emit!(self, "{} = new_{}();", dst, class),
+            Inst::NewLike { dst, object } => emit!(self, "{} = cool_class_new[{}->tag]();", dst, object),
+            Inst::GetField { dst, object, index } => {
+                let field = self.field(function.class, *object, *index);
+                emit!(self, "{} = {};", dst, field)
+            }
+            Inst::SetField { object, index, src } => {
+                let field = self.field(function.class, *object, *index);
+                emit!(self, "{} = {};", field, src)
+            }
+            Inst::TagIn { dst, object, first, last } => {
+                emit!(self, "{0} = {1}->tag >= {2} && {1}->tag <= {3};", dst, object, first, last)
+            }
+            Inst::Exception { dst } => emit!(self, "{} = cool_exception;", dst),
+            Inst::EndTry => emit!(self, "cool_handlers = cool_handlers->prev;"),
+            Inst::Call { dst, callee, args } => {
+                let callee = match callee {
+                    Callee::Static { class, method } => callee_name(*class, *method),
+                    Callee::Virtual { slot, .. } => {
+                        let params = vec!["Object *"; args.len()].join(", ");
+                        format!("((Object *(*)({}))({})->vtable[{}])", params, args[0], slot)
+                    }
+                };
+                let args: Vec<String> = args.iter().map(Var::to_string).collect();
+                emit!(self, "{} = {}({});", dst, callee, args.join(", "))
+            }
+        }
+    }
+}
+
+/// `s` as the contents of a C string literal. Octal escapes cannot run into
+/// the characters after them, and escaping `?` rules out trigraphs.
+fn escape(s: &str) -> String {
+    let mut out = String::new();
+    for byte in s.bytes() {
+        match byte {
+            b'"' | b'\\' | b'?' => write!(out, "\\{}", byte as char).unwrap(),
+            b' '..=b'~' => out.push(byte as char),
+            _ => write!(out, "\\{:03o}", byte).unwrap(),
+        }
+    }
+    out
+}
+
+/// The types every object shares and the built-in methods' prototypes.
+const HEADER: &str = r#"/* Generated by cool-rs */
+#include <setjmp.h>
+#include <stdint.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+
+/* Entries of a dispatch table, cast to the method's type to be called */
+typedef void (*cool_fn)(void);
+
+/* The start of every object */
+typedef struct Object {
+    const cool_fn *vtable;
+    int64_t tag;
+    /* In bytes, for copy */
+    int64_t size;
+} Object;
+
+/* Int and Bool objects */
+typedef struct {
+    Object header;
+    int32_t value;
+} cool_Int;
+
+/* Array objects, and the start of the objects of its subclasses */
+typedef struct {
+    Object header;
+    int64_t length;
+    Object **items;
+} cool_Array;
+
+/* The characters are NUL-terminated as well */
+typedef struct {
+    Object header;
+    int64_t length;
+    const char *chars;
+} cool_String;
+
+static Object *M6Object_abort(Object *self);
+static Object *M6Object_type_name(Object *self);
+static Object *M6Object_copy(Object *self);
+static Object *M2IO_out_string(Object *self, Object *s);
+static Object *M2IO_out_int(Object *self, Object *i);
+static Object *M2IO_in_string(Object *self);
+static Object *M2IO_in_int(Object *self);
+static Object *M6String_length(Object *self);
+static Object *M6String_concat(Object *self, Object *s);
+static Object *M6String_substr(Object *self, Object *i, Object *l);
+"#;
+
+/// The methods of the basic classes and the helpers the generated code
+/// calls. Strings are written with `fwrite`, so they may hold NUL
+/// characters read by `in_string`.
+const RUNTIME: &str = r#"static Object *cool_alloc(const cool_fn *vtable, int64_t tag, int64_t size) {
+    Object *object = calloc(1, (size_t)size);
+    if (object == NULL) {
+        fputs("Out of memory\n", stderr);
+        exit(1);
+    }
+    object->vtable = vtable;
+    object->tag = tag;
+    object->size = size;
+    return object;
+}
+
+static Object *cool_int(int32_t value) {
+    cool_Int *object = (cool_Int *)cool_alloc(vt_Int, cool_int_tag, sizeof(cool_Int));
+    object->value = value;
+    return &object->header;
+}
+
+static Object *cool_bool(int value) {
+    return value ? &bool_1.header : &bool_0.header;
+}
+
+static Object *cool_string(int64_t length, const char *chars) {
+    cool_String *object = (cool_String *)cool_alloc(vt_String, cool_string_tag, sizeof(cool_String));
+    object->length = length;
+    object->chars = chars;
+    return &object->header;
+}
+
+/* The value of an Int or Bool object */
+static int32_t cool_value(Object *object) {
+    return ((cool_Int *)object)->value;
+}
+
+/* Two's complement wrap-around, which C leaves to the implementation
+   for signed arithmetic */
+static int32_t cool_wrap(int64_t value) {
+    uint32_t bits = (uint32_t)value;
+    return bits <= INT32_MAX ? (int32_t)bits : -(int32_t)(~bits) - 1;
+}
+
+static int cool_equals(Object *a, Object *b) {
+    if (a == b) {
+        return 1;
+    }
+    if (a == NULL || b == NULL || a->tag != b->tag) {
+        return 0;
+    }
+    if (a->tag == cool_int_tag || a->tag == cool_bool_tag) {
+        return cool_value(a) == cool_value(b);
+    }
+    if (a->tag == cool_string_tag) {
+        cool_String *s = (cool_String *)a, *t = (cool_String *)b;
+        return s->length == t->length && memcmp(s->chars, t->chars, (size_t)s->length) == 0;
+    }
+    return 0;
+}
+
+static void cool_fail(const char *format, Object *class_name) {
+    fflush(stdout);
+    fprintf(stderr, format, ((cool_String *)class_name)->chars);
+    exit(1);
+}
+
+static Object *cool_case_abort(int line, Object *object) {
+    Object *class_name = cool_class_names[object->tag];
+    fflush(stdout);
+    fprintf(stderr, "[line %d] No 'case' branch for dynamic type '%s'\n", line, ((cool_String *)class_name)->chars);
+    exit(1);
+    return NULL;
+}
+
+static Object *cool_void_case_abort(int line) {
+    fflush(stdout);
+    fprintf(stderr, "[line %d] Case on void\n", line);
+    exit(1);
+    return NULL;
+}
+
+static Object *cool_division_abort(int line) {
+    fflush(stdout);
+    fprintf(stderr, "[line %d] Division by zero\n", line);
+    exit(1);
+    return NULL;
+}
+
+static Object *cool_overflow_abort(int line) {
+    fflush(stdout);
+    fprintf(stderr, "[line %d] Integer overflow\n", line);
+    exit(1);
+    return NULL;
+}
+
+static Object *cool_dispatch_abort(int line, const char *method) {
+    fflush(stdout);
+    fprintf(stderr, "[line %d] Dispatch of '%s' on void\n", line, method);
+    exit(1);
+    return NULL;
+}
+
+static Object *M6Object_abort(Object *self) {
+    cool_fail("Abort called from class %s\n", cool_class_names[self->tag]);
+    return NULL;
+}
+
+static Object *M6Object_type_name(Object *self) {
+    return cool_class_names[self->tag];
+}
+
+static Object *M6Object_copy(Object *self) {
+    Object *copy = cool_alloc(self->vtable, self->tag, self->size);
+    memcpy(copy, self, (size_t)self->size);
+    /* A copied array has elements of its own */
+    if (self->tag >= cool_array_first && self->tag <= cool_array_last) {
+        cool_Array *array = (cool_Array *)copy;
+        Object **items = calloc((size_t)array->length + 1, sizeof(Object *));
+        if (array->length > 0) {
+            memcpy(items, array->items, (size_t)array->length * sizeof(Object *));
+        }
+        array->items = items;
+    }
+    return copy;
+}
+
+static Object *M2IO_out_string(Object *self, Object *s) {
+    cool_String *string = (cool_String *)s;
+    fwrite(string->chars, 1, (size_t)string->length, stdout);
+    return self;
+}
+
+static Object *M2IO_out_int(Object *self, Object *i) {
+    printf("%d", (int)cool_value(i));
+    return self;
+}
+
+static Object *M2IO_in_string(Object *self) {
+    int64_t length = 0, capacity = 16;
+    char *buffer = malloc((size_t)capacity);
+    int c;
+    (void)self;
+    fflush(stdout);
+    while ((c = getchar()) != EOF && c != '\n') {
+        if (length + 1 >= capacity) {
+            capacity *= 2;
+            buffer = realloc(buffer, (size_t)capacity);
+        }
+        buffer[length++] = (char)c;
+    }
+    /* A line ending in \r\n is a line too */
+    if (c == '\n' && length > 0 && buffer[length - 1] == '\r') {
+        length--;
+    }
+    buffer[length] = '\0';
+    return cool_string(length, buffer);
+}
+
+static Object *M2IO_in_int(Object *self) {
+    cool_String *line = (cool_String *)M2IO_in_string(self);
+    return cool_int((int32_t)strtol(line->chars, NULL, 10));
+}
+
+static Object *M6String_length(Object *self) {
+    return cool_int((int32_t)((cool_String *)self)->length);
+}
+
+static Object *M6String_concat(Object *self, Object *s) {
+    cool_String *a = (cool_String *)self, *b = (cool_String *)s;
+    char *chars = malloc((size_t)(a->length + b->length + 1));
+    memcpy(chars, a->chars, (size_t)a->length);
+    memcpy(chars + a->length, b->chars, (size_t)b->length + 1);
+    return cool_string(a->length + b->length, chars);
+}
+
+static Object *M6String_substr(Object *self, Object *i, Object *l) {
+    cool_String *string = (cool_String *)self;
+    int64_t start = cool_value(i), count = cool_value(l);
+    char *chars;
+    if (start < 0 || count < 0 || start + count > string->length) {
+        fflush(stdout);
+        fprintf(stderr, "Substring out of range: substr(%d, %d) of a string of length %d\n", (int)start, (int)count,
+                (int)string->length);
+        exit(1);
+    }
+    chars = malloc((size_t)count + 1);
+    memcpy(chars, string->chars + start, (size_t)count);
+    chars[count] = '\0';
+    return cool_string(count, chars);
+}
+"#;
+
+/// The methods of `Array`, appended to `RUNTIME` when the program has
+/// arrays. Errors are reported as by `--run`, without a line, like those of
+/// `substr`.
+const ARRAY_RUNTIME: &str = r#"static Object *M5Array_init(Object *self, Object *size) {
+    cool_Array *array = (cool_Array *)self;
+    int32_t length = cool_value(size);
+    if (length < 0) {
+        fflush(stdout);
+        fprintf(stderr, "Negative array size %d\n", (int)length);
+        exit(1);
+    }
+    array->length = length;
+    array->items = calloc((size_t)length + 1, sizeof(Object *));
+    return self;
+}
+
+static Object *M5Array_length(Object *self) {
+    return cool_int((int32_t)((cool_Array *)self)->length);
+}
+
+/* The element at index i, which must be in bounds */
+static Object **cool_array_item(Object *self, Object *i) {
+    cool_Array *array = (cool_Array *)self;
+    int32_t index = cool_value(i);
+    if (index < 0 || index >= array->length) {
+        fflush(stdout);
+        fprintf(stderr, "Array index %d out of bounds for length %d\n", (int)index, (int)array->length);
+        exit(1);
+    }
+    return &array->items[index];
+}
+
+static Object *M5Array_get(Object *self, Object *i) {
+    return *cool_array_item(self, i);
+}
+
+static Object *M5Array_set(Object *self, Object *i, Object *v) {
+    *cool_array_item(self, i) = v;
+    return v;
+}
+"#;
+
+/// The handlers and `throw` of `--ext exceptions`, appended to `RUNTIME`
+/// when the program has them. A `try` pushes a handler, whose `setjmp`
+/// a throw returns to with `longjmp`; its body pops it when it is done.
+const EXCEPTION_RUNTIME: &str = r#"typedef struct cool_handler {
+    jmp_buf env;
+    struct cool_handler *prev;
+} cool_handler;
+
+/* The innermost handler, and what was last thrown and where */
+static cool_handler *cool_handlers;
+static Object *cool_exception;
+static int cool_exception_line;
+
+static Object *cool_rethrow(void) {
+    cool_handler *handler = cool_handlers;
+    if (handler == NULL) {
+        Object *class_name = cool_class_names[cool_exception->tag];
+        fflush(stdout);
+        fprintf(stderr, "[line %d] Uncaught exception of class %s\n", cool_exception_line,
+                ((cool_String *)class_name)->chars);
+        exit(1);
+    }
+    cool_handlers = handler->prev;
+    longjmp(handler->env, 1);
+    return NULL;
+}
+
+static Object *cool_throw(Object *value, int line) {
+    if (value == NULL) {
+        fflush(stdout);
+        fprintf(stderr, "[line %d] Throw of void\n", line);
+        exit(1);
+    }
+    cool_exception = value;
+    cool_exception_line = line;
+    return cool_rethrow();
+}
+"#;
+
+#[cfg(test)]
+mod tests {
+    use std::process::Command;
+
+    use super::*;
+    use crate::semantic::type_checker::annotate;
+
+    fn generate_for(source: &str) -> String {
+        let classes = crate::parse_program(source).unwrap();
+        let ctx = AnalysisContext::new(crate::prelude(), &classes);
+        generate(&ctx, &annotate(&ctx), Overflow::Wrap).unwrap()
+    }
+
+    #[test]
+    fn test_generate() {
+        let c = generate_for("class Main inherits IO {\n  n : Int <- 3;\n  main() : Object { out_int(n + 1) };\n};");
+        // Main's table inherits IO's methods and is laid out like IO's
+        let table = c.lines().find(|line| line.starts_with("static const cool_fn vt_Main[] = ")).unwrap();
+        assert!(table.contains(
+            "{(cool_fn)M6Object_abort, (cool_fn)M6Object_type_name, (cool_fn)M6Object_copy, (cool_fn)M2IO_out_string, "
+        ));
+        assert!(c.contains("struct C_Main {\n    Object header;\n    Object *f_n;\n};\n"));
+        let init = c.split("static Object *init_Main(Object *v0) {\n").nth(1).unwrap();
+        assert!(init.contains(" = init_IO(v0);\n"), "{}", init);
+        assert!(init.contains("((struct C_Main *)v0)->f_n = "), "{}", init);
+        // Dispatch on self goes through the table; out_int is entry 4
+        let main = c.split("static Object *M4Main_main(Object *v0) {\n").nth(1).unwrap();
+        assert!(main.contains("((Object *(*)(Object *, Object *))(v0)->vtable[4])(v0, "), "{}", main);
+        assert!(main.contains(" = cool_wrap((int64_t)"), "{}", main);
+        assert_eq!(escape("a\"?\\\n"), "a\\\"\\?\\\\\\012");
+    }
+
+    /// Compiled with the system C compiler, the examples print what the
+    /// interpreter prints. Skipped where there is no `cc`.
+    #[test]
+    fn test_examples_compile() {
+        if Command::new("cc").arg("--version").output().is_err() {
+            return;
+        }
+        let dir = std::env::temp_dir().join(format!("cool-rs-c-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let examples = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("examples");
+        for entry in std::fs::read_dir(examples).unwrap() {
+            let path = entry.unwrap().path();
+            if path.extension().is_none_or(|ext| ext != "cl") {
+                continue;
+            }
+            let source = std::fs::read_to_string(&path).unwrap();
+            let classes = crate::parse_program(&source).unwrap();
+            let ctx = AnalysisContext::new(crate::prelude(), &classes);
+            let (c_file, exe) = (dir.join("program.c"), dir.join("program"));
+            std::fs::write(&c_file, generate(&ctx, &annotate(&ctx), Overflow::Wrap).unwrap()).unwrap();
+            let cc = Command::new("cc")
+                .args(["-std=c99", "-pedantic", "-Wall", "-Wno-unused", "-Werror", "-o"])
+                .arg(&exe)
+                .arg(&c_file)
+                .output()
+                .unwrap();
+            assert!(cc.status.success(), "{}: {}", path.display(), String::from_utf8_lossy(&cc.stderr));
+
+            let input = "12\n3\n";
+            let mut child = Command::new(&exe)
+                .stdin(std::process::Stdio::piped())
+                .stdout(std::process::Stdio::piped())
+                .spawn()
+                .unwrap();
+            // A program that reads nothing may have exited already
+            std::io::Write::write_all(&mut child.stdin.take().unwrap(), input.as_bytes()).ok();
+            let output = child.wait_with_output().unwrap();
+            let mut expected = Vec::new();
+            crate::interp::run(&ctx, Overflow::Wrap, &mut input.as_bytes(), &mut expected).unwrap();
+            assert_eq!(String::from_utf8_lossy(&output.stdout), String::from_utf8_lossy(&expected), "{}", path.display());
+        }
+        std::fs::remove_dir_all(dir).ok();
+    }
+
+    /// A dispatch on void stops the program with the interpreter's message.
+    /// Skipped where there is no `cc`.
+    #[test]
+    fn test_dispatch_on_void() {
+        if Command::new("cc").arg("--version").output().is_err() {
+            return;
+        }
+        let source = "class Main inherits IO {\n    next : Main;\n    main() : Object {\n        \
+                      { out_int(1); next.main(); }\n    };\n};\n";
+        let c = generate_for(source);
+        let dir = std::env::temp_dir().join(format!("cool-rs-c-void-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let (c_file, exe) = (dir.join("program.c"), dir.join("program"));
+        std::fs::write(&c_file, c).unwrap();
+        let cc = Command::new("cc").args(["-std=c99", "-o"]).arg(&exe).arg(&c_file).output().unwrap();
+        assert!(cc.status.success(), "{}", String::from_utf8_lossy(&cc.stderr));
+        let output = Command::new(&exe).output().unwrap();
+        assert_eq!(output.status.code(), Some(1));
+        assert_eq!(String::from_utf8_lossy(&output.stdout), "1");
+        assert_eq!(String::from_utf8_lossy(&output.stderr), "[line 4] Dispatch of 'main' on void\n");
+        std::fs::remove_dir_all(dir).ok();
+    }
+
+    #[test]
+    fn test_case_aborts() {
+        if Command::new("cc").arg("--version").output().is_err() {
+            return;
+        }
+        let dir = std::env::temp_dir().join(format!("cool-rs-c-case-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let (c_file, exe) = (dir.join("program.c"), dir.join("program"));
+        let run = |scrutinee: &str| {
+            let source = format!(
+                "class Main {{\n    next : Main;\n    main() : Object {{\n        case {} of b : Bool => b; esac\n    }};\n}};\n",
+                scrutinee
+            );
+            std::fs::write(&c_file, generate_for(&source)).unwrap();
+            let cc = Command::new("cc").args(["-std=c99", "-o"]).arg(&exe).arg(&c_file).output().unwrap();
+            assert!(cc.status.success(), "{}", String::from_utf8_lossy(&cc.stderr));
+            let output = Command::new(&exe).output().unwrap();
+            assert_eq!(output.status.code(), Some(1));
+            String::from_utf8_lossy(&output.stderr).into_owned()
+        };
+        assert_eq!(run("next"), "[line 4] Case on void\n");
+        assert_eq!(run("self"), "[line 4] No 'case' branch for dynamic type 'Main'\n");
+        std::fs::remove_dir_all(dir).ok();
+    }
+
+    /// With `--ext arrays`, arrays and their subclasses work as in the
+    /// interpreter. Skipped where there is no `cc`.
+    #[test]
+    fn test_arrays() {
+        if Command::new("cc").arg("--version").output().is_err() {
+            return;
+        }
+        let dir = std::env::temp_dir().join(format!("cool-rs-c-arrays-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let (c_file, exe) = (dir.join("program.c"), dir.join("program"));
+        let source = "class Stack inherits Array {\n    top : Int;\n    push(x : Object) : Stack { { set(top, x); top <- top + 1; self; } };\n};\n\
+                      class Main inherits IO {\n    main() : Object {\n        \
+                      let s : Stack <- (new Stack).init(3), t : Stack in {\n            \
+                      s.push(1).push(\"two\");\n            t <- s.copy();\n            t.set(0, 10);\n            \
+                      case s.get(0) of i : Int => out_int(i); esac;\n            \
+                      case t.get(0) of i : Int => out_int(i); esac;\n            \
+                      case s.get(1) of s : String => out_string(s); esac;\n            \
+                      out_int(s.length());\n            s.get(3);\n        }\n    };\n};\n";
+        let extensions: Extensions = [Extension::Arrays].into_iter().collect();
+        let classes = crate::parse_program_with(source, &extensions).unwrap();
+        let ctx = AnalysisContext::new(crate::prelude_with(&extensions), &classes);
+        std::fs::write(&c_file, generate(&ctx, &annotate(&ctx), Overflow::Wrap).unwrap()).unwrap();
+        let cc = Command::new("cc")
+            .args(["-std=c99", "-pedantic", "-Wall", "-Wno-unused", "-Werror", "-o"])
+            .arg(&exe)
+            .arg(&c_file)
+            .output()
+            .unwrap();
+        assert!(cc.status.success(), "{}", String::from_utf8_lossy(&cc.stderr));
+        let output = Command::new(&exe).output().unwrap();
+        let mut expected = Vec::new();
+        let error = crate::interp::run(&ctx, Overflow::Wrap, &mut "".as_bytes(), &mut expected).unwrap_err();
+        assert_eq!(String::from_utf8_lossy(&output.stdout), String::from_utf8_lossy(&expected));
+        assert_eq!(String::from_utf8_lossy(&expected), "110two3");
+        assert_eq!(output.status.code(), Some(1));
+        assert!(error.to_string().contains("Array index 3 out of bounds for length 3"), "{}", error);
+        assert_eq!(String::from_utf8_lossy(&output.stderr), "Array index 3 out of bounds for length 3\n");
+        std::fs::remove_dir_all(dir).ok();
+    }
+
+    /// With `--ext exceptions`, what is thrown unwinds to the same handlers
+    /// as in the interpreter, and an uncaught one stops the program with the
+    /// same error. Skipped where there is no `cc`.
+    #[test]
+    fn test_exceptions() {
+        if Command::new("cc").arg("--version").output().is_err() {
+            return;
+        }
+        let dir = std::env::temp_dir().join(format!("cool-rs-c-exceptions-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let (c_file, exe) = (dir.join("program.c"), dir.join("program"));
+        let source = "class Oops { n : Int <- 7; n() : Int { n }; };\n\
+                      class Main inherits IO {\n    \
+                      fail(x : Object) : Int { { out_string(\"fail \"); throw x; 0; } };\n    \
+                      main() : Object {\n        \
+                      let count : Int in {\n            \
+                      out_int(try fail(new Oops) catch o : Oops => o.n() end);\n            \
+                      out_int(try { count <- count + 1; fail(3) + 1; } catch i : Int => i + count end);\n            \
+                      out_int(try try fail(\"inner\") catch o : Oops => 0 end catch s : String => s.length() end);\n            \
+                      out_int(try 5 catch o : Object => 6 end);\n            \
+                      try fail(true) catch o : Oops => 0 end;\n        \
+                      }\n    };\n};\n";
+        let extensions: Extensions = [Extension::Exceptions].into_iter().collect();
+        let classes = crate::parse_program_with(source, &extensions).unwrap();
+        let ctx = AnalysisContext::new(crate::prelude_with(&extensions), &classes);
+        std::fs::write(&c_file, generate(&ctx, &annotate(&ctx), Overflow::Wrap).unwrap()).unwrap();
+        let cc = Command::new("cc")
+            .args(["-std=c99", "-pedantic", "-Wall", "-Wno-unused", "-Werror", "-o"])
+            .arg(&exe)
+            .arg(&c_file)
+            .output()
+            .unwrap();
+        assert!(cc.status.success(), "{}", String::from_utf8_lossy(&cc.stderr));
+        let output = Command::new(&exe).output().unwrap();
+        let mut expected = Vec::new();
+        let error = crate::interp::run(&ctx, Overflow::Wrap, &mut "".as_bytes(), &mut expected).unwrap_err();
+        assert_eq!(String::from_utf8_lossy(&expected), "fail 7fail 4fail 55fail ");
+        assert_eq!(String::from_utf8_lossy(&output.stdout), String::from_utf8_lossy(&expected));
+        assert_eq!(output.status.code(), Some(1));
+        assert_eq!(String::from_utf8_lossy(&output.stderr), format!("[line {}] {}\n", error.line, error.message));
+        assert_eq!(error.message, "Uncaught exception of class Bool");
+        std::fs::remove_dir_all(dir).ok();
+    }
+
+    #[test]
+    fn test_overflow_traps() {
+        if Command::new("cc").arg("--version").output().is_err() {
+            return;
+        }
+        let dir = std::env::temp_dir().join(format!("cool-rs-c-overflow-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let (c_file, exe) = (dir.join("program.c"), dir.join("program"));
+        let source = "class Main inherits IO {\n    n : Int <- 2147483647;\n    main() : Object {\n        \
+                      { out_int(n * 1); out_int(n + 1); }\n    };\n};\n";
+        let classes = crate::parse_program(source).unwrap();
+        let ctx = AnalysisContext::new(crate::prelude(), &classes);
+        let run = |overflow: Overflow| {
+            std::fs::write(&c_file, generate(&ctx, &annotate(&ctx), overflow).unwrap()).unwrap();
+            let cc = Command::new("cc").args(["-std=c99", "-o"]).arg(&exe).arg(&c_file).output().unwrap();
+            assert!(cc.status.success(), "{}", String::from_utf8_lossy(&cc.stderr));
+            Command::new(&exe).output().unwrap()
+        };
+        let wrapped = run(Overflow::Wrap);
+        assert_eq!(String::from_utf8_lossy(&wrapped.stdout), "2147483647-2147483648");
+        let trapped = run(Overflow::Trap);
+        assert_eq!(trapped.status.code(), Some(1));
+        assert_eq!(String::from_utf8_lossy(&trapped.stdout), "2147483647");
+        assert_eq!(String::from_utf8_lossy(&trapped.stderr), "[line 4] Integer overflow\n");
+        std::fs::remove_dir_all(dir).ok();
+    }
+}
@@ -0,0 +1,106 @@
+//! Object layout: class tags, attribute offsets and dispatch tables.
+
+use std::collections::HashMap;
+
+use crate::ast::{Class, Feature, VarDecl};
+use crate::symbol::{sym, Symbol};
+
+/// Words before the first attribute: class tag, size and dispatch table.
+pub(crate) const HEADER_WORDS: usize = 3;
+
+#[derive(Debug)]
+pub(crate) struct ClassLayout<'a> {
+    pub class: &'a Class,
+    /// Tags are handed out in a preorder walk of the inheritance tree, so
+    /// a class and its descendants have the tags `tag..=last_descendant`
+    pub tag: usize,
+    pub last_descendant: usize,
+    /// Every attribute including inherited ones, in object order, with its type
+    pub attributes: Vec<(Symbol, Symbol)>,
+    /// Every method including inherited ones, in dispatch table order, with
+    /// the class whose definition is used
+    pub methods: Vec<(Symbol, Symbol)>,
+}
+
+#[derive(Debug)]
+pub(crate) struct Layout<'a> {
+    /// Indexed by tag
+    pub classes: Vec<ClassLayout<'a>>,
+    by_name: HashMap<Symbol, usize>,
+}
+
+impl<'a> Layout<'a> {
+    /// Lay out `classes`, which must form a well-formed tree rooted at `Object`.
+    pub fn new(classes: &[&'a Class]) -> Self {
+        let mut children: HashMap<Symbol, Vec<&'a Class>> = HashMap::new();
+        let mut root = None;
+        for &class in classes {
+            if class.name == sym::OBJECT {
+                root = Some(class);
+            } else {
+                children.entry(class.inherits.unwrap_or(sym::OBJECT)).or_default().push(class);
+            }
+        }
+
+        let mut layout = Layout { classes: Vec::new(), by_name: HashMap::new() };
+        let root = root.expect("Object is always defined");
+        layout.visit(root, None, &children);
+        layout
+    }
+
+    fn visit(&mut self, class: &'a Class, parent: Option<usize>, children: &HashMap<Symbol, Vec<&'a Class>>) {
+        let tag = self.classes.len();
+        let (mut attributes, mut methods) = match parent {
+            Some(p) => (self.classes[p].attributes.clone(), self.classes[p].methods.clone()),
+            None => (Vec::new(), Vec::new()),
+        };
+        for feature in &class.feature_list {
+            match feature {
+                Feature::Attribute(VarDecl { oid, tid, .. }) => attributes.push((*oid, *tid)),
+                // An override keeps the inherited slot
+                Feature::Method(name, ..) => match methods.iter_mut().find(|(m, _)| m == name) {
+                    Some(slot) => slot.1 = class.name,
+                    None => methods.push((*name, class.name)),
+                },
+            }
+        }
+        self.classes.push(ClassLayout { class, tag, last_descendant: tag, attributes, methods });
+        self.by_name.insert(class.name, tag);
+
+        for &child in children.get(&class.name).into_iter().flatten() {
+            self.visit(child, Some(tag), children);
+        }
+        self.classes[tag].last_descendant = self.classes.len() - 1;
+    }
+
+    pub fn class(&self, name: Symbol) -> &ClassLayout<'a> {
+        &self.classes[self.by_name[&name]]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_layout() {
+        let source = "class A { x : Int; f() : Int { x }; g() : Int { 0 }; };\n\
+                      class B inherits A { y : Bool; g() : Int { 1 }; h() : Int { 2 }; };\n\
+                      class C { };";
+        let classes = crate::parse_program(source).unwrap();
+        let all: Vec<&Class> = crate::prelude().iter().chain(&classes).collect();
+        let layout = Layout::new(&all);
+
+        let a = layout.class(Symbol::intern("A"));
+        let b = layout.class(Symbol::intern("B"));
+        assert_eq!((b.tag, a.last_descendant), (a.tag + 1, b.tag));
+        assert_eq!(layout.class(sym::OBJECT).last_descendant, layout.classes.len() - 1);
+
+        let names = |methods: &[(Symbol, Symbol)]| {
+            methods.iter().map(|(m, c)| format!("{}.{}", c, m)).collect::<Vec<_>>()
+        };
+        assert_eq!(names(&b.methods), ["Object.abort", "Object.type_name", "Object.copy", "A.f", "B.g", "B.h"]);
+        let attributes: Vec<&str> = b.attributes.iter().map(|(a, _)| a.as_str()).collect();
+        assert_eq!(attributes, ["x", "y"]);
+    }
+}
@@ -0,0 +1,1170 @@
+//! LLVM IR code generation (`--emit llvm-ir` and `--emit object`), behind
+//! the `llvm` cargo feature.
+//!
+//! Lowers a program that passed semantic analysis to textual LLVM IR that
+//! needs nothing but the C library: the built-in methods are written in IR
+//! in `RUNTIME`, on top of `printf`, `getchar` and `calloc`, and `main`
+//! evaluates `(new Main).main()`. Objects use the class tags and method
+//! order of the MIPS backend's `Layout`, with every slot 8 bytes wide:
+//!
+//! * An object is a pointer to its class's vtable, its class tag, its size
+//!   in bytes, then its attributes, inherited ones first. A method keeps its
+//!   vtable index in every descendant, so dynamic dispatch is an indexed load.
+//! * `Int`, `Bool` and `String` values are boxed in immutable objects whose
+//!   first slot after the header holds the value (for a string, its length
+//!   and then its NUL-terminated characters). Literals are constant objects;
+//!   arithmetic and the string methods allocate new ones.
+//! * Every method takes `self` and its arguments as `ptr` and returns a
+//!   `ptr`; void is `null`.
+//!
+//! The IR uses opaque pointers, which LLVM 14 only reads with
+//! `-opaque-pointers`. Memory is never freed. Arithmetic is done in 64 bits
+//! and wrapped or checked as `--int-overflow` says. A dispatch or `case` on
+//! void, a `case` with no branch for its value, a division by zero and an
+//! overflow that traps are reported with their line, as by `--run`. As in the MIPS backend, the `arrays`,
+//! `exceptions` and `natives` extensions have no code generation.
+
+use std::fmt::Write;
+use std::io::Write as _;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+use eyre::{bail, Result, WrapErr};
+
+use super::layout::Layout;
+use super::never_void::NeverVoid;
+use super::{check_supported, is_basic, may_be_void, may_be_zero};
+use crate::arith::Overflow;
+use crate::ast::{
+    CaseBranch, Class, ComparisonOperator, Expr, Feature, MathOperator, TypedExpr, UnaryOperator,
+    VarDecl,
+};
+use crate::ext::Extensions;
+use crate::semantic::context::AnalysisContext;
+use crate::symbol::{sym, Symbol};
+
+/// Slots before the first attribute: vtable, class tag and size.
+const HEADER_SLOTS: usize = 3;
+
+/// Size in bytes of `Int` and `Bool` objects, and of `String` ones.
+const INT_SIZE: usize = 8 * (HEADER_SLOTS + 1);
+const STRING_SIZE: usize = 8 * (HEADER_SLOTS + 2);
+
+/// LLVM IR for the program of `ctx`, which must have checked cleanly, to
+/// run with `overflow`. `classes` are its classes as annotated by the type
+/// checker.
+pub fn generate(ctx: &AnalysisContext<'_>, classes: &[Class], overflow: Overflow) -> Result<String> {
+    check_supported(ctx, classes, &Extensions::default())?;
+    let all: Vec<&Class> = ctx.builtins.iter().chain(classes).collect();
+    let layout = Layout::new(&all);
+    let mut generator = Generator {
+        never_void: NeverVoid::new(&layout),
+        layout,
+        overflow,
+        text: String::new(),
+        body: String::new(),
+        allocas: String::new(),
+        strings: Vec::new(),
+        ints: Vec::new(),
+        temps: 0,
+        labels: 0,
+        block: String::new(),
+        class: sym::OBJECT,
+        scope: Vec::new(),
+        initializer: false,
+    };
+    // The class name table needs these whatever the program uses
+    let names: Vec<Symbol> = generator.layout.classes.iter().map(|c| c.class.name).collect();
+    for name in names {
+        generator.string_const(name.as_str());
+    }
+    generator.text();
+    let data = generator.data();
+    Ok(format!("; Generated by cool-rs\n\n{}\n{}\n{}", data, RUNTIME, generator.text))
+}
+
+/// The oldest LLVM whose `llc` reads the IR: LLVM 14 with `-opaque-pointers`.
+const MIN_LLVM: u32 = 14;
+
+/// Compile `ir` to a native object file at `path` with `llc`, which must be
+/// on the `PATH` and from LLVM 14 or later. Linking the object with `cc`
+/// gives an executable.
+pub fn write_object(ir: &str, path: &Path) -> Result<()> {
+    let version = Command::new("llc").arg("--version").output().wrap_err("Failed to run llc")?;
+    let major = llvm_major(&String::from_utf8_lossy(&version.stdout));
+
+    let mut llc = Command::new("llc");
+    match major {
+        Some(major) if major < MIN_LLVM => bail!("llc is from LLVM {}, but the IR needs LLVM {} or later", major, MIN_LLVM),
+        // Opaque pointers are the default from LLVM 15 on
+        Some(14) => {
+            llc.arg("-opaque-pointers");
+        }
+        _ => {}
+    }
+    // Position-independent code links into the PIE executables that
+    // compilers build by default
+    llc.args(["-filetype=obj", "-relocation-model=pic", "-o"]).arg(path);
+    let mut child = llc
+        .stdin(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .wrap_err("Failed to run llc")?;
+    child.stdin.take().unwrap().write_all(ir.as_bytes()).wrap_err("Failed to run llc")?;
+    let output = child.wait_with_output().wrap_err("Failed to run llc")?;
+    if !output.status.success() {
+        bail!("llc failed: {}", String::from_utf8_lossy(&output.stderr).trim());
+    }
+    Ok(())
+}
+
+/// The major version in the output of `llc --version`, such as `14` in
+/// `Debian LLVM version 14.0.6`.
+fn llvm_major(version: &str) -> Option<u32> {
+    let number = version.split("LLVM version ").nth(1)?;
+    number.split('.').next()?.trim().parse().ok()
+}
+
+/// Where the value of a name lives.
+#[derive(Debug, Clone)]
+enum Location {
+    SelfObject,
+    /// Slot of an attribute of `self`
+    Attribute(usize),
+    /// Stack slot allocated in the entry block
+    Local(String),
+}
+
+struct Generator<'a> {
+    layout: Layout<'a>,
+    overflow: Overflow,
+    /// Finished function definitions
+    text: String,
+    /// Instructions of the function being emitted
+    body: String,
+    /// Its stack slots, which go in its entry block
+    allocas: String,
+    /// Constants, numbered by position
+    strings: Vec<String>,
+    ints: Vec<i32>,
+    /// Value and block numbers, restarting in every function
+    temps: usize,
+    labels: usize,
+    /// Block that instructions are being added to
+    block: String,
+    /// Class whose code is being emitted
+    class: Symbol,
+    /// Formals and locals in scope, innermost last
+    scope: Vec<(Symbol, Location)>,
+    never_void: NeverVoid,
+    /// Whether an initializer is being emitted, which may read attributes
+    /// before they are set
+    initializer: bool,
+}
+
+/// One instruction, indented.
+macro_rules! emit {
+    ($g:expr, $($arg:tt)*) => {
+        writeln!($g.body, "  {}", format_args!($($arg)*)).unwrap()
+    };
+}
+
+impl<'a> Generator<'a> {
+    fn temp(&mut self) -> String {
+        self.temps += 1;
+        format!("%t{}", self.temps)
+    }
+
+    fn label(&mut self) -> String {
+        self.labels += 1;
+        format!("L{}", self.labels)
+    }
+
+    /// Continue in a new block; the previous one must have ended in a terminator.
+    fn start_block(&mut self, label: &str) {
+        writeln!(self.body, "{}:", label).unwrap();
+        self.block = label.to_string();
+    }
+
+    /// `lhs op rhs` on two `i32`s, computed in 64 bits, where it cannot
+    /// overflow, and then wrapped or checked.
+    fn arithmetic(&mut self, op: &str, lhs: &str, rhs: &str, line: usize) -> String {
+        let (a, b, wide, value) = (self.temp(), self.temp(), self.temp(), self.temp());
+        emit!(self, "{} = sext i32 {} to i64", a, lhs);
+        emit!(self, "{} = sext i32 {} to i64", b, rhs);
+        emit!(self, "{} = {} i64 {}, {}", wide, op, a, b);
+        emit!(self, "{} = trunc i64 {} to i32", value, wide);
+        if self.overflow == Overflow::Trap {
+            let (exact, overflows) = (self.temp(), self.temp());
+            let (abort, fits) = (self.label(), self.label());
+            emit!(self, "{} = sext i32 {} to i64", exact, value);
+            emit!(self, "{} = icmp ne i64 {}, {}", overflows, exact, wide);
+            emit!(self, "br i1 {}, label %{}, label %{}", overflows, abort, fits);
+            self.start_block(&abort);
+            emit!(self, "call void @cool_overflow_abort(i32 {})", line);
+            emit!(self, "unreachable");
+            self.start_block(&fits);
+        }
+        value
+    }
+
+    fn string_const(&mut self, s: &str) -> String {
+        let index = match self.strings.iter().position(|t| t == s) {
+            Some(index) => index,
+            None => {
+                self.strings.push(s.to_string());
+                self.strings.len() - 1
+            }
+        };
+        format!("@string.{}", index)
+    }
+
+    fn int_const(&mut self, i: i32) -> String {
+        let index = match self.ints.iter().position(|&j| j == i) {
+            Some(index) => index,
+            None => {
+                self.ints.push(i);
+                self.ints.len() - 1
+            }
+        };
+        format!("@int.{}", index)
+    }
+
+    fn tag(&self, class: Symbol) -> usize {
+        self.layout.class(class).tag
+    }
+
+    // ---- Data -------------------------------------------------------------
+
+    fn data(&mut self) -> String {
+        let mut out = String::new();
+        for (name, class) in [("_int_tag", sym::INT), ("_bool_tag", sym::BOOL), ("_string_tag", sym::STRING)] {
+            writeln!(out, "@{} = constant i64 {}", name, self.tag(class)).unwrap();
+        }
+
+        let (int_tag, bool_tag, string_tag) = (self.tag(sym::INT), self.tag(sym::BOOL), self.tag(sym::STRING));
+        for (i, s) in self.strings.iter().enumerate() {
+            writeln!(out, "@string.{}.chars = private unnamed_addr constant [{} x i8] c\"{}\\00\"", i, s.len() + 1, escape(s))
+                .unwrap();
+            writeln!(
+                out,
+                "@string.{i} = private constant {{ ptr, i64, i64, i64, ptr }} \
+                 {{ ptr @String_vtable, i64 {string_tag}, i64 {STRING_SIZE}, i64 {}, ptr @string.{i}.chars }}",
+                s.len(),
+            )
+            .unwrap();
+        }
+        for (i, value) in self.ints.iter().enumerate() {
+            writeln!(
+                out,
+                "@int.{i} = private constant {{ ptr, i64, i64, i32 }} {{ ptr @Int_vtable, i64 {int_tag}, i64 {INT_SIZE}, i32 {value} }}"
+            )
+            .unwrap();
+        }
+        for value in 0..2 {
+            writeln!(
+                out,
+                "@bool.{value} = private constant {{ ptr, i64, i64, i32 }} {{ ptr @Bool_vtable, i64 {bool_tag}, i64 {INT_SIZE}, i32 {value} }}"
+            )
+            .unwrap();
+        }
+
+        for class in &self.layout.classes {
+            let methods: Vec<String> =
+                class.methods.iter().map(|(method, owner)| format!("ptr @{}.{}", owner, method)).collect();
+            writeln!(out, "@{}_vtable = constant [{} x ptr] [{}]", class.class.name, methods.len(), methods.join(", "))
+                .unwrap();
+        }
+        // Indexed by tag, for `type_name` and `new SELF_TYPE`
+        let names: Vec<String> = self
+            .layout
+            .classes
+            .iter()
+            .map(|c| format!("ptr @string.{}", self.strings.iter().position(|s| s == c.class.name.as_str()).unwrap()))
+            .collect();
+        writeln!(out, "@class_names = constant [{} x ptr] [{}]", names.len(), names.join(", ")).unwrap();
+        let news: Vec<String> = self.layout.classes.iter().map(|c| format!("ptr @{}_new", c.class.name)).collect();
+        writeln!(out, "@class_new = constant [{} x ptr] [{}]", news.len(), news.join(", ")).unwrap();
+        out
+    }
+
+    // ---- Text -------------------------------------------------------------
+
+    fn text(&mut self) {
+        let classes: Vec<(&'a Class, Option<Symbol>)> = self
+            .layout
+            .classes
+            .iter()
+            .map(|c| (c.class, (c.class.name != sym::OBJECT).then(|| c.class.inherits.unwrap_or(sym::OBJECT))))
+            .collect();
+        for &(class, parent) in &classes {
+            self.new_object(class);
+            self.init(class, parent);
+        }
+        for &(class, _) in &classes {
+            // The runtime implements the basic classes' methods
+            if is_basic(class.name) {
+                continue;
+            }
+            for feature in &class.feature_list {
+                if let Feature::Method(name, args, _, body, _) = feature {
+                    let formals: Vec<Symbol> = args.iter().map(|a| a.id).collect();
+                    self.method(class.name, *name, &formals, body);
+                }
+            }
+        }
+
+        let (_, owner) = *self.layout.class(sym::MAIN_CLASS).methods.iter().find(|(m, _)| *m == sym::MAIN_METHOD).unwrap();
+        writeln!(
+            self.text,
+            "define i32 @main() {{\n  %main = call ptr @Main_new()\n  call ptr @{}.main(ptr %main)\n  ret i32 0\n}}",
+            owner
+        )
+        .unwrap();
+    }
+
+    /// Start the function whose code goes in `body` and `allocas`.
+    fn start_function(&mut self) {
+        self.body.clear();
+        self.allocas.clear();
+        self.temps = 0;
+        self.labels = 0;
+        self.block = "entry".to_string();
+    }
+
+    fn finish_function(&mut self, header: &str) {
+        writeln!(self.text, "{} {{\nentry:\n{}{}}}\n", header, self.allocas, self.body).unwrap();
+    }
+
+    /// Attributes of `class` are in scope in its methods and initializers.
+    fn enter_class(&mut self, class: Symbol) {
+        self.class = class;
+        self.scope.clear();
+        let attributes = self.layout.class(class).attributes.clone();
+        for (i, (attr, _)) in attributes.into_iter().enumerate() {
+            self.scope.push((attr, Location::Attribute(HEADER_SLOTS + i)));
+        }
+    }
+
+    /// `C_new`: a new object of class `C` with default attribute values,
+    /// initialized by `C_init`.
+    fn new_object(&mut self, class: &'a Class) {
+        self.start_function();
+        let name = class.name;
+        if matches!(name, sym::INT | sym::STRING | sym::BOOL) {
+            // Values are immutable, so every new one can be the same constant
+            let value = self.default_value(name);
+            emit!(self, "ret ptr {}", value);
+            return self.finish_function(&format!("define ptr @{}_new()", name));
+        }
+
+        let attributes = self.layout.class(name).attributes.clone();
+        let size = 8 * (HEADER_SLOTS + attributes.len());
+        let object = self.temp();
+        emit!(self, "{} = call ptr @calloc(i64 1, i64 {})", object, size);
+        emit!(self, "store ptr @{}_vtable, ptr {}", name, object);
+        let tag = self.slot(&object, 1);
+        emit!(self, "store i64 {}, ptr {}", self.tag(name), tag);
+        let size_slot = self.slot(&object, 2);
+        emit!(self, "store i64 {}, ptr {}", size, size_slot);
+        for (i, (_, ty)) in attributes.into_iter().enumerate() {
+            // Everything else defaults to void, which calloc left in place
+            if matches!(ty, sym::INT | sym::STRING | sym::BOOL) {
+                let value = self.default_value(ty);
+                let slot = self.slot(&object, HEADER_SLOTS + i);
+                emit!(self, "store ptr {}, ptr {}", value, slot);
+            }
+        }
+        emit!(self, "call void @{}_init(ptr {})", name, object);
+        emit!(self, "ret ptr {}", object);
+        self.finish_function(&format!("define ptr @{}_new()", name));
+    }
+
+    /// `C_init`: run the attribute initializers of `class` on `%self`,
+    /// those of its ancestors first.
+    fn init(&mut self, class: &'a Class, parent: Option<Symbol>) {
+        self.start_function();
+        self.enter_class(class.name);
+        self.initializer = true;
+        if let Some(parent) = parent {
+            emit!(self, "call void @{}_init(ptr %self)", parent);
+        }
+        for feature in &class.feature_list {
+            if let Feature::Attribute(VarDecl { oid, expr: Some(init), .. }) = feature {
+                let value = self.expr(init);
+                let Location::Attribute(index) = self.lookup(*oid) else { unreachable!("attributes are in scope") };
+                let slot = self.slot("%self", index);
+                emit!(self, "store ptr {}, ptr {}", value, slot);
+            }
+        }
+        emit!(self, "ret void");
+        self.finish_function(&format!("define void @{}_init(ptr %self)", class.name));
+    }
+
+    fn method(&mut self, class: Symbol, name: Symbol, formals: &[Symbol], body: &TypedExpr) {
+        self.start_function();
+        self.enter_class(class);
+        self.initializer = false;
+        let mut params = vec!["ptr %self".to_string()];
+        for (i, formal) in formals.iter().enumerate() {
+            params.push(format!("ptr %arg{}", i));
+            let slot = self.local();
+            emit!(self, "store ptr %arg{}, ptr {}", i, slot);
+            self.scope.push((*formal, Location::Local(slot)));
+        }
+        let value = self.expr(body);
+        emit!(self, "ret ptr {}", value);
+        self.finish_function(&format!("define ptr @{}.{}({})", class, name, params.join(", ")));
+    }
+
+    /// A new stack slot for one object pointer.
+    fn local(&mut self) -> String {
+        let slot = self.temp();
+        writeln!(self.allocas, "  {} = alloca ptr", slot).unwrap();
+        slot
+    }
+
+    /// Pointer to slot `index` of `object`.
+    fn slot(&mut self, object: &str, index: usize) -> String {
+        let slot = self.temp();
+        emit!(self, "{} = getelementptr ptr, ptr {}, i64 {}", slot, object, index);
+        slot
+    }
+
+    /// The `i32` value held by an `Int` or `Bool` object.
+    fn int_value(&mut self, object: &str) -> String {
+        let slot = self.slot(object, HEADER_SLOTS);
+        let value = self.temp();
+        emit!(self, "{} = load i32, ptr {}", value, slot);
+        value
+    }
+
+    /// The `i1` value of a `Bool` object.
+    fn test(&mut self, object: &str) -> String {
+        let value = self.int_value(object);
+        let test = self.temp();
+        emit!(self, "{} = icmp ne i32 {}, 0", test, value);
+        test
+    }
+
+    /// A `Bool` object for the `i1` value `cond`.
+    fn bool_object(&mut self, cond: &str) -> String {
+        let object = self.temp();
+        emit!(self, "{} = select i1 {}, ptr @bool.1, ptr @bool.0", object, cond);
+        object
+    }
+
+    fn lookup(&self, name: Symbol) -> Location {
+        if name == sym::SELF {
+            return Location::SelfObject;
+        }
+        let (_, location) = self.scope.iter().rev().find(|(n, _)| *n == name).expect("bound by the type checker");
+        location.clone()
+    }
+
+    /// Whether `e` may be void where it is read. In a method, an attribute
+    /// proven never void is not, unless a formal or local hides it.
+    fn may_be_void(&self, e: &TypedExpr) -> bool {
+        may_be_void(e, |name| {
+            !self.initializer
+                && matches!(self.lookup(name), Location::Attribute(_))
+                && self.never_void.contains(self.class, name)
+        })
+    }
+
+    /// Bind `name` to a new local holding `value`.
+    fn bind_local(&mut self, name: Symbol, value: &str) {
+        let slot = self.local();
+        emit!(self, "store ptr {}, ptr {}", value, slot);
+        self.scope.push((name, Location::Local(slot)));
+    }
+
+    /// Static class of `e`, with `SELF_TYPE` read as the current class.
+    fn static_class(&self, e: &TypedExpr) -> Symbol {
+        match e.static_type.expect("filled in by annotate") {
+            sym::SELF_TYPE => self.class,
+            ty => ty,
+        }
+    }
+
+    /// The value of a new object of class `ty` with no initializer.
+    fn default_value(&mut self, ty: Symbol) -> String {
+        match ty {
+            sym::INT => self.int_const(0),
+            sym::STRING => self.string_const(""),
+            sym::BOOL => "@bool.0".to_string(),
+            _ => "null".to_string(),
+        }
+    }
+
+    /// Code that computes the value of `e`, which is returned as an operand.
+    fn expr(&mut self, e: &TypedExpr) -> String {
+        match &e.expr {
+            Expr::Paren(inner) => self.expr(inner),
+            Expr::Bool(b) => format!("@bool.{}", u8::from(*b)),
+            Expr::Int(i) => self.int_const(*i),
+            Expr::Str(s) => self.string_const(s),
+            Expr::Identifier(name) => match self.lookup(*name) {
+                Location::SelfObject => "%self".to_string(),
+                Location::Attribute(index) => {
+                    let slot = self.slot("%self", index);
+                    let value = self.temp();
+                    emit!(self, "{} = load ptr, ptr {}", value, slot);
+                    value
+                }
+                Location::Local(slot) => {
+                    let value = self.temp();
+                    emit!(self, "{} = load ptr, ptr {}", value, slot);
+                    value
+                }
+            },
+            Expr::Assignment(name, rhs) => {
+                let value = self.expr(rhs);
+                let slot = match self.lookup(*name) {
+                    Location::SelfObject => unreachable!("self cannot be assigned"),
+                    Location::Attribute(index) => self.slot("%self", index),
+                    Location::Local(slot) => slot,
+                };
+                emit!(self, "store ptr {}, ptr {}", value, slot);
+                value
+            }
+            Expr::New(ty) if *ty == sym::SELF_TYPE => {
+                // Look the constructor up by the tag of self
+                let tag_slot = self.slot("%self", 1);
+                let (tag, entry, new, object) = (self.temp(), self.temp(), self.temp(), self.temp());
+                emit!(self, "{} = load i64, ptr {}", tag, tag_slot);
+                emit!(self, "{} = getelementptr ptr, ptr @class_new, i64 {}", entry, tag);
+                emit!(self, "{} = load ptr, ptr {}", new, entry);
+                emit!(self, "{} = call ptr {}()", object, new);
+                object
+            }
+            Expr::New(ty) => {
+                let object = self.temp();
+                emit!(self, "{} = call ptr @{}_new()", object, ty);
+                object
+            }
+            Expr::Block(exprs) => {
+                let mut value = "null".to_string();
+                for e in exprs {
+                    value = self.expr(e);
+                }
+                value
+            }
+            Expr::Let(bindings, body) => {
+                for VarDecl { oid: id, tid: ty, expr: init, .. } in bindings {
+                    let value = match init {
+                        Some(init) => self.expr(init),
+                        None => self.default_value(*ty),
+                    };
+                    self.bind_local(*id, &value);
+                }
+                let value = self.expr(body);
+                for _ in bindings {
+                    self.scope.pop();
+                }
+                value
+            }
+            Expr::Math { lhs, op, rhs: divisor } => {
+                let (lhs, rhs) = (self.expr(lhs), self.expr(divisor));
+                let (lhs, rhs) = (self.int_value(&lhs), self.int_value(&rhs));
+                if *op == MathOperator::Div && may_be_zero(divisor) {
+                    let (abort, divide) = (self.label(), self.label());
+                    let zero = self.temp();
+                    emit!(self, "{} = icmp eq i32 {}, 0", zero, rhs);
+                    emit!(self, "br i1 {}, label %{}, label %{}", zero, abort, divide);
+                    self.start_block(&abort);
+                    emit!(self, "call void @cool_division_abort(i32 {})", e.line);
+                    emit!(self, "unreachable");
+                    self.start_block(&divide);
+                }
+                let op = match op {
+                    MathOperator::Add => "add",
+                    MathOperator::Subtract => "sub",
+                    MathOperator::Mul => "mul",
+                    MathOperator::Div => "sdiv",
+                };
+                let value = self.arithmetic(op, &lhs, &rhs, e.line);
+                let object = self.temp();
+                emit!(self, "{} = call ptr @cool_int(i32 {})", object, value);
+                object
+            }
+            Expr::UnaryOperation { op: UnaryOperator::Neg, s } => {
+                let operand = self.expr(s);
+                let operand = self.int_value(&operand);
+                let value = self.arithmetic("sub", "0", &operand, e.line);
+                let object = self.temp();
+                emit!(self, "{} = call ptr @cool_int(i32 {})", object, value);
+                object
+            }
+            Expr::UnaryOperation { op: UnaryOperator::Not, s } => {
+                let operand = self.expr(s);
+                let operand = self.int_value(&operand);
+                let test = self.temp();
+                emit!(self, "{} = icmp eq i32 {}, 0", test, operand);
+                self.bool_object(&test)
+            }
+            Expr::Comparison { lhs, op, rhs } => {
+                let (lhs, rhs) = (self.expr(lhs), self.expr(rhs));
+                let test = match op {
+                    // Identical objects are equal; otherwise the runtime
+                    // compares Int, String and Bool values
+                    ComparisonOperator::Equal => {
+                        let test = self.temp();
+                        emit!(self, "{} = call i1 @cool_equals(ptr {}, ptr {})", test, lhs, rhs);
+                        test
+                    }
+                    ComparisonOperator::Lt | ComparisonOperator::Le => {
+                        let (lhs, rhs) = (self.int_value(&lhs), self.int_value(&rhs));
+                        let test = self.temp();
+                        let cond = if matches!(op, ComparisonOperator::Lt) { "slt" } else { "sle" };
+                        emit!(self, "{} = icmp {} i32 {}, {}", test, cond, lhs, rhs);
+                        test
+                    }
+                };
+                self.bool_object(&test)
+            }
+            Expr::Isvoid(inner) => {
+                let value = self.expr(inner);
+                let test = self.temp();
+                emit!(self, "{} = icmp eq ptr {}, null", test, value);
+                self.bool_object(&test)
+            }
+            Expr::Conditional { test, then, orelse } => {
+                let (yes, no, done) = (self.label(), self.label(), self.label());
+                let test = self.expr(test);
+                let test = self.test(&test);
+                emit!(self, "br i1 {}, label %{}, label %{}", test, yes, no);
+                let mut arms = Vec::new();
+                for (label, arm) in [(yes, then), (no, orelse)] {
+                    self.start_block(&label);
+                    let value = self.expr(arm);
+                    arms.push(format!("[ {}, %{} ]", value, self.block));
+                    emit!(self, "br label %{}", done);
+                }
+                self.start_block(&done);
+                let value = self.temp();
+                emit!(self, "{} = phi ptr {}", value, arms.join(", "));
+                value
+            }
+            Expr::While { test, exec } => {
+                let (top, body, done) = (self.label(), self.label(), self.label());
+                emit!(self, "br label %{}", top);
+                self.start_block(&top);
+                let test = self.expr(test);
+                let test = self.test(&test);
+                emit!(self, "br i1 {}, label %{}, label %{}", test, body, done);
+                self.start_block(&body);
+                self.expr(exec);
+                emit!(self, "br label %{}", top);
+                self.start_block(&done);
+                "null".to_string()
+            }
+            Expr::Case(scrutinee, branches) => self.case(e, scrutinee, branches),
+            Expr::Dispatch { target, targettype, id, exprs } => {
+                let mut args = Vec::new();
+                for arg in exprs {
+                    let value = self.expr(arg);
+                    args.push(format!(", ptr {}", value));
+                }
+                let (receiver, class) = match target {
+                    Some(target) => {
+                        let receiver = self.expr(target);
+                        if self.may_be_void(target) {
+                            let (abort, call) = (self.label(), self.label());
+                            let void = self.temp();
+                            emit!(self, "{} = icmp eq ptr {}, null", void, receiver);
+                            emit!(self, "br i1 {}, label %{}, label %{}", void, abort, call);
+                            self.start_block(&abort);
+                            let name = self.string_const(id.as_str());
+                            emit!(self, "call void @cool_dispatch_abort(i32 {}, ptr {})", e.line, name);
+                            emit!(self, "unreachable");
+                            self.start_block(&call);
+                        }
+                        (receiver, self.static_class(target))
+                    }
+                    None => ("%self".to_string(), self.class),
+                };
+                let callee = match targettype {
+                    Some(ty) => {
+                        let methods = &self.layout.class(*ty).methods;
+                        let (_, owner) = methods.iter().find(|(m, _)| m == id).expect("resolved by the type checker");
+                        format!("@{}.{}", owner, id)
+                    }
+                    None => {
+                        let methods = &self.layout.class(class).methods;
+                        let index = methods.iter().position(|(m, _)| m == id).expect("resolved by the type checker");
+                        let (vtable, entry, callee) = (self.temp(), self.temp(), self.temp());
+                        emit!(self, "{} = load ptr, ptr {}", vtable, receiver);
+                        emit!(self, "{} = getelementptr ptr, ptr {}, i64 {}", entry, vtable, index);
+                        emit!(self, "{} = load ptr, ptr {}", callee, entry);
+                        callee
+                    }
+                };
+                let value = self.temp();
+                emit!(self, "{} = call ptr {}(ptr {}{})", value, callee, receiver, args.concat());
+                value
+            }
+            Expr::Try { .. } | Expr::Throw(_) | Expr::Native | Expr::Builtin => {
+                unreachable!("rejected by generate")
+            }
+        }
+    }
+
+    /// Branches are tried from the most specific class up, each matching
+    /// its class's range of tags.
+    fn case(&mut self, e: &TypedExpr, scrutinee: &TypedExpr, branches: &[CaseBranch]) -> String {
+        let value = self.expr(scrutinee);
+        if self.may_be_void(scrutinee) {
+            let (abort, test) = (self.label(), self.label());
+            let void = self.temp();
+            emit!(self, "{} = icmp eq ptr {}, null", void, value);
+            emit!(self, "br i1 {}, label %{}, label %{}", void, abort, test);
+            self.start_block(&abort);
+            emit!(self, "call void @cool_void_case_abort(i32 {})", e.line);
+            emit!(self, "unreachable");
+            self.start_block(&test);
+        }
+        let tag_slot = self.slot(&value, 1);
+        let tag = self.temp();
+        emit!(self, "{} = load i64, ptr {}", tag, tag_slot);
+        let done = self.label();
+        let mut ordered: Vec<&CaseBranch> = branches.iter().collect();
+        ordered.sort_by_key(|branch| std::cmp::Reverse(self.layout.class(branch.tid).tag));
+        let mut arms = Vec::new();
+        for branch in ordered {
+            let class = self.layout.class(branch.tid);
+            let (low, high) = (class.tag, class.last_descendant);
+            let (arm, next) = (self.label(), self.label());
+            let (above, below, matches) = (self.temp(), self.temp(), self.temp());
+            emit!(self, "{} = icmp sge i64 {}, {}", above, tag, low);
+            emit!(self, "{} = icmp sle i64 {}, {}", below, tag, high);
+            emit!(self, "{} = and i1 {}, {}", matches, above, below);
+            emit!(self, "br i1 {}, label %{}, label %{}", matches, arm, next);
+            self.start_block(&arm);
+            self.bind_local(branch.id, &value);
+            let result = self.expr(&branch.expr);
+            self.scope.pop();
+            arms.push(format!("[ {}, %{} ]", result, self.block));
+            emit!(self, "br label %{}", done);
+            self.start_block(&next);
+        }
+        // No branch matched; the runtime reports the class and aborts
+        emit!(self, "call void @cool_case_abort(i32 {}, ptr {})", e.line, value);
+        emit!(self, "unreachable");
+        self.start_block(&done);
+        let result = self.temp();
+        emit!(self, "{} = phi ptr {}", result, arms.join(", "));
+        result
+    }
+}
+
+/// `s` as the contents of an LLVM `c"..."` string.
+fn escape(s: &str) -> String {
+    let mut out = String::new();
+    for byte in s.bytes() {
+        match byte {
+            b'"' | b'\\' => write!(out, "\\{:02X}", byte).unwrap(),
+            b' '..=b'~' => out.push(byte as char),
+            _ => write!(out, "\\{:02X}", byte).unwrap(),
+        }
+    }
+    out
+}
+
+/// The methods of the basic classes and the helpers the generated code
+/// calls, in terms of the C library. Strings are printed with `%s`, which
+/// is safe because the scanner rejects NUL characters in literals.
+const RUNTIME: &str = r#"declare ptr @calloc(i64, i64)
+declare ptr @realloc(ptr, i64)
+declare ptr @memcpy(ptr, ptr, i64)
+declare i32 @memcmp(ptr, ptr, i64)
+declare i32 @printf(ptr, ...)
+declare i32 @dprintf(i32, ptr, ...)
+declare i32 @fflush(ptr)
+declare i32 @getchar()
+declare i64 @strtol(ptr, ptr, i32)
+declare void @exit(i32)
+
+@fmt.int = private unnamed_addr constant [3 x i8] c"%d\00"
+@fmt.string = private unnamed_addr constant [3 x i8] c"%s\00"
+@fmt.abort = private unnamed_addr constant [28 x i8] c"Abort called from class %s\0A\00"
+@fmt.case = private unnamed_addr constant [50 x i8] c"[line %d] No 'case' branch for dynamic type '%s'\0A\00"
+@fmt.void_case = private unnamed_addr constant [24 x i8] c"[line %d] Case on void\0A\00"
+@fmt.division = private unnamed_addr constant [28 x i8] c"[line %d] Division by zero\0A\00"
+@fmt.overflow = private unnamed_addr constant [28 x i8] c"[line %d] Integer overflow\0A\00"
+@fmt.dispatch = private unnamed_addr constant [36 x i8] c"[line %d] Dispatch of '%s' on void\0A\00"
+@fmt.substr = private unnamed_addr constant [65 x i8] c"Substring out of range: substr(%d, %d) of a string of length %d\0A\00"
+
+define ptr @cool_int(i32 %value) {
+entry:
+  %object = call ptr @calloc(i64 1, i64 32)
+  store ptr @Int_vtable, ptr %object
+  %tag = load i64, ptr @_int_tag
+  %tag.slot = getelementptr ptr, ptr %object, i64 1
+  store i64 %tag, ptr %tag.slot
+  %size.slot = getelementptr ptr, ptr %object, i64 2
+  store i64 32, ptr %size.slot
+  %value.slot = getelementptr ptr, ptr %object, i64 3
+  store i32 %value, ptr %value.slot
+  ret ptr %object
+}
+
+define ptr @cool_string(i64 %length, ptr %chars) {
+entry:
+  %object = call ptr @calloc(i64 1, i64 40)
+  store ptr @String_vtable, ptr %object
+  %tag = load i64, ptr @_string_tag
+  %tag.slot = getelementptr ptr, ptr %object, i64 1
+  store i64 %tag, ptr %tag.slot
+  %size.slot = getelementptr ptr, ptr %object, i64 2
+  store i64 40, ptr %size.slot
+  %length.slot = getelementptr ptr, ptr %object, i64 3
+  store i64 %length, ptr %length.slot
+  %chars.slot = getelementptr ptr, ptr %object, i64 4
+  store ptr %chars, ptr %chars.slot
+  ret ptr %object
+}
+
+define i1 @cool_equals(ptr %a, ptr %b) {
+entry:
+  %same = icmp eq ptr %a, %b
+  br i1 %same, label %yes, label %voids
+voids:
+  %a.void = icmp eq ptr %a, null
+  %b.void = icmp eq ptr %b, null
+  %void = or i1 %a.void, %b.void
+  br i1 %void, label %no, label %tags
+tags:
+  %a.tag.slot = getelementptr ptr, ptr %a, i64 1
+  %a.tag = load i64, ptr %a.tag.slot
+  %b.tag.slot = getelementptr ptr, ptr %b, i64 1
+  %b.tag = load i64, ptr %b.tag.slot
+  %same.tag = icmp eq i64 %a.tag, %b.tag
+  br i1 %same.tag, label %kind, label %no
+kind:
+  %int.tag = load i64, ptr @_int_tag
+  %bool.tag = load i64, ptr @_bool_tag
+  %string.tag = load i64, ptr @_string_tag
+  %is.int = icmp eq i64 %a.tag, %int.tag
+  %is.bool = icmp eq i64 %a.tag, %bool.tag
+  %is.value = or i1 %is.int, %is.bool
+  br i1 %is.value, label %values, label %strings
+values:
+  %a.value.slot = getelementptr ptr, ptr %a, i64 3
+  %a.value = load i32, ptr %a.value.slot
+  %b.value.slot = getelementptr ptr, ptr %b, i64 3
+  %b.value = load i32, ptr %b.value.slot
+  %equal = icmp eq i32 %a.value, %b.value
+  ret i1 %equal
+strings:
+  %is.string = icmp eq i64 %a.tag, %string.tag
+  br i1 %is.string, label %lengths, label %no
+lengths:
+  %a.length.slot = getelementptr ptr, ptr %a, i64 3
+  %a.length = load i64, ptr %a.length.slot
+  %b.length.slot = getelementptr ptr, ptr %b, i64 3
+  %b.length = load i64, ptr %b.length.slot
+  %same.length = icmp eq i64 %a.length, %b.length
+  br i1 %same.length, label %chars, label %no
+chars:
+  %a.chars.slot = getelementptr ptr, ptr %a, i64 4
+  %a.chars = load ptr, ptr %a.chars.slot
+  %b.chars.slot = getelementptr ptr, ptr %b, i64 4
+  %b.chars = load ptr, ptr %b.chars.slot
+  %compared = call i32 @memcmp(ptr %a.chars, ptr %b.chars, i64 %a.length)
+  %same.chars = icmp eq i32 %compared, 0
+  ret i1 %same.chars
+yes:
+  ret i1 true
+no:
+  ret i1 false
+}
+
+define ptr @cool_class_name(ptr %object) {
+entry:
+  %tag.slot = getelementptr ptr, ptr %object, i64 1
+  %tag = load i64, ptr %tag.slot
+  %name.slot = getelementptr ptr, ptr @class_names, i64 %tag
+  %name = load ptr, ptr %name.slot
+  ret ptr %name
+}
+
+define void @cool_fail(ptr %format, ptr %class) {
+entry:
+  call i32 @fflush(ptr null)
+  %name.slot = getelementptr ptr, ptr %class, i64 4
+  %name = load ptr, ptr %name.slot
+  call i32 (i32, ptr, ...) @dprintf(i32 2, ptr %format, ptr %name)
+  call void @exit(i32 1)
+  unreachable
+}
+
+define void @cool_case_abort(i32 %line, ptr %object) {
+entry:
+  call i32 @fflush(ptr null)
+  %class = call ptr @cool_class_name(ptr %object)
+  %name.slot = getelementptr ptr, ptr %class, i64 4
+  %name = load ptr, ptr %name.slot
+  call i32 (i32, ptr, ...) @dprintf(i32 2, ptr @fmt.case, i32 %line, ptr %name)
+  call void @exit(i32 1)
+  unreachable
+}
+
+define void @cool_void_case_abort(i32 %line) {
+entry:
+  call i32 @fflush(ptr null)
+  call i32 (i32, ptr, ...) @dprintf(i32 2, ptr @fmt.void_case, i32 %line)
+  call void @exit(i32 1)
+  unreachable
+}
+
+define void @cool_division_abort(i32 %line) {
+entry:
+  call i32 @fflush(ptr null)
+  call i32 (i32, ptr, ...) @dprintf(i32 2, ptr @fmt.division, i32 %line)
+  call void @exit(i32 1)
+  unreachable
+}
+
+define void @cool_overflow_abort(i32 %line) {
+entry:
+  call i32 @fflush(ptr null)
+  call i32 (i32, ptr, ...) @dprintf(i32 2, ptr @fmt.overflow, i32 %line)
+  call void @exit(i32 1)
+  unreachable
+}
+
+define void @cool_dispatch_abort(i32 %line, ptr %method) {
+entry:
+  call i32 @fflush(ptr null)
+  %name.slot = getelementptr ptr, ptr %method, i64 4
+  %name = load ptr, ptr %name.slot
+  call i32 (i32, ptr, ...) @dprintf(i32 2, ptr @fmt.dispatch, i32 %line, ptr %name)
+  call void @exit(i32 1)
+  unreachable
+}
+
+define ptr @Object.abort(ptr %self) {
+entry:
+  %class = call ptr @cool_class_name(ptr %self)
+  call void @cool_fail(ptr @fmt.abort, ptr %class)
+  unreachable
+}
+
+define ptr @Object.type_name(ptr %self) {
+entry:
+  %class = call ptr @cool_class_name(ptr %self)
+  ret ptr %class
+}
+
+define ptr @Object.copy(ptr %self) {
+entry:
+  %size.slot = getelementptr ptr, ptr %self, i64 2
+  %size = load i64, ptr %size.slot
+  %copy = call ptr @calloc(i64 1, i64 %size)
+  call ptr @memcpy(ptr %copy, ptr %self, i64 %size)
+  ret ptr %copy
+}
+
+define ptr @IO.out_string(ptr %self, ptr %s) {
+entry:
+  %chars.slot = getelementptr ptr, ptr %s, i64 4
+  %chars = load ptr, ptr %chars.slot
+  call i32 (ptr, ...) @printf(ptr @fmt.string, ptr %chars)
+  ret ptr %self
+}
+
+define ptr @IO.out_int(ptr %self, ptr %i) {
+entry:
+  %value.slot = getelementptr ptr, ptr %i, i64 3
+  %value = load i32, ptr %value.slot
+  call i32 (ptr, ...) @printf(ptr @fmt.int, i32 %value)
+  ret ptr %self
+}
+
+define ptr @IO.in_string(ptr %self) {
+entry:
+  %length.p = alloca i64
+  %capacity.p = alloca i64
+  %buffer.p = alloca ptr
+  call i32 @fflush(ptr null)
+  %initial = call ptr @realloc(ptr null, i64 16)
+  store ptr %initial, ptr %buffer.p
+  store i64 0, ptr %length.p
+  store i64 16, ptr %capacity.p
+  br label %read
+read:
+  %c = call i32 @getchar()
+  %eof = icmp eq i32 %c, -1
+  %newline = icmp eq i32 %c, 10
+  %end = or i1 %eof, %newline
+  br i1 %end, label %done, label %room
+room:
+  %length = load i64, ptr %length.p
+  %capacity = load i64, ptr %capacity.p
+  %next = add i64 %length, 1
+  %full = icmp uge i64 %next, %capacity
+  br i1 %full, label %grow, label %append
+grow:
+  %bigger = mul i64 %capacity, 2
+  store i64 %bigger, ptr %capacity.p
+  %old = load ptr, ptr %buffer.p
+  %new = call ptr @realloc(ptr %old, i64 %bigger)
+  store ptr %new, ptr %buffer.p
+  br label %append
+append:
+  %buffer = load ptr, ptr %buffer.p
+  %at = getelementptr i8, ptr %buffer, i64 %length
+  %byte = trunc i32 %c to i8
+  store i8 %byte, ptr %at
+  store i64 %next, ptr %length.p
+  br label %read
+done:
+  %final.length = load i64, ptr %length.p
+  %final = load ptr, ptr %buffer.p
+  %nul = getelementptr i8, ptr %final, i64 %final.length
+  store i8 0, ptr %nul
+  %string = call ptr @cool_string(i64 %final.length, ptr %final)
+  ret ptr %string
+}
+
+define ptr @IO.in_int(ptr %self) {
+entry:
+  %line = call ptr @IO.in_string(ptr %self)
+  %chars.slot = getelementptr ptr, ptr %line, i64 4
+  %chars = load ptr, ptr %chars.slot
+  %long = call i64 @strtol(ptr %chars, ptr null, i32 10)
+  %value = trunc i64 %long to i32
+  %int = call ptr @cool_int(i32 %value)
+  ret ptr %int
+}
+
+define ptr @String.length(ptr %self) {
+entry:
+  %length.slot = getelementptr ptr, ptr %self, i64 3
+  %length = load i64, ptr %length.slot
+  %value = trunc i64 %length to i32
+  %int = call ptr @cool_int(i32 %value)
+  ret ptr %int
+}
+
+define ptr @String.concat(ptr %self, ptr %s) {
+entry:
+  %a.length.slot = getelementptr ptr, ptr %self, i64 3
+  %a.length = load i64, ptr %a.length.slot
+  %a.chars.slot = getelementptr ptr, ptr %self, i64 4
+  %a.chars = load ptr, ptr %a.chars.slot
+  %b.length.slot = getelementptr ptr, ptr %s, i64 3
+  %b.length = load i64, ptr %b.length.slot
+  %b.chars.slot = getelementptr ptr, ptr %s, i64 4
+  %b.chars = load ptr, ptr %b.chars.slot
+  %length = add i64 %a.length, %b.length
+  %size = add i64 %length, 1
+  %chars = call ptr @calloc(i64 1, i64 %size)
+  call ptr @memcpy(ptr %chars, ptr %a.chars, i64 %a.length)
+  %tail = getelementptr i8, ptr %chars, i64 %a.length
+  call ptr @memcpy(ptr %tail, ptr %b.chars, i64 %b.length)
+  %string = call ptr @cool_string(i64 %length, ptr %chars)
+  ret ptr %string
+}
+
+define ptr @String.substr(ptr %self, ptr %i, ptr %l) {
+entry:
+  %length.slot = getelementptr ptr, ptr %self, i64 3
+  %length = load i64, ptr %length.slot
+  %start.slot = getelementptr ptr, ptr %i, i64 3
+  %start.32 = load i32, ptr %start.slot
+  %start = sext i32 %start.32 to i64
+  %count.slot = getelementptr ptr, ptr %l, i64 3
+  %count.32 = load i32, ptr %count.slot
+  %count = sext i32 %count.32 to i64
+  %end = add i64 %start, %count
+  %start.ok = icmp sge i64 %start, 0
+  %count.ok = icmp sge i64 %count, 0
+  %end.ok = icmp sle i64 %end, %length
+  %ok.1 = and i1 %start.ok, %count.ok
+  %ok = and i1 %ok.1, %end.ok
+  br i1 %ok, label %copy, label %fail
+copy:
+  %chars.slot = getelementptr ptr, ptr %self, i64 4
+  %chars = load ptr, ptr %chars.slot
+  %from = getelementptr i8, ptr %chars, i64 %start
+  %size = add i64 %count, 1
+  %copied = call ptr @calloc(i64 1, i64 %size)
+  call ptr @memcpy(ptr %copied, ptr %from, i64 %count)
+  %string = call ptr @cool_string(i64 %count, ptr %copied)
+  ret ptr %string
+fail:
+  call i32 @fflush(ptr null)
+  %length.32 = trunc i64 %length to i32
+  call i32 (i32, ptr, ...) @dprintf(i32 2, ptr @fmt.substr, i32 %start.32, i32 %count.32, i32 %length.32)
+  call void @exit(i32 1)
+  unreachable
+}
+"#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::semantic::type_checker::annotate;
+
+    fn generate_for(source: &str) -> String {
+        let classes = crate::parse_program(source).unwrap();
+        let ctx = AnalysisContext::new(crate::prelude(), &classes);
+        generate(&ctx, &annotate(&ctx), Overflow::Wrap).unwrap()
+    }
+
+    #[test]
+    fn test_generate() {
+        let ir = generate_for(
+            "class Main inherits IO {\n  n : Int <- 3;\n  main() : Object { out_int(n + 1) };\n};",
+        );
+        // Main's vtable inherits IO's methods and is laid out like IO's
+        let vtable = ir.lines().find(|line| line.starts_with("@Main_vtable = ")).unwrap();
+        assert!(vtable.contains(
+            "[ptr @Object.abort, ptr @Object.type_name, ptr @Object.copy, ptr @IO.out_string, ptr @IO.out_int, "
+        ));
+        // The constructor defaults the attribute, the initializer sets it
+        let new = ir.split("define ptr @Main_new() {\n").nth(1).unwrap();
+        assert!(new.contains("store ptr @int.") && new.contains("call void @Main_init("));
+        let init = ir.split("define void @Main_init(ptr %self) {\n").nth(1).unwrap();
+        assert!(init.starts_with("entry:\n  call void @IO_init(ptr %self)\n"));
+        // Dispatch on self goes through the vtable; out_int is entry 4
+        let main = ir.split("define ptr @Main.main(ptr %self) {\n").nth(1).unwrap();
+        assert!(main.contains("= getelementptr ptr, ptr %t") && main.contains(", i64 4\n"));
+        assert!(ir.contains("call ptr @Main.main(ptr %main)"));
+        // Only user methods are generated; the runtime has the basic ones
+        assert_eq!(ir.matches("define ptr @IO.out_int(").count(), 1);
+    }
+
+    /// With the `llc` on the `PATH`, whatever its supported version, the
+    /// object links and runs.
+    #[test]
+    fn test_write_object() {
+        if Command::new("llc").arg("--version").output().is_err() || Command::new("cc").arg("--version").output().is_err() {
+            return;
+        }
+        let dir = std::env::temp_dir().join(format!("cool-rs-llvm-object-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let (object, exe) = (dir.join("program.o"), dir.join("program"));
+        let ir = generate_for("class Main inherits IO {\n  main() : Object { out_int(6 * 7) };\n};");
+        write_object(&ir, &object).unwrap();
+        let cc = Command::new("cc").arg(&object).arg("-o").arg(&exe).output().unwrap();
+        assert!(cc.status.success(), "{}", String::from_utf8_lossy(&cc.stderr));
+        let output = Command::new(&exe).output().unwrap();
+        assert_eq!(String::from_utf8_lossy(&output.stdout), "42");
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn test_llvm_major() {
+        assert_eq!(llvm_major("Debian LLVM version 14.0.6\n  Optimized build.\n"), Some(14));
+        assert_eq!(llvm_major("LLVM (http://llvm.org/):\n  LLVM version 17.0.1\n"), Some(17));
+        assert_eq!(llvm_major("llc: unknown\n"), None);
+    }
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(escape("say \"hi\"\\\n"), "say \\22hi\\22\\5C\\0A");
+    }
+}
@@ -0,0 +1,813 @@
+//! MIPS code generation (`--emit asm`).
+//!
+//! Lowers a program that passed semantic analysis to MIPS32 assembly for
+//! SPIM, to be loaded together with the standard COOL runtime
+//! (`trap.handler`). The runtime supplies the built-in methods, `Object.copy`,
+//! `equality_test` and the garbage collector interface; this module emits
+//! everything else: the class name and object tables, a prototype object,
+//! init method and dispatch table for every class, the constants and the
+//! user's methods. The layout and calling convention are the runtime's:
+//!
+//! * An object is its class tag, its size in words, a pointer to its
+//!   dispatch table, then its attributes, inherited ones first.
+//! * Arguments are pushed left to right and the receiver is passed in
+//!   `$a0`; the callee pops the arguments. The result comes back in `$a0`.
+//! * `$s0` holds `self` and `$fp` the frame, whose locals (callee-saved
+//!   registers and spilled variables) sit below the saved `$fp`, `$s0` and
+//!   `$ra`.
+//!
+//! The methods are generated from the IR (`crate::ir`): the variables of
+//! each function are given registers by the allocator in `regalloc`, from
+//! their liveness, so `Int` and `Bool` arithmetic runs unboxed in registers
+//! and only values live across calls are saved.
+//!
+//! A dispatch or `case` on void, a `case` with no branch for its value, a
+//! division by zero and an overflow that traps print the error with its
+//! line and exit with status 1, like `--run`. No code is emitted for the `arrays`, `exceptions` or
+//! `natives` extensions, which have no runtime support.
+
+pub mod c;
+pub(crate) mod layout;
+#[cfg(feature = "llvm")]
+pub mod llvm;
+pub(crate) mod never_void;
+mod regalloc;
+
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use eyre::{bail, Result};
+
+use crate::arith::{self, Overflow};
+use crate::ast::{Class, Expr, Feature, TypedExpr, VarDecl};
+use crate::ext::{Extension, Extensions};
+use crate::ir::{self, BinaryOp, BlockId, Callee, Function, Inst, Terminator, Ty, UnaryOp, Var};
+use crate::semantic::context::AnalysisContext;
+use crate::symbol::{sym, Symbol};
+use layout::{Layout, HEADER_WORDS};
+use regalloc::Location;
+
+/// Assembly for the program of `ctx`, which must have checked cleanly, to
+/// run with `overflow`. `classes` are its classes as annotated by the type
+/// checker.
+pub fn generate(ctx: &AnalysisContext<'_>, classes: &[Class], overflow: Overflow) -> Result<String> {
+    check_supported(ctx, classes, &Extensions::default())?;
+    let all: Vec<&Class> = ctx.builtins.iter().chain(classes).collect();
+    let mut generator = Generator {
+        layout: Layout::new(&all),
+        overflow,
+        out: String::new(),
+        strings: Vec::new(),
+        literals: Vec::new(),
+        ints: Vec::new(),
+        labels: 0,
+        locations: Vec::new(),
+        constants: HashMap::new(),
+    };
+    // The data section needs these whatever the program uses
+    let names: Vec<Symbol> = generator.layout.classes.iter().map(|c| c.class.name).collect();
+    for name in names {
+        generator.string_const(name.as_str());
+    }
+    generator.string_const("");
+    generator.int_const(0);
+    generator.text();
+    let text = std::mem::take(&mut generator.out);
+    generator.data();
+    Ok(generator.out + &text)
+}
+
+/// Fails unless every class of the program, built-in or not, has code
+/// generation; the backends and the VM share what they support, and
+/// `extensions` are those the caller implements on top of it.
+pub(crate) fn check_supported(ctx: &AnalysisContext<'_>, classes: &[Class], extensions: &Extensions) -> Result<()> {
+    for class in ctx.builtins {
+        let supported = match class.name {
+            sym::OBJECT | sym::IO | sym::STRING | sym::INT | sym::BOOL => true,
+            name => name.as_str() == "Array" && extensions.contains(Extension::Arrays),
+        };
+        if !supported {
+            bail!("Code generation does not support the built-in class '{}'", class.name);
+        }
+    }
+    for class in classes {
+        for feature in &class.feature_list {
+            let root = match feature {
+                Feature::Attribute(VarDecl { expr, .. }) => expr.as_ref(),
+                Feature::Method(_, _, _, body, _) => Some(body),
+            };
+            if let Some(unsupported) = root.and_then(|e| unsupported(e, extensions)) {
+                bail!("Code generation does not support {} (in class '{}')", unsupported, class.name);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The first construct in `e` that has no code generation, if any.
+fn unsupported(e: &TypedExpr, extensions: &Extensions) -> Option<&'static str> {
+    match e.expr {
+        Expr::Try { .. } | Expr::Throw(_) if !extensions.contains(Extension::Exceptions) => Some("exceptions"),
+        Expr::Native => Some("native methods"),
+        _ => e.expr.children().into_iter().find_map(|child| unsupported(child, extensions)),
+    }
+}
+
+struct Generator<'a> {
+    layout: Layout<'a>,
+    overflow: Overflow,
+    out: String,
+    /// Constants, numbered by position
+    strings: Vec<String>,
+    /// The string literals of the IR, by index
+    literals: Vec<String>,
+    ints: Vec<i32>,
+    labels: usize,
+    /// Where the variables of the function being emitted are
+    locations: Vec<Option<Location>>,
+    /// Its variables holding constants, with their definitions
+    constants: HashMap<Var, Inst>,
+}
+
+/// One instruction, indented.
+macro_rules! emit {
+    ($g:expr, $($arg:tt)*) => {
+        writeln!($g.out, "\t{}", format_args!($($arg)*)).unwrap()
+    };
+}
+
+impl<'a> Generator<'a> {
+    fn label(&mut self) -> String {
+        self.labels += 1;
+        format!("label{}", self.labels)
+    }
+
+    fn string_const(&mut self, s: &str) -> String {
+        let index = match self.strings.iter().position(|t| t == s) {
+            Some(index) => index,
+            None => {
+                self.int_const(s.len() as i32);
+                self.strings.push(s.to_string());
+                self.strings.len() - 1
+            }
+        };
+        format!("str_const{}", index)
+    }
+
+    fn int_const(&mut self, i: i32) -> String {
+        let index = match self.ints.iter().position(|&j| j == i) {
+            Some(index) => index,
+            None => {
+                self.ints.push(i);
+                self.ints.len() - 1
+            }
+        };
+        format!("int_const{}", index)
+    }
+
+    fn tag(&self, class: Symbol) -> usize {
+        self.layout.class(class).tag
+    }
+
+    // ---- Data -------------------------------------------------------------
+
+    fn data(&mut self) {
+        writeln!(self.out, "\t.data").unwrap();
+        emit!(self, ".align\t2");
+        let globals = [
+            "class_nameTab", "Main_protObj", "Int_protObj", "String_protObj", "bool_const0",
+            "bool_const1", "_int_tag", "_bool_tag", "_string_tag",
+        ];
+        for global in globals {
+            emit!(self, ".globl\t{}", global);
+        }
+        let tags = [("_int_tag", sym::INT), ("_bool_tag", sym::BOOL), ("_string_tag", sym::STRING)];
+        for (label, class) in tags {
+            writeln!(self.out, "{}:", label).unwrap();
+            emit!(self, ".word\t{}", self.tag(class));
+        }
+        // The runtime's collectors need a cooperating code generator; this
+        // one allocates without ever collecting
+        for (label, value) in [
+            ("_MemMgr_INITIALIZER", "_NoGC_Init"),
+            ("_MemMgr_COLLECTOR", "_NoGC_Collect"),
+            ("_MemMgr_TEST", "0"),
+        ] {
+            emit!(self, ".globl\t{}", label);
+            writeln!(self.out, "{}:", label).unwrap();
+            emit!(self, ".word\t{}", value);
+        }
+
+        self.constants();
+        self.tables();
+        self.prototypes();
+
+        emit!(self, ".globl\theap_start");
+        writeln!(self.out, "heap_start:").unwrap();
+        emit!(self, ".word\t0");
+    }
+
+    fn constants(&mut self) {
+        // Every string constant's length is an int constant, so strings go first
+        let string_tag = self.tag(sym::STRING);
+        for (i, s) in self.strings.clone().iter().enumerate() {
+            let length = self.int_const(s.len() as i32);
+            emit!(self, ".word\t-1");
+            writeln!(self.out, "str_const{}:", i).unwrap();
+            emit!(self, ".word\t{}", string_tag);
+            emit!(self, ".word\t{}", HEADER_WORDS + 1 + (s.len() + 4) / 4);
+            emit!(self, ".word\tString_dispTab");
+            emit!(self, ".word\t{}", length);
+            self.ascii(s);
+            emit!(self, ".byte\t0");
+            emit!(self, ".align\t2");
+        }
+        let int_tag = self.tag(sym::INT);
+        for (i, value) in self.ints.clone().iter().enumerate() {
+            emit!(self, ".word\t-1");
+            writeln!(self.out, "int_const{}:", i).unwrap();
+            emit!(self, ".word\t{}", int_tag);
+            emit!(self, ".word\t{}", HEADER_WORDS + 1);
+            emit!(self, ".word\tInt_dispTab");
+            emit!(self, ".word\t{}", value);
+        }
+        let bool_tag = self.tag(sym::BOOL);
+        for value in 0..2 {
+            emit!(self, ".word\t-1");
+            writeln!(self.out, "bool_const{}:", value).unwrap();
+            emit!(self, ".word\t{}", bool_tag);
+            emit!(self, ".word\t{}", HEADER_WORDS + 1);
+            emit!(self, ".word\tBool_dispTab");
+            emit!(self, ".word\t{}", value);
+        }
+    }
+
+    /// `s` as `.ascii` runs, with bytes SPIM cannot take inside quotes as `.byte`.
+    fn ascii(&mut self, s: &str) {
+        let mut run = String::new();
+        for byte in s.bytes() {
+            match byte {
+                b'"' | b'\\' => {
+                    run.push('\\');
+                    run.push(byte as char);
+                }
+                b' '..=b'~' => run.push(byte as char),
+                _ => {
+                    if !run.is_empty() {
+                        emit!(self, ".ascii\t\"{}\"", run);
+                        run.clear();
+                    }
+                    emit!(self, ".byte\t{}", byte);
+                }
+            }
+        }
+        if !run.is_empty() {
+            emit!(self, ".ascii\t\"{}\"", run);
+        }
+    }
+
+    fn tables(&mut self) {
+        let names: Vec<Symbol> = self.layout.classes.iter().map(|c| c.class.name).collect();
+        let name_consts: Vec<String> = names.iter().map(|name| self.string_const(name.as_str())).collect();
+        writeln!(self.out, "class_nameTab:").unwrap();
+        for name in &name_consts {
+            emit!(self, ".word\t{}", name);
+        }
+        writeln!(self.out, "class_objTab:").unwrap();
+        for name in &names {
+            emit!(self, ".word\t{}_protObj", name);
+            emit!(self, ".word\t{}_init", name);
+        }
+        for class in &self.layout.classes {
+            writeln!(self.out, "{}_dispTab:", class.class.name).unwrap();
+            for (method, owner) in &class.methods {
+                writeln!(self.out, "\t.word\t{}.{}", owner, method).unwrap();
+            }
+        }
+    }
+
+    fn prototypes(&mut self) {
+        let mut prototypes = Vec::new();
+        for class in &self.layout.classes {
+            let name = class.class.name;
+            let defaults: Vec<Symbol> = class.attributes.iter().map(|(_, ty)| *ty).collect();
+            prototypes.push((name, class.tag, defaults));
+        }
+        let empty = self.string_const("");
+        for (name, tag, attributes) in prototypes {
+            // The basic classes keep their value in a hidden attribute
+            let hidden: &[String] = match name {
+                sym::INT | sym::BOOL => &["0".to_string()],
+                sym::STRING => &[self.int_const(0), "0".to_string()],
+                _ => &[],
+            };
+            let mut words = hidden.to_vec();
+            for ty in attributes {
+                words.push(match ty {
+                    sym::INT => self.int_const(0),
+                    sym::STRING => empty.clone(),
+                    sym::BOOL => "bool_const0".to_string(),
+                    _ => "0".to_string(),
+                });
+            }
+            emit!(self, ".word\t-1");
+            writeln!(self.out, "{}_protObj:", name).unwrap();
+            emit!(self, ".word\t{}", tag);
+            emit!(self, ".word\t{}", HEADER_WORDS + words.len());
+            emit!(self, ".word\t{}_dispTab", name);
+            for word in words {
+                emit!(self, ".word\t{}", word);
+            }
+        }
+    }
+
+    // ---- Text -------------------------------------------------------------
+
+    fn text(&mut self) {
+        emit!(self, ".text");
+        for global in ["Main_init", "Int_init", "String_init", "Bool_init", "Main.main"] {
+            emit!(self, ".globl\t{}", global);
+        }
+        let program = ir::lower_layout(&self.layout, self.overflow);
+        self.literals = program.strings;
+        for function in &program.functions {
+            self.function(function);
+        }
+    }
+
+    fn function(&mut self, function: &Function) {
+        let allocation = regalloc::allocate(function);
+        self.locations = allocation.locations.clone();
+        self.constants = allocation.constants.clone();
+        match function.name.as_str() {
+            "_init" => writeln!(self.out, "{}_init:", function.class).unwrap(),
+            _ => writeln!(self.out, "{}.{}:", function.class, function.name).unwrap(),
+        }
+        let frame = 12 + 4 * allocation.locals;
+        emit!(self, "addiu\t$sp $sp -{}", frame);
+        emit!(self, "sw\t$fp {}($sp)", frame);
+        emit!(self, "sw\t$s0 {}($sp)", frame - 4);
+        emit!(self, "sw\t$ra {}($sp)", frame - 8);
+        emit!(self, "addiu\t$fp $sp {}", frame - 8);
+        emit!(self, "move\t$s0 $a0");
+        for (i, register) in allocation.saved.iter().enumerate() {
+            emit!(self, "sw\t{} {}($fp)", register, -4 * (i as i32 + 1));
+        }
+        for &(param, register) in &allocation.loads {
+            emit!(self, "lw\t{} {}($fp)", register, 12 + 4 * (function.params - 1 - param.0 as usize));
+        }
+
+        // Only the blocks jumped to need a label; the others are fallen into
+        let mut labels = HashMap::new();
+        for (i, block) in function.blocks.iter().enumerate() {
+            let next = BlockId(i as u32 + 1);
+            let targets = match block.terminator {
+                Terminator::Jump(target) => vec![target],
+                Terminator::Branch { then, orelse, .. } if then == next => vec![orelse],
+                Terminator::Branch { then, orelse, .. } => vec![then, orelse],
+                Terminator::Return(_)
+                | Terminator::NoMatch { .. }
+                | Terminator::DispatchOnVoid { .. }
+                | Terminator::CaseOnVoid { .. }
+                | Terminator::DivisionByZero { .. }
+                | Terminator::IntegerOverflow { .. } => Vec::new(),
+                Terminator::Try { .. } | Terminator::Throw { .. } | Terminator::Rethrow => {
+                    unreachable!("rejected by check_supported")
+                }
+            };
+            for target in targets.into_iter().filter(|&target| target != next) {
+                labels.entry(target).or_insert_with(|| self.label());
+            }
+        }
+        for (i, block) in function.blocks.iter().enumerate() {
+            if let Some(label) = labels.get(&BlockId(i as u32)) {
+                writeln!(self.out, "{}:", label).unwrap();
+            }
+            for inst in &block.insts {
+                self.inst(function, inst);
+            }
+            let next = BlockId(i as u32 + 1);
+            match block.terminator {
+                Terminator::Jump(target) if target == next => {}
+                Terminator::Jump(target) => emit!(self, "b\t{}", labels[&target]),
+                Terminator::Branch { cond, then, orelse } => {
+                    let cond = self.read(cond, "$t1");
+                    if then == next {
+                        emit!(self, "beqz\t{} {}", cond, labels[&orelse]);
+                    } else {
+                        emit!(self, "bnez\t{} {}", cond, labels[&then]);
+                        if orelse != next {
+                            emit!(self, "b\t{}", labels[&orelse]);
+                        }
+                    }
+                }
+                Terminator::Return(value) => {
+                    let value = self.read(value, "$a0");
+                    self.mov("$a0", value);
+                    for (i, register) in allocation.saved.iter().enumerate() {
+                        emit!(self, "lw\t{} {}($fp)", register, -4 * (i as i32 + 1));
+                    }
+                    emit!(self, "lw\t$fp {}($sp)", frame);
+                    emit!(self, "lw\t$s0 {}($sp)", frame - 4);
+                    emit!(self, "lw\t$ra {}($sp)", frame - 8);
+                    emit!(self, "addiu\t$sp $sp {}", frame + 4 * (function.params - 1));
+                    emit!(self, "jr\t$ra");
+                }
+                // Printed here, as the runtime's aborts want the file name
+                Terminator::NoMatch { object, line } => {
+                    let object = self.read(object, "$t1");
+                    self.mov("$t1", object);
+                    self.print_const(&format!("[line {}] No 'case' branch for dynamic type '", line));
+                    // The class name is entry `tag` of `class_nameTab`
+                    emit!(self, "lw\t$t1 0($t1)");
+                    emit!(self, "sll\t$t1 $t1 2");
+                    emit!(self, "la\t$t2 class_nameTab");
+                    emit!(self, "addu\t$t2 $t2 $t1");
+                    emit!(self, "lw\t$a0 0($t2)");
+                    emit!(self, "addiu\t$a0 $a0 {}", 4 * (HEADER_WORDS + 1));
+                    emit!(self, "li\t$v0 4");
+                    emit!(self, "syscall");
+                    self.print_const("'\n");
+                    self.exit_failure();
+                }
+                Terminator::DispatchOnVoid { method, line } => {
+                    self.print_const(&format!("[line {}] Dispatch of '{}' on void\n", line, method));
+                    self.exit_failure();
+                }
+                Terminator::CaseOnVoid { line } => {
+                    self.print_const(&format!("[line {}] Case on void\n", line));
+                    self.exit_failure();
+                }
+                Terminator::DivisionByZero { line } => {
+                    self.print_const(&format!("[line {}] Division by zero\n", line));
+                    self.exit_failure();
+                }
+                Terminator::IntegerOverflow { line } => {
+                    self.print_const(&format!("[line {}] {}\n", line, arith::MESSAGE));
+                    self.exit_failure();
+                }
+                Terminator::Try { .. } | Terminator::Throw { .. } | Terminator::Rethrow => {
+                    unreachable!("rejected by check_supported")
+                }
+            }
+        }
+    }
+
+    /// The register holding `var`, loaded into `scratch` if it is spilled
+    /// or a constant.
+    fn read(&mut self, var: Var, scratch: &'static str) -> &'static str {
+        match self.constants.get(&var) {
+            Some(Inst::Int { value, .. }) => emit!(self, "li\t{} {}", scratch, value),
+            Some(Inst::Bool { value, .. }) => emit!(self, "li\t{} {}", scratch, u8::from(*value)),
+            Some(Inst::Str { index, .. }) => {
+                let label = self.string_const(&self.literals[*index as usize].clone());
+                emit!(self, "la\t{} {}", scratch, label);
+            }
+            Some(_) => return "$zero",
+            None => match self.locations[var.0 as usize].expect("allocated where used") {
+                Location::Register(register) => return register,
+                Location::Frame(offset) => emit!(self, "lw\t{} {}($fp)", scratch, offset),
+            },
+        }
+        scratch
+    }
+
+    /// The value of `var` if it is a constant that fits an `addiu`, whatever
+    /// its sign.
+    fn immediate(&self, var: Var) -> Option<i32> {
+        match self.constants.get(&var) {
+            Some(Inst::Int { value, .. }) if (-32767..=32767).contains(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    /// The register to compute `var` in; `$t0` if it is spilled, to be
+    /// stored by `write`.
+    fn dst(&self, var: Var) -> &'static str {
+        match self.locations[var.0 as usize] {
+            Some(Location::Register(register)) => register,
+            Some(Location::Frame(_)) => "$t0",
+            // An unread result is left where the callee returns it
+            None => "$a0",
+        }
+    }
+
+    fn write(&mut self, var: Var) {
+        if let Some(Location::Frame(offset)) = self.locations[var.0 as usize] {
+            emit!(self, "sw\t$t0 {}($fp)", offset);
+        }
+    }
+
+    /// Print the string constant `s`.
+    fn print_const(&mut self, s: &str) {
+        let label = self.string_const(s);
+        emit!(self, "la\t$a0 {}", label);
+        emit!(self, "addiu\t$a0 $a0 {}", 4 * (HEADER_WORDS + 1));
+        emit!(self, "li\t$v0 4");
+        emit!(self, "syscall");
+    }
+
+    /// Stop the program with exit status 1.
+    fn exit_failure(&mut self) {
+        emit!(self, "li\t$a0 1");
+        emit!(self, "li\t$v0 17");
+        emit!(self, "syscall");
+    }
+
+    fn mov(&mut self, dst: &str, src: &str) {
+        if dst != src {
+            emit!(self, "move\t{} {}", dst, src);
+        }
+    }
+
+    fn push(&mut self, register: &str) {
+        emit!(self, "sw\t{} 0($sp)", register);
+        emit!(self, "addiu\t$sp $sp -4");
+    }
+
+    /// Pop the top of the stack into `register`.
+    fn pop(&mut self, register: &str) {
+        emit!(self, "lw\t{} 4($sp)", register);
+        emit!(self, "addiu\t$sp $sp 4");
+    }
+
+    fn inst(&mut self, function: &Function, inst: &Inst) {
+        let Some(dst) = inst.dst() else {
+            let Inst::SetField { object, index, src } = inst else { unreachable!("rejected by check_supported") };
+            let object = self.read(*object, "$t1");
+            let src = self.read(*src, "$t2");
+            emit!(self, "sw\t{} {}({})", src, 4 * (HEADER_WORDS + *index as usize), object);
+            return;
+        };
+        if self.constants.contains_key(&dst) {
+            // Loaded where it is read
+            return;
+        }
+        if self.locations[dst.0 as usize].is_none() && !matches!(inst, Inst::Call { .. } | Inst::New { .. } | Inst::NewLike { .. }) {
+            // Never read, and without side effects
+            return;
+        }
+        let d = self.dst(dst);
+        match inst {
+            // SPIM expands `li` of any 32-bit value
+            Inst::Int { value, .. } => emit!(self, "li\t{} {}", d, value),
+            Inst::Bool { value, .. } => emit!(self, "li\t{} {}", d, u8::from(*value)),
+            Inst::Str { index, .. } => {
+                let label = self.string_const(&self.literals[*index as usize].clone());
+                emit!(self, "la\t{} {}", d, label);
+            }
+            Inst::Void { .. } => self.mov(d, "$zero"),
+            Inst::Box { src, .. } if self.constants.contains_key(src) => {
+                let label = match self.constants[src] {
+                    Inst::Int { value, .. } => self.int_const(value),
+                    Inst::Bool { value, .. } => format!("bool_const{}", u8::from(value)),
+                    _ => unreachable!("only Int and Bool are boxed"),
+                };
+                emit!(self, "la\t{} {}", d, label);
+            }
+            Inst::Copy { src, .. } => {
+                let src = self.read(*src, "$t1");
+                self.mov(d, src);
+            }
+            Inst::Box { src, .. } if function.vars[src.0 as usize] == Ty::Bool => {
+                let (src, done) = (self.read(*src, "$t1"), self.label());
+                emit!(self, "la\t{} bool_const1", d);
+                emit!(self, "bnez\t{} {}", src, done);
+                emit!(self, "la\t{} bool_const0", d);
+                writeln!(self.out, "{}:", done).unwrap();
+            }
+            Inst::Box { src, .. } => {
+                emit!(self, "la\t$a0 Int_protObj");
+                emit!(self, "jal\tObject.copy");
+                let src = self.read(*src, "$t1");
+                emit!(self, "sw\t{} 12($a0)", src);
+                self.mov(d, "$a0");
+            }
+            Inst::Unbox { src, .. } => {
+                let src = self.read(*src, "$t1");
+                emit!(self, "lw\t{} 12({})", d, src);
+            }
+            Inst::Unary { op, src, .. } => {
+                let src = self.read(*src, "$t1");
+                match op {
+                    UnaryOp::Neg => emit!(self, "negu\t{} {}", d, src),
+                    UnaryOp::Not => emit!(self, "xori\t{} {} 1", d, src),
+                }
+            }
+            Inst::Binary { op: op @ (BinaryOp::Add | BinaryOp::Sub), lhs, rhs, .. } if self.immediate(*rhs).is_some() => {
+                let value = self.immediate(*rhs).unwrap();
+                let lhs = self.read(*lhs, "$t1");
+                let value = if *op == BinaryOp::Sub { -value } else { value };
+                emit!(self, "addiu\t{} {} {}", d, lhs, value);
+            }
+            Inst::Binary { op, lhs, rhs, .. } => {
+                let (lhs, rhs) = (self.read(*lhs, "$t1"), self.read(*rhs, "$t2"));
+                let op = match op {
+                    // Wrapping, as in the interpreter
+                    BinaryOp::Add => "addu",
+                    BinaryOp::Sub => "subu",
+                    BinaryOp::Mul => "mul",
+                    BinaryOp::Div => "div",
+                    BinaryOp::Lt => "slt",
+                    BinaryOp::Le => "sle",
+                    BinaryOp::Eq => "seq",
+                };
+                emit!(self, "{}\t{} {} {}", op, d, lhs, rhs);
+            }
+            // `$v1` and `$a1` are free here: the result is written last
+            Inst::Overflows { op, lhs, rhs, .. } => {
+                let (lhs, rhs) = (self.read(*lhs, "$t1"), self.read(*rhs, "$t2"));
+                match op {
+                    // The sign of the sum differs from the signs of both operands
+                    BinaryOp::Add => {
+                        emit!(self, "addu\t$v1 {} {}", lhs, rhs);
+                        emit!(self, "xor\t$a1 $v1 {}", lhs);
+                        emit!(self, "xor\t$v1 $v1 {}", rhs);
+                        emit!(self, "and\t$v1 $v1 $a1");
+                        emit!(self, "slt\t{} $v1 $zero", d);
+                    }
+                    // The operands' signs differ, and the difference's
+                    // differs from the first's
+                    BinaryOp::Sub => {
+                        emit!(self, "subu\t$v1 {} {}", lhs, rhs);
+                        emit!(self, "xor\t$v1 $v1 {}", lhs);
+                        emit!(self, "xor\t$a1 {} {}", lhs, rhs);
+                        emit!(self, "and\t$v1 $v1 $a1");
+                        emit!(self, "slt\t{} $v1 $zero", d);
+                    }
+                    // The high word of the product is not the sign of the low one
+                    BinaryOp::Mul => {
+                        emit!(self, "mult\t{} {}", lhs, rhs);
+                        emit!(self, "mflo\t$v1");
+                        emit!(self, "mfhi\t$a1");
+                        emit!(self, "sra\t$v1 $v1 31");
+                        emit!(self, "sne\t{} $a1 $v1", d);
+                    }
+                    // Only the most negative word divided by -1
+                    _ => {
+                        emit!(self, "li\t$v1 -2147483648");
+                        emit!(self, "seq\t$v1 {} $v1", lhs);
+                        emit!(self, "li\t$a1 -1");
+                        emit!(self, "seq\t$a1 {} $a1", rhs);
+                        emit!(self, "and\t{} $v1 $a1", d);
+                    }
+                }
+            }
+            // Identical objects are equal; otherwise the runtime compares
+            // Int, String and Bool values
+            Inst::Equal { lhs, rhs, .. } => {
+                let lhs = self.read(*lhs, "$t1");
+                self.mov("$t1", lhs);
+                let rhs = self.read(*rhs, "$t2");
+                self.mov("$t2", rhs);
+                let done = self.label();
+                emit!(self, "la\t$a0 bool_const1");
+                emit!(self, "beq\t$t1 $t2 {}", done);
+                emit!(self, "la\t$a1 bool_const0");
+                emit!(self, "jal\tequality_test");
+                writeln!(self.out, "{}:", done).unwrap();
+                emit!(self, "lw\t{} 12($a0)", d);
+            }
+            Inst::IsVoid { src, .. } => {
+                let src = self.read(*src, "$t1");
+                emit!(self, "seq\t{} {} $zero", d, src);
+            }
+            Inst::New { class, .. } => {
+                emit!(self, "la\t$a0 {}_protObj", class);
+                emit!(self, "jal\tObject.copy");
+                emit!(self, "jal\t{}_init", class);
+                self.mov(d, "$a0");
+            }
+            // Look the class up by the tag of the object
+            Inst::NewLike { object, .. } => {
+                let object = self.read(*object, "$t1");
+                emit!(self, "la\t$t2 class_objTab");
+                emit!(self, "lw\t$t1 0({})", object);
+                emit!(self, "sll\t$t1 $t1 3");
+                emit!(self, "addu\t$t2 $t2 $t1");
+                self.push("$t2");
+                emit!(self, "lw\t$a0 0($t2)");
+                emit!(self, "jal\tObject.copy");
+                self.pop("$t2");
+                emit!(self, "lw\t$t2 4($t2)");
+                emit!(self, "jalr\t$t2");
+                self.mov(d, "$a0");
+            }
+            Inst::GetField { object, index, .. } => {
+                let object = self.read(*object, "$t1");
+                emit!(self, "lw\t{} {}({})", d, 4 * (HEADER_WORDS + *index as usize), object);
+            }
+            // Tags in `first..=last` are those below `last - first + 1`
+            // once `first` is subtracted, unsigned
+            Inst::TagIn { object, first, last, .. } => {
+                let object = self.read(*object, "$t1");
+                emit!(self, "lw\t$t1 0({})", object);
+                if *first > 0 {
+                    emit!(self, "addiu\t$t1 $t1 -{}", first);
+                }
+                emit!(self, "sltiu\t{} $t1 {}", d, last - first + 1);
+            }
+            Inst::Call { callee, args, .. } => {
+                for arg in &args[1..] {
+                    let arg = self.read(*arg, "$t1");
+                    self.push(arg);
+                }
+                let receiver = self.read(args[0], "$a0");
+                self.mov("$a0", receiver);
+                match callee {
+                    Callee::Static { class, method } if method.as_str() == "_init" => emit!(self, "jal\t{}_init", class),
+                    Callee::Static { class, method } => emit!(self, "jal\t{}.{}", class, method),
+                    Callee::Virtual { slot, .. } => {
+                        emit!(self, "lw\t$t1 8($a0)");
+                        emit!(self, "lw\t$t1 {}($t1)", 4 * slot);
+                        emit!(self, "jalr\t$t1");
+                    }
+                }
+                self.mov(d, "$a0");
+            }
+            Inst::Exception { .. } | Inst::EndTry => unreachable!("rejected by check_supported"),
+            Inst::SetField { .. } => unreachable!("emitted above"),
+        }
+        self.write(dst);
+    }
+}
+
+/// Whether `target`, the receiver of a dispatch or the value of a `case`,
+/// can be void, so that it needs a check: not `self`, `new`, a value of a
+/// basic type or an identifier that `never_void` says is an attribute
+/// proven never void (see `NeverVoid`).
+pub(crate) fn may_be_void(target: &TypedExpr, never_void: impl Fn(Symbol) -> bool) -> bool {
+    if matches!(target.static_type, Some(sym::INT | sym::BOOL | sym::STRING)) {
+        return false;
+    }
+    match &target.expr {
+        Expr::New(_) | Expr::Identifier(sym::SELF) => false,
+        Expr::Identifier(name) => !never_void(*name),
+        Expr::Paren(inner) => may_be_void(inner, never_void),
+        _ => true,
+    }
+}
+
+/// Whether the divisor `e` can be zero, so that the division needs a check:
+/// not an `Int` literal other than `0`.
+pub(crate) fn may_be_zero(e: &TypedExpr) -> bool {
+    match &e.expr {
+        Expr::Int(value) => *value == 0,
+        Expr::Paren(inner) => may_be_zero(inner),
+        _ => true,
+    }
+}
+
+#[cfg(feature = "llvm")]
+fn is_basic(class: Symbol) -> bool {
+    matches!(class, sym::OBJECT | sym::IO | sym::STRING | sym::INT | sym::BOOL)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::semantic::type_checker::annotate;
+
+    fn generate_for(source: &str) -> String {
+        let classes = crate::parse_program(source).unwrap();
+        let ctx = AnalysisContext::new(crate::prelude(), &classes);
+        generate(&ctx, &annotate(&ctx), Overflow::Wrap).unwrap()
+    }
+
+    #[test]
+    fn test_generate() {
+        let asm = generate_for(
+            "class Main inherits IO {\n  n : Int <- 3;\n  main() : Object { out_int(n + 1) };\n};",
+        );
+        // Everything the runtime links against is defined
+        for label in [
+            "class_nameTab:", "class_objTab:", "Main_protObj:", "Main_init:", "Main.main:",
+            "Int_protObj:", "String_protObj:", "bool_const0:", "_int_tag:", "heap_start:",
+        ] {
+            assert!(asm.contains(&format!("\n{}", label)), "missing {}", label);
+        }
+        // Main's table inherits IO's methods and is laid out like IO's
+        let table = asm.split("Main_dispTab:\n").nth(1).unwrap();
+        assert!(table.starts_with(
+            "\t.word\tObject.abort\n\t.word\tObject.type_name\n\t.word\tObject.copy\n\t.word\tIO.out_string\n\t.word\tIO.out_int\n"
+        ));
+        // The initializer stores the attribute, a boxed constant, after the
+        // header
+        let init = asm.split("Main_init:\n").nth(1).unwrap();
+        assert!(init.contains("\tjal\tIO_init\n\tla\t$t3 int_const"), "{}", init);
+        assert!(init.contains("\tsw\t$t3 12($s0)\n"), "{}", init);
+        // `n + 1` is unboxed into a register and added in place
+        let main = asm.split("Main.main:\n").nth(1).unwrap();
+        assert!(main.contains("\tlw\t$t3 12($t3)\n\taddiu\t$s1 $t3 1\n"), "{}", main);
+        // Only user methods are emitted; the runtime has the basic ones
+        assert!(!asm.contains("\nIO.out_int:"));
+    }
+
+    #[test]
+    fn test_unsupported() {
+        let extensions: crate::ext::Extensions = [crate::ext::Extension::Arrays].into_iter().collect();
+        let classes = crate::parse_program("class Main { main() : Int { 0 }; };").unwrap();
+        let ctx = AnalysisContext::new(crate::prelude_with(&extensions), &classes);
+        assert!(generate(&ctx, &annotate(&ctx), Overflow::Wrap).is_err());
+    }
+}
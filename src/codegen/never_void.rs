@@ -0,0 +1,130 @@
+//! Attributes that are never void where the methods read them, so that a
+//! dispatch or `case` on one needs no void check.
+
+use std::collections::HashSet;
+
+use super::layout::Layout;
+use super::may_be_void;
+use crate::ast::{Class, Expr, Feature, TypedExpr, VarDecl};
+use crate::symbol::Symbol;
+
+/// The attributes proven never void, by the class whose methods read them:
+/// the declaring class and its descendants.
+#[derive(Debug, Default)]
+pub(crate) struct NeverVoid {
+    attributes: HashSet<(Symbol, Symbol)>,
+}
+
+impl NeverVoid {
+    /// An attribute of class `C` is never void in the methods of `C` and its
+    /// descendants when its initializer is `new`, `self` or a value of a
+    /// basic type, every assignment to it in those classes is too, and no
+    /// method can run before it is initialized: no initializer of `C`'s
+    /// ancestors, or of `C` up to and including it, dispatches. Initializers
+    /// may read attributes before they are set, so they get no facts.
+    pub fn new(layout: &Layout<'_>) -> Self {
+        let mut never_void = NeverVoid::default();
+        for class in &layout.classes {
+            let mut dispatched = false;
+            let mut ancestor = class.class.inherits;
+            while let Some(name) = ancestor {
+                let parent = layout.class(name).class;
+                dispatched |= initializers(parent).any(dispatches);
+                ancestor = parent.inherits;
+            }
+            let descendants = &layout.classes[class.tag..=class.last_descendant];
+            for feature in &class.class.feature_list {
+                let Feature::Attribute(VarDecl { oid, expr: Some(init), .. }) = feature else { continue };
+                dispatched |= dispatches(init);
+                if dispatched || may_be_void(init, |_| false) {
+                    continue;
+                }
+                let reassigned = descendants.iter().any(|d| bodies(d.class).any(|e| assigns_void(e, *oid)));
+                if !reassigned {
+                    never_void.attributes.extend(descendants.iter().map(|d| (d.class.name, *oid)));
+                }
+            }
+        }
+        never_void
+    }
+
+    /// Whether the attribute `attr` is never void in the methods of `class`.
+    pub fn contains(&self, class: Symbol, attr: Symbol) -> bool {
+        self.attributes.contains(&(class, attr))
+    }
+}
+
+fn initializers(class: &Class) -> impl Iterator<Item = &TypedExpr> {
+    class.feature_list.iter().filter_map(|feature| match feature {
+        Feature::Attribute(VarDecl { expr, .. }) => expr.as_ref(),
+        Feature::Method(..) => None,
+    })
+}
+
+/// The initializers and method bodies of `class`.
+fn bodies(class: &Class) -> impl Iterator<Item = &TypedExpr> {
+    class.feature_list.iter().filter_map(|feature| match feature {
+        Feature::Attribute(VarDecl { expr, .. }) => expr.as_ref(),
+        Feature::Method(_, _, _, body, _) => Some(body),
+    })
+}
+
+/// Whether `e` calls a method anywhere.
+fn dispatches(e: &TypedExpr) -> bool {
+    matches!(e.expr, Expr::Dispatch { .. }) || e.expr.children().into_iter().any(dispatches)
+}
+
+/// Whether `e` assigns `attr`, or a local of the same name, a value that may
+/// be void.
+fn assigns_void(e: &TypedExpr, attr: Symbol) -> bool {
+    match &e.expr {
+        Expr::Assignment(name, rhs) if *name == attr && may_be_void(rhs, |_| false) => true,
+        _ => e.expr.children().into_iter().any(|child| assigns_void(child, attr)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::semantic::context::AnalysisContext;
+    use crate::semantic::type_checker::annotate;
+
+    fn never_void(source: &str) -> Vec<(String, String)> {
+        let classes = crate::parse_program(source).unwrap();
+        let ctx = AnalysisContext::new(crate::prelude(), &classes);
+        let typed = annotate(&ctx);
+        let all: Vec<&Class> = ctx.builtins.iter().chain(&typed).collect();
+        let facts = NeverVoid::new(&Layout::new(&all));
+        let mut found: Vec<(String, String)> =
+            facts.attributes.iter().map(|(class, attr)| (class.to_string(), attr.to_string())).collect();
+        found.sort();
+        found
+    }
+
+    #[test]
+    fn test_never_void() {
+        let source = "class A { f() : Int { 1 }; };\n\
+            class B {\n\
+                kept : A <- new A;\n\
+                me : B <- self;\n\
+                itself : B <- self;\n\
+                unset : A;\n\
+                reset : A <- new A;\n\
+                renewed : A <- new A;\n\
+                g() : Object { { reset <- unset; renewed <- new A; } };\n\
+            };\n\
+            class C inherits B { h() : Object { itself <- (let b : B in b) }; };\n\
+            class D { early : Int <- (new A).f(); late : A <- new A; };\n\
+            class Main { main() : Object { 0 }; };";
+        let pair = |class: &str, attr: &str| (class.to_string(), attr.to_string());
+        // `reset` may be assigned void in `B` and `itself` in `C`, and `late`
+        // may be read by the method `early` calls before it is set
+        assert_eq!(
+            never_void(source),
+            [
+                pair("B", "kept"), pair("B", "me"), pair("B", "renewed"),
+                pair("C", "kept"), pair("C", "me"), pair("C", "renewed"),
+            ]
+        );
+    }
+}
@@ -0,0 +1,343 @@
+//! Register allocation for the MIPS backend.
+//!
+//! Linear scan (Poletto and Sarkar) over live intervals: the blocks of a
+//! function are numbered in emission order, and a variable's interval runs
+//! from the first to the last position where it is live. Intervals are
+//! handed registers in order of their start, and when none is free the one
+//! that ends last is spilled to a frame slot. A variable that is live
+//! across a call (including the runtime routines behind `Box`, `New`,
+//! `NewLike` and `Equal`) gets a callee-saved register, which the function
+//! saves in its prologue; any other gets a temporary first. `self` stays in
+//! `$s0`, and a spilled formal stays in its argument slot.
+//!
+//! Some variables need no register at all: constants are loaded where they
+//! are read, and a call result read only as the receiver of the next call
+//! stays in `$a0`.
+
+use std::collections::{BTreeSet, HashMap};
+
+use crate::ir::cfg::Cfg;
+use crate::ir::liveness::Liveness;
+use crate::ir::{Function, Inst, Ty, Var};
+
+/// Registers only ever live between calls. `$t0` to `$t2` are left as
+/// scratch registers for the code of single instructions.
+const TEMPORARIES: [&str; 7] = ["$t3", "$t4", "$t5", "$t6", "$t7", "$t8", "$t9"];
+/// Registers preserved by callees, and so by the runtime.
+const SAVED: [&str; 7] = ["$s1", "$s2", "$s3", "$s4", "$s5", "$s6", "$s7"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum Location {
+    Register(&'static str),
+    /// Byte offset from `$fp`
+    Frame(i32),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(super) struct Allocation {
+    /// Indexed by `Var`; constants and variables never read have none
+    pub locations: Vec<Option<Location>>,
+    /// The variables that are constants, with the instruction defining them
+    pub constants: HashMap<Var, Inst>,
+    /// The callee-saved registers in use, saved in that order below `$fp`
+    pub saved: Vec<&'static str>,
+    /// Words of the frame below `$fp`: the saved registers, then the spills
+    pub locals: usize,
+    /// Formals in registers that are read before they are written, to be
+    /// loaded from their argument slots in the prologue
+    pub loads: Vec<(Var, &'static str)>,
+}
+
+/// The variables only ever assigned a constant: an `Int`, `Bool` or
+/// `String` literal, or void. They need no register, as the constant can
+/// be loaded wherever they are read, and the box of an `Int` or `Bool`
+/// constant is an object of the data section.
+fn constants(function: &Function) -> HashMap<Var, Inst> {
+    let mut defs: HashMap<Var, Option<&Inst>> = HashMap::new();
+    for inst in function.blocks.iter().flat_map(|b| &b.insts) {
+        if let Some(dst) = inst.dst() {
+            let constant = matches!(inst, Inst::Int { .. } | Inst::Bool { .. } | Inst::Str { .. } | Inst::Void { .. });
+            // A second assignment, even of a constant, disqualifies it
+            defs.entry(dst).and_modify(|def| *def = None).or_insert(constant.then_some(inst));
+        }
+    }
+    defs.into_iter().filter_map(|(var, def)| Some((var, def?.clone()))).collect()
+}
+
+/// Whether the code for `inst` calls out, clobbering the temporaries.
+fn calls(inst: &Inst, vars: &[Ty], constants: &HashMap<Var, Inst>) -> bool {
+    match inst {
+        Inst::Box { src, .. } => vars[src.0 as usize] == Ty::Int && !constants.contains_key(src),
+        Inst::New { .. } | Inst::NewLike { .. } | Inst::Equal { .. } | Inst::Call { .. } => true,
+        _ => false,
+    }
+}
+
+/// Whether the code for `inst` reads all its operands before it writes
+/// its result, so that the result can share a register with one of them.
+fn reads_first(inst: &Inst, vars: &[Ty]) -> bool {
+    match inst {
+        Inst::Box { src, .. } => vars[src.0 as usize] == Ty::Int,
+        Inst::SetField { .. } => false,
+        _ => true,
+    }
+}
+
+/// Whether the code for `inst` leaves `$a0` alone.
+fn keeps_a0(inst: &Inst, vars: &[Ty], constants: &HashMap<Var, Inst>) -> bool {
+    match inst {
+        Inst::Box { .. } => !calls(inst, vars, constants),
+        Inst::Equal { .. } | Inst::New { .. } | Inst::NewLike { .. } | Inst::Call { .. } => false,
+        _ => true,
+    }
+}
+
+/// The results of calls, which arrive in `$a0`, that can stay there: those
+/// read once, later in the same block, as the receiver of a call or the
+/// value of the terminator, with nothing in between that uses `$a0`.
+fn results_in_a0(function: &Function, constants: &HashMap<Var, Inst>) -> BTreeSet<Var> {
+    let mut reads = vec![0; function.vars.len()];
+    let mut writes = vec![0; function.vars.len()];
+    for block in &function.blocks {
+        for inst in &block.insts {
+            for var in inst.uses() {
+                reads[var.0 as usize] += 1;
+            }
+            if let Some(dst) = inst.dst() {
+                writes[dst.0 as usize] += 1;
+            }
+        }
+        if let Some(var) = block.terminator.uses() {
+            reads[var.0 as usize] += 1;
+        }
+    }
+    let mut in_a0 = BTreeSet::new();
+    for block in &function.blocks {
+        // The call result still in `$a0`, if any
+        let mut pending: Option<Var> = None;
+        for inst in &block.insts {
+            if let (Some(var), Inst::Call { args, .. }) = (pending, inst) {
+                if args[0] == var && !args[1..].contains(&var) {
+                    in_a0.insert(var);
+                }
+            }
+            if pending.is_some_and(|var| inst.uses().contains(&var) || !keeps_a0(inst, &function.vars, constants)) {
+                pending = None;
+            }
+            if let Inst::Call { dst, .. } | Inst::New { dst, .. } | Inst::NewLike { dst, .. } = inst {
+                if reads[dst.0 as usize] == 1 && writes[dst.0 as usize] == 1 {
+                    pending = Some(*dst);
+                }
+            }
+        }
+        if let Some(var) = pending {
+            if block.terminator.uses() == Some(var) {
+                in_a0.insert(var);
+            }
+        }
+    }
+    in_a0
+}
+
+#[derive(Debug)]
+struct Interval {
+    var: Var,
+    start: usize,
+    end: usize,
+    /// Live across a call
+    saved: bool,
+}
+
+pub(super) fn allocate(function: &Function) -> Allocation {
+    let cfg = Cfg::new(function);
+    let liveness = Liveness::new(function, &cfg);
+    let constants = constants(function);
+    // A variable never read needs no location
+    let read: BTreeSet<Var> = function
+        .blocks
+        .iter()
+        .flat_map(|b| b.insts.iter().flat_map(Inst::uses).chain(b.terminator.uses()))
+        .collect();
+    let vars = function.vars.len();
+    let (mut start, mut end) = (vec![usize::MAX; vars], vec![0; vars]);
+    let mut saved = vec![false; vars];
+    let mut extend = |var: Var, position: usize| {
+        let v = var.0 as usize;
+        start[v] = start[v].min(position);
+        end[v] = end[v].max(position);
+    };
+    // The instruction at each position, if not a terminator
+    let mut insts = Vec::new();
+    let mut position = 0;
+    for (b, block) in function.blocks.iter().enumerate() {
+        let before = liveness.live_before(function, b);
+        for var in &liveness.live_in[b] {
+            extend(*var, position);
+        }
+        for (i, inst) in block.insts.iter().enumerate() {
+            for var in before[i].iter().chain(&inst.uses()).chain(inst.dst().iter().filter(|v| read.contains(v))) {
+                extend(*var, position);
+            }
+            if calls(inst, &function.vars, &constants) {
+                let mut across: BTreeSet<Var> = before[i + 1].clone();
+                if let Some(dst) = inst.dst() {
+                    across.remove(&dst);
+                }
+                // The word is stored into the new object after the call
+                if let Inst::Box { src, .. } = inst {
+                    across.insert(*src);
+                }
+                for var in across {
+                    saved[var.0 as usize] = true;
+                }
+            }
+            insts.push(Some(inst));
+            position += 1;
+        }
+        for var in before[block.insts.len()].iter().chain(&liveness.live_out[b]) {
+            extend(*var, position);
+        }
+        insts.push(None);
+        position += 1;
+    }
+
+    let in_a0 = results_in_a0(function, &constants);
+    let mut intervals: Vec<Interval> = (1..vars)
+        .filter(|&v| start[v] != usize::MAX)
+        .filter(|&v| !in_a0.contains(&Var(v as u32)) && !constants.contains_key(&Var(v as u32)))
+        .map(|v| Interval { var: Var(v as u32), start: start[v], end: end[v], saved: saved[v] })
+        .collect();
+    intervals.sort_by_key(|interval| (interval.start, interval.var));
+
+    let mut locations = vec![None; vars];
+    locations[0] = Some(Location::Register("$s0"));
+    for var in &in_a0 {
+        locations[var.0 as usize] = Some(Location::Register("$a0"));
+    }
+    let mut spills = Vec::new();
+    // Allocated intervals still live, with their registers
+    let mut active: Vec<(usize, &'static str)> = Vec::new();
+    for (i, interval) in intervals.iter().enumerate() {
+        active.retain(|&(j, _)| intervals[j].end >= interval.start);
+        // A result can take the register of an operand read for the last
+        // time, which saves the move of a copy
+        let defined = insts[interval.start].filter(|inst| inst.dst() == Some(interval.var));
+        if let Some(inst) = defined.filter(|inst| reads_first(inst, &function.vars)) {
+            let operand = active.iter().position(|&(j, register)| {
+                intervals[j].end == interval.start
+                    && inst.uses().contains(&intervals[j].var)
+                    && (!interval.saved || SAVED.contains(&register))
+            });
+            if let Some(k) = operand {
+                let register = active[k].1;
+                active[k] = (i, register);
+                locations[interval.var.0 as usize] = Some(Location::Register(register));
+                continue;
+            }
+        }
+        let free = |pool: &[&'static str]| pool.iter().copied().find(|r| active.iter().all(|(_, a)| a != r));
+        let register = if interval.saved { free(&SAVED) } else { free(&TEMPORARIES).or_else(|| free(&SAVED)) };
+        if let Some(register) = register {
+            active.push((i, register));
+            locations[interval.var.0 as usize] = Some(Location::Register(register));
+            continue;
+        }
+        // Spill whichever interval ends last, of those whose register this
+        // one could use
+        let victim = active
+            .iter()
+            .enumerate()
+            .filter(|(_, (_, r))| !interval.saved || SAVED.contains(r))
+            .max_by_key(|(_, (j, _))| intervals[*j].end)
+            .map(|(k, &(j, r))| (k, j, r));
+        match victim {
+            Some((k, j, register)) if intervals[j].end > interval.end => {
+                active[k] = (i, register);
+                locations[interval.var.0 as usize] = Some(Location::Register(register));
+                spills.push(intervals[j].var);
+            }
+            _ => spills.push(interval.var),
+        }
+    }
+
+    let used: Vec<&'static str> =
+        SAVED.iter().copied().filter(|r| locations.contains(&Some(Location::Register(r)))).collect();
+    let mut locals = used.len();
+    let formals = function.params - 1;
+    for var in spills {
+        let v = var.0 as usize;
+        locations[v] = Some(if v < function.params {
+            // The first argument was pushed first, so it is the deepest
+            Location::Frame(12 + 4 * (formals - v) as i32)
+        } else {
+            locals += 1;
+            Location::Frame(-4 * locals as i32)
+        });
+    }
+    let loads = liveness.live_in[0]
+        .iter()
+        .filter(|var| (var.0 as usize) < function.params)
+        .filter_map(|&var| match locations[var.0 as usize] {
+            Some(Location::Register(register)) if var.0 > 0 => Some((var, register)),
+            _ => None,
+        })
+        .collect();
+    Allocation { locations, constants, saved: used, locals, loads }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::semantic::context::AnalysisContext;
+    use crate::semantic::type_checker::annotate;
+
+    fn allocate_main(source: &str) -> (Function, Allocation) {
+        let classes = crate::parse_program(source).unwrap();
+        let ctx = AnalysisContext::new(crate::prelude(), &classes);
+        let program = crate::ir::lower(&ctx, &annotate(&ctx), crate::arith::Overflow::Wrap).unwrap();
+        let function = program.functions.into_iter().find(|f| f.name.as_str() == "main").unwrap();
+        let allocation = allocate(&function);
+        (function, allocation)
+    }
+
+    #[test]
+    fn test_allocate() {
+        let (function, allocation) = allocate_main(
+            "class Main inherits IO {\n\
+                main() : Object { let x : Int <- in_int(), y : Int <- in_int() in { out_int(x * y); x + y; } };\n\
+            };",
+        );
+        // `x` and `y` are live across the call and need saved registers;
+        // nothing is spilled
+        let location = |v: u32| allocation.locations[v as usize];
+        let (x, y) = (1, 4);
+        assert_eq!((location(x), location(y)), (Some(Location::Register("$s1")), Some(Location::Register("$s2"))));
+        assert_eq!(allocation.saved[..2], ["$s1", "$s2"]);
+        assert!(allocation.locations.iter().all(|l| !matches!(l, Some(Location::Frame(_)))));
+        // The values read once, right after `in_int` returns, share a
+        // temporary
+        let temporaries: BTreeSet<&str> = (0..function.vars.len() as u32)
+            .filter_map(|v| match location(v) {
+                Some(Location::Register(r)) if r.starts_with("$t") => Some(r),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(temporaries, ["$t3"].into(), "{:?}", allocation);
+    }
+
+    #[test]
+    fn test_spill() {
+        // Ten values live at once across a call: three of them are spilled
+        let names: Vec<String> = (0..10).map(|i| format!("a{}", i)).collect();
+        let bindings: Vec<String> = names.iter().enumerate().map(|(i, n)| format!("{} : Int <- in_int() + {}", n, i)).collect();
+        let source = format!(
+            "class Main inherits IO {{\n main() : Object {{ let {} in {{ out_int(0); {}; }} }};\n}};",
+            bindings.join(", "),
+            names.join(" + ")
+        );
+        let (_, allocation) = allocate_main(&source);
+        let frames = allocation.locations.iter().filter(|l| matches!(l, Some(Location::Frame(_)))).count();
+        assert_eq!((allocation.saved.len(), frames), (7, 3));
+        assert_eq!(allocation.locals, 10);
+    }
+}
@@ -0,0 +1,192 @@
+//! Library entry point for embedders.
+//!
+//! `Compiler` runs the pipeline one stage at a time, so tools other than the
+//! binary can stop where they need to: `lex` for a highlighter, `parse` for
+//! an outline, `check` (or `analyze` on already-parsed classes) for
+//! diagnostics. Every stage returns its result; none prints or exits.
+//!
+//! ```
+//! use cool_rs::compiler::Compiler;
+//!
+//! let compiler = Compiler::default();
+//! let source = "class Main { main() : Int { 1 + true }; };";
+//! assert_eq!(compiler.lex(source).unwrap().len(), 16);
+//! let classes = compiler.parse(source).unwrap();
+//! let mut ec = Default::default();
+//! compiler.analyze(&classes, &mut ec);
+//! assert_eq!(ec.errors[0].code(), "E0010");
+//! ```
+//!
+//! A host application that wants to extend the COOL environment registers
+//! extra built-in classes on a `Compiler` instead of editing the prelude in
+//! `lib.rs`:
+//!
+//! ```
+//! use cool_rs::compiler::Compiler;
+//!
+//! let mut compiler = Compiler::default();
+//! compiler.register_builtin("class Clock { now() : Int native; };").unwrap();
+//! let errors = compiler.check("class Main { main() : Int { (new Clock).now() }; };").unwrap();
+//! assert!(errors.is_empty());
+//! ```
+
+use eyre::{bail, ensure, Result};
+
+use crate::ast::{Class, Expr, Feature, TypedExpr};
+use crate::ext::{Extension, Extensions};
+use crate::parsing::scanner::Scanner;
+use crate::parsing::token::{LexicalError, Loc, Token};
+use crate::semantic::collector::ErrorCollector;
+use crate::semantic::context::AnalysisContext;
+use crate::semantic::errors::SemanticError;
+use crate::symbol::sym;
+
+/// Compiles programs against the standard prelude plus any registered classes.
+pub struct Compiler {
+    extensions: Extensions,
+    builtins: Vec<Class>,
+}
+
+impl Default for Compiler {
+    fn default() -> Self {
+        Compiler::new(Extensions::default())
+    }
+}
+
+impl Compiler {
+    pub fn new(extensions: Extensions) -> Self {
+        let builtins = crate::prelude_with(&extensions).to_vec();
+        Compiler { extensions, builtins }
+    }
+
+    /// Add a built-in class, given as its COOL declaration.
+    ///
+    /// Every method must be declared `native`: the embedder supplies the
+    /// implementations, so its bodies are never type-checked. The parent,
+    /// if any, must itself be a built-in other than `Int`, `String` or `Bool`.
+    pub fn register_builtin(&mut self, signature: &str) -> Result<()> {
+        let extensions: Extensions = [Extension::Natives].into_iter().collect();
+        let classes = crate::parse_program_with(signature, &extensions)?;
+        let [class] = <[Class; 1]>::try_from(classes).map_err(|classes| {
+            eyre::eyre!("Expected one class declaration, found {}", classes.len())
+        })?;
+
+        ensure!(
+            !self.builtins.iter().any(|c| c.name == class.name),
+            "Built-in class '{}' is already defined",
+            class.name
+        );
+        let parent = class.inherits.unwrap_or(sym::OBJECT);
+        if matches!(parent, sym::INT | sym::STRING | sym::BOOL)
+            || !self.builtins.iter().any(|c| c.name == parent)
+        {
+            bail!("Built-in class '{}' cannot inherit from '{}'", class.name, parent);
+        }
+        for feature in &class.feature_list {
+            if let Feature::Method(name, _, _, body, _) = feature {
+                ensure!(
+                    matches!(body.expr, Expr::Native),
+                    "Method '{}.{}' of a built-in class must be declared native",
+                    class.name,
+                    name
+                );
+            }
+        }
+
+        self.builtins.push(class);
+        Ok(())
+    }
+
+    /// The language extensions programs are compiled with.
+    pub fn extensions(&self) -> &Extensions {
+        &self.extensions
+    }
+
+    /// The standard prelude followed by the registered classes.
+    pub fn builtins(&self) -> &[Class] {
+        &self.builtins
+    }
+
+    /// The tokens of `source`, or the first lexical error.
+    pub fn lex(&self, source: &str) -> Result<Vec<(Token, Loc)>, LexicalError> {
+        Scanner::with_extensions(source, self.extensions.clone()).scan_tokens()
+    }
+
+    /// Parse `source` into the user's classes (built-ins not included).
+    pub fn parse(&self, source: &str) -> Result<Vec<Class>> {
+        crate::parse_program_with(source, &self.extensions)
+    }
+
+    /// Parse `source` as a single expression.
+    pub fn parse_expression(&self, source: &str) -> Result<TypedExpr> {
+        crate::parse_expression(source, &self.extensions)
+    }
+
+    /// The analysis context for `classes` on top of this compiler's built-ins.
+    pub fn context<'a>(&'a self, classes: &'a [Class]) -> AnalysisContext<'a> {
+        AnalysisContext::new(&self.builtins, classes)
+    }
+
+    /// Run the semantic phases on parsed `classes`, reporting into `ec`,
+    /// whose levels decide which diagnostics are errors. Returns the classes
+    /// with their expressions' static types once type checking has run.
+    pub fn analyze(&self, classes: &[Class], ec: &mut ErrorCollector) -> Option<Vec<Class>> {
+        crate::check_program(&self.context(classes), ec)
+    }
+
+    /// Parse and check `source`. A parse error is returned as `Err`; semantic
+    /// errors are returned as the diagnostics of an otherwise successful run.
+    pub fn check(&self, source: &str) -> Result<Vec<SemanticError>> {
+        let classes = self.parse(source)?;
+        let mut ec = ErrorCollector::default();
+        self.analyze(&classes, &mut ec);
+        Ok(ec.errors)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_registered_builtins() {
+        let mut compiler = Compiler::default();
+        compiler
+            .register_builtin("class Counter inherits IO { count : Int; next(by : Int) : Int native; };")
+            .unwrap();
+        let errors = compiler
+            .check("class Main { c : Counter <- new Counter; main() : Object { c.next(1) + c.next(\"2\") }; };")
+            .unwrap();
+        assert_eq!(errors.iter().map(|e| e.code()).collect::<Vec<_>>(), ["E0010"]);
+        // A user class cannot take a registered name
+        let errors = compiler.check("class Counter {}; class Main { main() : Int { 0 }; };").unwrap();
+        assert_eq!(errors.iter().map(|e| e.code()).collect::<Vec<_>>(), ["E0001"]);
+
+        let rejected = [
+            "class Counter { };",
+            "class Boxed inherits Int { };",
+            "class Later inherits Main { };",
+            "class Eager { f() : Int { 0 }; };",
+            "class A { }; class B { };",
+        ];
+        for signature in rejected {
+            assert!(compiler.register_builtin(signature).is_err(), "{}", signature);
+        }
+    }
+
+    #[test]
+    fn test_stages() {
+        let compiler = Compiler::default();
+        let err = compiler.lex("class A { x : Int <- 1 # 2; };").unwrap_err();
+        assert!(matches!(err, LexicalError::InvalidChar('#', _)), "{:?}", err);
+        assert!(compiler.parse("class A { x : Int <- ; };").is_err());
+
+        let classes = compiler.parse("class Main { main() : Int { let x : Int in 0 }; };").unwrap();
+        let mut ec = ErrorCollector::default();
+        let typed = compiler.analyze(&classes, &mut ec).unwrap();
+        assert!(!ec.has_errors());
+        let Feature::Method(_, _, _, body, _) = &typed[0].feature_list[0] else { unreachable!() };
+        assert_eq!(body.static_type, Some(sym::INT));
+        assert_eq!(ec.warnings.iter().map(|e| e.code()).collect::<Vec<_>>(), ["W0001"]);
+    }
+}
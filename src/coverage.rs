@@ -0,0 +1,226 @@
+//! Grammar coverage of a corpus.
+//!
+//! Counts how often each syntactic construct appears in a set of parsed
+//! programs, so constructs the test suite never exercises (static dispatch,
+//! a `let` with several bindings, ...) show up as zeros.
+
+use std::fmt::Write;
+
+use crate::ast::{
+    Class, ComparisonOperator, Expr, Feature, MathOperator, TypedExpr, UnaryOperator, VarDecl,
+};
+use crate::symbol::sym;
+
+/// Every construct that is counted, in report order.
+pub const CONSTRUCTS: &[&str] = &[
+    "class",
+    "class with inherits",
+    "attribute",
+    "attribute with initializer",
+    "method",
+    "method with parameters",
+    "identifier",
+    "self",
+    "boolean literal",
+    "integer literal",
+    "string literal",
+    "new",
+    "block",
+    "block with several expressions",
+    "case",
+    "case with several branches",
+    "parentheses",
+    "let",
+    "let with several bindings",
+    "let binding with initializer",
+    "let binding without initializer",
+    "<",
+    "<=",
+    "=",
+    "+",
+    "-",
+    "*",
+    "/",
+    "~",
+    "not",
+    "assignment",
+    "if",
+    "while",
+    "isvoid",
+    "dispatch on self",
+    "dispatch on an expression",
+    "static dispatch (@)",
+    "dispatch with arguments",
+];
+
+/// Occurrence counts for every entry of `CONSTRUCTS`.
+pub struct Coverage {
+    counts: Vec<usize>,
+}
+
+impl Default for Coverage {
+    fn default() -> Self {
+        Coverage { counts: vec![0; CONSTRUCTS.len()] }
+    }
+}
+
+impl Coverage {
+    /// Occurrences of `construct` recorded so far.
+    pub fn count(&self, construct: &str) -> usize {
+        self.counts[index(construct)]
+    }
+
+    /// Constructs that were never recorded.
+    pub fn unexercised(&self) -> Vec<&'static str> {
+        CONSTRUCTS
+            .iter()
+            .zip(&self.counts)
+            .filter(|(_, &n)| n == 0)
+            .map(|(&name, _)| name)
+            .collect()
+    }
+
+    /// One line per construct with its count, unexercised ones marked.
+    pub fn report(&self) -> String {
+        let mut out = String::new();
+        for (name, &n) in CONSTRUCTS.iter().zip(&self.counts) {
+            let mark = if n == 0 { "  <- never exercised" } else { "" };
+            writeln!(out, "{:<32} {:>6}{}", name, n, mark).unwrap();
+        }
+        out
+    }
+
+    fn hit(&mut self, construct: &str) {
+        self.counts[index(construct)] += 1;
+    }
+
+    pub fn record_program(&mut self, classes: &[Class]) {
+        for class in classes {
+            self.hit("class");
+            if class.inherits.is_some() {
+                self.hit("class with inherits");
+            }
+            for feature in &class.feature_list {
+                match feature {
+                    Feature::Attribute(VarDecl { expr, .. }) => {
+                        self.hit("attribute");
+                        if let Some(init) = expr {
+                            self.hit("attribute with initializer");
+                            self.record_expr(init);
+                        }
+                    }
+                    Feature::Method(_, args, _, body, _) => {
+                        self.hit("method");
+                        if !args.is_empty() {
+                            self.hit("method with parameters");
+                        }
+                        self.record_expr(body);
+                    }
+                }
+            }
+        }
+    }
+
+    fn record_expr(&mut self, e: &TypedExpr) {
+        match &e.expr {
+            Expr::Identifier(name) if *name == sym::SELF => self.hit("self"),
+            Expr::Identifier(_) => self.hit("identifier"),
+            Expr::Bool(_) => self.hit("boolean literal"),
+            Expr::Int(_) => self.hit("integer literal"),
+            Expr::Str(_) => self.hit("string literal"),
+            Expr::New(_) => self.hit("new"),
+            Expr::Block(exprs) => {
+                self.hit("block");
+                if exprs.len() > 1 {
+                    self.hit("block with several expressions");
+                }
+            }
+            Expr::Case(_, branches) => {
+                self.hit("case");
+                if branches.len() > 1 {
+                    self.hit("case with several branches");
+                }
+            }
+            Expr::Paren(_) => self.hit("parentheses"),
+            Expr::Let(bindings, _) => {
+                self.hit("let");
+                if bindings.len() > 1 {
+                    self.hit("let with several bindings");
+                }
+                for binding in bindings {
+                    match binding.expr {
+                        Some(_) => self.hit("let binding with initializer"),
+                        None => self.hit("let binding without initializer"),
+                    }
+                }
+            }
+            Expr::Comparison { op, .. } => self.hit(match op {
+                ComparisonOperator::Lt => "<",
+                ComparisonOperator::Le => "<=",
+                ComparisonOperator::Equal => "=",
+            }),
+            Expr::Math { op, .. } => self.hit(match op {
+                MathOperator::Add => "+",
+                MathOperator::Subtract => "-",
+                MathOperator::Mul => "*",
+                MathOperator::Div => "/",
+            }),
+            Expr::UnaryOperation { op, .. } => self.hit(match op {
+                UnaryOperator::Neg => "~",
+                UnaryOperator::Not => "not",
+            }),
+            Expr::Assignment(..) => self.hit("assignment"),
+            Expr::Conditional { .. } => self.hit("if"),
+            Expr::While { .. } => self.hit("while"),
+            Expr::Isvoid(_) => self.hit("isvoid"),
+            Expr::Dispatch { target, targettype, exprs, .. } => {
+                match (target, targettype) {
+                    (None, _) => self.hit("dispatch on self"),
+                    (Some(_), None) => self.hit("dispatch on an expression"),
+                    (Some(_), Some(_)) => self.hit("static dispatch (@)"),
+                }
+                if !exprs.is_empty() {
+                    self.hit("dispatch with arguments");
+                }
+            }
+            // Extension syntax is not part of the grammar being measured
+            Expr::Try { .. } | Expr::Throw(_) | Expr::Native | Expr::Builtin => {}
+        }
+        for child in e.expr.children() {
+            self.record_expr(child);
+        }
+    }
+}
+
+fn index(construct: &str) -> usize {
+    CONSTRUCTS
+        .iter()
+        .position(|&c| c == construct)
+        .unwrap_or_else(|| panic!("unknown construct {:?}", construct))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_counts_and_gaps() {
+        let classes = crate::parse_program(
+            "class Main inherits IO {
+                main() : Object { let x : Int <- 1, y : Int in out_int(x@Int.copy() + y) };
+            };",
+        )
+        .unwrap();
+        let mut coverage = Coverage::default();
+        coverage.record_program(&classes);
+
+        assert_eq!(coverage.count("let with several bindings"), 1);
+        assert_eq!(coverage.count("let binding without initializer"), 1);
+        assert_eq!(coverage.count("static dispatch (@)"), 1);
+        assert_eq!(coverage.count("dispatch on self"), 1);
+        assert_eq!(coverage.count("identifier"), 2);
+        let gaps = coverage.unexercised();
+        assert!(gaps.contains(&"while") && gaps.contains(&"case"));
+        assert!(!gaps.contains(&"+"));
+    }
+}
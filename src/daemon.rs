@@ -0,0 +1,163 @@
+//! Resident compile server for scripted batch workloads.
+//!
+//! Clients connect to a Unix socket and send one JSON request per line:
+//!
+//! ```text
+//! {"request": "check", "file": "tests/valid/hello.cl"}
+//! {"request": "shutdown"}
+//! ```
+//!
+//! Each `check` is answered with one JSON line
+//! (`{"file": ..., "ok": ..., "cached": ..., "diagnostics": [...]}`).
+//! The interner, the built-in prelude and per-source results stay warm
+//! between requests, so re-checking an unchanged file costs one hash.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+
+use eyre::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::cache::content_hash;
+use crate::semantic::collector::ErrorCollector;
+use crate::semantic::context::AnalysisContext;
+use crate::source::SourceText;
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "request", rename_all = "snake_case")]
+enum Request {
+    Check { file: PathBuf },
+    Shutdown,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct Response {
+    file: PathBuf,
+    ok: bool,
+    cached: bool,
+    diagnostics: Vec<String>,
+}
+
+/// State kept alive across requests.
+struct Daemon {
+    /// Rendered diagnostics per source content hash
+    results: HashMap<u64, Vec<String>>,
+}
+
+impl Daemon {
+    fn new() -> Self {
+        Daemon {
+            results: HashMap::new(),
+        }
+    }
+
+    fn check(&mut self, file: PathBuf) -> Response {
+        let source = match SourceText::read(&file) {
+            Ok(source) => source,
+            Err(err) => {
+                return Response {
+                    file,
+                    ok: false,
+                    cached: false,
+                    diagnostics: vec![format!("{:#}", err)],
+                };
+            }
+        };
+
+        let key = content_hash(source.as_str());
+        let cached = self.results.contains_key(&key);
+        let diagnostics = self
+            .results
+            .entry(key)
+            .or_insert_with(|| compile(source.as_str()))
+            .clone();
+
+        Response { file, ok: diagnostics.is_empty(), cached, diagnostics }
+    }
+}
+
+/// Parse and check `source`, rendering every diagnostic it produces.
+fn compile(source: &str) -> Vec<String> {
+    let user = match crate::parse_program(source) {
+        Ok(user) => user,
+        Err(err) => return vec![format!("{:#}", err)],
+    };
+    let ctx = AnalysisContext::new(crate::prelude(), &user);
+    let mut ec = ErrorCollector::default();
+    crate::check_program(&ctx, &mut ec);
+    ec.errors.iter().map(|e| e.to_string()).collect()
+}
+
+/// Listen on `socket` until a client sends a `shutdown` request.
+pub fn run(socket: &Path) -> Result<()> {
+    // A socket file left behind by a previous daemon would make bind fail
+    if socket.exists() {
+        fs::remove_file(socket)
+            .wrap_err_with(|| format!("Failed to remove stale socket {:?}", socket))?;
+    }
+    let listener = UnixListener::bind(socket)
+        .wrap_err_with(|| format!("Failed to listen on {:?}", socket))?;
+    eprintln!("cool-rs daemon listening on {}", socket.display());
+
+    let mut daemon = Daemon::new();
+    for stream in listener.incoming() {
+        let stream = stream.wrap_err("Failed to accept connection")?;
+        match serve(&mut daemon, stream) {
+            Ok(true) => break,
+            Ok(false) => {}
+            // A misbehaving client must not take the daemon down
+            Err(err) => eprintln!("cool-rs daemon: {:#}", err),
+        }
+    }
+
+    fs::remove_file(socket).ok();
+    Ok(())
+}
+
+/// Answer every request on one connection. Returns `true` on `shutdown`.
+fn serve(daemon: &mut Daemon, stream: UnixStream) -> Result<bool> {
+    let mut writer = stream.try_clone()?;
+    for line in BufReader::new(stream).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let reply = match serde_json::from_str::<Request>(&line) {
+            Ok(Request::Check { file }) => serde_json::to_string(&daemon.check(file))?,
+            Ok(Request::Shutdown) => return Ok(true),
+            Err(err) => serde_json::json!({ "error": err.to_string() }).to_string(),
+        };
+        writeln!(writer, "{}", reply)?;
+    }
+    Ok(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_reuses_results() {
+        let dir = std::env::temp_dir().join(format!("cool-rs-daemon-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let good = dir.join("good.cl");
+        let bad = dir.join("bad.cl");
+        fs::write(&good, "class Main { main(): Int { 1 + 2 }; };\n").unwrap();
+        fs::write(&bad, "class Main { main(): Object { x }; };\n").unwrap();
+
+        let mut daemon = Daemon::new();
+        let first = daemon.check(good.clone());
+        assert!(first.ok && !first.cached);
+        let second = daemon.check(good);
+        assert!(second.ok && second.cached);
+
+        let failed = daemon.check(bad);
+        assert!(!failed.ok);
+        assert_eq!(failed.diagnostics, vec!["[line 1] Variable 'x' is not declared".to_string()]);
+
+        fs::remove_dir_all(dir).ok();
+    }
+}
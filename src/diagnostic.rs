@@ -0,0 +1,208 @@
+//! What the compiler reports about a program, whichever phase found it.
+//!
+//! Lexical errors, syntax errors and semantic diagnostics are each turned
+//! into a `Diagnostic`, and a `Reporter` prints them all the same way (see
+//! `render`). Whether the program is rejected depends only on the levels of
+//! the diagnostics reported, not on the phase they came from.
+
+use lalrpop_util::ParseError;
+
+use crate::parsing::recovery::SyntaxError;
+use crate::parsing::token::{Loc, Span};
+use crate::semantic::errors::SemanticError;
+use crate::semantic::severity::Level;
+
+pub mod render;
+
+use render::{render, SourceFile};
+
+/// The phase of the compiler a diagnostic comes from, in the order they run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Phase {
+    Lexing,
+    Parsing,
+    Semantic,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub phase: Phase,
+    pub level: Level,
+    /// Stable identifier, e.g. `E0010`
+    pub code: &'static str,
+    pub message: String,
+    pub help: Option<String>,
+    /// Input file, indexed as in `Class::file`; `None` for program-wide diagnostics
+    pub file: Option<usize>,
+    pub line: Option<usize>,
+    pub span: Option<Span>,
+}
+
+impl Diagnostic {
+    /// `err` as reported at `level`.
+    pub fn semantic(err: &SemanticError, level: Level) -> Self {
+        Diagnostic {
+            phase: Phase::Semantic,
+            level,
+            code: err.code(),
+            message: err.message(),
+            help: err.help(),
+            file: err.file(),
+            line: err.line(),
+            span: err.span(),
+        }
+    }
+
+    /// A syntax or lexical error in `source`, input file `file`.
+    pub fn syntax(err: &SyntaxError, file: usize, source: &str) -> Self {
+        let diagnostic = |code, message, help, line, start, end| Diagnostic {
+            phase: Phase::Parsing,
+            level: Level::Deny,
+            code,
+            message,
+            help,
+            file: Some(file),
+            line: Some(line),
+            span: Some(Span { file, start, end }),
+        };
+        match err {
+            ParseError::InvalidToken { location } => {
+                diagnostic("E0034", "Invalid token".to_string(), None, location.line, location.offset, location.offset)
+            }
+            ParseError::UnrecognizedEof { location, expected } => diagnostic(
+                "E0035",
+                "Unexpected end of file".to_string(),
+                expected_help(expected),
+                location.line,
+                location.offset,
+                location.offset,
+            ),
+            ParseError::UnrecognizedToken { token: (start, token, end), expected } => diagnostic(
+                "E0036",
+                format!("Unrecognized token `{}`", token),
+                expected_help(expected),
+                start.line,
+                start.offset,
+                end.offset,
+            ),
+            ParseError::ExtraToken { token: (start, token, end) } => diagnostic(
+                "E0037",
+                format!("Extra token `{}`", token),
+                None,
+                start.line,
+                start.offset,
+                end.offset,
+            ),
+            ParseError::User { error } => {
+                let start = offset(source, error.loc());
+                let end = source[start..].chars().next().map_or(start, |c| start + c.len_utf8());
+                Diagnostic {
+                    phase: Phase::Lexing,
+                    ..diagnostic(error.code(), error.message(), None, error.loc().line, start, end)
+                }
+            }
+        }
+    }
+}
+
+/// `expected one of "a", "b" or "c"`, as lalrpop words it.
+fn expected_help(expected: &[String]) -> Option<String> {
+    match expected {
+        [] => None,
+        [only] => Some(format!("expected {}", only)),
+        [init @ .., last] => Some(format!("expected one of {} or {}", init.join(", "), last)),
+    }
+}
+
+/// Byte offset of `loc` in `source`. Columns count bytes from 1.
+fn offset(source: &str, loc: Loc) -> usize {
+    let line_start = source.split_inclusive('\n').take(loc.line.saturating_sub(1)).map(str::len).sum::<usize>();
+    (line_start + loc.column.saturating_sub(1)).min(source.len())
+}
+
+/// Prints diagnostics to stderr, with snippets from the program's input
+/// files, and keeps count of the errors among them.
+pub struct Reporter<'a> {
+    files: &'a [SourceFile<'a>],
+    errors: usize,
+    /// The earliest phase any of the errors comes from
+    rejected_by: Option<Phase>,
+    /// Errors to print before the rest are dropped (`--max-errors`)
+    max_errors: Option<usize>,
+    /// Diagnostics dropped once `max_errors` errors were printed
+    dropped: usize,
+}
+
+impl<'a> Reporter<'a> {
+    pub fn new(files: &'a [SourceFile<'a>]) -> Self {
+        Reporter { files, errors: 0, rejected_by: None, max_errors: None, dropped: 0 }
+    }
+
+    /// Print only the first `max` errors, and the warnings among them.
+    pub fn max_errors(self, max: Option<usize>) -> Self {
+        Reporter { max_errors: max, ..self }
+    }
+
+    /// Print `diagnostic`, unless its level is `Allow` or the error limit
+    /// has been reached.
+    pub fn report(&mut self, diagnostic: &Diagnostic) {
+        if diagnostic.level == Level::Allow {
+            return;
+        }
+        if self.is_full() {
+            self.dropped += 1;
+            return;
+        }
+        if diagnostic.level == Level::Deny {
+            self.errors += 1;
+            self.rejected_by = Some(self.rejected_by.map_or(diagnostic.phase, |phase| phase.min(diagnostic.phase)));
+        }
+        eprint!("{}", render(diagnostic, self.files));
+    }
+
+    /// `true` once `max_errors` errors have been printed.
+    pub fn is_full(&self) -> bool {
+        self.max_errors.is_some_and(|max| self.errors >= max)
+    }
+
+    /// `true` once an error has been reported, so the program is rejected.
+    pub fn has_errors(&self) -> bool {
+        self.errors > 0
+    }
+
+    /// Say how many diagnostics were dropped, if any, and return the
+    /// earliest phase that rejected the program.
+    pub fn finish(self) -> Option<Phase> {
+        if self.dropped > 0 {
+            let plural = if self.dropped == 1 { "" } else { "s" };
+            eprintln!("note: {} more diagnostic{} not shown (--max-errors {})", self.dropped, plural, self.errors);
+        }
+        self.rejected_by
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parsing::recovery::SyntaxErrors;
+
+    fn syntax(source: &str) -> Vec<Diagnostic> {
+        let err = crate::parse_program(source).unwrap_err();
+        err.downcast::<SyntaxErrors>().unwrap().0.iter().map(|e| Diagnostic::syntax(e, 0, source)).collect()
+    }
+
+    #[test]
+    fn test_syntax_diagnostics() {
+        let source = "class Main {\n  a : Int <- ;\n  b : Int <- 1 # 2;\n};";
+        let diagnostics = syntax(source);
+        let summary: Vec<_> = diagnostics.iter().map(|d| (d.phase, d.code, d.message.as_str(), d.span)).collect();
+        assert_eq!(
+            summary,
+            [
+                (Phase::Parsing, "E0036", "Unrecognized token `;`", Some(Span { file: 0, start: 26, end: 27 })),
+                (Phase::Lexing, "E0027", "Invalid character '#'", Some(Span { file: 0, start: 43, end: 44 })),
+            ]
+        );
+        assert!(diagnostics[0].help.as_ref().unwrap().starts_with("expected one of \"objectid\", "));
+    }
+}
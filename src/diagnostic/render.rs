@@ -0,0 +1,146 @@
+//! Diagnostics as they are printed: the message with its code, the source
+//! line it points at with the offending span underlined, and a help note.
+//!
+//! ```text
+//! error[E0010]: Type mismatch: expected 'Int', found 'String'
+//!  --> foo.cl:3:15
+//!   |
+//! 3 |   x : Int <- "s";
+//!   |              ^^^
+//!   = help: the type of this expression must conform to 'Int'
+//! ```
+
+use std::fmt::Write;
+
+use crate::diagnostic::Diagnostic;
+use crate::semantic::severity::Level;
+
+/// An input file of the program, for diagnostics to point into.
+pub struct SourceFile<'a> {
+    pub name: &'a str,
+    pub text: &'a str,
+}
+
+/// `diagnostic`, ending in a newline. `files` are the program's input
+/// files, indexed as in `Class::file`. A diagnostic with no span is located
+/// by its file and line, as far as it has them.
+pub fn render(diagnostic: &Diagnostic, files: &[SourceFile<'_>]) -> String {
+    let mut out = String::new();
+    let kind = if diagnostic.level == Level::Warn { "warning" } else { "error" };
+    writeln!(out, "{}[{}]: {}", kind, diagnostic.code, diagnostic.message).unwrap();
+
+    let mut pad = " ".to_string();
+    let source = diagnostic.file.and_then(|file| files.get(file));
+    match (source, diagnostic.span) {
+        (Some(source), Some(span)) if span.end <= source.text.len() => {
+            let text = source.text;
+            let start = text[..span.start].rfind('\n').map_or(0, |i| i + 1);
+            let end = text[span.start..].find('\n').map_or(text.len(), |i| span.start + i);
+            let (number, column) = line_and_column(text, span.start);
+            pad = " ".repeat(number.to_string().len());
+
+            // Tabs before the span are kept so the carets line up under it
+            let indent: String = text[start..span.start].chars().map(|c| if c == '\t' { '\t' } else { ' ' }).collect();
+            let carets = text[span.start..span.end.min(end)].chars().count().max(1);
+            writeln!(out, "{}--> {}:{}:{}", pad, source.name, number, column).unwrap();
+            writeln!(out, "{} |", pad).unwrap();
+            writeln!(out, "{} | {}", number, &text[start..end]).unwrap();
+            writeln!(out, "{} | {}{}", pad, indent, "^".repeat(carets)).unwrap();
+        }
+        _ => match (source, diagnostic.line) {
+            (Some(source), Some(line)) => writeln!(out, "{}--> {}:{}", pad, source.name, line).unwrap(),
+            (Some(source), None) => writeln!(out, "{}--> {}", pad, source.name).unwrap(),
+            (None, Some(line)) => writeln!(out, "{}--> line {}", pad, line).unwrap(),
+            (None, None) => {}
+        },
+    }
+    if let Some(help) = &diagnostic.help {
+        writeln!(out, "{} = help: {}", pad, help).unwrap();
+    }
+    out
+}
+
+/// `diagnostic` on one line, as `foo.cl:3:15: error[E0010]: message`, for
+/// output that lists many of them at a glance.
+pub fn short(diagnostic: &Diagnostic, files: &[SourceFile<'_>]) -> String {
+    let kind = if diagnostic.level == Level::Warn { "warning" } else { "error" };
+    let source = diagnostic.file.and_then(|file| files.get(file));
+    let place = match (source, diagnostic.span, diagnostic.line) {
+        (Some(source), Some(span), _) if span.end <= source.text.len() => {
+            let (line, column) = line_and_column(source.text, span.start);
+            format!("{}:{}:{}: ", source.name, line, column)
+        }
+        (Some(source), _, Some(line)) => format!("{}:{}: ", source.name, line),
+        (Some(source), _, None) => format!("{}: ", source.name),
+        (None, _, Some(line)) => format!("line {}: ", line),
+        (None, _, None) => String::new(),
+    };
+    format!("{}{}[{}]: {}\n", place, kind, diagnostic.code, diagnostic.message)
+}
+
+/// The line and column of byte `offset` in `text`, both from 1.
+pub(crate) fn line_and_column(text: &str, offset: usize) -> (usize, usize) {
+    let start = text[..offset].rfind('\n').map_or(0, |i| i + 1);
+    (text[..offset].matches('\n').count() + 1, text[start..offset].chars().count() + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::semantic::collector::ErrorCollector;
+    use crate::semantic::context::AnalysisContext;
+    use crate::semantic::errors::SemanticError;
+
+    fn rendered(text: &str) -> String {
+        let classes = crate::parse_program(text).unwrap();
+        let ctx = AnalysisContext::new(crate::prelude(), &classes);
+        let mut ec = ErrorCollector::default();
+        crate::check_program(&ctx, &mut ec);
+        let files = [SourceFile { name: "test.cl", text }];
+        ec.diagnostics().iter().map(|d| render(d, &files)).collect()
+    }
+
+    #[test]
+    fn test_render() {
+        let text = "class Main {\n\tmain() : Int { let unused : Int in \"four\" };\n};";
+        assert_eq!(
+            rendered(text),
+            "error[E0010]: Type mismatch: expected 'Int', found 'String'\n \
+             --> test.cl:2:17\n  \
+             |\n\
+             2 | \tmain() : Int { let unused : Int in \"four\" };\n  \
+             | \t               ^^^^^^^^^^^^^^^^^^^^^^^^^^\n  \
+             = help: the type of this expression must conform to 'Int'\n\
+             warning[W0001]: Let binding 'unused' is never used\n \
+             --> test.cl:2:21\n  \
+             |\n\
+             2 | \tmain() : Int { let unused : Int in \"four\" };\n  \
+             | \t                   ^^^^^^^^^^^^\n  \
+             = help: remove the binding, or silence the lint with `--allow unused_let`\n"
+        );
+    }
+
+    #[test]
+    fn test_render_without_span() {
+        let expected = "error[E0016]: Class 'Main' is not defined\n  = help: execution starts with `(new Main).main()`\n";
+        let diagnostic = Diagnostic::semantic(&SemanticError::NoMainClass, Level::Deny);
+        assert_eq!(render(&diagnostic, &[]), expected);
+    }
+
+    #[test]
+    fn test_short() {
+        let text = "class Main {\n\tmain() : Int { let unused : Int in \"four\" };\n};";
+        let classes = crate::parse_program(text).unwrap();
+        let mut ec = ErrorCollector::default();
+        crate::check_program(&AnalysisContext::new(crate::prelude(), &classes), &mut ec);
+        let files = [SourceFile { name: "test.cl", text }];
+        let lines: String = ec.diagnostics().iter().map(|d| short(d, &files)).collect();
+        assert_eq!(
+            lines,
+            "test.cl:2:17: error[E0010]: Type mismatch: expected 'Int', found 'String'\n\
+             test.cl:2:21: warning[W0001]: Let binding 'unused' is never used\n"
+        );
+        let diagnostic = Diagnostic::semantic(&SemanticError::NoMainClass, Level::Deny);
+        assert_eq!(short(&diagnostic, &files), "error[E0016]: Class 'Main' is not defined\n");
+    }
+}
@@ -0,0 +1,136 @@
+//! Differential testing against a reference implementation
+//! (`cool-difftest`).
+//!
+//! Each program of a corpus is run on the same input by `cool-rs --run` and
+//! by a reference command, such as a script that compiles it with the
+//! reference `coolc` and runs it with `spim`, and what the two printed on
+//! stdout and the status they exited with are compared. A program that
+//! does not run alike in both is a divergence of one from the other.
+//!
+//! Both commands are given the program's path as their last argument, and
+//! its input on stdin: the `.in` file next to it, as the conformance tests
+//! use, or nothing.
+
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::thread;
+
+/// What running a program printed and how it ended.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Outcome {
+    pub stdout: String,
+    /// `None` when it was killed by a signal
+    pub status: Option<i32>,
+}
+
+/// Run `command`, a program and the arguments to give it before `file`,
+/// with `input` on its stdin. What it prints on stderr, which is not
+/// compared, is dropped.
+pub fn run(command: &[String], file: &Path, input: &[u8]) -> io::Result<Outcome> {
+    let (program, args) = command
+        .split_first()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "empty command"))?;
+    let mut child = Command::new(program)
+        .args(args)
+        .arg(file)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()?;
+    // Written from a thread of its own, as the child may fill its stdout
+    // before it has read all of its input
+    let mut stdin = child.stdin.take().unwrap();
+    let input = input.to_vec();
+    let writer = thread::spawn(move || stdin.write_all(&input));
+    let output = child.wait_with_output()?;
+    // A program may well stop before reading all of its input
+    let _ = writer.join();
+    Ok(Outcome { stdout: String::from_utf8_lossy(&output.stdout).into_owned(), status: output.status.code() })
+}
+
+/// How `ours` and `reference` differ, or `None` when they ran alike: the
+/// first line of stdout where they part ways, and their exit statuses.
+pub fn divergence(ours: &Outcome, reference: &Outcome) -> Option<String> {
+    let mut differences = Vec::new();
+    if ours.stdout != reference.stdout {
+        let mut ours_lines = ours.stdout.split_inclusive('\n');
+        let mut reference_lines = reference.stdout.split_inclusive('\n');
+        for line in 1.. {
+            match (ours_lines.next(), reference_lines.next()) {
+                (a, b) if a == b => continue,
+                (a, b) => {
+                    let show = |text: Option<&str>| text.map_or("<end of output>".to_string(), |text| format!("{:?}", text));
+                    differences.push(format!("stdout line {}: ours {}, reference {}", line, show(a), show(b)));
+                    break;
+                }
+            }
+        }
+    }
+    if ours.status != reference.status {
+        let show = |status: Option<i32>| status.map_or("killed".to_string(), |code| code.to_string());
+        differences.push(format!("exit status: ours {}, reference {}", show(ours.status), show(reference.status)));
+    }
+    (!differences.is_empty()).then(|| differences.join("; "))
+}
+
+/// The `.cl` files among `paths` and under those that are directories,
+/// sorted within each directory.
+pub fn programs(paths: &[PathBuf]) -> io::Result<Vec<PathBuf>> {
+    fn collect(path: &Path, out: &mut Vec<PathBuf>) -> io::Result<()> {
+        if path.is_dir() {
+            let mut entries = fs::read_dir(path)?.map(|entry| entry.map(|e| e.path())).collect::<io::Result<Vec<_>>>()?;
+            entries.sort();
+            for entry in entries {
+                collect(&entry, out)?;
+            }
+        } else if path.extension().is_some_and(|ext| ext == "cl") {
+            out.push(path.to_path_buf());
+        }
+        Ok(())
+    }
+    let mut out = Vec::new();
+    for path in paths {
+        collect(path, &mut out)?;
+    }
+    Ok(out)
+}
+
+/// The input to run `program` on: the `.in` file next to it, or nothing.
+pub fn input_for(program: &Path) -> Vec<u8> {
+    fs::read(program.with_extension("in")).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn outcome(stdout: &str, status: i32) -> Outcome {
+        Outcome { stdout: stdout.to_string(), status: Some(status) }
+    }
+
+    #[test]
+    fn test_divergence() {
+        assert_eq!(divergence(&outcome("a\nb\n", 0), &outcome("a\nb\n", 0)), None);
+        assert_eq!(
+            divergence(&outcome("a\nb\n", 1), &outcome("a\nc\n", 0)).unwrap(),
+            "stdout line 2: ours \"b\\n\", reference \"c\\n\"; exit status: ours 1, reference 0"
+        );
+        assert_eq!(
+            divergence(&outcome("a\n", 0), &outcome("a\nmore", 0)).unwrap(),
+            "stdout line 2: ours <end of output>, reference \"more\""
+        );
+        assert_eq!(divergence(&outcome("a", 0), &outcome("a\n", 0)).unwrap(), "stdout line 1: ours \"a\", reference \"a\\n\"");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_run() {
+        // The file comes last, and the input on stdin
+        let command: Vec<String> = ["sh", "-c", "cat; echo \" $0\"; exit 3"].map(String::from).to_vec();
+        let outcome = run(&command, Path::new("prog.cl"), b"input").unwrap();
+        assert_eq!(outcome, Outcome { stdout: "input prog.cl\n".to_string(), status: Some(3) });
+        assert!(run(&[], Path::new("prog.cl"), b"").is_err());
+    }
+}
@@ -0,0 +1,133 @@
+//! Opt-in language extensions (`--ext NAME`).
+//!
+//! With no extension enabled the compiler accepts exactly the language of
+//! the COOL reference manual. Each extension is off unless asked for, and
+//! every phase that implements one checks for it explicitly.
+
+use std::collections::BTreeSet;
+
+use clap::ValueEnum;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, ValueEnum)]
+pub enum Extension {
+    /// `\u{...}` escapes and `\(expr)` interpolation in string literals
+    Strings,
+    /// A built-in `Array` class of `Object` elements
+    Arrays,
+    /// `try ... catch x : T => ... end` and `throw expr`
+    Exceptions,
+    /// `import "file.cl";` directives, followed by the driver
+    Imports,
+    /// Methods declared `native`, implemented by a host function
+    Natives,
+}
+
+/// The set of enabled extensions.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct Extensions(BTreeSet<Extension>);
+
+impl Extensions {
+    pub fn contains(&self, ext: Extension) -> bool {
+        self.0.contains(&ext)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Command-line names of the enabled extensions, in a stable order.
+    pub fn names(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.0.iter().map(|ext| match ext {
+            Extension::Strings => "strings",
+            Extension::Arrays => "arrays",
+            Extension::Exceptions => "exceptions",
+            Extension::Imports => "imports",
+            Extension::Natives => "natives",
+        })
+    }
+
+    /// Whether a parse error may list `terminal` (as the grammar spells it)
+    /// among the expected tokens. Terminals of disabled extensions can never
+    /// be scanned, so suggesting them would only confuse.
+    pub(crate) fn allows_terminal(&self, terminal: &str) -> bool {
+        const EXCEPTIONS: &[&str] = &["\"try\"", "\"catch\"", "\"throw\"", "\"end\""];
+        match terminal {
+            "\"import\"" => self.contains(Extension::Imports),
+            "\"native\"" => self.contains(Extension::Natives),
+            t if EXCEPTIONS.contains(&t) => self.contains(Extension::Exceptions),
+            _ => true,
+        }
+    }
+}
+
+impl FromIterator<Extension> for Extensions {
+    fn from_iter<I: IntoIterator<Item = Extension>>(iter: I) -> Self {
+        Extensions(iter.into_iter().collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::semantic::collector::ErrorCollector;
+    use crate::semantic::context::AnalysisContext;
+    use crate::semantic::errors::SemanticError;
+
+    fn check(source: &str, extensions: &Extensions) -> Vec<SemanticError> {
+        let classes = crate::parse_program_with(source, extensions).unwrap();
+        let ctx = AnalysisContext::new(crate::prelude_with(extensions), &classes);
+        let mut ec = ErrorCollector::default();
+        crate::check_program(&ctx, &mut ec);
+        ec.errors
+    }
+
+    #[test]
+    fn test_arrays() {
+        let source = "class Main {
+            a : Array <- (new Array).init(3);
+            main() : Object { { a.set(0, a.length() + 1); a.get(0); a.get(\"0\"); } };
+        };";
+        let arrays: Extensions = [Extension::Arrays].into_iter().collect();
+        let errors = check(source, &arrays);
+        assert_eq!(errors.iter().map(|e| e.code()).collect::<Vec<_>>(), ["E0010"]);
+        // Without the extension, Array is just an undefined class
+        assert!(check(source, &Extensions::default()).iter().any(|e| e.code() == "E0008"));
+        assert_eq!(crate::prelude_with(&arrays).len(), crate::prelude().len() + 1);
+    }
+
+    #[test]
+    fn test_exceptions() {
+        let source = "class Main {
+            f(x : Int) : Int { if x < 0 then throw x else x fi };
+            main() : Object {
+                try f(~1) catch e : Int => e + 1 end + try 1 catch s : Strin => 2 end
+            };
+        };";
+        let exceptions: Extensions = [Extension::Exceptions].into_iter().collect();
+        let errors = check(source, &exceptions);
+        assert_eq!(errors.iter().map(|e| e.code()).collect::<Vec<_>>(), ["E0008"]);
+        // Printing keeps the new syntax reparseable
+        let classes = crate::parse_program_with(source, &exceptions).unwrap();
+        let printed = crate::pretty::print_program(&classes);
+        let reparsed = crate::parse_program_with(&printed, &exceptions).unwrap();
+        assert_eq!(crate::pretty::print_program(&reparsed), printed);
+        // Without the extension, `try` and `throw` are ordinary identifiers
+        assert!(crate::parse_program(source).is_err());
+    }
+
+    #[test]
+    fn test_natives() {
+        let source = "class Sys {
+            random(n : Int) : Int native;
+        };
+        class Main {
+            main() : Int { (new Sys).random(6) + 1 };
+        };";
+        let natives: Extensions = [Extension::Natives].into_iter().collect();
+        assert_eq!(check(source, &natives), []);
+        let classes = crate::parse_program_with(source, &natives).unwrap();
+        let printed = crate::pretty::print_program(&classes);
+        assert!(printed.contains("random(n : Int) : Int native;"), "{}", printed);
+        assert_eq!(crate::parse_program_with(&printed, &natives).unwrap().len(), 2);
+    }
+}
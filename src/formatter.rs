@@ -0,0 +1,641 @@
+//! `cool-rs fmt`: COOL source in one consistent layout.
+//!
+//! The file is parsed and its AST printed again as a `Doc`, text with the
+//! places where a line may break. A group of a `Doc` is laid out on one
+//! line if it fits within `WIDTH` columns, and is otherwise broken at each
+//! of its own `Line`s, after Wadler's "A prettier printer". Classes,
+//! features, the expressions of a block and `case` branches always start
+//! lines of their own, indented four spaces per level.
+//!
+//! The AST has no comments, so the scanner records where they are and each
+//! one is put back where a line starts: before the class, feature, block
+//! expression or `case` branch that follows it, or at the end of the line
+//! of the one it trails. A comment inside any other expression moves to
+//! the next such place. One blank line between them is kept; more are not.
+//!
+//! Redundant parentheses are dropped, like `pretty::print_program` does, so
+//! formatted source parses to the same AST up to its positions. A string
+//! with `--ext strings` interpolation is kept as written.
+
+use eyre::Result;
+
+use crate::ast::{
+    ArgDecl, CaseBranch, Class, ComparisonOperator, Expr, Feature, MathOperator, TypedExpr, UnaryOperator, VarDecl,
+};
+use crate::ext::Extensions;
+use crate::parsing::scanner::Scanner;
+use crate::parsing::token::{Span, Token};
+use crate::pretty::{level, level_of, quote};
+
+/// The column a line is kept within wherever it can break.
+pub const WIDTH: usize = 80;
+
+const INDENT: usize = 4;
+
+enum Doc {
+    /// A newline inside is printed as it is, with no indentation added
+    Text(String),
+    /// A space, or a newline if its group is broken
+    Line,
+    /// Nothing, or a newline if its group is broken
+    SoftLine,
+    /// A newline, which breaks every group around it
+    HardLine,
+    /// The contents, one level deeper after each newline
+    Nest(Vec<Doc>),
+    /// The contents, and whether they have a hard line
+    Group(Vec<Doc>, bool),
+}
+
+impl Doc {
+    fn has_hard_line(&self) -> bool {
+        match self {
+            Doc::Text(s) => s.contains('\n'),
+            Doc::Line | Doc::SoftLine => false,
+            Doc::HardLine => true,
+            Doc::Nest(docs) => docs.iter().any(Doc::has_hard_line),
+            Doc::Group(_, hard) => *hard,
+        }
+    }
+}
+
+fn text(s: impl Into<String>) -> Doc {
+    Doc::Text(s.into())
+}
+
+fn group(docs: Vec<Doc>) -> Doc {
+    let hard = docs.iter().any(Doc::has_hard_line);
+    Doc::Group(docs, hard)
+}
+
+/// What is left to print: the indentation level, whether the enclosing
+/// group is flat, and the document.
+type Work<'d> = Vec<(usize, bool, &'d Doc)>;
+
+fn render(doc: &Doc) -> String {
+    let mut out = String::new();
+    let mut column = 0;
+    let mut work: Work = vec![(0, false, doc)];
+    while let Some((indent, flat, doc)) = work.pop() {
+        match doc {
+            Doc::Text(s) => {
+                out.push_str(s);
+                column = match s.rfind('\n') {
+                    Some(i) => s[i + 1..].chars().count(),
+                    None => column + s.chars().count(),
+                };
+            }
+            Doc::Line if flat => {
+                out.push(' ');
+                column += 1;
+            }
+            Doc::SoftLine if flat => {}
+            Doc::Line | Doc::SoftLine | Doc::HardLine => {
+                out.truncate(out.trim_end_matches(' ').len());
+                out.push('\n');
+                out.extend(std::iter::repeat_n(' ', indent * INDENT));
+                column = indent * INDENT;
+            }
+            Doc::Nest(docs) => work.extend(docs.iter().rev().map(|d| (indent + 1, flat, d))),
+            Doc::Group(docs, hard) => {
+                let flat = flat || (!hard && fits(WIDTH as isize - column as isize, docs, &work));
+                work.extend(docs.iter().rev().map(|d| (indent, flat, d)));
+            }
+        }
+    }
+    out
+}
+
+/// Whether `docs`, laid out flat, and what follows them up to the next
+/// line break fit in `width` columns.
+fn fits(mut width: isize, docs: &[Doc], rest: &Work) -> bool {
+    let mut work: Vec<(bool, &Doc)> = docs.iter().rev().map(|d| (true, d)).collect();
+    let mut rest = rest.iter().rev();
+    loop {
+        let (flat, doc) = match work.pop() {
+            Some(item) => item,
+            None => match rest.next() {
+                Some(&(_, flat, doc)) => (flat, doc),
+                None => return true,
+            },
+        };
+        match doc {
+            Doc::Text(s) if s.contains('\n') => return true,
+            Doc::Text(s) => width -= s.chars().count() as isize,
+            Doc::Line if flat => width -= 1,
+            Doc::SoftLine if flat => {}
+            Doc::Line | Doc::SoftLine | Doc::HardLine => return true,
+            Doc::Nest(docs) | Doc::Group(docs, _) => work.extend(docs.iter().rev().map(|d| (flat, d))),
+        }
+        if width < 0 {
+            return false;
+        }
+    }
+}
+
+/// `source` in the standard layout, with its comments.
+pub fn format_source(source: &str, extensions: &Extensions) -> Result<String> {
+    let program = crate::parse_module(source, extensions)?;
+    // The source parsed, so it lexes
+    let mut scanner = Scanner::with_extensions(source, extensions.clone()).keep_comments();
+    let mut semicolons = Vec::new();
+    let mut imports = Vec::new();
+    while let Ok(Some((token, _, span))) = scanner.next_spanned_token() {
+        match token {
+            Token::Semicolon => semicolons.push(span.start),
+            Token::Import => imports.push(span.start),
+            _ => {}
+        }
+    }
+    let mut formatter = Formatter { source, comments: scanner.comments().to_vec(), semicolons };
+
+    let mut docs = Vec::new();
+    let mut end = 0;
+    for (i, path) in program.imports.iter().enumerate() {
+        if i > 0 {
+            docs.push(Doc::HardLine);
+            formatter.blank_line(end, imports[i], &mut docs);
+        }
+        formatter.leading(imports[i], &mut docs);
+        let mut import = String::from("import ");
+        quote(&mut import, path);
+        docs.push(text(import + ";"));
+        end = formatter.terminator(imports[i]);
+        formatter.trailing(end, &mut docs);
+    }
+    for class in &program.classes {
+        if !docs.is_empty() {
+            docs.extend([Doc::HardLine, Doc::HardLine]);
+        }
+        formatter.leading(class.location.span.start, &mut docs);
+        end = formatter.class(class, &mut docs);
+    }
+    // Comments after the last class
+    while !formatter.comments.is_empty() {
+        formatter.trailing(end, &mut docs);
+        let Some(&comment) = formatter.comments.first() else { break };
+        docs.push(Doc::HardLine);
+        formatter.blank_line(end, comment.start, &mut docs);
+        docs.push(formatter.take_comment(0));
+        end = comment.end;
+    }
+    docs.push(Doc::HardLine);
+    Ok(render(&group_all(docs)))
+}
+
+/// `docs` as one document that is never laid out flat.
+fn group_all(docs: Vec<Doc>) -> Doc {
+    Doc::Group(docs, true)
+}
+
+struct Formatter<'a> {
+    source: &'a str,
+    /// The comments not placed yet, in source order
+    comments: Vec<Span>,
+    /// Where each `;` is, in order
+    semicolons: Vec<usize>,
+}
+
+impl Formatter<'_> {
+    /// The end of the first `;` at or after `offset`.
+    fn terminator(&self, offset: usize) -> usize {
+        let i = self.semicolons.partition_point(|&s| s < offset);
+        self.semicolons.get(i).map_or(offset, |s| s + 1)
+    }
+
+    fn take_comment(&mut self, i: usize) -> Doc {
+        let span = self.comments.remove(i);
+        text(self.source[span.start..span.end].trim_end())
+    }
+
+    /// An empty line if the source has one between `from` and `to`.
+    fn blank_line(&self, from: usize, to: usize, docs: &mut Vec<Doc>) {
+        if from < to && self.source[from..to].matches('\n').count() > 1 {
+            docs.push(Doc::HardLine);
+        }
+    }
+
+    /// The comments before `offset`, each on a line of its own.
+    fn leading(&mut self, offset: usize, docs: &mut Vec<Doc>) {
+        while let Some(&comment) = self.comments.first().filter(|c| c.start < offset) {
+            docs.push(self.take_comment(0));
+            docs.push(Doc::HardLine);
+            let next = self.comments.first().map_or(offset, |c| c.start.min(offset));
+            self.blank_line(comment.end, next, docs);
+        }
+    }
+
+    /// The comment right after `end` on the same line, where the source has
+    /// one. Comments before `end` are left for the next place.
+    fn trailing(&mut self, end: usize, docs: &mut Vec<Doc>) {
+        let i = self.comments.partition_point(|c| c.start < end);
+        if let Some(comment) = self.comments.get(i) {
+            let gap = &self.source[end..comment.start];
+            if gap.trim_start_matches([' ', '\t']).is_empty() {
+                docs.push(text(" "));
+                docs.push(self.take_comment(i));
+            }
+        }
+    }
+
+    /// The comments before `offset`, each on a line of its own after what
+    /// `docs` has, as at the end of a block.
+    fn closing(&mut self, offset: usize, docs: &mut Vec<Doc>) {
+        while self.comments.first().is_some_and(|c| c.start < offset) {
+            docs.push(Doc::HardLine);
+            docs.push(self.take_comment(0));
+        }
+    }
+
+    /// Lay out `class`; returns the end of its final `;`.
+    fn class(&mut self, class: &Class, docs: &mut Vec<Doc>) -> usize {
+        let mut header = format!("class {}", class.name);
+        if let Some(parent) = class.inherits {
+            header += &format!(" inherits {}", parent);
+        }
+        docs.push(text(header + " {"));
+        let header_end = class.location.span.end;
+        self.trailing(self.source[header_end..].find('{').map_or(header_end, |i| header_end + i + 1), docs);
+
+        let mut body = Vec::new();
+        let mut end = class.location.span.end;
+        for (i, feature) in class.feature_list.iter().enumerate() {
+            let start = match feature {
+                Feature::Attribute(VarDecl { location, .. }) | Feature::Method(.., location) => location.span.start,
+            };
+            body.push(Doc::HardLine);
+            if i > 0 {
+                let next = self.comments.first().map_or(start, |c| c.start.min(start));
+                self.blank_line(end, next, &mut body);
+            }
+            self.leading(start, &mut body);
+            end = self.feature(feature, &mut body);
+            self.trailing(end, &mut body);
+        }
+        end = self.terminator(end);
+        self.closing(end, &mut body);
+        docs.push(Doc::Nest(body));
+        docs.extend([Doc::HardLine, text("};")]);
+        self.trailing(end, docs);
+        end
+    }
+
+    /// Lay out `feature` with its `;`; returns where the `;` ends.
+    fn feature(&mut self, feature: &Feature, docs: &mut Vec<Doc>) -> usize {
+        match feature {
+            Feature::Attribute(VarDecl { oid, tid, expr, location }) => {
+                let mut parts = vec![text(format!("{} : {}", oid, tid))];
+                if let Some(init) = expr {
+                    self.rhs(" <-", init, level::LET, &mut parts);
+                }
+                parts.push(text(";"));
+                docs.push(group(parts));
+                self.terminator(expr.as_ref().map_or(location.span.end, |init| init.span.end))
+            }
+            Feature::Method(name, args, ret_type, body, _) => {
+                let mut formals = vec![Doc::SoftLine];
+                for (i, ArgDecl { id, tid, .. }) in args.iter().enumerate() {
+                    if i > 0 {
+                        formals.extend([text(","), Doc::Line]);
+                    }
+                    formals.push(text(format!("{} : {}", id, tid)));
+                }
+                let signature = |close: String| {
+                    let mut parts = vec![text(format!("{}(", name))];
+                    if !args.is_empty() {
+                        parts.push(Doc::Nest(formals));
+                        parts.push(Doc::SoftLine);
+                    }
+                    parts.push(text(close));
+                    group(parts)
+                };
+                if let Expr::Native = body.expr {
+                    docs.push(signature(format!(") : {} native;", ret_type)));
+                    return body.span.end;
+                }
+                let body_doc = self.expr(body, level::LET);
+                docs.push(group(vec![
+                    signature(format!(") : {} {{", ret_type)),
+                    Doc::Nest(vec![Doc::Line, body_doc]),
+                    Doc::Line,
+                    text("};"),
+                ]));
+                self.terminator(body.span.end)
+            }
+        }
+    }
+
+    /// `op` and then `e`, on the next line if it does not fit; a block or
+    /// `case` stays on the line of `op`.
+    fn rhs(&mut self, op: &str, e: &TypedExpr, max: u8, docs: &mut Vec<Doc>) {
+        let doc = self.expr(e, max);
+        if hugs(e) {
+            docs.extend([text(format!("{} ", op)), doc]);
+        } else {
+            docs.extend([text(op), Doc::Nest(vec![Doc::Line, doc])]);
+        }
+    }
+
+    /// `keyword` after `e`, which the caller laid out with `rhs`.
+    fn then(&self, e: &TypedExpr, keyword: &str, docs: &mut Vec<Doc>) {
+        if hugs(e) {
+            docs.push(text(format!(" {}", keyword)));
+        } else {
+            docs.extend([Doc::Line, text(keyword)]);
+        }
+    }
+
+    /// Lay out `e` where the grammar accepts expressions up to level `max`.
+    fn expr(&mut self, e: &TypedExpr, max: u8) -> Doc {
+        if let Expr::Paren(inner) = &e.expr {
+            // A string with `--ext strings` interpolation is read as the
+            // conversions and `concat`s it stands for, in parentheses
+            if !e.span.is_empty() && self.source[e.span.start..].starts_with('"') {
+                self.comments.retain(|c| c.end <= e.span.start || c.start >= e.span.end);
+                return text(&self.source[e.span.start..e.span.end]);
+            }
+            return self.expr(inner, max);
+        }
+        if level_of(&e.expr) > max {
+            return group(vec![text("("), self.expr(e, level::LET), text(")")]);
+        }
+
+        match &e.expr {
+            Expr::Identifier(name) => text(name.as_str()),
+            Expr::Bool(b) => text(b.to_string()),
+            Expr::Int(i) => text(i.to_string()),
+            Expr::Str(s) => {
+                let mut quoted = String::new();
+                quote(&mut quoted, s);
+                text(quoted)
+            }
+            Expr::New(type_name) => text(format!("new {}", type_name)),
+            Expr::Block(exprs) => {
+                let mut body = Vec::new();
+                let mut end = e.span.start;
+                for (i, stmt) in exprs.iter().enumerate() {
+                    body.push(Doc::HardLine);
+                    if i > 0 {
+                        let next = self.comments.first().map_or(stmt.span.start, |c| c.start.min(stmt.span.start));
+                        self.blank_line(end, next, &mut body);
+                    }
+                    self.leading(stmt.span.start, &mut body);
+                    body.push(self.expr(stmt, level::LET));
+                    body.push(text(";"));
+                    end = self.terminator(stmt.span.end);
+                    self.trailing(end, &mut body);
+                }
+                self.closing(e.span.end, &mut body);
+                group_all(vec![text("{"), Doc::Nest(body), Doc::HardLine, text("}")])
+            }
+            Expr::Case(scrutinee, branches) => {
+                let mut docs = vec![text("case "), self.expr(scrutinee, level::LET), text(" of")];
+                let mut body = Vec::new();
+                for CaseBranch { id, tid, expr, .. } in branches {
+                    body.push(Doc::HardLine);
+                    self.leading(expr.span.start, &mut body);
+                    let mut branch = vec![text(format!("{} : {}", id, tid))];
+                    self.rhs(" =>", expr, level::LET, &mut branch);
+                    branch.push(text(";"));
+                    body.push(group(branch));
+                    let end = self.terminator(expr.span.end);
+                    self.trailing(end, &mut body);
+                }
+                self.closing(e.span.end, &mut body);
+                docs.extend([Doc::Nest(body), Doc::HardLine, text("esac")]);
+                group_all(docs)
+            }
+            Expr::Paren(_) => unreachable!("handled above"),
+            Expr::Let(bindings, body) => {
+                let mut decls = Vec::new();
+                for (i, VarDecl { oid: id, tid, expr: init, .. }) in bindings.iter().enumerate() {
+                    if i > 0 {
+                        decls.extend([text(","), Doc::Line]);
+                    }
+                    let mut decl = vec![text(format!("{} : {}", id, tid))];
+                    if let Some(init) = init {
+                        // A nested `let` would swallow the following bindings
+                        self.rhs(" <-", init, level::ASSIGN, &mut decl);
+                    }
+                    decls.push(group(decl));
+                }
+                let mut docs = vec![group(vec![text("let "), Doc::Nest(decls)])];
+                self.rhs(" in", body, level::LET, &mut docs);
+                group(docs)
+            }
+            Expr::Comparison { lhs, op, rhs } => {
+                let op = match op {
+                    ComparisonOperator::Lt => "<",
+                    ComparisonOperator::Le => "<=",
+                    ComparisonOperator::Equal => "=",
+                };
+                self.binary(lhs, op, rhs, level::COMPARISON)
+            }
+            Expr::Math { lhs, op, rhs } => {
+                let (op, level) = match op {
+                    MathOperator::Add => ("+", level::SUM),
+                    MathOperator::Subtract => ("-", level::SUM),
+                    MathOperator::Mul => ("*", level::PRODUCT),
+                    MathOperator::Div => ("/", level::PRODUCT),
+                };
+                self.binary(lhs, op, rhs, level)
+            }
+            Expr::UnaryOperation { op: UnaryOperator::Not, s } => group(vec![text("not "), self.expr(s, level::NOT - 1)]),
+            Expr::UnaryOperation { op: UnaryOperator::Neg, s } => group(vec![text("~"), self.expr(s, level::NEG - 1)]),
+            Expr::Assignment(name, rhs) => {
+                let mut docs = vec![text(name.as_str())];
+                self.rhs(" <-", rhs, level::ASSIGN, &mut docs);
+                group(docs)
+            }
+            Expr::Conditional { .. } => {
+                let mut docs = Vec::new();
+                self.conditional(e, 0, &mut docs);
+                group(docs)
+            }
+            Expr::While { test, exec } => {
+                let mut docs = vec![text("while "), self.expr(test, level::LET)];
+                self.rhs(" loop", exec, level::LET, &mut docs);
+                self.then(exec, "pool", &mut docs);
+                group(docs)
+            }
+            Expr::Isvoid(inner) => group(vec![text("isvoid "), self.expr(inner, level::ISVOID - 1)]),
+            Expr::Dispatch { target, targettype, id, exprs } => {
+                let mut docs = Vec::new();
+                if let Some(target) = target {
+                    docs.push(self.expr(target, level::DISPATCH));
+                    if let Some(tt) = targettype {
+                        docs.push(text(format!("@{}", tt)));
+                    }
+                    docs.push(text("."));
+                }
+                docs.push(text(format!("{}(", id)));
+                if !exprs.is_empty() {
+                    let mut args = vec![Doc::SoftLine];
+                    for (i, arg) in exprs.iter().enumerate() {
+                        if i > 0 {
+                            args.extend([text(","), Doc::Line]);
+                        }
+                        args.push(self.expr(arg, level::LET));
+                    }
+                    docs.extend([Doc::Nest(args), Doc::SoftLine]);
+                }
+                docs.push(text(")"));
+                group(docs)
+            }
+            Expr::Try { body, id, tid, handler } => {
+                let mut docs = Vec::new();
+                self.rhs("try", body, level::LET, &mut docs);
+                self.then(body, &format!("catch {} : {}", id, tid), &mut docs);
+                self.rhs(" =>", handler, level::LET, &mut docs);
+                self.then(handler, "end", &mut docs);
+                group(docs)
+            }
+            Expr::Throw(inner) => group(vec![text("throw "), self.expr(inner, level::LET)]),
+            Expr::Native => unreachable!("laid out as part of its method"),
+            Expr::Builtin => unreachable!("built-in classes are not formatted"),
+        }
+    }
+
+    /// An `if` and the `if`s in its `else` branches, as one `else if`
+    /// chain; the `fi`s are all at the end.
+    fn conditional(&mut self, e: &TypedExpr, depth: usize, docs: &mut Vec<Doc>) {
+        let Expr::Conditional { test, then, orelse } = &e.expr else { unreachable!() };
+        docs.extend([text("if "), self.expr(test, level::LET)]);
+        self.rhs(" then", then, level::LET, docs);
+        self.then(then, "else", docs);
+        match &unparenthesized(orelse).expr {
+            Expr::Conditional { .. } => {
+                docs.push(text(" "));
+                self.conditional(unparenthesized(orelse), depth + 1, docs);
+            }
+            _ => {
+                self.rhs("", orelse, level::LET, docs);
+                self.then(orelse, &vec!["fi"; depth + 1].join(" "), docs);
+            }
+        }
+    }
+
+    /// Left-associative binary operator at `level`.
+    fn binary(&mut self, lhs: &TypedExpr, op: &str, rhs: &TypedExpr, level: u8) -> Doc {
+        let lhs = self.expr(lhs, level);
+        let rhs = self.expr(rhs, level - 1);
+        group(vec![lhs, text(format!(" {}", op)), Doc::Nest(vec![Doc::Line, rhs])])
+    }
+}
+
+fn unparenthesized(e: &TypedExpr) -> &TypedExpr {
+    match &e.expr {
+        Expr::Paren(inner) => unparenthesized(inner),
+        _ => e,
+    }
+}
+
+/// Whether `e` starts on the line of what comes before it, however long.
+fn hugs(e: &TypedExpr) -> bool {
+    matches!(unparenthesized(e).expr, Expr::Block(_) | Expr::Case(..))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn format(source: &str) -> String {
+        format_source(source, &Extensions::default()).unwrap()
+    }
+
+    #[test]
+    fn test_comments() {
+        let source = "\
+-- header
+
+(* block
+   comment *)
+class Main inherits IO { -- trailing header
+  x : Int <- 1;   -- about x
+
+
+  -- before main
+  main() : Object {
+    {  out_string(\"hi\");  -- trailing stmt
+       case x of i : Int => i; -- branch
+       o : Object => 0; esac;
+       -- end of block
+    }
+  };
+}; -- after class
+-- eof
+";
+        let expected = "\
+-- header
+
+(* block
+   comment *)
+class Main inherits IO { -- trailing header
+    x : Int <- 1; -- about x
+
+    -- before main
+    main() : Object {
+        {
+            out_string(\"hi\"); -- trailing stmt
+            case x of
+                i : Int => i; -- branch
+                o : Object => 0;
+            esac;
+            -- end of block
+        }
+    };
+}; -- after class
+-- eof
+";
+        assert_eq!(format(source), expected);
+        assert_eq!(format(expected), expected);
+    }
+
+    #[test]
+    fn test_wrapping() {
+        let source = "class Main { main() : Int { if a_long_condition(1, 2, 3) then some_long_method(4, 5) \
+                      else another_long_method(6, 7) + 1 fi }; };";
+        let expected = "\
+class Main {
+    main() : Int {
+        if a_long_condition(1, 2, 3) then
+            some_long_method(4, 5)
+        else
+            another_long_method(6, 7) + 1
+        fi
+    };
+};
+";
+        assert_eq!(format(source), expected);
+
+        // The outermost group breaks first; arguments go one per line only
+        // when they do not fit on one
+        let arg = "a_fairly_long_argument_name";
+        let source = format!("class Main {{ x : Int <- f({}, {}, {}); }};", arg, arg, arg);
+        let expected = format!(
+            "class Main {{\n    x : Int <-\n        f(\n            {},\n            {},\n            {}\n        );\n}};\n",
+            arg, arg, arg
+        );
+        assert_eq!(format(&source), expected);
+    }
+
+    #[test]
+    fn test_interpolation() {
+        let extensions = [crate::ext::Extension::Strings].into_iter().collect();
+        let source = "class Main { s : String <- \"n = \\( 1 + (* one *) 2 )!\"; };";
+        let expected = "class Main {\n    s : String <- \"n = \\( 1 + (* one *) 2 )!\";\n};\n";
+        assert_eq!(format_source(source, &extensions).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_examples_are_formatted() {
+        for entry in std::fs::read_dir("examples").unwrap() {
+            let path = entry.unwrap().path();
+            if path.extension().is_some_and(|ext| ext == "cl") {
+                let source = std::fs::read_to_string(&path).unwrap();
+                let formatted = format(&source);
+                assert_eq!(format(&formatted), formatted, "{}", path.display());
+            }
+        }
+    }
+}
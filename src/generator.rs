@@ -0,0 +1,240 @@
+use std::fmt::Write;
+
+/// Shape of a synthetic program produced by `generate_program`.
+#[derive(Debug, Clone, Copy)]
+pub struct GenConfig {
+    /// Number of generated classes, not counting `Main`.
+    pub classes: usize,
+    /// Methods per class. Every class in a chain defines the same method
+    /// names, so subclasses override their parent's methods.
+    pub methods: usize,
+    /// Attributes per class.
+    pub attributes: usize,
+    /// Length of each inheritance chain; 1 means every class inherits Object.
+    pub depth: usize,
+    /// `--` comment lines emitted before each class and method.
+    pub comments: usize,
+    /// Nesting depth of a random Int expression added to every method body;
+    /// 0 adds none.
+    pub expr_depth: usize,
+    /// Seed for the random expressions. The same seed gives the same program.
+    pub seed: u64,
+}
+
+impl Default for GenConfig {
+    fn default() -> Self {
+        GenConfig {
+            classes: 100,
+            methods: 10,
+            attributes: 5,
+            depth: 1,
+            comments: 0,
+            expr_depth: 0,
+            seed: 0,
+        }
+    }
+}
+
+/// Emit a type-correct COOL program with the given shape.
+///
+/// Output is deterministic for a given configuration (including the seed),
+/// so benchmark inputs are identical across runs.
+pub fn generate_program(config: &GenConfig) -> String {
+    let depth = config.depth.max(1);
+    let mut rng = Rng(config.seed);
+    let mut out = String::new();
+
+    for i in 0..config.classes {
+        write_comment(&mut out, "", config.comments);
+        if i % depth == 0 {
+            writeln!(out, "class C{} {{", i).unwrap();
+        } else {
+            writeln!(out, "class C{} inherits C{} {{", i, i - 1).unwrap();
+        }
+
+        for k in 0..config.attributes {
+            writeln!(out, "    a{}_{} : Int <- {};", i, k, k).unwrap();
+        }
+        writeln!(out, "    s{} : String <- \"C{}\";", i, i).unwrap();
+
+        for j in 0..config.methods {
+            let attr = if config.attributes > 0 {
+                format!("a{}_{}", i, j % config.attributes)
+            } else {
+                "1".to_string()
+            };
+            // Each method calls the previous one, exercising dispatch lookup
+            let recurse = if j == 0 {
+                format!("y - s{}.length()", i)
+            } else {
+                format!("m{}(y - 1)", j - 1)
+            };
+            write_comment(&mut out, "    ", config.comments);
+            writeln!(out, "    m{}(x : Int) : Int {{", j).unwrap();
+            writeln!(out, "        let y : Int <- x + {} in {{", attr).unwrap();
+            if config.expr_depth > 0 {
+                let mut gen = ExprGen {
+                    rng: &mut rng,
+                    class: i,
+                    method: j,
+                    ints: vec!["x".to_string(), "y".to_string(), attr.clone()],
+                    lets: 0,
+                };
+                let e = gen.int(config.expr_depth);
+                writeln!(out, "            y <- {};", e).unwrap();
+            }
+            writeln!(out, "            while y < {} loop y <- y + 1 pool;", j * 10).unwrap();
+            writeln!(out, "            if y < {} then y * 2 else {} fi;", j, recurse).unwrap();
+            writeln!(out, "        }}").unwrap();
+            writeln!(out, "    }};").unwrap();
+        }
+        writeln!(out, "}};\n").unwrap();
+    }
+
+    writeln!(out, "class Main inherits IO {{").unwrap();
+    writeln!(out, "    main() : Object {{ {{").unwrap();
+    if config.methods > 0 {
+        for i in 0..config.classes {
+            writeln!(out, "        out_int(new C{}.m{}({}));", i, config.methods - 1, i).unwrap();
+        }
+    }
+    writeln!(out, "        out_string(\"done\\n\");").unwrap();
+    writeln!(out, "    }} }};").unwrap();
+    writeln!(out, "}};").unwrap();
+
+    out
+}
+
+/// SplitMix64: tiny, seedable and good enough to pick grammar productions.
+pub(crate) struct Rng(pub(crate) u64);
+
+impl Rng {
+    pub(crate) fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    pub(crate) fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+}
+
+/// Random type-correct expressions inside method `m{method}` of class `C{class}`.
+struct ExprGen<'r> {
+    rng: &'r mut Rng,
+    class: usize,
+    method: usize,
+    /// Int variables in scope
+    ints: Vec<String>,
+    /// Let bindings introduced so far, for fresh names
+    lets: usize,
+}
+
+impl ExprGen<'_> {
+    /// An expression of type Int nested at most `depth` levels deep.
+    fn int(&mut self, depth: usize) -> String {
+        if depth == 0 {
+            return match self.rng.below(3) {
+                0 => self.rng.below(100).to_string(),
+                1 => self.ints[self.rng.below(self.ints.len())].clone(),
+                _ => format!("s{}.length()", self.class),
+            };
+        }
+        let d = depth - 1;
+        match self.rng.below(9) {
+            0 => format!("({} + {})", self.int(d), self.int(d)),
+            1 => format!("({} - {})", self.int(d), self.int(d)),
+            2 => format!("({} * {})", self.int(d), self.int(d)),
+            3 => format!("({} / {})", self.int(d), 1 + self.rng.below(9)),
+            4 => format!("(~{})", self.int(d)),
+            5 => format!("(if {} then {} else {} fi)", self.boolean(d), self.int(d), self.int(d)),
+            6 => {
+                let name = format!("z{}", self.lets);
+                self.lets += 1;
+                let scope = self.ints.len();
+                let mut bindings = format!("{} : Int <- {}", name, self.int(d));
+                self.ints.push(name);
+                // Sometimes add a second, default-initialized binding
+                if self.rng.below(2) == 0 {
+                    let name = format!("z{}", self.lets);
+                    self.lets += 1;
+                    write!(bindings, ", {} : Int", name).unwrap();
+                    self.ints.push(name);
+                }
+                let body = self.int(d);
+                self.ints.truncate(scope);
+                format!("(let {} in {})", bindings, body)
+            }
+            7 => format!("{{ {}; {}; }}", self.statement(d), self.int(d)),
+            // Calls stay below the current method, so there is no recursion
+            _ if self.method > 0 => {
+                let callee = format!("m{}({})", self.rng.below(self.method), self.int(d));
+                match self.rng.below(4) {
+                    0 => callee,
+                    1 => format!("self.{}", callee),
+                    2 => format!("(new C{}).{}", self.class, callee),
+                    _ => format!("(new C{}) @ C{}.{}", self.class, self.class, callee),
+                }
+            }
+            _ => self.int(d),
+        }
+    }
+
+    /// An expression of type Bool.
+    fn boolean(&mut self, depth: usize) -> String {
+        if depth == 0 {
+            return if self.rng.below(2) == 0 { "true" } else { "false" }.to_string();
+        }
+        let d = depth - 1;
+        match self.rng.below(5) {
+            0 => format!("{} < {}", self.int(d), self.int(d)),
+            1 => format!("{} <= {}", self.int(d), self.int(d)),
+            2 => format!("{} = {}", self.int(d), self.int(d)),
+            3 => format!("(not {})", self.boolean(d)),
+            _ => format!("(isvoid new C{})", self.class),
+        }
+    }
+
+    /// An expression evaluated for its effect; its type is irrelevant.
+    /// Programs stay free of infinite loops so they can also be run.
+    fn statement(&mut self, depth: usize) -> String {
+        match self.rng.below(3) {
+            0 => format!("y <- {}", self.int(depth)),
+            // Halving a positive y always terminates
+            1 => format!("while 0 < y loop y <- y / {} pool", 2 + self.rng.below(8)),
+            _ => format!(
+                "case {} of n : Int => n; o : Object => o; esac",
+                self.int(depth)
+            ),
+        }
+    }
+}
+
+fn write_comment(out: &mut String, indent: &str, lines: usize) {
+    for _ in 0..lines {
+        writeln!(out, "{}-- Generated filler: the quick brown fox jumps over the lazy dog.", indent).unwrap();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::semantic::collector::ErrorCollector;
+    use crate::semantic::context::AnalysisContext;
+
+    #[test]
+    fn test_random_programs_type_check() {
+        for seed in 0..50 {
+            let config = GenConfig { classes: 4, methods: 4, depth: 2, expr_depth: 5, seed, ..GenConfig::default() };
+            let source = generate_program(&config);
+            let classes = crate::parse_program(&source).unwrap();
+            let ctx = AnalysisContext::new(crate::prelude(), &classes);
+            let mut ec = ErrorCollector::default();
+            crate::check_program(&ctx, &mut ec);
+            assert!(!ec.has_errors(), "seed {}: {:?}\n{}", seed, ec.errors, source);
+        }
+    }
+}
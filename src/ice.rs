@@ -0,0 +1,163 @@
+//! Internal compiler error reports.
+//!
+//! A panic anywhere in the compiler is a bug in the compiler, not in the
+//! user's program. Instead of a raw Rust backtrace, the driver prints a
+//! short report naming the phase, class and method being processed, and
+//! writes the details to a crash report file that can be attached to a bug
+//! report.
+//!
+//! Phases record where they are with `enter_phase` and `set_location`. The
+//! panic hook installed by `install_hook` captures the panic message and a
+//! backtrace; `report` then renders both.
+
+use std::backtrace::Backtrace;
+use std::cell::RefCell;
+use std::fmt::Write as _;
+use std::fs;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::symbol::Symbol;
+
+/// What the compiler was doing, for the report.
+#[derive(Debug, Clone, Default)]
+struct Context {
+    phase: Option<&'static str>,
+    class: Option<Symbol>,
+    method: Option<Symbol>,
+}
+
+/// A panic captured by the hook.
+struct Crash {
+    message: String,
+    location: String,
+    backtrace: Backtrace,
+}
+
+thread_local! {
+    static CONTEXT: RefCell<Context> = RefCell::new(Context::default());
+}
+
+/// Shared, since a phase may panic on a thread of its own.
+static CRASH: Mutex<Option<Crash>> = Mutex::new(None);
+
+/// Record that `phase` has started. Clears the class and method.
+pub fn enter_phase(phase: &'static str) {
+    CONTEXT.with(|c| *c.borrow_mut() = Context { phase: Some(phase), ..Context::default() });
+}
+
+/// Record the class, and the method within it if any, being processed.
+pub fn set_location(class: Symbol, method: Option<Symbol>) {
+    CONTEXT.with(|c| {
+        let mut c = c.borrow_mut();
+        c.class = Some(class);
+        c.method = method;
+    });
+}
+
+/// Capture panics for `report` instead of printing them.
+pub fn install_hook() {
+    panic::set_hook(Box::new(|info| {
+        let message = match info.payload().downcast_ref::<&str>() {
+            Some(s) => s.to_string(),
+            None => match info.payload().downcast_ref::<String>() {
+                Some(s) => s.clone(),
+                None => "Box<dyn Any>".to_string(),
+            },
+        };
+        // Panics that already call themselves internal compiler errors
+        let message = message.strip_prefix("internal compiler error: ").map_or(message.clone(), str::to_string);
+        let location = info.location().map(|l| l.to_string()).unwrap_or_default();
+        let crash = Crash { message, location, backtrace: Backtrace::force_capture() };
+        *CRASH.lock().unwrap_or_else(|poisoned| poisoned.into_inner()) = Some(crash);
+    }));
+}
+
+/// Print the report for the last captured panic while compiling `input`,
+/// and write the crash report file. Returns the file's path if it could be
+/// written.
+pub fn report(input: &Path) -> Option<PathBuf> {
+    let context = CONTEXT.with(|c| c.borrow().clone());
+    let crash = CRASH.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).take();
+    let (message, location) = match &crash {
+        Some(crash) => (crash.message.as_str(), crash.location.as_str()),
+        None => ("unknown panic", ""),
+    };
+
+    let mut summary = format!("internal compiler error: {}", message);
+    write!(summary, "\n  phase: {}", context.phase.unwrap_or("startup")).unwrap();
+    if let Some(class) = context.class {
+        write!(summary, "\n  class: {}", class).unwrap();
+    }
+    if let Some(method) = context.method {
+        write!(summary, "\n  method: {}", method).unwrap();
+    }
+    write!(summary, "\n  compiler: cool-rs {}", env!("CARGO_PKG_VERSION")).unwrap();
+
+    let mut details = format!("{}\n  input: {}\n  panicked at: {}\n", summary, input.display(), location);
+    if let Some(crash) = &crash {
+        write!(details, "\nbacktrace:\n{}\n", crash.backtrace).unwrap();
+    }
+    writeln!(details, "\n-- context").unwrap();
+    details.push_str(&context_source(input, context.class));
+
+    let path = std::env::temp_dir().join(format!("cool-rs-ice-{}.txt", std::process::id()));
+    let written = fs::write(&path, details).is_ok();
+    eprintln!("{}", summary);
+    if written {
+        eprintln!("  crash report: {}", path.display());
+    }
+    eprintln!("This is a bug in cool-rs. `cool-rs reduce --crash {}` can shrink the input.", input.display());
+    written.then_some(path)
+}
+
+/// The class being processed, printed back as source, or the whole input
+/// when the class is unknown or the input cannot be parsed again.
+fn context_source(input: &Path, class: Option<Symbol>) -> String {
+    let Ok(source) = fs::read_to_string(input) else {
+        return "(input could not be read)\n".to_string();
+    };
+    let Some(class) = class else { return source };
+    // The parser may be what crashed; keep a second panic out of the report
+    let hook = panic::take_hook();
+    panic::set_hook(Box::new(|_| {}));
+    let parsed = panic::catch_unwind(AssertUnwindSafe(|| crate::parse_program(&source)));
+    panic::set_hook(hook);
+    match parsed {
+        Ok(Ok(classes)) => match classes.iter().find(|c| c.name == class) {
+            Some(c) => crate::pretty::print_program(std::slice::from_ref(c)),
+            None => source,
+        },
+        _ => source,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_report() {
+        let input = std::env::temp_dir().join(format!("cool-rs-ice-test-{}.cl", std::process::id()));
+        fs::write(&input, "class A { };\nclass Main { main() : Int { 0 }; };\n").unwrap();
+
+        enter_phase("type checking");
+        set_location(Symbol::intern("Main"), Some(Symbol::intern("main")));
+        let hook = panic::take_hook();
+        install_hook();
+        let result = panic::catch_unwind(|| panic!("boom"));
+        panic::set_hook(hook);
+        assert!(result.is_err());
+
+        let path = report(&input).unwrap();
+        let details = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        fs::remove_file(&input).unwrap();
+        assert!(details.starts_with("internal compiler error: boom\n  phase: type checking\n  class: Main\n  method: main\n"));
+        assert!(details.contains(concat!("compiler: cool-rs ", env!("CARGO_PKG_VERSION"))));
+        // Only the class being checked is dumped
+        let context = details.split("-- context\n").nth(1).unwrap();
+        assert!(context.contains("class Main") && !context.contains("class A"));
+    }
+}
@@ -0,0 +1,173 @@
+//! Multi-file programs (`--ext imports`).
+//!
+//! A file may start with `import "path.cl";` directives. Paths are relative
+//! to the importing file. Every file is loaded once however often it is
+//! imported, and is placed after the files it imports, so each root file's
+//! classes come after those of its imports. Imports that lead back to a file still being loaded
+//! form a cycle, which is an error.
+
+use std::path::{Component, Path, PathBuf};
+
+use eyre::eyre;
+
+use crate::ast::Class;
+use crate::ext::Extensions;
+use crate::parsing::recovery::{SyntaxError, SyntaxErrors};
+use crate::source::{SourceReader, SourceText};
+
+/// One loaded file.
+pub struct Module {
+    pub path: PathBuf,
+    pub source: SourceText,
+    pub classes: Vec<Class>,
+}
+
+/// Why a program could not be loaded.
+#[derive(Debug)]
+pub enum LoadError {
+    /// A file could not be read
+    Failed(eyre::Report),
+    /// A file does not parse
+    Syntax { path: PathBuf, source: SourceText, errors: Vec<SyntaxError> },
+    /// The imports form a cycle
+    Rejected(eyre::Report),
+}
+
+/// Load `roots` and everything they import, dependencies first. The
+/// classes of each module are tagged with its index in the result.
+pub fn load(
+    reader: &dyn SourceReader,
+    roots: &[PathBuf],
+    extensions: &Extensions,
+) -> Result<Vec<Module>, LoadError> {
+    let mut loader = Loader {
+        reader,
+        extensions,
+        modules: Vec::new(),
+        active: Vec::new(),
+    };
+    for root in roots {
+        loader.visit(normalize(root))?;
+    }
+    Ok(loader.modules)
+}
+
+struct Loader<'a> {
+    reader: &'a dyn SourceReader,
+    extensions: &'a Extensions,
+    modules: Vec<Module>,
+    /// Files whose imports are being loaded, outermost first
+    active: Vec<PathBuf>,
+}
+
+impl Loader<'_> {
+    fn visit(&mut self, path: PathBuf) -> Result<(), LoadError> {
+        if let Some(start) = self.active.iter().position(|p| *p == path) {
+            let cycle: Vec<String> = self.active[start..]
+                .iter()
+                .chain([&path])
+                .map(|p| p.display().to_string())
+                .collect();
+            return Err(LoadError::Rejected(eyre!("Import cycle: {}", cycle.join(" -> "))));
+        }
+        if self.modules.iter().any(|m| m.path == path) {
+            return Ok(());
+        }
+
+        let source = SourceText::read_with(self.reader, &path).map_err(LoadError::Failed)?;
+        let mut program = match crate::parse_module(source.as_str(), self.extensions) {
+            Ok(program) => program,
+            Err(err) => {
+                let errors = err.downcast::<SyntaxErrors>().map_err(LoadError::Failed)?.0;
+                return Err(LoadError::Syntax { path, source, errors });
+            }
+        };
+
+        let dir = path.parent().map(Path::to_path_buf).unwrap_or_default();
+        self.active.push(path.clone());
+        for import in &program.imports {
+            self.visit(normalize(&dir.join(import)))?;
+        }
+        self.active.pop();
+
+        for class in &mut program.classes {
+            class.set_file(self.modules.len());
+        }
+        self.modules.push(Module { path, source, classes: program.classes });
+        Ok(())
+    }
+}
+
+/// Drop `.` components and fold `dir/..`, so different spellings of the same
+/// path are loaded once. This is purely textual: symlinks are not resolved.
+fn normalize(path: &Path) -> PathBuf {
+    let mut out = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir if matches!(out.components().next_back(), Some(Component::Normal(_))) => {
+                out.pop();
+            }
+            other => out.push(other),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::io;
+
+    use super::*;
+    use crate::ext::Extension;
+    use crate::source::Contents;
+
+    /// An in-memory file tree.
+    struct Files(HashMap<&'static str, &'static str>);
+
+    impl SourceReader for Files {
+        fn read(&self, path: &Path) -> io::Result<Contents> {
+            let text = path.to_str().and_then(|p| self.0.get(p)).ok_or(io::ErrorKind::NotFound)?;
+            Ok(Contents::Bytes(text.as_bytes().to_vec()))
+        }
+    }
+
+    fn load_files(files: &[(&'static str, &'static str)]) -> Result<Vec<Module>, LoadError> {
+        let extensions = [Extension::Imports].into_iter().collect();
+        load(&Files(files.iter().copied().collect()), &[PathBuf::from("main.cl")], &extensions)
+    }
+
+    #[test]
+    fn test_imports_load_once_dependencies_first() {
+        let modules = load_files(&[
+            ("main.cl", "import \"lib/a.cl\"; import \"./lib/b.cl\"; class Main {};"),
+            ("lib/a.cl", "import \"b.cl\"; class A {};"),
+            ("lib/b.cl", "import \"../common.cl\";"),
+            ("common.cl", "class Common {};"),
+        ])
+        .unwrap();
+        let paths: Vec<_> = modules.iter().map(|m| m.path.to_str().unwrap()).collect();
+        assert_eq!(paths, ["common.cl", "lib/b.cl", "lib/a.cl", "main.cl"]);
+        let files: Vec<_> = modules.iter().flat_map(|m| &m.classes).map(|c| (c.name.as_str(), c.file)).collect();
+        assert_eq!(files, [("Common", 0), ("A", 2), ("Main", 3)]);
+    }
+
+    #[test]
+    fn test_import_errors() {
+        let err = load_files(&[
+            ("main.cl", "import \"a.cl\"; class Main {};"),
+            ("a.cl", "import \"main.cl\"; class A {};"),
+        ])
+        .err()
+        .unwrap();
+        assert!(matches!(&err, LoadError::Rejected(e) if e.to_string() == "Import cycle: main.cl -> a.cl -> main.cl"));
+
+        let err = load_files(&[("main.cl", "import \"missing.cl\"; class Main {};")]).err().unwrap();
+        assert!(matches!(err, LoadError::Failed(_)));
+
+        // Imports must come before the first class
+        let err = load_files(&[("main.cl", "class Main {}; import \"a.cl\";")]).err().unwrap();
+        assert!(matches!(err, LoadError::Syntax { errors, .. } if errors.len() == 1));
+    }
+}
@@ -0,0 +1,526 @@
+//! Re-checking a program after each edit, for editors, watch mode and
+//! `--incremental`.
+//!
+//! A `Session` keeps what its last run found for each class, keyed by
+//! content hash. Each file is lexed whole and cut at every `class` keyword;
+//! a class whose text is unchanged is not parsed again, only moved to where
+//! it now starts. Its type-checking results are reused for as long as what
+//! they depend on is unchanged: its own text, the text of its subclasses
+//! (whose reads decide the unused-attribute lint), and the declarations of
+//! the classes it can see — those it names, their ancestors, the classes
+//! named in their declarations, and so on. So an edit inside a method body
+//! re-checks that class and its ancestors, and an edit to a declaration — a
+//! parent, an attribute type, a method signature — re-checks the classes
+//! that can see it. The inheritance and feature checks are cheap and always
+//! run on the whole program.
+//!
+//! `store` keeps the results in a `Cache`, one entry per file keyed by the
+//! hash of its text, for `load` to pick up in a later process.
+//!
+//! ```
+//! use cool_rs::compiler::Compiler;
+//! use cool_rs::incremental::Session;
+//!
+//! let mut session = Session::new(Compiler::default());
+//! let mut source = String::from("class A { f() : Int { 1 }; };\nclass Main { main() : Int { 0 }; };\n");
+//! session.analyze(&source, &mut Default::default()).unwrap();
+//! source = source.replace("{ 1 }", "{ 2 }");
+//! session.analyze(&source, &mut Default::default()).unwrap();
+//! assert_eq!((session.stats().parsed, session.stats().checked), (1, 1));
+//! ```
+
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write;
+use std::io;
+use std::time::{Duration, Instant};
+
+use eyre::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::ast::{Class, Feature, VarDecl};
+use crate::cache::{cache_key, Cache};
+use crate::compiler::Compiler;
+use crate::cool::ClassTyParser;
+use crate::ext::Extensions;
+use crate::parsing::nesting::limit_nesting;
+use crate::parsing::recovery::spanned;
+use crate::parsing::scanner::Scanner;
+use crate::parsing::token::{Position, Token};
+use crate::semantic::collector::ErrorCollector;
+use crate::semantic::context::AnalysisContext;
+use crate::semantic::errors::SemanticError;
+use crate::semantic::type_checker::check_class_expressions;
+use crate::symbol::{sym, Symbol};
+
+/// Where a cached class starts: as if it began its file, the first one.
+const ORIGIN: Position = Position { line: 1, offset: 0 };
+
+/// How much of the last run was done again, and how long each phase took.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Stats {
+    pub classes: usize,
+    /// Classes parsed again; the others were reused
+    pub parsed: usize,
+    /// Classes type-checked again
+    pub checked: usize,
+    pub lex_time: Duration,
+    pub parse_time: Duration,
+    /// All of the semantic checks
+    pub check_time: Duration,
+}
+
+/// One class of the program, as cut by `Session::parse`.
+struct Piece {
+    file: usize,
+    start: Position,
+    /// The hash of its text, from its `class` keyword up to the next one
+    text: u64,
+    /// The type names in its text
+    types: Vec<Symbol>,
+}
+
+/// The type-checking results of one class, at `ORIGIN`.
+#[derive(Clone, Serialize, Deserialize)]
+struct Checked {
+    /// With its static types
+    class: Class,
+    /// Every diagnostic reported, whatever its level
+    errors: Vec<SemanticError>,
+}
+
+/// What a `Cache` keeps of one file: its classes, by the hash of their
+/// text, and their type-checking results, by the hash of what those
+/// depend on.
+#[derive(Deserialize)]
+struct FileEntry {
+    parsed: Vec<(u64, Class)>,
+    checked: Vec<(u64, Checked)>,
+}
+
+#[derive(Serialize)]
+struct FileEntryRef<'a> {
+    parsed: Vec<(u64, &'a Class)>,
+    checked: Vec<(u64, &'a Checked)>,
+}
+
+/// A program checked again and again as it is edited.
+pub struct Session {
+    compiler: Compiler,
+    /// Parsed classes by the hash of their text, at `ORIGIN`
+    parsed: HashMap<u64, Class>,
+    /// Checked classes by the hash of what their results depend on
+    checked: HashMap<u64, Checked>,
+    /// The classes the last `parse` returned, if it cut the files into them
+    pieces: Option<Vec<Piece>>,
+    /// The key in `checked` of each of those, once they were checked
+    keys: Vec<u64>,
+    stats: Stats,
+}
+
+impl Session {
+    pub fn new(compiler: Compiler) -> Self {
+        Session {
+            compiler,
+            parsed: HashMap::new(),
+            checked: HashMap::new(),
+            pieces: None,
+            keys: Vec::new(),
+            stats: Stats::default(),
+        }
+    }
+
+    pub fn stats(&self) -> Stats {
+        self.stats
+    }
+
+    /// Parse and check `source`, the program's text as it is now, like
+    /// `Compiler::parse` and then `Compiler::analyze`, with the same
+    /// results. Only the results of the last run are kept.
+    pub fn analyze(&mut self, source: &str, ec: &mut ErrorCollector) -> Result<Option<Vec<Class>>> {
+        let classes = self.parse(&[source])?;
+        Ok(self.check(&classes, ec))
+    }
+
+    /// Parse `sources`, the program's files as they are now, like
+    /// `Compiler::parse` on each, with `Class::file` numbering them in
+    /// order. The first file with a syntax error is returned as
+    /// `Compiler::parse` returns it, with every error in that file.
+    pub fn parse(&mut self, sources: &[&str]) -> Result<Vec<Class>> {
+        self.stats = Stats::default();
+        self.pieces = None;
+        self.keys.clear();
+        let mut parsed = HashMap::new();
+        let mut pieces = Vec::new();
+        let mut classes = Vec::new();
+        let mut cut = true;
+        for (file, source) in sources.iter().enumerate() {
+            match self.parse_file(file, source, &mut parsed, &mut pieces)? {
+                Some(file_classes) => classes.extend(file_classes),
+                None => cut = false,
+            }
+        }
+        if !cut {
+            // Not all cut into classes (e.g. there are imports): everything is redone
+            let start = Instant::now();
+            let mut classes = Vec::new();
+            for (file, source) in sources.iter().enumerate() {
+                classes.extend(self.compiler.parse(source)?.into_iter().map(|mut class| {
+                    class.set_file(file);
+                    class
+                }));
+            }
+            let n = classes.len();
+            self.stats = Stats { classes: n, parsed: n, parse_time: start.elapsed(), ..Stats::default() };
+            return Ok(classes);
+        }
+        self.parsed = parsed;
+        self.stats.classes = pieces.len();
+        self.pieces = Some(pieces);
+        Ok(classes)
+    }
+
+    /// Check `classes`, as the last `parse` returned them, like
+    /// `Compiler::analyze`, with the same results.
+    pub fn check(&mut self, classes: &[Class], ec: &mut ErrorCollector) -> Option<Vec<Class>> {
+        let start = Instant::now();
+        let Some(pieces) = self.pieces.take().filter(|pieces| pieces.len() == classes.len()) else {
+            let typed = self.compiler.analyze(classes, ec);
+            self.stats.checked = if typed.is_some() { classes.len() } else { 0 };
+            self.stats.check_time = start.elapsed();
+            return typed;
+        };
+        let ctx = self.compiler.context(classes);
+
+        crate::ice::enter_phase("inheritance checks");
+        crate::semantic::analyzer::check_inheritance(&ctx, ec);
+        if !ec.has_errors() {
+            crate::ice::enter_phase("feature checks");
+            crate::semantic::symbols::check_class_features(&ctx, ec);
+        }
+        if ec.has_errors() {
+            self.pieces = Some(pieces);
+            self.stats.check_time = start.elapsed();
+            return None;
+        }
+
+        crate::ice::enter_phase("type checking");
+        let keys = check_keys(&ctx, &pieces, self.compiler.extensions());
+        let mut checked = HashMap::new();
+        let mut typed = Vec::new();
+        for ((piece, class), &key) in pieces.iter().zip(classes).zip(&keys) {
+            let entry = match self.checked.remove(&key) {
+                Some(entry) => entry,
+                None => {
+                    self.stats.checked += 1;
+                    let mut own = ErrorCollector::default();
+                    let mut class = check_class_expressions(class, &ctx, &mut own);
+                    class.relocate(piece.start, ORIGIN);
+                    class.set_file(0);
+                    let mut errors = own.reported().to_vec();
+                    for err in &mut errors {
+                        err.relocate(piece.start, ORIGIN);
+                        err.set_file(0);
+                    }
+                    Checked { class, errors }
+                }
+            };
+            for err in &entry.errors {
+                let mut err = err.clone();
+                err.relocate(ORIGIN, piece.start);
+                err.set_file(piece.file);
+                ec.add(err);
+            }
+            let mut class = entry.class.clone();
+            class.relocate(ORIGIN, piece.start);
+            class.set_file(piece.file);
+            typed.push(class);
+            checked.insert(key, entry);
+        }
+        self.checked = checked;
+        self.keys = keys;
+        self.pieces = Some(pieces);
+        self.stats.check_time = start.elapsed();
+        Some(typed)
+    }
+
+    /// Pick up what `store` kept in `cache` of `sources`, so the classes in
+    /// them need not be parsed or checked again. Files with no entry, or an
+    /// unreadable one, are left to be.
+    pub fn load(&mut self, cache: &Cache, sources: &[&str]) {
+        for source in sources {
+            if let Some(entry) = cache.load::<FileEntry>(self.file_key(source)) {
+                self.parsed.extend(entry.parsed);
+                self.checked.extend(entry.checked);
+            }
+        }
+    }
+
+    /// Keep in `cache` what the last run found in each of `sources`, the
+    /// files it was given. Nothing is kept of a program that did not get as
+    /// far as type checking.
+    pub fn store(&self, cache: &Cache, sources: &[&str]) -> io::Result<()> {
+        let Some(pieces) = self.pieces.as_ref().filter(|pieces| pieces.len() == self.keys.len()) else {
+            return Ok(());
+        };
+        for (file, source) in sources.iter().enumerate() {
+            let mut entry = FileEntryRef { parsed: Vec::new(), checked: Vec::new() };
+            for (piece, key) in pieces.iter().zip(&self.keys).filter(|(piece, _)| piece.file == file) {
+                entry.parsed.push((piece.text, &self.parsed[&piece.text]));
+                entry.checked.push((*key, &self.checked[key]));
+            }
+            cache.store(self.file_key(source), &entry)?;
+        }
+        Ok(())
+    }
+
+    fn file_key(&self, source: &str) -> u64 {
+        cache_key(&[source], self.compiler.extensions())
+    }
+
+    /// Cut `source`, file `file` of the program, into its classes and parse
+    /// those that changed, adding them to `parsed` and `pieces`. `None` when
+    /// the file does not start with a class.
+    fn parse_file(
+        &mut self,
+        file: usize,
+        source: &str,
+        parsed: &mut HashMap<u64, Class>,
+        pieces: &mut Vec<Piece>,
+    ) -> Result<Option<Vec<Class>>> {
+        let start = Instant::now();
+        let mut tokens = Vec::new();
+        for result in Scanner::with_extensions(source, self.compiler.extensions().clone()).spanned() {
+            match result {
+                Ok(token) => tokens.push(token),
+                Err(_) => return self.compiler.parse(source).map(|_| None),
+            }
+        }
+        // Rejected before the parser sees it, as a whole run rejects it
+        if limit_nesting(source, tokens.iter().cloned().map(|token| Ok(spanned(token)))).any(|token| token.is_err()) {
+            return self.compiler.parse(source).map(|_| None);
+        }
+        let starts: Vec<usize> = (0..tokens.len()).filter(|&i| tokens[i].0 == Token::Class_).collect();
+        if starts.first() != Some(&0) {
+            return Ok(None);
+        }
+        self.stats.lex_time += start.elapsed();
+        let start = Instant::now();
+
+        let mut classes = Vec::new();
+        for (i, &first) in starts.iter().enumerate() {
+            let last = starts.get(i + 1).copied().unwrap_or(tokens.len());
+            let (_, loc, span) = tokens[first];
+            let end = tokens.get(last).map_or(source.len(), |(_, _, next)| next.start);
+            let text = &source[span.start..end];
+            let start = Position { line: loc.line, offset: span.start };
+            let key = cache_key(&[text], self.compiler.extensions());
+            let class = match self.parsed.get(&key) {
+                Some(class) => class.clone(),
+                None => {
+                    self.stats.parsed += 1;
+                    let tokens: Vec<_> = tokens[first..last].iter().cloned().map(spanned).collect();
+                    let Ok(mut class) = ClassTyParser::new().parse(tokens) else {
+                        // Parsed whole again, to report every error
+                        return self.compiler.parse(source).map(|_| None);
+                    };
+                    class.relocate(start, ORIGIN);
+                    class
+                }
+            };
+            let mut moved = class.clone();
+            moved.relocate(ORIGIN, start);
+            moved.set_file(file);
+            parsed.insert(key, class);
+            let types = tokens[first..last]
+                .iter()
+                .filter_map(|(token, ..)| match token {
+                    Token::Typeid(name) => Some(*name),
+                    _ => None,
+                })
+                .collect();
+            pieces.push(Piece { file, start, text: key, types });
+            classes.push(moved);
+        }
+        self.stats.parse_time += start.elapsed();
+        Ok(Some(classes))
+    }
+}
+
+/// The key in `Session::checked` of each of the classes of `ctx`, cut into
+/// `pieces`: a hash of the texts of the class and of its subclasses, and of
+/// the declarations of every class it can see.
+fn check_keys(ctx: &AnalysisContext<'_>, pieces: &[Piece], extensions: &Extensions) -> Vec<u64> {
+    let declared: HashMap<Symbol, (String, Vec<Symbol>)> =
+        ctx.classes.iter().map(|class| (class.name, (declaration(class), referenced(class)))).collect();
+    let mut texts: HashMap<Symbol, Vec<u64>> = HashMap::new();
+    for (piece, class) in pieces.iter().zip(ctx.classes) {
+        for (name, _) in ctx.lineage(class.name) {
+            texts.entry(name).or_default().push(piece.text);
+        }
+    }
+    pieces
+        .iter()
+        .zip(ctx.classes)
+        .map(|(piece, class)| {
+            let mut inputs: Vec<String> =
+                texts.get(&class.name).into_iter().flatten().map(|text| format!("{:016x}", text)).collect();
+            // The classes it names, and then those their declarations name
+            let mut visible = Vec::new();
+            let mut seen = HashSet::new();
+            let mut next: Vec<Symbol> = piece.types.iter().copied().chain([class.name]).collect();
+            while let Some(name) = next.pop() {
+                if !seen.insert(name) {
+                    continue;
+                }
+                match declared.get(&name) {
+                    Some((declaration, names)) => {
+                        visible.push(declaration.clone());
+                        next.extend(names);
+                    }
+                    None if ctx.is_class(name) => visible.push(format!("class {}", name)),
+                    None => visible.push(format!("undefined {}", name)),
+                }
+            }
+            visible.sort();
+            inputs.extend(visible);
+            let inputs: Vec<&str> = inputs.iter().map(String::as_str).collect();
+            cache_key(&inputs, extensions)
+        })
+        .collect()
+}
+
+/// What other classes see of `class`: its name, parent, attribute types
+/// and method signatures.
+fn declaration(class: &Class) -> String {
+    let mut out = String::new();
+    writeln!(out, "class {} inherits {}", class.name, class.inherits.unwrap_or(sym::OBJECT)).unwrap();
+    for feature in &class.feature_list {
+        match feature {
+            Feature::Attribute(VarDecl { oid, tid, .. }) => writeln!(out, "{} : {}", oid, tid).unwrap(),
+            Feature::Method(name, args, ret_type, ..) => {
+                let formals: Vec<String> = args.iter().map(|arg| arg.tid.to_string()).collect();
+                writeln!(out, "{}({}) : {}", name, formals.join(", "), ret_type).unwrap();
+            }
+        }
+    }
+    out
+}
+
+/// The classes named in the `declaration` of `class`.
+fn referenced(class: &Class) -> Vec<Symbol> {
+    let mut names = vec![class.inherits.unwrap_or(sym::OBJECT)];
+    for feature in &class.feature_list {
+        match feature {
+            Feature::Attribute(VarDecl { tid, .. }) => names.push(*tid),
+            Feature::Method(_, args, ret_type, ..) => {
+                names.extend(args.iter().map(|arg| arg.tid));
+                names.push(*ret_type);
+            }
+        }
+    }
+    names
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PROGRAM: &str = "\
+class Shape {
+    sides : Int <- 0;
+    area() : Int { 0 };
+};
+
+class Square inherits Shape {
+    side : Int <- 2;
+    area() : Int { side * side };
+};
+
+class Main {
+    main() : Int { (new Square).area() + x };
+};
+";
+
+    /// Check `source` in `session`, and make sure it finds what a whole run
+    /// does. Returns how many classes there are, were parsed and were checked.
+    fn analyze(session: &mut Session, source: &str) -> (usize, usize, usize) {
+        let mut incremental = ErrorCollector::default();
+        let typed = session.analyze(source, &mut incremental).unwrap();
+        let compiler = Compiler::default();
+        let mut whole = ErrorCollector::default();
+        assert_eq!(typed, compiler.analyze(&compiler.parse(source).unwrap(), &mut whole), "{}", source);
+        assert_eq!(incremental.errors, whole.errors, "{}", source);
+        assert_eq!(incremental.warnings, whole.warnings, "{}", source);
+        let Stats { classes, parsed, checked, .. } = session.stats();
+        (classes, parsed, checked)
+    }
+
+    #[test]
+    fn test_rechecks_what_changed() {
+        let mut session = Session::new(Compiler::default());
+        assert_eq!(analyze(&mut session, PROGRAM), (3, 3, 3));
+        assert_eq!(analyze(&mut session, PROGRAM), (3, 0, 0));
+
+        // A new line in a body moves the classes after it, which are reused
+        let body = PROGRAM.replace("area() : Int { 0 };", "area() : Int {\n        sides\n    };");
+        assert_eq!(analyze(&mut session, &body), (3, 1, 1));
+        // A subclass decides whether its ancestors' attributes are read
+        let sub = body.replace("side * side", "side * sides");
+        assert_eq!(analyze(&mut session, &sub), (3, 1, 2));
+        // A declaration is seen by the classes that name it and by their subclasses
+        let decl = sub.replace("side : Int <- 2", "side : String <- 2");
+        assert_eq!(analyze(&mut session, &decl), (3, 1, 3));
+        let other = format!("{}class Other {{ n : Int; f() : Int {{ n }}; }};\n", decl);
+        assert_eq!(analyze(&mut session, &other), (4, 1, 1));
+        let other = other.replace("n : Int", "n : Bool");
+        assert_eq!(analyze(&mut session, &other), (4, 1, 1));
+        let named = other.replace("(new Square).area() + x", "(new Other).f() + x");
+        assert_eq!(analyze(&mut session, &named), (4, 1, 1));
+        assert_eq!(analyze(&mut session, &named.replace("f() : Int", "f() : Bool")), (4, 1, 2));
+    }
+
+    #[test]
+    fn test_files_and_cache() {
+        let dir = std::env::temp_dir().join(format!("cool-rs-incremental-{}", std::process::id()));
+        let cache = Cache::new(&dir);
+        let (lib, main) = PROGRAM.split_at(PROGRAM.find("class Main").unwrap());
+        let check = |session: &mut Session, sources: &[&str]| {
+            let classes = session.parse(sources).unwrap();
+            let mut ec = ErrorCollector::default();
+            let typed = session.check(&classes, &mut ec).unwrap();
+            session.store(&cache, sources).unwrap();
+            assert_eq!(typed.iter().map(|class| class.file).collect::<Vec<_>>(), [0, 0, 1]);
+            let errors: Vec<(Option<usize>, String)> = ec.errors.iter().map(|e| (e.file(), e.to_string())).collect();
+            assert_eq!(errors, [(Some(1), "[line 2] Variable 'x' is not declared".to_string())]);
+            let Stats { parsed, checked, .. } = session.stats();
+            (parsed, checked)
+        };
+        assert_eq!(check(&mut Session::new(Compiler::default()), &[lib, main]), (3, 3));
+
+        // A later process picks up what is stored for each file
+        let mut session = Session::new(Compiler::default());
+        session.load(&cache, &[lib, main]);
+        assert_eq!(check(&mut session, &[lib, main]), (0, 0));
+        let mut session = Session::new(Compiler::default());
+        let edited = main.replace("+ x", "+ x + 1");
+        session.load(&cache, &[lib, &edited]);
+        assert_eq!(check(&mut session, &[lib, &edited]), (1, 1));
+        std::fs::remove_dir_all(dir).ok();
+    }
+
+    #[test]
+    fn test_whole_runs() {
+        let mut session = Session::new(Compiler::default());
+        analyze(&mut session, PROGRAM);
+        // Errors before type checking are reported as by a whole run
+        let duplicate = format!("{}class Shape {{ }};\n", PROGRAM);
+        assert_eq!(analyze(&mut session, &duplicate).2, 0);
+
+        let broken = PROGRAM.replace("area() : Int { 0 };", "area() : Int { 0 }");
+        let err = session.analyze(&broken, &mut ErrorCollector::default()).unwrap_err();
+        let whole = Compiler::default().parse(&broken).unwrap_err();
+        assert_eq!(format!("{:#}", err), format!("{:#}", whole));
+        // What was cached before the error is still there
+        assert_eq!(analyze(&mut session, PROGRAM), (3, 0, 0));
+    }
+}
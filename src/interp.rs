@@ -0,0 +1,692 @@
+//! Tree-walking interpreter (`--run`).
+//!
+//! Executes a program that passed semantic analysis directly from its AST,
+//! starting with `(new Main).main()`. It implements the semantics of the COOL
+//! manual: objects are created with default values and then initialized from
+//! the root class down, dispatch is on the dynamic class of the receiver, and
+//! `case` picks the branch of the closest ancestor. `Int`, `Bool` and
+//! `String` are values rather than objects, which they cannot be told apart
+//! from in COOL. The `arrays`, `exceptions` and `natives` extensions run as
+//! well.
+//!
+//! What the manual calls a runtime error (dispatch or `case` on void, no
+//! matching branch, division by zero, a substring out of range, or `abort`)
+//! stops the program with a `RuntimeError`, as does an arithmetic overflow
+//! when overflow traps (`arith::Overflow`).
+
+use std::cell::RefCell;
+use std::fmt;
+use std::io::{BufRead, Write};
+use std::rc::Rc;
+
+use crate::arith::{self, Overflow};
+use crate::ast::{
+    CaseBranch, Class, ComparisonOperator, Expr, Feature, MathOperator, TypedExpr, UnaryOperator,
+    VarDecl,
+};
+use crate::codegen::layout::Layout;
+use crate::natives;
+use crate::semantic::context::AnalysisContext;
+use crate::semantic::errors::SemanticError;
+use crate::symbol::{sym, Symbol};
+
+/// Calls nested deeper than this are reported as a stack overflow instead
+/// of overflowing the interpreter's own stack.
+pub const MAX_DEPTH: usize = 10_000;
+
+/// Stack of the interpreter thread, enough for `MAX_DEPTH` calls.
+const STACK_SIZE: usize = 256 << 20;
+
+/// A COOL value.
+#[derive(Debug, Clone)]
+pub enum Value {
+    Void,
+    Int(i32),
+    Bool(bool),
+    Str(Rc<str>),
+    Object(Rc<Object>),
+}
+
+#[derive(Debug)]
+pub struct Object {
+    pub class: Symbol,
+    /// Attributes in layout order, inherited ones first
+    pub fields: RefCell<Vec<Value>>,
+    /// Elements of an `Array` (`--ext arrays`)
+    pub items: RefCell<Vec<Value>>,
+}
+
+impl Value {
+    /// The dynamic class, or `None` for void.
+    pub fn class(&self) -> Option<Symbol> {
+        match self {
+            Value::Void => None,
+            Value::Int(_) => Some(sym::INT),
+            Value::Bool(_) => Some(sym::BOOL),
+            Value::Str(_) => Some(sym::STRING),
+            Value::Object(object) => Some(object.class),
+        }
+    }
+
+    pub(crate) fn as_int(&self) -> i32 {
+        match self {
+            Value::Int(i) => *i,
+            _ => unreachable!("type-checked as Int: {:?}", self),
+        }
+    }
+
+    pub(crate) fn as_bool(&self) -> bool {
+        match self {
+            Value::Bool(b) => *b,
+            _ => unreachable!("type-checked as Bool: {:?}", self),
+        }
+    }
+
+    pub(crate) fn as_str(&self) -> &str {
+        match self {
+            Value::Str(s) => s,
+            _ => unreachable!("type-checked as String: {:?}", self),
+        }
+    }
+}
+
+/// Constants as they are written in COOL, objects as `<Class>`.
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Void => write!(f, "void"),
+            Value::Int(i) => write!(f, "{}", i),
+            Value::Bool(b) => write!(f, "{}", b),
+            Value::Str(s) => write!(f, "{}", crate::parsing::dump::escape_str(s)),
+            Value::Object(object) => write!(f, "<{}>", object.class),
+        }
+    }
+}
+
+/// Why a program stopped before `main` returned.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RuntimeError {
+    /// Line of the expression being evaluated
+    pub line: usize,
+    /// Input file of the class the line is in, as `Class::file`; `None`
+    /// when the error is in no class, e.g. a missing `Main`
+    pub file: Option<usize>,
+    pub message: String,
+}
+
+impl fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[line {}] {}", self.line, self.message)
+    }
+}
+
+impl RuntimeError {
+    /// The error as `--run` reports it, after the name of its file among
+    /// `files`, the program's input files: `foo.cl: [line 3] Division by zero`.
+    pub fn located(&self, files: &[String]) -> String {
+        match self.file.and_then(|file| files.get(file)) {
+            Some(name) => format!("{}: {}", name, self),
+            None => self.to_string(),
+        }
+    }
+}
+
+impl std::error::Error for RuntimeError {}
+
+/// Control leaving an expression other than by producing a value.
+enum Unwind {
+    Error(RuntimeError),
+    /// A `throw` looking for its `try` (`--ext exceptions`), with its line
+    /// and file
+    Throw(Value, usize, Option<usize>),
+}
+
+type Eval = Result<Value, Unwind>;
+
+fn error(line: usize, message: impl Into<String>) -> Unwind {
+    Unwind::Error(runtime_error(line, message))
+}
+
+fn runtime_error(line: usize, message: impl Into<String>) -> RuntimeError {
+    RuntimeError { line, file: None, message: message.into() }
+}
+
+/// `result` of evaluating code of the input file `file`: an error or
+/// `throw` not yet placed in a file came from that code, or from a call it
+/// made that failed before running any code of its own.
+fn in_file(result: Eval, file: usize) -> Eval {
+    result.map_err(|unwind| match unwind {
+        Unwind::Error(err) => Unwind::Error(RuntimeError { file: err.file.or(Some(file)), ..err }),
+        Unwind::Throw(value, line, thrown_in) => Unwind::Throw(value, line, thrown_in.or(Some(file))),
+    })
+}
+
+/// The message for a `case` on line `line` with no branch for `class`, the
+/// class of its value.
+pub(crate) fn no_branch(class: Symbol, line: usize) -> RuntimeError {
+    let diagnostic = SemanticError::NoBranchInCase { expr_type: class, line, span: Default::default() };
+    runtime_error(line, diagnostic.message())
+}
+
+/// Run the program of `ctx`, which must have checked cleanly, reading
+/// `in_string`/`in_int` input from `input` and writing to `output`, with
+/// `overflow` deciding what an overflowing `Int` operation does.
+pub fn run(
+    ctx: &AnalysisContext<'_>,
+    overflow: Overflow,
+    input: &mut (dyn BufRead + Send),
+    output: &mut (dyn Write + Send),
+) -> Result<(), RuntimeError> {
+    if !ctx.is_class(sym::MAIN_CLASS) {
+        return Err(runtime_error(0, "No class Main"));
+    }
+    on_own_stack(|| call_here(ctx, overflow, sym::MAIN_CLASS, sym::MAIN_METHOD, input, output).map(|_| ()))
+}
+
+/// Evaluate `(new class).method()` in the program of `ctx`, as `run` does
+/// for `Main.main` with arithmetic wrapping around, and return the result
+/// as the REPL shows it.
+pub fn call(
+    ctx: &AnalysisContext<'_>,
+    class: Symbol,
+    method: Symbol,
+    input: &mut (dyn BufRead + Send),
+    output: &mut (dyn Write + Send),
+) -> Result<String, RuntimeError> {
+    on_own_stack(|| call_here(ctx, Overflow::Wrap, class, method, input, output).map(|value| value.to_string()))
+}
+
+/// Evaluation recurses once per nested expression, so it gets a stack of
+/// its own rather than the caller's.
+fn on_own_stack<T: Send>(f: impl FnOnce() -> T + Send) -> T {
+    crate::on_own_stack("interpreter", STACK_SIZE, f)
+}
+
+fn call_here(
+    ctx: &AnalysisContext<'_>,
+    overflow: Overflow,
+    class: Symbol,
+    method: Symbol,
+    input: &mut dyn BufRead,
+    output: &mut dyn Write,
+) -> Result<Value, RuntimeError> {
+    let all: Vec<&Class> = ctx.all_classes().collect();
+    let layout = Layout::new(&all);
+    let mut interpreter = Interpreter { ctx, layout, overflow, input, output, depth: 0 };
+    let result = interpreter
+        .new_object(class)
+        .and_then(|receiver| interpreter.dispatch(receiver, class, method, Vec::new(), 0));
+    interpreter.output.flush().map_err(|err| runtime_error(0, format!("Failed to write output: {}", err)))?;
+    match result {
+        Ok(value) => Ok(value),
+        Err(Unwind::Error(err)) => Err(err),
+        Err(Unwind::Throw(value, line, file)) => Err(RuntimeError {
+            line,
+            file,
+            message: format!("Uncaught exception of class {}", value.class().unwrap()),
+        }),
+    }
+}
+
+struct Interpreter<'c, 'a, 'io> {
+    ctx: &'c AnalysisContext<'a>,
+    layout: Layout<'a>,
+    overflow: Overflow,
+    input: &'io mut dyn BufRead,
+    output: &'io mut dyn Write,
+    /// Method calls in progress
+    depth: usize,
+}
+
+/// Bindings of the method or initializer being evaluated.
+struct Frame {
+    this: Value,
+    /// Formals, `let` and `case` bindings, innermost last
+    locals: Vec<(Symbol, Value)>,
+}
+
+impl<'a> Interpreter<'_, 'a, '_> {
+    fn new_object(&mut self, class: Symbol) -> Eval {
+        match class {
+            sym::INT => return Ok(Value::Int(0)),
+            sym::BOOL => return Ok(Value::Bool(false)),
+            sym::STRING => return Ok(Value::Str("".into())),
+            _ => {}
+        }
+        let layout = self.layout.class(class);
+        let fields = layout.attributes.iter().map(|(_, ty)| default_value(*ty)).collect();
+        let object = Value::Object(Rc::new(Object {
+            class,
+            fields: RefCell::new(fields),
+            items: RefCell::new(Vec::new()),
+        }));
+
+        // Initializers run from the root class down, each seeing the
+        // attributes set by the ones before it
+        let mut ancestors = vec![self.layout.class(class).class];
+        while let Some(parent) = parent_of(ancestors.last().unwrap()) {
+            ancestors.push(self.layout.class(parent).class);
+        }
+        for ancestor in ancestors.into_iter().rev() {
+            for feature in &ancestor.feature_list {
+                if let Feature::Attribute(VarDecl { oid, expr: Some(init), .. }) = feature {
+                    let mut frame = Frame { this: object.clone(), locals: Vec::new() };
+                    let value = in_file(self.eval(init, &mut frame), ancestor.file)?;
+                    self.set_field(&object, *oid, value);
+                }
+            }
+        }
+        Ok(object)
+    }
+
+    fn field_index(&self, object: &Object, attr: Symbol) -> Option<usize> {
+        self.layout.class(object.class).attributes.iter().position(|(a, _)| *a == attr)
+    }
+
+    fn set_field(&self, this: &Value, attr: Symbol, value: Value) {
+        let Value::Object(object) = this else { unreachable!("attributes belong to objects") };
+        let index = self.field_index(object, attr).expect("bound by the type checker");
+        object.fields.borrow_mut()[index] = value;
+    }
+
+    /// Call `method` on `receiver`, looked up in the dispatch table of `class`.
+    fn dispatch(&mut self, receiver: Value, class: Symbol, method: Symbol, args: Vec<Value>, line: usize) -> Eval {
+        let layout = self.layout.class(class);
+        let (_, owner) = *layout.methods.iter().find(|(m, _)| *m == method).expect("resolved by the type checker");
+        let owner_class = self.layout.class(owner).class;
+        let Some((formals, ret, body)) = owner_class.feature_list.iter().find_map(|f| match f {
+            Feature::Method(name, formals, ret, body, _) if *name == method => Some((formals, *ret, body)),
+            _ => None,
+        }) else {
+            unreachable!("{}.{} is in the dispatch table", owner, method)
+        };
+
+        if let Expr::Native = body.expr {
+            let params: Vec<Symbol> = formals.iter().map(|f| f.tid).collect();
+            // Classes registered through `Compiler` may declare natives the table lacks
+            return match natives::lookup(owner, method, &params, ret) {
+                Some(host) => Ok((host.call)(&args)),
+                None => Err(error(line, format!("No host function for native method {}.{}", owner, method))),
+            };
+        }
+        if let Expr::Builtin = body.expr {
+            return builtin(receiver, owner, method, args, self.input, self.output, line).map_err(Unwind::Error);
+        }
+
+        if self.depth >= MAX_DEPTH {
+            return Err(error(line, format!("Stack overflow: more than {} nested calls", MAX_DEPTH)));
+        }
+        self.depth += 1;
+        let locals = formals.iter().map(|f| f.id).zip(args).collect();
+        let mut frame = Frame { this: receiver, locals };
+        let result = in_file(self.eval(body, &mut frame), owner_class.file);
+        self.depth -= 1;
+        result
+    }
+
+    fn lookup(&self, name: Symbol, frame: &Frame) -> Value {
+        if name == sym::SELF {
+            return frame.this.clone();
+        }
+        if let Some((_, value)) = frame.locals.iter().rev().find(|(n, _)| *n == name) {
+            return value.clone();
+        }
+        let Value::Object(object) = &frame.this else { unreachable!("bound by the type checker") };
+        let index = self.field_index(object, name).expect("bound by the type checker");
+        object.fields.borrow()[index].clone()
+    }
+
+    fn assign(&self, name: Symbol, value: Value, frame: &mut Frame) {
+        match frame.locals.iter_mut().rev().find(|(n, _)| *n == name) {
+            Some((_, slot)) => *slot = value,
+            None => self.set_field(&frame.this, name, value),
+        }
+    }
+
+    fn eval(&mut self, e: &TypedExpr, frame: &mut Frame) -> Eval {
+        match &e.expr {
+            Expr::Paren(inner) => self.eval(inner, frame),
+            Expr::Bool(b) => Ok(Value::Bool(*b)),
+            Expr::Int(i) => Ok(Value::Int(*i)),
+            Expr::Str(s) => Ok(Value::Str(s.as_str().into())),
+            Expr::Identifier(name) => Ok(self.lookup(*name, frame)),
+            Expr::Assignment(name, rhs) => {
+                let value = self.eval(rhs, frame)?;
+                self.assign(*name, value.clone(), frame);
+                Ok(value)
+            }
+            Expr::New(ty) => {
+                let class = match *ty {
+                    sym::SELF_TYPE => frame.this.class().unwrap(),
+                    ty => ty,
+                };
+                self.new_object(class)
+            }
+            Expr::Block(exprs) => {
+                let mut value = Value::Void;
+                for e in exprs {
+                    value = self.eval(e, frame)?;
+                }
+                Ok(value)
+            }
+            Expr::Let(bindings, body) => {
+                let mark = frame.locals.len();
+                for VarDecl { oid: id, tid: ty, expr: init, .. } in bindings {
+                    let value = match init {
+                        Some(init) => self.eval(init, frame),
+                        None => Ok(default_value(*ty)),
+                    };
+                    match value {
+                        Ok(value) => frame.locals.push((*id, value)),
+                        Err(unwind) => {
+                            frame.locals.truncate(mark);
+                            return Err(unwind);
+                        }
+                    }
+                }
+                let result = self.eval(body, frame);
+                frame.locals.truncate(mark);
+                result
+            }
+            Expr::Math { lhs, op, rhs } => {
+                let a = self.eval(lhs, frame)?.as_int();
+                let b = self.eval(rhs, frame)?.as_int();
+                if *op == MathOperator::Div && b == 0 {
+                    return Err(error(e.line, "Division by zero"));
+                }
+                let value = self.overflow.math(op, a, b).ok_or_else(|| error(e.line, arith::MESSAGE))?;
+                Ok(Value::Int(value))
+            }
+            Expr::UnaryOperation { op: UnaryOperator::Neg, s } => {
+                let value = self.eval(s, frame)?.as_int();
+                Ok(Value::Int(self.overflow.negate(value).ok_or_else(|| error(e.line, arith::MESSAGE))?))
+            }
+            Expr::UnaryOperation { op: UnaryOperator::Not, s } => Ok(Value::Bool(!self.eval(s, frame)?.as_bool())),
+            Expr::Comparison { lhs, op, rhs } => {
+                let a = self.eval(lhs, frame)?;
+                let b = self.eval(rhs, frame)?;
+                Ok(Value::Bool(match op {
+                    ComparisonOperator::Lt => a.as_int() < b.as_int(),
+                    ComparisonOperator::Le => a.as_int() <= b.as_int(),
+                    ComparisonOperator::Equal => equal(&a, &b),
+                }))
+            }
+            Expr::Isvoid(inner) => Ok(Value::Bool(matches!(self.eval(inner, frame)?, Value::Void))),
+            Expr::Conditional { test, then, orelse } => {
+                if self.eval(test, frame)?.as_bool() {
+                    self.eval(then, frame)
+                } else {
+                    self.eval(orelse, frame)
+                }
+            }
+            Expr::While { test, exec } => {
+                while self.eval(test, frame)?.as_bool() {
+                    self.eval(exec, frame)?;
+                }
+                Ok(Value::Void)
+            }
+            Expr::Case(scrutinee, branches) => {
+                let value = self.eval(scrutinee, frame)?;
+                let Some(class) = value.class() else {
+                    return Err(error(e.line, "Case on void"));
+                };
+                let Some(branch) = self.closest_branch(class, branches) else {
+                    return Err(Unwind::Error(no_branch(class, e.line)));
+                };
+                frame.locals.push((branch.id, value));
+                let result = self.eval(&branch.expr, frame);
+                frame.locals.pop();
+                result
+            }
+            Expr::Dispatch { target, targettype, id, exprs } => {
+                let mut args = Vec::with_capacity(exprs.len());
+                for arg in exprs {
+                    args.push(self.eval(arg, frame)?);
+                }
+                let receiver = match target {
+                    Some(target) => self.eval(target, frame)?,
+                    None => frame.this.clone(),
+                };
+                let Some(class) = receiver.class() else {
+                    return Err(error(e.line, format!("Dispatch of '{}' on void", id)));
+                };
+                self.dispatch(receiver, targettype.unwrap_or(class), *id, args, e.line)
+            }
+            Expr::Try { body, id, tid, handler } => match self.eval(body, frame) {
+                Err(Unwind::Throw(value, ..)) if self.ctx.hierarchy.conforms(value.class().unwrap(), *tid) => {
+                    frame.locals.push((*id, value));
+                    let result = self.eval(handler, frame);
+                    frame.locals.pop();
+                    result
+                }
+                result => result,
+            },
+            Expr::Throw(inner) => match self.eval(inner, frame)? {
+                Value::Void => Err(error(e.line, "Throw of void")),
+                value => Err(Unwind::Throw(value, e.line, None)),
+            },
+            Expr::Native => unreachable!("native bodies are dispatched to host functions"),
+            Expr::Builtin => unreachable!("built-in bodies are dispatched to `builtin`"),
+        }
+    }
+
+    /// The branch whose class is the closest ancestor of `class`.
+    fn closest_branch<'b>(&self, class: Symbol, branches: &'b [CaseBranch]) -> Option<&'b CaseBranch> {
+        let mut ancestor = Some(class);
+        while let Some(name) = ancestor {
+            if let Some(branch) = branches.iter().find(|b| b.tid == name) {
+                return Some(branch);
+            }
+            ancestor = parent_of(self.layout.class(name).class);
+        }
+        None
+    }
+}
+
+fn parent_of(class: &Class) -> Option<Symbol> {
+    (class.name != sym::OBJECT).then(|| class.inherits.unwrap_or(sym::OBJECT))
+}
+
+/// The value of an attribute or `let` binding without an initializer.
+pub(crate) fn default_value(ty: Symbol) -> Value {
+    match ty {
+        sym::INT => Value::Int(0),
+        sym::BOOL => Value::Bool(false),
+        sym::STRING => Value::Str("".into()),
+        _ => Value::Void,
+    }
+}
+
+/// `a = b`: basic values compare by value, objects by identity.
+pub(crate) fn equal(a: &Value, b: &Value) -> bool {
+    match (a, b) {
+        (Value::Void, Value::Void) => true,
+        (Value::Int(a), Value::Int(b)) => a == b,
+        (Value::Bool(a), Value::Bool(b)) => a == b,
+        (Value::Str(a), Value::Str(b)) => a == b,
+        (Value::Object(a), Value::Object(b)) => Rc::ptr_eq(a, b),
+        _ => false,
+    }
+}
+
+/// The methods of the built-in classes, called at `line` with `receiver`
+/// and `args`. The VM shares them with the interpreter.
+pub(crate) fn builtin(
+    receiver: Value,
+    class: Symbol,
+    method: Symbol,
+    args: Vec<Value>,
+    input: &mut dyn BufRead,
+    output: &mut dyn Write,
+    line: usize,
+) -> Result<Value, RuntimeError> {
+    let write_failed = |err: std::io::Error| runtime_error(line, format!("Failed to write output: {}", err));
+    match (class.as_str(), method.as_str()) {
+        ("Object", "abort") => {
+            let class = receiver.class().unwrap();
+            Err(runtime_error(line, format!("Abort called from class {}", class)))
+        }
+        ("Object", "type_name") => Ok(Value::Str(receiver.class().unwrap().as_str().into())),
+        ("Object", "copy") => Ok(match &receiver {
+            Value::Object(object) => Value::Object(Rc::new(Object {
+                class: object.class,
+                fields: RefCell::new(object.fields.borrow().clone()),
+                items: RefCell::new(object.items.borrow().clone()),
+            })),
+            value => value.clone(),
+        }),
+        ("IO", "out_string") => {
+            output.write_all(args[0].as_str().as_bytes()).map_err(write_failed)?;
+            Ok(receiver)
+        }
+        ("IO", "out_int") => {
+            write!(output, "{}", args[0].as_int()).map_err(write_failed)?;
+            Ok(receiver)
+        }
+        ("IO", "in_string") => Ok(Value::Str(read_line(input, output, line)?.into())),
+        ("IO", "in_int") => {
+            let line = read_line(input, output, line)?;
+            Ok(Value::Int(line.trim().parse().unwrap_or(0)))
+        }
+        ("String", "length") => Ok(Value::Int(receiver.as_str().len() as i32)),
+        ("String", "concat") => Ok(Value::Str(format!("{}{}", receiver.as_str(), args[0].as_str()).into())),
+        ("String", "substr") => {
+            let s = receiver.as_str();
+            let (start, length) = (args[0].as_int(), args[1].as_int());
+            let end = start.checked_add(length);
+            match (usize::try_from(start), end.and_then(|end| usize::try_from(end).ok())) {
+                (Ok(start), Some(end)) if end <= s.len() && s.is_char_boundary(start) && s.is_char_boundary(end) => {
+                    Ok(Value::Str(s[start..end].into()))
+                }
+                _ => Err(runtime_error(line, format!("Substring out of range: substr({}, {}) of a string of length {}", start, length, s.len()))),
+            }
+        }
+        ("Array", method) => {
+            let Value::Object(array) = &receiver else { unreachable!("arrays are objects") };
+            let index = |i: i32, len: usize| {
+                usize::try_from(i)
+                    .ok()
+                    .filter(|&i| i < len)
+                    .ok_or_else(|| runtime_error(line, format!("Array index {} out of bounds for length {}", i, len)))
+            };
+            match method {
+                "init" => {
+                    let size = usize::try_from(args[0].as_int())
+                        .map_err(|_| runtime_error(line, format!("Negative array size {}", args[0].as_int())))?;
+                    *array.items.borrow_mut() = vec![Value::Void; size];
+                    Ok(receiver.clone())
+                }
+                "length" => Ok(Value::Int(array.items.borrow().len() as i32)),
+                "get" => {
+                    let items = array.items.borrow();
+                    let i = index(args[0].as_int(), items.len())?;
+                    Ok(items[i].clone())
+                }
+                "set" => {
+                    let mut items = array.items.borrow_mut();
+                    let i = index(args[0].as_int(), items.len())?;
+                    items[i] = args[1].clone();
+                    Ok(args[1].clone())
+                }
+                _ => unreachable!("Array.{} is not a built-in", method),
+            }
+        }
+        _ => unreachable!("{}.{} is not a built-in", class, method),
+    }
+}
+
+/// One line of input without its line break, or "" at end of input.
+fn read_line(input: &mut dyn BufRead, output: &mut dyn Write, line: usize) -> Result<String, RuntimeError> {
+    // A prompt written just before must be visible while waiting
+    output.flush().map_err(|err| runtime_error(line, format!("Failed to write output: {}", err)))?;
+    let mut text = String::new();
+    input
+        .read_line(&mut text)
+        .map_err(|err| runtime_error(line, format!("Failed to read input: {}", err)))?;
+    if text.ends_with('\n') {
+        text.pop();
+        if text.ends_with('\r') {
+            text.pop();
+        }
+    }
+    Ok(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Output of running `source` on `input`, or the runtime error.
+    fn run_source(source: &str, input: &str) -> Result<String, RuntimeError> {
+        run_overflowing(source, input, Overflow::Wrap)
+    }
+
+    fn run_overflowing(source: &str, input: &str, overflow: Overflow) -> Result<String, RuntimeError> {
+        let classes = crate::parse_program(source).unwrap();
+        let ctx = AnalysisContext::new(crate::prelude(), &classes);
+        let mut output = Vec::new();
+        run(&ctx, overflow, &mut input.as_bytes(), &mut output)?;
+        Ok(String::from_utf8(output).unwrap())
+    }
+
+    #[test]
+    fn test_run() {
+        let source = "
+class Counter {
+    n : Int <- 10;
+    bump(by : Int) : SELF_TYPE { { n <- n + by; self; } };
+    get() : Int { n };
+};
+class Twice inherits Counter {
+    bump(by : Int) : SELF_TYPE { { n <- n + 2 * by; self; } };
+};
+class Main inherits IO {
+    main() : Object {
+        let c : Counter <- new Twice, name : String <- in_string() in {
+            out_int(c.bump(1)@Counter.bump(1).get());
+            out_string(\" \".concat(name.substr(1, 3)));
+            case c of o : Object => out_string(\" object\"); t : Counter => out_string(\" counter\"); esac;
+            out_string(\" \".concat(c.type_name()));
+            if isvoid c then out_string(\" void\") else out_string(\" \") fi;
+            out_int(in_int() / 2);
+            out_int(c.copy().bump(5).get() - c.get());
+        }
+    };
+};";
+        assert_eq!(run_source(source, "xabcx\n 42\n").unwrap(), "13 abc counter Twice 2110");
+    }
+
+    #[test]
+    fn test_runtime_errors() {
+        let program = |body: &str| format!("class Main {{ v : Main; main() : Object {{ {} }}; }};", body);
+        let message = |body: &str| run_source(&program(body), "").unwrap_err().message;
+        assert_eq!(message("v.main()"), "Dispatch of 'main' on void");
+        assert_eq!(message("case v of x : Int => x; esac"), "Case on void");
+        assert_eq!(message("case 1 of x : Bool => x; esac"), "No 'case' branch for dynamic type 'Int'");
+        assert_eq!(message("1 / 0"), "Division by zero");
+        assert_eq!(message("\"ab\".substr(1, 2)"), "Substring out of range: substr(1, 2) of a string of length 2");
+        assert_eq!(message("abort()"), "Abort called from class Main");
+        assert_eq!(message("main()"), format!("Stack overflow: more than {} nested calls", MAX_DEPTH));
+    }
+
+    #[test]
+    fn test_interpolation() {
+        let source = "class Main inherits IO {\n    main() : Object {\n        \
+                      let min : Int <- ~2147483647 - 1 in\n            \
+                      out_string(\"\\(0) \\(42) \\(~7) \\(min) \\(1 < 2) \\(\"s\") \\(self)\")\n    };\n};";
+        let extensions = [crate::ext::Extension::Strings].into_iter().collect();
+        let classes = crate::parse_program_with(source, &extensions).unwrap();
+        let ctx = AnalysisContext::new(crate::prelude_with(&extensions), &classes);
+        let mut output = Vec::new();
+        run(&ctx, Overflow::Trap, &mut "".as_bytes(), &mut output).unwrap();
+        assert_eq!(String::from_utf8(output).unwrap(), "0 42 -7 -2147483648 true s Main");
+    }
+
+    #[test]
+    fn test_overflow() {
+        let source = "class Main inherits IO {\n    main() : Object { out_int(~(2147483647 + 1)) };\n};";
+        assert_eq!(run_overflowing(source, "", Overflow::Wrap).unwrap(), "-2147483648");
+        let err = run_overflowing(source, "", Overflow::Trap).unwrap_err();
+        assert_eq!(err.to_string(), "[line 2] Integer overflow");
+    }
+}
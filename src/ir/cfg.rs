@@ -0,0 +1,233 @@
+//! Control-flow graphs and dominators of IR functions (`--emit cfg-dot`).
+//!
+//! `Cfg::new` links the blocks of a function by their terminators and
+//! computes the immediate dominator of every block reachable from the
+//! entry, with the iterative algorithm of Cooper, Harvey and Kennedy ("A
+//! Simple, Fast Dominance Algorithm"): blocks are visited in reverse
+//! postorder until no dominator changes. Dataflow analyses can iterate in
+//! the same order.
+
+use std::fmt::Write;
+
+use super::{BlockId, Function, Program, Terminator};
+
+impl Terminator {
+    /// The blocks it can transfer control to, in order.
+    pub fn successors(&self) -> Vec<BlockId> {
+        match *self {
+            Terminator::Jump(target) => vec![target],
+            Terminator::Branch { then, orelse, .. } if then == orelse => vec![then],
+            Terminator::Branch { then, orelse, .. } => vec![then, orelse],
+            // A throw in the body reaches the handler from wherever it is
+            Terminator::Try { body, handler } => vec![body, handler],
+            Terminator::Return(_)
+            | Terminator::Throw { .. }
+            | Terminator::Rethrow
+            | Terminator::NoMatch { .. }
+            | Terminator::DispatchOnVoid { .. }
+            | Terminator::CaseOnVoid { .. }
+            | Terminator::DivisionByZero { .. }
+            | Terminator::IntegerOverflow { .. } => Vec::new(),
+        }
+    }
+}
+
+/// The control-flow graph of a function, indexed by `BlockId`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cfg {
+    pub successors: Vec<Vec<BlockId>>,
+    /// In increasing order
+    pub predecessors: Vec<Vec<BlockId>>,
+    /// The blocks reachable from the entry, each before its successors
+    /// except along back edges
+    pub order: Vec<BlockId>,
+    /// The entry is its own; unreachable blocks have none
+    pub idom: Vec<Option<BlockId>>,
+}
+
+impl Cfg {
+    pub fn new(function: &Function) -> Cfg {
+        let successors: Vec<Vec<BlockId>> = function.blocks.iter().map(|b| b.terminator.successors()).collect();
+        let mut predecessors = vec![Vec::new(); successors.len()];
+        for (id, targets) in successors.iter().enumerate() {
+            for target in targets {
+                predecessors[target.0 as usize].push(BlockId(id as u32));
+            }
+        }
+
+        // Postorder by depth-first search from the entry
+        let mut postorder = Vec::new();
+        let mut visited = vec![false; successors.len()];
+        let mut stack = vec![(BlockId(0), 0)];
+        visited[0] = true;
+        while let Some((block, next)) = stack.pop() {
+            match successors[block.0 as usize].get(next) {
+                Some(&target) => {
+                    stack.push((block, next + 1));
+                    if !visited[target.0 as usize] {
+                        visited[target.0 as usize] = true;
+                        stack.push((target, 0));
+                    }
+                }
+                None => postorder.push(block),
+            }
+        }
+        let order: Vec<BlockId> = postorder.into_iter().rev().collect();
+
+        let mut number = vec![usize::MAX; successors.len()];
+        for (i, block) in order.iter().enumerate() {
+            number[block.0 as usize] = i;
+        }
+        let mut idom = vec![None; successors.len()];
+        idom[0] = Some(BlockId(0));
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for &block in &order[1..] {
+                // A block after the entry has a processed predecessor
+                let mut preds = predecessors[block.0 as usize].iter().filter(|p| idom[p.0 as usize].is_some());
+                let mut new = *preds.next().unwrap();
+                for &pred in preds {
+                    new = intersect(&idom, &number, pred, new);
+                }
+                if idom[block.0 as usize] != Some(new) {
+                    idom[block.0 as usize] = Some(new);
+                    changed = true;
+                }
+            }
+        }
+        Cfg { successors, predecessors, order, idom }
+    }
+
+    pub fn reachable(&self, block: BlockId) -> bool {
+        self.idom[block.0 as usize].is_some()
+    }
+
+    /// Whether every path from the entry to `b` goes through `a`. Every
+    /// block dominates itself.
+    pub fn dominates(&self, a: BlockId, b: BlockId) -> bool {
+        if !self.reachable(b) {
+            return false;
+        }
+        let mut block = b;
+        loop {
+            if block == a {
+                return true;
+            }
+            if block.0 == 0 {
+                return false;
+            }
+            block = self.idom[block.0 as usize].unwrap();
+        }
+    }
+}
+
+/// The nearest common dominator of `a` and `b`, walking up the tree built
+/// so far by reverse postorder numbers.
+fn intersect(idom: &[Option<BlockId>], number: &[usize], mut a: BlockId, mut b: BlockId) -> BlockId {
+    while a != b {
+        while number[a.0 as usize] > number[b.0 as usize] {
+            a = idom[a.0 as usize].unwrap();
+        }
+        while number[b.0 as usize] > number[a.0 as usize] {
+            b = idom[b.0 as usize].unwrap();
+        }
+    }
+    a
+}
+
+/// `s` inside a double-quoted Graphviz string, lines left-justified.
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\l")
+}
+
+/// A Graphviz graph of `program`: one cluster per function, with a box per
+/// block listing its instructions, solid edges for control flow and dashed
+/// ones from each block's immediate dominator.
+pub fn dot(program: &Program) -> String {
+    let mut out = String::from("digraph program {\n    node [shape=box, fontname=monospace];\n");
+    for (i, function) in program.functions.iter().enumerate() {
+        let cfg = Cfg::new(function);
+        let node = |block: BlockId| format!("f{}b{}", i, block.0);
+        writeln!(out, "    subgraph cluster_{} {{\n        label=\"{}.{}\";", i, function.class, function.name).unwrap();
+        for (id, block) in function.blocks.iter().enumerate() {
+            let mut label = format!("b{}:\n", id);
+            for inst in &block.insts {
+                writeln!(label, "{}", function.inst_to_string(inst, &program.strings)).unwrap();
+            }
+            writeln!(label, "{}", block.terminator).unwrap();
+            writeln!(out, "        {} [label=\"{}\"];", node(BlockId(id as u32)), escape(&label)).unwrap();
+        }
+        for (id, targets) in cfg.successors.iter().enumerate() {
+            for &target in targets {
+                writeln!(out, "        {} -> {};", node(BlockId(id as u32)), node(target)).unwrap();
+            }
+        }
+        for (id, idom) in cfg.idom.iter().enumerate().skip(1) {
+            if let Some(idom) = idom {
+                writeln!(out, "        {} -> {} [style=dashed, color=gray];", node(*idom), node(BlockId(id as u32)))
+                    .unwrap();
+            }
+        }
+        writeln!(out, "    }}").unwrap();
+    }
+    writeln!(out, "}}").unwrap();
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::arith::Overflow;
+    use crate::semantic::context::AnalysisContext;
+    use crate::semantic::type_checker::annotate;
+
+    fn main_method(source: &str) -> (Program, usize) {
+        let classes = crate::parse_program(source).unwrap();
+        let ctx = AnalysisContext::new(crate::prelude(), &classes);
+        let program = super::super::lower(&ctx, &annotate(&ctx), Overflow::Wrap).unwrap();
+        let index = program.functions.iter().position(|f| f.name.as_str() == "main").unwrap();
+        (program, index)
+    }
+
+    #[test]
+    fn test_dominators() {
+        let (program, index) = main_method(
+            "class Main {\n\
+                b : Bool;\n\
+                main() : Object { { if b then while b loop b <- false pool else 0 fi; 1; } };\n\
+            };",
+        );
+        let function = &program.functions[index];
+        let cfg = Cfg::new(function);
+        assert_eq!(cfg.order[0], BlockId(0));
+        assert_eq!(cfg.order.len(), function.blocks.len());
+        for (id, targets) in cfg.successors.iter().enumerate() {
+            for target in targets {
+                assert!(cfg.predecessors[target.0 as usize].contains(&BlockId(id as u32)));
+            }
+        }
+
+        // b1 is the `then` branch, b2 the `else` branch and b3 the join;
+        // b4 is the loop header, b5 the body that jumps back to it and b6 the
+        // exit
+        assert_eq!(cfg.predecessors[4], [BlockId(1), BlockId(5)]);
+        assert_eq!(cfg.predecessors[3], [BlockId(2), BlockId(6)]);
+        let idom: Vec<Option<u32>> = cfg.idom.iter().map(|b| b.map(|b| b.0)).collect();
+        assert_eq!(idom, [Some(0), Some(0), Some(0), Some(0), Some(1), Some(4), Some(4)]);
+        assert!(cfg.dominates(BlockId(4), BlockId(5)) && cfg.dominates(BlockId(5), BlockId(5)));
+        assert!(!cfg.dominates(BlockId(4), BlockId(3)));
+    }
+
+    #[test]
+    fn test_dot() {
+        let (program, index) = main_method("class Main { main() : Object { if true then \"a\\\"\" else 0 fi }; };");
+        let dot = dot(&Program { functions: vec![program.functions[index].clone()], ..program });
+        assert!(dot.starts_with("digraph program {\n"), "{}", dot);
+        assert!(dot.contains("        label=\"Main.main\";\n"), "{}", dot);
+        assert!(dot.contains("f0b0 [label=\"b0:\\lv1 : bool = true\\lbranch v1, b1, b2\\l\"];"), "{}", dot);
+        assert!(dot.contains(" = \\\"a\\\\\\\"\\\"\\l"), "{}", dot);
+        assert!(dot.contains("        f0b0 -> f0b1;\n        f0b0 -> f0b2;\n"), "{}", dot);
+        assert!(dot.contains("        f0b0 -> f0b3 [style=dashed, color=gray];\n"), "{}", dot);
+    }
+}
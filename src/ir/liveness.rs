@@ -0,0 +1,156 @@
+//! Liveness of the variables of IR functions.
+//!
+//! A variable is live at a point if some path from there reads it before
+//! writing it. The sets at block boundaries are found by iterating the
+//! usual backward dataflow equations over the CFG to a fixed point; within
+//! a block they follow from its instructions.
+
+use std::collections::BTreeSet;
+
+use super::cfg::Cfg;
+use super::{Function, Inst, Terminator, Var};
+
+impl Inst {
+    /// The variables it reads.
+    pub fn uses(&self) -> Vec<Var> {
+        match self {
+            Inst::Int { .. }
+            | Inst::Bool { .. }
+            | Inst::Str { .. }
+            | Inst::Void { .. }
+            | Inst::New { .. }
+            | Inst::Exception { .. }
+            | Inst::EndTry => Vec::new(),
+            Inst::Copy { src, .. }
+            | Inst::Box { src, .. }
+            | Inst::Unbox { src, .. }
+            | Inst::Unary { src, .. }
+            | Inst::IsVoid { src, .. } => vec![*src],
+            Inst::NewLike { object, .. } | Inst::GetField { object, .. } | Inst::TagIn { object, .. } => vec![*object],
+            Inst::Binary { lhs, rhs, .. } | Inst::Overflows { lhs, rhs, .. } | Inst::Equal { lhs, rhs, .. } => {
+                vec![*lhs, *rhs]
+            }
+            Inst::SetField { object, src, .. } => vec![*object, *src],
+            Inst::Call { args, .. } => args.clone(),
+        }
+    }
+}
+
+impl Terminator {
+    /// The variable it reads, if any.
+    pub fn uses(&self) -> Option<Var> {
+        match *self {
+            Terminator::Jump(_)
+            | Terminator::DispatchOnVoid { .. }
+            | Terminator::CaseOnVoid { .. }
+            | Terminator::DivisionByZero { .. }
+            | Terminator::IntegerOverflow { .. }
+            | Terminator::Try { .. }
+            | Terminator::Rethrow => None,
+            Terminator::Branch { cond: var, .. }
+            | Terminator::Return(var)
+            | Terminator::NoMatch { object: var, .. }
+            | Terminator::Throw { value: var, .. } => Some(var),
+        }
+    }
+}
+
+/// The live variables of a function at its block boundaries, indexed by
+/// `BlockId`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Liveness {
+    pub live_in: Vec<BTreeSet<Var>>,
+    pub live_out: Vec<BTreeSet<Var>>,
+}
+
+impl Liveness {
+    pub fn new(function: &Function, cfg: &Cfg) -> Liveness {
+        // What each block reads before writing it, and what it writes
+        let mut uses = Vec::new();
+        let mut defs = Vec::new();
+        for block in &function.blocks {
+            let mut used = BTreeSet::new();
+            let mut defined = BTreeSet::new();
+            for inst in &block.insts {
+                used.extend(inst.uses().into_iter().filter(|var| !defined.contains(var)));
+                defined.extend(inst.dst());
+            }
+            used.extend(block.terminator.uses().filter(|var| !defined.contains(var)));
+            uses.push(used);
+            defs.push(defined);
+        }
+
+        let mut live_in = vec![BTreeSet::new(); function.blocks.len()];
+        let mut live_out = vec![BTreeSet::new(); function.blocks.len()];
+        let mut changed = true;
+        while changed {
+            changed = false;
+            // Backward, so most blocks see their successors' final sets
+            for &block in cfg.order.iter().rev() {
+                let b = block.0 as usize;
+                let out: BTreeSet<Var> =
+                    cfg.successors[b].iter().flat_map(|s| live_in[s.0 as usize].iter().copied()).collect();
+                let mut live: BTreeSet<Var> = out.difference(&defs[b]).copied().collect();
+                live.extend(&uses[b]);
+                if live != live_in[b] || out != live_out[b] {
+                    live_in[b] = live;
+                    live_out[b] = out;
+                    changed = true;
+                }
+            }
+        }
+        Liveness { live_in, live_out }
+    }
+
+    /// The variables live just before each instruction of block `block`
+    /// of `function`, and last before its terminator.
+    pub fn live_before(&self, function: &Function, block: usize) -> Vec<BTreeSet<Var>> {
+        let insts = &function.blocks[block].insts;
+        let mut live = self.live_out[block].clone();
+        live.extend(function.blocks[block].terminator.uses());
+        let mut sets = vec![live.clone()];
+        for inst in insts.iter().rev() {
+            if let Some(dst) = inst.dst() {
+                live.remove(&dst);
+            }
+            live.extend(inst.uses());
+            sets.push(live.clone());
+        }
+        sets.reverse();
+        sets
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::arith::Overflow;
+    use crate::semantic::context::AnalysisContext;
+    use crate::semantic::type_checker::annotate;
+
+    #[test]
+    fn test_liveness() {
+        let source = "class Main {\n\
+            main() : Object { let i : Int <- 0, n : Int <- 10 in { while i < n loop i <- i + 1 pool; n; } };\n\
+        };";
+        let classes = crate::parse_program(source).unwrap();
+        let ctx = AnalysisContext::new(crate::prelude(), &classes);
+        let program = super::super::lower(&ctx, &annotate(&ctx), Overflow::Wrap).unwrap();
+        let function = program.functions.iter().find(|f| f.name.as_str() == "main").unwrap();
+        let cfg = Cfg::new(function);
+        let liveness = Liveness::new(function, &cfg);
+
+        // `i` and `n` are the first variables after self; both stay live
+        // around the loop, and only `n` after it
+        let (i, n) = (Var(1), Var(3));
+        let header = cfg.successors[0][0].0 as usize;
+        assert!(liveness.live_in[header].is_superset(&[i, n].into()));
+        let exit = cfg.successors[header][1].0 as usize;
+        assert!(liveness.live_in[exit].contains(&n) && !liveness.live_in[exit].contains(&i));
+        assert!(liveness.live_in[0].is_empty());
+
+        let before = liveness.live_before(function, header);
+        assert_eq!(before.len(), function.blocks[header].insts.len() + 1);
+        assert_eq!(before[0], liveness.live_in[header]);
+    }
+}
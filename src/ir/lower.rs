@@ -0,0 +1,606 @@
+//! Lowering of the typed AST to the IR.
+
+use std::collections::{HashMap, HashSet};
+
+use super::{BinaryOp, Block, BlockId, Callee, Function, Inst, Program, Terminator, Ty, UnaryOp, Var};
+use crate::arith::Overflow;
+use crate::ast::{CaseBranch, ComparisonOperator, Expr, Feature, MathOperator, TypedExpr, UnaryOperator, VarDecl};
+use crate::codegen::layout::Layout;
+use crate::codegen::never_void::NeverVoid;
+use crate::codegen::{may_be_void, may_be_zero};
+use crate::symbol::{sym, Symbol};
+
+pub(super) fn lower(layout: &Layout<'_>, overflow: Overflow) -> Program {
+    let never_void = NeverVoid::new(layout);
+    let mut lowerer = Lowerer { layout, overflow, never_void, strings: Vec::new(), string_index: HashMap::new() };
+    let mut functions = Vec::new();
+    for class in &layout.classes {
+        functions.push(lowerer.init(class.class.name));
+    }
+    for class in &layout.classes {
+        for feature in &class.class.feature_list {
+            if let Feature::Method(name, args, _, body, _) = feature {
+                // The runtime implements the built-in classes' methods
+                if matches!(body.expr, Expr::Builtin) {
+                    continue;
+                }
+                let formals: Vec<(Symbol, Symbol)> = args.iter().map(|a| (a.id, a.tid)).collect();
+                functions.push(lowerer.method(class.class.name, *name, &formals, body));
+            }
+        }
+    }
+    Program { functions, strings: lowerer.strings }
+}
+
+struct Lowerer<'l, 'a> {
+    layout: &'l Layout<'a>,
+    overflow: Overflow,
+    never_void: NeverVoid,
+    strings: Vec<String>,
+    string_index: HashMap<String, u32>,
+}
+
+/// The function being built, with its blocks in order of creation.
+struct Builder {
+    class: Symbol,
+    vars: Vec<Ty>,
+    blocks: Vec<(Vec<Inst>, Option<Terminator>)>,
+    current: BlockId,
+    /// Formals and locals in scope, innermost last
+    scope: Vec<(Symbol, Var)>,
+    /// Variables that are assigned more than once: formals, locals and the
+    /// results of branches
+    mutable: Vec<bool>,
+    /// The other representation of a boxed or unboxed value, for variables
+    /// that hold the same value wherever they are read
+    converted: HashMap<Var, Var>,
+    /// Names assigned somewhere in the function; other formals and locals
+    /// can be read without a copy
+    assigned: HashSet<Symbol>,
+    /// Whether this is a class's initializer, which may read attributes
+    /// before they are set
+    initializer: bool,
+}
+
+const SELF: Var = Var(0);
+
+impl Builder {
+    fn new(class: Symbol) -> Self {
+        Builder {
+            class,
+            vars: vec![Ty::Object(class)],
+            blocks: vec![(Vec::new(), None)],
+            current: BlockId(0),
+            scope: Vec::new(),
+            mutable: vec![false],
+            converted: HashMap::new(),
+            assigned: HashSet::new(),
+            initializer: false,
+        }
+    }
+
+    fn var(&mut self, ty: Ty) -> Var {
+        self.vars.push(ty);
+        self.mutable.push(false);
+        Var(self.vars.len() as u32 - 1)
+    }
+
+    /// A variable that may be assigned more than once.
+    fn local(&mut self, ty: Ty) -> Var {
+        let var = self.var(ty);
+        self.mutable[var.0 as usize] = true;
+        var
+    }
+
+    fn ty(&self, var: Var) -> Ty {
+        self.vars[var.0 as usize]
+    }
+
+    fn block(&mut self) -> BlockId {
+        self.blocks.push((Vec::new(), None));
+        BlockId(self.blocks.len() as u32 - 1)
+    }
+
+    fn emit(&mut self, inst: Inst) {
+        self.blocks[self.current.0 as usize].0.push(inst);
+    }
+
+    /// End the current block with `terminator` and continue in `next`.
+    fn terminate(&mut self, terminator: Terminator, next: BlockId) {
+        self.blocks[self.current.0 as usize].1 = Some(terminator);
+        self.current = next;
+    }
+
+    fn finish(self, name: Symbol, params: usize) -> Function {
+        let blocks = self
+            .blocks
+            .into_iter()
+            .map(|(insts, terminator)| Block { insts, terminator: terminator.expect("every block is terminated") })
+            .collect();
+        Function { class: self.class, name, params, vars: self.vars, blocks }
+    }
+
+    /// The type of a value of static type `ty`: a word for `Int` and
+    /// `Bool`, an object otherwise.
+    fn natural(&self, ty: Symbol) -> Ty {
+        match ty {
+            sym::INT => Ty::Int,
+            sym::BOOL => Ty::Bool,
+            sym::SELF_TYPE => Ty::Object(self.class),
+            // The type of `throw`, whose value is never produced
+            sym::NOTHING => Ty::Object(sym::OBJECT),
+            class => Ty::Object(class),
+        }
+    }
+
+    /// `var` as a value of type `ty`, boxed or unboxed if need be.
+    /// A value boxed and then unboxed, or the other way around, is the
+    /// variable it started in.
+    fn convert(&mut self, var: Var, ty: Ty) -> Var {
+        let boxing = match (self.ty(var), ty) {
+            (Ty::Int | Ty::Bool, Ty::Object(_)) => true,
+            (Ty::Object(_), Ty::Int | Ty::Bool) => false,
+            _ => return var,
+        };
+        if let Some(&original) = self.converted.get(&var) {
+            return original;
+        }
+        let dst = if boxing {
+            let class = if self.ty(var) == Ty::Int { sym::INT } else { sym::BOOL };
+            let dst = self.var(Ty::Object(class));
+            self.emit(Inst::Box { dst, src: var });
+            dst
+        } else {
+            let dst = self.var(ty);
+            self.emit(Inst::Unbox { dst, src: var });
+            dst
+        };
+        // `var` is defined before `dst`, so it can stand in wherever `dst` is read
+        if !self.mutable[var.0 as usize] {
+            self.converted.insert(dst, var);
+        }
+        dst
+    }
+
+    /// `var` as an object.
+    fn object(&mut self, var: Var) -> Var {
+        match self.ty(var) {
+            Ty::Int => self.convert(var, Ty::Object(sym::INT)),
+            Ty::Bool => self.convert(var, Ty::Object(sym::BOOL)),
+            Ty::Object(_) => var,
+        }
+    }
+}
+
+impl Lowerer<'_, '_> {
+    fn string(&mut self, s: &str) -> u32 {
+        if let Some(&index) = self.string_index.get(s) {
+            return index;
+        }
+        self.strings.push(s.to_string());
+        self.string_index.insert(s.to_string(), self.strings.len() as u32 - 1);
+        self.strings.len() as u32 - 1
+    }
+
+    /// The initializer of `class`: its parent's, then its own attribute
+    /// initializers in order.
+    fn init(&mut self, class: Symbol) -> Function {
+        let mut b = Builder::new(class);
+        b.initializer = true;
+        let layout = self.layout.class(class);
+        for feature in &layout.class.feature_list {
+            if let Feature::Attribute(VarDecl { expr: Some(init), .. }) = feature {
+                assignments(init, &mut b.assigned);
+            }
+        }
+        if class != sym::OBJECT {
+            let parent = layout.class.inherits.unwrap_or(sym::OBJECT);
+            let dst = b.var(Ty::Object(parent));
+            b.emit(Inst::Call { dst, callee: Callee::Static { class: parent, method: init_name() }, args: vec![SELF] });
+        }
+        for feature in &layout.class.feature_list {
+            if let Feature::Attribute(VarDecl { oid, expr: Some(init), .. }) = feature {
+                let value = self.expr(&mut b, init);
+                let src = b.object(value);
+                b.emit(Inst::SetField { object: SELF, index: self.attribute(class, *oid), src });
+            }
+        }
+        b.terminate(Terminator::Return(SELF), BlockId(0));
+        b.finish(init_name(), 1)
+    }
+
+    fn method(&mut self, class: Symbol, name: Symbol, formals: &[(Symbol, Symbol)], body: &TypedExpr) -> Function {
+        let mut b = Builder::new(class);
+        assignments(body, &mut b.assigned);
+        for &(formal, ty) in formals {
+            // Arguments are passed as objects
+            let var = b.local(object_ty(b.natural(ty)));
+            b.scope.push((formal, var));
+        }
+        let value = self.expr(&mut b, body);
+        let result = b.object(value);
+        b.terminate(Terminator::Return(result), BlockId(0));
+        b.finish(name, formals.len() + 1)
+    }
+
+    fn attribute(&self, class: Symbol, attr: Symbol) -> u32 {
+        let attributes = &self.layout.class(class).attributes;
+        attributes.iter().position(|(a, _)| *a == attr).expect("bound by the type checker") as u32
+    }
+
+    /// Whether `e` may be void where `b` reads it. In a method, an attribute
+    /// proven never void is not, unless a formal or local hides it.
+    fn may_be_void(&self, b: &Builder, e: &TypedExpr) -> bool {
+        may_be_void(e, |name| {
+            !b.initializer && b.scope.iter().all(|(n, _)| *n != name) && self.never_void.contains(b.class, name)
+        })
+    }
+
+    /// Static class of `e`, with `SELF_TYPE` read as the current class.
+    fn static_class(b: &Builder, e: &TypedExpr) -> Symbol {
+        match e.static_type.expect("filled in by annotate") {
+            sym::SELF_TYPE => b.class,
+            ty => ty,
+        }
+    }
+
+    /// A new variable holding the default value of type `ty`.
+    fn default_value(&mut self, b: &mut Builder, ty: Symbol) -> Var {
+        let dst = b.var(b.natural(ty));
+        b.emit(match ty {
+            sym::INT => Inst::Int { dst, value: 0 },
+            sym::BOOL => Inst::Bool { dst, value: false },
+            sym::STRING => Inst::Str { dst, index: self.string("") },
+            _ => Inst::Void { dst },
+        });
+        dst
+    }
+
+    /// Instructions computing `e`, whose value is returned in a variable of
+    /// the natural type of its static type, or a narrower one.
+    fn expr(&mut self, b: &mut Builder, e: &TypedExpr) -> Var {
+        match &e.expr {
+            Expr::Paren(inner) => self.expr(b, inner),
+            Expr::Int(value) => {
+                let dst = b.var(Ty::Int);
+                b.emit(Inst::Int { dst, value: *value });
+                dst
+            }
+            Expr::Bool(value) => {
+                let dst = b.var(Ty::Bool);
+                b.emit(Inst::Bool { dst, value: *value });
+                dst
+            }
+            Expr::Str(s) => {
+                let dst = b.var(Ty::Object(sym::STRING));
+                b.emit(Inst::Str { dst, index: self.string(s) });
+                dst
+            }
+            Expr::Identifier(name) if *name == sym::SELF => SELF,
+            Expr::Identifier(name) => match b.scope.iter().rev().find(|(n, _)| n == name) {
+                Some(&(_, local)) if !b.assigned.contains(name) => local,
+                // A copy, which later assignments to the variable leave alone
+                Some(&(_, local)) => {
+                    let dst = b.var(b.ty(local));
+                    b.emit(Inst::Copy { dst, src: local });
+                    dst
+                }
+                None => {
+                    let index = self.attribute(b.class, *name);
+                    let ty = self.layout.class(b.class).attributes[index as usize].1;
+                    let dst = b.var(object_ty(b.natural(ty)));
+                    b.emit(Inst::GetField { dst, object: SELF, index });
+                    dst
+                }
+            },
+            Expr::Assignment(name, rhs) => {
+                let value = self.expr(b, rhs);
+                match b.scope.iter().rev().find(|(n, _)| n == name) {
+                    Some(&(_, local)) => {
+                        let src = b.convert(value, b.ty(local));
+                        b.emit(Inst::Copy { dst: local, src });
+                    }
+                    None => {
+                        let src = b.object(value);
+                        b.emit(Inst::SetField { object: SELF, index: self.attribute(b.class, *name), src });
+                    }
+                }
+                value
+            }
+            Expr::New(ty) => match *ty {
+                sym::INT | sym::BOOL | sym::STRING => self.default_value(b, *ty),
+                sym::SELF_TYPE => {
+                    let dst = b.var(Ty::Object(b.class));
+                    b.emit(Inst::NewLike { dst, object: SELF });
+                    dst
+                }
+                class => {
+                    let dst = b.var(Ty::Object(class));
+                    b.emit(Inst::New { dst, class });
+                    dst
+                }
+            },
+            Expr::Block(exprs) => {
+                let mut value = None;
+                for e in exprs {
+                    value = Some(self.expr(b, e));
+                }
+                value.unwrap_or_else(|| self.default_value(b, sym::OBJECT))
+            }
+            Expr::Let(bindings, body) => {
+                for VarDecl { oid: id, tid: ty, expr: init, .. } in bindings {
+                    let var = b.local(b.natural(*ty));
+                    let value = match init {
+                        Some(init) => self.expr(b, init),
+                        None => self.default_value(b, *ty),
+                    };
+                    let src = b.convert(value, b.ty(var));
+                    b.emit(Inst::Copy { dst: var, src });
+                    b.scope.push((*id, var));
+                }
+                let value = self.expr(b, body);
+                b.scope.truncate(b.scope.len() - bindings.len());
+                value
+            }
+            Expr::Math { lhs, op, rhs: rhs_expr } => {
+                let (lhs, rhs) = (self.expr(b, lhs), self.expr(b, rhs_expr));
+                let (lhs, rhs) = (b.convert(lhs, Ty::Int), b.convert(rhs, Ty::Int));
+                let op = match op {
+                    MathOperator::Add => BinaryOp::Add,
+                    MathOperator::Subtract => BinaryOp::Sub,
+                    MathOperator::Mul => BinaryOp::Mul,
+                    MathOperator::Div => BinaryOp::Div,
+                };
+                if op == BinaryOp::Div && may_be_zero(rhs_expr) {
+                    let (zero, is_zero) = (b.var(Ty::Int), b.var(Ty::Bool));
+                    b.emit(Inst::Int { dst: zero, value: 0 });
+                    b.emit(Inst::Binary { dst: is_zero, op: BinaryOp::Eq, lhs: rhs, rhs: zero });
+                    let (abort, divide) = (b.block(), b.block());
+                    b.terminate(Terminator::Branch { cond: is_zero, then: abort, orelse: divide }, abort);
+                    b.terminate(Terminator::DivisionByZero { line: e.line }, divide);
+                }
+                self.check_overflow(b, e, op, lhs, rhs);
+                let dst = b.var(Ty::Int);
+                b.emit(Inst::Binary { dst, op, lhs, rhs });
+                dst
+            }
+            Expr::UnaryOperation { op, s } => {
+                let (op, ty) = match op {
+                    UnaryOperator::Neg => (UnaryOp::Neg, Ty::Int),
+                    UnaryOperator::Not => (UnaryOp::Not, Ty::Bool),
+                };
+                let value = self.expr(b, s);
+                let src = b.convert(value, ty);
+                if op == UnaryOp::Neg {
+                    let zero = b.var(Ty::Int);
+                    b.emit(Inst::Int { dst: zero, value: 0 });
+                    self.check_overflow(b, e, BinaryOp::Sub, zero, src);
+                }
+                let dst = b.var(ty);
+                b.emit(Inst::Unary { dst, op, src });
+                dst
+            }
+            Expr::Comparison { lhs, op, rhs } => {
+                let (lhs, rhs) = (self.expr(b, lhs), self.expr(b, rhs));
+                // If either side is Int or Bool, both are, but either may be boxed
+                let word = [b.ty(lhs), b.ty(rhs)].into_iter().find(|ty| !matches!(ty, Ty::Object(_)));
+                let (op, word) = match op {
+                    ComparisonOperator::Lt => (BinaryOp::Lt, Some(Ty::Int)),
+                    ComparisonOperator::Le => (BinaryOp::Le, Some(Ty::Int)),
+                    ComparisonOperator::Equal => (BinaryOp::Eq, word),
+                };
+                let dst = b.var(Ty::Bool);
+                match word {
+                    Some(ty) => {
+                        let (lhs, rhs) = (b.convert(lhs, ty), b.convert(rhs, ty));
+                        b.emit(Inst::Binary { dst, op, lhs, rhs });
+                    }
+                    None => {
+                        let (lhs, rhs) = (b.object(lhs), b.object(rhs));
+                        b.emit(Inst::Equal { dst, lhs, rhs });
+                    }
+                }
+                dst
+            }
+            Expr::Isvoid(inner) => {
+                let value = self.expr(b, inner);
+                let dst = b.var(Ty::Bool);
+                match b.ty(value) {
+                    Ty::Object(_) => b.emit(Inst::IsVoid { dst, src: value }),
+                    // A word is never void
+                    Ty::Int | Ty::Bool => b.emit(Inst::Bool { dst, value: false }),
+                }
+                dst
+            }
+            Expr::Conditional { test, then, orelse } => {
+                let test = self.expr(b, test);
+                let cond = b.convert(test, Ty::Bool);
+                let result = b.local(b.natural(Self::static_class(b, e)));
+                let (then_block, else_block, join) = (b.block(), b.block(), b.block());
+                b.terminate(Terminator::Branch { cond, then: then_block, orelse: else_block }, then_block);
+                for (arm, next) in [(then, else_block), (orelse, join)] {
+                    let value = self.expr(b, arm);
+                    let src = b.convert(value, b.ty(result));
+                    b.emit(Inst::Copy { dst: result, src });
+                    b.terminate(Terminator::Jump(join), next);
+                }
+                result
+            }
+            Expr::While { test, exec } => {
+                let (header, body, exit) = (b.block(), b.block(), b.block());
+                b.terminate(Terminator::Jump(header), header);
+                let test = self.expr(b, test);
+                let cond = b.convert(test, Ty::Bool);
+                b.terminate(Terminator::Branch { cond, then: body, orelse: exit }, body);
+                self.expr(b, exec);
+                b.terminate(Terminator::Jump(header), exit);
+                self.default_value(b, sym::OBJECT)
+            }
+            Expr::Case(scrutinee, branches) => self.case(b, e, scrutinee, branches),
+            Expr::Dispatch { target, targettype, id, exprs } => {
+                let mut args = vec![SELF];
+                for arg in exprs {
+                    let value = self.expr(b, arg);
+                    args.push(b.object(value));
+                }
+                let class = match target {
+                    Some(target) => {
+                        let value = self.expr(b, target);
+                        args[0] = b.object(value);
+                        if self.may_be_void(b, target) {
+                            let void = b.var(Ty::Bool);
+                            b.emit(Inst::IsVoid { dst: void, src: args[0] });
+                            let (abort, call) = (b.block(), b.block());
+                            b.terminate(Terminator::Branch { cond: void, then: abort, orelse: call }, abort);
+                            b.terminate(Terminator::DispatchOnVoid { method: *id, line: e.line }, call);
+                        }
+                        Self::static_class(b, target)
+                    }
+                    None => b.class,
+                };
+                let callee = match targettype {
+                    Some(ty) => {
+                        let methods = &self.layout.class(*ty).methods;
+                        let (_, owner) = methods.iter().find(|(m, _)| m == id).expect("resolved by the type checker");
+                        Callee::Static { class: *owner, method: *id }
+                    }
+                    None => {
+                        let methods = &self.layout.class(class).methods;
+                        let slot = methods.iter().position(|(m, _)| m == id).expect("resolved by the type checker");
+                        Callee::Virtual { method: *id, slot: slot as u32 }
+                    }
+                };
+                let dst = b.var(object_ty(b.natural(Self::static_class(b, e))));
+                b.emit(Inst::Call { dst, callee, args });
+                let natural = b.natural(Self::static_class(b, e));
+                b.convert(dst, natural)
+            }
+            Expr::Try { body, id, tid, handler } => self.try_catch(b, e, body, *id, *tid, handler),
+            Expr::Throw(inner) => {
+                let value = self.expr(b, inner);
+                let value = b.object(value);
+                let after = b.block();
+                b.terminate(Terminator::Throw { value, line: e.line }, after);
+                // Never reached, but what follows needs a value
+                let dst = b.var(b.natural(Self::static_class(b, e)));
+                b.emit(Inst::Void { dst });
+                dst
+            }
+            Expr::Native | Expr::Builtin => unreachable!("rejected by check_supported"),
+        }
+    }
+
+    /// The handler takes what `body` throws if its class is `tid` or one of
+    /// its descendants, and throws anything else again.
+    fn try_catch(
+        &mut self,
+        b: &mut Builder,
+        e: &TypedExpr,
+        body: &TypedExpr,
+        id: Symbol,
+        tid: Symbol,
+        handler: &TypedExpr,
+    ) -> Var {
+        let result = b.local(b.natural(Self::static_class(b, e)));
+        let (body_block, handler_block, join) = (b.block(), b.block(), b.block());
+        b.terminate(Terminator::Try { body: body_block, handler: handler_block }, body_block);
+        let value = self.expr(b, body);
+        let src = b.convert(value, b.ty(result));
+        b.emit(Inst::Copy { dst: result, src });
+        b.emit(Inst::EndTry);
+        b.terminate(Terminator::Jump(join), handler_block);
+
+        let exception = b.var(Ty::Object(sym::OBJECT));
+        b.emit(Inst::Exception { dst: exception });
+        let class = self.layout.class(tid);
+        let matched = b.var(Ty::Bool);
+        b.emit(Inst::TagIn { dst: matched, object: exception, first: class.tag as u32, last: class.last_descendant as u32 });
+        let (caught, rethrow) = (b.block(), b.block());
+        b.terminate(Terminator::Branch { cond: matched, then: caught, orelse: rethrow }, caught);
+        let ty = b.natural(tid);
+        let bound = b.local(ty);
+        let src = b.convert(exception, ty);
+        b.emit(Inst::Copy { dst: bound, src });
+        b.scope.push((id, bound));
+        let value = self.expr(b, handler);
+        b.scope.pop();
+        let src = b.convert(value, b.ty(result));
+        b.emit(Inst::Copy { dst: result, src });
+        b.terminate(Terminator::Jump(join), rethrow);
+        b.terminate(Terminator::Rethrow, join);
+        result
+    }
+
+    /// When overflow traps, stop the program if `lhs op rhs`, for the
+    /// operation `e`, overflows.
+    fn check_overflow(&self, b: &mut Builder, e: &TypedExpr, op: BinaryOp, lhs: Var, rhs: Var) {
+        if self.overflow == Overflow::Trap {
+            let overflows = b.var(Ty::Bool);
+            b.emit(Inst::Overflows { dst: overflows, op, lhs, rhs });
+            let (abort, compute) = (b.block(), b.block());
+            b.terminate(Terminator::Branch { cond: overflows, then: abort, orelse: compute }, abort);
+            b.terminate(Terminator::IntegerOverflow { line: e.line }, compute);
+        }
+    }
+
+    /// Branches are tested from the most specific class up, each matching
+    /// its class's range of tags.
+    fn case(&mut self, b: &mut Builder, e: &TypedExpr, scrutinee: &TypedExpr, branches: &[CaseBranch]) -> Var {
+        let value = self.expr(b, scrutinee);
+        let object = b.object(value);
+        if self.may_be_void(b, scrutinee) {
+            let void = b.var(Ty::Bool);
+            b.emit(Inst::IsVoid { dst: void, src: object });
+            let (abort, test) = (b.block(), b.block());
+            b.terminate(Terminator::Branch { cond: void, then: abort, orelse: test }, abort);
+            b.terminate(Terminator::CaseOnVoid { line: e.line }, test);
+        }
+        let result = b.local(b.natural(Self::static_class(b, e)));
+        let join = b.block();
+        let mut ordered: Vec<&CaseBranch> = branches.iter().collect();
+        ordered.sort_by_key(|branch| std::cmp::Reverse(self.layout.class(branch.tid).tag));
+        for branch in ordered {
+            let class = self.layout.class(branch.tid);
+            let matched = b.var(Ty::Bool);
+            b.emit(Inst::TagIn { dst: matched, object, first: class.tag as u32, last: class.last_descendant as u32 });
+            let (body, next) = (b.block(), b.block());
+            b.terminate(Terminator::Branch { cond: matched, then: body, orelse: next }, body);
+
+            let ty = b.natural(branch.tid);
+            let bound = b.local(ty);
+            let src = b.convert(object, ty);
+            b.emit(Inst::Copy { dst: bound, src });
+            b.scope.push((branch.id, bound));
+            let value = self.expr(b, &branch.expr);
+            b.scope.pop();
+            let src = b.convert(value, b.ty(result));
+            b.emit(Inst::Copy { dst: result, src });
+            b.terminate(Terminator::Jump(join), next);
+        }
+        b.terminate(Terminator::NoMatch { object, line: e.line }, join);
+        result
+    }
+}
+
+/// Add the names `e` assigns to `names`.
+fn assignments(e: &TypedExpr, names: &mut HashSet<Symbol>) {
+    if let Expr::Assignment(name, _) = &e.expr {
+        names.insert(*name);
+    }
+    for child in e.expr.children() {
+        assignments(child, names);
+    }
+}
+
+/// The type of `ty`'s values as objects.
+fn object_ty(ty: Ty) -> Ty {
+    match ty {
+        Ty::Int => Ty::Object(sym::INT),
+        Ty::Bool => Ty::Object(sym::BOOL),
+        ty => ty,
+    }
+}
+
+fn init_name() -> Symbol {
+    Symbol::intern("_init")
+}
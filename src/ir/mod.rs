@@ -0,0 +1,440 @@
+//! Typed intermediate representation between the AST and the backends
+//! (`--emit ir`).
+//!
+//! `lower` turns a program that passed semantic analysis into one
+//! `Function` per method and per class initializer. A function is a
+//! control-flow graph of basic blocks of three-address instructions over
+//! numbered variables, each with a `Ty`. What the AST leaves implicit is
+//! spelled out, so that a backend only has to translate instructions:
+//!
+//! * `Int` and `Bool` values are unboxed words wherever their static type
+//!   says so, and `Box`/`Unbox` convert them where an object is needed: in
+//!   attributes, arguments, return values, and variables of other types.
+//! * Names are resolved: a local is a variable, an attribute an index into
+//!   the object, and a dynamic dispatch a slot of the receiver's dispatch
+//!   table, all as laid out by `Layout`.
+//! * `if`, `while` and `case` are branches between blocks, and a `case`
+//!   branch is chosen by tests of the scrutinee's class tag against the
+//!   range of tags of the branch's class and its descendants.
+//!
+//! Variables are not in SSA form: a COOL variable keeps one IR variable,
+//! which its assignments overwrite. A dispatch on a receiver that may be
+//! void, and a `case` on a value that may be, test it first and stop the
+//! program if it is, as do a `case` whose value matches no branch and a
+//! division by zero. `self`, `new`, values of the basic classes and, in
+//! methods, the attributes that `NeverVoid` proves are never void are not
+//! tested. When overflow traps (`arith::Overflow`), an `Int`
+//! operation tests whether it overflows before it is done. The methods of
+//! `Array` (`--ext arrays`) are called like those of the other built-in
+//! classes, which the runtime implements. A `try` of `--ext exceptions` is
+//! a `Try` terminator that sets up a handler block for its body, which the
+//! edge to it stands for in the CFG wherever in the body a throw happens;
+//! the handler reads what was thrown with `Exception`. The IR has no
+//! support for the `natives` extension.
+
+pub mod cfg;
+pub mod liveness;
+mod lower;
+pub mod parse;
+
+use std::fmt;
+
+use eyre::Result;
+
+use crate::arith::Overflow;
+use crate::ast::Class;
+use crate::codegen::layout::Layout;
+use crate::ext::{Extension, Extensions};
+use crate::semantic::context::AnalysisContext;
+use crate::symbol::Symbol;
+
+/// A variable of a function, numbered from 0. `self` is `v0` and the
+/// formals follow it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Var(pub u32);
+
+/// A basic block of a function; `b0` is the entry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct BlockId(pub u32);
+
+/// Type of a variable.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Ty {
+    /// An unboxed 32-bit integer
+    Int,
+    /// An unboxed boolean
+    Bool,
+    /// A reference to an object of the class or of a subclass, or void
+    Object(Symbol),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnaryOp {
+    Neg,
+    Not,
+}
+
+/// Operations on unboxed words. Arithmetic wraps around; `Lt`, `Le` and
+/// `Eq` give a `Bool`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Lt,
+    Le,
+    Eq,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Callee {
+    /// Entry `slot` of the dispatch table of the receiver's class
+    Virtual { method: Symbol, slot: u32 },
+    /// The definition of `method` in `class`, whatever the receiver's
+    /// class: a static dispatch, or a parent's initializer
+    Static { class: Symbol, method: Symbol },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Inst {
+    Int { dst: Var, value: i32 },
+    Bool { dst: Var, value: bool },
+    /// The `String` constant `Program::strings[index]`
+    Str { dst: Var, index: u32 },
+    Void { dst: Var },
+    Copy { dst: Var, src: Var },
+    /// A new `Int` or `Bool` object holding the word `src`
+    Box { dst: Var, src: Var },
+    /// The word held by an `Int` or `Bool` object
+    Unbox { dst: Var, src: Var },
+    Unary { dst: Var, op: UnaryOp, src: Var },
+    Binary { dst: Var, op: BinaryOp, lhs: Var, rhs: Var },
+    /// Whether `Binary` with `op`, which is `Add`, `Sub`, `Mul` or `Div`,
+    /// would not fit the result in 32 bits and wrap around; never for a
+    /// division by zero
+    Overflows { dst: Var, op: BinaryOp, lhs: Var, rhs: Var },
+    /// `=` on objects: the same object, or `Int`, `Bool` or `String`
+    /// objects with the same value
+    Equal { dst: Var, lhs: Var, rhs: Var },
+    IsVoid { dst: Var, src: Var },
+    /// A new object of `class`, with its attributes initialized
+    New { dst: Var, class: Symbol },
+    /// A new object of the class of `object` (`new SELF_TYPE`)
+    NewLike { dst: Var, object: Var },
+    /// Attribute `index` of `object`, in layout order
+    GetField { dst: Var, object: Var, index: u32 },
+    SetField { object: Var, index: u32, src: Var },
+    /// Whether the class tag of `object` is in `first..=last`
+    TagIn { dst: Var, object: Var, first: u32, last: u32 },
+    /// `args[0]` is the receiver; every argument and the result are objects
+    Call { dst: Var, callee: Callee, args: Vec<Var> },
+    /// The object thrown to the handler of a `Try`, first in its block
+    Exception { dst: Var },
+    /// The body of the innermost `Try` is done: its handler is dropped
+    EndTry,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Terminator {
+    Jump(BlockId),
+    /// To `then` if the `Bool` `cond` is true, else to `orelse`
+    Branch { cond: Var, then: BlockId, orelse: BlockId },
+    Return(Var),
+    /// No branch of the `case` on `line` matches `object`; the program
+    /// reports its class and stops
+    NoMatch { object: Var, line: usize },
+    /// The value of the `case` on `line` is void; the program reports it and
+    /// stops
+    CaseOnVoid { line: usize },
+    /// The receiver of a dispatch of `method` on `line` is void; the
+    /// program reports it and stops
+    DispatchOnVoid { method: Symbol, line: usize },
+    /// The divisor of the division on `line` is zero; the program reports
+    /// it and stops
+    DivisionByZero { line: usize },
+    /// An `Int` operation on `line` overflows, and overflow traps; the
+    /// program reports it and stops
+    IntegerOverflow { line: usize },
+    /// Set up `handler` to catch what is thrown in `body`, until it reaches
+    /// an `EndTry`, and go to `body`. A throw goes to `handler`, which is
+    /// dropped first
+    Try { body: BlockId, handler: BlockId },
+    /// Throw the object `value` of the `throw` on `line`; the program
+    /// reports a void one, or one that no handler catches, and stops
+    Throw { value: Var, line: usize },
+    /// Throw the `Exception` again, for the handler around this one
+    Rethrow,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Block {
+    pub insts: Vec<Inst>,
+    pub terminator: Terminator,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Function {
+    /// The class that defines it, and the method's name or `_init`, the
+    /// initializer that runs the class's attribute initializers after its
+    /// parent's and returns `self`
+    pub class: Symbol,
+    pub name: Symbol,
+    /// `self` and the formals, the first variables
+    pub params: usize,
+    /// Indexed by `Var`
+    pub vars: Vec<Ty>,
+    /// Indexed by `BlockId`
+    pub blocks: Vec<Block>,
+}
+
+/// A lowered program.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Program {
+    /// The initializer of every class in tag order, including the basic
+    /// classes, then the methods of the user classes
+    pub functions: Vec<Function>,
+    pub strings: Vec<String>,
+}
+
+/// Lower the program of `ctx`, which must have checked cleanly, to run
+/// with `overflow`. `classes` are its classes as annotated by the type
+/// checker.
+pub fn lower(ctx: &AnalysisContext<'_>, classes: &[Class], overflow: Overflow) -> Result<Program> {
+    let extensions: Extensions = [Extension::Arrays, Extension::Exceptions].into_iter().collect();
+    crate::codegen::check_supported(ctx, classes, &extensions)?;
+    let all: Vec<&Class> = ctx.builtins.iter().chain(classes).collect();
+    Ok(lower_layout(&Layout::new(&all), overflow))
+}
+
+/// Lower the classes of `layout`, for a backend that lays them out itself.
+pub(crate) fn lower_layout(layout: &Layout<'_>, overflow: Overflow) -> Program {
+    lower::lower(layout, overflow)
+}
+
+impl fmt::Display for Var {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "v{}", self.0)
+    }
+}
+
+impl fmt::Display for BlockId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "b{}", self.0)
+    }
+}
+
+/// Words are lower case, classes as declared.
+impl fmt::Display for Ty {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Ty::Int => write!(f, "int"),
+            Ty::Bool => write!(f, "bool"),
+            Ty::Object(class) => write!(f, "{}", class),
+        }
+    }
+}
+
+impl fmt::Display for Terminator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Terminator::Jump(target) => write!(f, "jump {}", target),
+            Terminator::Branch { cond, then, orelse } => write!(f, "branch {}, {}, {}", cond, then, orelse),
+            Terminator::Return(value) => write!(f, "return {}", value),
+            Terminator::NoMatch { object, line } => write!(f, "nomatch {}, line {}", object, line),
+            Terminator::CaseOnVoid { line } => write!(f, "voidcase line {}", line),
+            Terminator::DivisionByZero { line } => write!(f, "divzero line {}", line),
+            Terminator::IntegerOverflow { line } => write!(f, "overflow line {}", line),
+            Terminator::DispatchOnVoid { method, line } => write!(f, "voiddispatch {}, line {}", method, line),
+            Terminator::Try { body, handler } => write!(f, "try {}, catch {}", body, handler),
+            Terminator::Throw { value, line } => write!(f, "throw {}, line {}", value, line),
+            Terminator::Rethrow => write!(f, "rethrow"),
+        }
+    }
+}
+
+impl Inst {
+    /// The variable it writes, if any.
+    pub fn dst(&self) -> Option<Var> {
+        match self {
+            Inst::SetField { .. } | Inst::EndTry => None,
+            Inst::Int { dst, .. }
+            | Inst::Bool { dst, .. }
+            | Inst::Str { dst, .. }
+            | Inst::Void { dst }
+            | Inst::Copy { dst, .. }
+            | Inst::Box { dst, .. }
+            | Inst::Unbox { dst, .. }
+            | Inst::Unary { dst, .. }
+            | Inst::Binary { dst, .. }
+            | Inst::Overflows { dst, .. }
+            | Inst::Equal { dst, .. }
+            | Inst::IsVoid { dst, .. }
+            | Inst::New { dst, .. }
+            | Inst::NewLike { dst, .. }
+            | Inst::GetField { dst, .. }
+            | Inst::TagIn { dst, .. }
+            | Inst::Call { dst, .. }
+            | Inst::Exception { dst } => Some(*dst),
+        }
+    }
+}
+
+impl Function {
+    /// `inst` as listed by `Program`'s `Display`.
+    pub fn inst_to_string(&self, inst: &Inst, strings: &[String]) -> String {
+        struct Listed<'a>(&'a Function, &'a Inst, &'a [String]);
+        impl fmt::Display for Listed<'_> {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                self.0.write_inst(f, self.1, self.2)
+            }
+        }
+        Listed(self, inst, strings).to_string()
+    }
+
+    fn write_inst(&self, f: &mut fmt::Formatter<'_>, inst: &Inst, strings: &[String]) -> fmt::Result {
+        let args = |args: &[Var]| args.iter().map(Var::to_string).collect::<Vec<_>>().join(", ");
+        let Some(dst) = inst.dst() else {
+            return match inst {
+                Inst::SetField { object, index, src } => write!(f, "setfield {}, {}, {}", object, index, src),
+                _ => write!(f, "endtry"),
+            };
+        };
+        write!(f, "{} : {} = ", dst, self.vars[dst.0 as usize])?;
+        match inst {
+            Inst::Int { value, .. } => write!(f, "{}", value),
+            Inst::Bool { value, .. } => write!(f, "{}", value),
+            Inst::Str { index, .. } => write!(f, "{:?}", strings[*index as usize]),
+            Inst::Void { .. } => write!(f, "void"),
+            Inst::Copy { src, .. } => write!(f, "{}", src),
+            Inst::Box { src, .. } => write!(f, "box {}", src),
+            Inst::Unbox { src, .. } => write!(f, "unbox {}", src),
+            Inst::Unary { op, src, .. } => write!(f, "{} {}", format!("{:?}", op).to_lowercase(), src),
+            Inst::Binary { op, lhs, rhs, .. } => write!(f, "{} {}, {}", format!("{:?}", op).to_lowercase(), lhs, rhs),
+            Inst::Overflows { op, lhs, rhs, .. } => {
+                write!(f, "overflows {} {}, {}", format!("{:?}", op).to_lowercase(), lhs, rhs)
+            }
+            Inst::Equal { lhs, rhs, .. } => write!(f, "equal {}, {}", lhs, rhs),
+            Inst::IsVoid { src, .. } => write!(f, "isvoid {}", src),
+            Inst::New { class, .. } => write!(f, "new {}", class),
+            Inst::NewLike { object, .. } => write!(f, "newlike {}", object),
+            Inst::GetField { object, index, .. } => write!(f, "getfield {}, {}", object, index),
+            Inst::TagIn { object, first, last, .. } => write!(f, "tagin {}, {}..={}", object, first, last),
+            Inst::Call { callee: Callee::Virtual { method, slot }, args: a, .. } => {
+                write!(f, "dispatch {}[{}]({})", method, slot, args(a))
+            }
+            Inst::Call { callee: Callee::Static { class, method }, args: a, .. } => {
+                write!(f, "call {}.{}({})", class, method, args(a))
+            }
+            Inst::Exception { .. } => write!(f, "exception"),
+            Inst::SetField { .. } | Inst::EndTry => unreachable!("printed above"),
+        }
+    }
+}
+
+/// A listing of every function, its blocks one instruction per line.
+impl fmt::Display for Program {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for function in &self.functions {
+            let params: Vec<String> =
+                (0..function.params).map(|i| format!("v{} : {}", i, function.vars[i])).collect();
+            writeln!(f, "{}.{}({}) {{", function.class, function.name, params.join(", "))?;
+            for (id, block) in function.blocks.iter().enumerate() {
+                writeln!(f, "b{}:", id)?;
+                for inst in &block.insts {
+                    write!(f, "    ")?;
+                    function.write_inst(f, inst, &self.strings)?;
+                    writeln!(f)?;
+                }
+                writeln!(f, "    {}", block.terminator)?;
+            }
+            writeln!(f, "}}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::semantic::type_checker::annotate;
+
+    #[test]
+    fn test_lower() {
+        let source = "class Main inherits IO {\n\
+            n : Int <- 3;\n\
+            main() : Object { case out_int(n + 1) of io : IO => io; o : Object => o; esac };\n\
+        };";
+        let classes = crate::parse_program(source).unwrap();
+        let ctx = AnalysisContext::new(crate::prelude(), &classes);
+        let listing = lower(&ctx, &annotate(&ctx), Overflow::Wrap).unwrap().to_string();
+        // Attributes hold objects, arithmetic is on words
+        assert!(listing.contains(
+            "Main._init(v0 : Main) {\nb0:\n    v1 : IO = call IO._init(v0)\n    v2 : int = 3\n    v3 : Int = box v2\n    \
+             setfield v0, 0, v3\n    return v0\n}\n"
+        ));
+        let main = listing.split("Main.main(v0 : Main) {\n").nth(1).unwrap();
+        assert!(main.contains("    v3 : int = unbox v1\n    v4 : int = add v3, v2\n    v5 : Int = box v4\n"), "{}", main);
+        // out_int is entry 4 of the table; IO and Main are tags 1 and 2
+        assert!(main.contains("    v6 : Main = dispatch out_int[4](v0, v5)\n    v7 : bool = isvoid v6\n"), "{}", main);
+        assert!(main.contains("b1:\n    voidcase line 3\nb2:\n    v9 : bool = tagin v6, 1..=2\n"), "{}", main);
+        assert!(main.contains("    nomatch v6, line 3\n}"), "{}", main);
+    }
+
+    #[test]
+    fn test_division_check() {
+        let source = "class Main {\n    n : Int;\n    main() : Int { n / 2 + n / n };\n};";
+        let classes = crate::parse_program(source).unwrap();
+        let ctx = AnalysisContext::new(crate::prelude(), &classes);
+        let listing = lower(&ctx, &annotate(&ctx), Overflow::Wrap).unwrap().to_string();
+        // Only the divisor that is not a literal is tested
+        assert_eq!(listing.matches(" = eq ").count(), 1, "{}", listing);
+        assert!(listing.contains("b1:\n    divzero line 3\nb2:\n"), "{}", listing);
+    }
+
+    #[test]
+    fn test_void_check() {
+        let source = "class A { f() : Int { 1 }; };\n\
+            class Main {\n\
+                a : A <- new A;\n\
+                b : A;\n\
+                main() : Int { a.f() + b.f() + (let a : A in a.f()) };\n\
+            };";
+        let classes = crate::parse_program(source).unwrap();
+        let ctx = AnalysisContext::new(crate::prelude(), &classes);
+        let listing = lower(&ctx, &annotate(&ctx), Overflow::Wrap).unwrap().to_string();
+        let main = listing.split("Main.main(v0 : Main) {\n").nth(1).unwrap();
+        // The attribute `a` is never void, but `b` and the local `a` may be
+        assert!(main.starts_with("b0:\n    v1 : A = getfield v0, 0\n    v2 : Int = dispatch f[3](v1)\n"), "{}", main);
+        assert_eq!(main.matches("voiddispatch f, line 5").count(), 2, "{}", main);
+    }
+
+    #[test]
+    fn test_try() {
+        let source = "class Main {\n    main() : Int { try throw 1 catch i : Int => i end };\n};";
+        let extensions: Extensions = [Extension::Exceptions].into_iter().collect();
+        let classes = crate::parse_program_with(source, &extensions).unwrap();
+        let ctx = AnalysisContext::new(crate::prelude_with(&extensions), &classes);
+        let listing = lower(&ctx, &annotate(&ctx), Overflow::Wrap).unwrap().to_string();
+        let main = listing.split("Main.main(v0 : Main) {\n").nth(1).unwrap();
+        // The body ends by dropping its handler; the handler tests the class
+        // of what was thrown and throws anything else again
+        assert!(main.starts_with("b0:\n    try b1, catch b2\nb1:\n    v2 : int = 1\n    v3 : Int = box v2\n    throw v3, line 2\n"), "{}", main);
+        assert!(main.contains("b2:\n    v6 : Object = exception\n    v7 : bool = tagin v6, "), "{}", main);
+        assert!(main.contains("    endtry\n    jump b3\n"), "{}", main);
+        assert!(main.contains("b6:\n    rethrow\n"), "{}", main);
+        assert_eq!(parse::parse(&listing).unwrap().to_string(), listing);
+    }
+
+    #[test]
+    fn test_overflow_check() {
+        let source = "class Main {\n    n : Int;\n    main() : Int { ~(n * n) };\n};";
+        let classes = crate::parse_program(source).unwrap();
+        let ctx = AnalysisContext::new(crate::prelude(), &classes);
+        assert!(!lower(&ctx, &annotate(&ctx), Overflow::Wrap).unwrap().to_string().contains("overflow"));
+        let listing = lower(&ctx, &annotate(&ctx), Overflow::Trap).unwrap().to_string();
+        assert!(listing.contains(" = overflows mul v3, v4\n"), "{}", listing);
+        assert!(listing.contains(" = overflows sub "), "{}", listing);
+        assert_eq!(listing.matches("    overflow line 3\n").count(), 2, "{}", listing);
+    }
+}
@@ -0,0 +1,638 @@
+//! Reading IR back from the listing that `Program`'s `Display` writes
+//! (`--emit ir`), so that passes can be tested on small handwritten
+//! functions rather than on whole COOL programs.
+//!
+//! The listing is read as written, with two allowances for hand-written
+//! input: blank lines and comments from `;` to the end of the line are
+//! skipped, and a string constant may appear any number of times, becoming
+//! one entry of `Program::strings`. Blocks must be labelled in order from
+//! `b0`, and every variable must be a parameter or written by some
+//! instruction, always with the same type.
+
+use eyre::{bail, eyre, Result};
+
+use super::{BinaryOp, Block, BlockId, Callee, Function, Inst, Program, Terminator, Ty, UnaryOp, Var};
+use crate::symbol::Symbol;
+
+/// The program listed in `text`.
+pub fn parse(text: &str) -> Result<Program> {
+    let mut program = Program { functions: Vec::new(), strings: Vec::new() };
+    let mut lines = text
+        .lines()
+        .enumerate()
+        .map(|(i, line)| (i + 1, strip_comment(line).trim()))
+        .filter(|(_, line)| !line.is_empty())
+        .peekable();
+    while let Some((number, header)) = lines.next() {
+        let at = |err: eyre::Report| eyre!("line {}: {}", number, err);
+        let mut function = header_of(header).map_err(at)?;
+        let mut types: Vec<Option<Ty>> = function.vars.iter().copied().map(Some).collect();
+        let mut block: Option<(usize, Vec<Inst>)> = None;
+        loop {
+            let Some((number, line)) = lines.next() else {
+                bail!("line {}: function {}.{} has no closing `}}`", number, function.class, function.name);
+            };
+            let at = |err: eyre::Report| eyre!("line {}: {}: {}", number, err, line);
+            if line == "}" {
+                if block.is_some() {
+                    bail!("line {}: block b{} has no terminator", number, function.blocks.len());
+                }
+                break;
+            }
+            if let Some(label) = line.strip_suffix(':') {
+                if let Some((id, _)) = block {
+                    bail!("line {}: block b{} has no terminator", number, id);
+                }
+                let id = block_id(label).map_err(at)?;
+                if id.0 as usize != function.blocks.len() {
+                    bail!("line {}: expected block b{}, found {}", number, function.blocks.len(), label);
+                }
+                block = Some((function.blocks.len(), Vec::new()));
+                continue;
+            }
+            let Some((_, insts)) = &mut block else {
+                bail!("line {}: expected a block label: {}", number, line);
+            };
+            let mut tokens = Tokens::new(line).map_err(at)?;
+            if let Some(terminator) = terminator(&mut tokens).map_err(at)? {
+                tokens.end().map_err(at)?;
+                let (_, insts) = block.take().unwrap();
+                function.blocks.push(Block { insts, terminator });
+                continue;
+            }
+            let inst = inst(&mut tokens, &mut types, &mut program.strings).map_err(at)?;
+            tokens.end().map_err(at)?;
+            insts.push(inst);
+        }
+        if function.blocks.is_empty() {
+            bail!("line {}: function {}.{} has no blocks", number, function.class, function.name);
+        }
+        function.vars = types
+            .into_iter()
+            .enumerate()
+            .map(|(i, ty)| ty.ok_or_else(|| eyre!("{}.{}: v{} is never written", function.class, function.name, i)))
+            .collect::<Result<_>>()?;
+        check_uses(&function)?;
+        program.functions.push(function);
+    }
+    Ok(program)
+}
+
+fn strip_comment(line: &str) -> &str {
+    // A `;` in a string constant does not start a comment
+    let mut quoted = false;
+    let mut escaped = false;
+    for (i, c) in line.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if quoted => escaped = true,
+            '"' => quoted = !quoted,
+            ';' if !quoted => return &line[..i],
+            _ => {}
+        }
+    }
+    line
+}
+
+/// `Class.name(v0 : Ty, ...) {`, with the types of the parameters.
+fn header_of(line: &str) -> Result<Function> {
+    let mut tokens = Tokens::new(line)?;
+    let class = tokens.word()?;
+    tokens.expect(".")?;
+    let name = tokens.word()?;
+    tokens.expect("(")?;
+    let mut vars = Vec::new();
+    while !tokens.eat(")") {
+        if !vars.is_empty() {
+            tokens.expect(",")?;
+        }
+        let var = tokens.var()?;
+        if var.0 as usize != vars.len() {
+            bail!("expected parameter v{}, found {}", vars.len(), var);
+        }
+        tokens.expect(":")?;
+        vars.push(tokens.ty()?);
+    }
+    tokens.expect("{")?;
+    tokens.end()?;
+    if vars.is_empty() {
+        bail!("{}.{} has no `self` parameter", class, name);
+    }
+    Ok(Function {
+        class: Symbol::intern(&class),
+        name: Symbol::intern(&name),
+        params: vars.len(),
+        vars,
+        blocks: Vec::new(),
+    })
+}
+
+/// The terminator on the line, or `None` if it is an instruction.
+fn terminator(tokens: &mut Tokens) -> Result<Option<Terminator>> {
+    let Some(Token::Word(word)) = tokens.peek() else {
+        return Ok(None);
+    };
+    let terminator = match word.as_str() {
+        "jump" => {
+            tokens.next();
+            Terminator::Jump(tokens.block()?)
+        }
+        "branch" => {
+            tokens.next();
+            let cond = tokens.var()?;
+            tokens.expect(",")?;
+            let then = tokens.block()?;
+            tokens.expect(",")?;
+            Terminator::Branch { cond, then, orelse: tokens.block()? }
+        }
+        "return" => {
+            tokens.next();
+            Terminator::Return(tokens.var()?)
+        }
+        "nomatch" => {
+            tokens.next();
+            let object = tokens.var()?;
+            tokens.expect(",")?;
+            Terminator::NoMatch { object, line: tokens.line()? }
+        }
+        "voidcase" => {
+            tokens.next();
+            Terminator::CaseOnVoid { line: tokens.line()? }
+        }
+        "divzero" => {
+            tokens.next();
+            Terminator::DivisionByZero { line: tokens.line()? }
+        }
+        "overflow" => {
+            tokens.next();
+            Terminator::IntegerOverflow { line: tokens.line()? }
+        }
+        "voiddispatch" => {
+            tokens.next();
+            let method = Symbol::intern(&tokens.word()?);
+            tokens.expect(",")?;
+            Terminator::DispatchOnVoid { method, line: tokens.line()? }
+        }
+        "try" => {
+            tokens.next();
+            let body = tokens.block()?;
+            tokens.expect(",")?;
+            tokens.expect("catch")?;
+            Terminator::Try { body, handler: tokens.block()? }
+        }
+        "throw" => {
+            tokens.next();
+            let value = tokens.var()?;
+            tokens.expect(",")?;
+            Terminator::Throw { value, line: tokens.line()? }
+        }
+        "rethrow" => {
+            tokens.next();
+            Terminator::Rethrow
+        }
+        _ => return Ok(None),
+    };
+    Ok(Some(terminator))
+}
+
+/// An instruction, recording the type of the variable it writes.
+fn inst(tokens: &mut Tokens, types: &mut Vec<Option<Ty>>, strings: &mut Vec<String>) -> Result<Inst> {
+    if tokens.eat("setfield") {
+        let object = tokens.var()?;
+        tokens.expect(",")?;
+        let index = tokens.number()?;
+        tokens.expect(",")?;
+        return Ok(Inst::SetField { object, index, src: tokens.var()? });
+    }
+    if tokens.eat("endtry") {
+        return Ok(Inst::EndTry);
+    }
+    let dst = tokens.var()?;
+    tokens.expect(":")?;
+    let ty = tokens.ty()?;
+    tokens.expect("=")?;
+    let slot = dst.0 as usize;
+    if types.len() <= slot {
+        types.resize(slot + 1, None);
+    }
+    match types[slot] {
+        Some(known) if known != ty => bail!("{} is {} elsewhere", dst, known),
+        _ => types[slot] = Some(ty),
+    }
+
+    let word = match tokens.next() {
+        Some(Token::Int(value)) => {
+            let value = i32::try_from(value).map_err(|_| eyre!("{} does not fit in 32 bits", value))?;
+            return Ok(Inst::Int { dst, value });
+        }
+        Some(Token::Str(s)) => {
+            let index = match strings.iter().position(|t| *t == s) {
+                Some(index) => index,
+                None => {
+                    strings.push(s);
+                    strings.len() - 1
+                }
+            };
+            return Ok(Inst::Str { dst, index: index as u32 });
+        }
+        Some(Token::Var(src)) => return Ok(Inst::Copy { dst, src: Var(src) }),
+        Some(Token::Word(word)) => word,
+        other => bail!("expected an instruction, found {}", describe(other.as_ref())),
+    };
+    let two = |tokens: &mut Tokens| -> Result<(Var, Var)> {
+        let lhs = tokens.var()?;
+        tokens.expect(",")?;
+        Ok((lhs, tokens.var()?))
+    };
+    let inst = match word.as_str() {
+        "true" | "false" => Inst::Bool { dst, value: word == "true" },
+        "void" => Inst::Void { dst },
+        "box" => Inst::Box { dst, src: tokens.var()? },
+        "unbox" => Inst::Unbox { dst, src: tokens.var()? },
+        "neg" => Inst::Unary { dst, op: UnaryOp::Neg, src: tokens.var()? },
+        "not" => Inst::Unary { dst, op: UnaryOp::Not, src: tokens.var()? },
+        "overflows" => {
+            let op = binary_op(&tokens.word()?).ok_or_else(|| eyre!("expected an arithmetic operation"))?;
+            let (lhs, rhs) = two(tokens)?;
+            Inst::Overflows { dst, op, lhs, rhs }
+        }
+        "equal" => {
+            let (lhs, rhs) = two(tokens)?;
+            Inst::Equal { dst, lhs, rhs }
+        }
+        "isvoid" => Inst::IsVoid { dst, src: tokens.var()? },
+        "new" => Inst::New { dst, class: Symbol::intern(&tokens.word()?) },
+        "newlike" => Inst::NewLike { dst, object: tokens.var()? },
+        "exception" => Inst::Exception { dst },
+        "getfield" => {
+            let object = tokens.var()?;
+            tokens.expect(",")?;
+            Inst::GetField { dst, object, index: tokens.number()? }
+        }
+        "tagin" => {
+            let object = tokens.var()?;
+            tokens.expect(",")?;
+            let first = tokens.number()?;
+            tokens.expect("..=")?;
+            Inst::TagIn { dst, object, first, last: tokens.number()? }
+        }
+        "dispatch" => {
+            let method = Symbol::intern(&tokens.word()?);
+            tokens.expect("[")?;
+            let slot = tokens.number()?;
+            tokens.expect("]")?;
+            Inst::Call { dst, callee: Callee::Virtual { method, slot }, args: tokens.args()? }
+        }
+        "call" => {
+            let class = Symbol::intern(&tokens.word()?);
+            tokens.expect(".")?;
+            let method = Symbol::intern(&tokens.word()?);
+            Inst::Call { dst, callee: Callee::Static { class, method }, args: tokens.args()? }
+        }
+        other => match binary_op(other) {
+            Some(op) => {
+                let (lhs, rhs) = two(tokens)?;
+                Inst::Binary { dst, op, lhs, rhs }
+            }
+            None => bail!("unknown instruction `{}`", other),
+        },
+    };
+    Ok(inst)
+}
+
+fn binary_op(word: &str) -> Option<BinaryOp> {
+    Some(match word {
+        "add" => BinaryOp::Add,
+        "sub" => BinaryOp::Sub,
+        "mul" => BinaryOp::Mul,
+        "div" => BinaryOp::Div,
+        "lt" => BinaryOp::Lt,
+        "le" => BinaryOp::Le,
+        "eq" => BinaryOp::Eq,
+        _ => return None,
+    })
+}
+
+/// Every variable read is one the function has, and every block jumped to
+/// one it defines.
+fn check_uses(function: &Function) -> Result<()> {
+    let name = || format!("{}.{}", function.class, function.name);
+    for (id, block) in function.blocks.iter().enumerate() {
+        let reads = block.insts.iter().flat_map(Inst::uses).chain(block.terminator.uses());
+        for var in reads {
+            if var.0 as usize >= function.vars.len() {
+                bail!("{}: b{} reads {}, which is never written", name(), id, var);
+            }
+        }
+        for target in block.terminator.successors() {
+            if target.0 as usize >= function.blocks.len() {
+                bail!("{}: b{} jumps to {}, which does not exist", name(), id, target);
+            }
+        }
+    }
+    Ok(())
+}
+
+fn block_id(label: &str) -> Result<BlockId> {
+    label
+        .strip_prefix('b')
+        .and_then(|n| n.parse().ok())
+        .map(BlockId)
+        .ok_or_else(|| eyre!("expected a block label, found `{}`", label))
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Word(String),
+    Var(u32),
+    Block(u32),
+    Int(i64),
+    Str(String),
+    /// Punctuation, `..=` included
+    Punct(&'static str),
+}
+
+fn describe(token: Option<&Token>) -> String {
+    match token {
+        None => "the end of the line".to_string(),
+        Some(Token::Word(word)) => format!("`{}`", word),
+        Some(Token::Var(n)) => format!("`v{}`", n),
+        Some(Token::Block(n)) => format!("`b{}`", n),
+        Some(Token::Int(n)) => format!("`{}`", n),
+        Some(Token::Str(s)) => format!("{:?}", s),
+        Some(Token::Punct(p)) => format!("`{}`", p),
+    }
+}
+
+/// The tokens of one line.
+struct Tokens {
+    tokens: std::iter::Peekable<std::vec::IntoIter<Token>>,
+}
+
+impl Tokens {
+    fn new(line: &str) -> Result<Self> {
+        let mut tokens = Vec::new();
+        let mut chars = line.char_indices().peekable();
+        while let Some(&(start, c)) = chars.peek() {
+            if c.is_whitespace() {
+                chars.next();
+            } else if c == '"' {
+                chars.next();
+                tokens.push(Token::Str(string(&mut chars)?));
+            } else if c.is_ascii_alphanumeric() || c == '_' || c == '-' {
+                let mut end = start;
+                while let Some(&(i, c)) = chars.peek() {
+                    if !(c.is_ascii_alphanumeric() || c == '_' || (c == '-' && i == start)) {
+                        break;
+                    }
+                    end = i + c.len_utf8();
+                    chars.next();
+                }
+                tokens.push(word(&line[start..end])?);
+            } else if line[start..].starts_with("..=") {
+                chars.nth(2);
+                tokens.push(Token::Punct("..="));
+            } else {
+                let punct = [".", "(", ")", ",", ":", "=", "{", "}", "[", "]"]
+                    .into_iter()
+                    .find(|p| p.starts_with(c))
+                    .ok_or_else(|| eyre!("unexpected `{}`", c))?;
+                chars.next();
+                tokens.push(Token::Punct(punct));
+            }
+        }
+        Ok(Tokens { tokens: tokens.into_iter().peekable() })
+    }
+
+    fn peek(&mut self) -> Option<&Token> {
+        self.tokens.peek()
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        self.tokens.next()
+    }
+
+    /// Take `text`, a word or punctuation, if it comes next.
+    fn eat(&mut self, text: &str) -> bool {
+        let next = match self.peek() {
+            Some(Token::Word(word)) => word == text,
+            Some(Token::Punct(punct)) => *punct == text,
+            _ => false,
+        };
+        if next {
+            self.next();
+        }
+        next
+    }
+
+    fn expect(&mut self, text: &str) -> Result<()> {
+        if self.eat(text) {
+            return Ok(());
+        }
+        bail!("expected `{}`, found {}", text, describe(self.peek()))
+    }
+
+    fn end(&mut self) -> Result<()> {
+        match self.peek() {
+            None => Ok(()),
+            other => bail!("expected the end of the line, found {}", describe(other)),
+        }
+    }
+
+    fn word(&mut self) -> Result<String> {
+        match self.next() {
+            Some(Token::Word(word)) => Ok(word),
+            other => bail!("expected a name, found {}", describe(other.as_ref())),
+        }
+    }
+
+    fn var(&mut self) -> Result<Var> {
+        match self.next() {
+            Some(Token::Var(n)) => Ok(Var(n)),
+            other => bail!("expected a variable, found {}", describe(other.as_ref())),
+        }
+    }
+
+    fn block(&mut self) -> Result<BlockId> {
+        match self.next() {
+            Some(Token::Block(n)) => Ok(BlockId(n)),
+            other => bail!("expected a block, found {}", describe(other.as_ref())),
+        }
+    }
+
+    fn number(&mut self) -> Result<u32> {
+        match self.next() {
+            Some(Token::Int(n)) => u32::try_from(n).map_err(|_| eyre!("expected a number, found `{}`", n)),
+            other => bail!("expected a number, found {}", describe(other.as_ref())),
+        }
+    }
+
+    /// `line N`
+    fn line(&mut self) -> Result<usize> {
+        self.expect("line")?;
+        Ok(self.number()? as usize)
+    }
+
+    /// `int`, `bool` or a class
+    fn ty(&mut self) -> Result<Ty> {
+        let word = self.word()?;
+        Ok(match word.as_str() {
+            "int" => Ty::Int,
+            "bool" => Ty::Bool,
+            class if class.starts_with(|c: char| c.is_ascii_uppercase()) => Ty::Object(Symbol::intern(class)),
+            other => bail!("expected a type, found `{}`", other),
+        })
+    }
+
+    /// `(v1, v2, ...)`
+    fn args(&mut self) -> Result<Vec<Var>> {
+        self.expect("(")?;
+        let mut args = Vec::new();
+        while !self.eat(")") {
+            if !args.is_empty() {
+                self.expect(",")?;
+            }
+            args.push(self.var()?);
+        }
+        Ok(args)
+    }
+}
+
+/// A word: a variable `vN`, a block `bN`, a number or a name.
+fn word(text: &str) -> Result<Token> {
+    let numbered = |prefix: char| text.strip_prefix(prefix).filter(|n| !n.is_empty() && n.bytes().all(|b| b.is_ascii_digit()));
+    if let Some(n) = numbered('v') {
+        return Ok(Token::Var(n.parse()?));
+    }
+    if let Some(n) = numbered('b') {
+        return Ok(Token::Block(n.parse()?));
+    }
+    if text.starts_with(|c: char| c == '-' || c.is_ascii_digit()) {
+        return text.parse().map(Token::Int).map_err(|_| eyre!("`{}` is not a number", text));
+    }
+    Ok(Token::Word(text.to_string()))
+}
+
+/// The rest of a string constant after its opening quote, escaped as by
+/// `{:?}`.
+fn string(chars: &mut std::iter::Peekable<std::str::CharIndices<'_>>) -> Result<String> {
+    let mut out = String::new();
+    loop {
+        let Some((_, c)) = chars.next() else {
+            bail!("unterminated string");
+        };
+        match c {
+            '"' => return Ok(out),
+            '\\' => {
+                let escaped = match chars.next().map(|(_, c)| c) {
+                    Some('n') => '\n',
+                    Some('t') => '\t',
+                    Some('r') => '\r',
+                    Some('0') => '\0',
+                    Some(c @ ('\\' | '"' | '\'')) => c,
+                    Some('u') => {
+                        let mut hex = String::new();
+                        if chars.next().map(|(_, c)| c) != Some('{') {
+                            bail!("expected `{{` after `\\u`");
+                        }
+                        for (_, c) in chars.by_ref() {
+                            if c == '}' {
+                                break;
+                            }
+                            hex.push(c);
+                        }
+                        u32::from_str_radix(&hex, 16)
+                            .ok()
+                            .and_then(char::from_u32)
+                            .ok_or_else(|| eyre!("`\\u{{{}}}` is not a character", hex))?
+                    }
+                    other => bail!("unknown escape `\\{}`", other.map_or(String::new(), String::from)),
+                };
+                out.push(escaped);
+            }
+            c => out.push(c),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::Path;
+
+    use super::*;
+    use crate::arith::Overflow;
+    use crate::semantic::context::AnalysisContext;
+    use crate::semantic::type_checker::annotate;
+
+    /// The listing of every example reads back as the same program.
+    #[test]
+    fn test_round_trip() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("examples");
+        for entry in fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path.extension().is_none_or(|ext| ext != "cl") {
+                continue;
+            }
+            let source = fs::read_to_string(&path).unwrap();
+            let classes = crate::parse_program(&source).unwrap();
+            let ctx = AnalysisContext::new(crate::prelude(), &classes);
+            let program = super::super::lower(&ctx, &annotate(&ctx), Overflow::Trap).unwrap();
+            let listing = program.to_string();
+            let read = parse(&listing).unwrap_or_else(|err| panic!("{}: {:#}", path.display(), err));
+            assert_eq!(read.functions, program.functions, "{}", path.display());
+            assert_eq!(read.to_string(), listing, "{}", path.display());
+        }
+    }
+
+    #[test]
+    fn test_parse() {
+        let text = "\
+; Handwritten, with comments
+Main.f(v0 : Main, v1 : Int) {
+b0:
+    v2 : int = unbox v1   ; the argument
+    v3 : int = -3
+    v4 : bool = lt v2, v3
+    branch v4, b1, b2
+b1:
+    v5 : String = \"a;\\\"\\n\\u{e9}\"
+    v6 : String = \"a;\\\"\\n\\u{e9}\"
+    v7 : Object = dispatch out_string[4](v0, v5)
+    return v7
+b2:
+    v7 : Object = call Main._init(v0)
+    voiddispatch f, line 12
+}
+";
+        let program = parse(text).unwrap();
+        assert_eq!(program.strings, ["a;\"\né"]);
+        let function = &program.functions[0];
+        assert_eq!((function.class.as_str(), function.name.as_str(), function.params), ("Main", "f", 2));
+        assert_eq!(function.vars[3], Ty::Int);
+        assert_eq!(function.blocks[0].insts[1], Inst::Int { dst: Var(3), value: -3 });
+        assert_eq!(function.blocks[1].insts[1], Inst::Str { dst: Var(6), index: 0 });
+        assert_eq!(
+            function.blocks[2].terminator,
+            Terminator::DispatchOnVoid { method: Symbol::intern("f"), line: 12 }
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        let header = "Main.f(v0 : Main) {\n";
+        for (body, message) in [
+            ("b0:\n    return v1\n}", "b0 reads v1, which is never written"),
+            ("b0:\n    v1 : int = 1\n    v1 : bool = true\n    return v0\n}", "line 4: v1 is int elsewhere"),
+            ("b1:\n    return v0\n}", "line 2: expected block b0, found b1"),
+            ("b0:\n    jump b3\n}", "b0 jumps to b3, which does not exist"),
+            ("b0:\n    v1 : int = 1\n}", "line 4: block b0 has no terminator"),
+            ("b0:\n    v1 : int = frob v0\n    return v0\n}", "line 3: unknown instruction `frob`"),
+            ("b0:\n    return v0 v0\n}", "line 3: expected the end of the line, found `v0`"),
+            ("b0:\n    return v0\n", "function Main.f has no closing `}`"),
+        ] {
+            let err = parse(&format!("{}{}", header, body)).unwrap_err().to_string();
+            assert!(err.contains(message), "{:?}: {}", body, err);
+        }
+    }
+}
@@ -0,0 +1,347 @@
+use eyre::Result;
+use lalrpop_util::ParseError;
+use crate::ast::{Class, Feature, ArgDecl, Expr, TypedExpr};
+use crate::symbol::{sym, Symbol};
+
+pub mod arith;
+pub mod ast;
+pub mod cache;
+pub mod codegen;
+pub mod compiler;
+// Generated from cool.lalrpop by `make generate`
+#[allow(clippy::all)]
+pub mod cool;
+pub mod coverage;
+#[cfg(unix)]
+pub mod daemon;
+pub mod diagnostic;
+pub mod differential;
+pub mod ext;
+pub mod formatter;
+pub mod generator;
+pub mod ice;
+pub mod imports;
+pub mod incremental;
+pub mod interp;
+pub mod ir;
+pub mod logging;
+pub mod mutate;
+pub mod natives;
+pub mod opt;
+pub mod parsing;
+pub mod pretty;
+pub mod reduce;
+pub mod repl;
+pub mod semantic;
+pub mod source;
+pub mod symbol;
+pub mod timings;
+pub mod verify;
+pub mod vm;
+pub mod watch;
+
+
+/// Returns a Vec<Class> containing Object, IO, String, Int and Bool. Every
+/// method body is `Expr::Builtin`: the interpreter and the backends supply
+/// the implementations.
+pub fn builtin_classes() -> Vec<Class> {
+    vec![
+        // 1) Object
+        Class {
+            name: sym::OBJECT,
+            inherits: None,
+            feature_list: vec![
+                // abort(): Object
+                Feature::new_method(
+                    Symbol::intern("abort"),
+                    Vec::new(),
+                    sym::OBJECT,
+                    TypedExpr::new(Expr::Builtin, 0),
+                ),
+                // type_name(): String
+                Feature::new_method(
+                    Symbol::intern("type_name"),
+                    Vec::new(),
+                    sym::STRING,
+                    TypedExpr::new(Expr::Builtin, 0),
+                ),
+                // copy(): SELF_TYPE
+                Feature::new_method(
+                    Symbol::intern("copy"),
+                    Vec::new(),
+                    sym::SELF_TYPE,
+                    TypedExpr::new(Expr::Builtin, 0),
+                ),
+            ],
+            file: 0,
+            location: Default::default(),
+        },
+
+        // 2) IO inherits Object
+        Class {
+            name: sym::IO,
+            inherits: Some(sym::OBJECT),
+            feature_list: vec![
+                // out_string(str: String): SELF_TYPE
+                Feature::new_method(
+                    Symbol::intern("out_string"),
+                    vec![ArgDecl::new(Symbol::intern("str"), sym::STRING)],
+                    sym::SELF_TYPE,
+                    TypedExpr::new(Expr::Builtin, 0),
+                ),
+                // out_int(i: Int): SELF_TYPE
+                Feature::new_method(
+                    Symbol::intern("out_int"),
+                    vec![ArgDecl::new(Symbol::intern("i"), sym::INT)],
+                    sym::SELF_TYPE,
+                    TypedExpr::new(Expr::Builtin, 0),
+                ),
+                // in_string(): String
+                Feature::new_method(
+                    Symbol::intern("in_string"),
+                    Vec::new(),
+                    sym::STRING,
+                    TypedExpr::new(Expr::Builtin, 0),
+                ),
+                // in_int(): Int
+                Feature::new_method(
+                    Symbol::intern("in_int"),
+                    Vec::new(),
+                    sym::INT,
+                    TypedExpr::new(Expr::Builtin, 0),
+                ),
+            ],
+            file: 0,
+            location: Default::default(),
+        },
+
+        // 3) String inherits Object
+        Class {
+            name: sym::STRING,
+            inherits: Some(sym::OBJECT),
+            feature_list: vec![
+                // length(): Int
+                Feature::new_method(
+                    Symbol::intern("length"),
+                    Vec::new(),
+                    sym::INT,
+                    TypedExpr::new(Expr::Builtin, 0),
+                ),
+                // concat(s: String): String
+                Feature::new_method(
+                    Symbol::intern("concat"),
+                    vec![ArgDecl::new(Symbol::intern("s"), sym::STRING)],
+                    sym::STRING,
+                    TypedExpr::new(Expr::Builtin, 0),
+                ),
+                // substr(i: Int, l: Int): String
+                Feature::new_method(
+                    Symbol::intern("substr"),
+                    vec![
+                        ArgDecl::new(Symbol::intern("i"), sym::INT),
+                        ArgDecl::new(Symbol::intern("l"), sym::INT),
+                    ],
+                    sym::STRING,
+                    TypedExpr::new(Expr::Builtin, 0),
+                ),
+            ],
+            file: 0,
+            location: Default::default(),
+        },
+
+        // 4) Int inherits Object (no methods)
+        Class {
+            name: sym::INT,
+            inherits: Some(sym::OBJECT),
+            feature_list: Vec::new(),
+            file: 0,
+            location: Default::default(),
+        },
+
+        // 5) Bool inherits Object (no methods)
+        Class {
+            name: sym::BOOL,
+            inherits: Some(sym::OBJECT),
+            feature_list: Vec::new(),
+            file: 0,
+            location: Default::default(),
+        },
+    ]
+}
+
+/// The `Array` class of `--ext arrays`: a fixed-size sequence of `Object`
+/// slots. `(new Array).init(n)` sizes it, and indices run from 0 to n - 1.
+pub fn array_class() -> Class {
+    let int_arg = |name| ArgDecl::new(Symbol::intern(name), sym::INT);
+    let array = Symbol::intern("Array");
+    Class {
+        name: array,
+        inherits: Some(sym::OBJECT),
+        feature_list: vec![
+            // init(size: Int): Array
+            Feature::new_method(
+                Symbol::intern("init"),
+                vec![int_arg("size")],
+                array,
+                TypedExpr::new(Expr::Builtin, 0),
+            ),
+            // length(): Int
+            Feature::new_method(
+                Symbol::intern("length"),
+                Vec::new(),
+                sym::INT,
+                TypedExpr::new(Expr::Builtin, 0),
+            ),
+            // get(i: Int): Object
+            Feature::new_method(
+                Symbol::intern("get"),
+                vec![int_arg("i")],
+                sym::OBJECT,
+                TypedExpr::new(Expr::Builtin, 0),
+            ),
+            // set(i: Int, v: Object): Object
+            Feature::new_method(
+                Symbol::intern("set"),
+                vec![int_arg("i"), ArgDecl::new(Symbol::intern("v"), sym::OBJECT)],
+                sym::OBJECT,
+                TypedExpr::new(Expr::Builtin, 0),
+            ),
+        ],
+        file: 0,
+        location: Default::default(),
+    }
+}
+
+/// Lex and parse `source` into the user's classes (built-ins not included).
+pub fn parse_program(source: &str) -> Result<Vec<Class>> {
+    parse_program_with(source, &ext::Extensions::default())
+}
+
+/// `parse_program` with the given language extensions enabled.
+pub fn parse_program_with(source: &str, extensions: &ext::Extensions) -> Result<Vec<Class>> {
+    parse_module(source, extensions).map(|program| program.classes)
+}
+
+/// Parse one file, keeping its `import` directives. They are not followed
+/// here; `imports::load` does that.
+pub fn parse_module(source: &str, extensions: &ext::Extensions) -> Result<ast::Program> {
+    // Lexing: tokens are streamed straight into the parser
+    let scanner = parsing::scanner::Scanner::with_extensions(source, extensions.clone());
+    parse_scanned(source, scanner.spanned().map(|res| res.map(parsing::recovery::spanned)), extensions)
+}
+
+/// `parse_module` on the tokens already scanned from `source`, up to and
+/// including the first lexical error if there is one, so that lexing can be
+/// timed apart from parsing.
+pub fn parse_scanned(
+    source: &str,
+    tokens: impl IntoIterator<Item = Result<parsing::recovery::Spanned, parsing::token::LexicalError>>,
+    extensions: &ext::Extensions,
+) -> Result<ast::Program> {
+    // Parsing. On an error, the file is parsed again piece by piece to find the rest
+    let first = match cool::ProgramTyParser::new().parse(parsing::nesting::limit_nesting(source, tokens)) {
+        Ok(program) => return Ok(program),
+        Err(err) => err,
+    };
+    let mut errors = parsing::recovery::syntax_errors(source, extensions, first);
+    for err in &mut errors {
+        if let ParseError::UnrecognizedToken { expected, .. } | ParseError::UnrecognizedEof { expected, .. } = err {
+            expected.retain(|terminal| extensions.allows_terminal(terminal));
+        }
+    }
+    Err(eyre::Report::new(parsing::recovery::SyntaxErrors(errors)).wrap_err("Parsing failed"))
+}
+
+/// Parse tokens lexed elsewhere, as read back from a token dump by
+/// `parsing::dump::read_coolc_tokens`, with their offsets into `text`. The
+/// first syntax error is reported.
+pub fn parse_tokens(
+    text: &str,
+    tokens: Vec<parsing::recovery::Spanned>,
+    extensions: &ext::Extensions,
+) -> Result<ast::Program> {
+    let token_iter = tokens.into_iter().map(Ok::<_, parsing::token::LexicalError>);
+    cool::ProgramTyParser::new().parse(parsing::nesting::limit_nesting(text, token_iter)).map_err(|mut err| {
+        if let ParseError::UnrecognizedToken { expected, .. } | ParseError::UnrecognizedEof { expected, .. } = &mut err {
+            expected.retain(|terminal| extensions.allows_terminal(terminal));
+        }
+        eyre::Report::new(parsing::recovery::SyntaxErrors(vec![err])).wrap_err("Parsing failed")
+    })
+}
+
+/// Lex and parse `source` as one expression, as the REPL reads its input.
+pub fn parse_expression(source: &str, extensions: &ext::Extensions) -> Result<TypedExpr> {
+    let scanner = parsing::scanner::Scanner::with_extensions(source, extensions.clone());
+    let token_iter = scanner.spanned().map(|res| res.map(parsing::recovery::spanned));
+    cool::ExprTyParser::new().parse(parsing::nesting::limit_nesting(source, token_iter)).map_err(|mut err| {
+        if let ParseError::UnrecognizedToken { expected, .. } | ParseError::UnrecognizedEof { expected, .. } = &mut err {
+            expected.retain(|terminal| extensions.allows_terminal(terminal));
+        }
+        eyre::Report::new(parsing::recovery::SyntaxErrors(vec![err])).wrap_err("Parsing failed")
+    })
+}
+
+/// Run `f` on a thread of its own, named `name`, with a stack of `size`
+/// bytes rather than the caller's, for the passes that recurse once per
+/// nested expression.
+pub fn on_own_stack<T: Send>(name: &str, size: usize, f: impl FnOnce() -> T + Send) -> T {
+    std::thread::scope(|scope| {
+        let thread = std::thread::Builder::new()
+            .name(name.to_string())
+            .stack_size(size)
+            .spawn_scoped(scope, f)
+            .unwrap_or_else(|err| panic!("failed to start the {} thread: {}", name, err));
+        thread.join().unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+    })
+}
+
+/// The built-in classes, built on first use and shared for the rest of the process.
+pub fn prelude() -> &'static [Class] {
+    static PRELUDE: std::sync::OnceLock<Vec<Class>> = std::sync::OnceLock::new();
+    PRELUDE.get_or_init(builtin_classes)
+}
+
+/// The built-in classes plus those added by `extensions`. Each distinct
+/// prelude is built once and shared, like `prelude()`.
+pub fn prelude_with(extensions: &ext::Extensions) -> &'static [Class] {
+    if !extensions.contains(ext::Extension::Arrays) {
+        return prelude();
+    }
+    static PRELUDES: std::sync::Mutex<Vec<(ext::Extensions, &'static [Class])>> =
+        std::sync::Mutex::new(Vec::new());
+    let mut preludes = PRELUDES.lock().unwrap();
+    if let Some((_, classes)) = preludes.iter().find(|(key, _)| key == extensions) {
+        return classes;
+    }
+    let mut classes = builtin_classes();
+    classes.push(array_class());
+    // There are only as many of these as combinations of extensions
+    let classes: &'static [Class] = Vec::leak(classes);
+    preludes.push((extensions.clone(), classes));
+    classes
+}
+
+/// Run the semantic phases in order, stopping after the first one that reports errors.
+/// Returns the type-annotated classes when every phase ran.
+pub fn check_program(
+    ctx: &semantic::context::AnalysisContext<'_>,
+    ec: &mut semantic::collector::ErrorCollector,
+) -> Option<Vec<Class>> {
+    // Inheritance checks
+    ice::enter_phase("inheritance checks");
+    semantic::analyzer::check_inheritance(ctx, ec);
+    if ec.has_errors() {
+        return None;
+    }
+
+    // Attribute/Method symbol checks
+    ice::enter_phase("feature checks");
+    semantic::symbols::check_class_features(ctx, ec);
+    if ec.has_errors() {
+        return None;
+    }
+
+    // Expression/type checks
+    ice::enter_phase("type checking");
+    Some(semantic::type_checker::check_expressions(ctx, ec))
+}
@@ -0,0 +1,82 @@
+//! Opt-in debugging output of the driver (`--verbose`).
+//!
+//! The driver logs through the `log` crate with one target per phase
+//! (`PHASES`), so a run can show what one phase did without the noise of
+//! the others. `init` installs a logger that writes the records it lets
+//! through to stderr, one line each:
+//!
+//! ```text
+//! info  parse: parsed main.cl in 412.3µs
+//! ```
+//!
+//! Nothing is logged unless `init` is called, and records from other
+//! crates are never shown.
+
+use log::{LevelFilter, Log, Metadata, Record};
+
+/// The log targets, one per phase of the driver.
+pub const PHASES: [&str; 7] = ["lex", "parse", "cache", "semant", "opt", "run", "codegen"];
+
+/// Shows records up to `level`, from `phases` or from every phase when
+/// `phases` is empty.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Logger {
+    level: LevelFilter,
+    phases: Vec<&'static str>,
+}
+
+impl Logger {
+    pub fn new(level: LevelFilter, phases: Vec<&'static str>) -> Self {
+        Logger { level, phases }
+    }
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        let target = metadata.target();
+        metadata.level() <= self.level
+            && PHASES.contains(&target)
+            && (self.phases.is_empty() || self.phases.contains(&target))
+    }
+
+    fn log(&self, record: &Record) {
+        if self.enabled(record.metadata()) {
+            let level = record.level().as_str().to_lowercase();
+            eprintln!("{:<5} {}: {}", level, record.target(), record.args());
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+/// Install `logger` for the rest of the process. Only the first call has
+/// an effect.
+pub fn init(logger: Logger) {
+    let level = logger.level;
+    if log::set_boxed_logger(Box::new(logger)).is_ok() {
+        log::set_max_level(level);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use log::Level;
+
+    use super::*;
+
+    fn enabled(logger: &Logger, target: &str, level: Level) -> bool {
+        logger.enabled(&Metadata::builder().target(target).level(level).build())
+    }
+
+    #[test]
+    fn test_filters() {
+        let all = Logger::new(LevelFilter::Info, Vec::new());
+        assert!(enabled(&all, "parse", Level::Info) && enabled(&all, "codegen", Level::Warn));
+        assert!(!enabled(&all, "parse", Level::Debug));
+        assert!(!enabled(&all, "regex::compile", Level::Info));
+
+        let semant = Logger::new(LevelFilter::Trace, vec!["semant"]);
+        assert!(enabled(&semant, "semant", Level::Trace));
+        assert!(!enabled(&semant, "parse", Level::Info));
+    }
+}
@@ -1,200 +1,756 @@
-#![allow(warnings)]
-
-use std::{fs, path::PathBuf};
-use clap::Parser;
-use eyre::{Result, Context};
-use crate::ast::{Class, Feature, VarDecl, ArgDecl, Expr, TypedExpr};
-
-mod ast;
-mod parsing;
-mod semantic;
-mod cool;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::time::Instant;
+use eyre::WrapErr;
+use clap::{ArgGroup, Parser, Subcommand, ValueEnum};
+use cool_rs::source::{Disk, SourceReader, SourceText};
+use cool_rs::ext::{Extension, Extensions};
+use cool_rs::imports::{self, LoadError};
+use cool_rs::semantic::severity::{Level, Levels};
+use cool_rs::diagnostic::render::SourceFile;
+use cool_rs::diagnostic::{Diagnostic, Phase, Reporter};
+use cool_rs::ast::{printer, Class};
+use cool_rs::compiler::Compiler;
+use cool_rs::incremental::Session;
+use cool_rs::timings::Timings;
+use cool_rs::arith::Overflow;
+use cool_rs::parsing::dump::{coolc_tokens, escape_str, read_coolc_tokens};
+use cool_rs::parsing::recovery::{spanned, SyntaxErrors};
+use cool_rs::parsing::scanner::Scanner;
+use cool_rs::{cache, codegen, formatter, ice, interp, ir, logging, opt, reduce, repl, semantic, verify, vm, watch};
+#[cfg(unix)]
+use cool_rs::daemon;
 
 
 /// Command-line options
 #[derive(Parser)]
 #[command(name = "cool-rs", version, about = "A COOL language compiler written in Rust")]
+#[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
+#[command(group(ArgGroup::new("stage").args(["lex", "parse", "semant"])))]
 struct Cli {
-    /// Path to the input COOL source file
-    #[arg(short, long, value_name = "FILE")]
-    file: PathBuf,
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// Input COOL source files, compiled as one program (may be repeated)
+    #[arg(short, long = "file", value_name = "FILE", required_unless_present = "tokens_in", num_args = 1..)]
+    files: Vec<PathBuf>,
+
+    /// Read the program's tokens from FILE, a dump in the reference `lexer`
+    /// format, instead of lexing source files
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["files", "lex", "watch", "incremental", "emit"])]
+    tokens_in: Option<PathBuf>,
+
+    /// Keep each file's parsed classes and semantic results under
+    /// .cool-cache/, and parse and check again only the classes that
+    /// changed or that depend on what did
+    #[arg(long)]
+    incremental: bool,
+
+    /// Check the file again each time it is saved, printing its diagnostics
+    /// one per line and the time each phase took, until interrupted
+    #[arg(long, conflicts_with_all = ["stage", "run", "emit", "incremental"])]
+    watch: bool,
+
+    /// Check each phase's output for internal consistency (for testing the compiler)
+    #[arg(long)]
+    verify: bool,
+
+    /// Print the wall time each phase took to stderr when done
+    #[arg(long)]
+    timings: bool,
+
+    /// Stop after lexing and print the tokens in the reference `lexer` format
+    #[arg(long)]
+    lex: bool,
+
+    /// Stop after parsing and print the AST in the reference `parse` format
+    #[arg(long)]
+    parse: bool,
+
+    /// Stop after the semantic checks and print the type-annotated AST in the
+    /// reference `semant` format, for tools that diff against the reference compiler
+    #[arg(long, visible_alias = "type")]
+    semant: bool,
+
+    /// Run the program after the semantic checks
+    #[arg(long, conflicts_with = "stage")]
+    run: bool,
+
+    /// With `--run`, run the program on the bytecode VM rather than the
+    /// tree-walking interpreter
+    #[arg(long, requires = "run")]
+    vm: bool,
+
+    /// Also write compiled output next to the input file (`asm`: a SPIM `.s`
+    /// file; `ast-json`: the typed AST as `.ast.json`; `bytecode`: a listing
+    /// of the VM's bytecode as `.bytecode`; `c`: a C99 `.c` file; `ir`: a
+    /// listing of the compiler's IR as `.ir`; `cfg-dot`: the IR's
+    /// control-flow graphs for Graphviz as `.dot`; with the `llvm` feature,
+    /// `llvm-ir`: a `.ll` file, `object`: a `.o` file)
+    #[arg(long, value_name = "KIND", value_enum, conflicts_with = "stage")]
+    emit: Option<Emit>,
+
+    /// Optimize the program before `--run --vm` or `--emit` (0: no
+    /// optimization; 1: constant folding and algebraic simplification; 2:
+    /// also dead-code elimination, which warns about the code it removes)
+    #[arg(long, value_name = "N", default_value_t = 0, value_parser = clap::value_parser!(u8).range(0..=opt::MAX_LEVEL as i64))]
+    opt_level: u8,
+
+    /// Report to stderr what each optimization pass did, and where
+    /// (`human`: one `file:line:column: remark[pass]: message` line each;
+    /// `json`: one JSON object per line)
+    #[arg(long, value_name = "FORMAT", value_enum, conflicts_with = "stage")]
+    remarks: Option<RemarksFormat>,
+
+    /// What an `Int` operation that overflows 32 bits does, when folded,
+    /// run or compiled (`wrap`: wraps around; `trap`: stops the program with
+    /// a runtime error)
+    #[arg(long, value_name = "MODE", value_enum, default_value_t = Overflow::Wrap)]
+    int_overflow: Overflow,
+
+    /// Report a lint as an error (may be repeated)
+    #[arg(long, value_name = "LINT")]
+    deny: Vec<String>,
+
+    /// Report a lint as a warning, its default (may be repeated)
+    #[arg(long, value_name = "LINT")]
+    warn: Vec<String>,
+
+    /// Do not report a lint at all (may be repeated)
+    #[arg(long, value_name = "LINT")]
+    allow: Vec<String>,
+
+    /// Report every lint that is not allowed as an error
+    #[arg(long)]
+    deny_warnings: bool,
+
+    /// Enable a language extension (may be repeated)
+    #[arg(long = "ext", value_name = "NAME", value_enum)]
+    extensions: Vec<Extension>,
+
+    /// Stop after reporting N errors
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u32).range(1..))]
+    max_errors: Option<u32>,
+
+    /// Print only diagnostics, not the message after a successful check
+    #[arg(short, long, conflicts_with = "verbose")]
+    quiet: bool,
+
+    /// Log what each phase does to stderr; repeat for more (-v: a summary
+    /// of each phase, -vv: also the parsed AST, -vvv: also the typed AST)
+    #[arg(short, long, action = clap::ArgAction::Count)]
+    verbose: u8,
+
+    /// With `--verbose`, log only this phase (may be repeated)
+    #[arg(long, value_name = "PHASE", requires = "verbose",
+          value_parser = clap::builder::PossibleValuesParser::new(logging::PHASES))]
+    log: Vec<String>,
 }
 
-/// Read the entire file into a String, with context on errors
-fn read_file(path: &PathBuf) -> Result<String> {
-    fs::read_to_string(path).wrap_err_with(|| format!("Failed to read source file: {:?}", path))
+#[derive(Clone, Copy, ValueEnum)]
+enum Emit {
+    /// MIPS assembly for SPIM, to be loaded with the COOL runtime
+    Asm,
+    /// The type-annotated AST as JSON, with spans, for external tools
+    AstJson,
+    /// Portable C99, with the runtime included
+    C,
+    /// The bytecode the VM runs, one instruction per line
+    Bytecode,
+    /// The typed intermediate representation the C backend translates
+    Ir,
+    /// The control-flow graph and dominator tree of every IR function, in
+    /// Graphviz's DOT language
+    CfgDot,
+    /// The inheritance tree, built-ins included, in Graphviz's DOT language;
+    /// written even when the semantic checks fail
+    HierarchyDot,
+    /// Where every class, method and attribute is defined and used
+    Xref,
+    /// LLVM IR, with the runtime included
+    #[cfg(feature = "llvm")]
+    LlvmIr,
+    /// A native object file compiled by `llc`, to be linked with `cc`
+    #[cfg(feature = "llvm")]
+    Object,
 }
 
+#[derive(Clone, Copy, ValueEnum)]
+enum RemarksFormat {
+    /// One line per remark, located like a diagnostic
+    Human,
+    /// One JSON object per remark and line, for tools
+    Json,
+}
 
-/// Returns a Vec<Class> containing Object, IO, String, Int and Bool,
-/// each with dummy TypedExpr bodies (line = 0).
-fn builtin_classes() -> Vec<Class> {
-    let mut result = Vec::new();
-
-    // 1) Object
-    result.push(Class {
-        name: "Object".to_string(),
-        inherits: None,
-        feature_list: vec![
-            // abort(): Object { abort }
-            Feature::Method(
-                "abort".to_string(),
-                Vec::new(),
-                "Object".to_string(),
-                // TypedExpr::new(expr, line)
-                TypedExpr::new(Expr::Identifier("abort".to_string()), 0),
-            ),
-            // type_name(): String { "Object" }
-            Feature::Method(
-                "type_name".to_string(),
-                Vec::new(),
-                "String".to_string(),
-                TypedExpr::new(Expr::Str("Object".to_string()), 0),
-            ),
-        ],
-    });
+/// The document written by `--emit ast-json`.
+#[derive(serde::Serialize)]
+struct AstJson<'a> {
+    /// Input files, which classes refer to by index (`Class::file`)
+    files: &'a [String],
+    classes: &'a [Class],
+}
 
-    // 2) IO inherits Object
-    result.push(Class {
-        name: "IO".to_string(),
-        inherits: Some("Object".to_string()),
-        feature_list: vec![
-            // out_string(str: String): IO { self }
-            Feature::Method(
-                "out_string".to_string(),
-                vec![ArgDecl::new("str".to_string(), "String".to_string())],
-                "IO".to_string(),
-                TypedExpr::new(Expr::Identifier("self".to_string()), 0),
-            ),
-            // out_int(i: Int): IO { self }
-            Feature::Method(
-                "out_int".to_string(),
-                vec![ArgDecl::new("i".to_string(), "Int".to_string())],
-                "IO".to_string(),
-                TypedExpr::new(Expr::Identifier("self".to_string()), 0),
-            ),
-            // in_string(): String { "" }
-            Feature::Method(
-                "in_string".to_string(),
-                Vec::new(),
-                "String".to_string(),
-                TypedExpr::new(Expr::Str("".to_string()), 0),
-            ),
-            // in_int(): Int { 0 }
-            Feature::Method(
-                "in_int".to_string(),
-                Vec::new(),
-                "Int".to_string(),
-                TypedExpr::new(Expr::Int(0), 0),
-            ),
-        ],
-    });
+#[derive(Subcommand)]
+enum Command {
+    /// Stay resident and serve compile requests over a local socket
+    #[cfg(unix)]
+    Daemon {
+        /// Path of the Unix socket to listen on
+        #[arg(long, value_name = "PATH", default_value = ".cool-rs.sock")]
+        socket: PathBuf,
+    },
+    /// Rewrite source files in the standard layout
+    Fmt {
+        /// COOL source files to format in place
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
 
-    // 3) String inherits Object
-    result.push(Class {
-        name: "String".to_string(),
-        inherits: Some("Object".to_string()),
-        feature_list: vec![
-            // length(): Int { 0 }
-            Feature::Method(
-                "length".to_string(),
-                Vec::new(),
-                "Int".to_string(),
-                TypedExpr::new(Expr::Int(0), 0),
-            ),
-            // concat(s: String): String { self }
-            Feature::Method(
-                "concat".to_string(),
-                vec![ArgDecl::new("s".to_string(), "String".to_string())],
-                "String".to_string(),
-                TypedExpr::new(Expr::Identifier("self".to_string()), 0),
-            ),
-            // substr(i: Int, l: Int): String { self }
-            Feature::Method(
-                "substr".to_string(),
-                vec![
-                    ArgDecl::new("i".to_string(), "Int".to_string()),
-                    ArgDecl::new("l".to_string(), "Int".to_string()),
-                ],
-                "String".to_string(),
-                TypedExpr::new(Expr::Identifier("self".to_string()), 0),
-            ),
-        ],
-    });
+        /// Only report the files that are not formatted; exit with 1 if any
+        #[arg(long)]
+        check: bool,
 
-    // 4) Int inherits Object (no methods)
-    result.push(Class {
-        name: "Int".to_string(),
-        inherits: Some("Object".to_string()),
-        feature_list: Vec::new(),
-    });
+        /// Enable a language extension (may be repeated)
+        #[arg(long = "ext", value_name = "NAME", value_enum)]
+        extensions: Vec<Extension>,
+    },
+    /// Define classes and evaluate expressions interactively
+    Repl {
+        /// Enable a language extension (may be repeated)
+        #[arg(long = "ext", value_name = "NAME", value_enum)]
+        extensions: Vec<Extension>,
+    },
+    /// Shrink a program while it still crashes the compiler or emits a diagnostic
+    Reduce {
+        /// COOL source file to reduce
+        file: PathBuf,
 
-    // 5) Bool inherits Object (no methods)
-    result.push(Class {
-        name: "Bool".to_string(),
-        inherits: Some("Object".to_string()),
-        feature_list: Vec::new(),
-    });
+        /// Keep programs that make the compiler panic
+        #[arg(long, required_unless_present = "diagnostic", conflicts_with = "diagnostic")]
+        crash: bool,
+
+        /// Keep programs that report this diagnostic code (e.g. E0010)
+        #[arg(long, value_name = "CODE")]
+        diagnostic: Option<String>,
+
+        /// Write the reduced program here instead of stdout
+        #[arg(short, long, value_name = "FILE")]
+        output: Option<PathBuf>,
+    },
+}
+
+/// Exit status when the program fails at run time, with `--run`.
+const EXIT_RUNTIME: u8 = 1;
+/// Exit statuses when the input program has lexical, syntax or semantic
+/// errors; the earliest phase with errors decides.
+const EXIT_LEXICAL: u8 = 2;
+const EXIT_SYNTAX: u8 = 3;
+const EXIT_SEMANTIC: u8 = 4;
+/// Exit status when the compiler could not do its job, e.g. unreadable
+/// input or a bad command line.
+const EXIT_FAILED: u8 = 5;
+
+/// Stack of the thread the compiler runs on, enough for every phase to get
+/// through `semantic::type_checker::MAX_NESTING` levels of nesting.
+const STACK_SIZE: usize = 1 << 30;
+
+/// The exit status for a program rejected by `phase`.
+fn rejected(phase: Phase) -> ExitCode {
+    ExitCode::from(match phase {
+        Phase::Lexing => EXIT_LEXICAL,
+        Phase::Parsing => EXIT_SYNTAX,
+        Phase::Semantic => EXIT_SEMANTIC,
+    })
+}
 
-    result
+fn main() -> ExitCode {
+    let cli = match Cli::try_parse() {
+        Ok(cli) => cli,
+        Err(err) => {
+            // `--help` and `--version` are not errors
+            let _ = err.print();
+            return ExitCode::from(if err.use_stderr() { EXIT_FAILED } else { 0 });
+        }
+    };
+    // A panic while compiling is a compiler bug: report it as one. The
+    // subcommands handle their own panics.
+    if cli.command.is_none() {
+        ice::install_hook();
+    }
+    if cli.verbose > 0 {
+        let level = match cli.verbose {
+            1 => log::LevelFilter::Info,
+            2 => log::LevelFilter::Debug,
+            _ => log::LevelFilter::Trace,
+        };
+        let phases = logging::PHASES.into_iter().filter(|phase| cli.log.iter().any(|p| p == phase)).collect();
+        logging::init(logging::Logger::new(level, phases));
+    }
+    // The phases recurse once per nested expression, up to
+    // `MAX_NESTING` levels; the report is made on the same thread, as the
+    // phase it names is recorded per thread
+    cool_rs::on_own_stack("compiler", STACK_SIZE, || {
+        let mut timings = Timings::default();
+        let result = panic::catch_unwind(AssertUnwindSafe(|| run(&cli, &Disk, &mut timings)));
+        if cli.timings {
+            eprint!("{}", timings.report());
+        }
+        match result {
+            Ok(Ok(code)) => code,
+            Ok(Err(err)) => {
+                eprintln!("error: {:#}", err);
+                ExitCode::from(EXIT_FAILED)
+            }
+            Err(_) => {
+                ice::report(cli.files.first().map_or("<none>".as_ref(), PathBuf::as_path));
+                ExitCode::from(EXIT_FAILED)
+            }
+        }
+    })
 }
 
-fn main() -> eyre::Result<()> {
-    let cli = Cli::parse();
-    let source = read_file(&cli.file)?;
+/// Print `diagnostics` with snippets from `inputs`, the program's input
+/// files, up to `cli.max_errors` errors. Returns the earliest phase that
+/// rejects the program, if any.
+fn report(cli: &Cli, inputs: &[(PathBuf, SourceText)], diagnostics: &[Diagnostic]) -> Option<Phase> {
+    let names: Vec<String> = inputs.iter().map(|(path, _)| path.display().to_string()).collect();
+    let files: Vec<SourceFile> =
+        inputs.iter().zip(&names).map(|((_, text), name)| SourceFile { name, text: text.as_str() }).collect();
+    let mut reporter = Reporter::new(&files).max_errors(cli.max_errors.map(|max| max as usize));
+    for diagnostic in diagnostics {
+        reporter.report(diagnostic);
+    }
+    reporter.finish()
+}
 
-    // Lexing
-    let mut scanner = parsing::scanner::Scanner::new(&source);
-    let tokens = scanner.scan_tokens().unwrap();
-    let token_iter = tokens.into_iter().map(|(tok, loc)| {
-        Ok((loc.line, tok, loc.line))
+/// `compiler.parse`, with lexing timed apart from parsing.
+fn parse_timed(compiler: &Compiler, source: &str, timings: &mut Timings) -> eyre::Result<Vec<Class>> {
+    let tokens = timings.time("lex", || {
+        let mut tokens = Vec::new();
+        for token in Scanner::with_extensions(source, compiler.extensions().clone()).spanned() {
+            let failed = token.is_err();
+            tokens.push(token.map(spanned));
+            if failed {
+                break;
+            }
+        }
+        tokens
     });
+    let program = timings.time("parse", || cool_rs::parse_scanned(source, tokens, compiler.extensions()))?;
+    Ok(program.classes)
+}
+
+/// Carry out the command line, reading input files through `reader`, and
+/// time the phases it runs into `timings`.
+fn run(cli: &Cli, reader: &dyn SourceReader, timings: &mut Timings) -> eyre::Result<ExitCode> {
+    match &cli.command {
+        #[cfg(unix)]
+        Some(Command::Daemon { socket }) => return daemon::run(socket).map(|()| ExitCode::SUCCESS),
+        Some(Command::Fmt { files, check, extensions }) => {
+            let extensions: Extensions = extensions.iter().copied().collect();
+            let mut unformatted = false;
+            for path in files {
+                let source = SourceText::read_with(reader, path)?;
+                let formatted = match formatter::format_source(source.as_str(), &extensions) {
+                    Ok(formatted) => formatted,
+                    Err(err) => {
+                        let errors = err.downcast::<SyntaxErrors>()?.0;
+                        let diagnostics: Vec<Diagnostic> =
+                            errors.iter().map(|err| Diagnostic::syntax(err, 0, source.as_str())).collect();
+                        let phase = report(cli, &[(path.clone(), source)], &diagnostics);
+                        return Ok(rejected(phase.unwrap_or(Phase::Parsing)));
+                    }
+                };
+                if formatted == source.as_str() {
+                    continue;
+                }
+                if *check {
+                    println!("Would reformat: {}", path.display());
+                    unformatted = true;
+                } else {
+                    std::fs::write(path, formatted)?;
+                }
+            }
+            return Ok(ExitCode::from(u8::from(unformatted)));
+        }
+        Some(Command::Repl { extensions }) => {
+            let compiler = Compiler::new(extensions.iter().copied().collect());
+            let mut input = std::io::BufReader::new(std::io::stdin());
+            repl::run(compiler, &mut input, &mut std::io::stdout())?;
+            return Ok(ExitCode::SUCCESS);
+        }
+        Some(Command::Reduce { file, crash: _, diagnostic, output }) => {
+            let target = match diagnostic {
+                Some(code) => reduce::Target::Diagnostic(code.clone()),
+                None => reduce::Target::Crash,
+            };
+            let source = SourceText::read_with(reader, file)?;
+            let reduction = reduce::reduce(source.as_str(), &target)?;
+            eprintln!(
+                "Reduced {} expression nodes to {} in {} tests",
+                reduction.nodes_before, reduction.nodes_after, reduction.tests
+            );
+            match output {
+                Some(path) => std::fs::write(path, reduction.source)?,
+                None => print!("{}", reduction.source),
+            }
+            return Ok(ExitCode::SUCCESS);
+        }
+        None => {}
+    }
+
+    let extensions: Extensions = cli.extensions.iter().copied().collect();
+    let compiler = Compiler::new(extensions.clone());
+    // When a lint is named more than once, the strictest level wins
+    let mut levels = Levels::default();
+    for (names, level) in [(&cli.allow, Level::Allow), (&cli.warn, Level::Warn), (&cli.deny, Level::Deny)] {
+        for name in names {
+            levels.set(name, level)?;
+        }
+    }
+    if cli.deny_warnings {
+        levels.deny_warnings();
+    }
+    if cli.watch {
+        let [path] = cli.files.as_slice() else {
+            eyre::bail!("--watch takes one input file");
+        };
+        if extensions.contains(Extension::Imports) {
+            eyre::bail!("--watch does not follow imports");
+        }
+        let watch = watch::Watch::new(path.clone(), Compiler::new(extensions), levels);
+        watch.run(&mut std::io::stdout())?;
+        return Ok(ExitCode::SUCCESS);
+    }
+    if cli.incremental && extensions.contains(Extension::Imports) {
+        eyre::bail!("--incremental does not follow imports");
+    }
+    // Only the classes whose text, or what they depend on, changed since a
+    // run kept under .cool-cache/ are parsed and checked again
+    let mut incremental =
+        cli.incremental.then(|| (cache::Cache::new(cache::CACHE_DIR), Session::new(Compiler::new(extensions.clone()))));
 
-    // Parsing
-    let program = cool::ProgramTyParser::new()
-        .parse(token_iter)
-        .wrap_err("Parsing failed")?;
+    if cli.lex {
+        ice::enter_phase("lexing");
+        let mut failed = false;
+        for path in &cli.files {
+            let source = SourceText::read_with(reader, path)?;
+            println!("#name {}", escape_str(&path.display().to_string()));
+            let lexed = coolc_tokens(source.as_str(), &extensions);
+            print!("{}", lexed.dump);
+            log::info!(target: "lex", "{}: tokens: {}", path.display(), lexed.tokens);
+            failed |= lexed.errors > 0;
+        }
+        return Ok(ExitCode::from(if failed { EXIT_LEXICAL } else { 0 }));
+    }
 
-    let mut ast: Vec<ast::Class> = program.classes;
+    ice::enter_phase("parsing");
+    // The program's input files, in the order `Class::file` numbers them
+    let (inputs, mut ast) = if let Some(path) = &cli.tokens_in {
+        let dump = SourceText::read_with(reader, path)?;
+        let dump = read_coolc_tokens(dump.as_str(), &extensions)
+            .wrap_err_with(|| format!("Failed to read token dump {}", path.display()))?;
+        let program = timings.time("parse", || cool_rs::parse_tokens(&dump.text, dump.tokens, &extensions));
+        // Named as the reference parser names it
+        let name = dump.name.map_or_else(|| path.clone(), PathBuf::from);
+        let inputs = vec![(name, SourceText::Owned(dump.text))];
+        let program = match program {
+            Ok(program) => program,
+            Err(err) => {
+                let errors = err.downcast::<SyntaxErrors>()?.0;
+                let diagnostics: Vec<Diagnostic> =
+                    errors.iter().map(|err| Diagnostic::syntax(err, 0, inputs[0].1.as_str())).collect();
+                let phase = report(cli, &inputs, &diagnostics);
+                return Ok(rejected(phase.unwrap_or(Phase::Parsing)));
+            }
+        };
+        if !program.imports.is_empty() {
+            eyre::bail!("Imports are not followed from a token dump");
+        }
+        (inputs, program.classes)
+    } else if extensions.contains(Extension::Imports) {
+        let modules = match timings.time("parse", || imports::load(reader, &cli.files, &extensions)) {
+            Ok(modules) => modules,
+            Err(LoadError::Failed(err)) => return Err(err),
+            Err(LoadError::Syntax { path, source, errors }) => {
+                let diagnostics: Vec<Diagnostic> =
+                    errors.iter().map(|err| Diagnostic::syntax(err, 0, source.as_str())).collect();
+                let phase = report(cli, &[(path, source)], &diagnostics);
+                return Ok(rejected(phase.unwrap_or(Phase::Parsing)));
+            }
+            Err(LoadError::Rejected(err)) => {
+                eprintln!("error: {:#}", err);
+                return Ok(ExitCode::from(EXIT_SEMANTIC));
+            }
+        };
+        let mut inputs = Vec::new();
+        let mut ast = Vec::new();
+        for module in modules {
+            inputs.push((module.path, module.source));
+            ast.extend(module.classes);
+        }
+        (inputs, ast)
+    } else {
+        let mut inputs = Vec::new();
+        for path in &cli.files {
+            inputs.push((path.clone(), SourceText::read_with(reader, path)?));
+        }
+        let sources: Vec<&str> = inputs.iter().map(|(_, text)| text.as_str()).collect();
+        let reused = incremental.as_mut().and_then(|(cache, session)| {
+            timings.time("cache", || session.load(cache, &sources));
+            let ast = session.parse(&sources).ok()?;
+            let stats = session.stats();
+            timings.add("lex", stats.lex_time);
+            timings.add("parse", stats.parse_time);
+            log::info!(target: "cache", "parsed {} of {} classes", stats.parsed, stats.classes);
+            Some(ast)
+        });
+        let ast = match reused {
+            Some(ast) => ast,
+            None => {
+                // Parse every file, so the syntax errors in all of them are reported
+                let mut ast = Vec::new();
+                let mut diagnostics = Vec::new();
+                for (i, (path, text)) in inputs.iter().enumerate() {
+                    let start = Instant::now();
+                    let classes = if cli.timings {
+                        parse_timed(&compiler, text.as_str(), timings)
+                    } else {
+                        compiler.parse(text.as_str())
+                    };
+                    match classes {
+                        Ok(classes) => {
+                            log::info!(target: "parse", "parsed {} in {:.1?}", path.display(), start.elapsed());
+                            log::debug!(target: "parse", "{}: {:#?}", path.display(), classes);
+                            ast.extend(classes.into_iter().map(|mut class| {
+                                class.set_file(i);
+                                class
+                            }))
+                        }
+                        Err(err) => {
+                            let errors = err.downcast::<SyntaxErrors>()?.0;
+                            diagnostics.extend(errors.iter().map(|err| Diagnostic::syntax(err, i, text.as_str())));
+                        }
+                    }
+                }
+                if !diagnostics.is_empty() {
+                    let phase = report(cli, &inputs, &diagnostics);
+                    return Ok(rejected(phase.unwrap_or(Phase::Parsing)));
+                }
+                ast
+            }
+        };
+        (inputs, ast)
+    };
 
-    let mut builtins = builtin_classes();
-    let existing: std::collections::HashSet<_> =
-        ast.iter().map(|c| c.name.clone()).collect();
-    builtins.retain(|c| !existing.contains(&c.name));
-    
-    builtins.append(&mut ast);
-    let ast = builtins;
+    // Before any of the passes that recurse once per nesting level
+    let too_deep = semantic::nesting::limit_nesting(&mut ast);
+    if !too_deep.is_empty() {
+        let mut ec = semantic::collector::ErrorCollector::with_levels(levels.clone());
+        too_deep.into_iter().for_each(|err| ec.add(err));
+        let phase = report(cli, &inputs, &ec.diagnostics());
+        return Ok(rejected(phase.unwrap_or(Phase::Semantic)));
+    }
+    if cli.verify {
+        for (i, (_, text)) in inputs.iter().enumerate() {
+            let classes: Vec<Class> = ast.iter().filter(|c| c.file == i).cloned().collect();
+            verify::assert_consistent("parsing", &verify::verify_parse(text.as_str(), &classes));
+        }
+    }
+    let names: Vec<String> = inputs.iter().map(|(path, _)| path.display().to_string()).collect();
 
-    // Display the parsed AST
-    println!("Parsed AST ({} classes):", ast.len());
-    for class in &ast {
-        println!("{:#?}", class);
+    if cli.parse {
+        let names: Vec<&str> = names.iter().map(String::as_str).collect();
+        print!("{}", printer::coolc_parse(&ast, &names));
+        return Ok(ExitCode::SUCCESS);
     }
 
     // Semantic Phases
-    let mut ec = semantic::collector::ErrorCollector::default();
+    let mut ec = semantic::collector::ErrorCollector::with_levels(levels.clone());
+    let start = Instant::now();
+    let typed = match &mut incremental {
+        Some((cache, session)) => {
+            let typed = timings.time("semant", || session.check(&ast, &mut ec));
+            let stats = session.stats();
+            log::info!(target: "cache", "checked {} of {} classes", stats.checked, stats.classes);
+            let sources: Vec<&str> = inputs.iter().map(|(_, text)| text.as_str()).collect();
+            if let Err(err) = timings.time("cache", || session.store(cache, &sources)) {
+                eprintln!("warning: could not write {}: {}", cache::CACHE_DIR, err);
+            }
+            typed
+        }
+        None => {
+            let ctx = timings.time("class table", || compiler.context(&ast));
+            timings.time("semant", || cool_rs::check_program(&ctx, &mut ec))
+        }
+    };
+    log::info!(target: "semant", "checked in {:.1?}", start.elapsed());
 
-    // Inheritance checks
-    semantic::analyzer::check_inheritance(&ast, &mut ec);
-    if ec.has_errors() {
-        ec.report_all();
-        std::process::exit(1);
+    log::info!(target: "semant", "errors: {}, warnings: {}", ec.errors.len(), ec.warnings.len());
+    // Before the diagnostics, as a broken hierarchy is what it helps to see
+    if let Some(Emit::HierarchyDot) = cli.emit {
+        let path = cli.files[0].with_extension("hierarchy.dot");
+        let output = semantic::analyzer::hierarchy_dot(&compiler.context(&ast), ec.reported());
+        log::info!(target: "codegen", "writing {} ({} bytes)", path.display(), output.len());
+        std::fs::write(&path, output).wrap_err_with(|| format!("Failed to write {}", path.display()))?;
+    }
+    if let Some(phase) = report(cli, &inputs, &ec.diagnostics()) {
+        return Ok(rejected(phase));
+    }
+    if cli.verify {
+        ice::enter_phase("verification");
+        let ctx = compiler.context(&ast);
+        verify::assert_consistent("semantic analysis", &verify::verify_semant(&ctx));
     }
 
-    // Attribute/Method symbol checks
-    semantic::symbols::check_class_features(&ast, &mut ec);
-    if ec.has_errors() {
-        ec.report_all();
-        std::process::exit(1);
+    // Type checking ran, as otherwise the program was rejected above
+    let mut typed = typed.unwrap_or_default();
+
+    log::trace!(target: "semant", "{:#?}", typed);
+
+    if cli.semant {
+        ice::enter_phase("semant dump");
+        let names: Vec<&str> = names.iter().map(String::as_str).collect();
+        print!("{}", printer::coolc_types(&typed, &names));
+        return Ok(ExitCode::SUCCESS);
     }
 
-    // Expression/type checks
-    semantic::type_checker::check_expressions(&ast, &mut ec);
-    if ec.has_errors() {
-        ec.report_all();
-        std::process::exit(1);
+    ice::enter_phase("optimization");
+    let mut ec = semantic::collector::ErrorCollector::with_levels(levels);
+    let start = Instant::now();
+    let remarks = opt::optimize(&mut typed, cli.opt_level, cli.int_overflow, &mut ec);
+    timings.add("opt", start.elapsed());
+    log::info!(target: "opt", "level {} in {:.1?}, {} remarks", cli.opt_level, start.elapsed(), remarks.len());
+    if let Some(format) = cli.remarks {
+        let names: Vec<String> = inputs.iter().map(|(path, _)| path.display().to_string()).collect();
+        let files: Vec<SourceFile> =
+            inputs.iter().zip(&names).map(|((_, text), name)| SourceFile { name, text: text.as_str() }).collect();
+        for remark in &remarks {
+            match format {
+                RemarksFormat::Human => eprint!("{}", remark.human(&files)),
+                RemarksFormat::Json => eprint!("{}", remark.json(&files)),
+            }
+        }
     }
+    if let Some(phase) = report(cli, &inputs, &ec.diagnostics()) {
+        return Ok(rejected(phase));
+    }
+    if !cli.run && !cli.quiet {
+        println!("Semantic checks passed without errors.");
+    }
+
+    if cli.run {
+        ice::enter_phase("running");
+        let ctx = compiler.context(&ast);
+        let mut input = std::io::BufReader::new(std::io::stdin());
+        let mut output = std::io::BufWriter::new(std::io::stdout());
+        let start = Instant::now();
+        let result = if cli.vm {
+            vm::run(&vm::compile(&ctx, &typed)?, cli.int_overflow, &mut input, &mut output)
+        } else {
+            interp::run(&ctx, cli.int_overflow, &mut input, &mut output)
+        };
+        timings.add("run", start.elapsed());
+        let engine = if cli.vm { "VM" } else { "interpreter" };
+        log::info!(target: "run", "{} finished in {:.1?}", engine, start.elapsed());
+        if let Err(err) = result {
+            eprintln!("error: {}", err.located(&names));
+            return Ok(ExitCode::from(EXIT_RUNTIME));
+        }
+    }
+
+    if let Some(emit) = cli.emit {
+        ice::enter_phase("code generation");
+        // Output is named after the first input file, as coolc does
+        let file = &cli.files[0];
+        let ctx = compiler.context(&ast);
+        let start = Instant::now();
+        let (output, extension) = match emit {
+            Emit::Asm => (codegen::generate(&ctx, &typed, cli.int_overflow)?, "s"),
+            Emit::AstJson => {
+                let program = AstJson { files: &names, classes: &typed };
+                (serde_json::to_string_pretty(&program)? + "\n", "ast.json")
+            }
+            Emit::C => (codegen::c::generate(&ctx, &typed, cli.int_overflow)?, "c"),
+            Emit::Bytecode => (vm::compile(&ctx, &typed)?.to_string(), "bytecode"),
+            Emit::Ir => (ir::lower(&ctx, &typed, cli.int_overflow)?.to_string(), "ir"),
+            Emit::CfgDot => (ir::cfg::dot(&ir::lower(&ctx, &typed, cli.int_overflow)?), "dot"),
+            // Written after the semantic checks
+            Emit::HierarchyDot => return Ok(ExitCode::SUCCESS),
+            Emit::Xref => {
+                let files: Vec<SourceFile> =
+                    inputs.iter().zip(&names).map(|((_, text), name)| SourceFile { name, text: text.as_str() }).collect();
+                // The program as written: optimizations drop uses
+                let typed = semantic::type_checker::annotate(&ctx);
+                (semantic::xref::resolve(&ctx, &typed).render(&files), "xref")
+            }
+            #[cfg(feature = "llvm")]
+            Emit::LlvmIr => (codegen::llvm::generate(&ctx, &typed, cli.int_overflow)?, "ll"),
+            #[cfg(feature = "llvm")]
+            Emit::Object => {
+                let path = file.with_extension("o");
+                codegen::llvm::write_object(&codegen::llvm::generate(&ctx, &typed, cli.int_overflow)?, &path)
+                    .wrap_err_with(|| format!("Failed to write {}", path.display()))?;
+                timings.add("codegen", start.elapsed());
+                return Ok(ExitCode::SUCCESS);
+            }
+        };
+        timings.add("codegen", start.elapsed());
+        let path = file.with_extension(extension);
+        log::info!(target: "codegen", "writing {} ({} bytes)", path.display(), output.len());
+        std::fs::write(&path, output).wrap_err_with(|| format!("Failed to write {}", path.display()))?;
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+    use std::path::Path;
+
+    use cool_rs::source::Contents;
+
+    use super::*;
 
-    println!("Semantic checks passed without errors.");
-    Ok(())
+    /// Serves fixed bytes, or fails with a fixed error, for every path.
+    struct FakeReader(Result<&'static [u8], io::ErrorKind>);
+
+    impl SourceReader for FakeReader {
+        fn read(&self, _path: &Path) -> io::Result<Contents> {
+            self.0.map(|bytes| Contents::Bytes(bytes.to_vec())).map_err(io::Error::from)
+        }
+    }
+
+    fn run_with(reader: FakeReader) -> eyre::Result<ExitCode> {
+        run(&Cli::parse_from(["cool-rs", "--file", "in.cl"]), &reader, &mut Timings::default())
+    }
+
+    #[test]
+    fn test_read_failures_are_clean_errors() {
+        let err = run_with(FakeReader(Err(io::ErrorKind::PermissionDenied))).unwrap_err();
+        assert_eq!(
+            format!("{:#}", err),
+            "Failed to read source file: \"in.cl\": permission denied"
+        );
+
+        let err = run_with(FakeReader(Ok(b"class Main {}; \xff"))).unwrap_err();
+        assert_eq!(
+            format!("{:#}", err),
+            "Failed to read source file: \"in.cl\": stream did not contain valid UTF-8 \
+             (invalid utf-8 sequence of 1 bytes from index 15)"
+        );
+    }
+
+    #[test]
+    fn test_exit_codes() {
+        let ok = run_with(FakeReader(Ok(b"class Main { main() : Int { 0 }; };"))).unwrap();
+        assert_eq!(ok, ExitCode::SUCCESS);
+        let ill_typed = run_with(FakeReader(Ok(b"class Main { main() : Int { x }; };"))).unwrap();
+        assert_eq!(ill_typed, ExitCode::from(EXIT_SEMANTIC));
+        let unparsable = run_with(FakeReader(Ok(b"class Main {"))).unwrap();
+        assert_eq!(unparsable, ExitCode::from(EXIT_SYNTAX));
+        let unlexable = run_with(FakeReader(Ok(b"class Main { main() : Int { 0 # 1 }; };"))).unwrap();
+        assert_eq!(unlexable, ExitCode::from(EXIT_LEXICAL));
+    }
 }
@@ -0,0 +1,177 @@
+//! Token-level mutation of valid programs.
+//!
+//! Each mutant is a corpus program with one targeted edit to its token
+//! stream, such as a deleted `;`, `<-` swapped for `=` or a renamed type.
+//! The mutants are almost valid, which makes them good at probing how
+//! the parser and semantic checks cope with the mistakes people actually
+//! make.
+
+use crate::generator::Rng;
+use crate::parsing::scanner::Scanner;
+use crate::parsing::token::{Loc, Token};
+use crate::symbol::Symbol;
+
+/// The kinds of edit a mutant can carry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mutation {
+    DeleteSemicolon,
+    DeleteToken,
+    DuplicateToken,
+    SwapAdjacent,
+    /// `<-` becomes `=` and vice versa.
+    SwapAssignEqual,
+    /// A type name becomes another type in the program, or an undefined one.
+    RenameType,
+    /// An identifier becomes another identifier in the program, or an undefined one.
+    RenameIdentifier,
+}
+
+const MUTATIONS: [Mutation; 7] = [
+    Mutation::DeleteSemicolon,
+    Mutation::DeleteToken,
+    Mutation::DuplicateToken,
+    Mutation::SwapAdjacent,
+    Mutation::SwapAssignEqual,
+    Mutation::RenameType,
+    Mutation::RenameIdentifier,
+];
+
+/// Produces mutants of one program.
+pub struct Mutator {
+    tokens: Vec<(Token, Loc)>,
+    types: Vec<Symbol>,
+    identifiers: Vec<Symbol>,
+    rng: Rng,
+}
+
+impl Mutator {
+    /// `None` if `source` does not lex.
+    pub fn new(source: &str, seed: u64) -> Option<Self> {
+        let tokens = Scanner::new(source).scan_tokens().ok()?;
+        let mut types = vec![Symbol::intern("Undefined")];
+        let mut identifiers = vec![Symbol::intern("undefined")];
+        for (token, _) in &tokens {
+            match token {
+                Token::Typeid(name) if !types.contains(name) => types.push(*name),
+                Token::Objectid(name) if !identifiers.contains(name) => identifiers.push(*name),
+                _ => {}
+            }
+        }
+        Some(Mutator { tokens, types, identifiers, rng: Rng(seed) })
+    }
+
+    /// The next mutant, with the edit it carries. Mutations that do not apply
+    /// to this program (no `<-` to swap, say) fall back to deleting a token.
+    pub fn mutant(&mut self) -> (Mutation, String) {
+        let mut tokens = self.tokens.clone();
+        let mutation = MUTATIONS[self.rng.below(MUTATIONS.len())];
+        let applied = match mutation {
+            Mutation::DeleteSemicolon => self
+                .pick(&tokens, |t| *t == Token::Semicolon)
+                .map(|i| drop(tokens.remove(i))),
+            Mutation::DeleteToken => self.pick(&tokens, |_| true).map(|i| drop(tokens.remove(i))),
+            Mutation::DuplicateToken => self
+                .pick(&tokens, |_| true)
+                .map(|i| tokens.insert(i, tokens[i].clone())),
+            Mutation::SwapAdjacent => self
+                .pick(&tokens[1..], |_| true)
+                .map(|i| {
+                    let (a, b) = (tokens[i].0.clone(), tokens[i + 1].0.clone());
+                    tokens[i].0 = b;
+                    tokens[i + 1].0 = a;
+                }),
+            Mutation::SwapAssignEqual => self
+                .pick(&tokens, |t| matches!(t, Token::Assign | Token::Equal))
+                .map(|i| {
+                    tokens[i].0 = match tokens[i].0 {
+                        Token::Assign => Token::Equal,
+                        _ => Token::Assign,
+                    }
+                }),
+            Mutation::RenameType => self
+                .pick(&tokens, |t| matches!(t, Token::Typeid(_)))
+                .map(|i| {
+                    let name = self.types[self.rng.below(self.types.len())];
+                    tokens[i].0 = Token::Typeid(name);
+                }),
+            Mutation::RenameIdentifier => self
+                .pick(&tokens, |t| matches!(t, Token::Objectid(_)))
+                .map(|i| {
+                    let name = self.identifiers[self.rng.below(self.identifiers.len())];
+                    tokens[i].0 = Token::Objectid(name);
+                }),
+        };
+
+        match applied {
+            Some(()) => (mutation, render(&tokens)),
+            None if tokens.is_empty() => (mutation, String::new()),
+            None => {
+                let i = self.rng.below(tokens.len());
+                tokens.remove(i);
+                (Mutation::DeleteToken, render(&tokens))
+            }
+        }
+    }
+
+    /// Index of a random token satisfying `matches`.
+    fn pick(&mut self, tokens: &[(Token, Loc)], matches: impl Fn(&Token) -> bool) -> Option<usize> {
+        let candidates: Vec<usize> = (0..tokens.len()).filter(|&i| matches(&tokens[i].0)).collect();
+        if candidates.is_empty() {
+            return None;
+        }
+        Some(candidates[self.rng.below(candidates.len())])
+    }
+}
+
+/// Source text for `tokens`, keeping every token on its original line so
+/// diagnostics can be compared with the unmutated program.
+fn render(tokens: &[(Token, Loc)]) -> String {
+    let mut out = String::new();
+    let mut line = 1;
+    for (token, loc) in tokens {
+        while line < loc.line {
+            out.push('\n');
+            line += 1;
+        }
+        if !out.is_empty() && !out.ends_with('\n') {
+            out.push(' ');
+        }
+        out.push_str(&token.to_string());
+    }
+    out.push('\n');
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(source: &str) -> Vec<(Token, usize)> {
+        let tokens = Scanner::new(source).scan_tokens().unwrap();
+        tokens.into_iter().map(|(token, loc)| (token, loc.line)).collect()
+    }
+
+    #[test]
+    fn test_render_keeps_tokens_and_lines() {
+        let source = "class Main inherits IO {\n  main() : Object {\n    out_string(\"hi\")\n  };\n};\n";
+        let tokens = Scanner::new(source).scan_tokens().unwrap();
+        assert_eq!(lines(&render(&tokens)), lines(source));
+    }
+
+    #[test]
+    fn test_mutants_differ_by_one_edit() {
+        let source = "class Main { x : Int <- 1; main() : Bool { x = 2 }; };";
+        let mut mutator = Mutator::new(source, 3).unwrap();
+        let original = lines(source).len();
+        for _ in 0..50 {
+            let (mutation, mutant) = mutator.mutant();
+            let tokens = Scanner::new(&mutant).scan_tokens().unwrap();
+            let expected = match mutation {
+                Mutation::DeleteSemicolon | Mutation::DeleteToken => original - 1,
+                Mutation::DuplicateToken => original + 1,
+                _ => original,
+            };
+            assert_eq!(tokens.len(), expected, "{:?}: {}", mutation, mutant);
+        }
+    }
+}
@@ -0,0 +1,74 @@
+//! Host functions for `native` methods (`--ext natives`).
+//!
+//! A method declared `f(x : Int) : Int native;` has no COOL body: a runtime
+//! supplies it from this table. Exposing extra host functionality, such as
+//! a clock, means adding an entry here. The type checker rejects native
+//! declarations whose class, name and signature match no entry, so a
+//! program never depends on a function the host does not provide.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::interp::Value;
+use crate::symbol::Symbol;
+
+/// Signature of one host function, as written in COOL, and its
+/// implementation for the interpreter.
+#[derive(Debug)]
+pub struct HostFunction {
+    pub class: &'static str,
+    pub method: &'static str,
+    pub params: &'static [&'static str],
+    pub ret: &'static str,
+    /// Called with the arguments, which have the types in `params`
+    pub call: fn(&[Value]) -> Value,
+}
+
+/// Every host function a native method can bind to.
+pub const HOST_FUNCTIONS: &[HostFunction] = &[
+    // Seconds since the Unix epoch
+    HostFunction { class: "Sys", method: "clock", params: &[], ret: "Int", call: clock },
+    // Uniformly distributed in [0, n), or 0 if n is not positive
+    HostFunction { class: "Sys", method: "random", params: &["Int"], ret: "Int", call: random },
+];
+
+fn clock(_: &[Value]) -> Value {
+    let seconds = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+    Value::Int(seconds as i32)
+}
+
+fn random(args: &[Value]) -> Value {
+    static STATE: AtomicU64 = AtomicU64::new(0);
+    let Value::Int(n) = args[0] else { unreachable!("type-checked as Int") };
+    if n <= 0 {
+        return Value::Int(0);
+    }
+    // xorshift64*, seeded from the clock on first use
+    let mut x = STATE.load(Ordering::Relaxed);
+    if x == 0 {
+        x = SystemTime::now().duration_since(UNIX_EPOCH).map_or(1, |d| d.as_nanos() as u64) | 1;
+    }
+    x ^= x >> 12;
+    x ^= x << 25;
+    x ^= x >> 27;
+    STATE.store(x, Ordering::Relaxed);
+    let r = x.wrapping_mul(0x2545_F491_4F6C_DD1D) >> 32;
+    Value::Int((r % n as u64) as i32)
+}
+
+/// The host function for native method `class.method` with the given
+/// parameter and return types, if there is one.
+pub fn lookup(
+    class: Symbol,
+    method: Symbol,
+    params: &[Symbol],
+    ret: Symbol,
+) -> Option<&'static HostFunction> {
+    HOST_FUNCTIONS.iter().find(|f| {
+        f.class == class.as_str()
+            && f.method == method.as_str()
+            && f.ret == ret.as_str()
+            && f.params.len() == params.len()
+            && f.params.iter().zip(params).all(|(p, q)| *p == q.as_str())
+    })
+}
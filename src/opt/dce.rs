@@ -0,0 +1,112 @@
+//! Dead-code elimination.
+//!
+//! Runs after folding, which reduces conditions such as `1 < 2` to a
+//! constant. `if true then a else b fi` becomes `a`, keeping the
+//! conditional's static type, which `a`'s conforms to, and `if false`
+//! becomes `b`; the body of `while false loop ... pool` is dropped. Each
+//! removed branch or body is reported as a `ConstantCondition` warning at
+//! its span, and as a remark. Nothing removed is searched further, so dead
+//! code inside dead code is reported once.
+
+use super::{Pass, Remark};
+use crate::ast::{Class, Expr, Feature, TypedExpr, VarDecl};
+use crate::semantic::collector::ErrorCollector;
+use crate::semantic::errors::SemanticError::ConstantCondition;
+use crate::symbol::sym;
+
+pub(super) fn run(classes: &mut [Class], ec: &mut ErrorCollector, remarks: &mut Vec<Remark>) {
+    for class in classes {
+        for feature in &mut class.feature_list {
+            match feature {
+                Feature::Attribute(VarDecl { expr: Some(init), .. }) => prune(init, ec, remarks),
+                Feature::Attribute(_) => {}
+                Feature::Method(_, _, _, body, _) => prune(body, ec, remarks),
+            }
+        }
+    }
+}
+
+/// Remove the dead code in `e`, reporting it to `ec` and `remarks`.
+fn prune(e: &mut TypedExpr, ec: &mut ErrorCollector, remarks: &mut Vec<Remark>) {
+    let expr = std::mem::replace(&mut e.expr, Expr::Builtin);
+    e.expr = match expr {
+        Expr::Conditional { test, then, orelse } if matches!(test.expr, Expr::Bool(_)) => {
+            let value = matches!(test.expr, Expr::Bool(true));
+            let (live, dead) = if value { (then, orelse) } else { (orelse, then) };
+            ec.add(ConstantCondition { value, line: dead.line, span: dead.span });
+            let message = format!(
+                "eliminated the dead `{}` branch of `if {}`",
+                if value { "else" } else { "then" },
+                value
+            );
+            remarks.push(Remark { pass: Pass::DeadCode, line: dead.line, span: dead.span, message });
+            *e = TypedExpr { static_type: e.static_type, ..*live };
+            return prune(e, ec, remarks);
+        }
+        Expr::While { test, exec } if matches!(test.expr, Expr::Bool(false)) => {
+            ec.add(ConstantCondition { value: false, line: exec.line, span: exec.span });
+            let message = "eliminated the body of `while false`".to_string();
+            remarks.push(Remark { pass: Pass::DeadCode, line: exec.line, span: exec.span, message });
+            // The loop still needs a body, and a constant costs nothing
+            let exec = Box::new(TypedExpr { expr: Expr::Bool(false), static_type: Some(sym::BOOL), ..*exec });
+            Expr::While { test, exec }
+        }
+        expr => expr,
+    };
+    for child in e.expr.children_mut() {
+        prune(child, ec, remarks);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::semantic::context::AnalysisContext;
+    use crate::semantic::type_checker::annotate;
+
+    #[test]
+    fn test_prune() {
+        let source = "class Main inherits IO {\n\
+            main() : Object { {\n\
+                if 1 < 2 then out_int(1) else if true then 2 else 3 fi fi;\n\
+                while not true loop\n\
+                    out_int(4)\n\
+                pool;\n\
+                if false then 0 else { while true loop out_int(5) pool; 6; } fi;\n\
+            } };\n\
+        };";
+        let classes = crate::parse_program(source).unwrap();
+        let ctx = AnalysisContext::new(crate::prelude(), &classes);
+        let mut typed = annotate(&ctx);
+        super::super::fold::run(&mut typed, crate::arith::Overflow::Wrap, &mut Vec::new());
+        let mut ec = ErrorCollector::default();
+        let mut remarks = Vec::new();
+        run(&mut typed, &mut ec, &mut remarks);
+
+        let warnings: Vec<String> = ec.warnings.iter().map(|w| format!("{} {}", w.code(), w)).collect();
+        assert_eq!(
+            warnings,
+            [
+                "W0005 [line 3] Unreachable code: the condition is always true",
+                "W0005 [line 5] Unreachable code: the condition is always false",
+                "W0005 [line 7] Unreachable code: the condition is always false",
+            ]
+        );
+        let remarks: Vec<(usize, &str)> = remarks.iter().map(|r| (r.line, r.message.as_str())).collect();
+        assert_eq!(
+            remarks,
+            [
+                (3, "eliminated the dead `else` branch of `if true`"),
+                (5, "eliminated the body of `while false`"),
+                (7, "eliminated the dead `then` branch of `if false`"),
+            ]
+        );
+        let printed = crate::pretty::print_program(&typed);
+        assert!(printed.contains("{\n            out_int(1);\n            while false loop false pool;\n"), "{}", printed);
+        assert!(printed.contains("while true loop out_int(5) pool;\n"), "{}", printed);
+        let Feature::Method(_, _, _, body, _) = &typed[0].feature_list[0] else { unreachable!() };
+        let Expr::Block(exprs) = &body.expr else { unreachable!() };
+        // The conditional's type, not the branch's
+        assert_eq!(exprs[0].static_type, Some(sym::OBJECT));
+    }
+}
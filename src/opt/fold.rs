@@ -0,0 +1,182 @@
+//! Constant folding and algebraic simplification.
+//!
+//! Expressions are rewritten bottom-up, so a fold can expose another one
+//! above it: `(1 + 2) * x` becomes `3 * x`. A folded expression keeps its
+//! node's line and static type. Arithmetic overflows as at run time, in the
+//! mode it is given: an operation that would trap is left for the program
+//! to fail on, as is a division by a constant zero. An
+//! operand is only dropped when it has no side effects to lose, so
+//! `f() * 0` stays as it is. Every rewrite is a remark, showing the
+//! expression as it was once its operands were folded.
+
+use super::{Pass, Remark};
+use crate::arith::Overflow;
+use crate::ast::{Class, ComparisonOperator, Expr, Feature, MathOperator, TypedExpr, UnaryOperator, VarDecl};
+use crate::pretty::print_expr;
+use crate::symbol::sym;
+
+pub(super) fn run(classes: &mut [Class], overflow: Overflow, remarks: &mut Vec<Remark>) {
+    for class in classes {
+        for feature in &mut class.feature_list {
+            match feature {
+                Feature::Attribute(VarDecl { expr: Some(init), .. }) => fold(init, overflow, remarks),
+                Feature::Attribute(_) => {}
+                Feature::Method(_, _, _, body, _) => fold(body, overflow, remarks),
+            }
+        }
+    }
+}
+
+fn int(e: &TypedExpr) -> Option<i32> {
+    match e.expr {
+        Expr::Int(i) => Some(i),
+        _ => None,
+    }
+}
+
+/// Whether some rule below may rewrite `e`, which is then printed for its
+/// remark.
+fn foldable(e: &TypedExpr) -> bool {
+    match &e.expr {
+        Expr::Math { .. } | Expr::UnaryOperation { .. } | Expr::Comparison { .. } => true,
+        Expr::Dispatch { target: Some(_), id, .. } => id.as_str() == "concat",
+        _ => false,
+    }
+}
+
+/// Fold `e` and everything in it, with a remark for each rewrite.
+fn fold(e: &mut TypedExpr, overflow: Overflow, remarks: &mut Vec<Remark>) {
+    for child in e.expr.children_mut() {
+        fold(child, overflow, remarks);
+    }
+    let before = foldable(e).then(|| (print_expr(e), e.line, e.span));
+    rewrite(e, overflow);
+    if let Some((before, line, span)) = before {
+        let after = print_expr(e);
+        if after != before {
+            let message = format!("folded `{}` to `{}`", before, after);
+            remarks.push(Remark { pass: Pass::Fold, line, span, message });
+        }
+    }
+}
+
+/// Apply the first rule that matches `e` itself.
+fn rewrite(e: &mut TypedExpr, overflow: Overflow) {
+    // Put back unless an arm replaces the whole node with an operand
+    let expr = std::mem::replace(&mut e.expr, Expr::Builtin);
+    e.expr = match expr {
+        // Parentheses only group in the source, and are printed back where
+        // precedence needs them
+        Expr::Paren(inner) => {
+            *e = *inner;
+            return;
+        }
+        Expr::Math { lhs, op, rhs } => match (int(&lhs), &op, int(&rhs)) {
+            (Some(_), MathOperator::Div, Some(0)) => Expr::Math { lhs, op, rhs },
+            (Some(a), op, Some(b)) => match overflow.math(op, a, b) {
+                Some(value) => Expr::Int(value),
+                None => Expr::Math { lhs, op: op.clone(), rhs },
+            },
+            (_, MathOperator::Add | MathOperator::Subtract, Some(0)) | (_, MathOperator::Mul | MathOperator::Div, Some(1)) => {
+                *e = *lhs;
+                return;
+            }
+            (Some(0), MathOperator::Add, _) | (Some(1), MathOperator::Mul, _) => {
+                *e = *rhs;
+                return;
+            }
+            _ => Expr::Math { lhs, op, rhs },
+        },
+        Expr::UnaryOperation { op, s } => match (op, s.expr) {
+            (UnaryOperator::Neg, Expr::Int(i)) => match overflow.negate(i) {
+                Some(value) => Expr::Int(value),
+                None => Expr::UnaryOperation { op: UnaryOperator::Neg, s: Box::new(TypedExpr { expr: Expr::Int(i), ..*s }) },
+            },
+            (UnaryOperator::Not, Expr::Bool(b)) => Expr::Bool(!b),
+            // `~~x` and `not not x` are `x`
+            (UnaryOperator::Neg, Expr::UnaryOperation { op: UnaryOperator::Neg, s: inner })
+            | (UnaryOperator::Not, Expr::UnaryOperation { op: UnaryOperator::Not, s: inner }) => {
+                *e = *inner;
+                return;
+            }
+            (op, expr) => Expr::UnaryOperation { op, s: Box::new(TypedExpr { expr, ..*s }) },
+        },
+        Expr::Comparison { lhs, op, rhs } => match (&lhs.expr, &op, &rhs.expr) {
+            (Expr::Int(a), ComparisonOperator::Lt, Expr::Int(b)) => Expr::Bool(a < b),
+            (Expr::Int(a), ComparisonOperator::Le, Expr::Int(b)) => Expr::Bool(a <= b),
+            (Expr::Int(a), ComparisonOperator::Equal, Expr::Int(b)) => Expr::Bool(a == b),
+            (Expr::Bool(a), ComparisonOperator::Equal, Expr::Bool(b)) => Expr::Bool(a == b),
+            (Expr::Str(a), ComparisonOperator::Equal, Expr::Str(b)) => Expr::Bool(a == b),
+            _ => Expr::Comparison { lhs, op, rhs },
+        },
+        // No class inherits from String, so this is String.concat
+        Expr::Dispatch { target: Some(target), targettype: None | Some(sym::STRING), id, exprs }
+            if id.as_str() == "concat" =>
+        {
+            match (&target.expr, exprs.as_slice()) {
+                (Expr::Str(a), [TypedExpr { expr: Expr::Str(b), .. }]) => Expr::Str(format!("{}{}", a, b)),
+                _ => Expr::Dispatch { target: Some(target), targettype: None, id, exprs },
+            }
+        }
+        expr => expr,
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::semantic::context::AnalysisContext;
+    use crate::semantic::type_checker::annotate;
+
+    /// The body of `Main.main` for `body`, folded and printed back.
+    fn folded(body: &str) -> String {
+        folded_overflowing(body, Overflow::Wrap)
+    }
+
+    fn folded_overflowing(body: &str, overflow: Overflow) -> String {
+        let source = format!("class Main {{ x : Int; b : Bool; main() : Object {{ {} }}; }};", body);
+        let classes = crate::parse_program(&source).unwrap();
+        let ctx = AnalysisContext::new(crate::prelude(), &classes);
+        let mut typed = annotate(&ctx);
+        run(&mut typed, overflow, &mut Vec::new());
+        let Feature::Method(_, _, _, body, _) = &typed[0].feature_list[2] else { unreachable!() };
+        crate::pretty::print_program(&[Class::new(sym::MAIN_CLASS, None, vec![Feature::new_method(
+            sym::MAIN_METHOD,
+            Vec::new(),
+            sym::OBJECT,
+            body.clone(),
+        )])])
+    }
+
+    #[test]
+    fn test_fold() {
+        let cases = [
+            ("(1 + 2) * x", "3 * x"),
+            ("2147483647 + 1", "-2147483648"),
+            ("7 / 2 - ~1", "4"),
+            ("1 / 0", "1 / 0"),
+            ("x * 1 + 0", "x"),
+            ("0 + x / 1", "x"),
+            ("x * 0", "x * 0"),
+            ("not (not b)", "b"),
+            ("~(~(x))", "x"),
+            ("not (1 < 2)", "false"),
+            ("2 <= 1 = false", "true"),
+            ("\"a\".concat(\"b\").concat(\"c\") = \"abc\"", "true"),
+            ("\"a\" = \"b\"", "false"),
+        ];
+        for (body, expected) in cases {
+            let printed = folded(body);
+            assert!(printed.contains(&format!("main() : Object {{\n        {}\n    }};", expected)), "{}: {}", body, printed);
+        }
+    }
+
+    #[test]
+    fn test_fold_trapping() {
+        let cases = [("2147483647 + 1", "2147483647 + 1"), ("(2147483647 + 1) * (2 + 3)", "(2147483647 + 1) * 5"), ("2 * 3", "6")];
+        for (body, expected) in cases {
+            let printed = folded_overflowing(body, Overflow::Trap);
+            assert!(printed.contains(&format!("main() : Object {{\n        {}\n    }};", expected)), "{}: {}", body, printed);
+        }
+    }
+}
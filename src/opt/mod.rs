@@ -0,0 +1,181 @@
+//! Optimization passes over the typed AST (`--opt-level`).
+//!
+//! The passes rewrite the classes annotated by the type checker, after the
+//! semantic checks and before the VM or a backend sees them, keeping every
+//! static type and line number valid. The interpreter runs the program as
+//! written. Level 0, the default, runs no pass; each level runs the passes
+//! of the levels below it and its own:
+//!
+//! 1. `fold`: constant folding and algebraic simplification.
+//! 2. `dce`: dead-code elimination of branches and loop bodies that a
+//!    constant condition rules out, reported as warnings.
+//!
+//! Each rewrite is also a `Remark` saying what the pass did and where, which
+//! `--remarks` prints.
+
+mod dce;
+mod fold;
+
+use std::fmt;
+
+use serde::Serialize;
+
+use crate::arith::Overflow;
+use crate::ast::Class;
+use crate::diagnostic::render::{line_and_column, SourceFile};
+use crate::parsing::token::Span;
+use crate::semantic::collector::ErrorCollector;
+
+/// The highest level `--opt-level` accepts.
+pub const MAX_LEVEL: u8 = 2;
+
+/// One rewrite of the typed AST.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Pass {
+    /// Fold operations on constants, such as `1 + 2` and `"a".concat("b")`,
+    /// and drop identities such as `x * 1` and `not (not b)`
+    Fold,
+    /// Replace `if` with the branch its constant condition selects, and
+    /// drop the body of `while false`
+    DeadCode,
+}
+
+/// What a pass did to the expression at `line` and `span`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Remark {
+    pub pass: Pass,
+    pub line: usize,
+    pub span: Span,
+    pub message: String,
+}
+
+/// As `--remarks` names them
+impl fmt::Display for Pass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Pass::Fold => "fold",
+            Pass::DeadCode => "dead-code",
+        })
+    }
+}
+
+/// The passes run at `level`, in order.
+pub fn passes(level: u8) -> &'static [Pass] {
+    match level {
+        0 => &[],
+        1 => &[Pass::Fold],
+        _ => &[Pass::Fold, Pass::DeadCode],
+    }
+}
+
+/// Rewrite `classes`, the user classes as annotated by the type checker,
+/// with the passes of `level`, for a program run with `overflow`. Dead code
+/// is reported to `ec`. Returns a remark for every rewrite, in the order
+/// the passes made them.
+pub fn optimize(classes: &mut [Class], level: u8, overflow: Overflow, ec: &mut ErrorCollector) -> Vec<Remark> {
+    let mut remarks = Vec::new();
+    for pass in passes(level) {
+        match pass {
+            Pass::Fold => fold::run(classes, overflow, &mut remarks),
+            Pass::DeadCode => dce::run(classes, ec, &mut remarks),
+        }
+    }
+    remarks
+}
+
+impl Remark {
+    /// Where the remark is in `files`, the program's input files: the file's
+    /// name, and the line and column of the start of its span if there is one.
+    fn place<'a>(&self, files: &[SourceFile<'a>]) -> (Option<&'a str>, usize, Option<usize>) {
+        match files.get(self.span.file) {
+            Some(source) if !self.span.is_empty() && self.span.end <= source.text.len() => {
+                let (line, column) = line_and_column(source.text, self.span.start);
+                (Some(source.name), line, Some(column))
+            }
+            source => (source.map(|source| source.name), self.line, None),
+        }
+    }
+
+    /// The remark on one line, as `foo.cl:3:15: remark[fold]: message`.
+    pub fn human(&self, files: &[SourceFile<'_>]) -> String {
+        let place = match self.place(files) {
+            (Some(name), line, Some(column)) => format!("{}:{}:{}: ", name, line, column),
+            (Some(name), line, None) => format!("{}:{}: ", name, line),
+            (None, line, _) => format!("line {}: ", line),
+        };
+        format!("{}remark[{}]: {}\n", place, self.pass, self.message)
+    }
+
+    /// The remark as one JSON object on a line, with the `pass`, `message`,
+    /// `file`, `line` and `column` (null when not known).
+    pub fn json(&self, files: &[SourceFile<'_>]) -> String {
+        let (file, line, column) = self.place(files);
+        let value = serde_json::json!({
+            "pass": self.pass,
+            "message": self.message,
+            "file": file,
+            "line": line,
+            "column": column,
+        });
+        format!("{}\n", value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::Path;
+
+    use super::*;
+    use crate::semantic::context::AnalysisContext;
+    use crate::semantic::type_checker::annotate;
+
+    /// Optimized, the examples print on the VM what the interpreter prints
+    /// for them as written.
+    #[test]
+    fn test_examples() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("examples");
+        for entry in fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path.extension().is_none_or(|ext| ext != "cl") {
+                continue;
+            }
+            let source = fs::read_to_string(&path).unwrap();
+            let classes = crate::parse_program(&source).unwrap();
+            let ctx = AnalysisContext::new(crate::prelude(), &classes);
+            let mut typed = annotate(&ctx);
+            optimize(&mut typed, MAX_LEVEL, Overflow::Wrap, &mut ErrorCollector::default());
+
+            let mut output = Vec::new();
+            let module = crate::vm::compile(&ctx, &typed).unwrap();
+            let result = crate::vm::run(&module, Overflow::Wrap, &mut "12\n3\n".as_bytes(), &mut output);
+            let mut expected = Vec::new();
+            let interpreted = crate::interp::run(&ctx, Overflow::Wrap, &mut "12\n3\n".as_bytes(), &mut expected);
+            assert_eq!((result, output), (interpreted, expected), "{}", path.display());
+        }
+    }
+
+    #[test]
+    fn test_remarks() {
+        let text = "class Main {\n    main() : Int { if 1 < 2 then 3 * 4 else 0 fi };\n};";
+        let classes = crate::parse_program(text).unwrap();
+        let ctx = AnalysisContext::new(crate::prelude(), &classes);
+        let mut typed = annotate(&ctx);
+        let remarks = optimize(&mut typed, MAX_LEVEL, Overflow::Wrap, &mut ErrorCollector::default());
+        let files = [SourceFile { name: "t.cl", text }];
+        let human: String = remarks.iter().map(|remark| remark.human(&files)).collect();
+        assert_eq!(
+            human,
+            "t.cl:2:23: remark[fold]: folded `1 < 2` to `true`\n\
+             t.cl:2:34: remark[fold]: folded `3 * 4` to `12`\n\
+             t.cl:2:45: remark[dead-code]: eliminated the dead `else` branch of `if true`\n"
+        );
+        assert_eq!(
+            remarks[2].json(&files),
+            "{\"column\":45,\"file\":\"t.cl\",\"line\":2,\"message\":\"eliminated the dead `else` branch of `if true`\",\"pass\":\"dead-code\"}\n"
+        );
+        // Nothing is done, and nothing remarked, at level 0
+        assert!(optimize(&mut annotate(&ctx), 0, Overflow::Wrap, &mut ErrorCollector::default()).is_empty());
+    }
+}
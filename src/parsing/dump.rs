@@ -0,0 +1,321 @@
+use std::fmt::Write;
+
+use eyre::{eyre, Result};
+
+use super::recovery::Spanned;
+use super::scanner::Scanner;
+use super::token::{LexicalError, Position, Span, Token};
+use crate::ext::Extensions;
+use crate::symbol::Symbol;
+
+/// A token stream dumped by `coolc_tokens`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Lexed {
+    /// One `#<line> <TOKEN> [value]` entry per line
+    pub dump: String,
+    /// Entries that are tokens
+    pub tokens: usize,
+    /// Entries that are `ERROR`s
+    pub errors: usize,
+}
+
+/// Token stream of `source`, scanned with `extensions`, in the reference
+/// `lexer` format.
+///
+/// As in the reference lexer, a lexical error is an `ERROR` entry on the
+/// line where it was found, with the reference's message, and lexing goes
+/// on after it. A `*)` outside a comment is the error `Unmatched *)` rather
+/// than two tokens.
+pub fn coolc_tokens(source: &str, extensions: &Extensions) -> Lexed {
+    let mut scanner = Scanner::with_extensions(source, extensions.clone());
+    // Line, entry, whether it is an error, and where it was in the source
+    let mut entries: Vec<(usize, String, bool, Span)> = Vec::new();
+    loop {
+        let entry = match scanner.next_spanned_token() {
+            Ok(None) => break,
+            Ok(Some((Token::Rparen, _, span)))
+                if matches!(entries.last(), Some((_, star, _, at)) if star == "'*'" && at.end == span.start) =>
+            {
+                let (line, ..) = entries.pop().unwrap();
+                (line, format!("ERROR {}", escape_str("Unmatched *)")), true, span)
+            }
+            Ok(Some((token, loc, span))) => (loc.line, coolc_token(&token), matches!(token, Token::Error(_)), span),
+            Err(err) => (scanner.line(), format!("ERROR {}", escape_str(&coolc_error(&err))), true, Span::default()),
+        };
+        entries.push(entry);
+    }
+    let mut lexed = Lexed { dump: String::new(), tokens: 0, errors: 0 };
+    for (line, entry, error, _) in entries {
+        writeln!(lexed.dump, "#{} {}", line, entry).unwrap();
+        if error {
+            lexed.errors += 1;
+        } else {
+            lexed.tokens += 1;
+        }
+    }
+    lexed
+}
+
+/// The message the reference lexer gives for `err`; errors it does not have
+/// keep their own.
+fn coolc_error(err: &LexicalError) -> String {
+    match err {
+        LexicalError::InvalidChar(c, _) => c.to_string(),
+        LexicalError::UnterminatedString(_) => "EOF in string constant".to_string(),
+        LexicalError::UnterminatedComment(_) => "EOF in comment".to_string(),
+        LexicalError::NewlineInString(_) | LexicalError::NullInString(_) | LexicalError::StringTooLong(_) => {
+            err.message()
+        }
+        LexicalError::InvalidNumber(..) | LexicalError::InvalidEscape(..) | LexicalError::NestedTooDeeply(_) => err.message(),
+    }
+}
+
+/// Token name as printed by the reference lexer, followed by its value if it has one.
+fn coolc_token(token: &Token) -> String {
+    match token {
+        Token::Class_ => "CLASS".to_string(),
+        Token::Else => "ELSE".to_string(),
+        Token::Fi => "FI".to_string(),
+        Token::If => "IF".to_string(),
+        Token::In => "IN".to_string(),
+        Token::Inherits => "INHERITS".to_string(),
+        Token::Let => "LET".to_string(),
+        Token::Loop => "LOOP".to_string(),
+        Token::Pool => "POOL".to_string(),
+        Token::Then => "THEN".to_string(),
+        Token::While => "WHILE".to_string(),
+        Token::Case => "CASE".to_string(),
+        Token::Esac => "ESAC".to_string(),
+        Token::Of => "OF".to_string(),
+        Token::New => "NEW".to_string(),
+        Token::Isvoid => "ISVOID".to_string(),
+        Token::Not => "NOT".to_string(),
+        Token::Try => "TRY".to_string(),
+        Token::Catch => "CATCH".to_string(),
+        Token::Throw => "THROW".to_string(),
+        Token::End => "END".to_string(),
+        Token::Import => "IMPORT".to_string(),
+        Token::Native => "NATIVE".to_string(),
+        Token::StrConst(s) => format!("STR_CONST {}", escape_str(s)),
+        Token::IntConst(s) => format!("INT_CONST {}", s),
+        Token::BoolConst(b) => format!("BOOL_CONST {}", b),
+        Token::Typeid(s) => format!("TYPEID {}", s),
+        Token::Objectid(s) => format!("OBJECTID {}", s),
+        Token::Darrow => "DARROW".to_string(),
+        Token::Assign => "ASSIGN".to_string(),
+        Token::Le => "LE".to_string(),
+        Token::Error(s) => format!("ERROR {}", escape_str(s)),
+        // Single-character tokens print as the quoted character
+        other => format!("'{}'", other),
+    }
+}
+
+/// A token dump read back by `read_coolc_tokens`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TokenDump {
+    /// The file named by a `#name` line, if there is one
+    pub name: Option<String>,
+    /// Each token's text on its line, for diagnostics to point into
+    pub text: String,
+    /// The tokens in the parser's form, spanning their text
+    pub tokens: Vec<Spanned>,
+}
+
+/// Read a token dump in the reference `lexer` format, as `coolc_tokens`
+/// writes it, for `--tokens-in`. The last `#name` line names the file. A
+/// keyword the `extensions` do not enable is an error, as is anything that
+/// is not an entry.
+pub fn read_coolc_tokens(dump: &str, extensions: &Extensions) -> Result<TokenDump> {
+    let mut out = TokenDump { name: None, text: String::new(), tokens: Vec::new() };
+    let mut current = 1;
+    for (i, entry) in dump.lines().enumerate() {
+        let bad = |what: &str| eyre!("line {}: {}: {}", i + 1, what, entry);
+        if let Some(name) = entry.strip_prefix("#name ") {
+            out.name = Some(unescape_str(name).ok_or_else(|| bad("not a string"))?);
+            continue;
+        }
+        if entry.trim().is_empty() {
+            continue;
+        }
+        let (line, rest) = entry
+            .strip_prefix('#')
+            .and_then(|entry| entry.split_once(' '))
+            .ok_or_else(|| bad("not a token entry"))?;
+        let line: usize = line.parse().map_err(|_| bad("not a line number"))?;
+        let (token, text) = read_entry(rest, extensions).ok_or_else(|| bad("unknown token"))?;
+
+        while current < line {
+            out.text.push('\n');
+            current += 1;
+        }
+        if !out.text.is_empty() && !out.text.ends_with('\n') {
+            out.text.push(' ');
+        }
+        let start = Position { line, offset: out.text.len() };
+        out.text.push_str(&text);
+        out.tokens.push((start, token, Position { line, offset: out.text.len() }));
+    }
+    Ok(out)
+}
+
+/// The token of one entry after its line number, and the text it stands
+/// for.
+fn read_entry(entry: &str, extensions: &Extensions) -> Option<(Token, String)> {
+    let (name, value) = entry.split_once(' ').unwrap_or((entry, ""));
+    let token = match name {
+        "STR_CONST" => Token::StrConst(unescape_str(value)?),
+        "ERROR" => Token::Error(unescape_str(value)?),
+        "INT_CONST" if !value.is_empty() && value.bytes().all(|b| b.is_ascii_digit()) => {
+            Token::IntConst(value.to_string())
+        }
+        "BOOL_CONST" if value == "true" || value == "false" => Token::BoolConst(value == "true"),
+        "TYPEID" if value.starts_with(|c: char| c.is_ascii_uppercase()) => Token::Typeid(Symbol::intern(value)),
+        "OBJECTID" if value.starts_with(|c: char| c.is_ascii_lowercase()) => Token::Objectid(Symbol::intern(value)),
+        _ => {
+            let text = match entry {
+                "DARROW" => "=>".to_string(),
+                "ASSIGN" => "<-".to_string(),
+                "LE" => "<=".to_string(),
+                _ => match entry.strip_prefix('\'').and_then(|entry| entry.strip_suffix('\'')) {
+                    Some(c) => c.to_string(),
+                    None => entry.to_lowercase(),
+                },
+            };
+            // A keyword or operator is whatever the scanner makes of its
+            // text, if that is dumped as this entry
+            let tokens = Scanner::with_extensions(&text, extensions.clone()).scan_tokens().ok()?;
+            return match tokens.as_slice() {
+                [(token, _)] if coolc_token(token) == entry => Some((token.clone(), text)),
+                _ => None,
+            };
+        }
+    };
+    Some((token, value.to_string()))
+}
+
+/// The string quoted by `escape_str`, or `None` if `s` is not quoted.
+fn unescape_str(s: &str) -> Option<String> {
+    let mut chars = s.strip_prefix('"')?.strip_suffix('"')?.chars();
+    let mut out = String::new();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        out.push(match chars.next()? {
+            'n' => '\n',
+            't' => '\t',
+            'b' => '\u{8}',
+            'f' => '\u{c}',
+            d @ '0'..='7' => {
+                let digits: String = std::iter::once(d).chain(chars.by_ref().take(2)).collect();
+                char::from_u32(u32::from_str_radix(&digits, 8).ok()?)?
+            }
+            c => c,
+        });
+    }
+    Some(out)
+}
+
+/// Quote `s` the way the reference lexer prints string values.
+pub fn escape_str(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            '\u{8}' => out.push_str("\\b"),
+            '\u{c}' => out.push_str("\\f"),
+            '\\' => out.push_str("\\\\"),
+            '"' => out.push_str("\\\""),
+            c if (c as u32) < 0x20 || c as u32 == 0x7f => {
+                write!(out, "\\{:03o}", c as u32).unwrap();
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ext::Extension;
+
+    #[test]
+    fn test_coolc_tokens() {
+        let lexed = coolc_tokens("class Main {\n  s : String <- \"a\\tb\n\";\n};", &Extensions::default());
+        let expected = "\
+#1 CLASS
+#1 TYPEID Main
+#1 '{'
+#2 OBJECTID s
+#2 ':'
+#2 TYPEID String
+#2 ASSIGN
+#3 ERROR \"Unterminated string constant\"
+#4 ERROR \"Unterminated string constant\"
+#4 '}'
+#4 ';'
+";
+        assert_eq!(lexed.dump, expected);
+        assert_eq!((lexed.tokens, lexed.errors), (9, 2));
+    }
+
+    #[test]
+    fn test_coolc_errors() {
+        let lexed = coolc_tokens("x # y\n\"a\0b\" 2 * 3 *)\n\"open\nz (* open\n\n", &Extensions::default());
+        let expected = "\
+#1 OBJECTID x
+#1 ERROR \"#\"
+#1 OBJECTID y
+#2 ERROR \"String contains null character\"
+#2 INT_CONST 2
+#2 '*'
+#2 INT_CONST 3
+#2 ERROR \"Unmatched *)\"
+#4 ERROR \"Unterminated string constant\"
+#4 OBJECTID z
+#6 ERROR \"EOF in comment\"
+";
+        assert_eq!(lexed.dump, expected);
+        assert_eq!((lexed.tokens, lexed.errors), (6, 5));
+    }
+
+    #[test]
+    fn test_coolc_tokens_extensions() {
+        let source = "try \"x = \\(x)\" catch e : Int => 0 end";
+        let extensions: Extensions = [Extension::Strings, Extension::Exceptions].into_iter().collect();
+        let plain = coolc_tokens(source, &Extensions::default());
+        assert!(plain.dump.starts_with("#1 OBJECTID try\n"), "{}", plain.dump);
+        let extended = coolc_tokens(source, &extensions);
+        assert!(extended.dump.starts_with("#1 TRY\n"), "{}", extended.dump);
+        assert!(extended.dump.contains("#1 STR_CONST \"x = \"\n#1 '.'\n#1 OBJECTID concat\n"), "{}", extended.dump);
+        assert!(extended.dump.ends_with("#1 END\n"), "{}", extended.dump);
+        assert_eq!(extended.errors, 0, "{}", extended.dump);
+        // The dump reads back with the same extensions
+        let read = read_coolc_tokens(&extended.dump, &extensions).unwrap();
+        assert_eq!(read.tokens.len(), extended.tokens);
+    }
+
+    #[test]
+    fn test_read_coolc_tokens() {
+        let source = "class Main {\n  s : String <- \"a\\tb\\\"\u{1}\";\n\n  f(x : Int) : Bool { x <= 0 };\n};";
+        let read = read_coolc_tokens(&coolc_tokens(source, &Extensions::default()).dump, &Extensions::default()).unwrap();
+        let scanned: Vec<Token> = Scanner::new(source).map(|token| token.unwrap().0).collect();
+        let tokens: Vec<Token> = read.tokens.iter().map(|(_, token, _)| token.clone()).collect();
+        assert_eq!(tokens, scanned);
+        assert_eq!(
+            read.text,
+            "class Main {\ns : String <- \"a\\tb\\\"\\001\" ;\n\nf ( x : Int ) : Bool { x <= 0 } ;\n} ;"
+        );
+        let (start, token, end) = &read.tokens[9];
+        assert_eq!((start.line, token, &read.text[start.offset..end.offset]), (4, &Token::Objectid(Symbol::intern("f")), "f"));
+
+        for bad in ["CLASS", "#x CLASS", "#1 TRY", "#1 TYPEID main", "#1 STR_CONST \"a"] {
+            assert!(read_coolc_tokens(bad, &Extensions::default()).is_err(), "{}", bad);
+        }
+    }
+}
@@ -1,2 +1,5 @@
+pub mod dump;
+pub mod nesting;
+pub mod recovery;
 pub mod scanner;
-pub mod token;
\ No newline at end of file
+pub mod token;
@@ -0,0 +1,80 @@
+//! A limit on how deeply the tokens of a file nest, checked on their way
+//! into the parser.
+//!
+//! The parser itself keeps its stack on the heap, but the tree it builds is
+//! dropped, printed and checked by recursion, so a file that is nothing but
+//! tens of thousands of `(` would overflow the stack before any later phase
+//! could reject it. Brackets (`(`, `{`, `if`…`fi`, `while`…`pool`,
+//! `case`…`esac`, `try`…`end`) count one level each while they are open, and
+//! so does each prefix operator in a run of them (`not not x`).
+
+use super::recovery::Spanned;
+use super::token::{LexicalError, Loc, Token};
+
+/// Levels of nesting the parser is fed before the file is rejected.
+pub const MAX_NESTING: usize = 10_000;
+
+/// `tokens`, cut short by a `NestedTooDeeply` error at the first token that
+/// opens a level past `MAX_NESTING`. `source` is the text the tokens'
+/// offsets point into.
+pub fn limit_nesting<'a>(
+    source: &'a str,
+    tokens: impl IntoIterator<Item = Result<Spanned, LexicalError>> + 'a,
+) -> impl Iterator<Item = Result<Spanned, LexicalError>> + 'a {
+    let mut brackets = 0usize;
+    let mut prefixes = 0usize;
+    let mut failed = false;
+    tokens.into_iter().map_while(move |token| {
+        if failed {
+            return None;
+        }
+        let Ok((start, kind, _)) = &token else {
+            return Some(token);
+        };
+        match kind {
+            Token::Neg | Token::Not | Token::Isvoid => prefixes += 1,
+            Token::Lparen | Token::Lbrace | Token::If | Token::While | Token::Case | Token::Try => {
+                brackets += 1;
+                prefixes = 0;
+            }
+            Token::Rparen | Token::Rbrace | Token::Fi | Token::Pool | Token::Esac | Token::End => {
+                brackets = brackets.saturating_sub(1);
+                prefixes = 0;
+            }
+            _ => prefixes = 0,
+        }
+        if brackets + prefixes > MAX_NESTING {
+            failed = true;
+            let line_start = source[..start.offset].rfind('\n').map_or(0, |i| i + 1);
+            let loc = Loc { line: start.line, column: start.offset - line_start + 1 };
+            return Some(Err(LexicalError::NestedTooDeeply(loc)));
+        }
+        Some(token)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parsing::recovery::spanned;
+    use crate::parsing::scanner::Scanner;
+
+    fn first_error(source: &str) -> Option<LexicalError> {
+        let tokens = Scanner::new(source).spanned().map(|token| token.map(spanned));
+        limit_nesting(source, tokens).find_map(Result::err)
+    }
+
+    #[test]
+    fn test_limit_nesting() {
+        let parens = |n: usize| format!("x\n  {}1{}", "(".repeat(n), ")".repeat(n));
+        assert_eq!(first_error(&parens(MAX_NESTING)), None);
+        let column = MAX_NESTING + 3;
+        assert_eq!(first_error(&parens(MAX_NESTING + 1)), Some(LexicalError::NestedTooDeeply(Loc { line: 2, column })));
+
+        // Levels that are closed again do not add up
+        assert_eq!(first_error(&"(1) ".repeat(MAX_NESTING + 1)), None);
+        assert_eq!(first_error(&"~1 + ".repeat(MAX_NESTING + 1)), None);
+        assert!(first_error(&format!("{}x", "not ".repeat(MAX_NESTING + 1))).is_some());
+        assert!(first_error(&format!("{}1", "~(".repeat(MAX_NESTING + 1))).is_some());
+    }
+}
@@ -0,0 +1,228 @@
+//! Panic-mode recovery, so one run reports every syntax error in a file.
+//!
+//! The generated parser stops at the first error. When a file fails to
+//! parse, its tokens are cut at class boundaries (`class` only ever starts a
+//! class) and each class is parsed on its own. A class that still fails is
+//! cut again at its features, each ending at a `;` outside any brackets, and
+//! each feature and the class header are parsed separately. A mistake is
+//! reported once, by the smallest piece that contains it, instead of
+//! cascading into the rest of the file.
+
+use std::fmt;
+
+use lalrpop_util::ParseError;
+
+use super::nesting::limit_nesting;
+use super::scanner::Scanner;
+use super::token::{LexicalError, Loc, Position, Span, Token};
+use crate::cool::{ClassTyParser, FeatureTyParser, ProgramTyParser};
+use crate::ext::Extensions;
+
+pub type SyntaxError = ParseError<Position, Token, LexicalError>;
+
+/// A token as the parser sees it: start, token, end.
+pub type Spanned = (Position, Token, Position);
+
+/// A scanned token in the parser's form.
+pub fn spanned((token, loc, span): (Token, Loc, Span)) -> Spanned {
+    let start = Position { line: loc.line, offset: span.start };
+    (start, token, Position { line: loc.line, offset: span.end })
+}
+
+/// Every syntax error found in one file, in source order.
+#[derive(Debug)]
+pub struct SyntaxErrors(pub Vec<SyntaxError>);
+
+impl fmt::Display for SyntaxErrors {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, err) in self.0.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{}", err)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for SyntaxErrors {}
+
+/// All syntax errors in `source`, given that parsing it as a whole stopped
+/// at `first`.
+pub fn syntax_errors(source: &str, extensions: &Extensions, first: SyntaxError) -> Vec<SyntaxError> {
+    // The scanner cannot go past a lexical error, and nothing before it
+    // failed to parse
+    if matches!(first, ParseError::User { .. }) {
+        return vec![first];
+    }
+
+    let mut tokens = Vec::new();
+    let mut lexical = None;
+    let scanned = Scanner::with_extensions(source, extensions.clone()).spanned().map(|token| token.map(spanned));
+    for result in limit_nesting(source, scanned) {
+        match result {
+            Ok(token) => tokens.push(token),
+            Err(err) => {
+                lexical = Some(err);
+                break;
+            }
+        }
+    }
+
+    let mut errors = Vec::new();
+    let starts: Vec<usize> = (0..tokens.len()).filter(|&i| tokens[i].1 == Token::Class_).collect();
+    let imports = &tokens[..starts.first().copied().unwrap_or(tokens.len())];
+    if !imports.is_empty() {
+        let next = tokens.get(imports.len());
+        errors.extend(parse_piece(imports, next, |t| ProgramTyParser::new().parse(t)).err());
+    }
+    for (i, &start) in starts.iter().enumerate() {
+        let end = starts.get(i + 1).copied().unwrap_or(tokens.len());
+        class_errors(&tokens[start..end], tokens.get(end), &mut errors);
+    }
+    if let Some(err) = lexical {
+        // The tokens were cut short by the lexical error, not by a mistake
+        errors.retain(|err| !matches!(err, ParseError::UnrecognizedEof { .. }));
+        errors.push(ParseError::User { error: err });
+    }
+
+    if !errors.iter().any(|err| same_place(err, &first)) {
+        errors.insert(0, first);
+    }
+    // A piece parsed on its own may expect other tokens than the whole
+    // file did at the same place; the first report wins
+    errors.sort_by_key(line);
+    errors.dedup_by(|later, earlier| same_place(later, earlier));
+    errors
+}
+
+/// Errors in one class, `tokens` from its `class` keyword up to the next one.
+fn class_errors(tokens: &[Spanned], next: Option<&Spanned>, errors: &mut Vec<SyntaxError>) {
+    let Err(err) = parse_piece(tokens, next, |t| ClassTyParser::new().parse(t)) else {
+        return;
+    };
+    let Some(open) = tokens.iter().position(|(_, t, _)| *t == Token::Lbrace) else {
+        errors.push(err);
+        return;
+    };
+
+    // Split the body into features, up to the `}` that closes it
+    let mut features = Vec::new();
+    let mut start = open + 1;
+    let mut depth = 0usize;
+    let mut close = tokens.len();
+    for (i, (_, token, _)) in tokens.iter().enumerate().skip(open + 1) {
+        match token {
+            Token::Lbrace | Token::Lparen | Token::Case => depth += 1,
+            Token::Rbrace if depth == 0 => {
+                close = i;
+                break;
+            }
+            Token::Rbrace | Token::Rparen | Token::Esac => depth = depth.saturating_sub(1),
+            Token::Semicolon if depth == 0 => {
+                features.push(start..i + 1);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    if start < close {
+        features.push(start..close);
+    }
+
+    // The header with an empty body stands in for the class itself
+    let at = tokens[open].2;
+    let mut shell = tokens[..=open].to_vec();
+    shell.extend([(at, Token::Rbrace, at), (at, Token::Semicolon, at)]);
+    let header = parse_piece(&shell, None, |t| ClassTyParser::new().parse(t)).err();
+    let checks_tail = header.is_none();
+    errors.extend(header);
+
+    for range in features {
+        let next = tokens.get(range.end).or(next);
+        errors.extend(parse_piece(&tokens[range], next, |t| FeatureTyParser::new().parse(t)).err());
+    }
+
+    if checks_tail {
+        let mut shell = tokens[..=open].to_vec();
+        shell.extend_from_slice(&tokens[close.min(tokens.len())..]);
+        errors.extend(parse_piece(&shell, next, |t| ClassTyParser::new().parse(t)).err());
+    }
+}
+
+/// Parse one piece of the token stream. Running out of tokens is blamed on
+/// `next`, the token that follows the piece in the file, as the parser
+/// would have done with the whole file.
+fn parse_piece<T>(
+    tokens: &[Spanned],
+    next: Option<&Spanned>,
+    parse: impl FnOnce(Vec<Spanned>) -> Result<T, SyntaxError>,
+) -> Result<T, SyntaxError> {
+    parse(tokens.to_vec()).map_err(|err| match (err, next) {
+        (ParseError::UnrecognizedEof { expected, .. }, Some(token)) => {
+            ParseError::UnrecognizedToken { token: token.clone(), expected }
+        }
+        (err, _) => err,
+    })
+}
+
+/// `a` and `b` are the same mistake, whatever tokens they expected instead.
+fn same_place(a: &SyntaxError, b: &SyntaxError) -> bool {
+    match (a, b) {
+        (ParseError::UnrecognizedToken { token: x, .. }, ParseError::UnrecognizedToken { token: y, .. }) => x == y,
+        (ParseError::UnrecognizedEof { location: x, .. }, ParseError::UnrecognizedEof { location: y, .. }) => x == y,
+        (a, b) => a == b,
+    }
+}
+
+fn line(err: &SyntaxError) -> usize {
+    match err {
+        ParseError::InvalidToken { location } | ParseError::UnrecognizedEof { location, .. } => location.line,
+        ParseError::UnrecognizedToken { token: (start, ..), .. } | ParseError::ExtraToken { token: (start, ..) } => start.line,
+        ParseError::User { error } => error.loc().line,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn errors(source: &str) -> Vec<String> {
+        let err = crate::parse_program(source).unwrap_err();
+        let errors = err.downcast::<SyntaxErrors>().unwrap().0;
+        errors.iter().map(|e| e.to_string().lines().next().unwrap().to_string()).collect()
+    }
+
+    #[test]
+    fn test_recovers_at_features_and_classes() {
+        let source = "class Main {\n\
+                          a : Int <- ;\n\
+                          f() : Int { { 1; 2 +; 3; } };\n\
+                          ok() : Int { case 1 of x : Int => x; esac };\n\
+                          g() : Int { 0 }\n\
+                      };\n\
+                      class inherits IO { h() : Int { ( }; };\n\
+                      class Fine { };\n\
+                      class Unclosed { x : Int; ";
+        assert_eq!(
+            errors(source),
+            [
+                "Unrecognized token `;` found at 2:2",
+                "Unrecognized token `;` found at 3:3",
+                "Unrecognized token `}` found at 6:6",
+                "Unrecognized token `INHERITS` found at 7:7",
+                "Unrecognized token `}` found at 7:7",
+                "Unrecognized EOF found at 9",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lexical_error_ends_recovery() {
+        let source = "class A { a : Int <- ; };\nclass B { b : Int <- 1 # 2; };\nclass C { c : Int <- ; };";
+        assert_eq!(
+            errors(source),
+            ["Unrecognized token `;` found at 1:1", "Invalid character '#' at 2:24"]
+        );
+    }
+}
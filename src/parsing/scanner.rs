@@ -1,19 +1,59 @@
-use std::collections::HashMap;
-use super::token::{Token, LexicalError, Loc};
+use std::collections::{HashMap, VecDeque};
+use memchr::{memchr, memchr3};
+use super::token::{Token, LexicalError, Loc, Span};
+use crate::ext::{Extension, Extensions};
+use crate::symbol::Symbol;
 
-pub struct Scanner {
-    source: Vec<u8>,
-    tokens: Vec<(Token, Loc)>,
+/// Longest string constant the manual allows, in characters.
+pub const MAX_STRING_LENGTH: usize = 1024;
+
+/// The source around an interpolated expression `e` under `--ext strings`
+/// that gives it as a `String`: a `String` as it is, a `Bool` as `true` or
+/// `false`, an `Int` in decimal and any other object as its `type_name()`.
+/// The `Int` is taken to zero or below before its digits are produced, so
+/// the most negative one does not overflow. `e` is evaluated before
+/// `cool_value` is bound, so these names never capture a name in it.
+const TO_STRING_PREFIX: &str = "(let cool_value : Object <- (";
+const TO_STRING_SUFFIX: &str = r#") in case cool_value of
+    cool_s : String => cool_s;
+    cool_b : Bool => if cool_b then "true" else "false" fi;
+    cool_i : Int => let cool_n : Int <- if 0 < cool_i then ~cool_i else cool_i fi, cool_t : String in {
+        while not cool_n = 0 loop {
+            cool_t <- "0123456789".substr(~(cool_n - cool_n / 10 * 10), 1).concat(cool_t);
+            cool_n <- cool_n / 10;
+        } pool;
+        if cool_i < 0 then "-".concat(cool_t) else if cool_t = "" then "0" else cool_t fi fi;
+    };
+    cool_o : Object => cool_o.type_name();
+esac)"#;
+
+/// Streaming scanner over a borrowed source buffer.
+///
+/// Tokens are produced on demand (via `next_token` or the `Iterator` impl), so
+/// the parser can consume them without a full token vector ever being built.
+pub struct Scanner<'a> {
+    source: &'a [u8],
+    /// Tokens scanned but not yet handed out; an interpolated string
+    /// produces several at once
+    pending: VecDeque<(Token, Loc, Span)>,
+    extensions: Extensions,
     start: usize,
     current: usize,
     line: usize,
     column: usize,
     keywords: HashMap<&'static str, Token>,
+    /// The byte ranges of the comments skipped so far, with `--` comments
+    /// ending before their newline; `None` unless `keep_comments` was called
+    comments: Option<Vec<Span>>,
 }
 
-impl Scanner {
-    pub fn new(source: &str) -> Self {
-        let keywords = vec![
+impl<'a> Scanner<'a> {
+    pub fn new(source: &'a str) -> Self {
+        Self::with_extensions(source, Extensions::default())
+    }
+
+    pub fn with_extensions(source: &'a str, extensions: Extensions) -> Self {
+        let mut keywords: HashMap<_, _> = vec![
             ("class", Token::Class_),
             ("else", Token::Else),
             ("fi", Token::Fi),
@@ -36,24 +76,92 @@ impl Scanner {
         ]
         .into_iter()
         .collect();
+        // Outside the extension these stay ordinary identifiers
+        if extensions.contains(Extension::Exceptions) {
+            keywords.extend([
+                ("try", Token::Try),
+                ("catch", Token::Catch),
+                ("throw", Token::Throw),
+                ("end", Token::End),
+            ]);
+        }
+        if extensions.contains(Extension::Imports) {
+            keywords.insert("import", Token::Import);
+        }
+        if extensions.contains(Extension::Natives) {
+            keywords.insert("native", Token::Native);
+        }
 
         Scanner {
-            source: source.as_bytes().to_vec(),
-            tokens: Vec::new(),
+            source: source.as_bytes(),
+            pending: VecDeque::new(),
+            extensions,
             start: 0,
             current: 0,
             line: 1,
             column: 0,
             keywords,
+            comments: None,
         }
     }
 
-    pub fn scan_tokens(&mut self) -> Result<Vec<(Token, Loc)>, LexicalError> {
-        while !self.is_at_end() {
+    /// Record the comments skipped from now on, for `comments`.
+    pub fn keep_comments(mut self) -> Self {
+        self.comments = Some(Vec::new());
+        self
+    }
+
+    /// The comments skipped so far, in source order, if `keep_comments`
+    /// was called.
+    pub fn comments(&self) -> &[Span] {
+        self.comments.as_deref().unwrap_or_default()
+    }
+
+    /// The line the scanner has reached, e.g. the last line after an
+    /// unterminated comment.
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    fn record_comment(&mut self) {
+        let span = Span { file: 0, start: self.start, end: self.current };
+        if let Some(comments) = &mut self.comments {
+            comments.push(span);
+        }
+    }
+
+    /// Scan up to the next token, skipping whitespace and comments.
+    /// Returns `Ok(None)` once the input is exhausted.
+    pub fn next_token(&mut self) -> Result<Option<(Token, Loc)>, LexicalError> {
+        Ok(self.next_spanned_token()?.map(|(token, loc, _)| (token, loc)))
+    }
+
+    /// Like `next_token`, also giving the byte range of the token.
+    pub fn next_spanned_token(&mut self) -> Result<Option<(Token, Loc, Span)>, LexicalError> {
+        loop {
+            if let Some(token) = self.pending.pop_front() {
+                return Ok(Some(token));
+            }
+            self.skip_whitespace();
+            if self.is_at_end() {
+                return Ok(None);
+            }
             self.start = self.current;
             self.scan_token()?;
         }
-        Ok(self.tokens.clone())
+    }
+
+    /// The remaining tokens with their byte ranges, for the parser.
+    pub fn spanned(mut self) -> impl Iterator<Item = Result<(Token, Loc, Span), LexicalError>> + 'a {
+        std::iter::from_fn(move || self.next_spanned_token().transpose())
+    }
+
+    pub fn scan_tokens(&mut self) -> Result<Vec<(Token, Loc)>, LexicalError> {
+        let mut tokens = Vec::new();
+        while let Some(token) = self.next_token()? {
+            tokens.push(token);
+        }
+        Ok(tokens)
     }
 
     fn scan_token(&mut self) -> Result<(), LexicalError> {
@@ -63,77 +171,278 @@ impl Scanner {
             column: self.column,
         };
 
-        match c {
-            ';' => Ok(self.add_token(Token::Semicolon, loc)),
-            ':' => Ok(self.add_token(Token::Colon, loc)),
-            '{' => Ok(self.add_token(Token::Lbrace, loc)),
-            '}' => Ok(self.add_token(Token::Rbrace, loc)),
-            '(' => Ok(self.add_token(Token::Lparen, loc)),
-            ')' => Ok(self.add_token(Token::Rparen, loc)),
-            ',' => Ok(self.add_token(Token::Comma, loc)),
-            '.' => Ok(self.add_token(Token::Period, loc)),
-            '@' => Ok(self.add_token(Token::At, loc)),
-            '~' => Ok(self.add_token(Token::Neg, loc)),
-            '=' => {
-                if self.match_next('>') {
-                    Ok(self.add_token(Token::Darrow, loc))
-                } else {
-                    Ok(self.add_token(Token::Equal, loc))
-                }
+        let token = match c {
+            ';' => Token::Semicolon,
+            ':' => Token::Colon,
+            '{' => Token::Lbrace,
+            '}' => Token::Rbrace,
+            '(' if self.match_next('*') => {
+                self.skip_block_comment(loc)?;
+                self.record_comment();
+                return Ok(());
             }
-            '+' => Ok(self.add_token(Token::Plus, loc)),
-            '*' => Ok(self.add_token(Token::Mul, loc)),
-            '/' => Ok(self.add_token(Token::Divide, loc)),
-            '<' => {
-                if self.match_next('=') {
-                    Ok(self.add_token(Token::Le, loc))
-                } else if self.match_next('-') {
-                    Ok(self.add_token(Token::Assign, loc))
-                } else {
-                    Ok(self.add_token(Token::Lt, loc))
-                }
+            '(' => Token::Lparen,
+            ')' => Token::Rparen,
+            ',' => Token::Comma,
+            '.' => Token::Period,
+            '@' => Token::At,
+            '~' => Token::Neg,
+            '=' if self.match_next('>') => Token::Darrow,
+            '=' => Token::Equal,
+            '+' => Token::Plus,
+            '*' => Token::Mul,
+            '/' => Token::Divide,
+            '<' if self.match_next('=') => Token::Le,
+            '<' if self.match_next('-') => Token::Assign,
+            '<' => Token::Lt,
+            '"' if self.extensions.contains(Extension::Strings) => return self.handle_extended_string(loc),
+            '"' => return self.handle_string(loc),
+            '-' if self.match_next('-') => {
+                self.skip_line_comment();
+                self.record_comment();
+                return Ok(());
             }
-            '"' => self.handle_string(loc),
-            ' ' | '\r' | '\t' => Ok(()),
-            '\n' => {
-                self.line += 1;
-                self.column = 0;
-                Ok(())
+            '-' => Token::Minus,
+            c if c.is_ascii_digit() => return self.handle_number(loc),
+            c if is_alpha(c) => return self.handle_identifier(loc),
+            _ => return Err(LexicalError::InvalidChar(self.whole_char(), loc)),
+        };
+        self.add_token(token, loc);
+        Ok(())
+    }
+
+    /// The character that starts the token, with the rest of its bytes
+    /// consumed: the scanner steps through the UTF-8 source a byte at a time.
+    fn whole_char(&mut self) -> char {
+        let end = (self.start + 4).min(self.source.len());
+        let c = String::from_utf8_lossy(&self.source[self.start..end]).chars().next().unwrap();
+        self.current = self.start + c.len_utf8();
+        c
+    }
+
+    /// Skip a run of blanks and newlines, keeping line and column in step.
+    fn skip_whitespace(&mut self) {
+        while let Some(&c) = self.source.get(self.current) {
+            match c {
+                b' ' | b'\r' | b'\t' => self.column += 1,
+                b'\n' => {
+                    self.line += 1;
+                    self.column = 0;
+                }
+                _ => break,
             }
-            '-' => {
-                if self.match_next('-') {
-                    while self.peek() != '\n' && !self.is_at_end() {
-                        self.advance();
-                    }
-                    Ok(())
-                } else {
-                    Ok(self.add_token(Token::Minus, loc))
+            self.current += 1;
+        }
+    }
+
+    /// Skip the body of a `--` comment, up to (not including) the newline.
+    fn skip_line_comment(&mut self) {
+        let rest = &self.source[self.current..];
+        let len = memchr(b'\n', rest).unwrap_or(rest.len());
+        self.current += len;
+        self.column += len;
+    }
+
+    /// Skip the rest of a `(* ... *)` comment opened at `loc`. Comments
+    /// nest, so each `(*` inside needs its own `*)`.
+    fn skip_block_comment(&mut self, loc: Loc) -> Result<(), LexicalError> {
+        let mut depth = 1;
+        while depth > 0 {
+            let rest = &self.source[self.current..];
+            let Some(i) = memchr3(b'(', b'*', b'\n', rest) else {
+                self.current = self.source.len();
+                return Err(LexicalError::UnterminatedComment(loc));
+            };
+            self.current += i + 1;
+            self.column += i + 1;
+            match rest[i] {
+                b'\n' => {
+                    self.line += 1;
+                    self.column = 0;
                 }
+                b'(' if self.match_next('*') => depth += 1,
+                b'*' if self.match_next(')') => depth -= 1,
+                _ => {}
             }
-            c if c.is_ascii_digit() => self.handle_number(loc),
-            c if is_alpha(c) => self.handle_identifier(loc),
-            c => Err(LexicalError::InvalidChar(c, loc)),
         }
+        Ok(())
     }
 
     fn handle_string(&mut self, loc: Loc) -> Result<(), LexicalError> {
-        let mut value = String::new();
-        while self.peek() != '"' && !self.is_at_end() {
-            if self.peek() == '\n' {
-                self.line += 1;
-                self.column = 0;
+        // Jump from one quote, backslash or newline to the next, copying the
+        // plain text in between
+        let mut text = Vec::new();
+        loop {
+            let rest = &self.source[self.current..];
+            let Some(i) = memchr3(b'"', b'\\', b'\n', rest) else {
+                self.current = self.source.len();
+                return Err(LexicalError::UnterminatedString(loc));
+            };
+            text.extend_from_slice(&rest[..i]);
+            self.current += i + 1;
+            self.column += i + 1;
+            match rest[i] {
+                b'"' => break,
+                // The manual ends the string here; lexing resumes on the next line
+                b'\n' => {
+                    self.line += 1;
+                    self.column = 0;
+                    return Err(LexicalError::NewlineInString(loc));
+                }
+                _ => self.escape(&mut text, loc)?,
             }
-            let c = self.advance();
-            value.push(c);
         }
+
+        let value = string_value(&mut text, loc)?;
+        self.add_token(Token::StrConst(value), loc);
+        Ok(())
+    }
+
+    /// The character after a backslash. `\n`, `\t`, `\b` and `\f` stand for
+    /// control characters; any other character, a newline included, stands
+    /// for itself.
+    fn escape(&mut self, text: &mut Vec<u8>, loc: Loc) -> Result<(), LexicalError> {
         if self.is_at_end() {
             return Err(LexicalError::UnterminatedString(loc));
         }
-        self.advance(); // Consume closing quote
+        let c = self.source[self.current];
+        self.current += 1;
+        self.column += 1;
+        text.push(match c {
+            b'n' => b'\n',
+            b't' => b'\t',
+            b'b' => 0x08,
+            b'f' => 0x0c,
+            b'\n' => {
+                self.line += 1;
+                self.column = 0;
+                b'\n'
+            }
+            c => c,
+        });
+        Ok(())
+    }
+
+    /// A string literal under `--ext strings`. `\u{XXXX}` stands for a
+    /// Unicode scalar value, and `\(expr)` splices in an expression as a
+    /// string: `"a\(e)b"` becomes `("a".concat(s).concat("b"))`, where `s`
+    /// is `e` converted by `TO_STRING_PREFIX` and `TO_STRING_SUFFIX`.
+    fn handle_extended_string(&mut self, loc: Loc) -> Result<(), LexicalError> {
+        let mut text = Vec::new();
+        let mut interpolated = false;
+        loop {
+            if self.is_at_end() {
+                return Err(LexicalError::UnterminatedString(loc));
+            }
+            let c = self.source[self.current];
+            self.current += 1;
+            self.column += 1;
+            match c {
+                b'"' => break,
+                b'\n' => {
+                    self.line += 1;
+                    self.column = 0;
+                    return Err(LexicalError::NewlineInString(loc));
+                }
+                b'\\' if self.match_next('u') => {
+                    let c = self.unicode_escape(loc)?;
+                    text.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+                }
+                b'\\' if !self.match_next('(') => self.escape(&mut text, loc)?,
+                b'\\' => {
+                    if interpolated {
+                        self.push_concat_literal(&mut text, loc)?;
+                    } else {
+                        self.add_token(Token::Lparen, loc);
+                        self.add_token(Token::StrConst(string_value(&mut text, loc)?), loc);
+                        interpolated = true;
+                    }
+                    self.add_token(Token::Period, loc);
+                    self.add_token(Token::Objectid(Symbol::intern("concat")), loc);
+                    self.add_token(Token::Lparen, loc);
+                    self.add_source(TO_STRING_PREFIX, loc);
+                    self.scan_interpolation(loc)?;
+                    self.add_source(TO_STRING_SUFFIX, loc);
+                    self.add_token(Token::Rparen, loc);
+                }
+                _ => text.push(c),
+            }
+        }
+
+        if interpolated {
+            if !text.is_empty() {
+                self.push_concat_literal(&mut text, loc)?;
+            }
+            self.add_token(Token::Rparen, loc);
+        } else {
+            self.add_token(Token::StrConst(string_value(&mut text, loc)?), loc);
+        }
+        Ok(())
+    }
+
+    /// `.concat("<text>")`, emptying `text`.
+    fn push_concat_literal(&mut self, text: &mut Vec<u8>, loc: Loc) -> Result<(), LexicalError> {
+        let value = string_value(text, loc)?;
+        self.add_token(Token::Period, loc);
+        self.add_token(Token::Objectid(Symbol::intern("concat")), loc);
+        self.add_token(Token::Lparen, loc);
         self.add_token(Token::StrConst(value), loc);
+        self.add_token(Token::Rparen, loc);
         Ok(())
     }
 
+    /// The tokens of `source`, a fragment of plain COOL, all at `loc`.
+    fn add_source(&mut self, source: &str, loc: Loc) {
+        for (token, _) in Scanner::new(source).scan_tokens().expect("valid COOL") {
+            self.add_token(token, loc);
+        }
+    }
+
+    /// The body of `\u{...}`, after the `u`.
+    fn unicode_escape(&mut self, loc: Loc) -> Result<char, LexicalError> {
+        let start = self.current;
+        let invalid = |scanner: &Self| {
+            let end = scanner.current.min(scanner.source.len());
+            let text = String::from_utf8_lossy(&scanner.source[start..end]);
+            LexicalError::InvalidEscape(format!("\\u{}", text), loc)
+        };
+        if !self.match_next('{') {
+            return Err(invalid(self));
+        }
+        let digits = self.current;
+        while self.peek().is_ascii_hexdigit() {
+            self.advance();
+        }
+        let hex = std::str::from_utf8(&self.source[digits..self.current]).unwrap();
+        let value = (1..=6).contains(&hex.len()).then(|| u32::from_str_radix(hex, 16).unwrap());
+        if !self.match_next('}') {
+            return Err(invalid(self));
+        }
+        value.and_then(char::from_u32).ok_or_else(|| invalid(self))
+    }
+
+    /// Tokens of the expression inside `\(...)`, up to and including the
+    /// matching `)`, which is consumed but not emitted.
+    fn scan_interpolation(&mut self, loc: Loc) -> Result<(), LexicalError> {
+        let mut depth = 0;
+        loop {
+            self.skip_whitespace();
+            match self.peek() {
+                _ if self.is_at_end() => return Err(LexicalError::UnterminatedString(loc)),
+                ')' if depth == 0 => {
+                    self.advance();
+                    return Ok(());
+                }
+                // `(*` starts a comment, not a nested expression
+                '(' if self.source.get(self.current + 1) != Some(&b'*') => depth += 1,
+                ')' => depth -= 1,
+                _ => {}
+            }
+            self.start = self.current;
+            self.scan_token()?;
+        }
+    }
+
     fn handle_number(&mut self, loc: Loc) -> Result<(), LexicalError> {
         while self.peek().is_ascii_digit() {
             self.advance();
@@ -161,15 +470,16 @@ impl Scanner {
         if let Some(token) = self.keywords.get(text.to_lowercase().as_str()) {
             self.add_token(token.clone(), loc);
         } else if text.chars().next().unwrap().is_uppercase() {
-            self.add_token(Token::Typeid(text), loc);
+            self.add_token(Token::Typeid(Symbol::intern(&text)), loc);
         } else {
-            self.add_token(Token::Objectid(text), loc);
+            self.add_token(Token::Objectid(Symbol::intern(&text)), loc);
         }
         Ok(())
     }
 
     fn add_token(&mut self, token: Token, loc: Loc) {
-        self.tokens.push((token, loc));
+        let span = Span { file: 0, start: self.start, end: self.current };
+        self.pending.push_back((token, loc, span));
     }
 
     fn advance(&mut self) -> char {
@@ -201,6 +511,28 @@ impl Scanner {
     }
 }
 
+impl Iterator for Scanner<'_> {
+    type Item = Result<(Token, Loc), LexicalError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_token().transpose()
+    }
+}
+
+/// The value of a string literal, emptying `text`. The manual forbids the
+/// null character in strings and limits them to `MAX_STRING_LENGTH`.
+fn string_value(text: &mut Vec<u8>, loc: Loc) -> Result<String, LexicalError> {
+    // Escapes only ever add ASCII between whole characters, so `text` is valid UTF-8
+    let value = String::from_utf8(std::mem::take(text)).unwrap();
+    if value.contains('\0') {
+        return Err(LexicalError::NullInString(loc));
+    }
+    if value.chars().count() > MAX_STRING_LENGTH {
+        return Err(LexicalError::StringTooLong(loc));
+    }
+    Ok(value)
+}
+
 fn is_alpha(c: char) -> bool {
     c.is_ascii_lowercase() || c.is_ascii_uppercase() || c == '_'
 }
@@ -218,12 +550,12 @@ mod tests {
         let input = "class if while let";
         let mut scanner = Scanner::new(input);
         let tokens = scanner.scan_tokens().unwrap();
-        let loc = Loc { line: 1, column: 0 };
+        let loc = |column| Loc { line: 1, column };
         let expected = vec![
-            (Token::Class_, loc.clone()),
-            (Token::If, loc.clone()),
-            (Token::While, loc.clone()),
-            (Token::Let, loc),
+            (Token::Class_, loc(1)),
+            (Token::If, loc(7)),
+            (Token::While, loc(10)),
+            (Token::Let, loc(16)),
         ];
         assert_eq!(tokens, expected);
     }
@@ -233,15 +565,144 @@ mod tests {
         let input = "+ - * / <- =>";
         let mut scanner = Scanner::new(input);
         let tokens = scanner.scan_tokens().unwrap();
-        let loc = Loc { line: 1, column: 0 };
+        let loc = |column| Loc { line: 1, column };
+        let expected = vec![
+            (Token::Plus, loc(1)),
+            (Token::Minus, loc(3)),
+            (Token::Mul, loc(5)),
+            (Token::Divide, loc(7)),
+            (Token::Assign, loc(9)),
+            (Token::Darrow, loc(12)),
+        ];
+        assert_eq!(tokens, expected);
+    }
+
+    #[test]
+    fn test_locations_after_comments_and_strings() {
+        let input = "-- header\n  x <- \"two\\\nlines\" ; -- trailing\n\ty";
+        let mut scanner = Scanner::new(input);
+        let tokens = scanner.scan_tokens().unwrap();
         let expected = vec![
-            (Token::Plus, loc.clone()),
-            (Token::Minus, loc.clone()),
-            (Token::Mul, loc.clone()),
-            (Token::Divide, loc.clone()),
-            (Token::Assign, loc.clone()),
-            (Token::Darrow, loc),
+            (Token::Objectid(Symbol::intern("x")), Loc { line: 2, column: 3 }),
+            (Token::Assign, Loc { line: 2, column: 5 }),
+            (Token::StrConst("two\nlines".to_string()), Loc { line: 2, column: 8 }),
+            (Token::Semicolon, Loc { line: 3, column: 8 }),
+            (Token::Objectid(Symbol::intern("y")), Loc { line: 4, column: 2 }),
         ];
         assert_eq!(tokens, expected);
     }
+
+    #[test]
+    fn test_block_comments() {
+        let input = "a (* one (* two *)\n still * ( ) *) b (*) *) c\n(* (* *)\n";
+        let tokens = Scanner::new(input).scan_tokens();
+        assert!(matches!(tokens, Err(LexicalError::UnterminatedComment(Loc { line: 3, column: 1 }))), "{:?}", tokens);
+
+        let tokens = Scanner::new(&input[..input.len() - 10]).scan_tokens().unwrap();
+        let expected = vec![
+            (Token::Objectid(Symbol::intern("a")), Loc { line: 1, column: 1 }),
+            (Token::Objectid(Symbol::intern("b")), Loc { line: 2, column: 17 }),
+            (Token::Objectid(Symbol::intern("c")), Loc { line: 2, column: 26 }),
+        ];
+        assert_eq!(tokens, expected);
+    }
+
+    #[test]
+    fn test_kept_comments() {
+        let input = "-- header\nx (* one (* two *) *) y -- trailing\n";
+        let mut scanner = Scanner::new(input).keep_comments();
+        assert_eq!(scanner.scan_tokens().unwrap().len(), 2);
+        let comments: Vec<&str> = scanner.comments().iter().map(|span| &input[span.start..span.end]).collect();
+        assert_eq!(comments, ["-- header", "(* one (* two *) *)", "-- trailing"]);
+        let mut plain = Scanner::new(input);
+        plain.scan_tokens().unwrap();
+        assert!(plain.comments().is_empty());
+    }
+
+    #[test]
+    fn test_non_ascii_character() {
+        let mut scanner = Scanner::new("é😀 x");
+        assert_eq!(scanner.next_token().unwrap_err(), LexicalError::InvalidChar('é', Loc { line: 1, column: 1 }));
+        assert_eq!(scanner.next_token().unwrap_err(), LexicalError::InvalidChar('😀', Loc { line: 1, column: 2 }));
+        assert_eq!(scanner.next_token().unwrap().unwrap().0, Token::Objectid(Symbol::intern("x")));
+    }
+
+    #[test]
+    fn test_unterminated_string() {
+        let mut scanner = Scanner::new("x \"abc");
+        let err = scanner.scan_tokens().unwrap_err();
+        assert!(matches!(err, LexicalError::UnterminatedString(Loc { line: 1, column: 3 })));
+    }
+
+    #[test]
+    fn test_newline_in_string() {
+        let mut scanner = Scanner::new("x \"abc\ndef\" y");
+        assert_eq!(scanner.next_token().unwrap().unwrap().0, Token::Objectid(Symbol::intern("x")));
+        assert_eq!(scanner.next_token().unwrap_err(), LexicalError::NewlineInString(Loc { line: 1, column: 3 }));
+        // Lexing resumes at the start of the next line
+        assert_eq!(scanner.next_token().unwrap().unwrap().0, Token::Objectid(Symbol::intern("def")));
+        assert_eq!(scanner.line(), 2);
+    }
+
+    #[test]
+    fn test_string_escapes() {
+        let tokens = Scanner::new("\"a\\tb\\nc\\\\\\\"\\z\\b\\f\\\nd\" x").scan_tokens().unwrap();
+        assert_eq!(tokens[0].0, Token::StrConst("a\tb\nc\\\"z\u{8}\u{c}\nd".to_string()));
+        assert_eq!(tokens[1].1, Loc { line: 2, column: 4 });
+
+        for input in ["\"a\0b\" x", "\"a\\\0b\" x"] {
+            let err = Scanner::new(input).scan_tokens().unwrap_err();
+            assert!(matches!(err, LexicalError::NullInString(Loc { line: 1, column: 1 })), "{:?}", err);
+        }
+
+        let longest = format!("\"{}\"", "é".repeat(MAX_STRING_LENGTH));
+        assert!(Scanner::new(&longest).scan_tokens().is_ok());
+        let too_long = format!("\"{}\\n\"", "a".repeat(MAX_STRING_LENGTH));
+        let err = Scanner::new(&too_long).scan_tokens().unwrap_err();
+        assert!(matches!(err, LexicalError::StringTooLong(_)), "{:?}", err);
+    }
+
+    fn scan_extended(input: &str) -> Result<Vec<Token>, LexicalError> {
+        let extensions = [Extension::Strings].into_iter().collect();
+        let tokens = Scanner::with_extensions(input, extensions).scan_tokens()?;
+        Ok(tokens.into_iter().map(|(token, _)| token).collect())
+    }
+
+    #[test]
+    fn test_extended_strings() {
+        let str_const = |s: &str| Token::StrConst(s.to_string());
+        let concat = || Token::Objectid(Symbol::intern("concat"));
+
+        // Without the extension `\u` is just an escaped `u`
+        let tokens = Scanner::new(r#""\u{e9}""#).scan_tokens().unwrap();
+        assert_eq!(tokens[0].0, str_const("u{e9}"));
+        assert_eq!(scan_extended(r#""caf\u{e9} \u{1F600}""#).unwrap(), vec![str_const("café 😀")]);
+
+        let tokens = scan_extended(r#""a\(f("(")) b""#).unwrap();
+        let plain = |source: &str| Scanner::new(source).scan_tokens().unwrap().into_iter().map(|(token, _)| token);
+        let mut expected = vec![Token::Lparen, str_const("a"), Token::Period, concat(), Token::Lparen];
+        expected.extend(plain(TO_STRING_PREFIX));
+        expected.extend([Token::Objectid(Symbol::intern("f")), Token::Lparen, str_const("("), Token::Rparen]);
+        expected.extend(plain(TO_STRING_SUFFIX));
+        expected.extend([
+            Token::Rparen,
+            Token::Period,
+            concat(),
+            Token::Lparen,
+            str_const(" b"),
+            Token::Rparen,
+            Token::Rparen,
+        ]);
+        assert_eq!(tokens, expected);
+    }
+
+    #[test]
+    fn test_extended_string_errors() {
+        for input in [r#""\u{110000}""#, r#""\u{}""#, r#""\u{12""#, r#""\u12""#] {
+            let err = scan_extended(input).unwrap_err();
+            assert!(matches!(err, LexicalError::InvalidEscape(..)), "{}: {:?}", input, err);
+        }
+        let err = scan_extended(r#""\(x"#).unwrap_err();
+        assert!(matches!(err, LexicalError::UnterminatedString(Loc { line: 1, column: 1 })));
+    }
 }
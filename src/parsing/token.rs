@@ -1,5 +1,9 @@
 use std::fmt;
 
+use serde::{Deserialize, Serialize};
+
+use crate::symbol::Symbol;
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum Token {
     Class_,
@@ -19,11 +23,17 @@ pub enum Token {
     New,
     Isvoid,
     Not,
+    Try,
+    Catch,
+    Throw,
+    End,
+    Import,
+    Native,
     StrConst(String),
     IntConst(String),
     BoolConst(bool),
-    Typeid(String),
-    Objectid(String),
+    Typeid(Symbol),
+    Objectid(Symbol),
     Darrow,
     Assign,
     Le,
@@ -66,6 +76,12 @@ impl fmt::Display for Token {
             Token::New => write!(f, "NEW"),
             Token::Isvoid => write!(f, "ISVOID"),
             Token::Not => write!(f, "NOT"),
+            Token::Try => write!(f, "TRY"),
+            Token::Catch => write!(f, "CATCH"),
+            Token::Throw => write!(f, "THROW"),
+            Token::End => write!(f, "END"),
+            Token::Import => write!(f, "IMPORT"),
+            Token::Native => write!(f, "NATIVE"),
             Token::StrConst(s) => write!(f, "\"{}\"", s),
             Token::IntConst(s) => write!(f, "{}", s),
             Token::BoolConst(b) => write!(f, "{}", b),
@@ -107,21 +123,107 @@ impl fmt::Display for Loc {
     }
 }
 
+/// Byte range of a token or expression in its source file. Nodes built by
+/// the compiler rather than parsed have an empty span at offset 0.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+pub struct Span {
+    /// Index of the file among the program's input files, in the order
+    /// they were given
+    pub file: usize,
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn is_empty(&self) -> bool {
+        self.start >= self.end
+    }
+}
+
+/// A parser location: the line, for messages, and the byte offset, for spans.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub struct Position {
+    pub line: usize,
+    pub offset: usize,
+}
+
+/// Only the line, so syntax errors read as they did before parser
+/// locations carried offsets.
+impl fmt::Display for Position {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.line)
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum LexicalError {
     InvalidChar(char, Loc),
     UnterminatedString(Loc),
+    /// At the string that an unescaped newline cuts short
+    NewlineInString(Loc),
     InvalidNumber(String, Loc),
+    InvalidEscape(String, Loc),
+    NullInString(Loc),
+    StringTooLong(Loc),
+    /// At the `(*` that is never closed
+    UnterminatedComment(Loc),
+    /// At the token that opens one level too many (`parsing::nesting`)
+    NestedTooDeeply(Loc),
 }
 
-impl fmt::Display for LexicalError {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+impl LexicalError {
+    pub fn loc(&self) -> Loc {
+        match self {
+            LexicalError::InvalidChar(_, loc)
+            | LexicalError::UnterminatedString(loc)
+            | LexicalError::NewlineInString(loc)
+            | LexicalError::InvalidNumber(_, loc)
+            | LexicalError::InvalidEscape(_, loc)
+            | LexicalError::NullInString(loc)
+            | LexicalError::StringTooLong(loc)
+            | LexicalError::UnterminatedComment(loc)
+            | LexicalError::NestedTooDeeply(loc) => *loc,
+        }
+    }
+
+    /// Stable identifier, in the same series as the semantic error codes.
+    pub fn code(&self) -> &'static str {
         match self {
-            LexicalError::InvalidChar(c, loc) => write!(f, "Invalid character '{}' at {}", c, loc),
-            LexicalError::UnterminatedString(loc) => write!(f, "Unterminated string at {}", loc),
-            LexicalError::InvalidNumber(s, loc) => write!(f, "Invalid number '{}' at {}", s, loc),
+            LexicalError::InvalidChar(..) => "E0027",
+            LexicalError::UnterminatedString(_) => "E0028",
+            LexicalError::InvalidNumber(..) => "E0029",
+            LexicalError::InvalidEscape(..) => "E0030",
+            LexicalError::NullInString(_) => "E0031",
+            LexicalError::StringTooLong(_) => "E0032",
+            LexicalError::UnterminatedComment(_) => "E0033",
+            LexicalError::NewlineInString(_) => "E0040",
+            LexicalError::NestedTooDeeply(_) => "E0041",
         }
     }
+
+    /// The error without its location.
+    pub fn message(&self) -> String {
+        match self {
+            LexicalError::InvalidChar(c, _) => format!("Invalid character '{}'", c),
+            LexicalError::UnterminatedString(_) => "Unterminated string".to_string(),
+            LexicalError::NewlineInString(_) => "Unterminated string constant".to_string(),
+            LexicalError::InvalidNumber(s, _) => format!("Invalid number '{}'", s),
+            LexicalError::InvalidEscape(s, _) => format!("Invalid escape sequence '{}'", s),
+            LexicalError::NullInString(_) => "String contains null character".to_string(),
+            LexicalError::StringTooLong(_) => "String constant too long".to_string(),
+            LexicalError::UnterminatedComment(_) => "Unterminated comment".to_string(),
+            LexicalError::NestedTooDeeply(_) => {
+                format!("Brackets and prefix operators nested more than {} levels deep", super::nesting::MAX_NESTING)
+            }
+        }
+    }
+}
+
+/// The message, then the location.
+impl fmt::Display for LexicalError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at {}", self.message(), self.loc())
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
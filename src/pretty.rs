@@ -0,0 +1,302 @@
+use std::fmt::Write;
+
+use crate::ast::{
+    ArgDecl, CaseBranch, Class, ComparisonOperator, Expr, Feature, MathOperator, TypedExpr,
+    UnaryOperator, VarDecl,
+};
+
+/// Print `classes` back as COOL source that parses to the same AST
+/// (up to line numbers and redundant parentheses).
+///
+/// Parentheses are inserted only where the grammar's precedence requires
+/// them, and `Paren` nodes print as their contents, so printing the
+/// reparsed output gives the same text again.
+pub fn print_program(classes: &[Class]) -> String {
+    let mut printer = Printer::default();
+    for (i, class) in classes.iter().enumerate() {
+        if i > 0 {
+            printer.out.push('\n');
+        }
+        printer.class(class);
+    }
+    printer.out
+}
+
+/// Print one expression back as COOL source, as `print_program` would
+/// inside a method body at the outermost indentation.
+pub fn print_expr(e: &TypedExpr) -> String {
+    let mut printer = Printer::default();
+    printer.expr(e, level::LET);
+    printer.out
+}
+
+/// Grammar precedence levels, from `Expr0Ty` (atoms) to `Expr10Ty` (`let`).
+/// An expression can appear unparenthesized wherever a level at least as
+/// high as its own is accepted.
+pub(crate) mod level {
+    pub const ATOM: u8 = 0;
+    /// `id(args)`, dispatch on self
+    pub const CALL: u8 = 1;
+    /// `e.id(args)` and `e@T.id(args)`, left-associative
+    pub const DISPATCH: u8 = 2;
+    pub const NEG: u8 = 3;
+    pub const ISVOID: u8 = 4;
+    pub const PRODUCT: u8 = 5;
+    pub const SUM: u8 = 6;
+    pub const COMPARISON: u8 = 7;
+    pub const NOT: u8 = 8;
+    pub const ASSIGN: u8 = 9;
+    pub const LET: u8 = 10;
+}
+
+pub(crate) fn level_of(expr: &Expr) -> u8 {
+    match expr {
+        Expr::Let(..) | Expr::Throw(_) => level::LET,
+        Expr::Assignment(..) => level::ASSIGN,
+        Expr::UnaryOperation { op: UnaryOperator::Not, .. } => level::NOT,
+        Expr::Comparison { .. } => level::COMPARISON,
+        Expr::Math { op: MathOperator::Add | MathOperator::Subtract, .. } => level::SUM,
+        Expr::Math { op: MathOperator::Mul | MathOperator::Div, .. } => level::PRODUCT,
+        Expr::Isvoid(_) => level::ISVOID,
+        Expr::UnaryOperation { op: UnaryOperator::Neg, .. } => level::NEG,
+        Expr::Dispatch { target: None, .. } => level::CALL,
+        Expr::Dispatch { .. } => level::DISPATCH,
+        Expr::Paren(inner) => level_of(&inner.expr),
+        _ => level::ATOM,
+    }
+}
+
+/// `s` as a string literal, escaping what the scanner would not read back
+/// as itself.
+pub(crate) fn quote(out: &mut String, s: &str) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            '\u{8}' => out.push_str("\\b"),
+            '\u{c}' => out.push_str("\\f"),
+            '\\' | '"' => {
+                out.push('\\');
+                out.push(c);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+#[derive(Default)]
+struct Printer {
+    out: String,
+    indent: usize,
+}
+
+impl Printer {
+    fn newline(&mut self) {
+        self.out.push('\n');
+        for _ in 0..self.indent {
+            self.out.push_str("    ");
+        }
+    }
+
+    fn class(&mut self, class: &Class) {
+        write!(self.out, "class {}", class.name).unwrap();
+        if let Some(parent) = class.inherits {
+            write!(self.out, " inherits {}", parent).unwrap();
+        }
+        self.out.push_str(" {");
+        self.indent += 1;
+        for feature in &class.feature_list {
+            self.newline();
+            self.feature(feature);
+        }
+        self.indent -= 1;
+        self.newline();
+        self.out.push_str("};\n");
+    }
+
+    fn feature(&mut self, feature: &Feature) {
+        match feature {
+            Feature::Attribute(VarDecl { oid, tid, expr, .. }) => {
+                write!(self.out, "{} : {}", oid, tid).unwrap();
+                if let Some(init) = expr {
+                    self.out.push_str(" <- ");
+                    self.expr(init, level::LET);
+                }
+            }
+            Feature::Method(name, args, ret_type, body, _) => {
+                write!(self.out, "{}(", name).unwrap();
+                for (i, ArgDecl { id, tid, .. }) in args.iter().enumerate() {
+                    if i > 0 {
+                        self.out.push_str(", ");
+                    }
+                    write!(self.out, "{} : {}", id, tid).unwrap();
+                }
+                if let Expr::Native = body.expr {
+                    write!(self.out, ") : {} native;", ret_type).unwrap();
+                    return;
+                }
+                write!(self.out, ") : {} {{", ret_type).unwrap();
+                self.indent += 1;
+                self.newline();
+                self.expr(body, level::LET);
+                self.indent -= 1;
+                self.newline();
+                self.out.push('}');
+            }
+        }
+        self.out.push(';');
+    }
+
+    /// Print `e` where the grammar accepts expressions up to level `max`.
+    fn expr(&mut self, e: &TypedExpr, max: u8) {
+        if let Expr::Paren(inner) = &e.expr {
+            return self.expr(inner, max);
+        }
+        if level_of(&e.expr) > max {
+            self.out.push('(');
+            self.expr(e, level::LET);
+            self.out.push(')');
+            return;
+        }
+
+        match &e.expr {
+            Expr::Identifier(name) => write!(self.out, "{}", name).unwrap(),
+            Expr::Bool(b) => write!(self.out, "{}", b).unwrap(),
+            Expr::Int(i) => write!(self.out, "{}", i).unwrap(),
+            Expr::Str(s) => quote(&mut self.out, s),
+            Expr::New(type_name) => write!(self.out, "new {}", type_name).unwrap(),
+            Expr::Block(exprs) => {
+                self.out.push('{');
+                self.indent += 1;
+                for e in exprs {
+                    self.newline();
+                    self.expr(e, level::LET);
+                    self.out.push(';');
+                }
+                self.indent -= 1;
+                self.newline();
+                self.out.push('}');
+            }
+            Expr::Case(scrutinee, branches) => {
+                self.out.push_str("case ");
+                self.expr(scrutinee, level::LET);
+                self.out.push_str(" of");
+                self.indent += 1;
+                for CaseBranch { id, tid, expr, .. } in branches {
+                    self.newline();
+                    write!(self.out, "{} : {} => ", id, tid).unwrap();
+                    self.expr(expr, level::LET);
+                    self.out.push(';');
+                }
+                self.indent -= 1;
+                self.newline();
+                self.out.push_str("esac");
+            }
+            Expr::Paren(_) => unreachable!("handled above"),
+            Expr::Let(bindings, body) => {
+                self.out.push_str("let ");
+                for (i, VarDecl { oid: id, tid, expr: init, .. }) in bindings.iter().enumerate() {
+                    if i > 0 {
+                        self.out.push_str(", ");
+                    }
+                    write!(self.out, "{} : {}", id, tid).unwrap();
+                    if let Some(init) = init {
+                        self.out.push_str(" <- ");
+                        // A nested `let` would swallow the following bindings
+                        self.expr(init, level::ASSIGN);
+                    }
+                }
+                self.out.push_str(" in ");
+                self.expr(body, level::LET);
+            }
+            Expr::Comparison { lhs, op, rhs } => {
+                let op = match op {
+                    ComparisonOperator::Lt => "<",
+                    ComparisonOperator::Le => "<=",
+                    ComparisonOperator::Equal => "=",
+                };
+                self.binary(lhs, op, rhs, level::COMPARISON);
+            }
+            Expr::Math { lhs, op, rhs } => {
+                let (op, level) = match op {
+                    MathOperator::Add => ("+", level::SUM),
+                    MathOperator::Subtract => ("-", level::SUM),
+                    MathOperator::Mul => ("*", level::PRODUCT),
+                    MathOperator::Div => ("/", level::PRODUCT),
+                };
+                self.binary(lhs, op, rhs, level);
+            }
+            Expr::UnaryOperation { op: UnaryOperator::Not, s } => {
+                self.out.push_str("not ");
+                self.expr(s, level::NOT - 1);
+            }
+            Expr::UnaryOperation { op: UnaryOperator::Neg, s } => {
+                self.out.push('~');
+                self.expr(s, level::NEG - 1);
+            }
+            Expr::Assignment(name, rhs) => {
+                write!(self.out, "{} <- ", name).unwrap();
+                self.expr(rhs, level::ASSIGN);
+            }
+            Expr::Conditional { test, then, orelse } => {
+                self.out.push_str("if ");
+                self.expr(test, level::LET);
+                self.out.push_str(" then ");
+                self.expr(then, level::LET);
+                self.out.push_str(" else ");
+                self.expr(orelse, level::LET);
+                self.out.push_str(" fi");
+            }
+            Expr::While { test, exec } => {
+                self.out.push_str("while ");
+                self.expr(test, level::LET);
+                self.out.push_str(" loop ");
+                self.expr(exec, level::LET);
+                self.out.push_str(" pool");
+            }
+            Expr::Isvoid(inner) => {
+                self.out.push_str("isvoid ");
+                self.expr(inner, level::ISVOID - 1);
+            }
+            Expr::Dispatch { target, targettype, id, exprs } => {
+                if let Some(target) = target {
+                    self.expr(target, level::DISPATCH);
+                    if let Some(tt) = targettype {
+                        write!(self.out, "@{}", tt).unwrap();
+                    }
+                    self.out.push('.');
+                }
+                write!(self.out, "{}(", id).unwrap();
+                for (i, arg) in exprs.iter().enumerate() {
+                    if i > 0 {
+                        self.out.push_str(", ");
+                    }
+                    self.expr(arg, level::LET);
+                }
+                self.out.push(')');
+            }
+            Expr::Try { body, id, tid, handler } => {
+                self.out.push_str("try ");
+                self.expr(body, level::LET);
+                write!(self.out, " catch {} : {} => ", id, tid).unwrap();
+                self.expr(handler, level::LET);
+                self.out.push_str(" end");
+            }
+            Expr::Throw(inner) => {
+                self.out.push_str("throw ");
+                self.expr(inner, level::LET);
+            }
+            Expr::Native => unreachable!("printed as part of its method"),
+            Expr::Builtin => unreachable!("built-in classes are not printed"),
+        }
+    }
+
+    /// Left-associative binary operator at `level`.
+    fn binary(&mut self, lhs: &TypedExpr, op: &str, rhs: &TypedExpr, level: u8) {
+        self.expr(lhs, level);
+        write!(self.out, " {} ", op).unwrap();
+        self.expr(rhs, level - 1);
+    }
+}
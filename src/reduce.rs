@@ -0,0 +1,343 @@
+//! Automatic test-case reduction.
+//!
+//! Starting from a program that triggers a bug (a panic, or a particular
+//! diagnostic), repeatedly try smaller variants and keep any that still
+//! trigger it: drop classes and features, replace expressions with literals
+//! or with one of their own subexpressions, and remove list elements. Every
+//! candidate is printed back to source and run through the pipeline
+//! in-process, so the result is a standalone reproducer.
+
+use std::panic::{self, AssertUnwindSafe};
+
+use eyre::{bail, Result};
+
+use crate::ast::{Class, Expr, Feature, TypedExpr, VarDecl};
+use crate::pretty::print_program;
+use crate::semantic::collector::ErrorCollector;
+use crate::semantic::context::AnalysisContext;
+use crate::symbol::sym;
+
+/// What the reduced program must keep doing.
+#[derive(Debug, Clone)]
+pub enum Target {
+    /// The compiler panics.
+    Crash,
+    /// The compiler reports a diagnostic with this code, e.g. `E0010`.
+    Diagnostic(String),
+}
+
+impl Target {
+    fn reproduces(&self, source: &str) -> bool {
+        let outcome = panic::catch_unwind(AssertUnwindSafe(|| {
+            let classes = crate::parse_program(source).ok()?;
+            let ctx = AnalysisContext::new(crate::prelude(), &classes);
+            let mut ec = ErrorCollector::default();
+            crate::check_program(&ctx, &mut ec);
+            Some(ec.errors.iter().map(|e| e.code()).collect::<Vec<_>>())
+        }));
+        match (self, outcome) {
+            (Target::Crash, Err(_)) => true,
+            (Target::Diagnostic(code), Ok(Some(codes))) => codes.contains(&code.as_str()),
+            _ => false,
+        }
+    }
+}
+
+/// Outcome of a reduction.
+pub struct Reduction {
+    pub source: String,
+    /// Expression nodes before and after.
+    pub nodes_before: usize,
+    pub nodes_after: usize,
+    /// Candidates that were compiled.
+    pub tests: usize,
+}
+
+/// Shrink `source` while it keeps reproducing `target`.
+pub fn reduce(source: &str, target: &Target) -> Result<Reduction> {
+    let classes = match panic::catch_unwind(|| crate::parse_program(source)) {
+        Ok(Ok(classes)) => classes,
+        Ok(Err(err)) => bail!("the input must parse before it can be reduced: {:#}", err),
+        Err(_) => bail!("the parser itself panics on this input; there is no AST to reduce"),
+    };
+
+    // Expected panics would otherwise print a message for every candidate
+    let hook = panic::take_hook();
+    panic::set_hook(Box::new(|_| {}));
+    let result = Reducer::new(classes, target).run();
+    panic::set_hook(hook);
+    result
+}
+
+struct Reducer<'t> {
+    target: &'t Target,
+    best: Vec<Class>,
+    tests: usize,
+}
+
+impl<'t> Reducer<'t> {
+    fn new(best: Vec<Class>, target: &'t Target) -> Self {
+        Reducer { target, best, tests: 0 }
+    }
+
+    fn run(mut self) -> Result<Reduction> {
+        // The printed form is what gets reduced, so it must reproduce too
+        self.tests += 1;
+        if !self.target.reproduces(&print_program(&self.best)) {
+            match self.target {
+                Target::Crash => bail!("the compiler does not panic on the input"),
+                Target::Diagnostic(code) => bail!("the input does not report {}", code),
+            }
+        }
+
+        let nodes_before = program_size(&self.best);
+        loop {
+            let mut progress = self.reduce_classes();
+            progress |= self.reduce_features();
+            progress |= self.reduce_exprs();
+            if !progress {
+                break;
+            }
+        }
+
+        Ok(Reduction {
+            source: print_program(&self.best),
+            nodes_before,
+            nodes_after: program_size(&self.best),
+            tests: self.tests,
+        })
+    }
+
+    /// Keep `candidate` if it still reproduces.
+    fn accept(&mut self, candidate: Vec<Class>) -> bool {
+        self.tests += 1;
+        if self.target.reproduces(&print_program(&candidate)) {
+            self.best = candidate;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Try `edit` on a copy of the current program at every index `0..len()`.
+    /// `len` is re-read after each success, since the program shrinks.
+    fn try_each(
+        &mut self,
+        len: impl Fn(&[Class]) -> usize,
+        edit: impl Fn(&mut Vec<Class>, usize) -> bool,
+    ) -> bool {
+        let mut progress = false;
+        let mut i = 0;
+        while i < len(&self.best) {
+            let mut candidate = self.best.clone();
+            if edit(&mut candidate, i) && self.accept(candidate) {
+                progress = true;
+            } else {
+                i += 1;
+            }
+        }
+        progress
+    }
+
+    fn reduce_classes(&mut self) -> bool {
+        // The grammar needs at least one class
+        let mut progress = self.try_each(
+            |p| if p.len() > 1 { p.len() } else { 0 },
+            |p, i| {
+                p.remove(i);
+                true
+            },
+        );
+        progress |= self.try_each(|p| p.len(), |p, i| p[i].inherits.take().is_some());
+        progress
+    }
+
+    fn reduce_features(&mut self) -> bool {
+        // Features are addressed by their position across all classes
+        let total = |p: &[Class]| p.iter().map(|c| c.feature_list.len()).sum();
+        let mut progress = self.try_each(total, |p, i| {
+            let (c, f) = locate_feature(p, i);
+            p[c].feature_list.remove(f);
+            true
+        });
+        progress |= self.try_each(total, |p, i| {
+            let (c, f) = locate_feature(p, i);
+            match &mut p[c].feature_list[f] {
+                Feature::Attribute(VarDecl { expr, .. }) => expr.take().is_some(),
+                Feature::Method(_, args, _, _, _) => args.pop().is_some(),
+            }
+        });
+        progress
+    }
+
+    fn reduce_exprs(&mut self) -> bool {
+        let mut progress = false;
+        let mut i = 0;
+        'nodes: while i < program_size(&self.best) {
+            let node = nth_expr(&mut self.best.clone(), i).unwrap().clone();
+            for replacement in simplifications(&node) {
+                let mut candidate = self.best.clone();
+                *nth_expr(&mut candidate, i).unwrap() = replacement;
+                if self.accept(candidate) {
+                    progress = true;
+                    continue 'nodes;
+                }
+            }
+            i += 1;
+        }
+        progress
+    }
+}
+
+/// (class, feature) indices of the `i`-th feature in the program.
+fn locate_feature(classes: &[Class], mut i: usize) -> (usize, usize) {
+    for (c, class) in classes.iter().enumerate() {
+        if i < class.feature_list.len() {
+            return (c, i);
+        }
+        i -= class.feature_list.len();
+    }
+    unreachable!("feature index out of range")
+}
+
+fn roots_mut(classes: &mut [Class]) -> impl Iterator<Item = &mut TypedExpr> {
+    classes
+        .iter_mut()
+        .flat_map(|c| c.feature_list.iter_mut())
+        .filter_map(|f| match f {
+            Feature::Attribute(VarDecl { expr, .. }) => expr.as_mut(),
+            Feature::Method(_, _, _, body, _) => Some(body),
+        })
+}
+
+fn expr_size(e: &TypedExpr) -> usize {
+    1 + e.expr.children().into_iter().map(expr_size).sum::<usize>()
+}
+
+fn program_size(classes: &[Class]) -> usize {
+    let mut classes = classes.to_vec();
+    roots_mut(&mut classes).map(|e| expr_size(e)).sum()
+}
+
+/// The `n`-th expression node of the program in preorder.
+fn nth_expr(classes: &mut [Class], mut n: usize) -> Option<&mut TypedExpr> {
+    fn walk<'a>(e: &'a mut TypedExpr, n: &mut usize) -> Option<&'a mut TypedExpr> {
+        if *n == 0 {
+            return Some(e);
+        }
+        *n -= 1;
+        e.expr.children_mut().into_iter().find_map(|child| walk(child, n))
+    }
+    roots_mut(classes).find_map(|root| walk(root, &mut n))
+}
+
+/// Smaller replacements for `e`, most aggressive first. Each one either has
+/// fewer nodes, turns a non-literal into a literal, or turns an identifier into
+/// `self`, so reduction terminates.
+fn simplifications(e: &TypedExpr) -> Vec<TypedExpr> {
+    let at_line = |expr| TypedExpr::new(expr, e.line);
+    let mut out = Vec::new();
+
+    // Identifiers are not literals: `self` or a constant often still reproduces
+    let is_literal = matches!(e.expr, Expr::Bool(_) | Expr::Int(_) | Expr::Str(_));
+    if !is_literal {
+        out.push(at_line(Expr::Int(0)));
+        out.push(at_line(Expr::Bool(false)));
+        out.push(at_line(Expr::Str(String::new())));
+        if !matches!(e.expr, Expr::Identifier(sym::SELF)) {
+            out.push(at_line(Expr::Identifier(sym::SELF)));
+        }
+    }
+    out.extend(e.expr.children().into_iter().cloned());
+
+    match &e.expr {
+        Expr::Block(exprs) if exprs.len() > 1 => {
+            for i in 0..exprs.len() {
+                let mut exprs = exprs.clone();
+                exprs.remove(i);
+                out.push(at_line(Expr::Block(exprs)));
+            }
+        }
+        Expr::Let(bindings, body) => {
+            for i in 0..bindings.len() {
+                if bindings.len() > 1 {
+                    let mut fewer = bindings.clone();
+                    fewer.remove(i);
+                    out.push(at_line(Expr::Let(fewer, body.clone())));
+                }
+                if bindings[i].expr.is_some() {
+                    let mut uninit = bindings.clone();
+                    uninit[i].expr = None;
+                    out.push(at_line(Expr::Let(uninit, body.clone())));
+                }
+            }
+        }
+        Expr::Case(scrutinee, branches) if branches.len() > 1 => {
+            for i in 0..branches.len() {
+                let mut branches = branches.clone();
+                branches.remove(i);
+                out.push(at_line(Expr::Case(scrutinee.clone(), branches)));
+            }
+        }
+        Expr::Dispatch { target, targettype, id, exprs } => {
+            if target.is_some() {
+                out.push(at_line(Expr::Dispatch {
+                    target: None,
+                    targettype: None,
+                    id: *id,
+                    exprs: exprs.clone(),
+                }));
+            }
+            for i in 0..exprs.len() {
+                let mut exprs = exprs.clone();
+                exprs.remove(i);
+                out.push(at_line(Expr::Dispatch {
+                    target: target.clone(),
+                    targettype: *targettype,
+                    id: *id,
+                    exprs,
+                }));
+            }
+        }
+        _ => {}
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reduces_to_the_offending_expression() {
+        let source = "\
+class Helper {
+    n : Int <- 3;
+    twice(x : Int) : Int { x + x };
+};
+class Main inherits IO {
+    h : Helper <- new Helper;
+    main() : Object {
+        {
+            out_int(h.twice(2));
+            let s : String <- \"a\" in out_int(1 + s.length() * (2 + s));
+            out_string(\"done\");
+        }
+    };
+};
+";
+        let target = Target::Diagnostic("E0010".to_string());
+        let reduction = reduce(source, &target).unwrap();
+
+        assert!(target.reproduces(&reduction.source));
+        assert!(reduction.nodes_after < reduction.nodes_before);
+        assert_eq!(reduction.source, "class Main {\n    main() : Object {\n        2 + false\n    };\n};\n");
+    }
+
+    #[test]
+    fn test_rejects_inputs_that_do_not_reproduce() {
+        let source = "class Main { main() : Int { 0 }; };";
+        assert!(reduce(source, &Target::Diagnostic("E0010".to_string())).is_err());
+        assert!(reduce(source, &Target::Crash).is_err());
+    }
+}
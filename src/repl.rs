@@ -0,0 +1,271 @@
+//! Interactive evaluation (`cool-rs repl`).
+//!
+//! Each input is either class definitions or an expression. Classes join the
+//! session's program once they check cleanly together with the classes
+//! defined before them. An expression is evaluated as the body of a method
+//! of a scratch class that inherits from `IO`, so `out_string` and the other
+//! IO methods can be called unqualified, against the classes defined so far.
+//!
+//! An input that stops in the middle, such as an open bracket or a `let`
+//! without its body, continues on the next line; an empty line ends it
+//! regardless. Lines are read as the terminal delivers them, so editing is
+//! the terminal's own; `rlwrap cool-rs repl` adds history.
+
+use std::io::{self, BufRead, Write};
+
+use lalrpop_util::ParseError;
+
+use crate::ast::{Class, Expr, Feature, TypedExpr};
+use crate::compiler::Compiler;
+use crate::diagnostic::render::SourceFile;
+use crate::diagnostic::{Diagnostic, Reporter};
+use crate::interp::{self, RuntimeError};
+use crate::parsing::recovery::SyntaxErrors;
+use crate::parsing::scanner::Scanner;
+use crate::parsing::token::{LexicalError, Token};
+use crate::semantic::collector::ErrorCollector;
+use crate::semantic::severity::Level;
+use crate::symbol::{sym, Symbol};
+
+const PROMPT: &str = "cool> ";
+const CONTINUATION: &str = "  ... ";
+
+/// What became of one input.
+#[derive(Debug, PartialEq)]
+pub enum Outcome {
+    /// Classes added to the session, in input order
+    Defined(Vec<Symbol>),
+    /// The value of an expression, as `interp::call` shows it, and its static type
+    Value(String, Symbol),
+    /// The input stops early and continues on the next line
+    Incomplete,
+    /// The input has errors, which were reported on stderr
+    Rejected,
+    /// Evaluation stopped with a runtime error
+    Failed(RuntimeError),
+}
+
+/// The classes defined so far, and the inputs they came from.
+pub struct Session {
+    compiler: Compiler,
+    classes: Vec<Class>,
+    /// Every input that was checked, for diagnostics to point into. Classes
+    /// and expressions are tagged with the index of theirs.
+    inputs: Vec<String>,
+}
+
+impl Session {
+    pub fn new(compiler: Compiler) -> Self {
+        Session { compiler, classes: Vec::new(), inputs: Vec::new() }
+    }
+
+    /// Handle `source`, one input. A program run by it reads `input` and
+    /// writes `output`.
+    pub fn eval(&mut self, source: &str, input: &mut (dyn BufRead + Send), output: &mut (dyn Write + Send)) -> Outcome {
+        let file = self.inputs.len();
+        if matches!(Scanner::new(source).next(), Some(Ok((Token::Class_, _)))) {
+            let mut defined = match self.compiler.parse(source) {
+                Ok(classes) => classes,
+                Err(err) => return self.rejected(source, err),
+            };
+            defined.iter_mut().for_each(|class| class.set_file(file));
+            let names = defined.iter().map(|c| c.name).collect();
+            let mut program = self.classes.clone();
+            program.extend(defined);
+            self.inputs.push(source.to_string());
+            if self.check(&program).is_none() {
+                return Outcome::Rejected;
+            }
+            self.classes = program;
+            return Outcome::Defined(names);
+        }
+
+        let body = match self.compiler.parse_expression(source.trim_end().trim_end_matches(';')) {
+            Ok(body) => body,
+            Err(err) => return self.rejected(source, err),
+        };
+        let mut scratch = Class::new(Symbol::intern(SCRATCH), Some(sym::IO), vec![Feature::new_method(
+            Symbol::intern(SCRATCH_METHOD),
+            Vec::new(),
+            sym::OBJECT,
+            body,
+        )]);
+        scratch.set_file(file);
+        let mut program = self.classes.clone();
+        program.push(scratch);
+        self.inputs.push(source.to_string());
+        let Some(typed) = self.check(&program) else {
+            return Outcome::Rejected;
+        };
+        let ty = typed
+            .iter()
+            .find(|c| c.name.as_str() == SCRATCH)
+            .and_then(|c| match &c.feature_list[0] {
+                Feature::Method(_, _, _, body, _) => body.static_type,
+                Feature::Attribute(_) => None,
+            })
+            .unwrap_or(sym::OBJECT);
+        let ctx = self.compiler.context(&program);
+        match interp::call(&ctx, Symbol::intern(SCRATCH), Symbol::intern(SCRATCH_METHOD), input, output) {
+            Ok(value) => Outcome::Value(value, ty),
+            Err(err) => Outcome::Failed(err),
+        }
+    }
+
+    /// Run the semantic checks on `program`, with a stand-in `Main` if it
+    /// has none, reporting what they find. The typed classes if it passed.
+    fn check(&self, program: &[Class]) -> Option<Vec<Class>> {
+        let mut checked = program.to_vec();
+        if !program.iter().any(|c| c.name == sym::MAIN_CLASS) {
+            let main = TypedExpr::new(Expr::Identifier(sym::SELF), 0);
+            let mut stand_in =
+                Class::new(sym::MAIN_CLASS, None, vec![Feature::new_method(sym::MAIN_METHOD, Vec::new(), sym::OBJECT, main)]);
+            // In no input, so diagnostics never point into it
+            stand_in.set_file(usize::MAX);
+            checked.push(stand_in);
+        }
+        let mut ec = ErrorCollector::default();
+        let typed = self.compiler.analyze(&checked, &mut ec);
+        // Warnings about earlier inputs were shown with them
+        let latest = self.inputs.len() - 1;
+        let mut diagnostics = ec.diagnostics();
+        diagnostics.retain(|d| d.level == Level::Deny || d.file.is_none_or(|file| file == latest));
+        if self.report(&diagnostics) {
+            return None;
+        }
+        typed
+    }
+
+    /// Report why `source`, the next input, did not parse, unless it only
+    /// stops early.
+    fn rejected(&mut self, source: &str, err: eyre::Report) -> Outcome {
+        let Some(SyntaxErrors(errors)) = err.downcast_ref::<SyntaxErrors>() else {
+            eprintln!("error: {:#}", err);
+            return Outcome::Rejected;
+        };
+        let stops_early = |err: &_| {
+            matches!(
+                err,
+                ParseError::UnrecognizedEof { .. } | ParseError::User { error: LexicalError::UnterminatedComment(_) }
+            )
+        };
+        if errors.iter().any(stops_early) {
+            return Outcome::Incomplete;
+        }
+        let file = self.inputs.len();
+        let diagnostics: Vec<Diagnostic> = errors.iter().map(|err| Diagnostic::syntax(err, file, source)).collect();
+        self.inputs.push(source.to_string());
+        self.report(&diagnostics);
+        Outcome::Rejected
+    }
+
+    /// Print `diagnostics`; `true` if any is an error.
+    fn report(&self, diagnostics: &[Diagnostic]) -> bool {
+        let names: Vec<String> = (1..=self.inputs.len()).map(|i| format!("<input {}>", i)).collect();
+        let files: Vec<SourceFile> =
+            self.inputs.iter().zip(&names).map(|(text, name)| SourceFile { name, text }).collect();
+        let mut reporter = Reporter::new(&files);
+        for diagnostic in diagnostics {
+            reporter.report(diagnostic);
+        }
+        reporter.has_errors()
+    }
+}
+
+/// The class expressions are evaluated in. Its name cannot be written in
+/// COOL, so it never clashes with the user's classes.
+const SCRATCH: &str = "_Scratch";
+const SCRATCH_METHOD: &str = "_eval";
+
+/// Read inputs from `input` until it ends or `:quit`, printing prompts and
+/// results to `output`. Programs read from and write to the same streams.
+pub fn run(compiler: Compiler, input: &mut (dyn BufRead + Send), output: &mut (dyn Write + Send)) -> io::Result<()> {
+    let mut session = Session::new(compiler);
+    let mut pending = String::new();
+    loop {
+        write!(output, "{}", if pending.is_empty() { PROMPT } else { CONTINUATION })?;
+        output.flush()?;
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            writeln!(output)?;
+            return Ok(());
+        }
+        if pending.is_empty() {
+            match line.trim() {
+                "" => continue,
+                ":quit" | ":q" => return Ok(()),
+                _ => {}
+            }
+        }
+
+        // An empty line submits whatever is pending, complete or not
+        let forced = line.trim().is_empty();
+        pending.push_str(&line);
+        let outcome = session.eval(&pending, input, output);
+        match outcome {
+            Outcome::Incomplete if !forced => continue,
+            Outcome::Incomplete => eprintln!("error: unexpected end of input"),
+            Outcome::Defined(names) => {
+                for name in names {
+                    writeln!(output, "class {}", name)?;
+                }
+            }
+            // The scratch object itself, as returned by `out_string`
+            Outcome::Value(_, sym::SELF_TYPE) => {}
+            Outcome::Value(value, ty) => writeln!(output, "{} : {}", value, ty)?,
+            Outcome::Rejected => {}
+            Outcome::Failed(err) => eprintln!("error: {}", err),
+        }
+        pending.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn transcript(lines: &str) -> String {
+        let mut input = io::Cursor::new(lines.as_bytes().to_vec());
+        let mut output = Vec::new();
+        run(Compiler::default(), &mut input, &mut output).unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn test_session() {
+        let output = transcript(
+            "1 + 2\n\
+             class Counter {\n\
+                 n : Int;\n\
+                 inc() : SELF_TYPE { { n <- n + 1; self; } };\n\
+                 get() : Int { n };\n\
+             };\n\
+             (new Counter).inc().inc().get()\n\
+             let c : Counter <- new Counter in\n\
+                 c.inc()\n\
+             out_string(\"hi\\n\")\n\
+             \"a\".concat(1)\n\
+             :quit\n",
+        );
+        assert_eq!(
+            output,
+            "cool> 3 : Int\n\
+             cool>   ...   ...   ...   ... class Counter\n\
+             cool> 2 : Int\n\
+             cool>   ... <Counter> : Counter\n\
+             cool> hi\n\
+             cool> cool> "
+        );
+    }
+
+    #[test]
+    fn test_failed_input_is_forgotten() {
+        let mut session = Session::new(Compiler::default());
+        let (mut input, mut output) = (io::empty(), io::sink());
+        assert_eq!(session.eval("class A { f() : Int { true }; };", &mut input, &mut output), Outcome::Rejected);
+        assert_eq!(session.eval("class A { f() : Int { 1 }; };", &mut input, &mut output), Outcome::Defined(vec![Symbol::intern("A")]));
+        assert_eq!(session.eval("(new A).f() + 1;", &mut input, &mut output), Outcome::Value("2".to_string(), sym::INT));
+        let outcome = session.eval("(new A).f() / 0", &mut input, &mut output);
+        assert!(matches!(&outcome, Outcome::Failed(err) if err.message == "Division by zero"), "{:?}", outcome);
+    }
+}
@@ -1,75 +1,85 @@
 use std::collections::{HashMap, HashSet};
-use crate::ast::Class;
-use crate::semantic::errors::SemanticError::*;
+use std::fmt::Write;
+use crate::ast::{Feature, Location};
+use crate::symbol::{sym, Symbol};
+use crate::semantic::errors::SemanticError::{self, *};
 use crate::semantic::collector::ErrorCollector;
+use crate::semantic::context::AnalysisContext;
 
-/// 1) Verifies duplicate class names, undefined parents, forbidden basic‐type inheritance,
+/// 1) Verifies duplicate class names and `SELF_TYPE` as one, undefined parents, forbidden basic‐type inheritance,
 ///    and genuine inheritance‐cycles (excluding the trivial Object→Object loop).
-pub fn check_inheritance(classes: &[Class], ec: &mut ErrorCollector) {
+pub fn check_inheritance(ctx: &AnalysisContext<'_>, ec: &mut ErrorCollector) {
     // 1.1) Detect duplicate class names
-    let mut seen_names: HashSet<&str> = HashSet::new();
-    for c in classes {
-        if !seen_names.insert(&c.name) {
-            ec.add(DuplicateClass { class: c.name.clone() });
+    let mut seen_names: HashSet<Symbol> = HashSet::new();
+    for c in ctx.all_classes() {
+        if c.name == sym::SELF_TYPE {
+            ec.add(SelfTypeClass { line: c.location.line, span: c.location.span });
+        } else if !seen_names.insert(c.name) {
+            ec.add(DuplicateClass { class: c.name, line: c.location.line, span: c.location.span });
         }
     }
 
     // 1.2) Build a parent_map for every class (treat “no parent” as inheriting Object).
     //      Except for "Object" itself, we always map c.name → parent_name.
-    let mut parent_map: HashMap<&str, &str> = HashMap::new();
-    for c in classes {
-        let parent = c.inherits.as_deref().unwrap_or("Object");
+    let mut parent_map: HashMap<Symbol, Symbol> = HashMap::new();
+    for c in ctx.all_classes() {
+        let parent = c.inherits.unwrap_or(sym::OBJECT);
         // Inheriting from Int, String, Bool, or SELF_TYPE is illegal
-        if matches!(parent, "Int" | "String" | "Bool" | "SELF_TYPE") {
+        if matches!(parent, sym::INT | sym::STRING | sym::BOOL | sym::SELF_TYPE) {
             ec.add(InheritBasicType {
-                class: c.name.clone(),
-                parent: parent.to_string(),
+                class: c.name,
+                parent,
+                line: c.location.line,
+                span: c.location.span,
             });
         }
-        if c.name != "Object" {
-            parent_map.insert(&c.name, parent);
+        if c.name != sym::OBJECT {
+            parent_map.insert(c.name, parent);
         }
     }
     // 1.3) Make sure Object → Object exists so that any chain landing on Object stops.
-    if !parent_map.contains_key("Object") {
-        parent_map.insert("Object", "Object");
-    }
+    parent_map.entry(sym::OBJECT).or_insert(sym::OBJECT);
 
     // 1.4) Check for undefined parents (except “Object,” which we assume always exists)
-    let defined: HashSet<&str> = classes.iter().map(|c| c.name.as_str()).collect();
+    let defined: HashSet<Symbol> = ctx.all_classes().map(|c| c.name).collect();
+    // The header of each class's first definition, for the errors below
+    let mut headers: HashMap<Symbol, Location> = HashMap::new();
+    for c in ctx.all_classes() {
+        headers.entry(c.name).or_insert(c.location);
+    }
     for (&cls, &parent) in parent_map.iter() {
-        if parent != "Object" && !defined.contains(parent) {
+        // SELF_TYPE is no class, but inheriting it was reported above
+        if parent != sym::OBJECT && parent != sym::SELF_TYPE && !defined.contains(&parent) {
             ec.add(UndefinedParent {
-                class: cls.to_string(),
-                parent: parent.to_string(),
+                class: cls,
+                parent,
+                line: headers[&cls].line,
+                span: headers[&cls].span,
             });
         }
     }
 
     // 1.5) Detect real inheritance cycles using Floyd’s algorithm.
     //      We ignore the trivial “Object→Object” loop.
-    for c in classes {
-        if c.name == "Object" {
+    let mut reported: HashSet<Symbol> = HashSet::new();
+    for c in ctx.all_classes() {
+        if c.name == sym::OBJECT {
             continue;
         }
 
         // Start both “tortoise” and “hare” at c.name
-        let mut tortoise = c.name.as_str();
-        let mut hare = c.name.as_str();
-
-        loop {
-            // Move tortoise one step
-            if let Some(&next_t) = parent_map.get(tortoise) {
-                tortoise = next_t;
-            } else {
-                // No further parent → no cycle along this chain
-                break;
-            }
+        let mut tortoise = c.name;
+        let mut hare = c.name;
+
+        // Move tortoise one step; with no further parent there is no
+        // cycle along this chain
+        while let Some(&next_t) = parent_map.get(&tortoise) {
+            tortoise = next_t;
 
             // Move hare two steps; if any step fails, stop
             let mut advance_ok = true;
             for _ in 0..2 {
-                if let Some(&next_h) = parent_map.get(hare) {
+                if let Some(&next_h) = parent_map.get(&hare) {
                     hare = next_h;
                 } else {
                     advance_ok = false;
@@ -83,13 +93,13 @@ pub fn check_inheritance(classes: &[Class], ec: &mut ErrorCollector) {
 
             // If both meet and neither is “Object,” that is a real cycle
             if tortoise == hare {
-                if tortoise != "Object" {
+                if tortoise != sym::OBJECT {
                     // Reconstruct the cycle path
                     let mut cycle = Vec::new();
                     let mut start = tortoise;
                     loop {
-                        cycle.push(start.to_string());
-                        if let Some(&p) = parent_map.get(start) {
+                        cycle.push(start);
+                        if let Some(&p) = parent_map.get(&start) {
                             start = p;
                         } else {
                             break;
@@ -98,16 +108,112 @@ pub fn check_inheritance(classes: &[Class], ec: &mut ErrorCollector) {
                             break;
                         }
                     }
-                    cycle.push(tortoise.to_string()); // close the loop
-                    ec.add(InheritanceCycle { cycle });
+                    // Start at the least name, so that the cycle is
+                    // reported once, from whichever class reached it
+                    let least = (0..cycle.len()).min_by_key(|&i| cycle[i].as_str()).unwrap_or(0);
+                    cycle.rotate_left(least);
+                    if reported.insert(cycle[0]) {
+                        cycle.push(cycle[0]); // close the loop
+                        let Location { line, span } = headers[&cycle[0]];
+                        ec.add(InheritanceCycle { cycle, line, span });
+                    }
                 }
                 break;
             }
 
             // If either pointer has reached “Object,” no real cycle
-            if tortoise == "Object" || hare == "Object" {
+            if tortoise == sym::OBJECT || hare == sym::OBJECT {
                 break;
             }
         }
     }
 }
+
+/// 2) The inheritance tree in Graphviz's DOT language, built-ins (shaded)
+///    included: a box per class with its number of methods, and an edge from
+///    each parent to its children. `errors` are what `check_inheritance`
+///    reported: the edges from an undefined parent and those on a cycle are red.
+pub fn hierarchy_dot(ctx: &AnalysisContext<'_>, errors: &[SemanticError]) -> String {
+    let mut orphans: HashSet<Symbol> = HashSet::new();
+    let mut on_cycle: HashSet<Symbol> = HashSet::new();
+    for err in errors {
+        match err {
+            UndefinedParent { class, .. } => {
+                orphans.insert(*class);
+            }
+            InheritanceCycle { cycle, .. } => on_cycle.extend(cycle),
+            _ => {}
+        }
+    }
+
+    let mut out = String::from("digraph hierarchy {\n    node [shape=box, fontname=monospace];\n");
+    // A class defined twice is drawn once, at its first definition
+    let mut drawn: HashSet<Symbol> = HashSet::new();
+    let classes = ctx.builtins.iter().map(|c| (c, true)).chain(ctx.classes.iter().map(|c| (c, false)));
+    for (c, builtin) in classes {
+        if !drawn.insert(c.name) {
+            continue;
+        }
+        let methods = c.feature_list.iter().filter(|f| matches!(f, Feature::Method(..))).count();
+        let plural = if methods == 1 { "" } else { "s" };
+        let style = if builtin { ", style=filled, fillcolor=lightgray" } else { "" };
+        writeln!(out, "    \"{}\" [label=\"{}\\n{} method{}\"{}];", c.name, c.name, methods, plural, style).unwrap();
+    }
+    for c in ctx.all_classes() {
+        if let Some(parent) = c.inherits.filter(|parent| drawn.insert(*parent)) {
+            writeln!(out, "    \"{}\" [label=\"{}\\n(undefined)\", style=dashed, color=red];", parent, parent).unwrap();
+        }
+    }
+    for c in ctx.all_classes().filter(|c| c.name != sym::OBJECT) {
+        let parent = c.inherits.unwrap_or(sym::OBJECT);
+        let red = orphans.contains(&c.name) || (on_cycle.contains(&c.name) && on_cycle.contains(&parent));
+        let color = if red { " [color=red]" } else { "" };
+        writeln!(out, "    \"{}\" -> \"{}\"{};", parent, c.name, color).unwrap();
+    }
+    out.push_str("}\n");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::semantic::collector::ErrorCollector;
+
+    fn dot(source: &str) -> String {
+        let classes = crate::parse_program(source).unwrap();
+        let ctx = AnalysisContext::new(crate::prelude(), &classes);
+        let mut ec = ErrorCollector::default();
+        check_inheritance(&ctx, &mut ec);
+        hierarchy_dot(&ctx, ec.reported())
+    }
+
+    #[test]
+    fn test_cycle_reported_once() {
+        // Every member, and E hanging off the cycle, reaches it
+        let source = "class C inherits B { };\nclass D inherits C { };\nclass B inherits D { };\nclass E inherits D { };";
+        let classes = crate::parse_program(source).unwrap();
+        let ctx = AnalysisContext::new(crate::prelude(), &classes);
+        let mut ec = ErrorCollector::default();
+        check_inheritance(&ctx, &mut ec);
+        let messages: Vec<String> = ec.errors.iter().map(ToString::to_string).collect();
+        assert_eq!(messages, ["[line 3] Inheritance cycle detected: B → D → C → B"]);
+    }
+
+    #[test]
+    fn test_hierarchy_dot() {
+        let out = dot("class Main inherits IO { main() : Object { 0 }; f() : Int { 1 }; };");
+        assert!(out.starts_with("digraph hierarchy {\n"), "{}", out);
+        assert!(out.contains("    \"IO\" [label=\"IO\\n4 methods\", style=filled, fillcolor=lightgray];\n"), "{}", out);
+        assert!(out.contains("    \"Main\" [label=\"Main\\n2 methods\"];\n"), "{}", out);
+        assert!(out.contains("    \"Object\" -> \"IO\";\n    \"Object\" -> \"String\";\n"), "{}", out);
+        assert!(out.contains("    \"IO\" -> \"Main\";\n"), "{}", out);
+        assert!(!out.contains("red"), "{}", out);
+
+        let out = dot("class A inherits B { }; class B inherits A { }; class C inherits A { }; class D inherits Nope { };");
+        assert!(out.contains("    \"Nope\" [label=\"Nope\\n(undefined)\", style=dashed, color=red];\n"), "{}", out);
+        assert!(out.contains("    \"Nope\" -> \"D\" [color=red];\n"), "{}", out);
+        assert!(out.contains("    \"B\" -> \"A\" [color=red];\n    \"A\" -> \"B\" [color=red];\n"), "{}", out);
+        // C only hangs off the cycle
+        assert!(out.contains("    \"A\" -> \"C\";\n"), "{}", out);
+    }
+}
@@ -1,68 +1,68 @@
 // src/semantic/class_table.rs
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use crate::ast::{Class, Feature, ArgDecl, VarDecl};
+use crate::symbol::{sym, Symbol};
 
 /// Entrada para a tabela de lookup de classes
 #[derive(Debug)]
 pub struct ClassInfo<'a> {
-    pub ast: &'a Class,
-    pub parent: String,
+    /// `None` para o Object implícito, quando o programa não o define
+    pub ast: Option<&'a Class>,
+    pub parent: Symbol,
     /// Lista de (nome_atributo, tipo_atributo)
-    pub attributes: Vec<(&'a str, &'a str)>,
+    pub attributes: Vec<(Symbol, Symbol)>,
     /// Lista de (nome_metodo, tipo_retorno, tipos_parametros)
-    pub methods: Vec<(&'a str, &'a str, Vec<&'a str>)>,
+    pub methods: Vec<(Symbol, Symbol, Vec<Symbol>)>,
 }
 
-pub fn build_class_table<'a>(classes: &'a [Class]) -> HashMap<String, ClassInfo<'a>> {
+pub fn build_class_table<'a>(classes: &'a [Class]) -> HashMap<Symbol, ClassInfo<'a>> {
     let mut table = HashMap::new();
 
     // 1) Inserir todas as classes do usuário (ou builtins já injetadas), com vetores vazios
     for c in classes {
-        let parent = c.inherits.clone().unwrap_or_else(|| "Object".into());
+        let parent = c.inherits.unwrap_or(sym::OBJECT);
         let info = ClassInfo {
-            ast: c,
+            ast: Some(c),
             parent,
             attributes: Vec::new(),
             methods: Vec::new(),
         };
-        table.insert(c.name.clone(), info);
+        table.insert(c.name, info);
     }
 
     // 2) Garantir que “Object” exista (caso o usuário não tenha definido)
-    if !table.contains_key("Object") {
-        let boxed = Box::new(Class {
-            name: "Object".to_string(),
-            inherits: None,
-            feature_list: Vec::new(),
-        });
-        let dummy_obj: &'static Class = Box::leak(boxed);
+    table.entry(sym::OBJECT).or_insert_with(|| ClassInfo {
+        ast: None,
+        parent: sym::OBJECT,
+        attributes: Vec::new(),
+        methods: Vec::new(),
+    });
 
-        let info = ClassInfo {
-            ast: dummy_obj,
-            parent: "Object".into(),
-            attributes: Vec::new(),
-            methods: Vec::new(),
-        };
-        table.insert("Object".into(), info);
-    }
-
-    // 3) Agora que todas as entradas existem, varremos de novo para preencher attributes e methods
+    // 3) Agora que todas as entradas existem, varremos de novo para preencher attributes e methods.
+    //    Nomes duplicados ficam com a primeira declaração; o erro é reportado em `symbols`.
     for c in classes {
         if let Some(info) = table.get_mut(&c.name) {
+            let mut attrs_seen = HashSet::new();
+            let mut methods_seen = HashSet::new();
             for feat in &c.feature_list {
                 match feat {
                     Feature::Attribute(VarDecl { oid, tid, .. }) => {
                         // Atributo: (nome, tipo)
-                        info.attributes.push((oid.as_str(), tid.as_str()));
+                        if attrs_seen.insert(*oid) {
+                            info.attributes.push((*oid, *tid));
+                        }
                     }
-                    Feature::Method(name, args, ret_type, _body) => {
+                    Feature::Method(name, args, ret_type, _body, _) => {
+                        if !methods_seen.insert(*name) {
+                            continue;
+                        }
                         // Método: (nome, retorno, [tipos dos parâmetros])
-                        let param_types: Vec<&str> = args
+                        let param_types: Vec<Symbol> = args
                             .iter()
-                            .map(|ArgDecl { tid, .. }| tid.as_str())
+                            .map(|ArgDecl { tid, .. }| *tid)
                             .collect();
-                        info.methods.push((name.as_str(), ret_type.as_str(), param_types));
+                        info.methods.push((*name, *ret_type, param_types));
                     }
                 }
             }
@@ -1,22 +1,56 @@
+use std::collections::HashSet;
+
+use crate::diagnostic::Diagnostic;
 use crate::semantic::errors::SemanticError;
+use crate::semantic::severity::{Level, Levels};
 
 #[derive(Debug, Default)]
 pub struct ErrorCollector {
+    /// Diagnostics at `Level::Deny`; any of these rejects the program
     pub errors: Vec<SemanticError>,
+    /// Diagnostics at `Level::Warn`
+    pub warnings: Vec<SemanticError>,
+    /// Every diagnostic in report order, whatever its level
+    reported: Vec<SemanticError>,
+    /// Diagnostics by their file and text, which leaves out the span, so a
+    /// mistake repeated on one line is reported once
+    seen: HashSet<(Option<usize>, String)>,
+    levels: Levels,
 }
 
 impl ErrorCollector {
+    pub fn with_levels(levels: Levels) -> Self {
+        ErrorCollector { levels, ..ErrorCollector::default() }
+    }
+
+    /// Record `err` at its level unless an identical diagnostic was already
+    /// reported.
     pub fn add(&mut self, err: SemanticError) {
-        self.errors.push(err);
+        if !self.seen.insert((err.file(), err.to_string())) {
+            return;
+        }
+        match self.levels.level(&err) {
+            Level::Deny => self.errors.push(err.clone()),
+            Level::Warn => self.warnings.push(err.clone()),
+            Level::Allow => {}
+        }
+        self.reported.push(err);
+    }
+
+    /// Every diagnostic reported, including allowed ones, so a cached result
+    /// can be replayed under different levels.
+    pub fn reported(&self) -> &[SemanticError] {
+        &self.reported
     }
 
     pub fn has_errors(&self) -> bool {
         !self.errors.is_empty()
     }
 
-    pub fn report_all(&self) {
-        for e in &self.errors {
-            eprintln!("{}", e);
-        }
+    /// Every error, then every warning, for a `Reporter`.
+    pub fn diagnostics(&self) -> Vec<Diagnostic> {
+        let errors = self.errors.iter().map(|e| Diagnostic::semantic(e, Level::Deny));
+        let warnings = self.warnings.iter().map(|w| Diagnostic::semantic(w, Level::Warn));
+        errors.chain(warnings).collect()
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,65 @@
+use std::collections::HashMap;
+
+use crate::ast::Class;
+use crate::semantic::class_table::{build_class_table, ClassInfo};
+use crate::semantic::hierarchy::ClassHierarchy;
+use crate::symbol::{sym, Symbol};
+
+/// State shared by every semantic phase.
+///
+/// The driver builds it once and hands each phase a reference, so the class
+/// table is never rebuilt. The built-in classes are an overlay: they keep
+/// their own table, consulted before the user's, and the user's class list
+/// is never modified to make room for them.
+#[derive(Debug)]
+pub struct AnalysisContext<'a> {
+    /// Built-in classes (Object, IO, String, Int, Bool)
+    pub builtins: &'a [Class],
+    /// Classes declared by the program, in source order
+    pub classes: &'a [Class],
+    pub builtin_table: HashMap<Symbol, ClassInfo<'a>>,
+    pub class_table: HashMap<Symbol, ClassInfo<'a>>,
+    /// Precomputed subtype/join queries over the inheritance tree
+    pub hierarchy: ClassHierarchy,
+}
+
+impl<'a> AnalysisContext<'a> {
+    pub fn new(builtins: &'a [Class], classes: &'a [Class]) -> Self {
+        AnalysisContext {
+            builtins,
+            classes,
+            builtin_table: build_class_table(builtins),
+            class_table: build_class_table(classes),
+            hierarchy: ClassHierarchy::new(builtins.iter().chain(classes)),
+        }
+    }
+
+    /// Built-ins first, then the program's classes.
+    pub fn all_classes(&self) -> impl Iterator<Item = &'a Class> {
+        self.builtins.iter().chain(self.classes)
+    }
+
+    /// Look up a class, giving built-ins precedence over user declarations.
+    pub fn class_info(&self, name: Symbol) -> Option<&ClassInfo<'a>> {
+        self.builtin_table
+            .get(&name)
+            .or_else(|| self.class_table.get(&name))
+    }
+
+    pub fn is_class(&self, name: Symbol) -> bool {
+        self.class_info(name).is_some()
+    }
+
+    /// `name` and then its ancestors, nearest first, ending at Object or at
+    /// the first undefined class. The inheritance checks must have passed,
+    /// as an inheritance cycle never ends.
+    pub fn lineage(&self, name: Symbol) -> impl Iterator<Item = (Symbol, &ClassInfo<'a>)> + '_ {
+        let mut next = Some(name);
+        std::iter::from_fn(move || {
+            let name = next?;
+            let info = self.class_info(name)?;
+            next = (name != sym::OBJECT).then_some(info.parent);
+            Some((name, info))
+        })
+    }
+}
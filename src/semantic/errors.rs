@@ -1,97 +1,474 @@
 use std::fmt;
 
-#[derive(Debug)]
+use serde::{Deserialize, Serialize};
+
+use crate::parsing::token::{Position, Span};
+use crate::symbol::Symbol;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum SemanticError {
+    // Class-level errors
+    // Each has the line and span of the declaration it is about: the
+    // class header, the attribute, the method signature or the formal
+
     // Inheritance graph errors
-    DuplicateClass { class: String },
-    InheritanceCycle { cycle: Vec<String> },
-    UndefinedParent { class: String, parent: String },
-    InheritBasicType { class: String, parent: String },
+    DuplicateClass { class: Symbol, line: usize, span: Span },
+    InheritanceCycle { cycle: Vec<Symbol>, line: usize, span: Span },
+    UndefinedParent { class: Symbol, parent: Symbol, line: usize, span: Span },
+    InheritBasicType { class: Symbol, parent: Symbol, line: usize, span: Span },
+    /// A class is declared with the name `SELF_TYPE`
+    SelfTypeClass { line: usize, span: Span },
 
     // Attribute/method errors
-    DuplicateAttribute { class: String, attr: String },
-    DuplicateMethod { class: String, method: String },
+    DuplicateAttribute { class: Symbol, attr: Symbol, line: usize, span: Span },
+    DuplicateMethod { class: Symbol, method: Symbol, line: usize, span: Span },
+    /// `attr` is declared in `class` and in its ancestor `parent`
+    AttributeOverride { class: Symbol, attr: Symbol, parent: Symbol, line: usize, span: Span },
+    SelfAttribute { class: Symbol, line: usize, span: Span },
+    SelfFormal { class: Symbol, method: Symbol, line: usize, span: Span },
+    /// Two formals of `method` in `class` are named `formal`
+    DuplicateFormal { class: Symbol, method: Symbol, formal: Symbol, line: usize, span: Span },
+    /// `formal` of `method` in `class` is declared `SELF_TYPE`
+    SelfTypeFormal { class: Symbol, method: Symbol, formal: Symbol, line: usize, span: Span },
+    /// `method` in `class` overrides the definition in its nearest
+    /// ancestor that has one, `parent`, with other formal or return types
     MethodOverrideMismatch {
-        class: String,
-        method: String,
-        parent: String,
-        expected: Vec<String>,
-        found: Vec<String>,
+        class: Symbol,
+        method: Symbol,
+        parent: Symbol,
+        expected: Vec<Symbol>,
+        found: Vec<Symbol>,
+        expected_return: Symbol,
+        found_return: Symbol,
+        line: usize, span: Span,
     },
 
+    // Program entry point errors
+    NoMainClass,
+    /// At the header of class Main
+    NoMainMethod { line: usize, span: Span },
+
     // Type errors in expressions
-    UndefinedClass { type_name: String, line: usize },
-    UndefinedVariable { name: String, line: usize },
+    // Each has the line and span of the expression it is about
+    UndefinedClass { type_name: Symbol, line: usize, span: Span },
+    /// `let_binding` says whether `name` is bound by an enclosing `let`,
+    /// but only after the initializer it is used in
+    UndefinedVariable { name: Symbol, let_binding: Option<LetBinding>, line: usize, span: Span },
     TypeMismatch {
-        expected: String,
-        found: String,
+        expected: Symbol,
+        found: Symbol,
         line: usize,
+        span: Span,
     },
     ArgumentCountMismatch {
-        method: String,
+        method: Symbol,
         expected: usize,
         found: usize,
         line: usize,
+        span: Span,
     },
-    DispatchOnVoid { line: usize },
-    CaseOnVoid { line: usize },
-    NoBranchInCase { expr_type: String, line: usize },
+    /// `method` is defined neither in `class` nor in any of its ancestors
+    UndefinedMethod { class: Symbol, method: Symbol, line: usize, span: Span },
+    /// The target of `e@expected.f()` has type `found`, which does not conform to `expected`
+    StaticDispatchTypeError { expected: Symbol, found: Symbol, line: usize, span: Span },
+    /// No branch of a `case` matches the class of its value, `expr_type`:
+    /// not found by the checks, but the interpreter's runtime error
+    NoBranchInCase { expr_type: Symbol, line: usize, span: Span },
+    /// A second `case` branch for `type_name`, at that branch's expression
+    DuplicateCaseBranch { type_name: Symbol, line: usize, span: Span },
+    AssignToSelf { line: usize, span: Span },
+    /// At the `let` expression
+    LetBindsSelf { line: usize, span: Span },
+    /// At the branch's expression
+    CaseBindsSelf { line: usize, span: Span },
+    /// At the outermost expression nested more than `limit` levels deep,
+    /// which is not checked
+    NestedTooDeeply { limit: usize, line: usize, span: Span },
+
+    // Extensions
+    UnboundNative { class: Symbol, method: Symbol, line: usize, span: Span },
+
+    // Lints
+    UnusedLet { name: Symbol, line: usize, span: Span },
+    /// `attr` is never read in `class` or its subclasses
+    UnusedAttribute { class: Symbol, attr: Symbol, line: usize, span: Span },
+    /// The branch for `type_name` can never be chosen, given the static type
+    /// of the `case` expression and the other branches
+    UnreachableCaseBranch { type_name: Symbol, line: usize, span: Span },
+    /// At the first expression of a block that follows `abort()` or `throw`
+    DeadCode { line: usize, span: Span },
+    /// At the branch of an `if`, or the body of a `while`, that the
+    /// optimizer removed because the condition is always `value`
+    ConstantCondition { value: bool, line: usize, span: Span },
+    /// At a `case` expression of static type `expr_type` that no branch
+    /// matches, so that some values of the type abort the program
+    NonExhaustiveCase { expr_type: Symbol, line: usize, span: Span },
+    /// At a read of `attr` in an attribute initializer that runs before
+    /// the initializer of `attr`, its own included
+    UninitializedAttribute { attr: Symbol, line: usize, span: Span },
+    /// At a dispatch of `method` whose receiver is void whenever it runs:
+    /// a `let` variable with no initializer and no assignment, or a `while`
+    DispatchOnVoid { method: Symbol, line: usize, span: Span },
+    /// At a division whose divisor is the literal `0`
+    DivisionByZero { line: usize, span: Span },
 }
 
-impl fmt::Display for SemanticError {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+/// Where a `let` binds a name that its initializers use out of scope.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum LetBinding {
+    /// In the initializer of the binding itself
+    Own,
+    /// In the initializer of an earlier binding
+    Later,
+}
+
+impl SemanticError {
+    /// Stable identifier for this kind of diagnostic, e.g. `E0001`.
+    /// Codes are never reused, so tests and tools can match on them.
+    /// Errors have `E` codes and lints `W` codes.
+    pub fn code(&self) -> &'static str {
+        use SemanticError::*;
+        match self {
+            DuplicateClass { .. } => "E0001",
+            InheritanceCycle { .. } => "E0002",
+            UndefinedParent { .. } => "E0003",
+            InheritBasicType { .. } => "E0004",
+            DuplicateAttribute { .. } => "E0005",
+            DuplicateMethod { .. } => "E0006",
+            MethodOverrideMismatch { .. } => "E0007",
+            UndefinedClass { .. } => "E0008",
+            UndefinedVariable { .. } => "E0009",
+            TypeMismatch { .. } => "E0010",
+            ArgumentCountMismatch { .. } => "E0011",
+            // E0013 was once a check that a `case` expression's static type was not Object
+            NoBranchInCase { .. } => "E0014",
+            UnboundNative { .. } => "E0015",
+            NoMainClass => "E0016",
+            NoMainMethod { .. } => "E0017",
+            AttributeOverride { .. } => "E0018",
+            DuplicateCaseBranch { .. } => "E0019",
+            AssignToSelf { .. } => "E0020",
+            LetBindsSelf { .. } => "E0021",
+            CaseBindsSelf { .. } => "E0022",
+            SelfFormal { .. } => "E0023",
+            DuplicateFormal { .. } => "E0038",
+            SelfAttribute { .. } => "E0024",
+            UndefinedMethod { .. } => "E0025",
+            StaticDispatchTypeError { .. } => "E0026",
+            NestedTooDeeply { .. } => "E0039",
+            // E0040 and E0041 are lexical errors
+            SelfTypeClass { .. } => "E0042",
+            SelfTypeFormal { .. } => "E0043",
+            UnusedLet { .. } => "W0001",
+            UnusedAttribute { .. } => "W0002",
+            UnreachableCaseBranch { .. } => "W0003",
+            DeadCode { .. } => "W0004",
+            ConstantCondition { .. } => "W0005",
+            NonExhaustiveCase { .. } => "W0006",
+            UninitializedAttribute { .. } => "W0007",
+            // E0012 was once given to this, when it was an error no check reported
+            DispatchOnVoid { .. } => "W0008",
+            DivisionByZero { .. } => "W0009",
+        }
+    }
+
+    /// Source line, for all but program-wide errors and those about
+    /// declarations the compiler made (line 0).
+    pub fn line(&self) -> Option<usize> {
+        self.location().map(|(line, _)| line).filter(|&line| line > 0)
+    }
+
+    /// Source span, for errors raised inside parsed source.
+    pub fn span(&self) -> Option<Span> {
+        self.location().map(|(_, span)| span).filter(|span| !span.is_empty())
+    }
+
+    /// Input file the diagnostic is about, for all but program-wide errors.
+    pub fn file(&self) -> Option<usize> {
+        match self {
+            SemanticError::NoMainClass => None,
+            _ => self.location().map(|(_, span)| span.file),
+        }
+    }
+
+    fn location(&self) -> Option<(usize, Span)> {
+        use SemanticError::*;
+        match self {
+            UndefinedClass { line, span, .. }
+            | UndefinedVariable { line, span, .. }
+            | TypeMismatch { line, span, .. }
+            | ArgumentCountMismatch { line, span, .. }
+            | UndefinedMethod { line, span, .. }
+            | StaticDispatchTypeError { line, span, .. }
+            | NoBranchInCase { line, span, .. }
+            | DuplicateCaseBranch { line, span, .. }
+            | AssignToSelf { line, span }
+            | LetBindsSelf { line, span }
+            | CaseBindsSelf { line, span }
+            | NestedTooDeeply { line, span, .. }
+            | UnboundNative { line, span, .. }
+            | UnusedLet { line, span, .. }
+            | UnreachableCaseBranch { line, span, .. }
+            | DeadCode { line, span }
+            | ConstantCondition { line, span, .. }
+            | NonExhaustiveCase { line, span, .. }
+            | UninitializedAttribute { line, span, .. }
+            | DispatchOnVoid { line, span, .. }
+            | DivisionByZero { line, span }
+            | DuplicateClass { line, span, .. }
+            | InheritanceCycle { line, span, .. }
+            | UndefinedParent { line, span, .. }
+            | InheritBasicType { line, span, .. }
+            | SelfTypeClass { line, span }
+            | DuplicateAttribute { line, span, .. }
+            | DuplicateMethod { line, span, .. }
+            | AttributeOverride { line, span, .. }
+            | SelfAttribute { line, span, .. }
+            | SelfFormal { line, span, .. }
+            | DuplicateFormal { line, span, .. }
+            | SelfTypeFormal { line, span, .. }
+            | MethodOverrideMismatch { line, span, .. }
+            | UnusedAttribute { line, span, .. }
+            | NoMainMethod { line, span } => Some((*line, *span)),
+            NoMainClass => None,
+        }
+    }
+
+    /// Move the diagnostic from `from` to `to` in its file, with the class
+    /// it is about; see `Class::relocate`.
+    pub fn relocate(&mut self, from: Position, to: Position) {
+        if let Some((line, span)) = self.location_mut() {
+            crate::ast::relocate(line, span, from, to);
+        }
+    }
+
+    /// Say the diagnostic is about input file `file`, as `Class::set_file`
+    /// does for the class it is about.
+    pub fn set_file(&mut self, file: usize) {
+        if let Some((_, span)) = self.location_mut() {
+            span.file = file;
+        }
+    }
+
+    fn location_mut(&mut self) -> Option<(&mut usize, &mut Span)> {
         use SemanticError::*;
         match self {
-            DuplicateClass { class } => write!(f, "Duplicate class '{}'", class),
-            InheritanceCycle { cycle } => {
-                write!(f, "Inheritance cycle detected: {}", cycle.join(" → "))
+            UndefinedClass { line, span, .. }
+            | UndefinedVariable { line, span, .. }
+            | TypeMismatch { line, span, .. }
+            | ArgumentCountMismatch { line, span, .. }
+            | UndefinedMethod { line, span, .. }
+            | StaticDispatchTypeError { line, span, .. }
+            | NoBranchInCase { line, span, .. }
+            | DuplicateCaseBranch { line, span, .. }
+            | AssignToSelf { line, span }
+            | LetBindsSelf { line, span }
+            | CaseBindsSelf { line, span }
+            | NestedTooDeeply { line, span, .. }
+            | UnboundNative { line, span, .. }
+            | UnusedLet { line, span, .. }
+            | UnreachableCaseBranch { line, span, .. }
+            | DeadCode { line, span }
+            | ConstantCondition { line, span, .. }
+            | NonExhaustiveCase { line, span, .. }
+            | UninitializedAttribute { line, span, .. }
+            | DispatchOnVoid { line, span, .. }
+            | DivisionByZero { line, span }
+            | DuplicateClass { line, span, .. }
+            | InheritanceCycle { line, span, .. }
+            | UndefinedParent { line, span, .. }
+            | InheritBasicType { line, span, .. }
+            | SelfTypeClass { line, span }
+            | DuplicateAttribute { line, span, .. }
+            | DuplicateMethod { line, span, .. }
+            | AttributeOverride { line, span, .. }
+            | SelfAttribute { line, span, .. }
+            | SelfFormal { line, span, .. }
+            | DuplicateFormal { line, span, .. }
+            | SelfTypeFormal { line, span, .. }
+            | MethodOverrideMismatch { line, span, .. }
+            | UnusedAttribute { line, span, .. }
+            | NoMainMethod { line, span } => Some((line, span)),
+            NoMainClass => None,
+        }
+    }
+
+    /// The diagnostic without its line, for output that shows the location
+    /// separately.
+    pub fn message(&self) -> String {
+        use SemanticError::*;
+        match self {
+            DuplicateClass { class, .. } => format!("Duplicate class '{}'", class),
+            InheritanceCycle { cycle, .. } => {
+                let names: Vec<&str> = cycle.iter().map(|s| s.as_str()).collect();
+                format!("Inheritance cycle detected: {}", names.join(" → "))
             }
-            UndefinedParent { class, parent } => {
-                write!(f, "Class '{}' inherits from undefined parent '{}'", class, parent)
+            UndefinedParent { class, parent, .. } => {
+                format!("Class '{}' inherits from undefined parent '{}'", class, parent)
             }
-            InheritBasicType { class, parent } => {
-                write!(f, "Class '{}' cannot inherit from basic type '{}'", class, parent)
+            InheritBasicType { class, parent, .. } => {
+                format!("Class '{}' cannot inherit from basic type '{}'", class, parent)
             }
-            DuplicateAttribute { class, attr } => {
-                write!(f, "In class '{}', attribute '{}' is duplicated", class, attr)
+            SelfTypeClass { .. } => "A class cannot be named 'SELF_TYPE'".to_string(),
+            DuplicateAttribute { class, attr, .. } => {
+                format!("In class '{}', attribute '{}' is duplicated", class, attr)
             }
-            DuplicateMethod { class, method } => {
-                write!(f, "In class '{}', method '{}' is duplicated", class, method)
+            DuplicateMethod { class, method, .. } => {
+                format!("In class '{}', method '{}' is duplicated", class, method)
             }
-            MethodOverrideMismatch { class, method, parent, expected, found } => write!(
-                f,
-                "Invalid override of method '{}' in '{}': parent '{}' signature = {:?}, found = {:?}",
-                method, class, parent, expected, found
-            ),
-            UndefinedClass { type_name, line } => {
-                write!(f, "[line {}] Type '{}' is not defined", line, type_name)
+            AttributeOverride { class, attr, parent, .. } => {
+                format!("In class '{}', attribute '{}' is already inherited from '{}'", class, attr, parent)
+            }
+            SelfAttribute { class, .. } => format!("In class '{}', an attribute is named 'self'", class),
+            SelfFormal { class, method, .. } => {
+                format!("In class '{}', method '{}' has a formal parameter named 'self'", class, method)
             }
-            UndefinedVariable { name, line } => {
-                write!(f, "[line {}] Variable '{}' is not declared", line, name)
+            DuplicateFormal { class, method, formal, .. } => {
+                format!("In class '{}', method '{}' has more than one formal parameter named '{}'", class, method, formal)
             }
-            TypeMismatch { expected, found, line } => {
-                write!(
-                    f,
-                    "[line {}] Type mismatch: expected '{}', found '{}'",
-                    line, expected, found
+            SelfTypeFormal { class, method, formal, .. } => {
+                format!("In class '{}', formal parameter '{}' of method '{}' has type 'SELF_TYPE'", class, formal, method)
+            }
+            MethodOverrideMismatch { class, method, parent, expected, found, expected_return, found_return, .. } => {
+                let signature = |formals: &[Symbol], ret| {
+                    let formals: Vec<&str> = formals.iter().map(|s| s.as_str()).collect();
+                    format!("({}) : {}", formals.join(", "), ret)
+                };
+                format!(
+                    "Invalid override of method '{}' in '{}': '{}' defines it as {}, found {}",
+                    method,
+                    class,
+                    parent,
+                    signature(expected, expected_return),
+                    signature(found, found_return)
                 )
             }
-            ArgumentCountMismatch { method, expected, found, line } => write!(
-                f,
-                "[line {}] Method '{}' expects {} arguments, but {} were given",
-                line, method, expected, found
+            NoMainClass => "Class 'Main' is not defined".to_string(),
+            NoMainMethod { .. } => "Class 'Main' has no method 'main' taking no arguments".to_string(),
+            UndefinedClass { type_name, .. } => format!("Type '{}' is not defined", type_name),
+            UndefinedVariable { name, let_binding: None, .. } => format!("Variable '{}' is not declared", name),
+            UndefinedVariable { name, let_binding: Some(LetBinding::Own), .. } => {
+                format!("Variable '{}' is used in its own initializer", name)
+            }
+            UndefinedVariable { name, let_binding: Some(LetBinding::Later), .. } => {
+                format!("Variable '{}' is used before the `let` binding that declares it", name)
+            }
+            TypeMismatch { expected, found, .. } => {
+                format!("Type mismatch: expected '{}', found '{}'", expected, found)
+            }
+            ArgumentCountMismatch { method, expected, found, .. } => format!(
+                "Method '{}' expects {} arguments, but {} were given",
+                method, expected, found
             ),
-            DispatchOnVoid { line } => {
-                write!(f, "[line {}] Dispatch on void reference", line)
+            UndefinedMethod { class, method, .. } => {
+                format!("Method '{}' is not defined in class '{}'", method, class)
             }
-            CaseOnVoid { line } => {
-                write!(f, "[line {}] Case on void expression", line)
+            StaticDispatchTypeError { expected, found, .. } => {
+                format!("Static dispatch to '{}' on an expression of type '{}'", expected, found)
             }
-            NoBranchInCase { expr_type, line } => write!(
-                f,
-                "[line {}] No 'case' branch for dynamic type '{}'",
-                line, expr_type
+            NoBranchInCase { expr_type, .. } => {
+                format!("No 'case' branch for dynamic type '{}'", expr_type)
+            }
+            DuplicateCaseBranch { type_name, .. } => {
+                format!("Duplicate branch for type '{}' in 'case'", type_name)
+            }
+            AssignToSelf { .. } => "Cannot assign to 'self'".to_string(),
+            LetBindsSelf { .. } => "'self' cannot be bound in a 'let'".to_string(),
+            CaseBindsSelf { .. } => "'self' cannot be bound in a 'case' branch".to_string(),
+            NestedTooDeeply { limit, .. } => format!("Expression nested more than {} levels deep", limit),
+            UnboundNative { class, method, .. } => {
+                format!("Native method '{}.{}' matches no host function", class, method)
+            }
+            UnusedLet { name, .. } => format!("Let binding '{}' is never used", name),
+            UnusedAttribute { class, attr, .. } => {
+                format!("In class '{}', attribute '{}' is never read", class, attr)
+            }
+            UnreachableCaseBranch { type_name, .. } => {
+                format!("The 'case' branch for type '{}' is never taken", type_name)
+            }
+            DeadCode { .. } => "Unreachable expression".to_string(),
+            ConstantCondition { value, .. } => format!("Unreachable code: the condition is always {}", value),
+            NonExhaustiveCase { expr_type, .. } => {
+                format!("No 'case' branch matches values of type '{}'", expr_type)
+            }
+            UninitializedAttribute { attr, .. } => format!("Attribute '{}' is read before its initializer runs", attr),
+            DispatchOnVoid { method, .. } => format!("Dispatch of '{}' on a value that is always void", method),
+            DivisionByZero { .. } => "Division by zero".to_string(),
+        }
+    }
+
+    /// A hint on how to fix the problem, where there is a usual one.
+    pub fn help(&self) -> Option<String> {
+        use SemanticError::*;
+        match self {
+            DuplicateClass { .. } => Some("every class needs a name of its own".to_string()),
+            InheritanceCycle { .. } => Some("every chain of parents must end at Object".to_string()),
+            UndefinedParent { parent, .. } => Some(format!("define class '{}' or inherit from another class", parent)),
+            InheritBasicType { .. } => Some("Int, String, Bool and SELF_TYPE cannot be inherited from".to_string()),
+            SelfTypeClass { .. } => Some("`SELF_TYPE` stands for the class of `self`; choose another name".to_string()),
+            SelfTypeFormal { .. } => {
+                Some("only a method's return type may be `SELF_TYPE`; declare the formal with a class name".to_string())
+            }
+            DuplicateAttribute { .. } | DuplicateMethod { .. } | DuplicateFormal { .. } => {
+                Some("rename or remove one of them".to_string())
+            }
+            AttributeOverride { .. } => Some("attributes cannot be overridden; give this one another name".to_string()),
+            MethodOverrideMismatch { .. } => {
+                Some("an override must keep the formal and return types of the method it overrides".to_string())
+            }
+            NoMainClass => Some("execution starts with `(new Main).main()`".to_string()),
+            NoMainMethod { .. } => Some("add `main() : Object { ... }` to Main or one of its ancestors".to_string()),
+            UndefinedClass { .. } => None,
+            UndefinedVariable { let_binding: None, .. } => {
+                Some("declare it as an attribute, a formal, or with `let` or `case`".to_string())
+            }
+            UndefinedVariable { .. } => {
+                Some("a `let` binding is in scope only in the initializers after it and in the body".to_string())
+            }
+            TypeMismatch { expected, .. } => Some(format!("the type of this expression must conform to '{}'", expected)),
+            ArgumentCountMismatch { .. } | NoBranchInCase { .. } => None,
+            UndefinedMethod { class, .. } => Some(format!("define it in '{}' or one of its ancestors", class)),
+            StaticDispatchTypeError { expected, .. } => {
+                Some(format!("the expression before `@` must conform to '{}'", expected))
+            }
+            DuplicateCaseBranch { .. } => Some("each branch of a `case` must declare a different type".to_string()),
+            AssignToSelf { .. } => Some("`self` always refers to the object the method was called on".to_string()),
+            LetBindsSelf { .. } | CaseBindsSelf { .. } | SelfFormal { .. } | SelfAttribute { .. } => {
+                Some("`self` is reserved for the current object; choose another name".to_string())
+            }
+            NestedTooDeeply { .. } => Some("move parts of it into methods of their own".to_string()),
+            UnboundNative { .. } => Some("the host functions are listed in src/natives.rs".to_string()),
+            UnusedLet { .. } => Some("remove the binding, or silence the lint with `--allow unused_let`".to_string()),
+            UnusedAttribute { .. } => {
+                Some("remove the attribute, or silence the lint with `--allow unused_attribute`".to_string())
+            }
+            UnreachableCaseBranch { .. } => Some(
+                "no value of the expression's type matches it, or another branch always matches first".to_string(),
             ),
+            DeadCode { .. } => Some("the expression before it never returns".to_string()),
+            ConstantCondition { .. } => {
+                Some("remove the dead code, or silence the lint with `--allow constant_condition`".to_string())
+            }
+            NonExhaustiveCase { expr_type, .. } => Some(format!(
+                "add a branch for '{}' or one of its ancestors, or the `case` aborts when none matches",
+                expr_type
+            )),
+            UninitializedAttribute { attr, .. } => Some(format!(
+                "attributes are initialized in declaration order, so '{}' still has its default value here",
+                attr
+            )),
+            DispatchOnVoid { .. } => Some("the program stops with a runtime error when this dispatch runs".to_string()),
+            DivisionByZero { .. } => Some("the program stops with a runtime error when this division runs".to_string()),
+        }
+    }
+}
+
+/// The message, with `[line N]` in front for errors that have a line.
+impl fmt::Display for SemanticError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.line() {
+            Some(line) => write!(f, "[line {}] {}", line, self.message()),
+            None => write!(f, "{}", self.message()),
         }
     }
 }
@@ -0,0 +1,174 @@
+use std::collections::HashMap;
+
+use crate::ast::Class;
+use crate::symbol::{sym, Symbol};
+
+/// Precomputed view of the inheritance tree rooted at `Object`.
+///
+/// Classes are numbered in DFS preorder; each class also records the last
+/// preorder number inside its subtree, so `sub ≤ sup` holds exactly when
+/// `sub`'s number falls in `sup`'s interval. Each node keeps binary-lifting
+/// jump pointers (`up[k]` is the 2^k-th ancestor) for O(log n) joins.
+///
+/// Classes that are not reachable from `Object` (undefined parents, cycles)
+/// get no label; they only conform to themselves.
+#[derive(Debug, Default)]
+pub struct ClassHierarchy {
+    index: HashMap<Symbol, usize>,
+    nodes: Vec<Node>,
+}
+
+#[derive(Debug)]
+struct Node {
+    name: Symbol,
+    /// Last preorder number in this node's subtree.
+    end: usize,
+    depth: usize,
+    up: Vec<usize>,
+}
+
+impl ClassHierarchy {
+    pub fn new<'a>(classes: impl IntoIterator<Item = &'a Class>) -> Self {
+        // 1) Group classes under their parents, keeping declaration order
+        let mut children: HashMap<Symbol, Vec<Symbol>> = HashMap::new();
+        for c in classes {
+            if c.name == sym::OBJECT {
+                continue;
+            }
+            let parent = c.inherits.unwrap_or(sym::OBJECT);
+            children.entry(parent).or_default().push(c.name);
+        }
+
+        // 2) Iterative DFS from Object, assigning preorder numbers
+        let mut hierarchy = ClassHierarchy::default();
+        let mut stack: Vec<(Symbol, Option<usize>, usize)> = vec![(sym::OBJECT, None, 0)];
+        while let Some((name, parent, next_child)) = stack.pop() {
+            let idx = if next_child == 0 {
+                if hierarchy.index.contains_key(&name) {
+                    // Duplicate class declaration: label only the first one
+                    continue;
+                }
+                hierarchy.push(name, parent)
+            } else {
+                hierarchy.index[&name]
+            };
+
+            let kids = children.get(&name).map(Vec::as_slice).unwrap_or(&[]);
+            if let Some(&child) = kids.get(next_child) {
+                stack.push((name, parent, next_child + 1));
+                stack.push((child, Some(idx), 0));
+            } else {
+                hierarchy.nodes[idx].end = hierarchy.nodes.len() - 1;
+            }
+        }
+
+        hierarchy
+    }
+
+    fn push(&mut self, name: Symbol, parent: Option<usize>) -> usize {
+        let idx = self.nodes.len();
+        let (depth, up) = match parent {
+            None => (0, Vec::new()),
+            Some(p) => {
+                let mut up = vec![p];
+                while let Some(&next) = self.nodes[up[up.len() - 1]].up.get(up.len() - 1) {
+                    up.push(next);
+                }
+                (self.nodes[p].depth + 1, up)
+            }
+        };
+        self.nodes.push(Node { name, end: idx, depth, up });
+        self.index.insert(name, idx);
+        idx
+    }
+
+    /// `true` if `sub` is `sup` or one of its descendants.
+    pub fn conforms(&self, sub: Symbol, sup: Symbol) -> bool {
+        if sub == sup {
+            return true;
+        }
+        match (self.index.get(&sub), self.index.get(&sup)) {
+            (Some(&b), Some(&a)) => a <= b && b <= self.nodes[a].end,
+            _ => false,
+        }
+    }
+
+    /// Least upper bound (closest common ancestor) of two classes.
+    /// Falls back to `Object` when either class is not in the tree.
+    pub fn lub(&self, a: Symbol, b: Symbol) -> Symbol {
+        if a == b {
+            return a;
+        }
+        let (Some(&x), Some(&y)) = (self.index.get(&a), self.index.get(&b)) else {
+            return sym::OBJECT;
+        };
+        let (mut x, mut y) = if self.nodes[x].depth >= self.nodes[y].depth {
+            (x, y)
+        } else {
+            (y, x)
+        };
+
+        // Lift the deeper node to the other one's depth
+        let mut diff = self.nodes[x].depth - self.nodes[y].depth;
+        let mut k = 0;
+        while diff > 0 {
+            if diff & 1 == 1 {
+                x = self.nodes[x].up[k];
+            }
+            diff >>= 1;
+            k += 1;
+        }
+        if x == y {
+            return self.nodes[x].name;
+        }
+
+        // Lift both while their ancestors differ
+        for k in (0..self.nodes[x].up.len()).rev() {
+            if let (Some(&px), Some(&py)) = (self.nodes[x].up.get(k), self.nodes[y].up.get(k)) {
+                if px != py {
+                    x = px;
+                    y = py;
+                }
+            }
+        }
+        self.nodes[self.nodes[x].up[0]].name
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn class(name: &str, parent: Option<&str>) -> Class {
+        Class::new(Symbol::intern(name), parent.map(Symbol::intern), Vec::new())
+    }
+
+    #[test]
+    fn test_conforms_and_lub() {
+        // Object ─┬─ A ─┬─ B ── D
+        //         │     └─ C
+        //         └─ E
+        let classes = vec![
+            class("Object", None),
+            class("A", None),
+            class("B", Some("A")),
+            class("C", Some("A")),
+            class("D", Some("B")),
+            class("E", Some("Object")),
+        ];
+        let h = ClassHierarchy::new(&classes);
+        let s = Symbol::intern;
+
+        assert!(h.conforms(s("D"), s("A")));
+        assert!(h.conforms(s("D"), sym::OBJECT));
+        assert!(!h.conforms(s("A"), s("D")));
+        assert!(!h.conforms(s("C"), s("B")));
+        assert!(!h.conforms(s("Undefined"), sym::OBJECT));
+
+        assert_eq!(h.lub(s("D"), s("C")), s("A"));
+        assert_eq!(h.lub(s("C"), s("D")), s("A"));
+        assert_eq!(h.lub(s("D"), s("B")), s("B"));
+        assert_eq!(h.lub(s("D"), s("E")), sym::OBJECT);
+        assert_eq!(h.lub(s("A"), s("Undefined")), sym::OBJECT);
+    }
+}
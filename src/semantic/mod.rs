@@ -1,6 +1,12 @@
 pub mod errors;
 pub mod collector;
+pub mod severity;
 pub mod analyzer;
 pub mod symbols;
 pub mod type_checker;
-pub mod class_table;
\ No newline at end of file
+pub mod type_env;
+pub mod class_table;
+pub mod context;
+pub mod hierarchy;
+pub mod nesting;
+pub mod xref;
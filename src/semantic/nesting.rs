@@ -0,0 +1,78 @@
+//! The check that no expression is nested more than `MAX_NESTING` levels
+//! deep, run right after parsing.
+//!
+//! Every pass after the parser, from `--verify` and the printers to the type
+//! checker, the optimizer and the back ends, recurses once per nested
+//! expression, and so does dropping the tree. This pass walks it with a stack
+//! of its own instead, and cuts the tree off above the first level past the
+//! limit, so what the later passes are given is never deeper than that.
+
+use crate::ast::{Class, Expr, Feature, TypedExpr, VarDecl};
+use crate::semantic::errors::SemanticError;
+use crate::semantic::type_checker::MAX_NESTING;
+
+/// Report the first expression in each method body or attribute initializer
+/// of `classes` nested `MAX_NESTING` levels below it, counting as the type
+/// checker does, and replace those around them, the deepest that are
+/// allowed, with empty blocks. The program is to be rejected if any is
+/// reported.
+pub fn limit_nesting(classes: &mut [Class]) -> Vec<SemanticError> {
+    let mut errors = Vec::new();
+    let mut stack: Vec<(&mut TypedExpr, usize)> = Vec::new();
+    for class in classes {
+        for feature in &mut class.feature_list {
+            match feature {
+                Feature::Attribute(VarDecl { expr: Some(init), .. }) => stack.push((init, 0)),
+                Feature::Attribute(_) => {}
+                Feature::Method(_, _, _, body, _) => stack.push((body, 0)),
+            }
+            let mut reported = false;
+            while let Some((expr, depth)) = stack.pop() {
+                // The deepest level left: its subexpressions are one too many
+                if depth == MAX_NESTING - 1 {
+                    if let Some(first) = expr.expr.children().first().filter(|_| !reported) {
+                        errors.push(SemanticError::NestedTooDeeply { limit: MAX_NESTING, line: first.line, span: first.span });
+                        reported = true;
+                    }
+                    if !expr.expr.children().is_empty() {
+                        dismantle(std::mem::replace(&mut expr.expr, Expr::Block(Vec::new())));
+                    }
+                    continue;
+                }
+                // Reversed, so the errors come out in source order
+                stack.extend(expr.expr.children_mut().into_iter().rev().map(|child| (child, depth + 1)));
+            }
+        }
+    }
+    errors
+}
+
+/// Drop `expr` a level at a time, as dropping it whole would recurse.
+fn dismantle(expr: Expr) {
+    let mut stack = vec![expr];
+    while let Some(mut expr) = stack.pop() {
+        stack.extend(expr.children_mut().into_iter().map(|child| std::mem::replace(&mut child.expr, Expr::Native)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_limit_nesting() {
+        // `1 + 1 + ... + 1` with `n` additions nests its first `1` `n` levels deep
+        let chain = |n| format!("class Main {{ main() : Int {{ {}1 }}; x : Int <- {}1; }};", "1 + ".repeat(n), "1 + ".repeat(n));
+        let mut classes = crate::parse_program(&chain(MAX_NESTING - 1)).unwrap();
+        assert!(limit_nesting(&mut classes).is_empty());
+
+        let mut classes = crate::parse_program(&chain(200_000)).unwrap();
+        let errors: Vec<String> = limit_nesting(&mut classes).iter().map(|e| e.to_string()).collect();
+        assert_eq!(errors, ["[line 1] Expression nested more than 10000 levels deep"; 2]);
+        // What is left is shallow enough for the type checker not to report it again
+        let ctx = crate::semantic::context::AnalysisContext::new(crate::prelude(), &classes);
+        let mut ec = crate::semantic::collector::ErrorCollector::default();
+        crate::semantic::type_checker::check_expressions(&ctx, &mut ec);
+        assert!(!ec.errors.iter().any(|e| matches!(e, SemanticError::NestedTooDeeply { .. })), "{:?}", ec.errors);
+    }
+}
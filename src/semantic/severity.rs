@@ -0,0 +1,112 @@
+//! Severity of each diagnostic (`--deny`, `--warn`, `--allow`).
+//!
+//! Errors (`E` codes) are always fatal. Lints (`W` codes) flag code that is
+//! legal but probably wrong, and default to warnings; the command line can
+//! raise a lint to an error or silence it. The collector asks `Levels` about
+//! every diagnostic as it is reported, so phases never decide severity
+//! themselves.
+
+use std::collections::HashMap;
+
+use eyre::{bail, Result};
+
+use crate::semantic::errors::SemanticError;
+
+/// What happens to a reported diagnostic.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Level {
+    /// Dropped
+    Allow,
+    /// Printed, but the program is still accepted
+    Warn,
+    /// Printed, and the program is rejected
+    Deny,
+}
+
+/// Every lint, as `(code, name)`. Either can be given on the command line.
+pub const LINTS: &[(&str, &str)] = &[
+    ("W0001", "unused_let"),
+    ("W0002", "unused_attribute"),
+    ("W0003", "unreachable_case_branch"),
+    ("W0004", "dead_code"),
+    ("W0005", "constant_condition"),
+    ("W0006", "non_exhaustive_case"),
+    ("W0007", "uninitialized_attribute"),
+    ("W0008", "dispatch_on_void"),
+    ("W0009", "division_by_zero"),
+];
+
+/// Levels chosen for individual lints; the rest keep their default.
+#[derive(Debug, Clone, Default)]
+pub struct Levels {
+    overrides: HashMap<&'static str, Level>,
+    /// Lints that would be warnings are errors (`--deny-warnings`)
+    deny_warnings: bool,
+}
+
+impl Levels {
+    /// Set the level of the lint `name`, given by code (`W0001`) or name
+    /// (`unused_let`). Errors can only be denied, which they already are.
+    pub fn set(&mut self, name: &str, level: Level) -> Result<()> {
+        if let Some(&(code, _)) = LINTS.iter().find(|(code, lint)| *code == name || *lint == name) {
+            self.overrides.insert(code, level);
+            return Ok(());
+        }
+        let is_error = name.len() == 5 && name.starts_with('E') && name[1..].bytes().all(|b| b.is_ascii_digit());
+        match (is_error, level) {
+            (true, Level::Deny) => Ok(()),
+            (true, _) => bail!("{} is an error and cannot be allowed or downgraded to a warning", name),
+            (false, _) => bail!("Unknown diagnostic '{}'", name),
+        }
+    }
+
+    /// Report every lint that is not allowed as an error.
+    pub fn deny_warnings(&mut self) {
+        self.deny_warnings = true;
+    }
+
+    /// The level `diagnostic` is reported at.
+    pub fn level(&self, diagnostic: &SemanticError) -> Level {
+        let code = diagnostic.code();
+        let level = match self.overrides.get(code) {
+            Some(&level) => level,
+            None if code.starts_with('W') => Level::Warn,
+            None => Level::Deny,
+        };
+        match level {
+            Level::Warn if self.deny_warnings => Level::Deny,
+            level => level,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::symbol::Symbol;
+    use crate::parsing::token::Span;
+
+    #[test]
+    fn test_levels() {
+        let unused = SemanticError::UnusedLet { name: Symbol::intern("x"), line: 1, span: Span::default() };
+        let self_assign = SemanticError::AssignToSelf { line: 1, span: Span::default() };
+        let mut levels = Levels::default();
+        assert_eq!(levels.level(&unused), Level::Warn);
+        assert_eq!(levels.level(&self_assign), Level::Deny);
+
+        levels.set("unused_let", Level::Deny).unwrap();
+        assert_eq!(levels.level(&unused), Level::Deny);
+        levels.set("W0001", Level::Allow).unwrap();
+        assert_eq!(levels.level(&unused), Level::Allow);
+
+        levels.set("E0020", Level::Deny).unwrap();
+        assert!(levels.set("E0020", Level::Warn).is_err());
+        assert!(levels.set("unused_var", Level::Allow).is_err());
+        assert_eq!(levels.level(&self_assign), Level::Deny);
+
+        levels.deny_warnings();
+        assert_eq!(levels.level(&unused), Level::Allow);
+        let dead = SemanticError::DeadCode { line: 1, span: Span::default() };
+        assert_eq!(levels.level(&dead), Level::Deny);
+    }
+}
@@ -1,107 +1,173 @@
 use std::collections::HashMap;
 
-use crate::ast::{ArgDecl, Class, Feature, VarDecl};
+use crate::ast::{Class, Feature, Location, VarDecl};
 use crate::semantic::errors::SemanticError::*;
 use crate::semantic::collector::ErrorCollector;
-use crate::semantic::class_table::build_class_table;
+use crate::semantic::context::AnalysisContext;
+use crate::symbol::{sym, Symbol};
 
-fn is_builtin_class(name: &str) -> bool {
-    matches!(name, "Object" | "IO" | "String" | "Int" | "Bool")
+fn is_builtin_class(name: Symbol) -> bool {
+    matches!(name, sym::OBJECT | sym::IO | sym::STRING | sym::INT | sym::BOOL)
+}
+
+/// Where `class` first declares the method (or else attribute) `name`.
+fn declared_at(class: &Class, name: Symbol, method: bool) -> Location {
+    class
+        .feature_list
+        .iter()
+        .find_map(|feature| match feature {
+            Feature::Attribute(VarDecl { oid, location, .. }) if !method && *oid == name => Some(*location),
+            Feature::Method(id, .., location) if method && *id == name => Some(*location),
+            _ => None,
+        })
+        .unwrap_or_default()
+}
+
+/// Report `tid`, the type of the declaration at `location`, unless it is a
+/// class or `SELF_TYPE`.
+fn check_declared(ctx: &AnalysisContext<'_>, tid: Symbol, location: &Location, ec: &mut ErrorCollector) {
+    if tid != sym::SELF_TYPE && !ctx.is_class(tid) {
+        ec.add(UndefinedClass { type_name: tid, line: location.line, span: location.span });
+    }
 }
 
 /// # Description
 /// 
-/// Given a slice of AST‐classes, build attribute/method symbol tables
-/// and detect:
+/// Given the shared analysis context, walk each class's features and detect:
 ///  - DuplicateAttribute, DuplicateMethod
+///  - SelfAttribute, SelfFormal, DuplicateFormal, SelfTypeFormal
+///  - UndefinedClass, for declared types that name no class
+///  - AttributeOverride
 ///  - MethodOverrideMismatch
-pub fn check_class_features(classes: &[Class], ec: &mut ErrorCollector) {
-    // First, build an empty class table
-    let mut class_table = build_class_table(classes);
+///  - NoMainClass, NoMainMethod
+pub fn check_class_features(ctx: &AnalysisContext<'_>, ec: &mut ErrorCollector) {
 
-    // 1) Fill in attributes and methods for each class
-    for c in classes {
-        if is_builtin_class(&c.name) {
+    // 1) Detect duplicated attributes and methods in each class
+    //    (the class table keeps only the first declaration of each name)
+    // Keep local attribute & method lookups to detect duplicates
+    // (cleared per class so their storage is reused)
+    let mut attrs_seen = HashMap::new();
+    let mut methods_seen = HashMap::new();
+    for c in ctx.classes {
+        if is_builtin_class(c.name) {
             continue;
         }
-        let info = class_table.get_mut(&c.name).unwrap();
-
-        // Keep local attribute & method lookups to detect duplicates
-        let mut attrs_seen = HashMap::new();
-        let mut methods_seen = HashMap::new();
+        crate::ice::set_location(c.name, None);
+        attrs_seen.clear();
+        methods_seen.clear();
 
         for feat in &c.feature_list {
             match feat {
                 Feature::Attribute(vd) => {
-                    let VarDecl { oid, tid, .. } = vd;
-                    if attrs_seen.insert(oid.clone(), ()) .is_some() {
+                    let VarDecl { oid, tid, location, .. } = vd;
+                    check_declared(ctx, *tid, location, ec);
+                    if *oid == sym::SELF {
+                        ec.add(SelfAttribute { class: c.name, line: location.line, span: location.span });
+                    }
+                    if attrs_seen.insert(*oid, ()) .is_some() {
                         ec.add(DuplicateAttribute {
-                            class: c.name.clone(),
-                            attr: oid.clone(),
+                            class: c.name,
+                            attr: *oid,
+                            line: location.line,
+                            span: location.span,
                         });
-                    } else {
-                        info.attributes.push((oid.as_str(), tid.as_str()));
                     }
                 }
-                Feature::Method(name, args, ret_type, _body_opt) => {
-                    if methods_seen.insert(name.clone(), ()).is_some() {
+                Feature::Method(name, args, ret_type, _body_opt, location) => {
+                    for arg in args {
+                        check_declared(ctx, arg.tid, &arg.location, ec);
+                    }
+                    check_declared(ctx, *ret_type, location, ec);
+                    if let Some(arg) = args.iter().find(|arg| arg.id == sym::SELF) {
+                        let Location { line, span } = arg.location;
+                        ec.add(SelfFormal { class: c.name, method: *name, line, span });
+                    }
+                    for arg in args.iter().filter(|arg| arg.tid == sym::SELF_TYPE) {
+                        let Location { line, span } = arg.location;
+                        ec.add(SelfTypeFormal { class: c.name, method: *name, formal: arg.id, line, span });
+                    }
+                    for (i, arg) in args.iter().enumerate() {
+                        // Reported once, at the second formal of the name
+                        if args[..i].iter().filter(|earlier| earlier.id == arg.id).count() == 1 {
+                            let Location { line, span } = arg.location;
+                            ec.add(DuplicateFormal { class: c.name, method: *name, formal: arg.id, line, span });
+                        }
+                    }
+                    if methods_seen.insert(*name, ()).is_some() {
                         ec.add(DuplicateMethod {
-                            class: c.name.clone(),
-                            method: name.clone(),
+                            class: c.name,
+                            method: *name,
+                            line: location.line,
+                            span: location.span,
                         });
-                    } else {
-                        // Record (method_name, return_type, param_types)
-                        let params: Vec<&str> = args.iter().map(|ArgDecl { id: _, tid }| tid.as_str()).collect();
-                        info.methods.push((name.as_str(), ret_type.as_str(), params));
                     }
                 }
             }
         }
     }
 
-    // 2) Check overrides against parent signatures
-    for c in classes {
-        if is_builtin_class(&c.name) {
+    // 2) Attributes cannot be redeclared below the class that defines them
+    for c in ctx.classes {
+        if is_builtin_class(c.name) {
             continue;
         }
-        if let Some(parent_name) = &c.inherits {
-            // If parent not in table, that was flagged earlier; skip
-            if let Some(parent_info) = class_table.get(parent_name) {
-                let child_info = class_table.get(&c.name).unwrap();
-
-                // Build a quick lookup for parent methods
-                let parent_methods: HashMap<&str, (&str, &[&str])> = parent_info
-                    .methods
-                    .iter()
-                    .map(|(mname, rtype, params)| (*mname, (*rtype, params.as_slice())))
-                    .collect();
-
-                for (mname, rtype, params) in &child_info.methods {
-                    if let Some((exp_ret, exp_params)) = parent_methods.get(mname) {
-                        // Compare signature: return type must match exactly and param list length/order
-                        let found_param_types: Vec<&str> = params.clone();
-                        let exp_param_types: Vec<&str> = exp_params.to_vec();
+        let Some(info) = ctx.class_info(c.name) else { continue };
+        for (attr, _) in &info.attributes {
+            let defined = ctx.lineage(info.parent).find(|(_, a)| a.attributes.iter().any(|(name, _)| name == attr));
+            if let Some((parent, _)) = defined {
+                let Location { line, span } = declared_at(c, *attr, false);
+                ec.add(AttributeOverride {
+                    class: c.name,
+                    attr: *attr,
+                    parent,
+                    line,
+                    span,
+                });
+            }
+        }
+    }
 
-                        if *exp_ret != *rtype || exp_param_types != found_param_types {
-                            ec.add(MethodOverrideMismatch {
-                                class: c.name.clone(),
-                                method: mname.to_string(),
-                                parent: parent_name.clone(),
-                                expected: {
-                                    let mut e = Vec::new();
-                                    e.extend(exp_param_types.iter().map(|&s| s.to_string()));
-                                    e
-                                },
-                                found: {
-                                    let mut f = Vec::new();
-                                    f.extend(found_param_types.iter().map(|&s| s.to_string()));
-                                    f
-                                },
-                            });
-                        }
-                    }
+    // 3) An override must have the signature of the definition it
+    //    overrides, the nearest one among the class's ancestors
+    for c in ctx.classes {
+        if is_builtin_class(c.name) {
+            continue;
+        }
+        let Some(info) = ctx.class_info(c.name) else { continue };
+        for (mname, rtype, params) in &info.methods {
+            let overridden = ctx.lineage(info.parent).find_map(|(name, a)| {
+                a.methods.iter().find(|(m, ..)| m == mname).map(|(_, ret, params)| (name, ret, params))
+            });
+            if let Some((parent, exp_ret, exp_params)) = overridden {
+                // The formal types and the return type must match exactly
+                if exp_ret != rtype || exp_params != params {
+                    let Location { line, span } = declared_at(c, *mname, true);
+                    ec.add(MethodOverrideMismatch {
+                        class: c.name,
+                        method: *mname,
+                        parent,
+                        expected: exp_params.clone(),
+                        found: params.clone(),
+                        expected_return: *exp_ret,
+                        found_return: *rtype,
+                        line,
+                        span,
+                    });
                 }
             }
         }
     }
+
+    // 4) The program starts by calling `(new Main).main()`, so Main must
+    //    define or inherit a `main` that takes no arguments
+    let Some(main) = ctx.classes.iter().find(|c| c.name == sym::MAIN_CLASS) else {
+        ec.add(NoMainClass);
+        return;
+    };
+    let main_method = ctx
+        .lineage(main.name)
+        .find_map(|(_, info)| info.methods.iter().find(|(m, ..)| *m == sym::MAIN_METHOD));
+    if !matches!(main_method, Some((_, _, params)) if params.is_empty()) {
+        ec.add(NoMainMethod { line: main.location.line, span: main.location.span });
+    }
 }
@@ -1,381 +1,1117 @@
 use std::collections::HashMap;
 
-use crate::ast::{Class, Expr, TypedExpr, VarDecl, CaseBranch, Feature, ArgDecl};
+use crate::ast::{Class, Expr, TypedExpr, VarDecl, CaseBranch, Feature, ArgDecl, ComparisonOperator, MathOperator};
+use crate::natives;
 use crate::semantic::errors::SemanticError::*;
 use crate::semantic::collector::ErrorCollector;
-use crate::semantic::class_table::{build_class_table, ClassInfo};
+use crate::semantic::context::AnalysisContext;
+use crate::semantic::type_env::TypeEnv;
+use crate::symbol::{sym, Symbol};
 
-/// A simple environment mapping variable names → their declared type
-type TypeEnv<'a> = HashMap<String, String>;
+/// Expressions nested deeper than this are reported rather than checked, as
+/// checking recurses once per level.
+pub const MAX_NESTING: usize = 10_000;
+
+/// Stack of the type checking thread, enough for `MAX_NESTING` levels.
+const STACK_SIZE: usize = 256 << 20;
 
 /// Return true if this class is one of the built-in COOL types.
-fn is_builtin_class(name: &str) -> bool {
-    matches!(name, "Object" | "IO" | "String" | "Int" | "Bool")
+fn is_builtin_class(name: Symbol) -> bool {
+    matches!(name, sym::OBJECT | sym::IO | sym::STRING | sym::INT | sym::BOOL)
 }
 
-/// Walk the inheritance chain to see if `sub` is a subtype of `sup`.
+/// `sub ≤ sup` in the inheritance tree, answered in O(1) from the precomputed hierarchy.
+/// The error type conforms both ways, so an already-reported error never cascades.
+/// The type of `throw` conforms to everything, as it never yields a value.
+/// Inside `class`, `SELF_TYPE` conforms to whatever `class` conforms to, but
+/// only `SELF_TYPE` itself conforms to `SELF_TYPE`: a subclass may be `self`.
 fn is_subtype(
-    sub: &str,
-    sup: &str,
-    class_table: &HashMap<String, ClassInfo<'_>>,
+    sub: Symbol,
+    sup: Symbol,
+    class: Symbol,
+    ctx: &AnalysisContext<'_>,
 ) -> bool {
-    // Fast check for exact match
-    if sub == sup {
+    if sub == sym::ERROR || sup == sym::ERROR || sub == sym::NOTHING {
         return true;
     }
+    match (sub == sym::SELF_TYPE, sup == sym::SELF_TYPE) {
+        (true, true) => true,
+        (false, true) => false,
+        (true, false) => ctx.hierarchy.conforms(class, sup),
+        (false, false) => ctx.hierarchy.conforms(sub, sup),
+    }
+}
 
-    // Walk upward from `sub` toward `Object`
-    let mut current = sub;
-    while let Some(info) = class_table.get(current) {
-        if &info.parent == sup {
-            return true;
+/// Least upper bound of two types inside `class`, the type of a value that may
+/// come from either. `SELF_TYPE` joins with itself to `SELF_TYPE` and with
+/// anything else as `class`; the type of `throw` is the identity, and the
+/// error type absorbs everything so one error is reported once.
+fn join(a: Symbol, b: Symbol, class: Symbol, ctx: &AnalysisContext<'_>) -> Symbol {
+    match (a, b) {
+        (sym::ERROR, _) | (_, sym::ERROR) => sym::ERROR,
+        (sym::NOTHING, t) | (t, sym::NOTHING) => t,
+        _ if a == b => a,
+        _ => {
+            let resolve = |t| if t == sym::SELF_TYPE { class } else { t };
+            ctx.hierarchy.lub(resolve(a), resolve(b))
         }
-        if info.parent == current {
-            // reached the root ("Object" → "Object"), stop
-            break;
+    }
+}
+
+/// `found` is exactly `expected`, or is the error type of an already-reported
+/// error, or is the type of `throw`.
+fn is_type(found: Symbol, expected: Symbol) -> bool {
+    found == expected || found == sym::ERROR || found == sym::NOTHING
+}
+
+/// Top-level: for every user-defined class (skip built-ins), check attribute
+/// initializers and method bodies. Returns a copy of the program's classes
+/// with `static_type` filled in on every expression the checker inferred.
+pub fn check_expressions(ctx: &AnalysisContext<'_>, ec: &mut ErrorCollector) -> Vec<Class> {
+    on_own_stack(|| {
+        // One environment serves every class; each class gets its own scope
+        let mut env = TypeEnv::new();
+        let readers = readers(ctx.classes);
+        for c in ctx.classes {
+            check_class(c, ctx, &readers, &mut env, ec);
         }
-        current = &info.parent;
+        ctx.classes.iter().map(|c| annotated(c, &env.inferred)).collect()
+    })
+}
+
+/// `check_expressions` for `c` alone, one of `ctx.classes`. What it reports
+/// depends only on `c`, on the declarations of every class, and on which
+/// names the subclasses of `c` read.
+pub fn check_class_expressions(c: &Class, ctx: &AnalysisContext<'_>, ec: &mut ErrorCollector) -> Class {
+    on_own_stack(|| {
+        let mut env = TypeEnv::new();
+        let readers = readers(ctx.classes.iter().filter(|other| ctx.hierarchy.conforms(other.name, c.name)));
+        check_class(c, ctx, &readers, &mut env, ec);
+        annotated(c, &env.inferred)
+    })
+}
+
+/// Checking, and copying the types into the annotated classes, recurse once
+/// per nested expression, so both get a stack of their own.
+fn on_own_stack<T: Send>(f: impl FnOnce() -> T + Send) -> T {
+    crate::on_own_stack("type checker", STACK_SIZE, || {
+        crate::ice::enter_phase("type checking");
+        f()
+    })
+}
+
+/// A copy of `class` with the types `inferred` for its expressions.
+fn annotated(class: &Class, inferred: &HashMap<*const TypedExpr, Symbol>) -> Class {
+    let mut copy = class.clone();
+    for (feature, copy) in class.feature_list.iter().zip(&mut copy.feature_list) {
+        match (feature, copy) {
+            (
+                Feature::Attribute(VarDecl { expr: Some(init), .. }),
+                Feature::Attribute(VarDecl { expr: Some(copy), .. }),
+            ) => copy_types(init, copy, inferred),
+            (Feature::Method(_, _, _, body, _), Feature::Method(_, _, _, copy, _)) => copy_types(body, copy, inferred),
+            _ => {}
+        }
+    }
+    copy
+}
+
+/// The classes of a program that already checked cleanly, annotated as by
+/// `check_expressions`, for callers that did not keep its result.
+pub fn annotate(ctx: &AnalysisContext<'_>) -> Vec<Class> {
+    check_expressions(ctx, &mut ErrorCollector::default())
+}
+
+/// Fill in `copy`, a clone of `expr`, with the types recorded for `expr`.
+/// What was not checked has no type recorded, and is left as it is.
+fn copy_types(expr: &TypedExpr, copy: &mut TypedExpr, inferred: &HashMap<*const TypedExpr, Symbol>) {
+    let Some(ty) = inferred.get(&(expr as *const TypedExpr)) else {
+        return;
+    };
+    copy.static_type = Some(*ty);
+    for (child, copy) in expr.expr.children().into_iter().zip(copy.expr.children_mut()) {
+        copy_types(child, copy, inferred);
     }
+}
 
-    false
+/// `e` is a call of `Object.abort`, which never returns. Called once `e`
+/// has been inferred.
+fn calls_abort(e: &TypedExpr, current_class: Symbol, env: &TypeEnv, ctx: &AnalysisContext<'_>) -> bool {
+    let Expr::Dispatch { target, targettype, id, exprs } = &e.expr else {
+        return false;
+    };
+    if id.as_str() != "abort" || !exprs.is_empty() {
+        return false;
+    }
+    let receiver = match (targettype, target) {
+        (Some(tc), _) => *tc,
+        (None, Some(target)) => env.inferred.get(&(&**target as *const TypedExpr)).copied().unwrap_or(sym::ERROR),
+        (None, None) => sym::SELF_TYPE,
+    };
+    let receiver = if receiver == sym::SELF_TYPE { current_class } else { receiver };
+    let defined_in = ctx.lineage(receiver).find(|(_, ci)| ci.methods.iter().any(|(name, _, _)| name == id));
+    matches!(defined_in, Some((sym::OBJECT, _)))
 }
 
-/// Top-level: for every user-defined class (skip built-ins), check attribute initializers and method bodies.
-pub fn check_expressions(classes: &[Class], ec: &mut ErrorCollector) {
-    // Build class info so we can lookup attribute/method return types
-    let class_table = build_class_table(classes);
+/// `name` is read somewhere in `e`. Shadowing is ignored, so a binding
+/// hidden by an inner one of the same name still counts as used.
+fn mentions(e: &TypedExpr, name: Symbol) -> bool {
+    matches!(e.expr, Expr::Identifier(id) if id == name)
+        || e.expr.children().into_iter().any(|child| mentions(child, name))
+}
 
-    for c in classes {
-        // Skip built-in classes entirely
-        if is_builtin_class(&c.name) {
-            continue;
+/// The classes among `classes` that read each name somewhere in their
+/// attribute initializers or method bodies, shadowing ignored as in
+/// `mentions`. Each class is listed once per name, in the order given.
+fn readers<'c>(classes: impl IntoIterator<Item = &'c Class>) -> HashMap<Symbol, Vec<Symbol>> {
+    fn collect(e: &TypedExpr, class: Symbol, out: &mut HashMap<Symbol, Vec<Symbol>>) {
+        if let Expr::Identifier(id) = e.expr {
+            let readers = out.entry(id).or_default();
+            if readers.last() != Some(&class) {
+                readers.push(class);
+            }
+        }
+        for child in e.expr.children() {
+            collect(child, class, out);
+        }
+    }
+    let mut out = HashMap::new();
+    for class in classes {
+        for feature in &class.feature_list {
+            match feature {
+                Feature::Attribute(VarDecl { expr: Some(init), .. }) => collect(init, class.name, &mut out),
+                Feature::Attribute(_) => {}
+                Feature::Method(_, _, _, body, _) => collect(body, class.name, &mut out),
+            }
         }
+    }
+    out
+}
 
-        // Start environment with “self : ClassName”
-        let mut env = TypeEnv::new();
-        env.insert("self".into(), c.name.clone());
-
-        // 1) Check each attribute’s initializer
-        for feat in &c.feature_list {
-            if let Feature::Attribute(VarDecl { oid, tid, expr }) = feat {
-                if let Some(init_expr) = expr.as_ref() {
-                    let found = infer_expr_type(init_expr, &c.name, &env, &class_table, ec);
-                    // Replace strict equality with subtype check:
-                    if !is_subtype(&found, tid, &class_table) {
-                        ec.add(TypeMismatch {
-                            expected: tid.clone(),
-                            found,
-                            line: init_expr.line,
-                        });
-                    }
+/// `name` is assigned somewhere in `e`, shadowing ignored as in `mentions`.
+fn assigns(e: &TypedExpr, name: Symbol) -> bool {
+    matches!(e.expr, Expr::Assignment(id, _) if id == name)
+        || e.expr.children().into_iter().any(|child| assigns(child, name))
+}
+
+/// `e` evaluates to void whatever happens: a loop, or a block ending in one.
+fn always_void(e: &TypedExpr) -> bool {
+    match &e.expr {
+        Expr::While { .. } => true,
+        Expr::Paren(inner) => always_void(inner),
+        Expr::Block(exprs) => exprs.last().is_some_and(always_void),
+        _ => false,
+    }
+}
+
+/// Whether `e` is the literal `0`, maybe in parentheses.
+fn is_zero(e: &TypedExpr) -> bool {
+    match &e.expr {
+        Expr::Int(0) => true,
+        Expr::Paren(inner) => is_zero(inner),
+        _ => false,
+    }
+}
+
+/// The dispatches in `e` whose explicit receiver is one of `targets`.
+fn dispatches_on<'e>(e: &'e TypedExpr, targets: &[*const TypedExpr], out: &mut Vec<(Symbol, &'e TypedExpr)>) {
+    if let Expr::Dispatch { target: Some(target), id, .. } = &e.expr {
+        if targets.contains(&(&**target as *const TypedExpr)) {
+            out.push((*id, e));
+        }
+    }
+    for child in e.expr.children() {
+        dispatches_on(child, targets, out);
+    }
+}
+
+/// The reads in `e` of the variables `names` that are not bound in `e`
+/// itself (`bound` while walking it), in source order.
+fn reads_of<'e>(e: &'e TypedExpr, names: &[Symbol], bound: &mut Vec<Symbol>, out: &mut Vec<(Symbol, &'e TypedExpr)>) {
+    match &e.expr {
+        Expr::Identifier(id) if names.contains(id) && !bound.contains(id) => out.push((*id, e)),
+        Expr::Let(bindings, body) => {
+            let mark = bound.len();
+            for binding in bindings {
+                if let Some(init) = &binding.expr {
+                    reads_of(init, names, bound, out);
                 }
-                env.insert(oid.clone(), tid.clone());
+                bound.push(binding.oid);
             }
+            reads_of(body, names, bound, out);
+            bound.truncate(mark);
         }
+        Expr::Case(scrutinee, branches) => {
+            reads_of(scrutinee, names, bound, out);
+            for branch in branches {
+                bound.push(branch.id);
+                reads_of(&branch.expr, names, bound, out);
+                bound.pop();
+            }
+        }
+        Expr::Try { body, id, handler, .. } => {
+            reads_of(body, names, bound, out);
+            bound.push(*id);
+            reads_of(handler, names, bound, out);
+            bound.pop();
+        }
+        _ => {
+            for child in e.expr.children() {
+                reads_of(child, names, bound, out);
+            }
+        }
+    }
+}
 
-        // 2) Check each method body
-        for feat in &c.feature_list {
-            if let Feature::Method(_name, args, ret_type, body) = feat {
-                let mut method_env = env.clone();
-                for ArgDecl { id, tid } in args.iter() {
-                    method_env.insert(id.clone(), tid.clone());
-                }
+/// `readers` is what `readers` finds in (at least) `c` and its subclasses.
+fn check_class(
+    c: &Class,
+    ctx: &AnalysisContext<'_>,
+    readers: &HashMap<Symbol, Vec<Symbol>>,
+    env: &mut TypeEnv,
+    ec: &mut ErrorCollector,
+) {
+    // Skip built-in classes entirely
+    if is_builtin_class(c.name) {
+        return;
+    }
 
-                let found = infer_expr_type(body, &c.name, &method_env, &class_table, ec);
-                if !is_subtype(&found, ret_type, &class_table) {
+    crate::ice::set_location(c.name, None);
+
+    // Start environment with “self : SELF_TYPE”
+    env.enter_scope();
+    env.bind(sym::SELF, sym::SELF_TYPE);
+
+    // Every attribute of the class and of its ancestors is in scope in
+    // the whole class, initializers included; bind them from Object down
+    let chain: Vec<_> = ctx.lineage(c.name).collect();
+    for (_, info) in chain.iter().rev() {
+        for (attr, tid) in &info.attributes {
+            env.bind(*attr, *tid);
+        }
+    }
+
+    // 1) Check each attribute’s initializer
+    for (i, feat) in c.feature_list.iter().enumerate() {
+        if let Feature::Attribute(VarDecl { tid, expr, .. }) = feat {
+            if let Some(init_expr) = expr.as_ref() {
+                // Initializers run in declaration order, after the
+                // ancestors' ones; until its own has run, an attribute
+                // holds its default value
+                let pending: Vec<Symbol> = c.feature_list[i..]
+                    .iter()
+                    .filter_map(|f| match f {
+                        Feature::Attribute(VarDecl { oid, expr: Some(_), .. }) => Some(*oid),
+                        _ => None,
+                    })
+                    .collect();
+                let mut reads = Vec::new();
+                reads_of(init_expr, &pending, &mut Vec::new(), &mut reads);
+                for (attr, read) in reads {
+                    ec.add(UninitializedAttribute { attr, line: read.line, span: read.span });
+                }
+                let found = infer_expr_type(init_expr, c.name, env, ctx, ec);
+                // Replace strict equality with subtype check:
+                if !is_subtype(found, *tid, c.name, ctx) {
                     ec.add(TypeMismatch {
-                        expected: ret_type.clone(),
+                        expected: *tid,
                         found,
+                        line: init_expr.line,
+                        span: init_expr.span,
+                    });
+                }
+            }
+        }
+    }
+
+    // 1b) Attributes are visible in subclasses, so any of them may read one
+    for feat in &c.feature_list {
+        if let Feature::Attribute(VarDecl { oid, location, .. }) = feat {
+            let read = readers.get(oid).is_some_and(|classes| classes.iter().any(|&r| ctx.hierarchy.conforms(r, c.name)));
+            if !read {
+                ec.add(UnusedAttribute { class: c.name, attr: *oid, line: location.line, span: location.span });
+            }
+        }
+    }
+
+    // 2) Check each method body
+    for feat in &c.feature_list {
+        if let Feature::Method(name, args, ret_type, body, _) = feat {
+            crate::ice::set_location(c.name, Some(*name));
+            if let Expr::Native = body.expr {
+                let params: Vec<Symbol> = args.iter().map(|a| a.tid).collect();
+                if natives::lookup(c.name, *name, &params, *ret_type).is_none() {
+                    ec.add(UnboundNative {
+                        class: c.name,
+                        method: *name,
                         line: body.line,
+                        span: body.span,
                     });
                 }
+                continue;
+            }
+            env.enter_scope();
+            for ArgDecl { id, tid, .. } in args.iter() {
+                env.bind(*id, *tid);
+            }
+
+            let found = infer_expr_type(body, c.name, env, ctx, ec);
+            env.exit_scope();
+            if !is_subtype(found, *ret_type, c.name, ctx) {
+                ec.add(TypeMismatch {
+                    expected: *ret_type,
+                    found,
+                    line: body.line,
+                    span: body.span,
+                });
             }
         }
     }
+    env.exit_scope();
 }
 
-/// Infer the type of a `TypedExpr`. Errors are reported via `ec`.
+/// Infer the type of a `TypedExpr`. Errors are reported via `ec`. An
+/// expression more than `MAX_NESTING` levels deep is reported, and has the
+/// error type without its subexpressions being checked.
 fn infer_expr_type(
     expr: &TypedExpr,
-    current_class: &str,
-    env: &TypeEnv<'_>,
-    class_table: &HashMap<String, ClassInfo<'_>>,
+    current_class: Symbol,
+    env: &mut TypeEnv,
+    ctx: &AnalysisContext<'_>,
+    ec: &mut ErrorCollector,
+) -> Symbol {
+    let ty = if env.depth == MAX_NESTING {
+        ec.add(NestedTooDeeply { limit: MAX_NESTING, line: expr.line, span: expr.span });
+        sym::ERROR
+    } else {
+        env.depth += 1;
+        let ty = infer_node_type(expr, current_class, env, ctx, ec);
+        env.depth -= 1;
+        ty
+    };
+    env.inferred.insert(expr, ty);
+    ty
+}
+
+fn infer_node_type(
+    expr: &TypedExpr,
+    current_class: Symbol,
+    env: &mut TypeEnv,
+    ctx: &AnalysisContext<'_>,
     ec: &mut ErrorCollector,
-) -> String {
+) -> Symbol {
     match &expr.expr {
         Expr::Identifier(name) => {
-            if let Some(ty) = env.get(name) {
-                ty.clone()
+            if let Some(ty) = env.get(*name) {
+                ty
             } else {
                 ec.add(UndefinedVariable {
-                    name: name.clone(),
+                    name: *name,
+                    let_binding: env.pending_let(*name),
                     line: expr.line,
+                    span: expr.span,
                 });
-                "Object".into()
+                sym::ERROR
             }
         }
-        Expr::Int(_) => "Int".into(),
-        Expr::Bool(_) => "Bool".into(),
-        Expr::Str(_) => "String".into(),
+        Expr::Int(_) => sym::INT,
+        Expr::Bool(_) => sym::BOOL,
+        Expr::Str(_) => sym::STRING,
         Expr::New(type_name) => {
-            if !class_table.contains_key(type_name) {
+            if *type_name != sym::SELF_TYPE && !ctx.is_class(*type_name) {
                 ec.add(UndefinedClass {
-                    type_name: type_name.clone(),
+                    type_name: *type_name,
                     line: expr.line,
+                    span: expr.span,
                 });
-                "Object".into()
+                sym::ERROR
             } else {
-                type_name.clone()
+                *type_name
             }
         }
         Expr::Assignment(var_name, rhs) => {
-            let rhs_ty = infer_expr_type(rhs, current_class, env, class_table, ec);
-            if let Some(var_ty) = env.get(var_name) {
-                if !is_subtype(&rhs_ty, var_ty, class_table) {
+            let rhs_ty = infer_expr_type(rhs, current_class, env, ctx, ec);
+            if *var_name == sym::SELF {
+                ec.add(AssignToSelf { line: expr.line, span: expr.span });
+                rhs_ty
+            } else if let Some(var_ty) = env.get(*var_name) {
+                if !is_subtype(rhs_ty, var_ty, current_class, ctx) {
                     ec.add(TypeMismatch {
-                        expected: var_ty.clone(),
-                        found: rhs_ty.clone(),
+                        expected: var_ty,
+                        found: rhs_ty,
                         line: expr.line,
+                        span: expr.span,
                     });
                 }
-                var_ty.clone()
+                var_ty
             } else {
                 ec.add(UndefinedVariable {
-                    name: var_name.clone(),
+                    name: *var_name,
+                    let_binding: env.pending_let(*var_name),
                     line: expr.line,
+                    span: expr.span,
                 });
                 rhs_ty
             }
         }
-        Expr::Math { lhs, op: _, rhs } => {
-            let lt = infer_expr_type(lhs, current_class, env, class_table, ec);
-            let rt = infer_expr_type(rhs, current_class, env, class_table, ec);
-            if lt != "Int" {
+        Expr::Math { lhs, op, rhs } => {
+            let lt = infer_expr_type(lhs, current_class, env, ctx, ec);
+            let rt = infer_expr_type(rhs, current_class, env, ctx, ec);
+            if *op == MathOperator::Div && is_zero(rhs) {
+                ec.add(DivisionByZero { line: expr.line, span: expr.span });
+            }
+            if !is_type(lt, sym::INT) {
                 ec.add(TypeMismatch {
-                    expected: "Int".into(),
-                    found: lt.clone(),
+                    expected: sym::INT,
+                    found: lt,
                     line: lhs.line,
+                    span: lhs.span,
                 });
             }
-            if rt != "Int" {
+            if !is_type(rt, sym::INT) {
                 ec.add(TypeMismatch {
-                    expected: "Int".into(),
-                    found: rt.clone(),
+                    expected: sym::INT,
+                    found: rt,
                     line: rhs.line,
+                    span: rhs.span,
                 });
             }
-            "Int".into()
+            sym::INT
         }
-        Expr::Comparison { lhs, op: _, rhs } => {
-            let lt = infer_expr_type(lhs, current_class, env, class_table, ec);
-            let rt = infer_expr_type(rhs, current_class, env, class_table, ec);
-            if lt != rt {
-                ec.add(TypeMismatch {
-                    expected: lt.clone(),
-                    found: rt.clone(),
-                    line: expr.line,
-                });
+        Expr::Comparison { lhs, op, rhs } => {
+            let lt = infer_expr_type(lhs, current_class, env, ctx, ec);
+            let rt = infer_expr_type(rhs, current_class, env, ctx, ec);
+            match op {
+                ComparisonOperator::Lt | ComparisonOperator::Le => {
+                    for (t, operand) in [(lt, lhs), (rt, rhs)] {
+                        if !is_type(t, sym::INT) {
+                            ec.add(TypeMismatch {
+                                expected: sym::INT,
+                                found: t,
+                                line: operand.line,
+                                span: operand.span,
+                            });
+                        }
+                    }
+                }
+                // Objects compare by identity whatever their types, but an
+                // Int, String or Bool can only equal a value of its own type
+                ComparisonOperator::Equal => {
+                    let primitive = [lt, rt].into_iter().find(|t| [sym::INT, sym::STRING, sym::BOOL].contains(t));
+                    if let Some(expected) = primitive {
+                        let found = if lt == expected { rt } else { lt };
+                        if !is_type(found, expected) {
+                            ec.add(TypeMismatch { expected, found, line: expr.line, span: expr.span });
+                        }
+                    }
+                }
             }
-            "Bool".into()
+            sym::BOOL
         }
         Expr::UnaryOperation { op, s } => {
-            let st = infer_expr_type(s, current_class, env, class_table, ec);
+            let st = infer_expr_type(s, current_class, env, ctx, ec);
             match op {
                 crate::ast::UnaryOperator::Neg => {
-                    if st != "Int" {
+                    if !is_type(st, sym::INT) {
                         ec.add(TypeMismatch {
-                            expected: "Int".into(),
-                            found: st.clone(),
+                            expected: sym::INT,
+                            found: st,
                             line: s.line,
+                            span: s.span,
                         });
                     }
-                    "Int".into()
+                    sym::INT
                 }
                 crate::ast::UnaryOperator::Not => {
-                    if st != "Bool" {
+                    if !is_type(st, sym::BOOL) {
                         ec.add(TypeMismatch {
-                            expected: "Bool".into(),
-                            found: st.clone(),
+                            expected: sym::BOOL,
+                            found: st,
                             line: s.line,
+                            span: s.span,
                         });
                     }
-                    "Bool".into()
+                    sym::BOOL
                 }
             }
         }
         Expr::Dispatch { target, targettype, id, exprs } => {
-            // Infer each argument
-            let mut arg_types = Vec::new();
+            // Infer each argument (kept on the environment's argument stack)
+            let args = env.args_mark();
             for arg in exprs.iter() {
-                arg_types.push(infer_expr_type(arg, current_class, env, class_table, ec));
+                let ty = infer_expr_type(arg, current_class, env, ctx, ec);
+                env.push_arg(ty);
+            }
+
+            // The target is checked even when `@` fixes the lookup class
+            let target_ty = target
+                .as_ref()
+                .map(|t| infer_expr_type(t, current_class, env, ctx, ec));
+            if target.as_deref().is_some_and(always_void) {
+                ec.add(DispatchOnVoid { method: *id, line: expr.line, span: expr.span });
             }
 
             // Determine which class to look up `id` in (static or dynamic)
-            let mut lookup_class: &str = if let Some(tc) = targettype {
-                if !class_table.contains_key(tc) {
+            let lookup_class: Symbol = if let Some(tc) = targettype {
+                if !ctx.is_class(*tc) {
                     ec.add(UndefinedClass {
-                        type_name: tc.clone(),
+                        type_name: *tc,
                         line: expr.line,
+                        span: expr.span,
                     });
-                    "Object"
+                    sym::ERROR
                 } else {
-                    tc
+                    // `e@T.f()` needs `e` to conform to `T`; lookup starts at `T` all the same
+                    let found = target_ty.unwrap_or(sym::SELF_TYPE);
+                    if !is_subtype(found, *tc, current_class, ctx) {
+                        ec.add(StaticDispatchTypeError {
+                            expected: *tc,
+                            found,
+                            line: expr.line,
+                            span: expr.span,
+                        });
+                    }
+                    *tc
                 }
             } else {
-                if let Some(t) = target.as_ref() {
-                    &infer_expr_type(t, current_class, env, class_table, ec)
-                } else {
-                    current_class
+                match target_ty.unwrap_or(sym::SELF_TYPE) {
+                    sym::SELF_TYPE => current_class,
+                    ty => ty,
                 }
             };
 
-            // Walk up the inheritance chain until we find the method or hit "Object"
-            let mut found_sig: Option<(&str, &Vec<&str>)> = None;
-            let mut return_ty: Option<&str> = None;
-            let mut expected_count = 0;
-
-            while let Some(ci) = class_table.get(lookup_class) {
-                for (mname, rtype, params) in &ci.methods {
-                    if mname == id {
-                        expected_count = params.len();
-                        if params.len() == arg_types.len() {
-                            found_sig = Some((rtype, params));
-                            return_ty = Some(rtype);
+            // The nearest definition of `id` in the lookup class or its ancestors
+            let method = ctx
+                .lineage(lookup_class)
+                .find_map(|(_, ci)| ci.methods.iter().find(|(mname, _, _)| mname == id));
+
+            let arg_types = env.args_since(args);
+            let result = match method {
+                Some((_, rtype, param_list)) if param_list.len() == arg_types.len() => {
+                    for (actual, expected_ty) in arg_types.iter().zip(param_list) {
+                        if !is_subtype(*actual, *expected_ty, current_class, ctx) {
+                            ec.add(TypeMismatch {
+                                expected: *expected_ty,
+                                found: *actual,
+                                line: expr.line,
+                                span: expr.span,
+                            });
                         }
-                        break;
+                    }
+                    // A method returning SELF_TYPE returns its receiver's type
+                    match *rtype {
+                        sym::SELF_TYPE => target_ty.unwrap_or(sym::SELF_TYPE),
+                        ty => ty,
                     }
                 }
-                if found_sig.is_some() {
-                    break;
-                }
-                if lookup_class == &ci.parent {
-                    // reached root ("Object" → "Object")
-                    break;
+                Some((_, _, param_list)) => {
+                    ec.add(ArgumentCountMismatch {
+                        method: *id,
+                        expected: param_list.len(),
+                        found: arg_types.len(),
+                        line: expr.line,
+                        span: expr.span,
+                    });
+                    sym::ERROR
                 }
-                lookup_class = &ci.parent;
-            }
-
-            if let Some((rtype, param_list)) = found_sig {
-                for (idx, actual) in arg_types.iter().enumerate() {
-                    let expected_ty = param_list[idx];
-                    if !is_subtype(actual, expected_ty, class_table) {
-                        ec.add(TypeMismatch {
-                            expected: expected_ty.to_string(),
-                            found: actual.clone(),
-                            line: expr.line,
-                        });
-                    }
+                // The receiver already failed to type-check, or never yields a value
+                None if lookup_class == sym::ERROR || lookup_class == sym::NOTHING => lookup_class,
+                None => {
+                    ec.add(UndefinedMethod {
+                        class: lookup_class,
+                        method: *id,
+                        line: expr.line,
+                        span: expr.span,
+                    });
+                    sym::ERROR
                 }
-                return_ty.unwrap().to_string()
-            } else {
-                ec.add(ArgumentCountMismatch {
-                    method: id.clone(),
-                    expected: expected_count,
-                    found: arg_types.len(),
-                    line: expr.line,
-                });
-                "Object".into()
-            }
+            };
+            env.truncate_args(args);
+            result
         }
         Expr::Conditional { test, then, orelse } => {
-            let t1 = infer_expr_type(test, current_class, env, class_table, ec);
-            if t1 != "Bool" {
+            let t1 = infer_expr_type(test, current_class, env, ctx, ec);
+            if !is_type(t1, sym::BOOL) {
                 ec.add(TypeMismatch {
-                    expected: "Bool".into(),
-                    found: t1.clone(),
+                    expected: sym::BOOL,
+                    found: t1,
                     line: test.line,
+                    span: test.span,
                 });
             }
-            let t2 = infer_expr_type(then, current_class, env, class_table, ec);
-            let t3 = infer_expr_type(orelse, current_class, env, class_table, ec);
-            if !is_subtype(&t3, &t2, class_table) && !is_subtype(&t2, &t3, class_table) {
-                // If branches do not share a common subtype relationship, report mismatch
-                ec.add(TypeMismatch {
-                    expected: t2.clone(),
-                    found: t3.clone(),
-                    line: expr.line,
-                });
-            }
-            // The result is the least common ancestor, but for now pick one:
-            if is_subtype(&t2, &t3, class_table) {
-                t3
-            } else if is_subtype(&t3, &t2, class_table) {
-                t2
-            } else {
-                "Object".into()
-            }
+            let t2 = infer_expr_type(then, current_class, env, ctx, ec);
+            let t3 = infer_expr_type(orelse, current_class, env, ctx, ec);
+            join(t2, t3, current_class, ctx)
         }
         Expr::While { test, exec } => {
-            let t1 = infer_expr_type(test, current_class, env, class_table, ec);
-            if t1 != "Bool" {
+            let t1 = infer_expr_type(test, current_class, env, ctx, ec);
+            if !is_type(t1, sym::BOOL) {
                 ec.add(TypeMismatch {
-                    expected: "Bool".into(),
-                    found: t1.clone(),
+                    expected: sym::BOOL,
+                    found: t1,
                     line: test.line,
+                    span: test.span,
                 });
             }
-            let _ = infer_expr_type(exec, current_class, env, class_table, ec);
-            "Object".into()
+            let _ = infer_expr_type(exec, current_class, env, ctx, ec);
+            sym::OBJECT
         }
         Expr::Isvoid(inner) => {
-            let _ = infer_expr_type(inner, current_class, env, class_table, ec);
-            "Bool".into()
+            let _ = infer_expr_type(inner, current_class, env, ctx, ec);
+            sym::BOOL
         }
         Expr::Block(exprs) => {
-            let mut last = "Object".into();
-            for e in exprs.iter() {
-                last = infer_expr_type(e, current_class, env, class_table, ec);
+            let mut last = sym::OBJECT;
+            let mut returns = true;
+            for (i, e) in exprs.iter().enumerate() {
+                // `{ abort(); 0; }` is the usual way to give such a block a type
+                let placeholder = i == exprs.len() - 1
+                    && matches!(e.expr, Expr::Int(_) | Expr::Str(_) | Expr::Bool(_) | Expr::Identifier(_) | Expr::New(_));
+                if !returns && !placeholder {
+                    ec.add(DeadCode { line: e.line, span: e.span });
+                }
+                last = infer_expr_type(e, current_class, env, ctx, ec);
+                returns = last != sym::NOTHING && !calls_abort(e, current_class, env, ctx);
             }
             last
         }
         Expr::Let(bindings, body) => {
-            let mut new_env = env.clone();
-            for (id, typeid, init_opt) in bindings.iter() {
+            // Bindings are sequential: each initializer sees only the ones
+            // before it
+            env.enter_scope();
+            for (i, VarDecl { oid: id, tid: typeid, expr: init_opt, location }) in bindings.iter().enumerate() {
+                // An undefined type is reported once, and the variable has the error type
+                let declared = if *typeid != sym::SELF_TYPE && !ctx.is_class(*typeid) {
+                    ec.add(UndefinedClass {
+                        type_name: *typeid,
+                        line: location.line,
+                        span: location.span,
+                    });
+                    sym::ERROR
+                } else {
+                    *typeid
+                };
                 if let Some(init_expr) = init_opt {
+                    let mark = env.push_pending_lets(bindings[i..].iter().map(|binding| binding.oid));
                     let found =
-                        infer_expr_type(init_expr, current_class, &new_env, class_table, ec);
-                    if !is_subtype(&found, typeid, class_table) {
+                        infer_expr_type(init_expr, current_class, env, ctx, ec);
+                    env.truncate_pending_lets(mark);
+                    if !is_subtype(found, declared, current_class, ctx) {
                         ec.add(TypeMismatch {
-                            expected: typeid.clone(),
-                            found: found.clone(),
+                            expected: declared,
+                            found,
                             line: init_expr.line,
+                            span: init_expr.span,
                         });
                     }
                 }
-                new_env.insert(id.clone(), typeid.clone());
+                if *id == sym::SELF {
+                    ec.add(LetBindsSelf { line: location.line, span: location.span });
+                } else {
+                    env.bind(*id, declared);
+                }
+            }
+            let body_ty = infer_expr_type(body, current_class, env, ctx, ec);
+            env.exit_scope();
+
+            // A binding is in scope in the later initializers and the body
+            for (i, binding) in bindings.iter().enumerate().filter(|(_, binding)| binding.oid != sym::SELF) {
+                let later = bindings[i + 1..].iter().filter_map(|binding| binding.expr.as_ref());
+                if !later.chain([&**body]).any(|e| mentions(e, binding.oid)) {
+                    ec.add(UnusedLet { name: binding.oid, line: binding.location.line, span: binding.location.span });
+                }
+            }
+
+            // A binding of a class type with no initializer starts void, and
+            // stays void if nothing assigns it
+            for (i, VarDecl { oid: id, tid: typeid, expr: init, .. }) in bindings.iter().enumerate() {
+                if init.is_some() || *id == sym::SELF || matches!(*typeid, sym::INT | sym::STRING | sym::BOOL) {
+                    continue;
+                }
+                // Up to a later binding of the same name, whose own initializer still sees this one
+                let mut scope = Vec::new();
+                let mut shadowed = false;
+                for later in &bindings[i + 1..] {
+                    scope.extend(later.expr.as_ref());
+                    if later.oid == *id {
+                        shadowed = true;
+                        break;
+                    }
+                }
+                if !shadowed {
+                    scope.push(&**body);
+                }
+                if scope.iter().any(|e| assigns(e, *id)) {
+                    continue;
+                }
+                let mut reads = Vec::new();
+                for e in &scope {
+                    reads_of(e, &[*id], &mut Vec::new(), &mut reads);
+                }
+                let reads: Vec<*const TypedExpr> = reads.into_iter().map(|(_, read)| read as *const TypedExpr).collect();
+                let mut dispatches = Vec::new();
+                for e in &scope {
+                    dispatches_on(e, &reads, &mut dispatches);
+                }
+                for (method, dispatch) in dispatches {
+                    ec.add(DispatchOnVoid { method, line: dispatch.line, span: dispatch.span });
+                }
             }
-            infer_expr_type(body, current_class, &new_env, class_table, ec)
+            body_ty
         }
         Expr::Case(expr, branches) => {
-            let t_expr = infer_expr_type(expr, current_class, env, class_table, ec);
-            if t_expr == "Object" {
-                ec.add(CaseOnVoid { line: expr.line });
+            let t_expr = infer_expr_type(expr, current_class, env, ctx, ec);
+            // The dynamic type of the expression conforms to `scrutinee`, and
+            // the closest branch above it is taken
+            let scrutinee = if t_expr == sym::SELF_TYPE { current_class } else { t_expr };
+            let matches = |tid: Symbol| ctx.hierarchy.conforms(scrutinee, tid);
+            let reachable = |tid: Symbol| {
+                if matches(tid) {
+                    !branches.iter().any(|b| b.tid != tid && matches(b.tid) && ctx.hierarchy.conforms(b.tid, tid))
+                } else {
+                    ctx.hierarchy.conforms(tid, scrutinee)
+                }
+            };
+            let checks_reachability = ctx.is_class(scrutinee) && branches.iter().all(|b| ctx.is_class(b.tid));
+            // A value of exactly the static type needs a branch for it or an
+            // ancestor, and every other value then has one too
+            if checks_reachability && !branches.iter().any(|b| matches(b.tid)) {
+                ec.add(NonExhaustiveCase { expr_type: scrutinee, line: expr.line, span: expr.span });
             }
-            let mut result_type = "Object".to_string();
-            for CaseBranch { id, tid, expr: br_expr } in branches.iter() {
-                if !class_table.contains_key(tid) {
+
+            // `throw` is the identity of the join, so the first branch's type starts the fold
+            let mut result_type = sym::NOTHING;
+            for (i, CaseBranch { id, tid, expr: br_expr, location }) in branches.iter().enumerate() {
+                if !ctx.is_class(*tid) {
                     ec.add(UndefinedClass {
-                        type_name: tid.clone(),
-                        line: br_expr.line,
+                        type_name: *tid,
+                        line: location.line,
+                        span: location.span,
                     });
                 }
-                let mut branch_env = env.clone();
-                branch_env.insert(id.clone(), tid.clone());
-                let t_branch =
-                    infer_expr_type(br_expr, current_class, &branch_env, class_table, ec);
-
-                // Compute “join” of result_type and t_branch
-                if is_subtype(&t_branch, &result_type, class_table) {
-                    // t_branch ≤ result_type ⇒ keep result_type
-                } else if is_subtype(&result_type, &t_branch, class_table) {
-                    result_type = t_branch;
+                if branches[..i].iter().any(|earlier| earlier.tid == *tid) {
+                    ec.add(DuplicateCaseBranch {
+                        type_name: *tid,
+                        line: location.line,
+                        span: location.span,
+                    });
+                } else if checks_reachability && !reachable(*tid) {
+                    ec.add(UnreachableCaseBranch {
+                        type_name: *tid,
+                        line: location.line,
+                        span: location.span,
+                    });
+                }
+                env.enter_scope();
+                if *id == sym::SELF {
+                    ec.add(CaseBindsSelf { line: location.line, span: location.span });
                 } else {
-                    // No direct subtype relationship ⇒ fallback to Object
-                    result_type = "Object".to_string();
+                    env.bind(*id, *tid);
                 }
+                let t_branch =
+                    infer_expr_type(br_expr, current_class, env, ctx, ec);
+                env.exit_scope();
+
+                result_type = join(result_type, t_branch, current_class, ctx);
             }
             result_type
         }
-        Expr::Paren(inner) => infer_expr_type(inner, current_class, env, class_table, ec),
+        Expr::Paren(inner) => infer_expr_type(inner, current_class, env, ctx, ec),
+        Expr::Try { body, id, tid, handler } => {
+            let t_body = infer_expr_type(body, current_class, env, ctx, ec);
+            if !ctx.is_class(*tid) {
+                ec.add(UndefinedClass {
+                    type_name: *tid,
+                    line: handler.line,
+                    span: handler.span,
+                });
+            }
+            env.enter_scope();
+            env.bind(*id, *tid);
+            let t_handler = infer_expr_type(handler, current_class, env, ctx, ec);
+            env.exit_scope();
+            // Same join as a conditional: either branch may produce the value
+            join(t_body, t_handler, current_class, ctx)
+        }
+        // Only ever a method body, which is checked against the host function
+        Expr::Native => unreachable!("native method bodies are not inferred"),
+        Expr::Builtin => unreachable!("built-in classes are not type-checked"),
+        Expr::Throw(inner) => {
+            // Any object can be thrown
+            infer_expr_type(inner, current_class, env, ctx, ec);
+            sym::NOTHING
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check(source: &str) -> Vec<String> {
+        let classes = crate::parse_program(source).unwrap();
+        let ctx = AnalysisContext::new(crate::prelude(), &classes);
+        let mut ec = ErrorCollector::default();
+        check_expressions(&ctx, &mut ec);
+        ec.errors.iter().map(|e| e.to_string()).collect()
+    }
+
+    #[test]
+    fn test_deep_nesting() {
+        // `1 + 1 + ... + 1` with `n` additions nests its first `1` `n` levels deep
+        let chain = |n| format!("class Main {{ main() : Int {{ {}1 }}; }};", "1 + ".repeat(n));
+        assert_eq!(check(&chain(MAX_NESTING - 1)), Vec::<String>::new());
+        assert_eq!(check(&chain(MAX_NESTING)), ["[line 1] Expression nested more than 10000 levels deep"]);
+    }
+
+    #[test]
+    fn test_unused_attribute() {
+        // A read in a subclass counts; one in the parent or a sibling does not
+        let classes = crate::parse_program(
+            "class A { a : Int; b : Int; c : Int; f() : Int { a }; };\n\
+             class B inherits A { g() : Int { b }; };\n\
+             class C { c : Int; h() : Int { c }; };\n",
+        )
+        .unwrap();
+        let ctx = AnalysisContext::new(crate::prelude(), &classes);
+        let mut ec = ErrorCollector::default();
+        check_expressions(&ctx, &mut ec);
+        let warnings: Vec<String> = ec.warnings.iter().map(|w| w.to_string()).collect();
+        assert_eq!(warnings, ["[line 1] In class 'A', attribute 'c' is never read"]);
+        // Checking the class on its own reports the same
+        let mut ec = ErrorCollector::default();
+        check_class_expressions(&classes[0], &ctx, &mut ec);
+        assert_eq!(ec.warnings.iter().map(|w| w.to_string()).collect::<Vec<_>>(), warnings);
+    }
+
+    #[test]
+    fn test_undefined_names_do_not_cascade() {
+        let errors = check(
+            "class Main inherits IO {\n\
+                 main(): Int { {\n\
+                     out_int(x + 1);\n\
+                     out_string(x.length());\n\
+                     if x then (new Foo).bar(x) else 0 fi;\n\
+                 } };\n\
+             };\n",
+        );
+        assert_eq!(
+            errors,
+            vec![
+                "[line 3] Variable 'x' is not declared".to_string(),
+                "[line 4] Variable 'x' is not declared".to_string(),
+                "[line 5] Variable 'x' is not declared".to_string(),
+                "[line 5] Type 'Foo' is not defined".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_binding_locations() {
+        // Reported at the binding or branch, not at the `let` or `case`
+        let errors = check(
+            "class Main {\n\
+                 main() : Object { let a : Int <- 1,\n\
+                     b : Missing in\n\
+                     case a of\n\
+                         i : Int => i;\n\
+                         j : Absent => j;\n\
+                         self : Object => 0;\n\
+                     esac\n\
+                 };\n\
+             };\n",
+        );
+        assert_eq!(
+            errors,
+            vec![
+                "[line 3] Type 'Missing' is not defined".to_string(),
+                "[line 6] Type 'Absent' is not defined".to_string(),
+                "[line 7] 'self' cannot be bound in a 'case' branch".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_self_type() {
+        let errors = check(
+            "class A {\n\
+                 me() : SELF_TYPE { self };\n\
+                 fresh() : SELF_TYPE { new SELF_TYPE };\n\
+                 wrong() : SELF_TYPE { new A };\n\
+                 up() : A { self };\n\
+             };\n\
+             class B inherits A {\n\
+                 b : B <- (new B).me().fresh();\n\
+                 a : B <- (new B)@A.me();\n\
+                 c : B <- up();\n\
+             };\n",
+        );
+        assert_eq!(
+            errors,
+            vec![
+                "[line 4] Type mismatch: expected 'SELF_TYPE', found 'A'".to_string(),
+                "[line 10] Type mismatch: expected 'B', found 'A'".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_join() {
+        let errors = check(
+            "class A { };\n\
+             class B inherits A { };\n\
+             class C inherits A { };\n\
+             class D inherits B {\n\
+                 cond(x : Bool) : A { if x then new D else new C fi };\n\
+                 branches(o : A) : A { case o of b : B => b; c : C => c; d : D => d; esac };\n\
+                 same(x : Bool) : SELF_TYPE { if x then self else new SELF_TYPE fi };\n\
+                 mixed(x : Bool) : B { if x then self else new C fi };\n\
+                 unrelated(x : Bool) : Int { if x then 1 else \"one\" fi };\n\
+             };\n",
+        );
+        assert_eq!(
+            errors,
+            vec![
+                "[line 8] Type mismatch: expected 'B', found 'A'".to_string(),
+                "[line 9] Type mismatch: expected 'Int', found 'Object'".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_inherited_attributes_in_scope() {
+        let errors = check(
+            "class A { a : Int <- b; b : Int; };\n\
+             class B inherits A { c : Int <- a + b; };\n\
+             class C inherits B {\n\
+                 sum() : Int { a + b + c };\n\
+                 wrong() : String { a };\n\
+             };\n",
+        );
+        assert_eq!(errors, vec!["[line 5] Type mismatch: expected 'String', found 'Int'".to_string()]);
+    }
+
+    #[test]
+    fn test_let_bindings_are_sequential() {
+        let errors = check(
+            "class Main {\n\
+                 x : Int;\n\
+                 earlier() : Int { let a : Int <- 1, b : Int <- a + 1 in b };\n\
+                 own() : Int { let n : Int <- n + 1 in n };\n\
+                 later() : Int { let a : Int <- b, b : Int <- 2 in a + b };\n\
+                 assigned() : Int { let a : Int <- (a <- 1) in a };\n\
+                 shadows() : Int { let x : Int <- x + 1 in x };\n\
+                 nested() : Int { let a : Int <- (let c : Int <- 1 in b + c), b : Int <- 2 in a };\n\
+             };\n",
+        );
+        // An initializer sees the attribute its binding shadows
+        assert_eq!(
+            errors,
+            vec![
+                "[line 4] Variable 'n' is used in its own initializer".to_string(),
+                "[line 5] Variable 'b' is used before the `let` binding that declares it".to_string(),
+                "[line 6] Variable 'a' is used in its own initializer".to_string(),
+                "[line 8] Variable 'b' is used before the `let` binding that declares it".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_comparisons() {
+        let errors = check(
+            "class A { };\n\
+             class B { };\n\
+             class Main {\n\
+                 objects() : Bool { new A = new B };\n\
+                 primitives() : Bool { 1 = \"1\" };\n\
+                 boxed() : Bool { new A = true };\n\
+                 strings() : Bool { \"a\" < \"b\" };\n\
+             };\n",
+        );
+        assert_eq!(
+            errors,
+            vec![
+                "[line 5] Type mismatch: expected 'Int', found 'String'".to_string(),
+                "[line 6] Type mismatch: expected 'Bool', found 'A'".to_string(),
+                "[line 7] Type mismatch: expected 'Int', found 'String'".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lints() {
+        let classes = crate::parse_program(
+            "class A { };\n\
+             class B inherits A { };\n\
+             class Main {\n\
+                 read : Int;\n\
+                 written : Int;\n\
+                 narrow(b : B) : Int { case b of a : A => 0; o : Object => 1; i : Int => 2; esac };\n\
+                 wide(o : Object) : Int { case o of a : A => 0; b : B => 1; x : Object => 2; esac };\n\
+                 partial(a : A) : Int { case a of b : B => 0; esac };\n\
+                 stop() : Int { { abort(); written <- read; 0; } };\n\
+                 typed() : Int { { abort(); 0; } };\n\
+             };\n\
+             class C { early : Int <- late + 1; late : Int <- let early : Int in early; };\n",
+        )
+        .unwrap();
+        let ctx = AnalysisContext::new(crate::prelude(), &classes);
+        let mut ec = ErrorCollector::default();
+        check_expressions(&ctx, &mut ec);
+        let warnings: Vec<String> = ec.warnings.iter().map(|w| format!("{} {}", w.code(), w)).collect();
+        assert_eq!(
+            warnings,
+            [
+                "W0002 [line 5] In class 'Main', attribute 'written' is never read",
+                "W0003 [line 6] The 'case' branch for type 'Object' is never taken",
+                "W0003 [line 6] The 'case' branch for type 'Int' is never taken",
+                "W0006 [line 8] No 'case' branch matches values of type 'A'",
+                "W0004 [line 9] Unreachable expression",
+                "W0007 [line 12] Attribute 'late' is read before its initializer runs",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_dispatch_on_void() {
+        let classes = crate::parse_program(
+            "class Main {\n\
+                 main() : Object { let m : Main in m.main() };\n\
+                 assigned() : Object { let m : Main in { m <- self; m.main(); } };\n\
+                 shadowed() : Object { let m : Main, m : Main <- self in m.main() };\n\
+                 basic() : Int { let s : String in s.length() };\n\
+                 looped() : Object { (while false loop 0 pool).type_name() };\n\
+             };\n",
+        )
+        .unwrap();
+        let ctx = AnalysisContext::new(crate::prelude(), &classes);
+        let mut ec = ErrorCollector::default();
+        check_expressions(&ctx, &mut ec);
+        let warnings: Vec<String> = ec.warnings.iter().map(|w| format!("{} {}", w.code(), w)).collect();
+        assert_eq!(
+            warnings,
+            [
+                "W0008 [line 2] Dispatch of 'main' on a value that is always void",
+                "W0008 [line 6] Dispatch of 'type_name' on a value that is always void",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_division_by_zero() {
+        let classes = crate::parse_program(
+            "class Main {\n\
+                 x : Int;\n\
+                 main() : Int { 1 / 0 };\n\
+                 paren() : Int { 1 / (0) };\n\
+                 fine() : Int { 0 / 1 + 1 / x + 1 / (0 + 0) };\n\
+             };\n",
+        )
+        .unwrap();
+        let ctx = AnalysisContext::new(crate::prelude(), &classes);
+        let mut ec = ErrorCollector::default();
+        check_expressions(&ctx, &mut ec);
+        let warnings: Vec<String> = ec.warnings.iter().map(|w| format!("{} {}", w.code(), w)).collect();
+        assert_eq!(warnings, ["W0009 [line 3] Division by zero", "W0009 [line 4] Division by zero"]);
     }
 }
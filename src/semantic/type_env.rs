@@ -0,0 +1,125 @@
+use std::collections::HashMap;
+
+use crate::ast::TypedExpr;
+use crate::semantic::errors::LetBinding;
+use crate::symbol::Symbol;
+
+/// Scoped environment mapping variable names → their declared type.
+///
+/// Bindings live in a single table; every `bind` records what it replaced in an
+/// undo log, and `exit_scope` rolls the log back to where the matching
+/// `enter_scope` left it. Entering a scope is therefore O(1), and leaving one
+/// costs only as much as the bindings made inside it.
+#[derive(Debug, Default)]
+pub struct TypeEnv {
+    bindings: HashMap<Symbol, Symbol>,
+    undo_log: Vec<(Symbol, Option<Symbol>)>,
+    scope_marks: Vec<usize>,
+    /// Types of the dispatch arguments currently being checked. Nested
+    /// dispatches stack their arguments here, so one buffer serves a whole run.
+    arg_types: Vec<Symbol>,
+    /// The names bound by the `let` bindings whose initializers are being
+    /// checked and by the bindings after them, innermost `let` last; `true`
+    /// for the binding of the initializer itself
+    pending_lets: Vec<(Symbol, bool)>,
+    /// Inferred type of every expression checked, keyed by node address
+    pub(crate) inferred: HashMap<*const TypedExpr, Symbol>,
+    /// How many expressions enclose the one being checked
+    pub(crate) depth: usize,
+}
+
+impl TypeEnv {
+    pub fn new() -> Self {
+        TypeEnv::default()
+    }
+
+    pub fn get(&self, name: Symbol) -> Option<Symbol> {
+        self.bindings.get(&name).copied()
+    }
+
+    /// Bind `name` in the innermost scope, shadowing any outer binding.
+    pub fn bind(&mut self, name: Symbol, ty: Symbol) {
+        let previous = self.bindings.insert(name, ty);
+        self.undo_log.push((name, previous));
+    }
+
+    pub fn enter_scope(&mut self) {
+        self.scope_marks.push(self.undo_log.len());
+    }
+
+    /// Drop every binding made since the matching `enter_scope`.
+    pub fn exit_scope(&mut self) {
+        let mark = self.scope_marks.pop().expect("exit_scope without enter_scope");
+        while self.undo_log.len() > mark {
+            let (name, previous) = self.undo_log.pop().unwrap();
+            match previous {
+                Some(ty) => self.bindings.insert(name, ty),
+                None => self.bindings.remove(&name),
+            };
+        }
+    }
+
+    /// Start collecting argument types; pass the mark to `args_since` and `truncate_args`.
+    pub fn args_mark(&self) -> usize {
+        self.arg_types.len()
+    }
+
+    pub fn push_arg(&mut self, ty: Symbol) {
+        self.arg_types.push(ty);
+    }
+
+    pub fn args_since(&self, mark: usize) -> &[Symbol] {
+        &self.arg_types[mark..]
+    }
+
+    pub fn truncate_args(&mut self, mark: usize) {
+        self.arg_types.truncate(mark);
+    }
+
+    /// Note that the initializer of `bindings[0]` is checked next, so none
+    /// of `bindings` is in scope yet. Undone by `truncate_pending_lets`
+    /// with the returned mark.
+    pub fn push_pending_lets(&mut self, bindings: impl IntoIterator<Item = Symbol>) -> usize {
+        let mark = self.pending_lets.len();
+        self.pending_lets.extend(bindings.into_iter().enumerate().map(|(i, name)| (name, i == 0)));
+        mark
+    }
+
+    pub fn truncate_pending_lets(&mut self, mark: usize) {
+        self.pending_lets.truncate(mark);
+    }
+
+    /// How the innermost `let` being checked binds `name` later, if it
+    /// does.
+    pub fn pending_let(&self, name: Symbol) -> Option<LetBinding> {
+        let &(_, own) = self.pending_lets.iter().rev().find(|(pending, _)| *pending == name)?;
+        Some(if own { LetBinding::Own } else { LetBinding::Later })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shadowing_is_undone_on_exit() {
+        let x = Symbol::intern("x");
+        let y = Symbol::intern("y");
+        let int = Symbol::intern("Int");
+        let string = Symbol::intern("String");
+
+        let mut env = TypeEnv::new();
+        env.bind(x, int);
+
+        env.enter_scope();
+        env.bind(x, string);
+        env.bind(y, int);
+        env.bind(x, int);
+        assert_eq!(env.get(x), Some(int));
+        assert_eq!(env.get(y), Some(int));
+        env.exit_scope();
+
+        assert_eq!(env.get(x), Some(int));
+        assert_eq!(env.get(y), None);
+    }
+}
@@ -0,0 +1,358 @@
+//! Where each class, method and attribute is defined and where it is used,
+//! for `--emit xref`.
+//!
+//! `resolve` walks the type-annotated AST and resolves every reference to
+//! the definition it names: a dispatch to the method the receiver's static
+//! type finds first among its ancestors, a name that is not a local to the
+//! attribute of the enclosing class or an ancestor, and `new`, `inherits`
+//! and every type in a declaration, `case` branch or static dispatch to the
+//! class. A dispatch is resolved statically, so calling an overridden method
+//! through a parent's type counts as a use of the parent's method.
+//!
+//! ```text
+//! class Shape: main.cl:1:1
+//!     main.cl:6:1: inherits
+//! method Shape.area: main.cl:3:5
+//!     main.cl:12:53: dispatch
+//! ```
+
+use std::collections::HashMap;
+use std::fmt::{self, Write};
+
+use crate::ast::{Class, Expr, Feature, Location, TypedExpr, VarDecl};
+use crate::diagnostic::render::{line_and_column, SourceFile};
+use crate::parsing::token::Span;
+use crate::semantic::context::AnalysisContext;
+use crate::symbol::{sym, Symbol};
+
+/// What is defined.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Kind {
+    Class,
+    Method,
+    Attribute,
+}
+
+/// How a reference uses what it names.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Use {
+    /// The parent in `class C inherits P`
+    Inherits,
+    /// `new C`
+    New,
+    /// The type of an attribute, formal, method, `let` or `case` binding, or
+    /// of a static dispatch
+    Type,
+    Dispatch,
+    /// An attribute's value is read
+    Read,
+    /// An attribute is assigned to
+    Assignment,
+}
+
+impl fmt::Display for Use {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Use::Inherits => "inherits",
+            Use::New => "new",
+            Use::Type => "type",
+            Use::Dispatch => "dispatch",
+            Use::Read => "read",
+            Use::Assignment => "assignment",
+        })
+    }
+}
+
+/// One class, method or attribute, and every reference to it in source order.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Entry {
+    pub kind: Kind,
+    pub class: Symbol,
+    /// The method or attribute; `None` for a class
+    pub name: Option<Symbol>,
+    /// `None` for the built-in classes and their methods
+    pub definition: Option<Location>,
+    pub references: Vec<(Location, Use)>,
+}
+
+/// Every class, method and attribute of a program, built-ins first.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct CrossReference {
+    pub entries: Vec<Entry>,
+}
+
+impl CrossReference {
+    /// One line per definition and an indented line per reference, each
+    /// located as `file:line:column`. A built-in no one uses is left out.
+    pub fn render(&self, files: &[SourceFile<'_>]) -> String {
+        let mut out = String::new();
+        for entry in &self.entries {
+            let definition = match entry.definition {
+                Some(location) => place(location, files),
+                None if entry.references.is_empty() => continue,
+                None => "built-in".to_string(),
+            };
+            writeln!(out, "{}: {}", title(entry), definition).unwrap();
+            for (location, how) in &entry.references {
+                writeln!(out, "    {}: {}", place(*location, files), how).unwrap();
+            }
+        }
+        out
+    }
+}
+
+fn title(entry: &Entry) -> String {
+    match (entry.kind, entry.name) {
+        (Kind::Class, _) | (_, None) => format!("class {}", entry.class),
+        (Kind::Method, Some(name)) => format!("method {}.{}", entry.class, name),
+        (Kind::Attribute, Some(name)) => format!("attribute {}.{}", entry.class, name),
+    }
+}
+
+fn place(location: Location, files: &[SourceFile<'_>]) -> String {
+    match files.get(location.span.file) {
+        Some(source) if location.span.end <= source.text.len() && location.span != Span::default() => {
+            let (line, column) = line_and_column(source.text, location.span.start);
+            format!("{}:{}:{}", source.name, line, column)
+        }
+        Some(source) => format!("{}:{}", source.name, location.line),
+        None => format!("line {}", location.line),
+    }
+}
+
+/// Resolve every reference in `typed`, the annotated classes of the
+/// program `ctx` was built for, which passed the semantic checks.
+pub fn resolve(ctx: &AnalysisContext<'_>, typed: &[Class]) -> CrossReference {
+    let mut resolver = Resolver { ctx, entries: Vec::new(), index: HashMap::new() };
+    let builtins = ctx.builtins.iter().map(|c| (c, false));
+    for (class, defined) in builtins.chain(ctx.classes.iter().map(|c| (c, true))) {
+        let at = |location: Location| defined.then_some(location);
+        resolver.define(Kind::Class, class.name, None, at(class.location));
+        for feature in &class.feature_list {
+            match feature {
+                Feature::Attribute(VarDecl { oid, location, .. }) => {
+                    resolver.define(Kind::Attribute, class.name, Some(*oid), at(*location))
+                }
+                Feature::Method(name, _, _, _, location) => {
+                    resolver.define(Kind::Method, class.name, Some(*name), at(*location))
+                }
+            }
+        }
+    }
+    for class in typed {
+        resolver.class(class);
+    }
+    CrossReference { entries: resolver.entries }
+}
+
+struct Resolver<'c, 'a> {
+    ctx: &'c AnalysisContext<'a>,
+    entries: Vec<Entry>,
+    index: HashMap<(Kind, Symbol, Option<Symbol>), usize>,
+}
+
+impl Resolver<'_, '_> {
+    /// A class defined twice, or a feature defined twice in a class, is
+    /// entered at its first definition.
+    fn define(&mut self, kind: Kind, class: Symbol, name: Option<Symbol>, definition: Option<Location>) {
+        let entries = &mut self.entries;
+        self.index.entry((kind, class, name)).or_insert_with(|| {
+            entries.push(Entry { kind, class, name, definition, references: Vec::new() });
+            entries.len() - 1
+        });
+    }
+
+    fn refer(&mut self, kind: Kind, class: Symbol, name: Option<Symbol>, location: Location, how: Use) {
+        if let Some(&i) = self.index.get(&(kind, class, name)) {
+            self.entries[i].references.push((location, how));
+        }
+    }
+
+    /// `SELF_TYPE` is not a class, and names no definition.
+    fn class_use(&mut self, class: Symbol, location: Location, how: Use) {
+        if class != sym::SELF_TYPE {
+            self.refer(Kind::Class, class, None, location, how);
+        }
+    }
+
+    fn class(&mut self, class: &Class) {
+        if let Some(parent) = class.inherits {
+            self.class_use(parent, class.location, Use::Inherits);
+        }
+        for feature in &class.feature_list {
+            match feature {
+                Feature::Attribute(VarDecl { tid, expr, location, .. }) => {
+                    self.class_use(*tid, *location, Use::Type);
+                    if let Some(expr) = expr {
+                        self.expr(class.name, expr, &mut Vec::new());
+                    }
+                }
+                Feature::Method(_, args, ret_type, body, location) => {
+                    // The method's location is where its header starts
+                    self.class_use(*ret_type, *location, Use::Type);
+                    for arg in args {
+                        self.class_use(arg.tid, arg.location, Use::Type);
+                    }
+                    let mut locals = args.iter().map(|arg| arg.id).collect();
+                    self.expr(class.name, body, &mut locals);
+                }
+            }
+        }
+    }
+
+    /// Resolve the references in `expr`, in class `current`, where `locals`
+    /// are the formals and bindings in scope.
+    fn expr(&mut self, current: Symbol, expr: &TypedExpr, locals: &mut Vec<Symbol>) {
+        let here = Location { line: expr.line, span: expr.span };
+        match &expr.expr {
+            Expr::Identifier(name) => self.attribute_use(current, *name, locals, here, Use::Read),
+            Expr::Assignment(name, value) => {
+                self.attribute_use(current, *name, locals, here, Use::Assignment);
+                self.expr(current, value, locals);
+            }
+            Expr::New(class) => self.class_use(*class, here, Use::New),
+            Expr::Let(bindings, body) => {
+                let depth = locals.len();
+                // Each initializer sees the bindings before it
+                for VarDecl { oid, tid, expr: init, location } in bindings {
+                    self.class_use(*tid, *location, Use::Type);
+                    if let Some(init) = init {
+                        self.expr(current, init, locals);
+                    }
+                    locals.push(*oid);
+                }
+                self.expr(current, body, locals);
+                locals.truncate(depth);
+            }
+            Expr::Case(scrutinee, branches) => {
+                self.expr(current, scrutinee, locals);
+                for branch in branches {
+                    self.class_use(branch.tid, branch.location, Use::Type);
+                    locals.push(branch.id);
+                    self.expr(current, &branch.expr, locals);
+                    locals.pop();
+                }
+            }
+            Expr::Try { body, id, tid, handler } => {
+                self.expr(current, body, locals);
+                self.class_use(*tid, here, Use::Type);
+                locals.push(*id);
+                self.expr(current, handler, locals);
+                locals.pop();
+            }
+            Expr::Dispatch { target, targettype, id, exprs } => {
+                if let Some(target) = target {
+                    self.expr(current, target, locals);
+                }
+                if let Some(class) = targettype {
+                    self.class_use(*class, here, Use::Type);
+                }
+                let receiver = match (targettype, target) {
+                    (Some(class), _) => Some(*class),
+                    (None, Some(target)) => target.static_type,
+                    (None, None) => Some(current),
+                };
+                if let Some(receiver) = receiver {
+                    let receiver = if receiver == sym::SELF_TYPE { current } else { receiver };
+                    let owner = self
+                        .ctx
+                        .lineage(receiver)
+                        .find(|(_, info)| info.methods.iter().any(|(name, ..)| name == id))
+                        .map(|(owner, _)| owner);
+                    if let Some(owner) = owner {
+                        self.refer(Kind::Method, owner, Some(*id), here, Use::Dispatch);
+                    }
+                }
+                for arg in exprs {
+                    self.expr(current, arg, locals);
+                }
+            }
+            other => {
+                for child in other.children() {
+                    self.expr(current, child, locals);
+                }
+            }
+        }
+    }
+
+    /// `name` is an attribute unless it is `self` or a local.
+    fn attribute_use(&mut self, current: Symbol, name: Symbol, locals: &[Symbol], location: Location, how: Use) {
+        if name == sym::SELF || locals.contains(&name) {
+            return;
+        }
+        let owner = self
+            .ctx
+            .lineage(current)
+            .find(|(_, info)| info.attributes.iter().any(|(attribute, _)| *attribute == name))
+            .map(|(owner, _)| owner);
+        if let Some(owner) = owner {
+            self.refer(Kind::Attribute, owner, Some(name), location, how);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::semantic::type_checker::annotate;
+
+    const PROGRAM: &str = "\
+class Shape {
+    sides : Int <- 0;
+    area() : Int { sides };
+};
+
+class Square inherits Shape {
+    side : Int <- 2;
+    area() : Int { let sides : Int <- side in sides * side };
+};
+
+class Main inherits IO {
+    main() : Object { out_int((new Square).area() + (new Square)@Shape.area()) };
+};
+";
+
+    fn xref(source: &str) -> String {
+        let classes = crate::parse_program(source).unwrap();
+        let ctx = AnalysisContext::new(crate::prelude(), &classes);
+        let files = [SourceFile { name: "main.cl", text: source }];
+        resolve(&ctx, &annotate(&ctx)).render(&files)
+    }
+
+    #[test]
+    fn test_xref() {
+        let out = xref(PROGRAM);
+        let expected = "\
+class Object: built-in
+    main.cl:12:5: type
+class IO: built-in
+    main.cl:11:1: inherits
+method IO.out_int: built-in
+    main.cl:12:23: dispatch
+class Int: built-in
+    main.cl:2:5: type
+    main.cl:3:5: type
+    main.cl:7:5: type
+    main.cl:8:5: type
+    main.cl:8:24: type
+class Shape: main.cl:1:1
+    main.cl:6:1: inherits
+    main.cl:12:53: type
+attribute Shape.sides: main.cl:2:5
+    main.cl:3:20: read
+method Shape.area: main.cl:3:5
+    main.cl:12:53: dispatch
+class Square: main.cl:6:1
+    main.cl:12:32: new
+    main.cl:12:54: new
+attribute Square.side: main.cl:7:5
+    main.cl:8:39: read
+    main.cl:8:55: read
+method Square.area: main.cl:8:5
+    main.cl:12:31: dispatch
+class Main: main.cl:11:1
+method Main.main: main.cl:12:5
+";
+        assert_eq!(out, expected, "{}", out);
+    }
+}
@@ -0,0 +1,78 @@
+use std::fs::{self, File};
+use std::io;
+use std::path::Path;
+
+use eyre::{bail, Context, Result};
+use memmap2::Mmap;
+
+/// Files at least this large are memory-mapped instead of read into memory.
+const MMAP_THRESHOLD: u64 = 1 << 20;
+
+/// The text of an input file.
+///
+/// Large inputs (e.g. generated stress tests) are memory-mapped so the source
+/// bytes are never copied onto the heap; the scanner borrows them directly.
+#[derive(Debug)]
+pub enum SourceText {
+    Owned(String),
+    Mapped(Mmap),
+}
+
+impl SourceText {
+    /// Read `path` from disk, with context on errors. The contents must be valid UTF-8.
+    pub fn read(path: &Path) -> Result<Self> {
+        Self::read_with(&Disk, path)
+    }
+
+    /// Read `path` through `reader`, with context on errors.
+    pub fn read_with(reader: &dyn SourceReader, path: &Path) -> Result<Self> {
+        let context = || format!("Failed to read source file: {:?}", path);
+        match reader.read(path).wrap_err_with(context)? {
+            Contents::Bytes(bytes) => match String::from_utf8(bytes) {
+                Ok(text) => Ok(SourceText::Owned(text)),
+                Err(err) => bail!("{}: stream did not contain valid UTF-8 ({})", context(), err.utf8_error()),
+            },
+            Contents::Mapped(map) => {
+                if let Err(err) = std::str::from_utf8(&map) {
+                    bail!("{}: stream did not contain valid UTF-8 ({})", context(), err);
+                }
+                Ok(SourceText::Mapped(map))
+            }
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            SourceText::Owned(text) => text,
+            // SAFETY: validated as UTF-8 in `read`, and the mapping is read-only.
+            SourceText::Mapped(map) => unsafe { std::str::from_utf8_unchecked(map) },
+        }
+    }
+}
+
+/// Raw contents of an input file, before UTF-8 validation.
+pub enum Contents {
+    Bytes(Vec<u8>),
+    Mapped(Mmap),
+}
+
+/// Where the driver gets its input files from. Tests substitute readers that
+/// fail in ways a real filesystem rarely does on demand.
+pub trait SourceReader {
+    fn read(&self, path: &Path) -> io::Result<Contents>;
+}
+
+/// The real filesystem.
+pub struct Disk;
+
+impl SourceReader for Disk {
+    fn read(&self, path: &Path) -> io::Result<Contents> {
+        let file = File::open(path)?;
+        if file.metadata()?.len() < MMAP_THRESHOLD {
+            return fs::read(path).map(Contents::Bytes);
+        }
+        // SAFETY: the mapping is read-only; like any compiler reading its input,
+        // we assume the file is not truncated or rewritten while we compile it.
+        unsafe { Mmap::map(&file) }.map(Contents::Mapped)
+    }
+}
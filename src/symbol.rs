@@ -0,0 +1,149 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Mutex, OnceLock};
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// An interned string.
+///
+/// Symbols are cheap to copy and compare: two symbols are equal exactly when
+/// the strings they were interned from are equal.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Symbol(u32);
+
+/// Symbols that are pre-interned in a fixed order, so they can be used as constants.
+pub mod sym {
+    use super::Symbol;
+
+    pub const OBJECT: Symbol = Symbol(0);
+    pub const IO: Symbol = Symbol(1);
+    pub const STRING: Symbol = Symbol(2);
+    pub const INT: Symbol = Symbol(3);
+    pub const BOOL: Symbol = Symbol(4);
+    pub const SELF_TYPE: Symbol = Symbol(5);
+    pub const SELF: Symbol = Symbol(6);
+    pub const MAIN_CLASS: Symbol = Symbol(7);
+    pub const MAIN_METHOD: Symbol = Symbol(8);
+    /// Type given to an expression that already produced an error. It is not
+    /// a valid type name, and checks involving it are skipped so one mistake
+    /// is reported once instead of cascading through every enclosing node.
+    pub const ERROR: Symbol = Symbol(9);
+    /// Type of `throw` (`--ext exceptions`), which never produces a value.
+    /// It conforms to every type but is not a valid type name either.
+    pub const NOTHING: Symbol = Symbol(10);
+
+    /// Must list the strings in the same order as the constants above.
+    pub(super) const PREDEFINED: [&str; 11] = [
+        "Object",
+        "IO",
+        "String",
+        "Int",
+        "Bool",
+        "SELF_TYPE",
+        "self",
+        "Main",
+        "main",
+        "<error>",
+        "<nothing>",
+    ];
+}
+
+struct Interner {
+    names: HashMap<&'static str, Symbol>,
+    strings: Vec<&'static str>,
+}
+
+impl Interner {
+    fn new() -> Self {
+        let mut interner = Interner {
+            names: HashMap::new(),
+            strings: Vec::new(),
+        };
+        for s in sym::PREDEFINED {
+            interner.intern(s);
+        }
+        interner
+    }
+
+    fn intern(&mut self, s: &str) -> Symbol {
+        if let Some(&symbol) = self.names.get(s) {
+            return symbol;
+        }
+        // Interned strings live for the rest of the process, so handing out
+        // `&'static str` from `as_str` is sound.
+        let s: &'static str = Box::leak(s.to_owned().into_boxed_str());
+        let symbol = Symbol(self.strings.len() as u32);
+        self.strings.push(s);
+        self.names.insert(s, symbol);
+        symbol
+    }
+}
+
+fn interner() -> &'static Mutex<Interner> {
+    static INTERNER: OnceLock<Mutex<Interner>> = OnceLock::new();
+    INTERNER.get_or_init(|| Mutex::new(Interner::new()))
+}
+
+impl Symbol {
+    pub fn intern(s: &str) -> Self {
+        interner().lock().unwrap().intern(s)
+    }
+
+    pub fn as_str(self) -> &'static str {
+        interner().lock().unwrap().strings[self.0 as usize]
+    }
+}
+
+impl From<&str> for Symbol {
+    fn from(s: &str) -> Self {
+        Symbol::intern(s)
+    }
+}
+
+impl fmt::Display for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl fmt::Debug for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self.as_str())
+    }
+}
+
+/// Symbols serialize as their string, so serialized data stays valid across runs.
+impl Serialize for Symbol {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for Symbol {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        Ok(Symbol::intern(&s))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_predefined_symbols() {
+        for (i, s) in sym::PREDEFINED.iter().enumerate() {
+            assert_eq!(Symbol::intern(s), Symbol(i as u32));
+        }
+        assert_eq!(sym::SELF_TYPE.as_str(), "SELF_TYPE");
+    }
+
+    #[test]
+    fn test_intern_is_idempotent() {
+        let a = Symbol::intern("Cons");
+        let b = Symbol::intern("Cons");
+        assert_eq!(a, b);
+        assert_ne!(a, Symbol::intern("Nil"));
+        assert_eq!(a.as_str(), "Cons");
+    }
+}
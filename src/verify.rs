@@ -0,0 +1,243 @@
+//! Consistency checks between phases (`--verify`).
+//!
+//! A phase's output is checked for invariants that the next phase takes for
+//! granted. A violation is never the user's fault: it is reported as an
+//! internal compiler error, so a bug surfaces where it was introduced
+//! instead of as a confusing diagnostic further down the pipeline. These are
+//! meant for testing; they walk the whole tree again and cost about as much
+//! as the type checker.
+
+use crate::ast::{CaseBranch, Class, Expr, Feature, TypedExpr, VarDecl};
+use crate::semantic::context::AnalysisContext;
+use crate::symbol::{sym, Symbol};
+
+/// Invariants of a freshly parsed AST for `source`: every node carries a
+/// line inside the file, and sequences appear in source order.
+pub fn verify_parse(source: &str, classes: &[Class]) -> Vec<String> {
+    let lines = source.lines().count().max(1);
+    let mut problems = Vec::new();
+    for class in classes {
+        for root in roots(class) {
+            check_lines(root, lines, class.name, &mut problems);
+        }
+    }
+    problems
+}
+
+fn check_lines(e: &TypedExpr, lines: usize, class: Symbol, problems: &mut Vec<String>) {
+    if e.line == 0 || e.line > lines {
+        problems.push(format!("class {}: expression on line {} of a {}-line file", class, e.line, lines));
+    }
+    let sequence = match &e.expr {
+        Expr::Block(exprs) | Expr::Dispatch { exprs, .. } => Some(exprs),
+        _ => None,
+    };
+    if let Some(exprs) = sequence {
+        if exprs.windows(2).any(|w| w[0].line > w[1].line) {
+            problems.push(format!("class {}: sequence on line {} is out of source order", class, e.line));
+        }
+    }
+    for child in e.expr.children() {
+        check_lines(child, lines, class, problems);
+    }
+}
+
+/// Invariants of a program that passed semantic analysis without errors:
+/// every type named in the program is a class, every identifier is bound,
+/// every method called on `self` or through `@` exists, and every static
+/// type that has been filled in is a class.
+pub fn verify_semant(ctx: &AnalysisContext<'_>) -> Vec<String> {
+    let mut verifier = Verifier { ctx, class: sym::OBJECT, scope: Vec::new(), problems: Vec::new() };
+    for class in ctx.classes {
+        verifier.class(class);
+    }
+    verifier.problems
+}
+
+struct Verifier<'c, 'a> {
+    ctx: &'c AnalysisContext<'a>,
+    class: Symbol,
+    /// Names bound by attributes, formals, let and case
+    scope: Vec<Symbol>,
+    problems: Vec<String>,
+}
+
+impl Verifier<'_, '_> {
+    fn class(&mut self, class: &Class) {
+        self.class = class.name;
+        if let Some(parent) = class.inherits {
+            self.resolve_type(parent, 0);
+        }
+
+        // Attributes of the class and its ancestors are in scope everywhere
+        self.scope.clear();
+        self.scope.push(sym::SELF);
+        let mut ancestor = Some(class.name);
+        while let Some(name) = ancestor {
+            let info = self.ctx.class_info(name).expect("resolved by inheritance checks");
+            self.scope.extend(info.attributes.iter().map(|(attr, _)| *attr));
+            ancestor = (name != sym::OBJECT).then_some(info.parent);
+        }
+
+        for feature in &class.feature_list {
+            match feature {
+                Feature::Attribute(VarDecl { tid, expr, .. }) => {
+                    self.resolve_type(*tid, 0);
+                    if let Some(init) = expr {
+                        self.expr(init);
+                    }
+                }
+                Feature::Method(_, args, ret, body, _) => {
+                    self.resolve_type(*ret, body.line);
+                    let mark = self.scope.len();
+                    for arg in args {
+                        self.resolve_type(arg.tid, body.line);
+                        self.scope.push(arg.id);
+                    }
+                    self.expr(body);
+                    self.scope.truncate(mark);
+                }
+            }
+        }
+    }
+
+    fn expr(&mut self, e: &TypedExpr) {
+        if let Some(ty) = e.static_type {
+            self.resolve_type(ty, e.line);
+        }
+        match &e.expr {
+            Expr::Identifier(name) if !self.scope.contains(name) => {
+                self.problem(e.line, format!("identifier '{}' is not bound", name));
+            }
+            Expr::Assignment(name, _) if !self.scope.contains(name) => {
+                self.problem(e.line, format!("assignment to unbound '{}'", name));
+            }
+            Expr::New(ty) => self.resolve_type(*ty, e.line),
+            Expr::Let(bindings, body) => {
+                let mark = self.scope.len();
+                for VarDecl { oid: id, tid: ty, expr: init, location } in bindings {
+                    self.resolve_type(*ty, location.line);
+                    if let Some(init) = init {
+                        self.expr(init);
+                    }
+                    self.scope.push(*id);
+                }
+                self.expr(body);
+                self.scope.truncate(mark);
+                return;
+            }
+            Expr::Case(scrutinee, branches) => {
+                self.expr(scrutinee);
+                for CaseBranch { id, tid, expr, location } in branches {
+                    self.resolve_type(*tid, location.line);
+                    self.scope.push(*id);
+                    self.expr(expr);
+                    self.scope.pop();
+                }
+                return;
+            }
+            Expr::Try { body, id, tid, handler } => {
+                self.expr(body);
+                self.resolve_type(*tid, handler.line);
+                self.scope.push(*id);
+                self.expr(handler);
+                self.scope.pop();
+                return;
+            }
+            Expr::Dispatch { target, targettype, id, .. } => {
+                let receiver = match (target, targettype) {
+                    (None, _) => Some(self.class),
+                    (Some(_), Some(ty)) => {
+                        self.resolve_type(*ty, e.line);
+                        Some(*ty)
+                    }
+                    // The receiver's type is only known once static types are filled in
+                    (Some(_), None) => None,
+                };
+                if let Some(class) = receiver {
+                    if self.ctx.is_class(class) && !self.has_method(class, *id) {
+                        self.problem(e.line, format!("method '{}' is not defined in '{}'", id, class));
+                    }
+                }
+            }
+            _ => {}
+        }
+        for child in e.expr.children() {
+            self.expr(child);
+        }
+    }
+
+    fn has_method(&self, class: Symbol, method: Symbol) -> bool {
+        let mut ancestor = Some(class);
+        while let Some(name) = ancestor {
+            let Some(info) = self.ctx.class_info(name) else { return false };
+            if info.methods.iter().any(|(m, _, _)| *m == method) {
+                return true;
+            }
+            ancestor = (name != sym::OBJECT).then_some(info.parent);
+        }
+        false
+    }
+
+    fn resolve_type(&mut self, ty: Symbol, line: usize) {
+        if ty != sym::SELF_TYPE && !self.ctx.is_class(ty) {
+            self.problem(line, format!("type '{}' does not resolve", ty));
+        }
+    }
+
+    fn problem(&mut self, line: usize, message: String) {
+        self.problems.push(format!("class {}, line {}: {}", self.class, line, message));
+    }
+}
+
+/// Abort with an internal compiler error if the output of `phase` broke any
+/// invariant.
+pub fn assert_consistent(phase: &str, problems: &[String]) {
+    if !problems.is_empty() {
+        panic!(
+            "internal compiler error: inconsistent state after {}:\n  {}",
+            phase,
+            problems.join("\n  ")
+        );
+    }
+}
+
+fn roots(class: &Class) -> impl Iterator<Item = &TypedExpr> {
+    class.feature_list.iter().filter_map(|f| match f {
+        Feature::Attribute(VarDecl { expr, .. }) => expr.as_ref(),
+        Feature::Method(_, _, _, body, _) => Some(body),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::Expr;
+
+    #[test]
+    fn test_detects_inconsistencies() {
+        let source = "class Main {\n    main() : Int { { 1; 2; } };\n};\n";
+        let mut classes = crate::parse_program(source).unwrap();
+        assert!(verify_parse(source, &classes).is_empty());
+
+        let ctx = AnalysisContext::new(crate::prelude(), &classes);
+        assert!(verify_semant(&ctx).is_empty());
+
+        // Corrupt the AST the way a buggy pass might
+        let Feature::Method(_, _, _, body, _) = &mut classes[0].feature_list[0] else { unreachable!() };
+        let Expr::Block(exprs) = &mut body.expr else { unreachable!() };
+        exprs[0].line = 9;
+        exprs[1].expr = Expr::Identifier(crate::symbol::Symbol::intern("ghost"));
+        exprs[1].static_type = Some(crate::symbol::Symbol::intern("Ghost"));
+
+        assert_eq!(verify_parse(source, &classes).len(), 2);
+        let ctx = AnalysisContext::new(crate::prelude(), &classes);
+        assert_eq!(
+            verify_semant(&ctx),
+            vec![
+                "class Main, line 2: type 'Ghost' does not resolve".to_string(),
+                "class Main, line 2: identifier 'ghost' is not bound".to_string(),
+            ]
+        );
+    }
+}
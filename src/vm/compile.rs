@@ -0,0 +1,422 @@
+//! Lowering of the typed AST to bytecode.
+
+use std::collections::HashMap;
+
+use super::{ClassInfo, Function, FunctionId, Module, Op};
+use crate::ast::{Class, ComparisonOperator, Expr, Feature, MathOperator, TypedExpr, UnaryOperator, VarDecl};
+use crate::codegen::layout::Layout;
+use crate::semantic::context::AnalysisContext;
+use crate::symbol::{sym, Symbol};
+
+pub(super) fn lower(ctx: &AnalysisContext<'_>, classes: &[Class]) -> Module {
+    let all: Vec<&Class> = ctx.builtins.iter().chain(classes).collect();
+    let layout = Layout::new(&all);
+
+    // Every function gets its id up front, so calls can refer to ones not lowered yet
+    let mut ids = HashMap::new();
+    let mut methods = Vec::new();
+    for class in &layout.classes {
+        for feature in &class.class.feature_list {
+            if let Feature::Method(name, ..) = feature {
+                ids.insert((class.class.name, *name), methods.len() as FunctionId);
+                methods.push((class.class, feature));
+            }
+        }
+    }
+    // Preorder, so a parent's initializer is numbered before its children's
+    let mut inits: Vec<Option<FunctionId>> = Vec::with_capacity(layout.classes.len());
+    let mut own_inits = Vec::new();
+    for class in &layout.classes {
+        let inherited = parent_of(class.class).and_then(|parent| inits[layout.class(parent).tag]);
+        if class.class.feature_list.iter().any(|f| matches!(f, Feature::Attribute(VarDecl { expr: Some(_), .. }))) {
+            inits.push(Some((methods.len() + own_inits.len()) as FunctionId));
+            own_inits.push((class.class, inherited));
+        } else {
+            inits.push(inherited);
+        }
+    }
+
+    let mut strings = Strings::default();
+    let mut functions = Vec::with_capacity(methods.len() + own_inits.len());
+    for (class, feature) in methods {
+        let Feature::Method(name, formals, _, body, _) = feature else { unreachable!("collected as a method") };
+        let mut lowerer = Lowerer::new(&layout, &ids, &mut strings, class, *name, formals.len());
+        for (slot, formal) in formals.iter().enumerate() {
+            lowerer.scope.push((formal.id, slot as u16));
+        }
+        if let Expr::Builtin = body.expr {
+            lowerer.emit(Op::Builtin, body.line);
+        } else {
+            lowerer.expr(body);
+            lowerer.emit(Op::Return, body.line);
+        }
+        functions.push(lowerer.function);
+    }
+    for (class, inherited) in own_inits {
+        let mut lowerer = Lowerer::new(&layout, &ids, &mut strings, class, Symbol::intern("_init"), 0);
+        if let Some(parent) = inherited {
+            lowerer.emit(Op::LoadSelf, 0);
+            lowerer.emit(Op::Call { function: parent, argc: 0 }, 0);
+            lowerer.emit(Op::Pop, 0);
+        }
+        for feature in &class.feature_list {
+            if let Feature::Attribute(VarDecl { oid, expr: Some(init), .. }) = feature {
+                lowerer.expr(init);
+                lowerer.emit(Op::SetField(lowerer.field(*oid)), init.line);
+                lowerer.emit(Op::Pop, init.line);
+            }
+        }
+        lowerer.emit(Op::LoadSelf, 0);
+        lowerer.emit(Op::Return, 0);
+        functions.push(lowerer.function);
+    }
+
+    let classes = layout
+        .classes
+        .iter()
+        .zip(inits)
+        .map(|(class, init)| ClassInfo {
+            name: class.class.name,
+            fields: class.attributes.iter().map(|(_, ty)| *ty).collect(),
+            dispatch: class.methods.iter().map(|(method, owner)| ids[&(*owner, *method)]).collect(),
+            init,
+        })
+        .collect();
+    Module { classes, functions, strings: strings.list }
+}
+
+fn parent_of(class: &Class) -> Option<Symbol> {
+    (class.name != sym::OBJECT).then(|| class.inherits.unwrap_or(sym::OBJECT))
+}
+
+/// The string constants of the module, each stored once.
+#[derive(Default)]
+struct Strings {
+    list: Vec<String>,
+    index: HashMap<String, u32>,
+}
+
+impl Strings {
+    fn intern(&mut self, s: &str) -> u32 {
+        if let Some(&i) = self.index.get(s) {
+            return i;
+        }
+        let i = self.list.len() as u32;
+        self.list.push(s.to_string());
+        self.index.insert(s.to_string(), i);
+        i
+    }
+}
+
+/// Lowers the body of one function.
+struct Lowerer<'l, 'a> {
+    layout: &'l Layout<'a>,
+    ids: &'l HashMap<(Symbol, Symbol), FunctionId>,
+    strings: &'l mut Strings,
+    /// Bindings in scope, innermost last, with their slots
+    scope: Vec<(Symbol, u16)>,
+    function: Function,
+}
+
+impl<'l, 'a> Lowerer<'l, 'a> {
+    fn new(
+        layout: &'l Layout<'a>,
+        ids: &'l HashMap<(Symbol, Symbol), FunctionId>,
+        strings: &'l mut Strings,
+        class: &Class,
+        name: Symbol,
+        params: usize,
+    ) -> Self {
+        let function = Function {
+            class: class.name,
+            file: class.file,
+            name,
+            params,
+            locals: params,
+            code: Vec::new(),
+            lines: Vec::new(),
+            cases: Vec::new(),
+        };
+        Lowerer { layout, ids, strings, scope: Vec::new(), function }
+    }
+
+    /// Append `op`, returning its index for `patch`.
+    fn emit(&mut self, op: Op, line: usize) -> usize {
+        self.function.code.push(op);
+        self.function.lines.push(line);
+        self.function.code.len() - 1
+    }
+
+    /// Point the jump at `at` to the next op.
+    fn patch(&mut self, at: usize) {
+        let here = self.function.code.len() as u32;
+        match &mut self.function.code[at] {
+            Op::Jump(target) | Op::JumpIfFalse(target) => *target = here,
+            op => unreachable!("{:?} is not a jump", op),
+        }
+    }
+
+    fn field(&self, attr: Symbol) -> u16 {
+        let attributes = &self.layout.class(self.function.class).attributes;
+        attributes.iter().position(|(a, _)| *a == attr).expect("bound by the type checker") as u16
+    }
+
+    /// Bind `id` to a fresh slot, which takes the value on top of the stack.
+    fn bind(&mut self, id: Symbol, line: usize) {
+        let slot = self.next_slot();
+        self.emit(Op::Store(slot), line);
+        self.emit(Op::Pop, line);
+        self.scope.push((id, slot));
+        self.function.locals = self.function.locals.max(slot as usize + 1);
+    }
+
+    /// The first slot above the formals and the bindings in scope.
+    fn next_slot(&self) -> u16 {
+        self.scope.last().map_or(self.function.params as u16, |&(_, slot)| slot + 1)
+    }
+
+    fn default_value(&mut self, ty: Symbol, line: usize) {
+        let op = match ty {
+            sym::INT => Op::Int(0),
+            sym::BOOL => Op::Bool(false),
+            sym::STRING => Op::Str(self.strings.intern("")),
+            _ => Op::Void,
+        };
+        self.emit(op, line);
+    }
+
+    fn expr(&mut self, e: &TypedExpr) {
+        let line = e.line;
+        match &e.expr {
+            Expr::Paren(inner) => self.expr(inner),
+            Expr::Bool(b) => {
+                self.emit(Op::Bool(*b), line);
+            }
+            Expr::Int(i) => {
+                self.emit(Op::Int(*i), line);
+            }
+            Expr::Str(s) => {
+                let i = self.strings.intern(s);
+                self.emit(Op::Str(i), line);
+            }
+            Expr::Identifier(name) if *name == sym::SELF => {
+                self.emit(Op::LoadSelf, line);
+            }
+            Expr::Identifier(name) => {
+                let op = match self.scope.iter().rev().find(|(id, _)| id == name) {
+                    Some(&(_, slot)) => Op::Load(slot),
+                    None => Op::GetField(self.field(*name)),
+                };
+                self.emit(op, line);
+            }
+            Expr::Assignment(name, rhs) => {
+                self.expr(rhs);
+                let op = match self.scope.iter().rev().find(|(id, _)| id == name) {
+                    Some(&(_, slot)) => Op::Store(slot),
+                    None => Op::SetField(self.field(*name)),
+                };
+                self.emit(op, line);
+            }
+            Expr::New(ty) => match *ty {
+                sym::SELF_TYPE => {
+                    self.emit(Op::NewSelfType, line);
+                }
+                sym::INT | sym::BOOL | sym::STRING => self.default_value(*ty, line),
+                ty => {
+                    self.emit(Op::New(self.layout.class(ty).tag as u16), line);
+                }
+            },
+            Expr::Block(exprs) => {
+                for (i, e) in exprs.iter().enumerate() {
+                    if i > 0 {
+                        self.emit(Op::Pop, e.line);
+                    }
+                    self.expr(e);
+                }
+            }
+            Expr::Let(bindings, body) => {
+                let mark = self.scope.len();
+                for VarDecl { oid: id, tid: ty, expr: init, .. } in bindings {
+                    match init {
+                        Some(init) => self.expr(init),
+                        None => self.default_value(*ty, line),
+                    }
+                    self.bind(*id, line);
+                }
+                self.expr(body);
+                self.scope.truncate(mark);
+            }
+            Expr::Math { lhs, op, rhs } => {
+                self.expr(lhs);
+                self.expr(rhs);
+                let op = match op {
+                    MathOperator::Add => Op::Add,
+                    MathOperator::Subtract => Op::Sub,
+                    MathOperator::Mul => Op::Mul,
+                    MathOperator::Div => Op::Div,
+                };
+                self.emit(op, line);
+            }
+            Expr::UnaryOperation { op, s } => {
+                self.expr(s);
+                let op = match op {
+                    UnaryOperator::Neg => Op::Neg,
+                    UnaryOperator::Not => Op::Not,
+                };
+                self.emit(op, line);
+            }
+            Expr::Comparison { lhs, op, rhs } => {
+                self.expr(lhs);
+                self.expr(rhs);
+                let op = match op {
+                    ComparisonOperator::Lt => Op::Lt,
+                    ComparisonOperator::Le => Op::Le,
+                    ComparisonOperator::Equal => Op::Eq,
+                };
+                self.emit(op, line);
+            }
+            Expr::Isvoid(inner) => {
+                self.expr(inner);
+                self.emit(Op::IsVoid, line);
+            }
+            Expr::Conditional { test, then, orelse } => {
+                self.expr(test);
+                let to_else = self.emit(Op::JumpIfFalse(0), line);
+                self.expr(then);
+                let to_end = self.emit(Op::Jump(0), line);
+                self.patch(to_else);
+                self.expr(orelse);
+                self.patch(to_end);
+            }
+            Expr::While { test, exec } => {
+                let start = self.function.code.len() as u32;
+                self.expr(test);
+                let to_end = self.emit(Op::JumpIfFalse(0), line);
+                self.expr(exec);
+                self.emit(Op::Pop, line);
+                self.emit(Op::Jump(start), line);
+                self.patch(to_end);
+                self.emit(Op::Void, line);
+            }
+            Expr::Case(scrutinee, branches) => {
+                self.expr(scrutinee);
+                let table = self.function.cases.len();
+                self.function.cases.push(Vec::new());
+                self.emit(Op::Case(table as u16), line);
+                let mut targets = HashMap::new();
+                let mut to_end = Vec::new();
+                for branch in branches {
+                    targets.insert(branch.tid, self.function.code.len() as u32);
+                    self.bind(branch.id, branch.expr.line);
+                    self.expr(&branch.expr);
+                    self.scope.pop();
+                    to_end.push(self.emit(Op::Jump(0), line));
+                }
+                for at in to_end {
+                    self.patch(at);
+                }
+                // The branch of the closest ancestor of each class
+                self.function.cases[table] = self
+                    .layout
+                    .classes
+                    .iter()
+                    .map(|class| {
+                        let mut ancestor = Some(class.class.name);
+                        while let Some(name) = ancestor {
+                            if let Some(&target) = targets.get(&name) {
+                                return Some(target);
+                            }
+                            ancestor = parent_of(self.layout.class(name).class);
+                        }
+                        None
+                    })
+                    .collect();
+            }
+            Expr::Dispatch { target, targettype, id, exprs } => {
+                for arg in exprs {
+                    self.expr(arg);
+                }
+                let argc = exprs.len() as u8;
+                let class = match target {
+                    Some(target) => {
+                        self.expr(target);
+                        match target.static_type.expect("annotated by the type checker") {
+                            sym::SELF_TYPE => self.function.class,
+                            ty => ty,
+                        }
+                    }
+                    None => {
+                        self.emit(Op::LoadSelf, line);
+                        self.function.class
+                    }
+                };
+                let methods = &self.layout.class(targettype.unwrap_or(class)).methods;
+                let slot = methods.iter().position(|(m, _)| m == id).expect("resolved by the type checker");
+                let op = match targettype {
+                    Some(_) => Op::Call { function: self.ids[&(methods[slot].1, *id)], argc },
+                    None => Op::Dispatch { method: *id, slot: slot as u16, argc },
+                };
+                self.emit(op, line);
+            }
+            Expr::Try { .. } | Expr::Throw(_) | Expr::Native => {
+                unreachable!("rejected by check_supported")
+            }
+            Expr::Builtin => unreachable!("built-in bodies are lowered to Op::Builtin"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::semantic::type_checker::annotate;
+
+    fn lower_source(source: &str) -> Module {
+        let classes = crate::parse_program(source).unwrap();
+        let ctx = AnalysisContext::new(crate::prelude(), &classes);
+        lower(&ctx, &annotate(&ctx))
+    }
+
+    fn function<'m>(module: &'m Module, class: &str, name: &str) -> &'m Function {
+        module.functions.iter().find(|f| f.class.as_str() == class && f.name.as_str() == name).unwrap()
+    }
+
+    #[test]
+    fn test_lower() {
+        let module = lower_source(
+            "class A { x : Int <- 1; f(y : Int) : Int { let z : Int <- y in x <- z + y }; };\n\
+             class B inherits A { g() : Object { while f(2) < 3 loop self@A.f(4) pool }; };\n\
+             class Main { main() : Object { (new B).f(0) }; };",
+        );
+        use Op::*;
+        assert_eq!(
+            function(&module, "A", "f").code,
+            [Load(0), Store(1), Pop, Load(1), Load(0), Add, SetField(0), Return]
+        );
+        assert_eq!(function(&module, "A", "f").locals, 2);
+        let a_f = module.functions.iter().position(|f| f.class.as_str() == "A" && f.name.as_str() == "f").unwrap();
+        // f follows Object's three methods in the dispatch table
+        assert_eq!(
+            function(&module, "B", "g").code,
+            [
+                Int(2),
+                LoadSelf,
+                Dispatch { method: Symbol::intern("f"), slot: 3, argc: 1 },
+                Int(3),
+                Lt,
+                JumpIfFalse(11),
+                Int(4),
+                LoadSelf,
+                Call { function: a_f as FunctionId, argc: 1 },
+                Pop,
+                Jump(0),
+                Void,
+                Return,
+            ]
+        );
+        // B inherits A's initializer rather than getting one of its own
+        let b = module.classes.iter().find(|c| c.name.as_str() == "B").unwrap();
+        assert_eq!(b.init, module.classes.iter().find(|c| c.name.as_str() == "A").unwrap().init);
+        assert_eq!(function(&module, "A", "_init").code, [Int(1), SetField(0), Pop, LoadSelf, Return]);
+    }
+}
@@ -0,0 +1,258 @@
+//! Execution of a `Module`.
+//!
+//! One value stack holds every frame's locals and temporaries: a call's
+//! arguments, already pushed by the caller, become the first slots of the
+//! callee's frame, and returning truncates the stack back to them.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::io::{BufRead, Write};
+use std::rc::Rc;
+
+use super::{FunctionId, Module, Op};
+use crate::arith::{self, Overflow};
+use crate::ast::MathOperator;
+use crate::interp::{self, default_value, equal, no_branch, Object, RuntimeError, Value, MAX_DEPTH};
+use crate::symbol::{sym, Symbol};
+
+struct Frame {
+    function: FunctionId,
+    pc: usize,
+    this: Value,
+    /// Index of the frame's first slot in the stack
+    base: usize,
+    /// Line of the call, where a built-in method reports its errors
+    called_at: usize,
+}
+
+struct Machine<'m, 'io> {
+    module: &'m Module,
+    tags: HashMap<Symbol, usize>,
+    strings: Vec<Rc<str>>,
+    stack: Vec<Value>,
+    frames: Vec<Frame>,
+    overflow: Overflow,
+    input: &'io mut dyn BufRead,
+    output: &'io mut dyn Write,
+}
+
+fn error(line: usize, message: impl Into<String>) -> RuntimeError {
+    RuntimeError { line, file: None, message: message.into() }
+}
+
+pub(super) fn run(
+    module: &Module,
+    overflow: Overflow,
+    input: &mut dyn BufRead,
+    output: &mut dyn Write,
+) -> Result<(), RuntimeError> {
+    let tags: HashMap<Symbol, usize> = module.classes.iter().enumerate().map(|(tag, c)| (c.name, tag)).collect();
+    let Some(&main) = tags.get(&sym::MAIN_CLASS) else {
+        return Err(error(0, "No class Main"));
+    };
+    let strings = module.strings.iter().map(|s| Rc::from(s.as_str())).collect();
+    let mut machine = Machine { module, tags, strings, stack: Vec::new(), frames: Vec::new(), overflow, input, output };
+
+    let result = machine.new_object(main, 0).and_then(|()| {
+        if !machine.frames.is_empty() {
+            let object = machine.execute()?;
+            machine.stack.push(object);
+        }
+        let dispatch = &module.classes[main].dispatch;
+        let slot = dispatch.iter().position(|&f| module.functions[f as usize].name == sym::MAIN_METHOD);
+        let slot = slot.expect("checked by the type checker");
+        machine.dispatch(sym::MAIN_METHOD, slot, 0, 0)?;
+        machine.execute()
+    });
+    machine.output.flush().map_err(|err| error(0, format!("Failed to write output: {}", err)))?;
+    result.map(|_| ()).map_err(|err| RuntimeError { file: machine.error_file(), ..err })
+}
+
+impl Machine<'_, '_> {
+    /// The input file of the code that failed: frames are left in place on
+    /// an error, so it is that of the top frame, or of its caller if the top
+    /// frame is a built-in method, which reports its errors at the call.
+    fn error_file(&self) -> Option<usize> {
+        let mut frames = self.frames.iter().rev();
+        let top = frames.next()?;
+        let function = &self.module.functions[top.function as usize];
+        let frame = match function.code.get(top.pc.wrapping_sub(1)) {
+            Some(Op::Builtin) => frames.next()?,
+            _ => top,
+        };
+        Some(self.module.functions[frame.function as usize].file)
+    }
+
+    /// Push a new object of the class with `tag`. If the class has an
+    /// initializer, it is called and leaves the object on the stack when it
+    /// returns.
+    fn new_object(&mut self, tag: usize, line: usize) -> Result<(), RuntimeError> {
+        let class = &self.module.classes[tag];
+        let object = Value::Object(Rc::new(Object {
+            class: class.name,
+            fields: RefCell::new(class.fields.iter().map(|ty| default_value(*ty)).collect()),
+            items: RefCell::new(Vec::new()),
+        }));
+        match class.init {
+            Some(init) => self.enter(init, object, 0, line),
+            None => {
+                self.stack.push(object);
+                Ok(())
+            }
+        }
+    }
+
+    /// Call entry `slot` of the dispatch table of the receiver's class. The
+    /// receiver is on top of the stack, above `argc` arguments.
+    fn dispatch(&mut self, method: Symbol, slot: usize, argc: usize, line: usize) -> Result<(), RuntimeError> {
+        let receiver = self.stack.pop().unwrap();
+        let Some(class) = receiver.class() else {
+            return Err(error(line, format!("Dispatch of '{}' on void", method)));
+        };
+        let function = self.module.classes[self.tags[&class]].dispatch[slot];
+        self.enter(function, receiver, argc, line)
+    }
+
+    /// Start a frame for `function`, whose arguments are the top `argc`
+    /// values of the stack.
+    fn enter(&mut self, function: FunctionId, this: Value, argc: usize, line: usize) -> Result<(), RuntimeError> {
+        if self.frames.len() >= MAX_DEPTH {
+            return Err(error(line, format!("Stack overflow: more than {} nested calls", MAX_DEPTH)));
+        }
+        let base = self.stack.len() - argc;
+        let locals = self.module.functions[function as usize].locals;
+        self.stack.resize(base + locals, Value::Void);
+        self.frames.push(Frame { function, pc: 0, this, base, called_at: line });
+        Ok(())
+    }
+
+    /// Run until the frame on top when called returns, giving its result.
+    fn execute(&mut self) -> Result<Value, RuntimeError> {
+        let module = self.module;
+        let bottom = self.frames.len() - 1;
+        loop {
+            let frame = self.frames.last_mut().unwrap();
+            let function = &module.functions[frame.function as usize];
+            let (op, line) = (function.code[frame.pc], function.lines[frame.pc]);
+            frame.pc += 1;
+            let base = frame.base;
+            match op {
+                Op::Int(i) => self.stack.push(Value::Int(i)),
+                Op::Bool(b) => self.stack.push(Value::Bool(b)),
+                Op::Str(i) => self.stack.push(Value::Str(self.strings[i as usize].clone())),
+                Op::Void => self.stack.push(Value::Void),
+                Op::LoadSelf => {
+                    let this = frame.this.clone();
+                    self.stack.push(this);
+                }
+                Op::Load(slot) => self.stack.push(self.stack[base + slot as usize].clone()),
+                Op::Store(slot) => self.stack[base + slot as usize] = self.stack.last().unwrap().clone(),
+                Op::GetField(i) => {
+                    let Value::Object(object) = &frame.this else { unreachable!("attributes belong to objects") };
+                    let value = object.fields.borrow()[i as usize].clone();
+                    self.stack.push(value);
+                }
+                Op::SetField(i) => {
+                    let Value::Object(object) = &frame.this else { unreachable!("attributes belong to objects") };
+                    object.fields.borrow_mut()[i as usize] = self.stack.last().unwrap().clone();
+                }
+                Op::Pop => {
+                    self.stack.pop();
+                }
+                Op::New(tag) => self.new_object(tag as usize, line)?,
+                Op::NewSelfType => {
+                    let tag = self.tags[&frame.this.class().unwrap()];
+                    self.new_object(tag, line)?;
+                }
+                Op::Dispatch { method, slot, argc } => self.dispatch(method, slot as usize, argc as usize, line)?,
+                Op::Call { function: callee, argc } => {
+                    let receiver = self.stack.pop().unwrap();
+                    if let Value::Void = receiver {
+                        let method = module.functions[callee as usize].name;
+                        return Err(error(line, format!("Dispatch of '{}' on void", method)));
+                    }
+                    self.enter(callee, receiver, argc as usize, line)?;
+                }
+                Op::Builtin => {
+                    let (this, args) = (frame.this.clone(), self.stack[base..base + function.params].to_vec());
+                    let line = frame.called_at;
+                    let value = interp::builtin(this, function.class, function.name, args, self.input, self.output, line)?;
+                    if let Some(value) = self.leave(value, bottom) {
+                        return Ok(value);
+                    }
+                }
+                Op::Return => {
+                    let value = self.stack.pop().unwrap();
+                    if let Some(value) = self.leave(value, bottom) {
+                        return Ok(value);
+                    }
+                }
+                Op::Jump(target) => frame.pc = target as usize,
+                Op::JumpIfFalse(target) => {
+                    if !self.stack.pop().unwrap().as_bool() {
+                        frame.pc = target as usize;
+                    }
+                }
+                Op::Case(table) => {
+                    let value = self.stack.pop().unwrap();
+                    let Some(class) = value.class() else {
+                        return Err(error(line, "Case on void"));
+                    };
+                    let Some(target) = function.cases[table as usize][self.tags[&class]] else {
+                        return Err(no_branch(class, line));
+                    };
+                    self.stack.push(value);
+                    frame.pc = target as usize;
+                }
+                Op::Add | Op::Sub | Op::Mul | Op::Div => {
+                    let b = self.stack.pop().unwrap().as_int();
+                    let a = self.stack.pop().unwrap().as_int();
+                    let math = match op {
+                        Op::Add => MathOperator::Add,
+                        Op::Sub => MathOperator::Subtract,
+                        Op::Mul => MathOperator::Mul,
+                        _ if b == 0 => return Err(error(line, "Division by zero")),
+                        _ => MathOperator::Div,
+                    };
+                    let value = self.overflow.math(&math, a, b).ok_or_else(|| error(line, arith::MESSAGE))?;
+                    self.stack.push(Value::Int(value));
+                }
+                Op::Lt | Op::Le => {
+                    let b = self.stack.pop().unwrap().as_int();
+                    let a = self.stack.pop().unwrap().as_int();
+                    self.stack.push(Value::Bool(if op == Op::Lt { a < b } else { a <= b }));
+                }
+                Op::Neg => {
+                    let a = self.stack.pop().unwrap().as_int();
+                    let value = self.overflow.negate(a).ok_or_else(|| error(line, arith::MESSAGE))?;
+                    self.stack.push(Value::Int(value));
+                }
+                Op::Not => {
+                    let a = self.stack.pop().unwrap().as_bool();
+                    self.stack.push(Value::Bool(!a));
+                }
+                Op::Eq => {
+                    let b = self.stack.pop().unwrap();
+                    let a = self.stack.pop().unwrap();
+                    self.stack.push(Value::Bool(equal(&a, &b)));
+                }
+                Op::IsVoid => {
+                    let a = self.stack.pop().unwrap();
+                    self.stack.push(Value::Bool(matches!(a, Value::Void)));
+                }
+            }
+        }
+    }
+
+    /// Pop the top frame, which returns `value`. The value itself if that
+    /// was the frame `execute` started from, which is above `bottom`.
+    fn leave(&mut self, value: Value, bottom: usize) -> Option<Value> {
+        let frame = self.frames.pop().unwrap();
+        self.stack.truncate(frame.base);
+        if self.frames.len() == bottom {
+            return Some(value);
+        }
+        self.stack.push(value);
+        None
+    }
+}
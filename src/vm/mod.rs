@@ -0,0 +1,274 @@
+//! Bytecode compiler and stack virtual machine (`--run --vm`).
+//!
+//! `compile` lowers a program that passed semantic analysis, from its
+//! type-annotated classes, to a `Module`: one `Function` per method and per
+//! class initializer, each a flat sequence of `Op`s. `run` executes the
+//! module on a stack machine (see `machine`). Names are resolved while
+//! lowering, so at run time a local is a slot in the frame, an attribute an
+//! index into the object, and a dynamic dispatch an index into the dispatch
+//! table of the receiver's class, laid out as for the backends (`Layout`).
+//! Calls do not recurse in Rust, so programs run without a stack of their
+//! own.
+//!
+//! Values, the built-in methods and runtime errors are the interpreter's,
+//! and so are the results: a program prints the same and fails with the
+//! same `RuntimeError` either way. Like the backends, the VM does not
+//! support the `arrays`, `exceptions` and `natives` extensions.
+
+mod compile;
+mod machine;
+
+use std::fmt;
+use std::io::{BufRead, Write};
+
+use eyre::Result;
+
+use crate::arith::Overflow;
+use crate::ast::Class;
+use crate::ext::Extensions;
+use crate::interp::RuntimeError;
+use crate::semantic::context::AnalysisContext;
+use crate::symbol::Symbol;
+
+/// Index of a function in `Module::functions`.
+pub type FunctionId = u32;
+
+/// One instruction. Operands are popped from the stack and results pushed;
+/// `Store` and `SetField` leave the value they store on the stack, as an
+/// assignment evaluates to it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Op {
+    Int(i32),
+    Bool(bool),
+    /// The string constant with this index in `Module::strings`
+    Str(u32),
+    Void,
+    /// The receiver of the running function
+    LoadSelf,
+    /// A local slot of the frame: formals first, then `let` and `case` bindings
+    Load(u16),
+    Store(u16),
+    /// An attribute of `self`, in layout order
+    GetField(u16),
+    SetField(u16),
+    Pop,
+    /// A new object of the class with this tag, initialized
+    New(u16),
+    /// `new SELF_TYPE`: a new object of the class of `self`
+    NewSelfType,
+    /// Pops the receiver, then `argc` arguments pushed before it, and calls
+    /// entry `slot` of the dispatch table of the receiver's class
+    Dispatch { method: Symbol, slot: u16, argc: u8 },
+    /// As `Dispatch`, but calls `function` whatever the receiver's class
+    /// (static dispatch and initializers)
+    Call { function: FunctionId, argc: u8 },
+    /// Runs the running function as a built-in method of its class
+    Builtin,
+    Return,
+    Jump(u32),
+    /// Pops a `Bool` and jumps if it is false
+    JumpIfFalse(u32),
+    /// Pops the scrutinee and jumps to the branch for its class in
+    /// `Function::cases[table]`, with the scrutinee pushed back
+    Case(u16),
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Neg,
+    Not,
+    Lt,
+    Le,
+    Eq,
+    IsVoid,
+}
+
+#[derive(Debug)]
+pub struct Function {
+    /// The class that defines it, and the method's name or `_init`
+    pub class: Symbol,
+    pub name: Symbol,
+    /// The input file of `class`, for runtime errors
+    pub file: usize,
+    pub params: usize,
+    /// Slots in the frame, `params` included
+    pub locals: usize,
+    pub code: Vec<Op>,
+    /// Source line of each op, for runtime errors
+    pub lines: Vec<usize>,
+    /// Jump tables of the `case` expressions, indexed by class tag
+    pub cases: Vec<Vec<Option<u32>>>,
+}
+
+#[derive(Debug)]
+pub struct ClassInfo {
+    pub name: Symbol,
+    /// Types of the attributes, in layout order, which give their defaults
+    pub fields: Vec<Symbol>,
+    pub dispatch: Vec<FunctionId>,
+    /// Runs the attribute initializers, those of the ancestors first; `None`
+    /// if neither the class nor an ancestor has any
+    pub init: Option<FunctionId>,
+}
+
+/// A compiled program.
+#[derive(Debug)]
+pub struct Module {
+    /// Indexed by class tag
+    pub classes: Vec<ClassInfo>,
+    pub functions: Vec<Function>,
+    pub strings: Vec<String>,
+}
+
+/// Lower the program of `ctx`, which must have checked cleanly. `classes`
+/// are its classes as annotated by the type checker.
+pub fn compile(ctx: &AnalysisContext<'_>, classes: &[Class]) -> Result<Module> {
+    crate::codegen::check_supported(ctx, classes, &Extensions::default())?;
+    Ok(compile::lower(ctx, classes))
+}
+
+/// Run `module` from `(new Main).main()`, reading `in_string`/`in_int`
+/// input from `input` and writing to `output`, as `interp::run` does.
+pub fn run(
+    module: &Module,
+    overflow: Overflow,
+    input: &mut dyn BufRead,
+    output: &mut dyn Write,
+) -> Result<(), RuntimeError> {
+    machine::run(module, overflow, input, output)
+}
+
+/// A listing of every function, one op per line.
+impl fmt::Display for Module {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (id, function) in self.functions.iter().enumerate() {
+            writeln!(f, "{} {}.{}:", id, function.class, function.name)?;
+            for (pc, op) in function.code.iter().enumerate() {
+                match op {
+                    Op::Str(i) => writeln!(f, "  {:4} Str({:?})", pc, self.strings[*i as usize])?,
+                    op => writeln!(f, "  {:4} {:?}", pc, op)?,
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::Path;
+
+    use super::*;
+    use crate::semantic::type_checker::annotate;
+
+    /// Output of running `source` on `input` with the VM and with the
+    /// interpreter, which must agree.
+    fn run_both(source: &str, input: &str) -> Result<String, RuntimeError> {
+        run_both_overflowing(source, input, Overflow::Wrap)
+    }
+
+    fn run_both_overflowing(source: &str, input: &str, overflow: Overflow) -> Result<String, RuntimeError> {
+        let classes = crate::parse_program(source).unwrap();
+        let ctx = AnalysisContext::new(crate::prelude(), &classes);
+        let module = compile(&ctx, &annotate(&ctx)).unwrap();
+        let mut output = Vec::new();
+        let result =
+            run(&module, overflow, &mut input.as_bytes(), &mut output).map(|()| String::from_utf8(output).unwrap());
+
+        let mut expected = Vec::new();
+        let interpreted = crate::interp::run(&ctx, overflow, &mut input.as_bytes(), &mut expected)
+            .map(|()| String::from_utf8(expected).unwrap());
+        assert_eq!(result, interpreted);
+        result
+    }
+
+    #[test]
+    fn test_run() {
+        let source = "
+class Counter {
+    n : Int <- 10;
+    bump(by : Int) : SELF_TYPE { { n <- n + by; self; } };
+    get() : Int { n };
+};
+class Twice inherits Counter {
+    m : Int <- n * 2;
+    bump(by : Int) : SELF_TYPE { { n <- n + 2 * by; self; } };
+};
+class Main inherits IO {
+    main() : Object {
+        let c : Counter <- new Twice, name : String <- in_string(), i : Int in {
+            out_int(c.bump(1)@Counter.bump(1).get());
+            out_string(\" \".concat(name.substr(1, 3)));
+            case c of o : Object => out_string(\" object\"); t : Counter => out_string(\" counter\"); esac;
+            out_string(\" \".concat(c.type_name()));
+            if isvoid c then out_string(\" void\") else out_string(\" \") fi;
+            while i < 3 loop { out_int(i); i <- i + 1; } pool;
+            out_int(in_int() / 2);
+            out_int(c.copy().bump(5).get() - c.get());
+            if c = c.copy() then abort() else out_string(\"\\n\") fi;
+        }
+    };
+};";
+        assert_eq!(run_both(source, "xabcx\n 42\n").unwrap(), "13 abc counter Twice 0122110\n");
+    }
+
+    #[test]
+    fn test_runtime_errors() {
+        let program = |body: &str| format!("class Main {{ v : Main; main() : Object {{ {} }}; }};", body);
+        let message = |body: &str| run_both(&program(body), "").unwrap_err().message;
+        assert_eq!(message("v.main()"), "Dispatch of 'main' on void");
+        assert_eq!(message("v@Main.main()"), "Dispatch of 'main' on void");
+        assert_eq!(message("case v of x : Int => x; esac"), "Case on void");
+        assert_eq!(message("case 1 of x : Bool => x; esac"), "No 'case' branch for dynamic type 'Int'");
+        assert_eq!(message("1 / 0"), "Division by zero");
+        assert_eq!(message("abort()"), "Abort called from class Main");
+        assert_eq!(message("main()"), format!("Stack overflow: more than {} nested calls", crate::interp::MAX_DEPTH));
+    }
+
+    #[test]
+    fn test_error_files() {
+        // `A` is read from a second file, and `abort` fails in its caller's
+        let source = "class A inherits IO { f(x : A) : Object { x.g() }; g() : Object { abort() }; };\n\
+                      class Main { a : A; main() : Object { { (new A).f(new A); a.f(a); } }; };";
+        let files = ["main.cl".to_string(), "a.cl".to_string()];
+        let located = |mut classes: Vec<Class>| {
+            classes[0].file = 1;
+            let ctx = AnalysisContext::new(crate::prelude(), &classes);
+            let module = compile(&ctx, &annotate(&ctx)).unwrap();
+            let err = run(&module, Overflow::Wrap, &mut "".as_bytes(), &mut Vec::new()).unwrap_err();
+            let interpreted = crate::interp::run(&ctx, Overflow::Wrap, &mut "".as_bytes(), &mut Vec::new());
+            assert_eq!(interpreted.as_ref(), Err(&err));
+            err.located(&files)
+        };
+        assert_eq!(located(crate::parse_program(source).unwrap()), "a.cl: [line 1] Abort called from class A");
+        let source = source.replace("abort()", "0");
+        assert_eq!(located(crate::parse_program(&source).unwrap()), "main.cl: [line 2] Dispatch of 'f' on void");
+    }
+
+    #[test]
+    fn test_overflow() {
+        let program = |body: &str| format!("class Main inherits IO {{ main() : Object {{ out_int({}) }}; }};", body);
+        for (body, wrapped) in [("2147483647 + 1", "-2147483648"), ("~2147483647 - 2", "2147483647"), ("65536 * 65536", "0")] {
+            assert_eq!(run_both_overflowing(&program(body), "", Overflow::Wrap).unwrap(), wrapped);
+            let err = run_both_overflowing(&program(body), "", Overflow::Trap).unwrap_err();
+            assert_eq!(err.message, "Integer overflow");
+        }
+        let min = "~2147483647 - 1";
+        assert_eq!(run_both_overflowing(&program(&format!("({}) / ~1", min)), "", Overflow::Wrap).unwrap(), "-2147483648");
+        assert!(run_both_overflowing(&program(&format!("~({})", min)), "", Overflow::Trap).is_err());
+    }
+
+    #[test]
+    fn test_examples() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("examples");
+        for entry in fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path.extension().is_some_and(|ext| ext == "cl") {
+                let source = fs::read_to_string(&path).unwrap();
+                // The two must agree whether or not the example fails
+                let _ = run_both(&source, "12\n3\n");
+            }
+        }
+    }
+}
@@ -0,0 +1,149 @@
+//! `--watch`: check a file again each time it is saved.
+//!
+//! The file's modification time and size are polled, which needs nothing
+//! beyond the standard library and notices every editor's way of saving.
+//! Each run goes through an `incremental::Session`, so a save re-checks
+//! only what it touched, and prints the diagnostics one per line and then a
+//! summary with the time each phase took:
+//!
+//! ```text
+//! foo.cl:3:15: error[E0010]: Type mismatch: expected 'Int', found 'String'
+//! foo.cl: 1 error, 0 warnings (lex 21.4µs; parse 80.1µs, 1 of 3 classes; semant 95.0µs, 1 of 3 classes)
+//! ```
+
+use std::fs;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+use crate::compiler::Compiler;
+use crate::diagnostic::render::{short, SourceFile};
+use crate::diagnostic::Diagnostic;
+use crate::incremental::{Session, Stats};
+use crate::parsing::recovery::SyntaxErrors;
+use crate::semantic::collector::ErrorCollector;
+use crate::semantic::severity::{Level, Levels};
+use crate::source::SourceText;
+
+/// How often the file is looked at.
+pub const POLL: Duration = Duration::from_millis(200);
+
+/// One watched file and what is known about it.
+pub struct Watch {
+    path: PathBuf,
+    session: Session,
+    levels: Levels,
+    /// The modification time and size last seen, if the file was there
+    stamp: Option<(SystemTime, u64)>,
+}
+
+impl Watch {
+    pub fn new(path: PathBuf, compiler: Compiler, levels: Levels) -> Self {
+        let stamp = stamp(&path);
+        Watch { path, session: Session::new(compiler), levels, stamp }
+    }
+
+    /// Check the file now and then after every change, forever.
+    pub fn run(mut self, out: &mut dyn Write) -> io::Result<()> {
+        self.check(out)?;
+        loop {
+            thread::sleep(POLL);
+            if self.changed() {
+                self.check(out)?;
+            }
+        }
+    }
+
+    /// Whether the file was saved, created or removed since the last call.
+    pub fn changed(&mut self) -> bool {
+        let stamp = stamp(&self.path);
+        let changed = stamp != self.stamp;
+        self.stamp = stamp;
+        changed
+    }
+
+    /// Check the file, and print its diagnostics and the summary to `out`.
+    pub fn check(&mut self, out: &mut dyn Write) -> io::Result<()> {
+        let name = self.path.display().to_string();
+        let source = match SourceText::read(&self.path) {
+            Ok(source) => source,
+            Err(err) => return writeln!(out, "error: {:#}", err),
+        };
+        let start = Instant::now();
+        let mut ec = ErrorCollector::with_levels(self.levels.clone());
+        let (diagnostics, times) = match self.session.analyze(source.as_str(), &mut ec) {
+            Ok(_) => (ec.diagnostics(), phase_times(self.session.stats())),
+            Err(err) => match err.downcast::<SyntaxErrors>() {
+                Ok(errors) => {
+                    let diagnostics = errors.0.iter().map(|err| Diagnostic::syntax(err, 0, source.as_str())).collect();
+                    (diagnostics, format!("lex and parse {:.1?}", start.elapsed()))
+                }
+                Err(err) => return writeln!(out, "error: {:#}", err),
+            },
+        };
+
+        let files = [SourceFile { name: &name, text: source.as_str() }];
+        for diagnostic in &diagnostics {
+            write!(out, "{}", short(diagnostic, &files))?;
+        }
+        let errors = diagnostics.iter().filter(|d| d.level == Level::Deny).count();
+        let warnings = diagnostics.len() - errors;
+        writeln!(out, "{}: {}, {} ({})", name, plural(errors, "error"), plural(warnings, "warning"), times)?;
+        out.flush()
+    }
+}
+
+fn stamp(path: &std::path::Path) -> Option<(SystemTime, u64)> {
+    let metadata = fs::metadata(path).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
+
+fn phase_times(stats: Stats) -> String {
+    let Stats { classes, parsed, checked, lex_time, parse_time, check_time } = stats;
+    format!(
+        "lex {:.1?}; parse {:.1?}, {} of {} classes; semant {:.1?}, {} of {} classes",
+        lex_time, parse_time, parsed, classes, check_time, checked, classes
+    )
+}
+
+fn plural(n: usize, what: &str) -> String {
+    format!("{} {}{}", n, what, if n == 1 { "" } else { "s" })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_checks_each_save() {
+        let dir = std::env::temp_dir().join(format!("cool-rs-watch-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("main.cl");
+        fs::write(&file, "class A { f() : Int { 1 }; };\nclass Main { main() : Int { 0 }; };\n").unwrap();
+
+        let mut watch = Watch::new(file.clone(), Compiler::default(), Levels::default());
+        let mut out = Vec::new();
+        watch.check(&mut out).unwrap();
+        let first = String::from_utf8(std::mem::take(&mut out)).unwrap();
+        let summary = format!("{}: 0 errors, 0 warnings (lex ", file.display());
+        assert!(first.starts_with(&summary) && first.ends_with(", 2 of 2 classes)\n"), "{}", first);
+        assert!(!watch.changed());
+
+        fs::write(&file, "class A { f() : Int { \"one\" }; };\nclass Main { main() : Int { 0 }; };\n").unwrap();
+        assert!(watch.changed());
+        watch.check(&mut out).unwrap();
+        let second = String::from_utf8(std::mem::take(&mut out)).unwrap();
+        let (diagnostic, summary) = second.split_once('\n').unwrap();
+        let expected = format!("{}:1:23: error[E0010]: Type mismatch: expected 'Int', found 'String'", file.display());
+        assert_eq!(diagnostic, expected);
+        assert!(summary.contains(": 1 error, 0 warnings (") && summary.ends_with(", 1 of 2 classes)\n"), "{}", summary);
+
+        fs::write(&file, "class A {").unwrap();
+        watch.check(&mut out).unwrap();
+        let third = String::from_utf8(out).unwrap();
+        assert!(third.contains("error[E0035]: Unexpected end of file\n"), "{}", third);
+        assert!(third.contains(": 1 error, 0 warnings (lex and parse "), "{}", third);
+        fs::remove_dir_all(dir).ok();
+    }
+}
@@ -0,0 +1,594 @@
+//! Exit codes and error reporting of the `cool-rs` binary when its input
+//! cannot be read or is rejected, `--max-errors`, `--quiet` and
+//! `--verbose`, programs split across several input files, the flags that
+//! stop after one phase, `--emit ast-json`, `--deny-warnings`, the REPL,
+//! `fmt`, `--watch`, `--incremental`, the VM, `--emit c`, `--emit ir`,
+//! `--emit cfg-dot`, `--emit hierarchy-dot`, `--opt-level`, `--remarks`,
+//! `--timings` and
+//! input nested too deeply.
+
+use std::fs;
+use std::process::{Command, Output};
+
+fn cool_rs(file: &std::path::Path) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_cool-rs"))
+        .arg("--file")
+        .arg(file)
+        .output()
+        .expect("failed to run cool-rs")
+}
+
+fn scratch_dir(name: &str) -> std::path::PathBuf {
+    let dir = std::env::temp_dir().join(format!("cool-rs-cli-{}-{}", name, std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[test]
+fn test_missing_file() {
+    let dir = scratch_dir("missing");
+    let output = cool_rs(&dir.join("missing.cl"));
+    assert_eq!(output.status.code(), Some(5));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.starts_with("error: Failed to read source file: "), "{}", stderr);
+    assert!(stderr.ends_with("No such file or directory (os error 2)\n"), "{}", stderr);
+    fs::remove_dir_all(dir).ok();
+}
+
+#[test]
+fn test_invalid_utf8_and_directories() {
+    let dir = scratch_dir("utf8");
+    let file = dir.join("latin1.cl");
+    fs::write(&file, b"class Main { s : String <- \"caf\xe9\"; };\n").unwrap();
+
+    for path in [&file, &dir] {
+        let output = cool_rs(path);
+        assert_eq!(output.status.code(), Some(5));
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        // One clean line, without eyre's report sections
+        assert!(stderr.starts_with("error: Failed to read source file: "), "{}", stderr);
+        assert_eq!(stderr.lines().count(), 1, "{}", stderr);
+    }
+    fs::remove_dir_all(dir).ok();
+}
+
+#[test]
+fn test_multiple_files() {
+    let dir = scratch_dir("multi");
+    let lib = dir.join("lib.cl");
+    let main = dir.join("main.cl");
+    fs::write(&lib, "class Greeter inherits IO {\n    greet() : Object { out_string(\"hi\\n\") };\n};\n").unwrap();
+    fs::write(&main, "class Main {\n    main() : Object { (new Greeter).greet() };\n};\n").unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_cool-rs"))
+        .args(["--run", "--file"])
+        .args([&lib, &main])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "hi\n");
+
+    // Each diagnostic names the file it is in
+    fs::write(&lib, "class Greeter inherits IO {\n    greet() : Int { out_string(\"hi\\n\") };\n};\n").unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_cool-rs"))
+        .arg("--file")
+        .arg(&main)
+        .arg("--file")
+        .arg(&lib)
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(4));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains(&format!(" --> {}:2:21\n", lib.display())), "{}", stderr);
+    fs::remove_dir_all(dir).ok();
+}
+
+#[test]
+fn test_stage_flags() {
+    let dir = scratch_dir("stages");
+    let file = dir.join("ill_typed.cl");
+    fs::write(&file, "class Main {\n    main() : Int { \"zero\" };\n};\n").unwrap();
+    let stage = |flag: &str| {
+        let output = Command::new(env!("CARGO_BIN_EXE_cool-rs"))
+            .args([flag, "--file"])
+            .arg(&file)
+            .output()
+            .unwrap();
+        (output.status.code(), String::from_utf8(output.stdout).unwrap())
+    };
+
+    let (code, stdout) = stage("--lex");
+    assert_eq!(code, Some(0));
+    assert!(stdout.starts_with(&format!("#name \"{}\"\n#1 CLASS\n", file.display())), "{}", stdout);
+    // The type error is only found by the semantic checks
+    let (code, stdout) = stage("--parse");
+    assert_eq!(code, Some(0));
+    assert!(stdout.starts_with("#1\n_program\n  #1\n  _class\n    Main\n"), "{}", stdout);
+    let (code, stdout) = stage("--semant");
+    assert_eq!((code, stdout.as_str()), (Some(4), ""));
+
+    // The status follows the errors the dump reports
+    fs::write(&file, "class Main {};\n*)\n").unwrap();
+    let (code, stdout) = stage("--lex");
+    assert_eq!(code, Some(2));
+    assert!(stdout.ends_with("#2 ERROR \"Unmatched *)\"\n"), "{}", stdout);
+    fs::remove_dir_all(dir).ok();
+}
+
+#[test]
+fn test_tokens_in() {
+    let dir = scratch_dir("tokens");
+    let file = dir.join("main.cl");
+    fs::write(&file, "class Main inherits IO {\n    main() : Object { out_int(6 * 7) };\n};\n").unwrap();
+    let lexed = Command::new(env!("CARGO_BIN_EXE_cool-rs")).args(["--lex", "--file"]).arg(&file).output().unwrap();
+    let tokens = dir.join("main.tokens");
+    fs::write(&tokens, &lexed.stdout).unwrap();
+    let from_tokens = |args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_cool-rs")).args(args).arg("--tokens-in").arg(&tokens).output().unwrap()
+    };
+
+    let output = from_tokens(&["-q", "--run"]);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "42");
+    let parsed = from_tokens(&["--parse"]);
+    let from_source = Command::new(env!("CARGO_BIN_EXE_cool-rs")).args(["--parse", "--file"]).arg(&file).output().unwrap();
+    assert_eq!(String::from_utf8(parsed.stdout).unwrap(), String::from_utf8(from_source.stdout).unwrap());
+
+    // Diagnostics point into the tokens laid out on their lines
+    let broken = String::from_utf8(lexed.stdout).unwrap().replace("#2 '*'", "#2 '*'\n#2 '*'");
+    fs::write(&tokens, broken).unwrap();
+    let output = from_tokens(&[]);
+    assert_eq!(output.status.code(), Some(3));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains(&format!(" --> {}:2:35\n", file.display())), "{}", stderr);
+    fs::write(&tokens, "#1 CLASS\n#1 AT\n").unwrap();
+    let output = from_tokens(&[]);
+    assert_eq!(output.status.code(), Some(5));
+    assert!(String::from_utf8(output.stderr).unwrap().contains("line 2: unknown token: #1 AT"));
+    fs::remove_dir_all(dir).ok();
+}
+
+#[test]
+fn test_emit_ast_json() {
+    let dir = scratch_dir("json");
+    let file = dir.join("main.cl");
+    fs::write(&file, "class Main {\n    main() : Int { 1 + 2 };\n};\n").unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_cool-rs"))
+        .args(["--emit", "ast-json", "--file"])
+        .arg(&file)
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(0));
+
+    let json: serde_json::Value = serde_json::from_str(&fs::read_to_string(dir.join("main.ast.json")).unwrap()).unwrap();
+    assert_eq!(json["files"][0], file.display().to_string());
+    let class = &json["classes"][0];
+    assert_eq!((&class["name"], &class["file"]), (&"Main".into(), &0.into()));
+    let body = &class["feature_list"][0]["Method"][3];
+    assert_eq!(body["static_type"], "Int");
+    assert_eq!(body["span"], serde_json::json!({ "file": 0, "start": 32, "end": 37 }));
+    fs::remove_dir_all(dir).ok();
+}
+
+#[test]
+fn test_deny_warnings() {
+    let dir = scratch_dir("lints");
+    let file = dir.join("main.cl");
+    fs::write(&file, "class Main {\n    unused : Int;\n    main() : Int { 0 };\n};\n").unwrap();
+    let run = |flags: &[&str]| {
+        let output = Command::new(env!("CARGO_BIN_EXE_cool-rs")).args(flags).arg("--file").arg(&file).output().unwrap();
+        (output.status.code(), String::from_utf8(output.stderr).unwrap())
+    };
+
+    let (code, stderr) = run(&[]);
+    assert_eq!(code, Some(0));
+    assert!(stderr.starts_with("warning[W0002]: "), "{}", stderr);
+    let (code, stderr) = run(&["--deny-warnings"]);
+    assert_eq!(code, Some(4));
+    assert!(stderr.starts_with("error[W0002]: "), "{}", stderr);
+    // An allowed lint stays silent
+    assert_eq!(run(&["--deny-warnings", "--allow", "unused_attribute"]), (Some(0), String::new()));
+    fs::remove_dir_all(dir).ok();
+}
+
+#[test]
+fn test_exit_codes_and_max_errors() {
+    let dir = scratch_dir("exit");
+    let file = dir.join("main.cl");
+    let run = |source: &str, flags: &[&str]| {
+        fs::write(&file, source).unwrap();
+        let output = Command::new(env!("CARGO_BIN_EXE_cool-rs")).args(flags).arg("--file").arg(&file).output().unwrap();
+        (output.status.code(), String::from_utf8(output.stderr).unwrap())
+    };
+
+    // Each phase that rejects the program has its own status
+    assert_eq!(run("class Main { main() : Int { 0 # 1 }; };\n", &[]).0, Some(2));
+    assert_eq!(run("class Main { main() : Int { 0 }\n", &[]).0, Some(3));
+    assert_eq!(run("class Main { main() : Object { abort() }; };\n", &["--run"]).0, Some(1));
+
+    let ill_typed = "class Main {\n    a : Int <- \"a\";\n    b : Int <- \"b\";\n    c : Int <- \"c\";\n    main() : Int { 0 };\n};\n";
+    let (code, stderr) = run(ill_typed, &[]);
+    assert_eq!((code, stderr.matches("error[E0010]").count()), (Some(4), 3));
+    let (code, stderr) = run(ill_typed, &["--max-errors", "2"]);
+    assert_eq!((code, stderr.matches("error[E0010]").count()), (Some(4), 2));
+    assert!(stderr.ends_with("note: 4 more diagnostics not shown (--max-errors 2)\n"), "{}", stderr);
+    assert_eq!(run(ill_typed, &["--max-errors", "0"]).0, Some(5));
+    fs::remove_dir_all(dir).ok();
+}
+
+#[test]
+fn test_quiet_and_verbose() {
+    let dir = scratch_dir("verbose");
+    let file = dir.join("main.cl");
+    fs::write(&file, "class Main {\n    main() : Int { 0 };\n};\n").unwrap();
+    let run = |flags: &[&str]| {
+        let output = Command::new(env!("CARGO_BIN_EXE_cool-rs")).args(flags).arg("--file").arg(&file).output().unwrap();
+        (String::from_utf8(output.stdout).unwrap(), String::from_utf8(output.stderr).unwrap())
+    };
+
+    assert_eq!(run(&[]), ("Semantic checks passed without errors.\n".to_string(), String::new()));
+    assert_eq!(run(&["--quiet"]), (String::new(), String::new()));
+    let (_, stderr) = run(&["-v"]);
+    assert!(stderr.starts_with(&format!("info  parse: parsed {} in ", file.display())), "{}", stderr);
+    assert!(stderr.contains("info  semant: errors: 0, warnings: 0\n") && !stderr.contains("debug"), "{}", stderr);
+    // -vv adds the AST; --log keeps one phase
+    let (_, stderr) = run(&["-vv", "--log", "parse"]);
+    assert!(stderr.contains("debug parse: ") && stderr.contains("name: \"Main\""), "{}", stderr);
+    assert!(!stderr.contains("semant"), "{}", stderr);
+    fs::remove_dir_all(dir).ok();
+}
+
+#[test]
+fn test_fmt() {
+    let dir = scratch_dir("fmt");
+    let file = dir.join("main.cl");
+    let messy = "class Main {   main() : Int {0}; -- zero\n};\n";
+    fs::write(&file, messy).unwrap();
+    let fmt = |flags: &[&str]| Command::new(env!("CARGO_BIN_EXE_cool-rs")).arg("fmt").args(flags).arg(&file).output().unwrap();
+
+    let output = fmt(&["--check"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), format!("Would reformat: {}\n", file.display()));
+    assert_eq!(fs::read_to_string(&file).unwrap(), messy);
+
+    assert_eq!(fmt(&[]).status.code(), Some(0));
+    assert_eq!(fs::read_to_string(&file).unwrap(), "class Main {\n    main() : Int { 0 }; -- zero\n};\n");
+    assert_eq!(fmt(&["--check"]).status.code(), Some(0));
+
+    fs::write(&file, "class Main {").unwrap();
+    let output = fmt(&[]);
+    assert_eq!(output.status.code(), Some(3));
+    assert!(String::from_utf8(output.stderr).unwrap().contains("Unexpected end of file"));
+    fs::remove_dir_all(dir).ok();
+}
+
+#[test]
+fn test_watch() {
+    use std::io::BufRead;
+
+    let dir = scratch_dir("watch");
+    let file = dir.join("main.cl");
+    fs::write(&file, "class Main {\n    main() : Int { 0 };\n};\n").unwrap();
+    let mut child = Command::new(env!("CARGO_BIN_EXE_cool-rs"))
+        .arg("--file")
+        .arg(&file)
+        .arg("--watch")
+        .stdout(std::process::Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut lines = std::io::BufReader::new(child.stdout.take().unwrap()).lines();
+    let first = lines.next().unwrap().unwrap();
+    assert!(first.starts_with(&format!("{}: 0 errors, 0 warnings (lex ", file.display())), "{}", first);
+
+    fs::write(&file, "class Main {\n    main() : Int { \"zero\" };\n};\n").unwrap();
+    let diagnostic = lines.next().unwrap().unwrap();
+    assert!(diagnostic.ends_with(":2:20: error[E0010]: Type mismatch: expected 'Int', found 'String'"), "{}", diagnostic);
+    let summary = lines.next().unwrap().unwrap();
+    assert!(summary.contains(": 1 error, 0 warnings ("), "{}", summary);
+    child.kill().unwrap();
+    child.wait().unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_cool-rs"))
+        .args(["--watch", "--file"])
+        .args([&file, &file])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(5));
+    assert_eq!(String::from_utf8(output.stderr).unwrap(), "error: --watch takes one input file\n");
+    fs::remove_dir_all(dir).ok();
+}
+
+#[test]
+fn test_incremental() {
+    let dir = scratch_dir("incremental");
+    let lib = dir.join("lib.cl");
+    let main = dir.join("main.cl");
+    fs::write(&lib, "class Greeter inherits IO {\n    greet() : Object { out_string(\"hi\\n\") };\n};\n").unwrap();
+    fs::write(&main, "class Main {\n    main() : Object { (new Greeter).greet() };\n};\n").unwrap();
+    // Run where the cache is to be kept
+    let run = || {
+        let output = Command::new(env!("CARGO_BIN_EXE_cool-rs"))
+            .current_dir(&dir)
+            .args(["--incremental", "--run", "-v", "--log", "cache", "--file", "lib.cl", "--file", "main.cl"])
+            .output()
+            .unwrap();
+        assert_eq!(output.status.code(), Some(0));
+        assert_eq!(String::from_utf8(output.stdout).unwrap(), "hi\n");
+        String::from_utf8(output.stderr).unwrap()
+    };
+
+    assert_eq!(run(), "info  cache: parsed 2 of 2 classes\ninfo  cache: checked 2 of 2 classes\n");
+    assert_eq!(run(), "info  cache: parsed 0 of 2 classes\ninfo  cache: checked 0 of 2 classes\n");
+    // One entry per file
+    assert_eq!(fs::read_dir(dir.join(".cool-cache")).unwrap().count(), 2);
+    fs::write(&main, "class Main {\n    main() : Object { {\n        (new Greeter).greet();\n    } };\n};\n").unwrap();
+    assert_eq!(run(), "info  cache: parsed 1 of 2 classes\ninfo  cache: checked 1 of 2 classes\n");
+    fs::remove_dir_all(dir).ok();
+}
+
+#[test]
+fn test_repl() {
+    use std::io::Write;
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_cool-rs"))
+        .arg("repl")
+        .stdin(std::process::Stdio::piped())
+        .stdout(std::process::Stdio::piped())
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(b"class A { f() : Int { 4 }; };\n(new A).f() *\n2\nin_int() + 1\n41\nx\n").unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "cool> class A\ncool>   ... 8 : Int\ncool> 42 : Int\ncool> cool> \n");
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.starts_with("error[E0009]: Variable 'x' is not declared\n --> <input 4>:1:1\n"), "{}", stderr);
+}
+
+#[test]
+fn test_vm() {
+    let dir = scratch_dir("vm");
+    let file = dir.join("main.cl");
+    fs::write(&file, "class Main inherits IO {\n    main() : Object { out_int(in_int() * 2) };\n};\n").unwrap();
+    let run = |flags: &[&str]| {
+        use std::io::Write;
+        let mut child = Command::new(env!("CARGO_BIN_EXE_cool-rs"))
+            .args(flags)
+            .arg("--file")
+            .arg(&file)
+            .stdin(std::process::Stdio::piped())
+            .stdout(std::process::Stdio::piped())
+            .spawn()
+            .unwrap();
+        child.stdin.take().unwrap().write_all(b"21\n").ok();
+        let output = child.wait_with_output().unwrap();
+        (output.status.code(), String::from_utf8(output.stdout).unwrap())
+    };
+
+    assert_eq!(run(&["--run", "--vm"]), (Some(0), "42".to_string()));
+    assert_eq!(run(&["--emit", "bytecode"]).0, Some(0));
+    let listing = fs::read_to_string(dir.join("main.bytecode")).unwrap();
+    assert!(listing.contains(" Main.main:\n"), "{}", listing);
+    fs::remove_dir_all(dir).ok();
+}
+
+#[test]
+fn test_emit_c() {
+    let dir = scratch_dir("c");
+    let file = dir.join("main.cl");
+    fs::write(&file, "class Main inherits IO {\n    main() : Object { out_int(6 * 7) };\n};\n").unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_cool-rs"))
+        .args(["--emit", "c", "--file"])
+        .arg(&file)
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(0));
+    let c = fs::read_to_string(dir.join("main.c")).unwrap();
+    assert!(c.contains("static Object *M4Main_main(Object *v0) {\n"), "{}", c);
+    assert!(c.contains("int main(void) {\n"), "{}", c);
+    fs::remove_dir_all(dir).ok();
+}
+
+#[test]
+fn test_emit_ir() {
+    let dir = scratch_dir("ir");
+    let file = dir.join("main.cl");
+    fs::write(&file, "class Main inherits IO {\n    main() : Object { out_int(6 * 7) };\n};\n").unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_cool-rs"))
+        .args(["--emit", "ir", "--file"])
+        .arg(&file)
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(0));
+    let ir = fs::read_to_string(dir.join("main.ir")).unwrap();
+    assert!(ir.contains("Main.main(v0 : Main) {\nb0:\n"), "{}", ir);
+    assert!(ir.contains(" = mul v1, v2\n"), "{}", ir);
+
+    let output = Command::new(env!("CARGO_BIN_EXE_cool-rs"))
+        .args(["--emit", "cfg-dot", "--file"])
+        .arg(&file)
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(0));
+    let dot = fs::read_to_string(dir.join("main.dot")).unwrap();
+    assert!(dot.starts_with("digraph program {\n"), "{}", dot);
+    assert!(dot.contains("label=\"Main.main\";"), "{}", dot);
+    fs::remove_dir_all(dir).ok();
+}
+
+#[test]
+fn test_emit_hierarchy_dot() {
+    let dir = scratch_dir("hierarchy");
+    let file = dir.join("main.cl");
+    fs::write(&file, "class Main inherits Shape {\n    main() : Object { 0 };\n};\n").unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_cool-rs"))
+        .args(["--emit", "hierarchy-dot", "--file"])
+        .arg(&file)
+        .output()
+        .unwrap();
+    // Rejected, but drawn
+    assert_eq!(output.status.code(), Some(4));
+    let dot = fs::read_to_string(dir.join("main.hierarchy.dot")).unwrap();
+    assert!(dot.starts_with("digraph hierarchy {\n"), "{}", dot);
+    assert!(dot.contains("    \"Shape\" -> \"Main\" [color=red];\n"), "{}", dot);
+    fs::remove_dir_all(dir).ok();
+}
+
+#[test]
+fn test_opt_level() {
+    let dir = scratch_dir("opt");
+    let file = dir.join("main.cl");
+    fs::write(&file, "class Main inherits IO {\n    main() : Object { out_int(6 * 7) };\n};\n").unwrap();
+    let listing = |level: &str| {
+        let output = Command::new(env!("CARGO_BIN_EXE_cool-rs"))
+            .args(["--emit", "bytecode", "--opt-level", level, "--file"])
+            .arg(&file)
+            .output()
+            .unwrap();
+        (output.status.code(), fs::read_to_string(dir.join("main.bytecode")).unwrap_or_default())
+    };
+
+    let (code, unoptimized) = listing("0");
+    assert_eq!(code, Some(0));
+    assert!(unoptimized.contains(" Int(6)\n") && !unoptimized.contains(" Int(42)\n"), "{}", unoptimized);
+    let (code, optimized) = listing("1");
+    assert_eq!(code, Some(0));
+    assert!(optimized.contains(" Int(42)\n") && !optimized.contains(" Mul\n"), "{}", optimized);
+    assert_eq!(listing("9").0, Some(5));
+
+    // Dead code is removed from the output and reported
+    fs::write(&file, "class Main inherits IO {\n    main() : Object { if 1 < 0 then abort() else out_int(42) fi };\n};\n").unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_cool-rs"))
+        .args(["--emit", "bytecode", "--opt-level", "2", "--file"])
+        .arg(&file)
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(0));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.starts_with("warning[W0005]: Unreachable code: the condition is always false\n"), "{}", stderr);
+    assert!(stderr.contains(&format!(" --> {}:2:37\n", file.display())), "{}", stderr);
+    let optimized = fs::read_to_string(dir.join("main.bytecode")).unwrap();
+    assert!(!optimized.contains("JumpIfFalse") && optimized.contains(" Int(42)\n"), "{}", optimized);
+
+    // What the passes did, with where
+    let remarks = |format: &str| {
+        let output = Command::new(env!("CARGO_BIN_EXE_cool-rs"))
+            .args(["--opt-level", "2", "--allow", "constant_condition", "-q", "--remarks", format, "--file"])
+            .arg(&file)
+            .output()
+            .unwrap();
+        assert_eq!(output.status.code(), Some(0));
+        String::from_utf8(output.stderr).unwrap()
+    };
+    assert_eq!(
+        remarks("human"),
+        format!(
+            "{0}:2:26: remark[fold]: folded `1 < 0` to `false`\n\
+             {0}:2:37: remark[dead-code]: eliminated the dead `then` branch of `if false`\n",
+            file.display()
+        )
+    );
+    let json = remarks("json");
+    let first: serde_json::Value = serde_json::from_str(json.lines().next().unwrap()).unwrap();
+    assert_eq!(first["pass"], "fold");
+    assert_eq!((first["line"].as_u64(), first["column"].as_u64()), (Some(2), Some(26)));
+    assert_eq!(json.lines().count(), 2, "{}", json);
+    fs::remove_dir_all(dir).ok();
+}
+
+#[test]
+fn test_int_overflow() {
+    let dir = scratch_dir("overflow");
+    let file = dir.join("main.cl");
+    fs::write(&file, "class Main inherits IO {\n    main() : Object { out_int(2147483647 + 1) };\n};\n").unwrap();
+    let run = |args: &[&str]| {
+        Command::new(env!("CARGO_BIN_EXE_cool-rs")).args(["-q", "--run"]).args(args).arg("--file").arg(&file).output().unwrap()
+    };
+
+    let wrapped = run(&[]);
+    assert_eq!(wrapped.status.code(), Some(0));
+    assert_eq!(String::from_utf8(wrapped.stdout).unwrap(), "-2147483648");
+    for args in [&["--int-overflow", "trap"][..], &["--int-overflow", "trap", "--vm", "--opt-level", "1"]] {
+        let trapped = run(args);
+        assert_eq!(trapped.status.code(), Some(1), "{:?}", args);
+        let expected = format!("error: {}: [line 2] Integer overflow\n", file.display());
+        assert_eq!(String::from_utf8(trapped.stderr).unwrap(), expected, "{:?}", args);
+    }
+    fs::remove_dir_all(dir).ok();
+}
+
+#[test]
+fn test_timings() {
+    let dir = scratch_dir("timings");
+    let file = dir.join("main.cl");
+    fs::write(&file, "class Main inherits IO {\n    main() : Object { out_int(1 + 2) };\n};\n").unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_cool-rs"))
+        .args(["-q", "--run", "--timings", "--file"])
+        .arg(&file)
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "3");
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    let phases: Vec<&str> = stderr.lines().map(|line| line.rsplit_once(' ').unwrap().0.trim_end()).collect();
+    assert_eq!(phases, ["lex", "parse", "class table", "semant", "opt", "run", "total"], "{}", stderr);
+
+    // A rejected program reports the phases that ran
+    fs::write(&file, "class Main {\n    main() : Int { \"one\" };\n};\n").unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_cool-rs")).args(["--timings", "--file"]).arg(&file).output().unwrap();
+    assert_eq!(output.status.code(), Some(4));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("\nsemant ") && stderr.ends_with("\n") && stderr.lines().last().unwrap().starts_with("total "), "{}", stderr);
+    fs::remove_dir_all(dir).ok();
+}
+
+#[test]
+fn test_deep_brackets() {
+    let dir = scratch_dir("brackets");
+    let file = dir.join("main.cl");
+    let n = 50_000;
+    for body in [format!("{}1{}", "(".repeat(n), ")".repeat(n)), format!("{}1", "~(".repeat(n)), "{".repeat(n)] {
+        fs::write(&file, format!("class Main {{\n    main() : Int {{ {} }};\n}};\n", body)).unwrap();
+        for args in [&[][..], &["--parse"], &["--timings"]] {
+            let output = Command::new(env!("CARGO_BIN_EXE_cool-rs")).args(args).arg("--file").arg(&file).output().unwrap();
+            let stderr = String::from_utf8(output.stderr).unwrap();
+            assert_eq!(output.status.code(), Some(2), "{:?}: {}", args, stderr);
+            assert!(stderr.starts_with("error[E0041]: Brackets and prefix operators nested more than 10000 levels deep\n"), "{}", stderr);
+        }
+    }
+    fs::remove_dir_all(dir).ok();
+}
+
+#[test]
+fn test_deep_nesting() {
+    let dir = scratch_dir("nesting");
+    let file = dir.join("main.cl");
+    let modes: [&[&str]; 8] = [
+        &[],
+        &["--parse"],
+        &["--semant"],
+        &["--verify"],
+        &["--run"],
+        &["--run", "--vm"],
+        &["--emit", "c"],
+        &["--emit", "asm"],
+    ];
+    // Just within the limit, every phase gets through it
+    let chain = |n| format!("class Main {{\n    main() : Int {{ {}1 }};\n}};\n", "1 + ".repeat(n));
+    fs::write(&file, chain(9_990)).unwrap();
+    for args in modes {
+        let output = Command::new(env!("CARGO_BIN_EXE_cool-rs")).arg("-q").args(args).arg("--file").arg(&file).output().unwrap();
+        assert_eq!(output.status.code(), Some(0), "{:?}: {}", args, String::from_utf8_lossy(&output.stderr));
+    }
+    // Past it, the program is rejected before any of them runs
+    for n in [50_000, 200_000] {
+        fs::write(&file, chain(n)).unwrap();
+        for args in modes {
+            let output = Command::new(env!("CARGO_BIN_EXE_cool-rs")).args(args).arg("--file").arg(&file).output().unwrap();
+            let stderr = String::from_utf8(output.stderr).unwrap();
+            assert_eq!(output.status.code(), Some(4), "{} {:?}: {}", n, args, stderr);
+            assert!(stderr.starts_with("error[E0039]: Expression nested more than 10000 levels deep\n"), "{}", stderr);
+            assert!(output.stdout.is_empty(), "{} {:?}", n, args);
+        }
+    }
+    fs::remove_dir_all(dir).ok();
+}
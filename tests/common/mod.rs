@@ -0,0 +1,35 @@
+//! Helpers shared by the file-comparison test harnesses.
+
+// Each harness uses only some of these
+#![allow(dead_code)]
+
+/// First line where `expected` and `actual` differ, for the failure report.
+pub fn first_difference(expected: &str, actual: &str) -> String {
+    let mut expected_lines = expected.lines();
+    let mut actual_lines = actual.lines();
+    for line in 1.. {
+        match (expected_lines.next(), actual_lines.next()) {
+            (Some(e), Some(a)) if e == a => continue,
+            (None, None) => break,
+            (e, a) => {
+                return format!(
+                    "line {}:\n  expected: {}\n  actual:   {}",
+                    line,
+                    e.unwrap_or("<end of file>"),
+                    a.unwrap_or("<end of file>")
+                );
+            }
+        }
+    }
+    "only trailing whitespace differs".to_string()
+}
+
+/// Extensions a fixture asks for in a leading `-- ext: NAME ...` comment.
+pub fn fixture_extensions(source: &str) -> Vec<&str> {
+    source
+        .lines()
+        .next()
+        .and_then(|line| line.strip_prefix("-- ext:"))
+        .map(|names| names.split_whitespace().collect())
+        .unwrap_or_default()
+}
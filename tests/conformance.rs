@@ -0,0 +1,87 @@
+//! Conformance tests: each program under `tests/conformance` encodes what a
+//! section of the COOL reference manual says a program does when it runs,
+//! cited in its leading comment, and must print exactly what the `.out`
+//! file next to it holds. It reads the `.in` file next to it, if there is
+//! one, as its input. A run that stops at a runtime error ends with a line
+//! `error: ` and the error, as `--run` reports it less the file name.
+//!
+//! Every program is run by the interpreter and by the VM, which must agree
+//! with the manual alike.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use cool_rs::arith::Overflow;
+use cool_rs::semantic::collector::ErrorCollector;
+use cool_rs::semantic::context::AnalysisContext;
+use cool_rs::{check_program, interp, parse_program, prelude, vm};
+
+mod common;
+use common::first_difference;
+
+#[derive(Debug, Clone, Copy)]
+enum Engine {
+    Interpreter,
+    Vm,
+}
+
+/// What running `source` on `input` with `engine` prints, or why it could
+/// not be run.
+fn run(source: &str, input: &str, engine: Engine) -> Result<String, String> {
+    let classes = parse_program(source).map_err(|err| format!("{:#}", err))?;
+    let ctx = AnalysisContext::new(prelude(), &classes);
+    let mut ec = ErrorCollector::default();
+    let typed = check_program(&ctx, &mut ec);
+    let Some(typed) = typed.filter(|_| ec.errors.is_empty()) else {
+        return Err(ec.errors.iter().map(|e| e.to_string()).collect::<Vec<_>>().join("\n"));
+    };
+
+    let mut output = Vec::new();
+    let result = match engine {
+        Engine::Interpreter => interp::run(&ctx, Overflow::Wrap, &mut input.as_bytes(), &mut output),
+        Engine::Vm => {
+            let module = vm::compile(&ctx, &typed).map_err(|err| format!("{:#}", err))?;
+            vm::run(&module, Overflow::Wrap, &mut input.as_bytes(), &mut output)
+        }
+    };
+    let mut printed = String::from_utf8(output).map_err(|err| err.to_string())?;
+    if let Err(err) = result {
+        printed.push_str(&format!("error: {}\n", err));
+    }
+    Ok(printed)
+}
+
+fn programs(dir: &Path) -> Vec<PathBuf> {
+    let mut files: Vec<PathBuf> = fs::read_dir(dir)
+        .unwrap_or_else(|err| panic!("cannot read {}: {}", dir.display(), err))
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "cl"))
+        .collect();
+    files.sort();
+    files
+}
+
+#[test]
+fn test_conformance() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/conformance");
+    let programs = programs(&dir);
+    assert!(!programs.is_empty(), "no programs found in {}", dir.display());
+
+    let mut failures = Vec::new();
+    for path in &programs {
+        let source = fs::read_to_string(path).unwrap();
+        let input = fs::read_to_string(path.with_extension("in")).unwrap_or_default();
+        let expected = fs::read_to_string(path.with_extension("out"))
+            .unwrap_or_else(|err| panic!("cannot read the output expected of {}: {}", path.display(), err));
+        for engine in [Engine::Interpreter, Engine::Vm] {
+            match run(&source, &input, engine) {
+                Ok(actual) if actual == expected => {}
+                Ok(actual) => {
+                    failures.push(format!("{} ({:?}): {}", path.display(), engine, first_difference(&expected, &actual)))
+                }
+                Err(err) => failures.push(format!("{} ({:?}) was not run:\n{}", path.display(), engine, err)),
+            }
+        }
+    }
+    assert!(failures.is_empty(), "{} of {} programs failed:\n\n{}", failures.len(), programs.len() * 2, failures.join("\n\n"));
+}
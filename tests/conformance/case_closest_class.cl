@@ -0,0 +1,35 @@
+-- COOL manual, section 7.9 (case): the branch taken is the one with the
+-- least type the dynamic class of the value conforms to, whatever order
+-- the branches are written in and whatever the static type of the value.
+-- A case on void is a runtime error.
+class A { };
+class B inherits A { };
+class C inherits B { };
+class D inherits A { };
+
+class Main inherits IO {
+    which(x : Object) : String {
+        case x of
+            o : Object => "Object";
+            a : A => "A";
+            b : B => "B";
+            i : Int => "Int";
+            s : String => "String";
+        esac
+    };
+
+    main() : Object { {
+        out_string(which(new A)).out_string(" ").out_string(which(new B)).out_string(" ");
+        out_string(which(new C)).out_string(" ").out_string(which(new D)).out_string("\n");
+        out_string(which(7)).out_string(" ").out_string(which("seven")).out_string(" ");
+        out_string(which(true)).out_string(" ").out_string(which(self)).out_string("\n");
+
+        let a : A <- new C in
+            case a of
+                a : A => out_string("A\n");
+                c : C => out_string("C\n");
+            esac;
+
+        let void : A in which(void);
+    } };
+};
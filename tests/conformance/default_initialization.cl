@@ -0,0 +1,47 @@
+-- COOL manual, section 5 (attributes) and section 7.10 (let): a variable
+-- without an initializer holds the default value of its type, 0 for Int,
+-- "" for String, false for Bool and void for any other class. Section
+-- 13.3 (new): every attribute of a new object holds its default before
+-- any initializer runs; the inherited initializers run first, and then
+-- those of the class, in the order they are written.
+class Base {
+    first : Int <- 1;
+    early : Int <- later;
+    later : Int <- 2;
+    first() : Int { first };
+    early() : Int { early };
+    later() : Int { later };
+};
+
+class Derived inherits Base {
+    sum : Int <- first + later;
+    me : SELF_TYPE;
+    sum() : Int { sum };
+    me() : SELF_TYPE { me };
+};
+
+class Main inherits IO {
+    i : Int;
+    s : String;
+    b : Bool;
+    o : Object;
+    io : IO;
+
+    void(x : Object) : String { if isvoid x then "void" else "object" fi };
+    bool(x : Bool) : String { if x then "true" else "false" fi };
+
+    main() : Object { {
+        out_int(i).out_string(" [").out_string(s).out_string("] ").out_string(bool(b));
+        out_string(" ").out_string(void(o)).out_string(" ").out_string(void(io)).out_string("\n");
+
+        let i : Int, s : String, b : Bool, o : Object in {
+            out_int(i).out_string(" [").out_string(s).out_string("] ").out_string(bool(b));
+            out_string(" ").out_string(void(o)).out_string("\n");
+        };
+
+        let d : Derived <- new Derived in {
+            out_int(d.first()).out_string(" ").out_int(d.early()).out_string(" ").out_int(d.later());
+            out_string(" ").out_int(d.sum()).out_string(" ").out_string(void(d.me())).out_string("\n");
+        };
+    } };
+};
@@ -0,0 +1,33 @@
+-- COOL manual, section 7.4 (dispatch): in e0.f(e1, ..., en) the arguments
+-- are evaluated from left to right, and then e0; a static dispatch
+-- e0@T.f(...) evaluates them in the same order. The method called is the
+-- one of the dynamic class of e0, or of T for a static dispatch. Each
+-- argument sees what the ones before it did. Section 7.12 (arithmetic and
+-- comparison): the left operand is evaluated before the right one.
+class Log inherits IO {
+    say(s : String, n : Int) : Int { { out_string(s); n; } };
+    me(s : String) : SELF_TYPE { { out_string(s); self; } };
+    pick(a : Int, b : Int, c : Int) : Int { a * 100 + b * 10 + c };
+    name() : String { "Log" };
+};
+
+class Loud inherits Log {
+    name() : String { "Loud" };
+};
+
+class Main inherits IO {
+    log : Log <- new Loud;
+
+    show(a : Int, b : Int, c : Int) : Object { out_int(a).out_int(b).out_int(c).out_string("\n") };
+
+    main() : Object { {
+        out_int(log.me("e0 ").pick(log.say("e1 ", 1), log.say("e2 ", 2), log.say("e3 ", 3))).out_string("\n");
+        out_int(log.me("e0 ")@Log.pick(log.say("e1 ", 4), log.say("e2 ", 5), log.say("e3 ", 6))).out_string("\n");
+        out_string(log.name()).out_string(" ").out_string(log@Log.name()).out_string("\n");
+
+        let x : Int <- 1 in show(x, x <- 2, x + 1);
+
+        out_int(log.say("left ", 5) - log.say("right ", 3)).out_string("\n");
+        if log.say("left ", 1) < log.say("right ", 2) then out_string("less\n") else out_string("not less\n") fi;
+    } };
+};
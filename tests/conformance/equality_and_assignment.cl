@@ -0,0 +1,35 @@
+-- COOL manual, section 7.3 (assignment): the value of an assignment is the
+-- value assigned. Section 7.12 (comparison): = compares objects of the
+-- basic classes Int, String and Bool by value and any others by identity,
+-- and void equals only void. Section 8.1 (Object): copy makes a shallow
+-- copy, a new object whose attributes hold the same values.
+class Cell {
+    item : Object;
+    set(x : Object) : SELF_TYPE { { item <- x; self; } };
+    item() : Object { item };
+};
+
+class Main inherits IO {
+    yes(b : Bool) : Object { out_string(if b then "y" else "n" fi) };
+
+    main() : Object {
+        let x : Int, y : Int, c : Cell <- new Cell, none : Cell in {
+            x <- y <- 3;
+            out_int(x); out_int(y); out_int(x <- x + 1); out_int(x); out_string("\n");
+
+            yes(2 + 2 = 4); yes(2 = 3); yes("ab".concat("c") = "abc"); yes("ab" = "abc");
+            yes(true = (not false)); yes(false = true);
+            out_string("\n");
+            yes(c = c); yes(c = new Cell); yes(c = c.copy()); yes(none = none); yes(c = none);
+            out_string("\n");
+
+            let d : Cell <- c.set(new Cell).copy() in {
+                yes(d.item() = c.item());
+                d.set(1);
+                yes(d.item() = c.item());
+                case d.item() of i : Int => yes(i = 1); esac;
+                out_string("\n");
+            };
+        }
+    };
+};
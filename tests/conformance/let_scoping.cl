@@ -0,0 +1,15 @@
+-- COOL manual, section 7.10 (let): the bindings are made from left to
+-- right, each one in scope in the initializers after it and in the body,
+-- and a binding hides an attribute or earlier binding of the same name
+-- until the let ends. The value of a let is the value of its body.
+class Main inherits IO {
+    x : Int <- 1;
+
+    main() : Object { {
+        let y : Int <- x + 1, x : Int <- y * 10, y : Int <- x + y in
+            out_int(x).out_string(" ").out_int(y).out_string("\n");
+        out_int(x).out_string("\n");
+        out_int(let x : Int <- 5 in x * x).out_string("\n");
+        out_int(let x : Int in x <- x + 7).out_string(" ").out_int(x).out_string("\n");
+    } };
+};
@@ -0,0 +1,25 @@
+-- COOL manual, section 4.1 and 7.8 (new): new SELF_TYPE makes an object of
+-- the dynamic class of self, so a method inherited from a parent makes an
+-- object of the subclass it is called on. A method declared to return
+-- SELF_TYPE returns an object of the class of its receiver. Section 8.1:
+-- type_name is the name of the dynamic class.
+class Animal {
+    spawn() : SELF_TYPE { new SELF_TYPE };
+    me() : SELF_TYPE { self };
+};
+
+class Cat inherits Animal {
+    purr() : String { "purr" };
+};
+
+class Main inherits IO {
+    main() : Object { {
+        out_string((new Animal).spawn().type_name()).out_string(" ");
+        out_string((new Cat).spawn().type_name()).out_string(" ");
+        out_string((new Cat).me().spawn().purr()).out_string(" ");
+        let a : Animal <- new Cat in out_string(a.spawn().type_name()).out_string(" ");
+        out_string(type_name()).out_string(" ").out_string(copy().type_name()).out_string("\n");
+        out_string(1.type_name()).out_string(" ").out_string("s".type_name()).out_string(" ");
+        out_string(true.type_name()).out_string(" ").out_string((new Object).type_name()).out_string("\n");
+    } };
+};
@@ -0,0 +1,16 @@
+-- COOL manual, section 8.3 (String): length, concat, and substr(i, l), l
+-- characters from the zero-based position i, where a range outside the
+-- string is a runtime error. Section 8.2 (IO): in_string reads a line up
+-- to, not including, its newline; in_int reads an integer from a line.
+class Main inherits IO {
+    main() : Object {
+        let line : String <- in_string(), n : Int <- in_int(), empty : String <- in_string() in {
+            out_string("[").out_string(line).out_string("] ").out_int(line.length()).out_string("\n");
+            out_int(n + 1).out_string(" [").out_string(empty).out_string("]\n");
+            out_string(line.substr(0, 5)).out_string("|").out_string(line.substr(6, 5)).out_string("|");
+            out_string(line.substr(11, 0)).out_string("|").out_string(line.concat("!")).out_string("\n");
+            out_string("".concat("")).out_int("".length()).out_string("\n");
+            out_string(line.substr(7, 5));
+        }
+    };
+};
@@ -0,0 +1,13 @@
+-- COOL manual, section 7.6 (loops): the condition is evaluated before each
+-- iteration, so a loop whose condition is false at once never runs its
+-- body, and the value of a loop is void.
+class Main inherits IO {
+    main() : Object {
+        let i : Int <- 0, value : Object <- while i < 3 loop { out_int(i); i <- i + 1; } pool in {
+            out_string("\n");
+            if isvoid value then out_string("void\n") else out_string("not void\n") fi;
+            if isvoid (while not (i = 3) loop i <- i + 1 pool) then out_string("void\n") else out_string("not void\n") fi;
+            (while i < 3 loop i <- i + 1 pool).type_name();
+        }
+    };
+};
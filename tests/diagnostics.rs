@@ -0,0 +1,90 @@
+//! Snapshot tests for rendered diagnostics.
+//!
+//! Each program under `tests/diagnostics` triggers one kind of diagnostic.
+//! The `cool-rs` binary is run on it, and its stderr must match the snapshot
+//! `<name>.stderr` byte for byte, so changes to wording or layout show up in
+//! review. A program that needs language extensions names them in a first
+//! line such as `-- ext: natives`. Programs for lints (`W` codes) must still
+//! compile; the rest must be rejected.
+//!
+//! Run `cargo test --test diagnostics -- --bless` to rewrite the snapshots
+//! after an intended change, then review the diff.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitCode};
+
+mod common;
+use common::{first_difference, fixture_extensions};
+
+/// Rendered stderr of `cool-rs --file <program>`, run from the crate root so
+/// paths in messages are relative, with the extensions the program asks for.
+fn render(root: &Path, program: &Path) -> String {
+    let source = fs::read_to_string(program).unwrap();
+    let extensions = fixture_extensions(&source).into_iter().flat_map(|name| ["--ext", name]);
+    let output = Command::new(env!("CARGO_BIN_EXE_cool-rs"))
+        .current_dir(root)
+        .args(extensions)
+        .arg("--file")
+        .arg(program.strip_prefix(root).unwrap())
+        .env_remove("RUST_BACKTRACE")
+        .env_remove("RUST_LIB_BACKTRACE")
+        .output()
+        .expect("failed to run cool-rs");
+    let lint = program.file_name().unwrap().to_string_lossy().starts_with('W');
+    if lint {
+        assert!(output.status.success(), "{} was rejected", program.display());
+    } else {
+        assert!(!output.status.success(), "{} compiled cleanly", program.display());
+    }
+    String::from_utf8(output.stderr).unwrap()
+}
+
+fn programs(dir: &Path) -> Vec<PathBuf> {
+    let mut files: Vec<PathBuf> = fs::read_dir(dir)
+        .unwrap_or_else(|err| panic!("cannot read {}: {}", dir.display(), err))
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "cl"))
+        .collect();
+    files.sort();
+    files
+}
+
+fn main() -> ExitCode {
+    let bless = std::env::args().any(|arg| arg == "--bless");
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let dir = root.join("tests").join("diagnostics");
+
+    let mut failures = Vec::new();
+    let programs = programs(&dir);
+    for program in &programs {
+        let actual = render(root, program);
+        let snapshot = program.with_extension("stderr");
+        if bless {
+            fs::write(&snapshot, actual).unwrap();
+            continue;
+        }
+
+        let name = program.file_name().unwrap().to_string_lossy();
+        match fs::read_to_string(&snapshot) {
+            Ok(expected) if expected == actual => {}
+            Ok(expected) => failures.push(format!("{}: {}", name, first_difference(&expected, &actual))),
+            Err(_) => failures.push(format!("{}: no snapshot", name)),
+        }
+    }
+
+    if bless {
+        println!("diagnostics: snapshots rewritten under {}", dir.display());
+        return ExitCode::SUCCESS;
+    }
+    for failure in &failures {
+        eprintln!("FAILED {}", failure);
+    }
+    println!("diagnostics: {} checked, {} failed", programs.len(), failures.len());
+    if failures.is_empty() {
+        ExitCode::SUCCESS
+    } else {
+        eprintln!("run `cargo test --test diagnostics -- --bless` to accept the new output");
+        ExitCode::FAILURE
+    }
+}
@@ -0,0 +1,3 @@
+class A { };
+class A { };
+class Main { main() : Int { 0 }; };
@@ -0,0 +1,3 @@
+class A inherits B { };
+class B inherits A { };
+class Main { main() : Int { 0 }; };
@@ -0,0 +1,2 @@
+class A inherits Missing { };
+class Main { main() : Int { 0 }; };
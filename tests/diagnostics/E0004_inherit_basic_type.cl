@@ -0,0 +1,3 @@
+class A inherits Int { };
+class B inherits SELF_TYPE { };
+class Main { main() : Int { 0 }; };
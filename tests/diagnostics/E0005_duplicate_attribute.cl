@@ -0,0 +1,5 @@
+class Main {
+    x : Int;
+    x : String;
+    main() : Int { 0 };
+};
@@ -0,0 +1,4 @@
+class Main {
+    main() : Int { 0 };
+    main() : Int { 1 };
+};
@@ -0,0 +1,7 @@
+class A {
+    f(a : Int) : Int { a };
+};
+class B inherits A {
+    f(a : String) : Int { 0 };
+};
+class Main { main() : Int { 0 }; };
@@ -0,0 +1,8 @@
+class Main {
+    main() : Object {
+        new Missing
+    };
+    f() : Object {
+        let x : Absent <- 1 in x
+    };
+};
@@ -0,0 +1,5 @@
+class Main {
+    main() : Int {
+        1 + "one"
+    };
+};
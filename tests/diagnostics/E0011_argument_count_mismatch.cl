@@ -0,0 +1,6 @@
+class Main {
+    f(a : Int) : Int { a };
+    main() : Int {
+        f(1, 2)
+    };
+};
@@ -0,0 +1,9 @@
+-- ext: natives
+class Sys {
+    clock() : Int native;
+    random(bound : Int) : Bool native;
+};
+
+class Main {
+    main() : Int { (new Sys).clock() };
+};
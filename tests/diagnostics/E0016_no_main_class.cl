@@ -0,0 +1,3 @@
+class Program inherits IO {
+    main() : Object { out_string("Hello\n") };
+};
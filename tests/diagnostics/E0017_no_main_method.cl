@@ -0,0 +1,3 @@
+class Main inherits IO {
+    main(greeting : String) : Object { out_string(greeting) };
+};
@@ -0,0 +1,12 @@
+class A {
+    x : Int;
+};
+
+class B inherits A {
+    y : Int;
+};
+
+class Main inherits B {
+    x : String;
+    main() : Int { 0 };
+};
@@ -0,0 +1,12 @@
+class A { };
+class B inherits A { };
+
+class Main {
+    main() : Int {
+        case new A of
+            b : B => 0;
+            a : A => 1;
+            c : B => 2;
+        esac
+    };
+};
@@ -0,0 +1,5 @@
+class Main {
+    main() : Object {
+        self <- new Main
+    };
+};
@@ -0,0 +1,5 @@
+class Main {
+    main() : Int {
+        let self : Int <- 1 in 2
+    };
+};
@@ -0,0 +1,7 @@
+class Main {
+    main() : Int {
+        case 0 of
+            self : Int => 1;
+        esac
+    };
+};
@@ -0,0 +1,4 @@
+class Main {
+    f(self : Int) : Int { 0 };
+    main() : Int { 0 };
+};
@@ -0,0 +1,4 @@
+class Main {
+    self : Int;
+    main() : Int { 0 };
+};
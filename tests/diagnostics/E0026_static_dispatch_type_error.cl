@@ -0,0 +1,9 @@
+class A {
+    name() : String { "A" };
+};
+
+class Main inherits IO {
+    main() : Object {
+        out_string((new Main)@A.name())
+    };
+};
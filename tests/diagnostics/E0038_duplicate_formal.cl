@@ -0,0 +1,4 @@
+class Main {
+    add(x : Int, y : Int, x : Int) : Int { x + y };
+    main() : Int { add(1, 2, 3) };
+};
@@ -0,0 +1,2 @@
+class SELF_TYPE { };
+class Main { main() : Int { 0 }; };
@@ -0,0 +1,4 @@
+class Main {
+    f(x : SELF_TYPE) : Int { 0 };
+    main() : Int { f(self) };
+};
@@ -0,0 +1,5 @@
+class Main {
+    main() : Int {
+        let unused : Int <- 1 in 0
+    };
+};
@@ -0,0 +1,4 @@
+class Main {
+    count : Int;
+    main() : Int { 0 };
+};
@@ -0,0 +1,8 @@
+class Main {
+    main() : Int {
+        case 1 of
+            n : Int => n;
+            s : String => 0;
+        esac
+    };
+};
@@ -0,0 +1,6 @@
+class Main inherits IO {
+    main() : Object { {
+        abort();
+        out_string("never printed\n");
+    } };
+};
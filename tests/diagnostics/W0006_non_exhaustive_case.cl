@@ -0,0 +1,9 @@
+class A { };
+class B inherits A { };
+class Main {
+    main() : Int {
+        case new A of
+            b : B => 1;
+        esac
+    };
+};
@@ -0,0 +1,5 @@
+class Main {
+    total : Int <- count + 1;
+    count : Int <- 2;
+    main() : Int { total };
+};
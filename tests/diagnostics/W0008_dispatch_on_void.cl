@@ -0,0 +1,5 @@
+class Main inherits IO {
+    main() : Object {
+        let io : IO in io.out_string("hello\n")
+    };
+};
@@ -0,0 +1,7 @@
+class Main {
+    a : Int <- ;
+    main() : Int { { 1; 2 +; 3; } };
+    b : Bool
+};
+
+class inherits IO { };
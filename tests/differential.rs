@@ -0,0 +1,67 @@
+//! Differential testing (`cool-difftest`): with `COOL_REFERENCE` set to a
+//! reference implementation's command, run as `cool-difftest --reference`
+//! runs it, every program of the corpus must run the same with it as with
+//! `cool-rs --run`. Without it that test is skipped; the others check
+//! `cool-difftest` itself, against references made up for them.
+
+use std::fs;
+use std::process::{Command, Output};
+
+fn difftest(reference: &str, paths: &[&std::path::Path]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_cool-difftest"))
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .args(["--reference", reference])
+        .args(paths)
+        .output()
+        .expect("failed to run cool-difftest")
+}
+
+#[test]
+fn test_against_reference() {
+    let Ok(reference) = std::env::var("COOL_REFERENCE") else {
+        eprintln!("COOL_REFERENCE is not set: not comparing with a reference implementation");
+        return;
+    };
+    let output = difftest(&reference, &[]);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(output.status.success(), "the corpus runs differently with {}:\n{}", reference, stdout);
+}
+
+#[test]
+fn test_agrees_with_itself() {
+    let reference = format!("{} --run --file", env!("CARGO_BIN_EXE_cool-rs"));
+    let output = difftest(&reference, &[]);
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(output.status.success(), "{}", stdout);
+    assert!(stdout.ends_with(" programs, 0 diverged\n") && !stdout.starts_with("0 "), "{}", stdout);
+}
+
+#[cfg(unix)]
+#[test]
+fn test_reports_divergences() {
+    let dir = std::env::temp_dir().join(format!("cool-rs-difftest-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("hello.cl"), "class Main inherits IO { main() : Object { out_string(\"hello\\n\") }; };\n").unwrap();
+    fs::write(dir.join("echo.cl"), "class Main inherits IO { main() : Object { out_string(in_string()) }; };\n").unwrap();
+    fs::write(dir.join("echo.in"), "hello\n").unwrap();
+    fs::write(dir.join("abort.cl"), "class Main { main() : Object { abort() }; };\n").unwrap();
+    // Prints "hello" and succeeds, whatever the program
+    let reference = dir.join("reference.sh");
+    fs::write(&reference, "echo hello\n").unwrap();
+
+    let output = difftest(&format!("sh {}", reference.display()), &[&dir]);
+    assert_eq!(output.status.code(), Some(1));
+    let abort = dir.join("abort.cl");
+    let echo = dir.join("echo.cl");
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        format!(
+            "DIFF {}: stdout line 1: ours <end of output>, reference \"hello\\n\"; exit status: ours 1, reference 0\n\
+             DIFF {}: stdout line 1: ours \"hello\", reference \"hello\\n\"\n\
+             3 programs, 2 diverged\n",
+            abort.display(),
+            echo.display()
+        )
+    );
+    fs::remove_dir_all(dir).ok();
+}
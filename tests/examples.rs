@@ -0,0 +1,53 @@
+//! Every program in `examples/` must lex, parse and type-check cleanly, and
+//! pass the consistency checks between phases.
+
+use std::fs;
+use std::path::Path;
+
+use cool_rs::parsing::scanner::Scanner;
+use cool_rs::semantic::collector::ErrorCollector;
+use cool_rs::semantic::context::AnalysisContext;
+use cool_rs::{check_program, parse_program, prelude, verify};
+
+/// Run one program through the front end, describing the first stage that fails.
+fn check_example(source: &str) -> Result<(), String> {
+    Scanner::new(source)
+        .scan_tokens()
+        .map_err(|err| format!("lexing failed: {}", err))?;
+    let classes = parse_program(source).map_err(|err| format!("{:#}", err))?;
+    verify::assert_consistent("parsing", &verify::verify_parse(source, &classes));
+
+    let ctx = AnalysisContext::new(prelude(), &classes);
+    let mut ec = ErrorCollector::default();
+    check_program(&ctx, &mut ec);
+    match ec.errors.as_slice() {
+        [] => {
+            verify::assert_consistent("semantic analysis", &verify::verify_semant(&ctx));
+            Ok(())
+        }
+        errors => Err(errors.iter().map(|e| e.to_string()).collect::<Vec<_>>().join("\n")),
+    }
+}
+
+#[test]
+fn test_examples_compile_cleanly() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("examples");
+    let mut examples: Vec<_> = fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "cl"))
+        .collect();
+    examples.sort();
+    assert!(!examples.is_empty(), "no examples found in {}", dir.display());
+
+    let failures: Vec<String> = examples
+        .iter()
+        .filter_map(|path| {
+            let source = fs::read_to_string(path).unwrap();
+            check_example(&source)
+                .err()
+                .map(|err| format!("{}:\n{}", path.display(), err))
+        })
+        .collect();
+    assert!(failures.is_empty(), "examples failed:\n\n{}", failures.join("\n\n"));
+}
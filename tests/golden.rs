@@ -0,0 +1,129 @@
+//! Golden-file tests for the lexer, parser and semantic stages.
+//!
+//! Every program under `tests/valid` and `tests/invalid` is run through each
+//! stage, and the output is compared with the reference dump stored under
+//! `tests/golden/<dir>/<name>.<stage>`:
+//!
+//! - `lex`: the token stream in the reference `lexer` format
+//! - `parse`: the AST in the reference `parse` format, or the parse error
+//! - `semant`: the annotated AST in the reference `semant` format, or the
+//!   semantic diagnostics, one per line
+//!
+//! Stages after a failing one produce no output and must have no dump.
+//! Run `cargo test --test golden -- --bless` to rewrite the dumps after an
+//! intended change, then review the diff.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use cool_rs::ast::printer::{coolc_parse, coolc_types};
+use cool_rs::ext::Extensions;
+use cool_rs::parsing::dump::coolc_tokens;
+use cool_rs::semantic::collector::ErrorCollector;
+use cool_rs::semantic::context::AnalysisContext;
+use cool_rs::{check_program, parse_program, prelude};
+
+mod common;
+use common::first_difference;
+
+const CORPUS: [&str; 2] = ["valid", "invalid"];
+const STAGES: [&str; 3] = ["lex", "parse", "semant"];
+
+/// Output of each stage that ran on `source`, read from the file
+/// `filename`, in pipeline order.
+fn run_stages(source: &str, filename: &str) -> Vec<String> {
+    let mut outputs = vec![coolc_tokens(source, &Extensions::default()).dump];
+
+    let classes = match parse_program(source) {
+        Ok(classes) => classes,
+        Err(err) => {
+            outputs.push(format!("{:#}\n", err));
+            return outputs;
+        }
+    };
+    outputs.push(coolc_parse(&classes, &[filename]));
+
+    let ctx = AnalysisContext::new(prelude(), &classes);
+    let mut ec = ErrorCollector::default();
+    let typed = check_program(&ctx, &mut ec);
+    outputs.push(match typed.filter(|_| ec.errors.is_empty()) {
+        Some(typed) => coolc_types(&typed, &[filename]),
+        None => ec.errors.iter().map(|e| format!("{}\n", e)).collect(),
+    });
+    outputs
+}
+
+/// Programs in one corpus directory, sorted for a stable report.
+fn programs(dir: &Path) -> Vec<PathBuf> {
+    let mut files: Vec<PathBuf> = fs::read_dir(dir)
+        .unwrap_or_else(|err| panic!("cannot read {}: {}", dir.display(), err))
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "cl"))
+        .collect();
+    files.sort();
+    files
+}
+
+fn main() -> ExitCode {
+    let bless = std::env::args().any(|arg| arg == "--bless");
+    let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests");
+
+    let mut checked = 0;
+    let mut failures = Vec::new();
+    for dir in CORPUS {
+        let golden_dir = root.join("golden").join(dir);
+        if bless {
+            fs::create_dir_all(&golden_dir).unwrap();
+        }
+
+        for program in programs(&root.join(dir)) {
+            let source = fs::read_to_string(&program).unwrap();
+            let stem = program.file_stem().unwrap().to_string_lossy();
+            let outputs = run_stages(&source, &format!("{}.cl", stem));
+
+            for (i, stage) in STAGES.iter().enumerate() {
+                let golden = golden_dir.join(format!("{}.{}", stem, stage));
+                let actual = outputs.get(i);
+                if bless {
+                    match actual {
+                        Some(output) => fs::write(&golden, output).unwrap(),
+                        None => {
+                            fs::remove_file(&golden).ok();
+                        }
+                    }
+                    continue;
+                }
+
+                checked += actual.is_some() as usize;
+                let name = format!("{}/{}.cl [{}]", dir, stem, stage);
+                match (fs::read_to_string(&golden).ok(), actual) {
+                    (Some(expected), Some(output)) if expected == *output => {}
+                    (Some(expected), Some(output)) => {
+                        failures.push(format!("{}: {}", name, first_difference(&expected, output)))
+                    }
+                    (None, Some(_)) => failures.push(format!("{}: no golden file", name)),
+                    (Some(_), None) => {
+                        failures.push(format!("{}: stage no longer runs, but a golden file exists", name))
+                    }
+                    (None, None) => {}
+                }
+            }
+        }
+    }
+
+    if bless {
+        println!("golden: dumps rewritten under {}", root.join("golden").display());
+        return ExitCode::SUCCESS;
+    }
+    for failure in &failures {
+        eprintln!("FAILED {}", failure);
+    }
+    println!("golden: {} checked, {} failed", checked, failures.len());
+    if failures.is_empty() {
+        ExitCode::SUCCESS
+    } else {
+        eprintln!("run `cargo test --test golden -- --bless` to accept the new output");
+        ExitCode::FAILURE
+    }
+}
@@ -0,0 +1,8 @@
+class Main {
+   main() : Object {
+      case 1 of
+         i : Int => i;
+         j : Int => j + 1;
+      esac
+   };
+};
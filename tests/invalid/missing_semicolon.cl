@@ -0,0 +1,8 @@
+class A {
+   x : Int <- 1
+   y : Int <- 2;
+};
+
+class Main {
+   main() : Int { 0 };
+};
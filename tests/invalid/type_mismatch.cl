@@ -0,0 +1,6 @@
+class Main inherits IO {
+   count : Int <- "three";
+   main() : Object {
+      if count then out_int(count) else self fi
+   };
+};
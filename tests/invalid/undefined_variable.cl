@@ -1,6 +1,8 @@
 class Main {
    main(): Int {
+     {
        y <- 3;  -- `y` was never declared
-       y
+       y;
+     }
    };
 };
@@ -0,0 +1,3 @@
+class Main inherits IO {
+   main() : Object { out_string("never closed) };
+};
@@ -1,7 +1,7 @@
-class Main {
+class Main inherits IO {
    main(): Object {
        let x : Int <- 5 in {
-           out_string(x)  -- trying to pass an Int to out_string (expects String)
+           out_string(x);  -- trying to pass an Int to out_string (expects String)
        }
    };
 };
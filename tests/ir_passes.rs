@@ -0,0 +1,154 @@
+//! FileCheck-style tests of the passes and analyses over the IR: each
+//! `.ir` file under `tests/ir` holds handwritten functions in the
+//! `--emit ir` listing format, read by `ir::parse`, and comment lines that
+//! say what to run on them and what its output must hold:
+//!
+//! - `; RUN: <pass>` names the pass: `print` lists the program as read,
+//!   `cfg-dot` draws its control-flow graphs, and `liveness` lists the
+//!   variables live into and out of every block.
+//! - `; CHECK: <text>` must be found on a line after the previous match.
+//! - `; CHECK-NEXT: <text>` must be found on the line right after it.
+//! - `; CHECK-NOT: <text>` must not be found between the matches around
+//!   it, or after the last one.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use cool_rs::ir::cfg::{self, Cfg};
+use cool_rs::ir::liveness::Liveness;
+use cool_rs::ir::{self, Program};
+
+#[derive(Debug)]
+enum Check<'a> {
+    Here(&'a str),
+    Next(&'a str),
+    Not(&'a str),
+}
+
+/// What `pass` makes of `program`.
+fn run(pass: &str, program: &Program) -> Result<String, String> {
+    match pass {
+        "print" => Ok(program.to_string()),
+        "cfg-dot" => Ok(cfg::dot(program)),
+        "liveness" => {
+            let mut out = String::new();
+            for function in &program.functions {
+                let liveness = Liveness::new(function, &Cfg::new(function));
+                out.push_str(&format!("{}.{}:\n", function.class, function.name));
+                let set = |vars: &std::collections::BTreeSet<ir::Var>| {
+                    vars.iter().map(ToString::to_string).collect::<Vec<_>>().join(", ")
+                };
+                for (id, (live_in, live_out)) in liveness.live_in.iter().zip(&liveness.live_out).enumerate() {
+                    out.push_str(&format!("b{}: in {{{}}} out {{{}}}\n", id, set(live_in), set(live_out)));
+                }
+            }
+            Ok(out)
+        }
+        other => Err(format!("unknown pass `{}`", other)),
+    }
+}
+
+/// Whether `output` holds what `checks` say, in order.
+fn check(output: &str, checks: &[Check]) -> Result<(), String> {
+    let lines: Vec<&str> = output.lines().collect();
+    // The first line not yet matched, and the patterns that must not be
+    // found before the next match
+    let mut next = 0;
+    let mut forbidden: Vec<&str> = Vec::new();
+    let found = |from: usize, to: usize, forbidden: &mut Vec<&str>| -> Result<(), String> {
+        for pattern in forbidden.drain(..) {
+            if let Some(line) = lines[from..to].iter().find(|line| line.contains(pattern)) {
+                return Err(format!("CHECK-NOT: {} found in: {}", pattern, line));
+            }
+        }
+        Ok(())
+    };
+    for check in checks {
+        match *check {
+            Check::Here(pattern) => {
+                let Some(offset) = lines[next..].iter().position(|line| line.contains(pattern)) else {
+                    return Err(format!("CHECK: {} not found after line {}", pattern, next));
+                };
+                found(next, next + offset, &mut forbidden)?;
+                next += offset + 1;
+            }
+            Check::Next(pattern) => {
+                match lines.get(next) {
+                    Some(line) if line.contains(pattern) => {}
+                    line => {
+                        return Err(format!("CHECK-NEXT: {} not found on line {}: {}", pattern, next + 1, line.unwrap_or(&"")))
+                    }
+                }
+                found(next, next, &mut forbidden)?;
+                next += 1;
+            }
+            Check::Not(pattern) => forbidden.push(pattern),
+        }
+    }
+    found(next, lines.len(), &mut forbidden)
+}
+
+/// The pass a test runs and its checks.
+fn directives(text: &str) -> Result<(&str, Vec<Check<'_>>), String> {
+    let mut pass = None;
+    let mut checks = Vec::new();
+    for line in text.lines() {
+        let Some(comment) = line.trim().strip_prefix(';') else { continue };
+        let comment = comment.trim_start();
+        if let Some(name) = comment.strip_prefix("RUN:") {
+            pass = Some(name.trim());
+        } else if let Some(pattern) = comment.strip_prefix("CHECK:") {
+            checks.push(Check::Here(pattern.trim()));
+        } else if let Some(pattern) = comment.strip_prefix("CHECK-NEXT:") {
+            checks.push(Check::Next(pattern.trim()));
+        } else if let Some(pattern) = comment.strip_prefix("CHECK-NOT:") {
+            checks.push(Check::Not(pattern.trim()));
+        }
+    }
+    let pass = pass.ok_or("no `; RUN:` line")?;
+    if checks.is_empty() {
+        return Err("no `; CHECK` lines".to_string());
+    }
+    Ok((pass, checks))
+}
+
+fn tests(dir: &Path) -> Vec<PathBuf> {
+    let mut files: Vec<PathBuf> = fs::read_dir(dir)
+        .unwrap_or_else(|err| panic!("cannot read {}: {}", dir.display(), err))
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "ir"))
+        .collect();
+    files.sort();
+    files
+}
+
+#[test]
+fn test_ir_passes() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/ir");
+    let tests = tests(&dir);
+    assert!(!tests.is_empty(), "no tests found in {}", dir.display());
+
+    let mut failures = Vec::new();
+    for path in &tests {
+        let text = fs::read_to_string(path).unwrap();
+        let result = directives(&text).and_then(|(pass, checks)| {
+            let program = ir::parse::parse(&text).map_err(|err| format!("{:#}", err))?;
+            let output = run(pass, &program)?;
+            check(&output, &checks).map_err(|err| format!("{}\noutput:\n{}", err, output))
+        });
+        if let Err(err) = result {
+            failures.push(format!("{}: {}", path.display(), err));
+        }
+    }
+    assert!(failures.is_empty(), "{} of {} tests failed:\n\n{}", failures.len(), tests.len(), failures.join("\n\n"));
+}
+
+#[test]
+fn test_check() {
+    let output = "a\nb\nc\n";
+    assert!(check(output, &[Check::Here("a"), Check::Next("b"), Check::Not("x"), Check::Here("c")]).is_ok());
+    assert!(check(output, &[Check::Here("b"), Check::Here("a")]).is_err());
+    assert!(check(output, &[Check::Here("a"), Check::Next("c")]).is_err());
+    assert!(check(output, &[Check::Here("a"), Check::Not("b"), Check::Here("c")]).is_err());
+    assert!(check(output, &[Check::Here("b"), Check::Not("c")]).is_err());
+}
@@ -0,0 +1,96 @@
+//! Mutation testing of the front end.
+//!
+//! Every corpus program is mutated a few hundred times with one targeted
+//! token edit each (see `cool_rs::mutate`). Whatever the edit, the compiler
+//! must not panic, and its diagnostics must stay sensible: a bounded number
+//! of them, each pointing at a line that exists. Mutants that get through a
+//! phase must also pass its consistency checks.
+
+use std::fs;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::Path;
+
+use cool_rs::mutate::Mutator;
+use cool_rs::semantic::collector::ErrorCollector;
+use cool_rs::semantic::context::AnalysisContext;
+use cool_rs::{check_program, parse_program, prelude, verify};
+
+const CORPUS: [&str; 3] = ["tests/valid", "tests/ui", "examples"];
+const MUTANTS_PER_PROGRAM: usize = 300;
+
+/// Why a mutant's handling is unacceptable, if it is.
+fn problem(source: &str) -> Option<String> {
+    let outcome = panic::catch_unwind(AssertUnwindSafe(|| {
+        let classes = parse_program(source).ok()?;
+        verify::assert_consistent("parsing", &verify::verify_parse(source, &classes));
+        let ctx = AnalysisContext::new(prelude(), &classes);
+        let mut ec = ErrorCollector::default();
+        check_program(&ctx, &mut ec);
+        if !ec.has_errors() {
+            verify::assert_consistent("semantic analysis", &verify::verify_semant(&ctx));
+        }
+        Some(ec.errors)
+    }));
+    let errors = match outcome {
+        Err(payload) => {
+            let message = payload
+                .downcast_ref::<String>()
+                .cloned()
+                .or_else(|| payload.downcast_ref::<&str>().map(|s| s.to_string()))
+                .unwrap_or_default();
+            return Some(format!("panicked: {}", message));
+        }
+        Ok(None) => return None,
+        Ok(Some(errors)) => errors,
+    };
+
+    let lines = source.lines().count();
+    let tokens = source.split_whitespace().count();
+    if errors.len() > tokens {
+        return Some(format!("{} diagnostics for {} tokens", errors.len(), tokens));
+    }
+    errors.iter().find_map(|err| match err.line() {
+        Some(line) if line == 0 || line > lines => {
+            Some(format!("points at line {} of {}: {}", line, lines, err))
+        }
+        _ => None,
+    })
+}
+
+#[test]
+fn test_mutants_are_handled_gracefully() {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let mut programs = Vec::new();
+    for dir in CORPUS {
+        for entry in fs::read_dir(root.join(dir)).unwrap() {
+            let path = entry.unwrap().path();
+            if path.extension().is_some_and(|ext| ext == "cl") {
+                programs.push(path);
+            }
+        }
+    }
+    programs.sort();
+
+    // The panics are expected to be caught; keep their messages out of the output
+    let hook = panic::take_hook();
+    panic::set_hook(Box::new(|_| {}));
+    let mut failures = Vec::new();
+    for (seed, path) in programs.iter().enumerate() {
+        let source = fs::read_to_string(path).unwrap();
+        let mut mutator = Mutator::new(&source, seed as u64).unwrap();
+        for _ in 0..MUTANTS_PER_PROGRAM {
+            let (mutation, mutant) = mutator.mutant();
+            if let Some(problem) = problem(&mutant) {
+                failures.push(format!("{} ({:?}): {}\n{}", path.display(), mutation, problem, mutant));
+            }
+        }
+    }
+    panic::set_hook(hook);
+
+    assert!(
+        failures.is_empty(),
+        "{} mutants mishandled, first:\n{}",
+        failures.len(),
+        failures[0]
+    );
+}
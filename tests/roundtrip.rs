@@ -0,0 +1,240 @@
+//! Property tests for the source printer and the formatter: printing or
+//! formatting a random AST and parsing it back gives the same AST, and both
+//! are idempotent.
+
+use proptest::prelude::*;
+
+use cool_rs::ast::{
+    ArgDecl, CaseBranch, Class, ComparisonOperator, Expr, Feature, MathOperator, TypedExpr,
+    UnaryOperator, VarDecl,
+};
+use cool_rs::ext::Extensions;
+use cool_rs::formatter::format_source;
+use cool_rs::parse_program;
+use cool_rs::pretty::print_program;
+use cool_rs::symbol::Symbol;
+
+// Names are drawn from small pools that avoid keywords in any letter case.
+fn object_id() -> impl Strategy<Value = Symbol> {
+    prop::sample::select(vec!["a", "b", "x", "foo", "bar_1", "self"]).prop_map(Symbol::intern)
+}
+
+fn type_id() -> impl Strategy<Value = Symbol> {
+    prop::sample::select(vec!["A", "B", "Int", "String", "Foo_2", "SELF_TYPE"]).prop_map(Symbol::intern)
+}
+
+fn typed(expr: Expr) -> TypedExpr {
+    TypedExpr::new(expr, 0)
+}
+
+fn boxed(e: TypedExpr) -> Box<TypedExpr> {
+    Box::new(e)
+}
+
+fn expr() -> impl Strategy<Value = TypedExpr> {
+    let leaf = prop_oneof![
+        object_id().prop_map(Expr::Identifier),
+        any::<bool>().prop_map(Expr::Bool),
+        (0..=i32::MAX).prop_map(Expr::Int),
+        "[a-zA-Z0-9 \\n\\t\\x08\\x0c\"\\\\]{0,8}".prop_map(Expr::Str),
+        type_id().prop_map(Expr::New),
+    ]
+    .prop_map(typed);
+
+    leaf.prop_recursive(4, 48, 4, |inner| {
+        let binding = (object_id(), type_id(), prop::option::of(inner.clone()));
+        prop_oneof![
+            prop::collection::vec(inner.clone(), 1..4).prop_map(Expr::Block),
+            (inner.clone(), prop::collection::vec((object_id(), type_id(), inner.clone()), 1..3))
+                .prop_map(|(e, branches)| Expr::Case(
+                    boxed(e),
+                    branches
+                        .into_iter()
+                        .map(|(id, tid, expr)| CaseBranch::new(id, tid, expr))
+                        .collect(),
+                )),
+            inner.clone().prop_map(|e| Expr::Paren(boxed(e))),
+            (prop::collection::vec(binding, 1..3), inner.clone()).prop_map(|(bindings, body)| {
+                let bindings = bindings.into_iter().map(|(id, tid, init)| VarDecl::new(id, tid, init)).collect();
+                Expr::Let(bindings, boxed(body))
+            }),
+            (inner.clone(), comparison_op(), inner.clone())
+                .prop_map(|(l, op, r)| Expr::Comparison { lhs: boxed(l), op, rhs: boxed(r) }),
+            (inner.clone(), math_op(), inner.clone())
+                .prop_map(|(l, op, r)| Expr::Math { lhs: boxed(l), op, rhs: boxed(r) }),
+            (unary_op(), inner.clone()).prop_map(|(op, s)| Expr::UnaryOperation { op, s: boxed(s) }),
+            (object_id(), inner.clone()).prop_map(|(id, e)| Expr::Assignment(id, boxed(e))),
+            (inner.clone(), inner.clone(), inner.clone()).prop_map(|(test, then, orelse)| {
+                Expr::Conditional { test: boxed(test), then: boxed(then), orelse: boxed(orelse) }
+            }),
+            (inner.clone(), inner.clone())
+                .prop_map(|(test, exec)| Expr::While { test: boxed(test), exec: boxed(exec) }),
+            inner.clone().prop_map(|e| Expr::Isvoid(boxed(e))),
+            (
+                prop::option::of((inner.clone(), prop::option::of(type_id()))),
+                object_id(),
+                prop::collection::vec(inner, 0..3),
+            )
+                .prop_map(|(target, id, exprs)| {
+                    let (target, targettype) = match target {
+                        Some((t, tt)) => (Some(boxed(t)), tt),
+                        None => (None, None),
+                    };
+                    Expr::Dispatch { target, targettype, id, exprs }
+                }),
+        ]
+        .prop_map(typed)
+    })
+}
+
+fn comparison_op() -> impl Strategy<Value = ComparisonOperator> {
+    prop_oneof![
+        Just(ComparisonOperator::Lt),
+        Just(ComparisonOperator::Le),
+        Just(ComparisonOperator::Equal),
+    ]
+}
+
+fn math_op() -> impl Strategy<Value = MathOperator> {
+    prop_oneof![
+        Just(MathOperator::Add),
+        Just(MathOperator::Subtract),
+        Just(MathOperator::Mul),
+        Just(MathOperator::Div),
+    ]
+}
+
+fn unary_op() -> impl Strategy<Value = UnaryOperator> {
+    prop_oneof![Just(UnaryOperator::Neg), Just(UnaryOperator::Not)]
+}
+
+fn feature() -> impl Strategy<Value = Feature> {
+    prop_oneof![
+        (object_id(), type_id(), prop::option::of(expr()))
+            .prop_map(|(oid, tid, expr)| Feature::new_attribute(oid, tid, expr)),
+        (
+            object_id(),
+            prop::collection::vec((object_id(), type_id()), 0..3),
+            type_id(),
+            expr(),
+        )
+            .prop_map(|(name, args, ret, body)| {
+                let args = args.into_iter().map(|(id, tid)| ArgDecl::new(id, tid)).collect();
+                Feature::new_method(name, args, ret, body)
+            }),
+    ]
+}
+
+fn program() -> impl Strategy<Value = Vec<Class>> {
+    let class = (type_id(), prop::option::of(type_id()), prop::collection::vec(feature(), 0..4))
+        .prop_map(|(name, inherits, features)| Class::new(name, inherits, features));
+    prop::collection::vec(class, 1..3)
+}
+
+/// Drop what printing does not preserve: positions and explicit parentheses.
+fn normalize(e: &mut TypedExpr) {
+    e.line = 0;
+    e.span = Default::default();
+    if let Expr::Paren(inner) = &mut e.expr {
+        let mut inner = std::mem::replace(&mut **inner, typed(Expr::Bool(false)));
+        normalize(&mut inner);
+        *e = inner;
+        return;
+    }
+    match &mut e.expr {
+        Expr::Block(exprs) => exprs.iter_mut().for_each(normalize),
+        Expr::Case(scrutinee, branches) => {
+            normalize(scrutinee);
+            for branch in branches {
+                branch.location = Default::default();
+                normalize(&mut branch.expr);
+            }
+        }
+        Expr::Let(bindings, body) => {
+            for binding in bindings {
+                binding.location = Default::default();
+                binding.expr.iter_mut().for_each(normalize);
+            }
+            normalize(body);
+        }
+        Expr::Comparison { lhs, rhs, .. } | Expr::Math { lhs, rhs, .. } => {
+            normalize(lhs);
+            normalize(rhs);
+        }
+        Expr::UnaryOperation { s, .. } => normalize(s),
+        Expr::Assignment(_, rhs) => normalize(rhs),
+        Expr::Conditional { test, then, orelse } => {
+            normalize(test);
+            normalize(then);
+            normalize(orelse);
+        }
+        Expr::While { test, exec } => {
+            normalize(test);
+            normalize(exec);
+        }
+        Expr::Isvoid(inner) => normalize(inner),
+        Expr::Dispatch { target, exprs, .. } => {
+            if let Some(target) = target {
+                normalize(target);
+            }
+            exprs.iter_mut().for_each(normalize);
+        }
+        _ => {}
+    }
+}
+
+fn normalized(mut classes: Vec<Class>) -> Vec<Class> {
+    for class in &mut classes {
+        class.location = Default::default();
+        for feature in &mut class.feature_list {
+            match feature {
+                Feature::Attribute(VarDecl { expr, location, .. }) => {
+                    *location = Default::default();
+                    expr.iter_mut().for_each(normalize);
+                }
+                Feature::Method(_, args, _, body, location) => {
+                    *location = Default::default();
+                    args.iter_mut().for_each(|arg| arg.location = Default::default());
+                    normalize(body);
+                }
+            }
+        }
+    }
+    classes
+}
+
+proptest! {
+    #[test]
+    fn print_then_parse_roundtrips(classes in program()) {
+        let source = print_program(&classes);
+        let reparsed = parse_program(&source)
+            .map_err(|err| TestCaseError::fail(format!("{:#}\n{}", err, source)))?;
+        prop_assert_eq!(normalized(reparsed), normalized(classes), "{}", source);
+    }
+
+    #[test]
+    fn printing_is_idempotent(classes in program()) {
+        let source = print_program(&classes);
+        let reparsed = parse_program(&source)
+            .map_err(|err| TestCaseError::fail(format!("{:#}\n{}", err, source)))?;
+        prop_assert_eq!(print_program(&reparsed), source);
+    }
+
+    #[test]
+    fn format_then_parse_roundtrips(classes in program()) {
+        let source = format_source(&print_program(&classes), &Extensions::default())
+            .map_err(|err| TestCaseError::fail(format!("{:#}", err)))?;
+        let reparsed = parse_program(&source)
+            .map_err(|err| TestCaseError::fail(format!("{:#}\n{}", err, source)))?;
+        prop_assert_eq!(normalized(reparsed), normalized(classes), "{}", source);
+    }
+
+    #[test]
+    fn formatting_is_idempotent(classes in program()) {
+        let source = format_source(&print_program(&classes), &Extensions::default())
+            .map_err(|err| TestCaseError::fail(format!("{:#}", err)))?;
+        let again = format_source(&source, &Extensions::default())
+            .map_err(|err| TestCaseError::fail(format!("{:#}\n{}", err, source)))?;
+        prop_assert_eq!(again, source);
+    }
+}
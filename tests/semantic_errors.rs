@@ -0,0 +1,123 @@
+//! One fixture per `SemanticError` variant.
+//!
+//! `fixtures!` expands to an exhaustive `match` over the enum, so adding a
+//! variant fails to compile until it is given a fixture here. The fixtures
+//! are the programs under `tests/diagnostics`, which also back the rendered
+//! output snapshots.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use cool_rs::semantic::collector::ErrorCollector;
+use cool_rs::semantic::context::AnalysisContext;
+use cool_rs::semantic::errors::SemanticError;
+use clap::ValueEnum;
+
+use cool_rs::ext::{Extension, Extensions};
+use cool_rs::{check_program, parse_program_with, prelude_with};
+
+mod common;
+use common::fixture_extensions;
+
+macro_rules! fixtures {
+    ($($variant:ident => $fixture:expr,)*) => {
+        /// Fixture file that reports `err`, or `None` if nothing can.
+        fn fixture(err: &SemanticError) -> Option<&'static str> {
+            match err {
+                $(SemanticError::$variant { .. } => $fixture,)*
+            }
+        }
+
+        const FIXTURES: &[(&str, Option<&str>)] = &[$((stringify!($variant), $fixture),)*];
+    };
+}
+
+fixtures! {
+    DuplicateClass => Some("E0001_duplicate_class.cl"),
+    InheritanceCycle => Some("E0002_inheritance_cycle.cl"),
+    UndefinedParent => Some("E0003_undefined_parent.cl"),
+    InheritBasicType => Some("E0004_inherit_basic_type.cl"),
+    SelfTypeClass => Some("E0042_self_type_class.cl"),
+    DuplicateAttribute => Some("E0005_duplicate_attribute.cl"),
+    DuplicateMethod => Some("E0006_duplicate_method.cl"),
+    AttributeOverride => Some("E0018_attribute_override.cl"),
+    SelfAttribute => Some("E0024_self_attribute.cl"),
+    SelfFormal => Some("E0023_self_formal.cl"),
+    DuplicateFormal => Some("E0038_duplicate_formal.cl"),
+    SelfTypeFormal => Some("E0043_self_type_formal.cl"),
+    MethodOverrideMismatch => Some("E0007_method_override_mismatch.cl"),
+    UndefinedClass => Some("E0008_undefined_class.cl"),
+    UndefinedVariable => Some("E0009_undefined_variable.cl"),
+    TypeMismatch => Some("E0010_type_mismatch.cl"),
+    ArgumentCountMismatch => Some("E0011_argument_count_mismatch.cl"),
+    UndefinedMethod => Some("E0025_undefined_method.cl"),
+    StaticDispatchTypeError => Some("E0026_static_dispatch_type_error.cl"),
+    // An unmatched case is a runtime error in COOL, reported by the
+    // interpreter; the type checker has no way to find it
+    NoBranchInCase => None,
+    DuplicateCaseBranch => Some("E0019_duplicate_case_branch.cl"),
+    AssignToSelf => Some("E0020_assign_to_self.cl"),
+    LetBindsSelf => Some("E0021_let_binds_self.cl"),
+    CaseBindsSelf => Some("E0022_case_binds_self.cl"),
+    // Needs thousands of levels of nesting; `semantic::nesting` and the
+    // type checker build such programs in their own tests
+    NestedTooDeeply => None,
+    UnboundNative => Some("E0015_unbound_native.cl"),
+    NoMainClass => Some("E0016_no_main_class.cl"),
+    NoMainMethod => Some("E0017_no_main_method.cl"),
+    UnusedLet => Some("W0001_unused_let.cl"),
+    UnusedAttribute => Some("W0002_unused_attribute.cl"),
+    UnreachableCaseBranch => Some("W0003_unreachable_case_branch.cl"),
+    DeadCode => Some("W0004_dead_code.cl"),
+    // Reported by the optimizer (`--opt-level 2`), after the checks
+    ConstantCondition => None,
+    NonExhaustiveCase => Some("W0006_non_exhaustive_case.cl"),
+    UninitializedAttribute => Some("W0007_uninitialized_attribute.cl"),
+    DispatchOnVoid => Some("W0008_dispatch_on_void.cl"),
+    DivisionByZero => Some("W0009_division_by_zero.cl"),
+}
+
+fn fixture_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("diagnostics")
+}
+
+fn check(file: &str) -> Vec<SemanticError> {
+    let source = fs::read_to_string(fixture_dir().join(file)).unwrap();
+    let extensions: Extensions = fixture_extensions(&source)
+        .into_iter()
+        .map(|name| Extension::from_str(name, false).unwrap())
+        .collect();
+    let classes =
+        parse_program_with(&source, &extensions).unwrap_or_else(|err| panic!("{}: {:#}", file, err));
+    let ctx = AnalysisContext::new(prelude_with(&extensions), &classes);
+    let mut ec = ErrorCollector::default();
+    check_program(&ctx, &mut ec);
+    ec.reported().to_vec()
+}
+
+#[test]
+fn test_each_fixture_reports_only_its_variant() {
+    for (variant, file) in FIXTURES {
+        let Some(file) = file else { continue };
+        let errors = check(file);
+        assert!(!errors.is_empty(), "{} ({}) reports nothing", file, variant);
+        for err in &errors {
+            assert_eq!(fixture(err), Some(*file), "{} reports {:?}", file, err);
+        }
+    }
+}
+
+#[test]
+fn test_every_error_fixture_is_listed() {
+    for entry in fs::read_dir(fixture_dir()).unwrap() {
+        let name = entry.unwrap().file_name().to_string_lossy().into_owned();
+        // Lexical and syntax error fixtures are not semantic errors
+        if (name.starts_with('E') || name.starts_with('W')) && name.ends_with(".cl") {
+            assert!(
+                FIXTURES.iter().any(|(_, file)| *file == Some(name.as_str())),
+                "{} is not listed in fixtures!",
+                name
+            );
+        }
+    }
+}
@@ -0,0 +1,110 @@
+//! UI tests: each fixture under `tests/ui` declares the diagnostics it
+//! must produce in comments, and the runner checks that exactly those are
+//! reported, no more and no fewer.
+//!
+//! ```text
+//! -- ERROR(12): E0010 free-form note
+//! -- ERROR: E0001 free-form note
+//! ```
+//!
+//! The first form expects a diagnostic with that code on line 12. The
+//! second matches diagnostics that carry no line, such as a missing
+//! Main class. Text after the code is for the reader and is not checked.
+
+use std::fs;
+use std::path::Path;
+
+use regex::Regex;
+
+use cool_rs::semantic::collector::ErrorCollector;
+use cool_rs::semantic::context::AnalysisContext;
+use cool_rs::{check_program, parse_program, prelude};
+
+/// (line, code) of one diagnostic.
+type Expectation = (Option<usize>, String);
+
+fn expected_diagnostics(source: &str) -> Vec<Expectation> {
+    let annotation = Regex::new(r"--\s*ERROR(?:\((\d+)\))?:\s*(E\d{4})").unwrap();
+    let mut expected: Vec<Expectation> = annotation
+        .captures_iter(source)
+        .map(|cap| {
+            let line = cap.get(1).map(|m| m.as_str().parse().unwrap());
+            (line, cap[2].to_string())
+        })
+        .collect();
+    expected.sort();
+    expected
+}
+
+fn actual_diagnostics(source: &str) -> Result<Vec<Expectation>, String> {
+    let classes = parse_program(source).map_err(|err| format!("{:#}", err))?;
+    let ctx = AnalysisContext::new(prelude(), &classes);
+    let mut ec = ErrorCollector::default();
+    check_program(&ctx, &mut ec);
+
+    let mut actual: Vec<Expectation> = ec
+        .errors
+        .iter()
+        .map(|e| (e.line(), e.code().to_string()))
+        .collect();
+    actual.sort();
+    Ok(actual)
+}
+
+fn describe((line, code): &Expectation) -> String {
+    match line {
+        Some(line) => format!("{} on line {}", code, line),
+        None => format!("{} (no line)", code),
+    }
+}
+
+/// Elements of `a` not matched one-for-one in `b`.
+fn unmatched<'a>(a: &'a [Expectation], b: &[Expectation]) -> Vec<&'a Expectation> {
+    let mut rest = b.to_vec();
+    a.iter()
+        .filter(|item| match rest.iter().position(|r| r == *item) {
+            Some(i) => {
+                rest.remove(i);
+                false
+            }
+            None => true,
+        })
+        .collect()
+}
+
+#[test]
+fn test_ui_fixtures() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/ui");
+    let mut fixtures: Vec<_> = fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "cl"))
+        .collect();
+    fixtures.sort();
+    assert!(!fixtures.is_empty(), "no fixtures found in {}", dir.display());
+
+    let mut failures = Vec::new();
+    for path in &fixtures {
+        let source = fs::read_to_string(path).unwrap();
+        let expected = expected_diagnostics(&source);
+        let actual = match actual_diagnostics(&source) {
+            Ok(actual) => actual,
+            Err(err) => {
+                failures.push(format!("{}: does not parse: {}", path.display(), err));
+                continue;
+            }
+        };
+
+        let mut problems = Vec::new();
+        for missing in unmatched(&expected, &actual) {
+            problems.push(format!("  missing    {}", describe(missing)));
+        }
+        for extra in unmatched(&actual, &expected) {
+            problems.push(format!("  unexpected {}", describe(extra)));
+        }
+        if !problems.is_empty() {
+            failures.push(format!("{}:\n{}", path.display(), problems.join("\n")));
+        }
+    }
+    assert!(failures.is_empty(), "UI fixtures failed:\n\n{}", failures.join("\n\n"));
+}
@@ -0,0 +1,33 @@
+-- Type checking inside expressions. An undefined name is reported once,
+-- without follow-on mismatches from the expressions that use it.
+class Main inherits IO {
+    main() : Object {
+        {
+            out_int(missing + 1);
+            -- ERROR(6): E0009 undefined variable
+
+            new Nowhere;
+            -- ERROR(9): E0008 undefined class
+
+            out_int("four");
+            -- ERROR(12): E0010 String is not Int
+
+            out_string();
+            -- ERROR(15): E0011 missing argument
+
+            if 1 then 2 else 3 fi;
+            -- ERROR(18): E0010 condition must be Bool
+
+            case self.type_name().length() of n : Int => n; esac;
+            case new Object of o : Object => o; esac;
+            -- an Object-typed expression is fine; only a void one fails, at run time
+
+            (not 3) + 1;
+            -- ERROR(25): E0010 not requires Bool
+            -- ERROR(25): E0010 + requires Int
+
+            ghost@Object.type_name();
+            -- ERROR(29): E0009 the target of a static dispatch is checked too
+        }
+    };
+};
@@ -0,0 +1,38 @@
+-- Attribute and method declarations.
+class A {
+    x : Int;
+    x : String;
+    -- ERROR(4): E0005 duplicate attribute
+
+    f() : Int { 1 };
+    f() : Int { 2 };
+    -- ERROR(8): E0006 duplicate method
+
+    g(a : Int) : Int { a };
+    k() : Int { 0 };
+
+    h(a : Int, b : Int, a : Int, a : Int) : Int { a };
+    -- ERROR(14): E0038 duplicate formal, reported once
+
+    y : Absent;
+    -- ERROR(17): E0008 undefined attribute type
+
+    u(a : Nowhere) : Missing { a };
+    -- ERROR(20): E0008 undefined formal type
+    -- ERROR(20): E0008 undefined return type
+};
+
+class B inherits A {
+    g(a : String) : Int { 0 };
+    -- ERROR(26): E0007 override changes parameter types
+};
+
+class C inherits B {
+    x : Bool;
+    -- ERROR(31): E0018 attribute inherited from A
+
+    k() : String { "" };
+    -- ERROR(34): E0007 override of A's method, which B does not redefine
+};
+
+class Main { main() : Int { 0 }; };
@@ -0,0 +1,20 @@
+-- Inheritance graph checks. Class-level errors are at the class header.
+class A inherits B { };
+class B inherits A { };
+-- ERROR(2): E0002 cycle A -> B -> A
+-- B is on the same cycle, reported once at its least member
+
+class C { };
+class C { };
+-- ERROR(8): E0001 duplicate class C
+
+class D inherits Missing { };
+-- ERROR(11): E0003 undefined parent
+
+class E inherits Int { };
+-- ERROR(14): E0004 cannot inherit from Int
+
+class IO { };
+-- ERROR(17): E0001 basic classes cannot be redefined
+
+class Main { main() : Int { 0 }; };
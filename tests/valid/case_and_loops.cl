@@ -0,0 +1,31 @@
+-- Dynamic dispatch, static dispatch, case, let and while together
+class Animal {
+   name() : String { "animal" };
+   speak() : String { name().concat(" makes a sound") };
+};
+
+class Dog inherits Animal {
+   name() : String { "dog" };
+   speak() : String { self@Animal.speak().concat(", woof") };
+};
+
+class Main inherits IO {
+   describe(a : Object) : String {
+      case a of
+         d : Dog => "a dog";
+         x : Animal => "an animal";
+         o : Object => "something else";
+      esac
+   };
+
+   main() : Object {
+      let i : Int <- 0, animals : Animal in {
+         while i < 3 loop {
+            animals <- if i = 1 then new Dog else new Animal fi;
+            out_string(describe(animals).concat(": ").concat(animals.speak()).concat("\n"));
+            i <- i + 1;
+         } pool;
+         out_string(describe(self).concat("\n"));
+      }
+   };
+};
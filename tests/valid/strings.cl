@@ -0,0 +1,14 @@
+(* Escapes, the String methods and SELF_TYPE
+   (* comments nest *) *)
+class Main inherits IO {
+   copy_of_me() : SELF_TYPE { self };
+
+   main() : Object {
+      let s : String <- "tab\there \"quoted\"\n", void : Object in {
+         out_string(s);
+         out_int(s.length());
+         out_string("\n".concat(s.substr(0, 3)).concat("\n"));
+         if isvoid void then copy_of_me().out_string("void\n") else abort() fi;
+      }
+   };
+};